@@ -0,0 +1,234 @@
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, Error,
+};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::warn;
+
+use crate::models::AccessLogFormat;
+use crate::security::SecurityManager;
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+struct OpenLog {
+    file: File,
+    size: u64,
+}
+
+/// Appends one line per request to `logging.access_log_file` in Apache's Common or Combined format
+///
+/// Unlike the audit log, lines carry no parameters or client labels, only
+/// what web log tooling expects. The file is rotated to `<file>.1`,
+/// `<file>.2`, ... once it would grow past `max_bytes`.
+pub struct AccessLogger {
+    path: PathBuf,
+    format: AccessLogFormat,
+    max_bytes: u64,
+    keep: usize,
+    file: Mutex<Option<OpenLog>>,
+}
+
+impl AccessLogger {
+    /// An empty path disables the access log; a `max_bytes` of 0 never rotates
+    pub fn new(path: impl Into<PathBuf>, format: AccessLogFormat, max_bytes: u64, keep: usize) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            max_bytes,
+            keep,
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.path.as_os_str().is_empty()
+    }
+
+    pub fn record(&self, line: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = self.append(&mut file, line.as_bytes()) {
+            warn!("Cannot write access log {}: {}", self.path.display(), e);
+            *file = None;
+        }
+    }
+
+    fn append(&self, file: &mut Option<OpenLog>, line: &[u8]) -> std::io::Result<()> {
+        if self.max_bytes > 0
+            && let Some(open) = file.as_ref()
+            && open.size > 0
+            && open.size + line.len() as u64 > self.max_bytes
+        {
+            *file = None;
+            self.rotate()?;
+        }
+        let open = match file {
+            Some(open) => open,
+            None => {
+                let opened = OpenOptions::new().create(true).append(true).open(&self.path)?;
+                let size = opened.metadata()?.len();
+                file.insert(OpenLog { file: opened, size })
+            }
+        };
+        open.file.write_all(line)?;
+        open.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts `<file>.N` up by one, dropping the oldest, and moves the current file to `<file>.1`
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        let _ = fs::remove_file(rotated(self.keep));
+        for n in (1..self.keep).rev() {
+            let from = rotated(n);
+            if from.exists() {
+                fs::rename(&from, rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// `[10/Oct/2000:13:55:36 +0000]`-style time, always in UTC
+fn clf_time(time: SystemTime) -> String {
+    // "2000-10-10T13:55:36Z"
+    let rfc3339 = humantime::format_rfc3339_seconds(time).to_string();
+    let month = rfc3339[5..7].parse::<usize>().unwrap_or(1);
+    format!(
+        "{}/{}/{}:{} +0000",
+        &rfc3339[8..10],
+        MONTHS[month.clamp(1, 12) - 1],
+        &rfc3339[0..4],
+        &rfc3339[11..19]
+    )
+}
+
+/// Escapes quotes, backslashes and non-printable bytes the way Apache does, so one request stays one line
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Middleware writing every request to the [`AccessLogger`]
+pub async fn log_access(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(logger) = req.app_data::<web::Data<Arc<AccessLogger>>>().cloned() else {
+        return next.call(req).await;
+    };
+    if !logger.is_enabled() {
+        return next.call(req).await;
+    }
+    let security = req.app_data::<web::Data<Arc<SecurityManager>>>().cloned();
+
+    let time = clf_time(SystemTime::now());
+    let host = req.peer_addr().map_or_else(|| "-".to_string(), |addr| addr.ip().to_canonical().to_string());
+    let target = match req.query_string() {
+        "" => req.path().to_string(),
+        query => format!("{}?{}", req.path(), security.map_or_else(|| query.to_string(), |s| s.redact(query))),
+    };
+    let request_line = escape(&format!("{} {} {:?}", req.method(), target, req.version()));
+    let header_field = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map_or_else(|| "-".to_string(), escape)
+    };
+    let (referer, user_agent) = (header_field(header::REFERER), header_field(header::USER_AGENT));
+
+    let result = next.call(req).await;
+    let (status, bytes) = match &result {
+        Ok(res) => (res.status().as_u16(), res.response().body().size()),
+        Err(e) => (e.as_response_error().status_code().as_u16(), BodySize::None),
+    };
+    let bytes = match bytes {
+        BodySize::Sized(bytes) if bytes > 0 => bytes.to_string(),
+        _ => "-".to_string(),
+    };
+    let mut line = format!("{} - - [{}] \"{}\" {} {}", host, time, request_line, status, bytes);
+    if logger.format == AccessLogFormat::Combined {
+        line.push_str(&format!(" \"{}\" \"{}\"", referer, user_agent));
+    }
+    line.push('\n');
+    logger.record(&line);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{middleware::from_fn, App, HttpResponse};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[actix_web::test]
+    async fn test_access_log() {
+        assert_eq!(clf_time(UNIX_EPOCH + Duration::from_secs(971_186_136)), "10/Oct/2000:13:55:36 +0000");
+
+        let dir = tempfile::tempdir().unwrap();
+        // Room for one line per file
+        let logger = Arc::new(AccessLogger::new(dir.path().join("access.log"), AccessLogFormat::Combined, 100, 2));
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(logger.clone()))
+                .wrap(from_fn(log_access))
+                .route("/api/health", web::get().to(|| async { HttpResponse::Ok().body("ok") }))
+                .route("/api/exec", web::post().to(HttpResponse::Forbidden)),
+        )
+        .await;
+
+        let get = TestRequest::get()
+            .uri("/api/health?verbose=1")
+            .peer_addr("192.168.1.20:50000".parse().unwrap())
+            .insert_header((header::USER_AGENT, "curl/8.0 \"quoted\""))
+            .to_request();
+        call_service(&app, get).await;
+        let line = std::fs::read_to_string(logger.path()).unwrap();
+        let pattern = regex::Regex::new(
+            r#"^192\.168\.1\.20 - - \[\d{2}/\w{3}/\d{4}:\d{2}:\d{2}:\d{2} \+0000\] "GET /api/health\?verbose=1 HTTP/1\.1" 200 2 "-" "curl/8\.0 \\"quoted\\""\n$"#,
+        )
+        .unwrap();
+        assert!(pattern.is_match(&line), "{}", line);
+
+        for _ in 0..3 {
+            call_service(&app, TestRequest::post().uri("/api/exec").to_request()).await;
+        }
+        let rotated = |n| {
+            let mut name = logger.path().as_os_str().to_owned();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        assert!(std::fs::read_to_string(logger.path()).unwrap().contains("\"POST /api/exec HTTP/1.1\" 403 -"));
+        assert!(std::fs::read_to_string(rotated(2)).unwrap().contains("POST"));
+        assert!(!rotated(3).exists());
+    }
+}
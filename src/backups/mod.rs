@@ -0,0 +1,351 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{error, info, warn};
+
+use crate::handlers::archive::{pack, plan_archive, ArchiveFailure};
+use crate::models::{ArchiveFormat, BackupProfile, BackupRun, BackupStatus, Config};
+use crate::operations::CancelToken;
+use crate::platform::{create_dir_all_with_mode, display_path, extended_path};
+use crate::security::workspace::build_glob_set;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// How often the scheduler looks for due backups
+const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+
+/// Why a backup did not run, or ran and was refused
+pub enum BackupError {
+    NotFound,
+    /// A run of the same profile is still going
+    Running,
+    /// The policy denied the source, destination or archive; the run is recorded as failed
+    Refused(Box<BackupRun>),
+}
+
+#[derive(Default)]
+struct BackupState {
+    running: bool,
+    last_started: Option<SystemTime>,
+    last_run: Option<BackupRun>,
+}
+
+/// Backup profiles from the configuration, with the state of their runs
+///
+/// Each run packs the source into `<name>-<UTC timestamp>.<ext>` in the
+/// destination and then deletes the oldest archives beyond `keep`.
+pub struct BackupService {
+    profiles: BTreeMap<String, BackupProfile>,
+    states: Mutex<HashMap<String, BackupState>>,
+}
+
+fn format_of(profile: &BackupProfile) -> ArchiveFormat {
+    profile.format.unwrap_or(ArchiveFormat::TarGz)
+}
+
+fn extension(format: ArchiveFormat) -> &'static str {
+    match format {
+        ArchiveFormat::Zip => "zip",
+        ArchiveFormat::TarGz => "tar.gz",
+    }
+}
+
+/// Timestamp in archive names, such as `20261017T093000Z`, which sorts chronologically
+fn archive_timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string().replace(['-', ':'], "")
+}
+
+/// Whether `file_name` is an archive written for the profile `name`
+fn is_profile_archive(name: &str, format: ArchiveFormat, file_name: &str) -> bool {
+    file_name
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.strip_suffix(extension(format)))
+        .and_then(|rest| rest.strip_suffix('.'))
+        .is_some_and(|stamp| stamp.len() == 16 && stamp.ends_with('Z') && stamp.as_bytes()[8] == b'T')
+}
+
+/// Archives of a profile in its destination, newest first
+fn list_archives(name: &str, profile: &BackupProfile) -> Vec<PathBuf> {
+    let destination = extended_path(Path::new(&profile.destination));
+    let Ok(entries) = std::fs::read_dir(&destination) else {
+        return vec![];
+    };
+    let mut archives: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_profile_archive(name, format_of(profile), &entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect();
+    archives.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+    archives
+}
+
+impl BackupService {
+    pub fn new(profiles: HashMap<String, BackupProfile>) -> Self {
+        Self {
+            profiles: profiles.into_iter().collect(),
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.backups.clone().unwrap_or_default())
+    }
+
+    /// Whether any profile runs on a schedule
+    pub fn is_scheduled(&self) -> bool {
+        self.profiles.values().any(|profile| profile.interval_minutes.is_some())
+    }
+
+    /// When a scheduled profile last started, falling back to its newest archive after a restart
+    fn last_started(&self, name: &str, profile: &BackupProfile) -> Option<SystemTime> {
+        if let Some(started) = self.states.lock().unwrap_or_else(PoisonError::into_inner).get(name).and_then(|state| state.last_started) {
+            return Some(started);
+        }
+        let newest = list_archives(name, profile).into_iter().next()?;
+        std::fs::metadata(newest).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    /// When a scheduled profile runs next; a profile that never ran is due `now`
+    fn next_run(&self, name: &str, profile: &BackupProfile, now: SystemTime) -> Option<SystemTime> {
+        let interval = Duration::from_secs(profile.interval_minutes? * 60);
+        Some(self.last_started(name, profile).map_or(now, |started| started + interval))
+    }
+
+    /// Every profile with its schedule, latest run and archives; reads the destinations
+    pub fn statuses(&self) -> Vec<BackupStatus> {
+        let now = SystemTime::now();
+        self.profiles
+            .iter()
+            .map(|(name, profile)| {
+                let (running, last_run) = match self.states.lock().unwrap_or_else(PoisonError::into_inner).get(name) {
+                    Some(state) => (state.running, state.last_run.clone()),
+                    None => (false, None),
+                };
+                BackupStatus {
+                    name: name.clone(),
+                    source: profile.source.clone(),
+                    destination: profile.destination.clone(),
+                    format: format_of(profile),
+                    interval_minutes: profile.interval_minutes,
+                    keep: profile.keep,
+                    running,
+                    next_run: self
+                        .next_run(name, profile, now)
+                        .map(|time| humantime::format_rfc3339_seconds(time).to_string()),
+                    last_run,
+                    archives: list_archives(name, profile).iter().map(|path| display_path(path)).collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Names of the scheduled profiles whose next run has come
+    fn due(&self) -> Vec<String> {
+        let now = SystemTime::now();
+        self.profiles
+            .iter()
+            .filter(|(name, profile)| self.next_run(name, profile, now).is_some_and(|next| next <= now))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Runs a backup to completion; blocking, so callers use the filesystem pool
+    pub fn run(&self, security: &SecurityManager, name: &str) -> Result<BackupRun, BackupError> {
+        let profile = self.profiles.get(name).ok_or(BackupError::NotFound)?;
+        let started = SystemTime::now();
+        {
+            let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+            let state = states.entry(name.to_string()).or_default();
+            if state.running {
+                return Err(BackupError::Running);
+            }
+            state.running = true;
+            state.last_started = Some(started);
+        }
+
+        info!("Running backup {}", name);
+        let timer = Instant::now();
+        let outcome = create_backup(security, name, profile, started);
+        let mut run = BackupRun {
+            success: false,
+            started: humantime::format_rfc3339_seconds(started).to_string(),
+            duration_ms: 0,
+            archive: None,
+            entries: None,
+            bytes: None,
+            skipped: None,
+            pruned: vec![],
+            error: None,
+        };
+        let refused = match outcome {
+            Ok((archive, entries, bytes, skipped)) => {
+                run.success = true;
+                run.archive = Some(display_path(&archive));
+                run.entries = Some(entries);
+                run.bytes = Some(bytes);
+                run.skipped = Some(skipped);
+                run.pruned = prune_archives(security, name, profile);
+                info!("Backup {} wrote {} ({} bytes)", name, display_path(&archive), bytes);
+                false
+            }
+            Err(ArchiveFailure::Refused(e, _)) | Err(ArchiveFailure::UnsafeEntry(e)) => {
+                warn!("Backup {} refused: {}", name, e);
+                run.error = Some(e);
+                true
+            }
+            Err(ArchiveFailure::Io(e)) => {
+                error!("Backup {} failed: {}", name, e);
+                run.error = Some(format!("Failed to create archive: {}", e));
+                false
+            }
+        };
+        run.duration_ms = timer.elapsed().as_millis() as u64;
+
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = states.entry(name.to_string()).or_default();
+        state.running = false;
+        state.last_run = Some(run.clone());
+        if refused { Err(BackupError::Refused(Box::new(run))) } else { Ok(run) }
+    }
+}
+
+/// Packs a profile's source into a new archive, checking it as `/api/archive` would
+fn create_backup(
+    security: &SecurityManager,
+    name: &str,
+    profile: &BackupProfile,
+    started: SystemTime,
+) -> Result<(PathBuf, usize, u64, Vec<String>), ArchiveFailure> {
+    let source = extended_path(Path::new(&profile.source));
+    let destination = extended_path(Path::new(&profile.destination));
+    if !security.is_path_allowed(&source) {
+        return Err(ArchiveFailure::Refused(format!("Access denied to source path: {}", profile.source), None));
+    }
+    if !source.is_dir() {
+        return Err(ArchiveFailure::Io(std::io::Error::other(format!(
+            "Source is not a directory: {}",
+            profile.source
+        ))));
+    }
+
+    let format = format_of(profile);
+    let archive = destination.join(format!("{}-{}.{}", name, archive_timestamp(started), extension(format)));
+    let denied = || ArchiveFailure::Refused(format!("Access denied to destination path: {}", profile.destination), None);
+    if !security.is_path_allowed(&destination) || security.is_workspace_policy_path(&destination) {
+        return Err(denied());
+    }
+    create_dir_all_with_mode(&destination, security.dir_mode())?;
+    security.invalidate_path_cache(&destination);
+    if !security.is_path_allowed(&archive) || security.is_workspace_policy_path(&archive) {
+        return Err(denied());
+    }
+    security.check_protected(&archive, false).map_err(|e| ArchiveFailure::Refused(e, None))?;
+
+    let globs = |patterns: &Option<Vec<String>>| {
+        patterns.as_deref().map(build_glob_set).transpose().map_err(std::io::Error::other)
+    };
+    let (include, exclude) = (globs(&profile.include)?, globs(&profile.exclude)?);
+    let root_name = source.file_name().map_or_else(|| name.to_string(), |root| root.to_string_lossy().into_owned());
+    let never = CancelToken::default();
+    let plan = plan_archive(security, &[(source, root_name)], include.as_ref(), exclude.as_ref(), &archive, &never)?;
+    let bytes = pack(security, &plan, format, &archive, &never)?;
+    Ok((archive, plan.entries.len(), bytes, plan.skipped))
+}
+
+/// Deletes a profile's archives beyond `keep`, oldest last in line; archives the policy protects are kept
+fn prune_archives(security: &SecurityManager, name: &str, profile: &BackupProfile) -> Vec<String> {
+    let Some(keep) = profile.keep else {
+        return vec![];
+    };
+    let mut pruned = Vec::new();
+    for archive in list_archives(name, profile).into_iter().skip(keep) {
+        if !security.is_path_allowed(&archive)
+            || security.is_workspace_policy_path(&archive)
+            || security.check_protected(&archive, false).is_err()
+        {
+            warn!("Backup {} keeps {}: the policy does not allow deleting it", name, display_path(&archive));
+            continue;
+        }
+        match std::fs::remove_file(&archive) {
+            Ok(()) => {
+                security.invalidate_path_cache(&archive);
+                pruned.push(display_path(&archive));
+            }
+            Err(e) => warn!("Backup {} could not delete {}: {}", name, display_path(&archive), e),
+        }
+    }
+    pruned
+}
+
+/// Runs scheduled backups as they come due, one at a time, until the task is dropped
+pub async fn run_backup_scheduler(service: Arc<BackupService>, security: Arc<SecurityManager>, pools: Arc<WorkerPools>) {
+    let mut interval = tokio::time::interval(SCHEDULER_TICK);
+    loop {
+        interval.tick().await;
+        let checker = service.clone();
+        let Ok(due) = pools.background.run(move || checker.due()).await else {
+            continue;
+        };
+        for name in due {
+            let (service, security) = (service.clone(), security.clone());
+            // Failures are recorded in the profile's status
+            let _ = pools.background.run(move || service.run(&security, &name)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_runs_and_prunes() {
+        let dir = std::env::temp_dir().join(format!("exex-backup-{}", uuid::Uuid::new_v4().simple()));
+        let project = dir.join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::create_dir_all(project.join("target")).unwrap();
+        std::fs::write(project.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(project.join("target").join("main.rs"), "generated").unwrap();
+        std::fs::write(project.join("notes.txt"), "notes").unwrap();
+
+        let profile = BackupProfile {
+            source: display_path(&project),
+            include: Some(vec!["*.rs".to_string()]),
+            exclude: Some(vec!["target".to_string()]),
+            destination: display_path(&dir.join("backups")),
+            format: Some(ArchiveFormat::Zip),
+            interval_minutes: Some(60),
+            keep: Some(2),
+        };
+        let service = BackupService::new(HashMap::from([("project".to_string(), profile)]));
+        let security = SecurityManager::new(crate::config::get_default_config());
+
+        // A profile that never ran is due at once
+        assert_eq!(service.due(), ["project"]);
+        let run = service.run(&security, "project").ok().unwrap();
+        assert!(run.success);
+        assert_eq!(run.entries, Some(1));
+        assert!(service.due().is_empty());
+
+        // Older archives beyond `keep` are deleted, and unrelated files left alone
+        let backups = dir.join("backups");
+        std::fs::write(backups.join("project-20200101T000000Z.zip"), "old").unwrap();
+        std::fs::write(backups.join("project-20200102T000000Z.zip"), "old").unwrap();
+        std::fs::write(backups.join("other-20200101T000000Z.zip"), "other").unwrap();
+        assert_eq!(prune_archives(&security, "project", &service.profiles["project"]).len(), 1);
+        assert!(backups.join("project-20200102T000000Z.zip").exists());
+        assert!(backups.join("other-20200101T000000Z.zip").exists());
+
+        let status = &service.statuses()[0];
+        assert_eq!(status.archives.len(), 2);
+        assert!(status.last_run.as_ref().is_some_and(|run| run.success));
+        assert!(matches!(service.run(&security, "missing"), Err(BackupError::NotFound)));
+
+        assert!(is_profile_archive("project", ArchiveFormat::TarGz, "project-20261017T093000Z.tar.gz"));
+        assert!(!is_profile_archive("project", ArchiveFormat::TarGz, "project-extra-20261017T093000Z.tar.gz"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
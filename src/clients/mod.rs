@@ -0,0 +1,209 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use crate::models::{ClientInfo, ClientRecord};
+use crate::security::throttle::ClientId;
+
+/// Header clients may send to describe themselves: `name=<name>; type=<type>; version=<version>`
+pub const CLIENT_INFO_HEADER: &str = "X-Exex-Client-Info";
+
+/// The least recently seen client is forgotten beyond this many
+const MAX_TRACKED_CLIENTS: usize = 256;
+
+/// Longest name, type or version kept, in characters
+const MAX_FIELD_LEN: usize = 64;
+
+struct Entry {
+    info: ClientInfo,
+    first_seen: SystemTime,
+    last_seen: SystemTime,
+    requests: u64,
+}
+
+/// Clients that have called the API, keyed by [`ClientId`]
+///
+/// Names, types and versions are whatever clients claim through
+/// [`CLIENT_INFO_HEADER`] or a registration call; they label clients for
+/// people reading the list or the audit log and grant nothing. Entries are
+/// kept in memory only.
+#[derive(Default)]
+pub struct ClientRegistry {
+    clients: Mutex<HashMap<String, Entry>>,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a request from `client`, taking any fields it described itself with
+    pub fn seen(&self, client: &ClientId, info: Option<ClientInfo>) {
+        let now = SystemTime::now();
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        if clients.len() >= MAX_TRACKED_CLIENTS
+            && !clients.contains_key(&client.0)
+            && let Some(oldest) = clients.iter().min_by_key(|(_, entry)| entry.last_seen).map(|(id, _)| id.clone())
+        {
+            clients.remove(&oldest);
+        }
+
+        let entry = clients.entry(client.0.clone()).or_insert_with(|| Entry {
+            info: ClientInfo::default(),
+            first_seen: now,
+            last_seen: now,
+            requests: 0,
+        });
+        entry.last_seen = now;
+        entry.requests += 1;
+        if let Some(info) = info {
+            merge(&mut entry.info, info);
+        }
+    }
+
+    /// Records what `client` says it is, replacing fields it described itself with before
+    pub fn register(&self, client: &ClientId, info: ClientInfo) -> Option<ClientRecord> {
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        match clients.get_mut(&client.0) {
+            Some(entry) => {
+                merge(&mut entry.info, info);
+                Some(to_record(&client.0, entry))
+            }
+            None => {
+                drop(clients);
+                self.seen(client, Some(info));
+                self.record(client)
+            }
+        }
+    }
+
+    /// What `client` has said about itself, if anything
+    pub fn info(&self, client: &ClientId) -> Option<ClientInfo> {
+        let clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        clients
+            .get(&client.0)
+            .map(|entry| entry.info.clone())
+            .filter(|info| *info != ClientInfo::default())
+    }
+
+    pub fn record(&self, client: &ClientId) -> Option<ClientRecord> {
+        let clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        clients.get(&client.0).map(|entry| to_record(&client.0, entry))
+    }
+
+    /// Every tracked client, most recently seen first
+    pub fn list(&self) -> Vec<ClientRecord> {
+        let clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entries: Vec<_> = clients.iter().collect();
+        entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.last_seen));
+        entries.into_iter().map(|(id, entry)| to_record(id, entry)).collect()
+    }
+}
+
+fn to_record(id: &str, entry: &Entry) -> ClientRecord {
+    ClientRecord {
+        id: id.to_string(),
+        info: entry.info.clone(),
+        first_seen: humantime::format_rfc3339_seconds(entry.first_seen).to_string(),
+        last_seen: humantime::format_rfc3339_seconds(entry.last_seen).to_string(),
+        requests: entry.requests,
+    }
+}
+
+fn merge(into: &mut ClientInfo, from: ClientInfo) {
+    let from = sanitize(from);
+    into.name = from.name.or(into.name.take());
+    into.client_type = from.client_type.or(into.client_type.take());
+    into.version = from.version.or(into.version.take());
+}
+
+/// Drops empty fields and control characters, and shortens long fields
+pub fn sanitize(info: ClientInfo) -> ClientInfo {
+    let clean = |field: Option<String>| {
+        field
+            .map(|value| value.chars().filter(|c| !c.is_control()).take(MAX_FIELD_LEN).collect::<String>())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    ClientInfo {
+        name: clean(info.name),
+        client_type: clean(info.client_type),
+        version: clean(info.version),
+    }
+}
+
+/// Parses a [`CLIENT_INFO_HEADER`] value; unknown keys are ignored
+pub fn parse_client_info(header: &str) -> ClientInfo {
+    let mut info = ClientInfo::default();
+    for pair in header.split(';') {
+        let Some((key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let value = Some(value.trim().to_string());
+        match key.trim().to_ascii_lowercase().as_str() {
+            "name" => info.name = value,
+            "type" => info.client_type = value,
+            "version" => info.version = value,
+            _ => {}
+        }
+    }
+    sanitize(info)
+}
+
+/// Middleware counting each request against its client in the [`ClientRegistry`]
+pub async fn track_clients(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(registry) = req.app_data::<web::Data<Arc<ClientRegistry>>>() {
+        let info = req
+            .headers()
+            .get(CLIENT_INFO_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(parse_client_info);
+        registry.seen(&ClientId::of(req.request()), info);
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_registry() {
+        let info = parse_client_info("name=vscode-exex; type=editor ; version=1.4.2; extra=1");
+        assert_eq!(info.name.as_deref(), Some("vscode-exex"));
+        assert_eq!(info.client_type.as_deref(), Some("editor"));
+        assert_eq!(info.version.as_deref(), Some("1.4.2"));
+        assert_eq!(parse_client_info(&format!("name={}\u{7}", "n".repeat(100))).name.unwrap().len(), MAX_FIELD_LEN);
+
+        let registry = ClientRegistry::new();
+        let editor = ClientId("editor".to_string());
+        registry.seen(&editor, None);
+        assert!(registry.info(&editor).is_none());
+        registry.seen(&editor, Some(info));
+        // Later requests without the header keep what the client said before
+        registry.seen(&editor, None);
+        let record = registry.register(&editor, ClientInfo { version: Some("1.5.0".to_string()), ..ClientInfo::default() }).unwrap();
+        assert_eq!(record.requests, 3);
+        assert_eq!(record.info.name.as_deref(), Some("vscode-exex"));
+        assert_eq!(record.info.version.as_deref(), Some("1.5.0"));
+
+        // Every other client is seen later, so `editor` is the one forgotten
+        std::thread::sleep(std::time::Duration::from_millis(2));
+
+        for i in 0..MAX_TRACKED_CLIENTS {
+            registry.seen(&ClientId(format!("client-{}", i)), None);
+        }
+        let clients = registry.list();
+        assert_eq!(clients.len(), MAX_TRACKED_CLIENTS);
+        assert!(clients.iter().all(|client| client.id != "editor"));
+    }
+}
@@ -1,10 +1,24 @@
-use crate::models::{Config, ServerConfig, SecurityConfig, LoggingConfig};
+pub mod remote;
+pub mod signature;
+pub mod store;
+
+use crate::models::{
+    AccessLogFormat, AppsConfig, Config, ServerConfig, SecurityConfig, LoggingConfig, MalwareScanConfig, OriginAccess, OriginClass, PowerAction,
+    ProcessPriority, ShellKind,
+};
+use crate::platform::registry::RegistryPath;
+use crate::platform::expand_path_entries;
+use crate::security::path_rules::check_path_rule;
+use crate::security::secrets::is_secret_name;
+use crate::security::{build_protected_set, parse_mode};
+use crate::security::workspace::build_glob_set;
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use tracing::{info, warn, error};
 
 /// Gets the config directory path based on the operating system
-fn get_config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+pub fn get_config_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
     let config_dir = if cfg!(target_os = "windows") {
         let mut dir = dirs::data_local_dir()
             .ok_or("Could not determine local data directory")?;
@@ -119,11 +133,29 @@ pub fn get_default_config() -> Config {
         })
         .unwrap_or_else(|_| "audit.log".to_string());
 
+    let quarantine_dir = get_config_dir()
+        .map(|mut path| {
+            path.push("quarantine");
+            path.to_string_lossy().to_string()
+        })
+        .ok();
+
     Config {
         version: "1.0".to_string(),
         server: ServerConfig {
             host: "127.0.0.1".to_string(),
             port: 8080,
+            allow_credentials: Some(true),
+            csrf_protection: Some(true),
+            exec_workers: Some(4),
+            fs_workers: Some(8),
+            background_workers: Some(1),
+            worker_queue_limit: Some(256),
+            keep_awake_during_jobs: Some(true),
+            job_log_max_mb: Some(16),
+            job_log_retention: Some(200),
+            job_artifact_max_mb: Some(256),
+            tls: None,
         },
         security: SecurityConfig {
             allowed_paths,
@@ -161,16 +193,145 @@ pub fn get_default_config() -> Config {
                 "chmod".to_string(),
                 "chown".to_string(),
             ]),
+            command_aliases: Some(HashMap::new()),
             max_file_size_mb: 100,
+            malware_scan: Some(MalwareScanConfig {
+                enabled: false,
+                clamd_socket: None,
+                command: Some(vec!["clamscan".to_string(), "--no-summary".to_string(), "{file}".to_string()]),
+                quarantine_dir,
+            }),
+            encrypted_paths: Some(vec![]),
+            encryption_key_file: None,
+            path_cache_ttl_secs: Some(5),
+            path_cache_capacity: Some(4096),
+            disk_reserve_mb: Some(256),
+            write_limit_mb_per_sec: Some(0),
+            max_affected_entries: Some(10_000),
+            default_priority: Some(ProcessPriority::Low),
+            max_spawned_processes: Some(32),
+            default_exec_timeout_secs: Some(0),
+            registry_keys: Some(default_registry_keys()),
+            power_actions: Some(default_power_actions()),
+            max_wake_lease_secs: Some(12 * 60 * 60),
+            file_mode: Some("644".to_string()),
+            dir_mode: Some("755".to_string()),
+            max_mode: Some("775".to_string()),
+            protected_patterns: Some(default_protected_patterns()),
+            allow_protected_override: Some(false),
+            origin_access: Some(default_origin_access()),
+            ban_after_failures: Some(5),
+            ban_window_secs: Some(600),
+            ban_command: Some(vec![]),
+            ban_webhook: None,
+            allowed_shells: Some(default_allowed_shells()),
+            allow_login_shell: Some(true),
+            allow_config_updates: Some(false),
+            blocked_env_vars: Some(vec![]),
+            extra_path_entries: Some(vec![]),
+            omit_private_metadata: Some(false),
         },
         logging: LoggingConfig {
             level: "info".to_string(),
             audit_file,
+            redact_patterns: Some(vec![]),
+            policy_allow_sample_rate: Some(0.0),
+            policy_deny_sample_rate: Some(1.0),
+            access_log_file: Some(String::new()),
+            access_log_format: Some(AccessLogFormat::Combined),
+            access_log_max_mb: Some(10),
+            access_log_keep: Some(5),
         },
+        apps: Some(AppsConfig {
+            editors: Some(default_editors()),
+            default_editor: Some("code".to_string()),
+            url_schemes: Some(default_url_schemes()),
+            url_domains: Some(vec![]),
+        }),
+        remote_url: None,
+        remote_refresh_secs: Some(900),
+        ignore_patterns: Some(default_ignore_patterns()),
+        formatters: Some(HashMap::new()),
+        pipelines: Some(HashMap::new()),
+        backups: Some(HashMap::new()),
+    }
+}
+
+/// Argument templates for common editors that accept a line and column
+pub fn default_editors() -> HashMap<String, Vec<String>> {
+    // The VS Code launcher is a batch script on Windows
+    let code = if cfg!(target_os = "windows") { "code.cmd" } else { "code" };
+    let templates: [(&str, &[&str]); 3] = [
+        ("code", &[code, "-g", "{file}:{line}:{column}"]),
+        ("subl", &["subl", "{file}:{line}:{column}"]),
+        ("idea", &["idea", "--line", "{line}", "--column", "{column}", "{file}"]),
+    ];
+
+    templates
+        .into_iter()
+        .map(|(name, args)| (name.to_string(), args.iter().map(|a| a.to_string()).collect()))
+        .collect()
+}
+
+/// URL schemes `open_url` accepts by default
+pub fn default_url_schemes() -> Vec<String> {
+    ["https", "vscode", "mailto"].iter().map(|s| s.to_string()).collect()
+}
+
+/// Version control metadata and dependency and build output directories
+pub fn default_ignore_patterns() -> Vec<String> {
+    [".git", "node_modules", "target"].iter().map(|s| s.to_string()).collect()
+}
+
+/// Repository metadata and private keys
+pub fn default_protected_patterns() -> Vec<String> {
+    [".git/**", ".ssh/**", ".gnupg/**", "*.key", "*.pem", "id_rsa*", "id_ecdsa*", "id_ed25519*"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Power actions available unless `security.power_actions` narrows them
+pub fn default_power_actions() -> Vec<PowerAction> {
+    vec![PowerAction::Lock, PowerAction::Sleep, PowerAction::KeepAwake]
+}
+
+/// Shells `/api/exec` requests may choose unless `security.allowed_shells` narrows them
+pub fn default_allowed_shells() -> Vec<ShellKind> {
+    vec![ShellKind::Bash, ShellKind::Zsh, ShellKind::Pwsh, ShellKind::Cmd, ShellKind::None]
+}
+
+/// Full access from every origin; narrow it when binding to a non-loopback address
+pub fn default_origin_access() -> HashMap<OriginClass, OriginAccess> {
+    [OriginClass::Loopback, OriginClass::Lan, OriginClass::Tunnel, OriginClass::Public]
+        .into_iter()
+        .map(|class| (class, OriginAccess::Full))
+        .collect()
+}
+
+/// Installed-application uninstall entries and environment keys on Windows; nothing elsewhere
+pub fn default_registry_keys() -> Vec<String> {
+    if !cfg!(target_os = "windows") {
+        return Vec::new();
     }
+    [
+        r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall",
+        r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall",
+        r"HKEY_CURRENT_USER\Software\Microsoft\Windows\CurrentVersion\Uninstall",
+        r"HKEY_CURRENT_USER\Environment",
+        r"HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Control\Session Manager\Environment",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
 }
 
 /// Validates the configuration structure and content
+/// Whether a pipeline name can appear unescaped in `/api/pipelines/{name}/run`
+pub fn is_pipeline_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 pub fn validate_config(config: &Config) -> Result<(), String> {
     // Check required fields
     if config.version.trim().is_empty() {
@@ -184,6 +345,16 @@ pub fn validate_config(config: &Config) -> Result<(), String> {
     if config.server.port == 0 {
         return Err("Server port must be greater than 0".to_string());
     }
+
+    if [config.server.exec_workers, config.server.fs_workers, config.server.background_workers].contains(&Some(0)) {
+        return Err("Worker pools must have at least one thread".to_string());
+    }
+
+    if let Some(tls) = config.server.tls.as_ref().filter(|tls| tls.enabled)
+        && (tls.cert_path.trim().is_empty() || tls.key_path.trim().is_empty())
+    {
+        return Err("TLS requires both cert_path and key_path".to_string());
+    }
     
     // Validate security configuration
     if config.security.max_file_size_mb == 0 {
@@ -195,14 +366,64 @@ pub fn validate_config(config: &Config) -> Result<(), String> {
         if path.trim().is_empty() {
             return Err("Disallowed paths cannot be empty".to_string());
         }
+        check_path_rule(path)?;
     }
     
     for path in &config.security.allowed_paths {
         if path.trim().is_empty() {
             return Err("Allowed paths cannot be empty".to_string());
         }
+        check_path_rule(path)?;
     }
     
+    // Validate redaction patterns
+    if let Some(patterns) = &config.logging.redact_patterns {
+        for pattern in patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(format!("Invalid redaction pattern '{}': {}", pattern, e));
+            }
+        }
+    }
+
+    for (key, rate) in [
+        ("policy_allow_sample_rate", config.logging.policy_allow_sample_rate),
+        ("policy_deny_sample_rate", config.logging.policy_deny_sample_rate),
+    ] {
+        if let Some(rate) = rate
+            && !(0.0..=1.0).contains(&rate)
+        {
+            return Err(format!("logging.{} must be between 0 and 1, got {}", key, rate));
+        }
+    }
+
+    if let Some(webhook) = config.security.ban_webhook.as_deref().filter(|url| !url.is_empty()) {
+        match url::Url::parse(webhook) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => return Err(format!("security.ban_webhook must be an http or https URL, got '{}'", webhook)),
+        }
+    }
+
+    for name in config.security.blocked_env_vars.iter().flatten() {
+        if name.trim().is_empty() || name.contains('=') || name.trim_end_matches('*').contains('*') {
+            return Err(format!("Invalid blocked_env_vars entry '{}'", name));
+        }
+    }
+
+    for entry in config.security.extra_path_entries.iter().flatten() {
+        let expanded = expand_path_entries(std::slice::from_ref(entry));
+        if !expanded.first().is_some_and(|dir| dir.is_absolute() && std::env::join_paths([dir]).is_ok()) {
+            return Err(format!("security.extra_path_entries must be absolute directories, got '{}'", entry));
+        }
+    }
+
+    if let Some(paths) = &config.security.encrypted_paths {
+        for path in paths {
+            if path.trim().is_empty() {
+                return Err("Encrypted paths cannot be empty".to_string());
+            }
+        }
+    }
+
     // Validate command lists
     for cmd in &config.security.command_whitelist {
         if cmd.trim().is_empty() {
@@ -210,6 +431,84 @@ pub fn validate_config(config: &Config) -> Result<(), String> {
         }
     }
     
+    if let Some(aliases) = &config.security.command_aliases {
+        for (name, program) in aliases {
+            if name.trim().is_empty() || program.trim().is_empty() {
+                return Err("Command aliases cannot be empty".to_string());
+            }
+        }
+    }
+
+    if let Some(keys) = &config.security.registry_keys {
+        for key in keys {
+            RegistryPath::parse(key).map_err(|e| format!("Invalid registry key '{}': {}", key, e))?;
+        }
+    }
+
+    if let Some(patterns) = &config.ignore_patterns {
+        build_glob_set(patterns).map_err(|e| format!("Invalid ignore pattern {}", e))?;
+    }
+
+    if let Some(formatters) = &config.formatters {
+        for (extension, command) in formatters {
+            if extension.trim().is_empty() || command.first().is_none_or(|program| program.trim().is_empty()) {
+                return Err(format!("Formatter for '{}' must name an extension and a command", extension));
+            }
+        }
+    }
+
+    if let Some(pipelines) = &config.pipelines {
+        for (name, pipeline) in pipelines {
+            if !is_pipeline_name(name) {
+                return Err(format!("Pipeline name '{}' may only use letters, digits, '-', '_' and '.'", name));
+            }
+            if pipeline.steps.is_empty() || pipeline.steps.iter().any(|step| step.command.trim().is_empty()) {
+                return Err(format!("Pipeline '{}' must have steps, each naming a command", name));
+            }
+            let secrets = pipeline.secrets.iter().chain(pipeline.steps.iter().filter_map(|step| step.secrets.as_ref()));
+            for (var, secret) in secrets.flatten() {
+                if var.is_empty() || var.contains('=') || !is_secret_name(secret) {
+                    return Err(format!("Pipeline '{}' maps variable '{}' to an invalid secret name '{}'", name, var, secret));
+                }
+            }
+        }
+    }
+
+    if let Some(backups) = &config.backups {
+        for (name, backup) in backups {
+            if !is_pipeline_name(name) {
+                return Err(format!("Backup name '{}' may only use letters, digits, '-', '_' and '.'", name));
+            }
+            if backup.source.trim().is_empty() || backup.destination.trim().is_empty() {
+                return Err(format!("Backup '{}' must have a source and a destination", name));
+            }
+            if backup.interval_minutes == Some(0) {
+                return Err(format!("Backup '{}' interval must be greater than 0", name));
+            }
+            for pattern in backup.include.iter().chain(backup.exclude.iter()) {
+                build_glob_set(pattern).map_err(|e| format!("Backup '{}' has an invalid pattern {}", name, e))?;
+            }
+        }
+    }
+
+    for (name, mode) in [
+        ("file_mode", &config.security.file_mode),
+        ("dir_mode", &config.security.dir_mode),
+        ("max_mode", &config.security.max_mode),
+    ] {
+        if let Some(mode) = mode {
+            parse_mode(mode).map_err(|e| format!("Invalid {}: {}", name, e))?;
+        }
+    }
+
+    if let Some(patterns) = &config.security.protected_patterns {
+        build_protected_set(patterns).map_err(|e| format!("Invalid protected pattern {}", e))?;
+    }
+
+    if config.security.max_wake_lease_secs == Some(0) {
+        return Err("max_wake_lease_secs must be greater than 0".to_string());
+    }
+
     if let Some(blacklist) = &config.security.command_blacklist {
         for cmd in blacklist {
             if cmd.trim().is_empty() {
@@ -218,6 +517,16 @@ pub fn validate_config(config: &Config) -> Result<(), String> {
         }
     }
     
+    if let Some(remote_url) = &config.remote_url {
+        match url::Url::parse(remote_url) {
+            Ok(url) if matches!(url.scheme(), "https" | "http") => {}
+            _ => return Err(format!("Remote policy URL must be an http or https URL: {}", remote_url)),
+        }
+        if config.remote_refresh_secs == Some(0) {
+            return Err("Remote policy refresh interval must be greater than 0".to_string());
+        }
+    }
+
     // Platform-specific critical path checks
     let critical_paths = if cfg!(target_os = "windows") {
         vec!["C:/Windows/", "C:/Program Files/"]
@@ -261,14 +570,14 @@ pub fn load_config() -> Config {
     info!("Config file location: {}", config_path.display());
 
     // Create config directory if it doesn't exist
-    if let Some(parent) = config_path.parent() {
-        if !parent.exists() {
-            info!("Creating config directory: {}", parent.display());
-            if let Err(e) = fs::create_dir_all(parent) {
-                error!("Failed to create config directory: {}", e);
-                warn!("Using default configuration due to directory creation error");
-                return get_default_config();
-            }
+    if let Some(parent) = config_path.parent()
+        && !parent.exists()
+    {
+        info!("Creating config directory: {}", parent.display());
+        if let Err(e) = fs::create_dir_all(parent) {
+            error!("Failed to create config directory: {}", e);
+            warn!("Using default configuration due to directory creation error");
+            return get_default_config();
         }
     }
 
@@ -305,7 +614,7 @@ pub fn load_config() -> Config {
         }
         
         info!("Successfully loaded and validated existing configuration");
-        return config;
+        config
     } else {
         info!("Config file doesn't exist, creating with defaults");
         
@@ -335,6 +644,6 @@ pub fn load_config() -> Config {
         }
         
         info!("Using default configuration");
-        return default_config;
+        default_config
     }
 }
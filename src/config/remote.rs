@@ -0,0 +1,226 @@
+use ed25519_dalek::VerifyingKey;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use super::signature::{load_trusted_keys, signature_path, verify_signature, KEYRING_FILE};
+use super::{get_config_dir, validate_config};
+use crate::models::{Config, SecurityConfig};
+
+/// Last verified remote policy, kept with its `.sig` in the config directory for offline starts
+pub const CACHE_FILE: &str = "remote_policy.json";
+
+/// Refresh interval used when `remote_refresh_secs` is not set
+pub const DEFAULT_REFRESH_SECS: u64 = 900;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where a remote policy was loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicySource {
+    Remote,
+    Cache,
+}
+
+/// A security policy from `remote_url` whose signature has been verified
+#[derive(Debug, Clone)]
+pub struct RemotePolicy {
+    pub security: SecurityConfig,
+    /// SHA-256 of the policy document, used to notice changes
+    pub digest: String,
+    /// Hex encoding of the trusted key that signed the policy
+    pub key: String,
+    pub source: PolicySource,
+}
+
+impl RemotePolicy {
+    /// Replaces the security section of `config` with this policy
+    pub fn apply_to(&self, config: &Config) -> Result<Config, String> {
+        let mut config = config.clone();
+        config.security = self.security.clone();
+        validate_config(&config).map_err(|e| format!("Remote policy is invalid: {}", e))?;
+        Ok(config)
+    }
+}
+
+/// How often the remote policy is fetched again
+pub fn refresh_interval(config: &Config) -> Duration {
+    Duration::from_secs(config.remote_refresh_secs.unwrap_or(DEFAULT_REFRESH_SECS).max(1))
+}
+
+/// Loads the policy at `url`, or the cached copy when it cannot be fetched or verified
+pub async fn load_remote_policy(url: &str) -> Result<RemotePolicy, String> {
+    let keys = load_trusted_keys()?;
+    let cache_dir = get_config_dir().map_err(|e| e.to_string())?;
+    let fetched = fetch_policy(url).await;
+    resolve_policy(fetched, &cache_dir, &keys)
+}
+
+/// Fetches the policy document and its detached signature (`<url path>.sig`)
+pub async fn fetch_policy(url: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut signature_url = url::Url::parse(url).map_err(|e| format!("Invalid remote policy URL {}: {}", url, e))?;
+    signature_url.set_path(&format!("{}.sig", signature_url.path()));
+
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let content = fetch(&client, url).await?;
+    let signature = fetch(&client, signature_url.as_str()).await?;
+    Ok((content, signature))
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    Ok(body.to_vec())
+}
+
+/// Verifies a fetched policy and caches it, or falls back to the cached copy
+///
+/// A policy that fails to download or verify never replaces the cache, so a
+/// tampered or broken server leaves machines on the last good policy.
+pub fn resolve_policy(
+    fetched: Result<(Vec<u8>, Vec<u8>), String>,
+    cache_dir: &Path,
+    keys: &[VerifyingKey],
+) -> Result<RemotePolicy, String> {
+    if keys.is_empty() {
+        return Err(format!(
+            "No trusted keys to verify the remote policy; add one to {}",
+            cache_dir.join(KEYRING_FILE).display()
+        ));
+    }
+
+    let cache_path = cache_dir.join(CACHE_FILE);
+    let remote = fetched.and_then(|(content, signature)| {
+        let policy = verify_policy(&content, &signature, keys, PolicySource::Remote)
+            .map_err(|e| format!("Remote policy rejected: {}", e))?;
+        Ok((policy, content, signature))
+    });
+
+    match remote {
+        Ok((policy, content, signature)) => {
+            if let Err(e) = fs::write(signature_path(&cache_path), &signature).and_then(|_| fs::write(&cache_path, &content)) {
+                warn!("Failed to cache remote policy at {}: {}", cache_path.display(), e);
+            }
+            Ok(policy)
+        }
+        Err(e) => {
+            let cached = fs::read(&cache_path)
+                .and_then(|content| Ok((content, fs::read(signature_path(&cache_path))?)))
+                .map_err(|err| format!("{}; no cached policy is available: {}", e, err))?;
+            warn!("{}; using the cached policy", e);
+            verify_policy(&cached.0, &cached.1, keys, PolicySource::Cache)
+                .map_err(|err| format!("{}; cached policy {} is unusable: {}", e, cache_path.display(), err))
+        }
+    }
+}
+
+fn verify_policy(
+    content: &[u8],
+    signature: &[u8],
+    keys: &[VerifyingKey],
+    source: PolicySource,
+) -> Result<RemotePolicy, String> {
+    let key = verify_signature(content, signature, keys)?;
+    let security = serde_json::from_slice(content).map_err(|e| format!("invalid policy document: {}", e))?;
+    Ok(RemotePolicy {
+        security,
+        digest: hex::encode(Sha256::digest(content)),
+        key,
+        source,
+    })
+}
+
+/// Fetches the policy every `interval` until a verified copy differs from `digest`
+///
+/// Returns the new policy already applied to `base`. Failed fetches and
+/// policies that do not validate are logged and retried on the next tick.
+pub async fn watch_remote_policy(
+    url: String,
+    interval: Duration,
+    mut digest: Option<String>,
+    base: Config,
+) -> (Config, RemotePolicy) {
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick completes immediately and the policy was just loaded
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let policy = match load_remote_policy(&url).await {
+            Ok(policy) if policy.source == PolicySource::Remote => policy,
+            Ok(_) => continue,
+            Err(e) => {
+                warn!("Failed to refresh remote policy: {}", e);
+                continue;
+            }
+        };
+        if digest.as_deref() == Some(policy.digest.as_str()) {
+            continue;
+        }
+
+        match policy.apply_to(&base) {
+            Ok(config) => return (config, policy),
+            Err(e) => {
+                warn!("{}; keeping the current policy", e);
+                digest = Some(policy.digest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_remote_policy_falls_back_to_cache() {
+        let dir = std::env::temp_dir().join(format!("exex-remote-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        let signer = SigningKey::from_bytes(&[9; 32]);
+        let keys = vec![signer.verifying_key()];
+
+        let mut security = get_default_config().security;
+        security.command_whitelist = vec!["git".to_string()];
+        let content = serde_json::to_vec(&security).unwrap();
+        let signature = signer.sign(&content).to_bytes().to_vec();
+
+        // Nothing cached yet, so an offline start has no policy
+        assert!(resolve_policy(Err("offline".to_string()), &dir, &keys).is_err());
+        assert!(resolve_policy(Ok((content.clone(), signature.clone())), &dir, &[]).is_err());
+
+        let policy = resolve_policy(Ok((content.clone(), signature.clone())), &dir, &keys).unwrap();
+        assert_eq!(policy.source, PolicySource::Remote);
+        assert_eq!(policy.security.command_whitelist, vec!["git"]);
+        assert!(dir.join(CACHE_FILE).exists());
+
+        let cached = resolve_policy(Err("offline".to_string()), &dir, &keys).unwrap();
+        assert_eq!(cached.source, PolicySource::Cache);
+        assert_eq!(cached.digest, policy.digest);
+
+        // A tampered document is rejected and does not replace the cache
+        let mut tampered = content.clone();
+        tampered.extend_from_slice(b" ");
+        let fallback = resolve_policy(Ok((tampered, signature)), &dir, &keys).unwrap();
+        assert_eq!(fallback.source, PolicySource::Cache);
+        assert_eq!(fs::read(dir.join(CACHE_FILE)).unwrap(), content);
+
+        let config = policy.apply_to(&get_default_config()).unwrap();
+        assert_eq!(config.security.command_whitelist, vec!["git"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
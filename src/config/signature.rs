@@ -0,0 +1,167 @@
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use super::{get_config_dir, get_config_file_path};
+
+/// Hex Ed25519 public key baked in at build time through `EXEX_CONFIG_PUBLIC_KEY`
+const BUILTIN_PUBLIC_KEY: Option<&str> = option_env!("EXEX_CONFIG_PUBLIC_KEY");
+
+/// Keyring in the config directory: one hex Ed25519 public key per line, `#` starts a comment
+///
+/// Only safe because `SecurityManager` denies the whole config directory to the
+/// API; otherwise a client could add its own key and sign any config.
+pub const KEYRING_FILE: &str = "trusted_keys";
+
+/// Outcome of checking the config file against the trusted keys
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// No trusted keys are configured, so the config does not need a signature
+    NotRequired,
+    /// The signature matched the trusted key with this hex encoding
+    Verified(String),
+}
+
+/// Path of the detached signature for a config file (`exex.config.json.sig`)
+pub fn signature_path(config_path: &Path) -> PathBuf {
+    let mut path = config_path.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Verifies the config file's detached signature when trusted keys are configured
+pub fn verify_config_signature() -> Result<Verification, String> {
+    let config_path = get_config_file_path().map_err(|e| e.to_string())?;
+
+    let keys = load_trusted_keys()?;
+    if keys.is_empty() {
+        return Ok(Verification::NotRequired);
+    }
+    verify_file(&config_path, &keys).map(Verification::Verified)
+}
+
+/// Trusted keys from the build and from the keyring in the config directory
+pub fn load_trusted_keys() -> Result<Vec<VerifyingKey>, String> {
+    let config_dir = get_config_dir().map_err(|e| e.to_string())?;
+    trusted_keys(BUILTIN_PUBLIC_KEY, &config_dir.join(KEYRING_FILE))
+}
+
+/// Collects the built-in key and the keys listed in the keyring file
+pub fn trusted_keys(builtin: Option<&str>, keyring: &Path) -> Result<Vec<VerifyingKey>, String> {
+    let mut keys = Vec::new();
+
+    if let Some(key) = builtin.map(str::trim).filter(|key| !key.is_empty()) {
+        keys.push(parse_public_key(key).map_err(|e| format!("Built-in public key is invalid: {}", e))?);
+    }
+
+    match fs::read_to_string(keyring) {
+        Ok(content) => {
+            for (number, line) in content.lines().enumerate() {
+                let key = line.split('#').next().unwrap_or_default().trim();
+                if key.is_empty() {
+                    continue;
+                }
+                let key = parse_public_key(key)
+                    .map_err(|e| format!("{} line {}: {}", keyring.display(), number + 1, e))?;
+                keys.push(key);
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {}: {}", keyring.display(), e)),
+    }
+
+    Ok(keys)
+}
+
+/// Checks a file against its `.sig` signature, returning the hex key that signed it
+pub fn verify_file(path: &Path, keys: &[VerifyingKey]) -> Result<String, String> {
+    let content = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let sig_path = signature_path(path);
+    let signature = match fs::read(&sig_path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(format!("{} is not signed: {} is missing", path.display(), sig_path.display()));
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", sig_path.display(), e)),
+    };
+
+    verify_signature(&content, &signature, keys).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Checks content against a detached signature, returning the hex key that signed it
+pub fn verify_signature(content: &[u8], signature: &[u8], keys: &[VerifyingKey]) -> Result<String, String> {
+    let signature = parse_signature(signature)?;
+    keys.iter()
+        .find(|key| key.verify(content, &signature).is_ok())
+        .map(|key| hex::encode(key.as_bytes()))
+        .ok_or_else(|| "signature does not match any trusted key".to_string())
+}
+
+fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; PUBLIC_KEY_LENGTH] = hex::decode(text)
+        .map_err(|e| format!("not a hex key: {}", e))?
+        .try_into()
+        .map_err(|_| format!("expected {} bytes", PUBLIC_KEY_LENGTH))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+/// Accepts a raw 64-byte signature (as written by `openssl pkeyutl`) or its hex or base64 text
+fn parse_signature(bytes: &[u8]) -> Result<Signature, String> {
+    let raw = if bytes.len() == SIGNATURE_LENGTH {
+        bytes.to_vec()
+    } else {
+        let text = std::str::from_utf8(bytes).map_err(|_| "signature is neither raw nor text".to_string())?.trim();
+        hex::decode(text)
+            .or_else(|_| base64::engine::general_purpose::STANDARD.decode(text))
+            .map_err(|_| "signature is not valid hex or base64".to_string())?
+    };
+
+    let raw: [u8; SIGNATURE_LENGTH] = raw
+        .try_into()
+        .map_err(|_| format!("expected a {}-byte signature", SIGNATURE_LENGTH))?;
+    Ok(Signature::from_bytes(&raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_verify_config_file() {
+        let dir = std::env::temp_dir().join(format!("exex-sig-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("exex.config.json");
+        let content = br#"{"version": "1.0"}"#;
+        fs::write(&config, content).unwrap();
+
+        let signer = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let keyring = dir.join(KEYRING_FILE);
+        fs::write(
+            &keyring,
+            format!("# endpoint management\n{}\n", hex::encode(signer.verifying_key().as_bytes())),
+        )
+        .unwrap();
+        let keys = trusted_keys(Some(&hex::encode(other.verifying_key().as_bytes())), &keyring).unwrap();
+        assert_eq!(keys.len(), 2);
+
+        assert!(verify_file(&config, &keys).unwrap_err().contains("not signed"));
+
+        fs::write(signature_path(&config), signer.sign(content).to_bytes()).unwrap();
+        assert_eq!(verify_file(&config, &keys).unwrap(), hex::encode(signer.verifying_key().as_bytes()));
+
+        // Text signatures are accepted as well
+        fs::write(signature_path(&config), hex::encode(signer.sign(content).to_bytes())).unwrap();
+        assert!(verify_file(&config, &keys).is_ok());
+
+        fs::write(&config, br#"{"version": "1.1"}"#).unwrap();
+        assert!(verify_file(&config, &keys).unwrap_err().contains("does not match"));
+
+        assert!(trusted_keys(Some("zz"), &keyring).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
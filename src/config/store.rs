@@ -0,0 +1,150 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tracing::info;
+
+use super::signature::load_trusted_keys;
+use super::{get_config_file_path, validate_config};
+use crate::models::Config;
+
+/// Why a configuration change was not applied
+#[derive(Debug)]
+pub enum ConfigUpdateError {
+    /// `security.allow_config_updates` is off
+    Disabled,
+    /// The configuration is signed or its policy remote, so local changes would not hold
+    Managed(String),
+    Invalid(String),
+    Failed(String),
+}
+
+impl fmt::Display for ConfigUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigUpdateError::Disabled => f.write_str("Configuration updates are disabled by security.allow_config_updates"),
+            ConfigUpdateError::Managed(reason) => write!(f, "Configuration is managed: {}", reason),
+            ConfigUpdateError::Invalid(e) => write!(f, "Invalid configuration: {}", e),
+            ConfigUpdateError::Failed(e) => write!(f, "Failed to save configuration: {}", e),
+        }
+    }
+}
+
+/// The configuration in effect, and changes to it made through `/api/config`
+///
+/// An update is written to the config file before it is announced, so the
+/// server restarts with what it would also load on its next start.
+pub struct ConfigStore {
+    current: Mutex<Config>,
+    /// Config file updates are saved to; without one, updates are refused
+    path: Option<PathBuf>,
+    /// Why updates are refused regardless of the policy
+    managed: Option<String>,
+    updated: Notify,
+}
+
+impl ConfigStore {
+    pub fn new(config: Config, path: Option<PathBuf>, managed: Option<String>) -> Self {
+        Self {
+            current: Mutex::new(config),
+            path,
+            managed,
+            updated: Notify::new(),
+        }
+    }
+
+    /// Store for the config file in the config directory
+    pub fn from_config(config: &Config) -> Self {
+        let managed = if config.remote_url.is_some() {
+            Some("the security policy is loaded from remote_url".to_string())
+        } else {
+            match load_trusted_keys() {
+                Ok(keys) if keys.is_empty() => None,
+                Ok(_) => Some("the config file must be signed by a trusted key".to_string()),
+                Err(e) => Some(e),
+            }
+        };
+        Self::new(config.clone(), get_config_file_path().ok(), managed)
+    }
+
+    pub fn current(&self) -> Config {
+        self.current.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Records a configuration put into effect by other means, such as a changed remote policy
+    pub fn set_active(&self, config: Config) {
+        *self.current.lock().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    /// Validates and saves a new configuration, then announces it to [`ConfigStore::updated`]
+    ///
+    /// This is the only way the API changes the config file: the file API
+    /// cannot reach it because `SecurityManager` denies the config directory.
+    pub fn update(&self, config: Config) -> Result<(), ConfigUpdateError> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        if !current.security.allow_config_updates.unwrap_or(false) {
+            return Err(ConfigUpdateError::Disabled);
+        }
+        if let Some(reason) = &self.managed {
+            return Err(ConfigUpdateError::Managed(reason.clone()));
+        }
+        let Some(path) = &self.path else {
+            return Err(ConfigUpdateError::Failed("the config file location is unknown".to_string()));
+        };
+        validate_config(&config).map_err(ConfigUpdateError::Invalid)?;
+
+        // Written beside the config file and renamed over it, so a failed write keeps the old file
+        let content = serde_json::to_string_pretty(&config).map_err(|e| ConfigUpdateError::Failed(e.to_string()))?;
+        let staging = path.with_extension("json.partial");
+        fs::write(&staging, content)
+            .and_then(|_| fs::rename(&staging, path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&staging);
+                ConfigUpdateError::Failed(e.to_string())
+            })?;
+
+        info!(target: "audit", "Configuration updated and saved to {}", path.display());
+        *current = config;
+        self.updated.notify_one();
+        Ok(())
+    }
+
+    /// Waits for the next update; one made before the wait began is not missed
+    pub async fn updated(&self) -> Config {
+        self.updated.notified().await;
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+
+    #[actix_web::test]
+    async fn test_config_update() {
+        let path = std::env::temp_dir().join(format!("exex-config-{}.json", uuid::Uuid::new_v4().simple()));
+        let mut config = get_default_config();
+        let store = ConfigStore::new(config.clone(), Some(path.clone()), None);
+        assert!(matches!(store.update(config.clone()), Err(ConfigUpdateError::Disabled)));
+
+        config.security.allow_config_updates = Some(true);
+        store.set_active(config.clone());
+        let mut invalid = config.clone();
+        invalid.security.max_mode = Some("9".to_string());
+        assert!(matches!(store.update(invalid), Err(ConfigUpdateError::Invalid(_))));
+        assert!(!path.exists());
+
+        config.security.command_whitelist.push("deno".to_string());
+        store.update(config.clone()).unwrap();
+        let saved: Config = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(saved.security.command_whitelist.contains(&"deno".to_string()));
+        assert!(store.updated().await.security.command_whitelist.contains(&"deno".to_string()));
+
+        let managed = ConfigStore::new(config.clone(), Some(path.clone()), Some("signed".to_string()));
+        assert!(matches!(managed.update(config), Err(ConfigUpdateError::Managed(_))));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
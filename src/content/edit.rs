@@ -0,0 +1,209 @@
+use sha2::{Digest, Sha256};
+use std::io::{self, BufRead, Write};
+
+use crate::models::LineEdit;
+
+/// A line edit as a half-open range of original lines and its replacement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span<'a> {
+    /// First affected line, 1-based
+    pub start: usize,
+    /// Line after the last affected one; equal to `start` for insertions
+    pub end: usize,
+    pub content: Option<&'a str>,
+}
+
+/// Result of applying line edits
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditOutcome {
+    /// Hex SHA-256 of the input
+    pub original_hash: String,
+    /// Hex SHA-256 of the output
+    pub hash: String,
+    /// Lines in the output
+    pub lines: usize,
+    /// Bytes in the output
+    pub bytes: u64,
+}
+
+/// Orders edits by position and checks that they do not overlap
+///
+/// Line numbers always refer to the file as it was before any of the edits,
+/// so a client can compute every edit from one snapshot. Insertions at the
+/// same line keep their request order.
+pub fn plan_edits(edits: &[LineEdit]) -> Result<Vec<Span<'_>>, String> {
+    let mut spans = Vec::with_capacity(edits.len());
+    for edit in edits {
+        if let LineEdit::ReplaceRange { start_line, end_line, .. } | LineEdit::DeleteLines { start_line, end_line } = edit
+            && end_line < start_line
+        {
+            return Err(format!("Range {}-{} ends before it starts", start_line, end_line));
+        }
+        let span = match edit {
+            LineEdit::InsertAtLine { line, content } => Span { start: *line, end: *line, content: Some(content) },
+            LineEdit::ReplaceRange { start_line, end_line, content } => Span {
+                start: *start_line,
+                end: end_line.saturating_add(1),
+                content: Some(content),
+            },
+            LineEdit::DeleteLines { start_line, end_line } => Span {
+                start: *start_line,
+                end: end_line.saturating_add(1),
+                content: None,
+            },
+        };
+        if span.start == 0 {
+            return Err("Line numbers start at 1".to_string());
+        }
+        spans.push(span);
+    }
+
+    spans.sort_by_key(|span| (span.start, span.end));
+    for pair in spans.windows(2) {
+        if pair[1].start < pair[0].end {
+            return Err(format!("Edits at lines {} and {} overlap", pair[0].start, pair[1].start));
+        }
+    }
+    Ok(spans)
+}
+
+/// Copies `input` to `output` line by line, applying the planned edits on the way
+///
+/// Inserted text takes the line ending of the file's first line and always
+/// ends with one. Edits past the end of the input fail with `InvalidInput`;
+/// inserting at the line after the last one appends.
+pub fn apply_line_edits(mut input: impl BufRead, output: impl Write, spans: &[Span]) -> io::Result<EditOutcome> {
+    let mut original = Sha256::new();
+    let mut read_line = |buffer: &mut Vec<u8>| -> io::Result<bool> {
+        buffer.clear();
+        let read = input.read_until(b'\n', buffer)?;
+        original.update(&buffer[..]);
+        Ok(read > 0)
+    };
+
+    let mut line = Vec::new();
+    let mut has_line = read_line(&mut line)?;
+    let ending = if line.ends_with(b"\r\n") { "\r\n" } else { "\n" };
+    let mut out = LineWriter { output, hash: Sha256::new(), newlines: 0, bytes: 0, terminated: true };
+    let mut current = 1;
+
+    for span in spans {
+        while current < span.start {
+            if !has_line {
+                return Err(beyond_end(span.start, current - 1));
+            }
+            out.write(&line)?;
+            has_line = read_line(&mut line)?;
+            current += 1;
+        }
+        if let Some(content) = span.content.filter(|c| !c.is_empty()) {
+            if !out.terminated {
+                out.write(ending.as_bytes())?;
+            }
+            let mut text = content.replace("\r\n", "\n");
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            out.write(text.replace('\n', ending).as_bytes())?;
+        }
+        while current < span.end {
+            if !has_line {
+                return Err(beyond_end(span.end - 1, current - 1));
+            }
+            has_line = read_line(&mut line)?;
+            current += 1;
+        }
+    }
+    while has_line {
+        out.write(&line)?;
+        has_line = read_line(&mut line)?;
+    }
+    out.output.flush()?;
+
+    Ok(EditOutcome {
+        original_hash: hex::encode(original.finalize()),
+        hash: hex::encode(out.hash.finalize()),
+        // A last line without a line ending still counts
+        lines: out.newlines + usize::from(!out.terminated),
+        bytes: out.bytes,
+    })
+}
+
+fn beyond_end(line: usize, lines: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Line {} is beyond the end of the file ({} lines)", line, lines),
+    )
+}
+
+/// Hashes and counts what is written
+struct LineWriter<W> {
+    output: W,
+    hash: Sha256,
+    newlines: usize,
+    bytes: u64,
+    /// Whether the output is empty or ends with a line ending
+    terminated: bool,
+}
+
+impl<W: Write> LineWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.output.write_all(data)?;
+        self.hash.update(data);
+        self.bytes += data.len() as u64;
+        self.newlines += data.iter().filter(|b| **b == b'\n').count();
+        self.terminated = data.ends_with(b"\n");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(input: &str, edits: &[LineEdit]) -> Result<String, String> {
+        let spans = plan_edits(edits)?;
+        let mut output = Vec::new();
+        let outcome = apply_line_edits(input.as_bytes(), &mut output, &spans).map_err(|e| e.to_string())?;
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(outcome.original_hash, hex::encode(Sha256::digest(input)));
+        assert_eq!(outcome.hash, hex::encode(Sha256::digest(&output)));
+        assert_eq!(outcome.lines, output.lines().count());
+        Ok(output)
+    }
+
+    #[test]
+    fn test_line_edits() {
+        let insert = |line, content: &str| LineEdit::InsertAtLine { line, content: content.to_string() };
+        let replace = |start_line, end_line, content: &str| LineEdit::ReplaceRange {
+            start_line,
+            end_line,
+            content: content.to_string(),
+        };
+        let delete = |start_line, end_line| LineEdit::DeleteLines { start_line, end_line };
+        let text = "a\nb\nc\nd\n";
+
+        assert_eq!(edit(text, &[insert(1, "x")]).unwrap(), "x\na\nb\nc\nd\n");
+        assert_eq!(edit(text, &[insert(5, "x\ny")]).unwrap(), "a\nb\nc\nd\nx\ny\n");
+        assert_eq!(edit(text, &[replace(2, 3, "B")]).unwrap(), "a\nB\nd\n");
+        assert_eq!(edit(text, &[delete(1, 1), delete(4, 4)]).unwrap(), "b\nc\n");
+        // Line numbers refer to the original, whatever the order of the edits
+        assert_eq!(edit(text, &[delete(3, 4), insert(2, "x"), replace(1, 1, "A")]).unwrap(), "A\nx\nb\n");
+        assert_eq!(edit(text, &[replace(2, 2, "")]).unwrap(), "a\nc\nd\n");
+
+        // Inserted text follows the file's line endings and terminates an unterminated last line
+        assert_eq!(edit("a\r\nb\r\n", &[insert(2, "x\ny")]).unwrap(), "a\r\nx\r\ny\r\nb\r\n");
+        assert_eq!(edit("a\nb", &[insert(3, "c")]).unwrap(), "a\nb\nc\n");
+        assert_eq!(edit("a\nb", &[delete(1, 1)]).unwrap(), "b");
+        assert_eq!(edit("", &[insert(1, "x")]).unwrap(), "x\n");
+
+        assert!(edit(text, &[insert(6, "x")]).unwrap_err().contains("beyond the end of the file (4 lines)"));
+        assert!(edit(text, &[delete(3, 5)]).unwrap_err().contains("Line 5 is beyond"));
+        assert!(edit(text, &[delete(2, 3), insert(3, "x")]).unwrap_err().contains("overlap"));
+        assert!(edit(text, &[delete(3, 2)]).unwrap_err().contains("ends before it starts"));
+        assert!(edit(text, &[insert(0, "x")]).is_err());
+    }
+}
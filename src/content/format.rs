@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Longest a formatter may run before it is killed and the content written as sent
+pub const FORMAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Formatter commands by file extension, run over content before it is written
+///
+/// Commands format `{file}` in place (`rustfmt {file}`, `prettier --write
+/// {file}`); when no argument contains `{file}` the path is appended. The
+/// staged copy sits next to the target, under a hidden name with the same
+/// extension, so formatters find the project's own configuration.
+#[derive(Debug, Default)]
+pub struct Formatters {
+    commands: HashMap<String, Vec<String>>,
+}
+
+impl Formatters {
+    pub fn new(commands: HashMap<String, Vec<String>>) -> Self {
+        let commands = commands
+            .into_iter()
+            .filter(|(_, command)| !command.is_empty())
+            .map(|(extension, command)| (extension.trim_start_matches('.').to_ascii_lowercase(), command))
+            .collect();
+        Self { commands }
+    }
+
+    /// The formatter configured for a path's extension
+    pub fn for_path(&self, path: &Path) -> Option<&[String]> {
+        let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+        self.commands.get(&extension).map(Vec::as_slice)
+    }
+
+    /// Formats content destined for `path`; `Ok(None)` when no formatter is configured
+    pub fn format(&self, path: &Path, content: &str) -> Result<Option<String>, String> {
+        let Some(command) = self.for_path(path) else {
+            return Ok(None);
+        };
+        let staged = staging_path(path);
+        std::fs::write(&staged, content).map_err(|e| format!("Failed to stage content for formatting: {}", e))?;
+        let result = run_formatter(command, &staged)
+            .and_then(|_| std::fs::read_to_string(&staged).map_err(|e| format!("Failed to read formatted content: {}", e)));
+        let _ = std::fs::remove_file(&staged);
+        result.map(Some)
+    }
+}
+
+/// A hidden file with the target's extension, next to it when its directory exists
+fn staging_path(path: &Path) -> PathBuf {
+    let name = format!(
+        ".exex-format-{}.{}",
+        uuid::Uuid::new_v4().simple(),
+        path.extension().unwrap_or_default().to_string_lossy()
+    );
+    match path.parent().filter(|dir| dir.is_dir()) {
+        Some(dir) => dir.join(name),
+        None => std::env::temp_dir().join(name),
+    }
+}
+
+fn run_formatter(command: &[String], file: &Path) -> Result<(), String> {
+    let file_str = file.to_string_lossy().to_string();
+    let mut args: Vec<String> = command[1..].iter().map(|a| a.replace("{file}", &file_str)).collect();
+    if !command[1..].iter().any(|a| a.contains("{file}")) {
+        args.push(file_str);
+    }
+
+    debug!("Formatting {:?} with {}", file, command[0]);
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&args).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::piped());
+    if let Some(dir) = file.parent() {
+        cmd.current_dir(dir);
+    }
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to run formatter {}: {}", command[0], e))?;
+
+    let stderr = child.stderr.take();
+    std::thread::scope(|scope| {
+        // Drained on its own thread so a chatty formatter cannot block on a full pipe
+        let messages = scope.spawn(move || {
+            let mut text = String::new();
+            if let Some(mut pipe) = stderr {
+                let _ = pipe.read_to_string(&mut text);
+            }
+            text
+        });
+
+        let deadline = Instant::now() + FORMAT_TIMEOUT;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break Err(format!("Formatter {} timed out after {:?}", command[0], FORMAT_TIMEOUT));
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+                Err(e) => break Err(format!("Failed to wait for formatter {}: {}", command[0], e)),
+            }
+        };
+        let messages = messages.join().unwrap_or_default();
+
+        match status? {
+            status if status.success() => Ok(()),
+            status => Err(format!(
+                "Formatter {} exited with {:?}: {}",
+                command[0],
+                status.code(),
+                messages.trim()
+            )),
+        }
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatters() {
+        let formatters = Formatters::new(HashMap::from([
+            // Uppercases the file in place, standing in for a real formatter
+            (".TXT".to_string(), vec!["sh".to_string(), "-c".to_string(), "tr a-z A-Z < \"$0\" > \"$0.out\" && mv \"$0.out\" \"$0\"".to_string()]),
+            ("json".to_string(), vec!["false".to_string()]),
+        ]));
+        let dir = std::env::temp_dir().join(format!("exex-format-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(formatters.format(&dir.join("a.txt"), "hello").unwrap(), Some("HELLO".to_string()));
+        assert_eq!(formatters.format(&dir.join("a.md"), "hello").unwrap(), None);
+        assert!(formatters.format(&dir.join("a.json"), "{}").unwrap_err().contains("exited with Some(1)"));
+        // Staged copies are cleaned up
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+pub mod edit;
+pub mod format;
+
+use std::fmt;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::models::{ContentEncoding, FileKind, SyntaxError};
+
+/// Bytes inspected when classifying a file; enough to reach the tar header magic
+pub const SNIFF_LEN: usize = 1024;
+
+/// Signatures of image formats, checked against the start of the file
+///
+/// BMP and ICO magic is too short to trust alone; [`is_bmp`] and [`is_ico`] check their headers.
+const IMAGE_SIGNATURES: &[&[u8]] = &[
+    b"\x89PNG\r\n\x1a\n",
+    b"\xff\xd8\xff",
+    b"GIF87a",
+    b"GIF89a",
+    b"II*\x00",
+    b"MM\x00*",
+];
+
+/// Signatures of archive and compressed formats, checked against the start of the file
+const ARCHIVE_SIGNATURES: &[&[u8]] = &[
+    b"PK\x03\x04",
+    b"PK\x05\x06",
+    b"\x1f\x8b",
+    b"7z\xbc\xaf\x27\x1c",
+    b"Rar!\x1a\x07",
+    b"\xfd7zXZ\x00",
+    b"BZh",
+    b"\x28\xb5\x2f\xfd",
+];
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileKind::Text => "text",
+            FileKind::Binary => "binary",
+            FileKind::Image => "image",
+            FileKind::Archive => "archive",
+        })
+    }
+}
+
+impl fmt::Display for ContentEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ContentEncoding::Utf8 => "utf8",
+            ContentEncoding::Base64 => "base64",
+        })
+    }
+}
+
+/// Classifies content from its first bytes
+///
+/// `head` should hold the first [`SNIFF_LEN`] bytes of the file, or all of it
+/// when shorter. Empty files count as text.
+pub fn classify(head: &[u8]) -> FileKind {
+    if IMAGE_SIGNATURES.iter().any(|sig| head.starts_with(sig)) || is_webp(head) || is_bmp(head) || is_ico(head) {
+        return FileKind::Image;
+    }
+    if ARCHIVE_SIGNATURES.iter().any(|sig| head.starts_with(sig)) || is_tar(head) {
+        return FileKind::Archive;
+    }
+    if head.contains(&0) {
+        return FileKind::Binary;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => FileKind::Text,
+        // The sniff window may cut a multi-byte character in half
+        Err(e) if e.error_len().is_none() => FileKind::Text,
+        Err(_) => FileKind::Binary,
+    }
+}
+
+/// Reads the start of a file and classifies it
+pub fn sniff_file(path: &Path) -> io::Result<FileKind> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    crate::platform::open_for_read(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    Ok(classify(&head))
+}
+
+/// Best-effort classification from the file extension, for content that cannot be inspected
+pub fn kind_from_extension(path: &Path) -> Option<FileKind> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let kind = match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "gif" | "bmp" | "ico" | "tif" | "tiff" | "webp" => FileKind::Image,
+        "zip" | "gz" | "tgz" | "tar" | "7z" | "rar" | "xz" | "bz2" | "zst" => FileKind::Archive,
+        "txt" | "md" | "json" | "toml" | "yaml" | "yml" | "csv" | "log" | "xml" | "html" | "css"
+        | "js" | "ts" | "rs" | "py" | "sh" | "svg" => FileKind::Text,
+        "exe" | "dll" | "so" | "dylib" | "bin" | "pdf" => FileKind::Binary,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Media type for serving a file, from its extension and else its detected kind
+pub fn mime_type(path: &Path, kind: FileKind) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json") => "application/json",
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("md") => "text/markdown; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz" | "tgz") => "application/gzip",
+        Some("tar") => "application/x-tar",
+        Some("wasm") => "application/wasm",
+        _ if kind == FileKind::Text => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Checks that content parses as the format named by the file's extension
+///
+/// JSON, YAML (all documents of a stream) and TOML are checked; other
+/// extensions pass unchecked.
+pub fn validate_syntax(path: &Path, content: &str) -> Result<(), SyntaxError> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "json" => serde_json::from_str::<serde::de::IgnoredAny>(content).map(|_| ()).map_err(|e| SyntaxError {
+            format: "json".to_string(),
+            line: Some(e.line()),
+            column: Some(e.column()),
+            message: e.to_string(),
+        }),
+        "yaml" | "yml" => {
+            use serde::Deserialize;
+            for document in serde_yaml::Deserializer::from_str(content) {
+                if let Err(e) = serde::de::IgnoredAny::deserialize(document) {
+                    let location = e.location();
+                    return Err(SyntaxError {
+                        format: "yaml".to_string(),
+                        line: location.as_ref().map(|l| l.line()),
+                        column: location.as_ref().map(|l| l.column()),
+                        message: e.to_string(),
+                    });
+                }
+            }
+            Ok(())
+        }
+        "toml" => content.parse::<toml::Table>().map(|_| ()).map_err(|e| {
+            let (line, column) = match e.span() {
+                Some(span) => {
+                    let (line, column) = line_and_column(content, span.start);
+                    (Some(line), Some(column))
+                }
+                None => (None, None),
+            };
+            SyntaxError {
+                format: "toml".to_string(),
+                line,
+                column,
+                message: e.message().to_string(),
+            }
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// 1-based line and column of a byte offset
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..content.floor_char_boundary(offset)];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Removes ANSI escape sequences, such as colors, cursor movement and window titles, from terminal output
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // Control sequence: parameters and intermediates, then a final character in '@'..='~'
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // Strings (OSC titles and hyperlinks, DCS), ended by BEL or ESC \
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' {
+                            chars.next_if_eq(&'\\');
+                            break;
+                        }
+                    }
+                }
+                // Character set selection takes one more character
+                Some('(' | ')' | '*' | '+' | '#' | '%') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            _ => plain.push(c),
+        }
+    }
+    plain
+}
+
+fn is_webp(head: &[u8]) -> bool {
+    head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP"
+}
+
+/// "BM", then a file size that covers the pixel data, which starts after a DIB header of a known size
+fn is_bmp(head: &[u8]) -> bool {
+    if head.len() < 18 || !head.starts_with(b"BM") {
+        return false;
+    }
+    let file_size = le_u32(&head[2..6]);
+    let pixel_offset = le_u32(&head[10..14]);
+    let dib_size = le_u32(&head[14..18]);
+    matches!(dib_size, 12 | 40 | 52 | 56 | 64 | 108 | 124)
+        && pixel_offset >= 14 + dib_size
+        && (file_size == 0 || file_size >= pixel_offset)
+}
+
+/// Icon directory with at least one image, whose first entry points past the directory
+fn is_ico(head: &[u8]) -> bool {
+    if head.len() < 22 || !head.starts_with(b"\x00\x00\x01\x00") {
+        return false;
+    }
+    let count = u32::from(u16::from_le_bytes([head[4], head[5]]));
+    let entry = &head[6..22];
+    let planes = u16::from_le_bytes([entry[4], entry[5]]);
+    count > 0 && entry[3] == 0 && planes <= 1 && le_u32(&entry[8..12]) > 0 && le_u32(&entry[12..16]) >= 6 + 16 * count
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn is_tar(head: &[u8]) -> bool {
+    head.get(257..262) == Some(b"ustar")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(b"hello world\n"), FileKind::Text);
+        assert_eq!(classify(b""), FileKind::Text);
+        assert_eq!(classify("caf\u{e9}".as_bytes()), FileKind::Text);
+        // Truncated multi-byte character at the end of the sniff window
+        assert_eq!(classify(&"\u{e9}".as_bytes()[..1]), FileKind::Text);
+        assert_eq!(classify(b"\x89PNG\r\n\x1a\n\x00\x00"), FileKind::Image);
+        assert_eq!(classify(b"RIFF\x00\x00\x00\x00WEBPVP8 "), FileKind::Image);
+        assert_eq!(classify(b"BM\x46\x00\x00\x00\x00\x00\x00\x00\x36\x00\x00\x00\x28\x00\x00\x00"), FileKind::Image);
+        assert_eq!(classify(b"BMI notes: 24.1 in March, 23.8 in April\n"), FileKind::Text);
+        let mut ico = b"\x00\x00\x01\x00\x01\x00\x10\x10\x00\x00\x01\x00\x20\x00".to_vec();
+        ico.extend_from_slice(b"\x68\x04\x00\x00\x16\x00\x00\x00");
+        assert_eq!(classify(&ico), FileKind::Image);
+        assert_eq!(classify(b"\x00\x00\x01\x00\x00\x00"), FileKind::Binary);
+        assert_eq!(classify(b"PK\x03\x04\x14\x00"), FileKind::Archive);
+        assert_eq!(classify(b"\x7fELF\x02\x01\x01\x00"), FileKind::Binary);
+        assert_eq!(classify(b"\xff\xfe\xfa"), FileKind::Binary);
+
+        let mut tar = vec![b'a'; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(classify(&tar), FileKind::Archive);
+    }
+
+    #[test]
+    fn test_kind_from_extension() {
+        assert_eq!(kind_from_extension(Path::new("photo.JPG")), Some(FileKind::Image));
+        assert_eq!(kind_from_extension(Path::new("backup.tar")), Some(FileKind::Archive));
+        assert_eq!(kind_from_extension(Path::new("notes.md")), Some(FileKind::Text));
+        assert_eq!(kind_from_extension(Path::new("unknown.xyz")), None);
+    }
+
+    #[test]
+    fn test_validate_syntax() {
+        assert!(validate_syntax(Path::new("package.json"), r#"{"name": "app"}"#).is_ok());
+        let error = validate_syntax(Path::new("package.json"), "{\n  \"name\": \"app\",\n}").unwrap_err();
+        assert_eq!((error.format.as_str(), error.line, error.column), ("json", Some(3), Some(1)));
+
+        assert!(validate_syntax(Path::new("ci.yml"), "a: 1\n---\nb: [2, 3]\n").is_ok());
+        let error = validate_syntax(Path::new("ci.YAML"), "a: 1\n---\nb: [2, 3\n").unwrap_err();
+        assert_eq!(error.format, "yaml");
+        assert_eq!(error.line, Some(4));
+
+        assert!(validate_syntax(Path::new("Cargo.toml"), "[package]\nname = \"app\"\n").is_ok());
+        let error = validate_syntax(Path::new("Cargo.toml"), "[package]\nname = \"app\"\nname = \"dup\"\n").unwrap_err();
+        assert_eq!((error.format.as_str(), error.line, error.column), ("toml", Some(3), Some(1)));
+
+        assert!(validate_syntax(Path::new("notes.md"), "{ not json").is_ok());
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1m\x1b[32m   Compiling\x1b[0m app"), "   Compiling app");
+        assert_eq!(strip_ansi("50%\x1b[2K\r\x1b[1A100%"), "50%\r100%");
+        assert_eq!(strip_ansi("\x1b]0;title\x07\x1b]8;;https://x.dev\x1b\\link\x1b]8;;\x1b\\"), "link");
+        assert_eq!(strip_ansi("\x1b(Bplain \x1b=café"), "plain café");
+        assert_eq!(strip_ansi("cut off\x1b[3"), "cut off");
+    }
+}
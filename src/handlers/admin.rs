@@ -0,0 +1,33 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::{Config, SelfTestResponse};
+use crate::selftest::{is_healthy, run_self_test};
+use crate::workers::WorkerPools;
+
+/// Re-runs the startup self-test and reports the result for each component
+pub async fn self_test(
+    config: web::Data<Arc<Config>>,
+    pools: web::Data<Arc<WorkerPools>>,
+) -> Result<HttpResponse> {
+    // Probing directories and PATH touches the disk, keep it off the executor
+    let config = config.get_ref().clone();
+    match pools.fs.run(move || run_self_test(&config)).await {
+        Ok(checks) => Ok(HttpResponse::Ok().json(SelfTestResponse {
+            success: true,
+            healthy: is_healthy(&checks),
+            checks,
+            error: None,
+        })),
+        Err(e) => {
+            error!("Failed to run self-test: {}", e);
+            Ok(HttpResponse::Ok().json(SelfTestResponse {
+                success: false,
+                healthy: false,
+                checks: vec![],
+                error: Some(format!("Failed to run self-test: {}", e)),
+            }))
+        }
+    }
+}
@@ -1,18 +1,73 @@
 use actix_web::{web, HttpResponse, Result};
+use std::ffi::OsStr;
 use std::process::{Command, Stdio};
 use std::sync::Arc;
-use std::path::PathBuf;
-use tracing::{info, error};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tracing::{info, error, warn};
+use url::Url;
 
-use crate::models::{OpenAppRequest, OpenAppResponse, ShutdownResponse};
+use crate::config::{default_editors, default_url_schemes};
+use crate::models::{
+    AppRunResponse, Config, OpenAppRequest, ProcessKind, OpenAppResponse, OpenEditorRequest, OpenTerminalRequest, OpenUrlRequest,
+    ShutdownResponse,
+};
+use crate::handlers::shortcut::read_shortcut;
+use crate::platform::desktop_entry::{
+    application_dirs, desktop_entries_supported, find_desktop_entry, is_desktop_file, is_desktop_id, parse_desktop_entry,
+};
+use crate::platform::process_tree::{ProcessTree, TreeKiller};
+use crate::platform::shortcut::is_shortcut;
+use crate::platform::{
+    app_bundle_launcher, apply_env, apply_priority, display_path, extended_path, is_app_bundle, remove_secret_env,
+    resolve_command_in, terminal_launchers, url_opener,
+};
+use crate::power::PowerManager;
+use crate::processes::ProcessRegistry;
 use crate::security::SecurityManager;
+use crate::workers::{PoolError, WorkerPools};
 
 /// Handles application launch requests
+///
+/// Applications are detached and tracked in the process registry by default;
+/// with `wait` they run to completion on the exec pool like `/api/exec`.
+/// A `.lnk` shortcut launches its target with the shortcut's arguments, a
+/// macOS `.app` bundle is launched through `open -a`, and on Linux a desktop
+/// ID or `.desktop` file is launched from its `Exec` line.
 pub async fn open_application(
+    config: web::Data<Arc<Config>>,
     security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    power: web::Data<Arc<PowerManager>>,
     req: web::Json<OpenAppRequest>,
 ) -> Result<HttpResponse> {
-    let app_path = PathBuf::from(&req.application);
+    let mut app_path = PathBuf::from(&req.application);
+    let wait = req.wait.unwrap_or(false);
+    let capture_output = req.capture_output.unwrap_or(false);
+
+    if capture_output && !wait {
+        return Ok(HttpResponse::BadRequest().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some("capture_output requires wait: detached applications run without captured output".to_string()),
+        }));
+    }
+
+    // Desktop IDs name an entry in the XDG application directories
+    let desktop = desktop_entries_supported() && is_desktop_file(&app_path);
+    if desktop && is_desktop_id(&req.application) {
+        match find_desktop_entry(&req.application, &application_dirs()) {
+            Some(path) => app_path = path,
+            None => {
+                return Ok(HttpResponse::Ok().json(OpenAppResponse {
+                    success: false,
+                    pid: None,
+                    error: Some(format!("Desktop entry not found: {}", req.application)),
+                }));
+            }
+        }
+    }
 
     // Check if the application path is allowed
     if !security.is_path_allowed(&app_path) {
@@ -32,17 +87,189 @@ pub async fn open_application(
         }));
     }
 
-    info!("Opening application: {}", req.application);
+    // Shortcuts cannot be executed directly, so launch what they point at
+    let mut program = req.application.clone();
+    let mut shortcut_args = None;
+    let mut default_dir = None;
+    if is_shortcut(&app_path) {
+        let target = match read_shortcut(&extended_path(&app_path)).await {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                error!("Failed to resolve shortcut {}: {}", req.application, e);
+                return Ok(HttpResponse::Ok().json(OpenAppResponse {
+                    success: false,
+                    pid: None,
+                    error: Some(format!("Failed to resolve shortcut: {}", e)),
+                }));
+            }
+        };
+        let Some(target_path) = target.target else {
+            return Ok(HttpResponse::Ok().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(format!("Shortcut does not point at a file: {}", req.application)),
+            }));
+        };
+        if !security.is_path_allowed(&extended_path(Path::new(&target_path))) || !security.is_command_safe(&target_path) {
+            return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(format!("Access denied to shortcut target: {}", target_path)),
+            }));
+        }
+        program = target_path;
+        shortcut_args = target.arguments;
+        default_dir = target.working_dir.filter(|dir| !dir.is_empty());
+    }
 
-    let mut command = Command::new(&req.application);
-    
-    // Add arguments if provided
-    if let Some(args) = &req.args {
-        command.args(args);
+    let files = req.files.clone().unwrap_or_default();
+    for file in &files {
+        // URLs are held to the same scheme and domain policy as /api/open_url
+        let denied = match Url::parse(file) {
+            Ok(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map_or(Err(format!("Invalid file URL: {}", file)), |path| {
+                    check_file(&security, &path, file)
+                }),
+            Ok(url) if url.scheme().len() > 1 => check_url_policy(&config, file).map(|_| ()),
+            _ => check_file(&security, Path::new(file), file),
+        };
+        if let Err(e) = denied {
+            return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(e),
+            }));
+        }
+    }
+
+    // Desktop entries are launched from their Exec line, with files substituted for its field codes
+    let mut desktop_argv = None;
+    if desktop {
+        if req.args.as_ref().is_some_and(|args| !args.is_empty()) {
+            return Ok(HttpResponse::BadRequest().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some("Desktop entries take files, not args".to_string()),
+            }));
+        }
+        let entry = match tokio::fs::read_to_string(&app_path).await {
+            Ok(content) => parse_desktop_entry(&content, &app_path),
+            Err(e) => Err(format!("Failed to read desktop entry: {}", e)),
+        };
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                error!("Failed to load desktop entry {}: {}", req.application, e);
+                return Ok(HttpResponse::Ok().json(OpenAppResponse {
+                    success: false,
+                    pid: None,
+                    error: Some(e),
+                }));
+            }
+        };
+        if !security.is_command_safe(&entry.exec) {
+            return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(format!("Desktop entry command deemed unsafe: {}", entry.exec)),
+            }));
+        }
+        // The program is checked like a shortcut target, wherever PATH finds it
+        let search_path = security.child_path().map(OsStr::to_owned).or_else(|| std::env::var_os("PATH"));
+        let Some(exec_path) = entry
+            .program()
+            .ok()
+            .and_then(|program| resolve_command_in(&program, search_path.as_deref()))
+        else {
+            return Ok(HttpResponse::Ok().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(format!("Desktop entry command not found: {}", entry.exec)),
+            }));
+        };
+        if !security.is_path_allowed(&extended_path(&exec_path)) {
+            return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(format!("Access denied to desktop entry command: {}", exec_path.display())),
+            }));
+        }
+        match entry.command_line(&files) {
+            Ok(argv) => desktop_argv = Some(argv),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(OpenAppResponse {
+                    success: false,
+                    pid: None,
+                    error: Some(e),
+                }));
+            }
+        }
+        default_dir = entry.working_dir;
     }
 
-    // Set working directory if provided
-    if let Some(cwd) = &req.cwd {
+    // Executing a bundle directory fails; Launch Services starts it instead
+    let bundle = cfg!(target_os = "macos") && is_app_bundle(Path::new(&program));
+    if bundle && capture_output {
+        return Ok(HttpResponse::BadRequest().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some("capture_output is not supported for app bundles: Launch Services does not forward their output".to_string()),
+        }));
+    }
+
+    if let Err(e) = check_spawn_limit(&security, &registry) {
+        warn!("Launch refused: {}", e);
+        return Ok(HttpResponse::TooManyRequests().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(e),
+        }));
+    }
+
+    info!(
+        "Opening application: {} with args: {:?}",
+        program,
+        req.args.as_deref().map(|a| security.redact_args(a))
+    );
+
+    let mut command = if bundle {
+        let (launcher, launch_args) = app_bundle_launcher(
+            Path::new(&program),
+            &files,
+            req.args.as_deref().unwrap_or_default(),
+            req.activate.unwrap_or(true),
+            req.new_instance.unwrap_or(false),
+            wait,
+        );
+        let mut command = Command::new(launcher);
+        command.args(launch_args);
+        command
+    } else if let Some(argv) = &desktop_argv {
+        let mut command = Command::new(&argv[0]);
+        command.args(&argv[1..]);
+        command
+    } else {
+        let mut command = Command::new(&program);
+
+        // The shortcut's arguments are a raw command line and go first
+        if let Some(args) = &shortcut_args {
+            #[cfg(windows)]
+            std::os::windows::process::CommandExt::raw_arg(&mut command, args);
+            #[cfg(not(windows))]
+            command.args(args.split_whitespace());
+        }
+
+        // Add arguments if provided, then the files to open
+        if let Some(args) = &req.args {
+            command.args(args);
+        }
+        command.args(&files);
+        command
+    };
+
+    // Set working directory if provided, falling back to the shortcut's or desktop entry's
+    if let Some(cwd) = req.cwd.as_ref().or(default_dir.as_ref()) {
         let cwd_path = PathBuf::from(cwd);
         if security.is_path_allowed(&cwd_path) {
             command.current_dir(cwd);
@@ -55,6 +282,26 @@ pub async fn open_application(
         }
     }
 
+    if let Err(e) = security.check_env(&req.environment) {
+        warn!("Application environment denied: {}", e);
+        return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(e),
+        }));
+    }
+    apply_env(&mut command, &req.environment);
+    if let Some(path) = security.child_path() {
+        command.env("PATH", path);
+    }
+    apply_priority(&mut command, security.process_priority(req.priority));
+
+    if wait {
+        let registry = registry.get_ref().clone();
+        let power = power.get_ref().clone();
+        return run_attached(command, &pools, registry, power, req.application.clone(), capture_output).await;
+    }
+
     // Configure process to run independently
     command
         .stdin(Stdio::null())
@@ -63,7 +310,7 @@ pub async fn open_application(
 
     match command.spawn() {
         Ok(child) => {
-            let pid = child.id();
+            let pid = registry.adopt(ProcessKind::App, &req.application, child);
             info!("Successfully launched application: {} (PID: {})", req.application, pid);
             Ok(HttpResponse::Ok().json(OpenAppResponse {
                 success: true,
@@ -82,6 +329,392 @@ pub async fn open_application(
     }
 }
 
+/// Checks that a file handed to an application is within the allowed paths
+fn check_file(security: &SecurityManager, path: &Path, file: &str) -> std::result::Result<(), String> {
+    if security.is_path_allowed(&extended_path(path)) {
+        Ok(())
+    } else {
+        Err(format!("Access denied to file: {}", file))
+    }
+}
+
+/// Refuses a launch once `security.max_spawned_processes` launched processes are running
+fn check_spawn_limit(security: &SecurityManager, registry: &ProcessRegistry) -> std::result::Result<(), String> {
+    let Some(limit) = security.max_spawned_processes() else {
+        return Ok(());
+    };
+
+    // Processes that exited since the last reaper pass must not count
+    registry.reap();
+    let running = registry.running_launches();
+    if running >= limit {
+        return Err(format!(
+            "{} launched processes are still running, the maximum set by security.max_spawned_processes; close some before launching more",
+            running
+        ));
+    }
+    Ok(())
+}
+
+/// Runs an application to completion on the exec pool, optionally capturing its output
+async fn run_attached(
+    mut command: Command,
+    pools: &WorkerPools,
+    registry: Arc<ProcessRegistry>,
+    power: Arc<PowerManager>,
+    name: String,
+    capture_output: bool,
+) -> Result<HttpResponse> {
+    let output_stdio = || if capture_output { Stdio::piped() } else { Stdio::null() };
+    command.stdin(Stdio::null()).stdout(output_stdio()).stderr(output_stdio());
+    ProcessTree::prepare(&mut command);
+
+    let result = pools
+        .exec
+        .run(move || {
+            let _awake = power.job_started();
+            let child = command.spawn()?;
+            let pid = child.id();
+            let killer = Arc::new(TreeKiller::default());
+            match ProcessTree::attach(&child) {
+                Ok(tree) => killer.track(tree),
+                Err(e) => warn!("Descendants of PID {} cannot be tracked: {}", pid, e),
+            }
+            registry.started(ProcessKind::App, &name, pid, Some(killer.clone()));
+            let output = child.wait_with_output();
+            killer.release();
+            registry.exited(pid, output.as_ref().ok().map(|o| o.status));
+            output.map(|output| (pid, output))
+        })
+        .await;
+
+    let failure = |error: String| AppRunResponse {
+        success: false,
+        pid: None,
+        exit_code: None,
+        stdout: None,
+        stderr: None,
+        error: Some(error),
+    };
+
+    match result {
+        Ok(Ok((pid, output))) => {
+            info!("Application (PID: {}) exited with {}", pid, output.status);
+            let text = |bytes: &[u8]| capture_output.then(|| String::from_utf8_lossy(bytes).to_string());
+            Ok(HttpResponse::Ok().json(AppRunResponse {
+                success: output.status.success(),
+                pid: Some(pid),
+                exit_code: output.status.code(),
+                stdout: text(&output.stdout),
+                stderr: text(&output.stderr),
+                error: None,
+            }))
+        }
+        Ok(Err(e)) => {
+            error!("Failed to launch application: {}", e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to launch application: {}", e))))
+        }
+        Err(e @ PoolError::Saturated(_)) => {
+            warn!("Application launch rejected: {}", e);
+            Ok(HttpResponse::ServiceUnavailable().json(failure(e.to_string())))
+        }
+        Err(e) => {
+            error!("Failed to run application: {}", e);
+            Ok(HttpResponse::InternalServerError().json(failure(format!("Failed to run application: {}", e))))
+        }
+    }
+}
+
+/// Opens the platform terminal with its working directory set to an allowed directory
+pub async fn open_terminal(
+    security: web::Data<Arc<SecurityManager>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    req: web::Json<OpenTerminalRequest>,
+) -> Result<HttpResponse> {
+    let dir = extended_path(Path::new(&req.path));
+
+    if !security.is_path_allowed(&dir) {
+        return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(format!("Access denied to directory: {}", req.path)),
+        }));
+    }
+
+    if !dir.is_dir() {
+        return Ok(HttpResponse::Ok().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(format!("Not a directory: {}", req.path)),
+        }));
+    }
+
+    if let Err(e) = check_spawn_limit(&security, &registry) {
+        warn!("Launch refused: {}", e);
+        return Ok(HttpResponse::TooManyRequests().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(e),
+        }));
+    }
+
+    info!("Opening terminal in: {}", req.path);
+
+    // Try each known terminal until one is installed
+    let mut last_error = None;
+    for (program, args) in terminal_launchers(&dir) {
+        let mut command = Command::new(program);
+        remove_secret_env(&mut command);
+        let spawned = command
+            .args(&args)
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        match spawned {
+            Ok(child) => {
+                let pid = registry.adopt(ProcessKind::Terminal, program, child);
+                info!("Launched {} in {} (PID: {})", program, req.path, pid);
+                return Ok(HttpResponse::Ok().json(OpenAppResponse {
+                    success: true,
+                    pid: Some(pid),
+                    error: None,
+                }));
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                warn!("Terminal {} is not available", program);
+                last_error = Some(e);
+            }
+            Err(e) => {
+                error!("Failed to launch terminal {}: {}", program, e);
+                last_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    let reason = match last_error {
+        Some(e) if e.kind() != ErrorKind::NotFound => e.to_string(),
+        _ => "no supported terminal emulator is installed".to_string(),
+    };
+    Ok(HttpResponse::Ok().json(OpenAppResponse {
+        success: false,
+        pid: None,
+        error: Some(format!("Failed to open terminal: {}", reason)),
+    }))
+}
+
+/// Opens a file at a line and column in a configured editor
+pub async fn open_editor(
+    config: web::Data<Arc<Config>>,
+    security: web::Data<Arc<SecurityManager>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    req: web::Json<OpenEditorRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+
+    if !security.is_path_allowed(&path) {
+        return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(format!("Access denied to file: {}", req.path)),
+        }));
+    }
+
+    if let Err(e) = check_spawn_limit(&security, &registry) {
+        warn!("Launch refused: {}", e);
+        return Ok(HttpResponse::TooManyRequests().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(e),
+        }));
+    }
+
+    let apps = config.apps.as_ref();
+    let editor = req
+        .editor
+        .clone()
+        .or_else(|| apps.and_then(|a| a.default_editor.clone()))
+        .unwrap_or_else(|| "code".to_string());
+    let editors = apps
+        .and_then(|a| a.editors.clone())
+        .unwrap_or_else(default_editors);
+
+    let Some(template) = editors.get(&editor) else {
+        let mut known: Vec<&String> = editors.keys().collect();
+        known.sort();
+        return Ok(HttpResponse::BadRequest().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(format!("Unknown editor '{}'; configured editors: {:?}", editor, known)),
+        }));
+    };
+
+    let Some(file) = editor_file_arg(&path) else {
+        return Ok(HttpResponse::BadRequest().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(format!("Invalid path: {}", req.path)),
+        }));
+    };
+    let line = req.line.unwrap_or(1).max(1);
+    let column = req.column.unwrap_or(1).max(1);
+    let Some((program, args)) = expand_editor_template(template, &file, line, column) else {
+        return Ok(HttpResponse::Ok().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(format!("Editor '{}' has an empty command template", editor)),
+        }));
+    };
+
+    info!("Opening {} at {}:{} in {}", req.path, line, column, editor);
+
+    let mut command = Command::new(&program);
+    remove_secret_env(&mut command);
+    let spawned = command
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    match spawned {
+        Ok(child) => {
+            let pid = registry.adopt(ProcessKind::Editor, &editor, child);
+            info!("Launched editor {} (PID: {})", program, pid);
+            Ok(HttpResponse::Ok().json(OpenAppResponse {
+                success: true,
+                pid: Some(pid),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to launch editor {}: {}", program, e);
+            Ok(HttpResponse::Ok().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(format!("Failed to launch editor '{}': {}", editor, e)),
+            }))
+        }
+    }
+}
+
+/// Substitutes `{file}`, `{line}` and `{column}` and splits the template into program and arguments
+/// The file as handed to an editor: absolute, so it cannot start with `-` or `+` and be read as an option
+fn editor_file_arg(path: &Path) -> Option<String> {
+    std::path::absolute(path).ok().map(|absolute| display_path(&absolute))
+}
+
+fn expand_editor_template(
+    template: &[String],
+    file: &str,
+    line: u32,
+    column: u32,
+) -> Option<(String, Vec<String>)> {
+    let mut parts = template.iter().map(|part| {
+        part.replace("{file}", file)
+            .replace("{line}", &line.to_string())
+            .replace("{column}", &column.to_string())
+    });
+    let program = parts.next()?;
+    Some((program, parts.collect()))
+}
+
+/// Opens a URL with the system handler, limited to the configured schemes and domains
+pub async fn open_url(
+    config: web::Data<Arc<Config>>,
+    security: web::Data<Arc<SecurityManager>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    req: web::Json<OpenUrlRequest>,
+) -> Result<HttpResponse> {
+    let url = match check_url_policy(&config, &req.url) {
+        Ok(url) => url,
+        Err(reason) => {
+            warn!(target: "audit", "open_url denied: {} ({})", req.url, reason);
+            return Ok(HttpResponse::Forbidden().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(reason),
+            }));
+        }
+    };
+
+    if let Err(e) = check_spawn_limit(&security, &registry) {
+        warn!("Launch refused: {}", e);
+        return Ok(HttpResponse::TooManyRequests().json(OpenAppResponse {
+            success: false,
+            pid: None,
+            error: Some(e),
+        }));
+    }
+
+    info!(target: "audit", "open_url allowed: {}", url);
+
+    let (program, args) = url_opener(url.as_str());
+    let mut command = Command::new(program);
+    remove_secret_env(&mut command);
+    let spawned = command
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    match spawned {
+        Ok(child) => {
+            let pid = registry.adopt(ProcessKind::Url, program, child);
+            info!("Opened {} with {} (PID: {})", url, program, pid);
+            Ok(HttpResponse::Ok().json(OpenAppResponse {
+                success: true,
+                pid: Some(pid),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to open {} with {}: {}", url, program, e);
+            Ok(HttpResponse::Ok().json(OpenAppResponse {
+                success: false,
+                pid: None,
+                error: Some(format!("Failed to open URL: {}", e)),
+            }))
+        }
+    }
+}
+
+/// Checks a URL against the schemes and domains configured under `apps`
+pub(crate) fn check_url_policy(config: &Config, raw: &str) -> std::result::Result<Url, String> {
+    let apps = config.apps.as_ref();
+    let schemes = apps
+        .and_then(|a| a.url_schemes.clone())
+        .unwrap_or_else(default_url_schemes);
+    let domains = apps.and_then(|a| a.url_domains.clone()).unwrap_or_default();
+    check_url(raw, &schemes, &domains)
+}
+
+/// Parses a URL and checks it against the scheme allowlist and, for http(s), the domain allowlist
+fn check_url(raw: &str, schemes: &[String], domains: &[String]) -> std::result::Result<Url, String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("Invalid URL '{}': {}", raw, e))?;
+
+    if !schemes.iter().any(|s| s.trim_end_matches("://").eq_ignore_ascii_case(url.scheme())) {
+        return Err(format!("URL scheme '{}' is not allowed", url.scheme()));
+    }
+
+    if matches!(url.scheme(), "http" | "https") && !domains.is_empty() {
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let allowed = domains.iter().any(|domain| {
+            let domain = domain.trim_start_matches("*.").to_ascii_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        });
+        if !allowed {
+            return Err(format!("URL host '{}' is not allowed", host));
+        }
+    }
+
+    Ok(url)
+}
+
 /// Handles server shutdown requests
 pub async fn shutdown_server() -> Result<HttpResponse> {
     info!("Received shutdown request");
@@ -102,3 +735,92 @@ pub async fn shutdown_server() -> Result<HttpResponse> {
 
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[actix_web::test]
+    async fn test_desktop_entry_exec_checked() {
+        use actix_web::test::{call_service, init_service, TestRequest};
+        use actix_web::App;
+        use crate::config::get_default_config;
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("exex-desktop-{}", uuid::Uuid::new_v4().simple()));
+        let denied = root.join("denied");
+        std::fs::create_dir_all(&denied).unwrap();
+        let tool = denied.join("tool");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let entry = root.join("tool.desktop");
+        std::fs::write(&entry, format!("[Desktop Entry]\nType=Application\nExec={} %f\n", tool.display())).unwrap();
+
+        let mut config = get_default_config();
+        config.security.allowed_paths = vec![];
+        config.security.disallowed_paths = vec![denied.to_string_lossy().into_owned()];
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config.clone()))))
+                .app_data(web::Data::new(Arc::new(config)))
+                .app_data(web::Data::new(Arc::new(ProcessRegistry::new())))
+                .app_data(web::Data::new(Arc::new(PowerManager::new(false))))
+                .route("/open", web::post().to(open_application)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/open")
+            .set_json(serde_json::json!({"application": entry.to_string_lossy()}))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), 403);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_expand_editor_template() {
+        let editors = default_editors();
+
+        let (program, args) = expand_editor_template(&editors["idea"], "/src/main.rs", 12, 5).unwrap();
+        assert_eq!(program, "idea");
+        assert_eq!(args, ["--line", "12", "--column", "5", "/src/main.rs"]);
+
+        let (_, args) = expand_editor_template(&editors["code"], "/src/main.rs", 3, 1).unwrap();
+        assert_eq!(args, ["-g", "/src/main.rs:3:1"]);
+
+        assert!(expand_editor_template(&[], "/src/main.rs", 1, 1).is_none());
+    }
+
+    #[test]
+    fn test_editor_file_is_absolute() {
+        for requested in ["--install-extension=evil.vsix", "+!rm -rf ~"] {
+            let file = editor_file_arg(Path::new(requested)).unwrap();
+            assert!(Path::new(&file).is_absolute());
+            assert!(file.ends_with(requested));
+            let (_, args) = expand_editor_template(&default_editors()["idea"], &file, 1, 1).unwrap();
+            assert_eq!(args.last(), Some(&file));
+        }
+        assert!(editor_file_arg(Path::new("")).is_none());
+    }
+
+    #[test]
+    fn test_check_url() {
+        let schemes = default_url_schemes();
+        let any: Vec<String> = vec![];
+        let github = vec!["github.com".to_string()];
+
+        assert!(check_url("https://example.com/docs?a=1&b=2", &schemes, &any).is_ok());
+        assert!(check_url("vscode://file/home/me/main.rs:3", &schemes, &github).is_ok());
+        assert!(check_url("mailto:me@example.com", &schemes, &github).is_ok());
+        assert!(check_url("HTTPS://api.GitHub.com/repos", &schemes, &github).is_ok());
+
+        assert!(check_url("http://example.com", &schemes, &any).is_err());
+        assert!(check_url("file:///etc/passwd", &schemes, &any).is_err());
+        assert!(check_url("https://example.com", &schemes, &github).is_err());
+        assert!(check_url("https://evilgithub.com", &schemes, &github).is_err());
+        assert!(check_url("not a url", &schemes, &any).is_err());
+    }
+}
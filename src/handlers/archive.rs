@@ -0,0 +1,776 @@
+use actix_web::{web, HttpResponse, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use globset::GlobSet;
+use std::collections::HashSet;
+use std::fs::{File, Metadata};
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::handlers::file_ops::{canonical_target, check_disk_space, run_fs, throttle_write};
+use crate::handlers::operations::start_failure_status;
+use crate::models::{ArchiveFormat, ArchiveRequest, ArchiveResponse, ErrorCode, ExtractRequest, ExtractResponse};
+use crate::operations::{cancellation_code, CancelToken, OperationRegistry};
+use crate::platform::{create_dir_all_with_mode, display_path, extended_path, set_mode, write_with_mode, Placement};
+use crate::security::throttle::ClientId;
+use crate::security::workspace::build_glob_set;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Infers an archive's format from its extension
+pub fn format_from_path(path: &Path) -> Option<ArchiveFormat> {
+    let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveFormat::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveFormat::TarGz)
+    } else {
+        None
+    }
+}
+
+/// Where an entry lands below the destination, or `None` if its name could escape it
+///
+/// Names are split on both `/` and `\`, so archives made on Windows cannot climb
+/// out through backslashes either.
+fn entry_path(name: &str) -> Option<PathBuf> {
+    if name.starts_with(['/', '\\']) || name.contains('\0') {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => continue,
+            ".." => return None,
+            // Drive letters and alternate data streams
+            part if cfg!(windows) && part.contains(':') => return None,
+            part => path.push(part),
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Why packing or unpacking stopped
+pub(crate) enum ArchiveFailure {
+    Refused(String, Option<ErrorCode>),
+    /// The archive has an entry whose name points outside the destination
+    UnsafeEntry(String),
+    Io(io::Error),
+}
+
+impl From<io::Error> for ArchiveFailure {
+    fn from(e: io::Error) -> Self {
+        ArchiveFailure::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for ArchiveFailure {
+    fn from(e: zip::result::ZipError) -> Self {
+        ArchiveFailure::Io(io::Error::other(e))
+    }
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Opens an archive for reading, decrypting it first if it lies in an encrypted directory
+fn open_archive(security: &SecurityManager, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+    if security.is_encrypted_path(path) {
+        let data = security.decrypt_for_path(path, std::fs::read(path)?).map_err(io::Error::other)?;
+        return Ok(Box::new(Cursor::new(data)));
+    }
+    Ok(Box::new(File::open(path)?))
+}
+
+/// Permission bits stored with an entry
+fn stored_mode(metadata: &Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o777
+    }
+    #[cfg(not(unix))]
+    {
+        if metadata.is_dir() { 0o755 } else { 0o644 }
+    }
+}
+
+/// The entries of an archive worked out in full before it is written
+pub(crate) struct PackPlan {
+    /// Source path and entry name of each entry, directories before what they contain
+    pub entries: Vec<(PathBuf, String, bool)>,
+    pub bytes: u64,
+    pub skipped: Vec<String>,
+}
+
+/// Walks the sources like a copy does, leaving out symlinks, special files, denied and excluded paths
+///
+/// With `include`, only matching files are packed and directories are left
+/// implicit in their paths. An existing archive at `archive` is never packed
+/// into itself.
+pub(crate) fn plan_archive(
+    security: &SecurityManager,
+    sources: &[(PathBuf, String)],
+    include: Option<&GlobSet>,
+    exclude: Option<&GlobSet>,
+    archive: &Path,
+    cancel: &CancelToken,
+) -> io::Result<PackPlan> {
+    let mut plan = PackPlan {
+        entries: Vec::new(),
+        bytes: 0,
+        skipped: Vec::new(),
+    };
+    let archive_real = canonical_target(archive);
+    for (root, root_name) in sources {
+        let mut stack = vec![(root.clone(), root_name.clone())];
+        while let Some((source, name)) = stack.pop() {
+            cancel.check()?;
+            let metadata = if &source == root {
+                std::fs::metadata(&source)?
+            } else {
+                std::fs::symlink_metadata(&source)?
+            };
+            if !(metadata.is_dir() || metadata.is_file()) || !security.is_path_allowed(&source) {
+                plan.skipped.push(display_path(&source));
+            } else if metadata.is_dir() {
+                let mut children = std::fs::read_dir(&source)?.collect::<io::Result<Vec<_>>>()?;
+                children.sort_by_key(|child| std::cmp::Reverse(child.file_name()));
+                for child in children {
+                    let child_name = format!("{}/{}", name, child.file_name().to_string_lossy());
+                    let relative = &child_name[root_name.len() + 1..];
+                    if exclude.is_some_and(|exclude| exclude.is_match(relative)) {
+                        continue;
+                    }
+                    stack.push((extended_path(&child.path()), child_name));
+                }
+                if include.is_none() {
+                    plan.entries.push((source, name, true));
+                }
+            } else if include.is_some_and(|include| !include.is_match(name.get(root_name.len() + 1..).unwrap_or(&name))) {
+                continue;
+            } else if source.file_name() != archive.file_name() || canonical_target(&source) != archive_real {
+                plan.bytes += metadata.len();
+                plan.entries.push((source, name, false));
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Writes the planned entries to `file`; encrypted sources are packed as plaintext
+fn write_archive(
+    security: &SecurityManager,
+    plan: &PackPlan,
+    format: ArchiveFormat,
+    file: File,
+    cancel: &CancelToken,
+) -> Result<(), ArchiveFailure> {
+    let content = |source: &Path| -> io::Result<Vec<u8>> {
+        security.decrypt_for_path(source, std::fs::read(source)?).map_err(io::Error::other)
+    };
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipWriter::new(file);
+            for (source, name, is_dir) in &plan.entries {
+                cancel.check()?;
+                let metadata = std::fs::metadata(source)?;
+                let options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .unix_permissions(stored_mode(&metadata))
+                    .large_file(metadata.len() > u32::MAX as u64);
+                if *is_dir {
+                    zip.add_directory(name.as_str(), options)?;
+                } else {
+                    zip.start_file(name.as_str(), options)?;
+                    if security.is_encrypted_path(source) {
+                        io::copy(&mut content(source)?.as_slice(), &mut zip)?;
+                    } else {
+                        io::copy(&mut File::open(source)?, &mut zip)?;
+                    }
+                }
+            }
+            zip.finish()?;
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            for (source, name, is_dir) in &plan.entries {
+                cancel.check()?;
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&std::fs::metadata(source)?);
+                if *is_dir {
+                    header.set_size(0);
+                    tar.append_data(&mut header, name, io::empty())?;
+                } else if security.is_encrypted_path(source) {
+                    let data = content(source)?;
+                    header.set_size(data.len() as u64);
+                    tar.append_data(&mut header, name, data.as_slice())?;
+                } else {
+                    // The length is taken from the open file, so a file changing meanwhile cannot corrupt the stream
+                    let file = File::open(source)?;
+                    let len = file.metadata()?.len();
+                    header.set_size(len);
+                    tar.append_data(&mut header, name, file.take(len))?;
+                }
+            }
+            tar.into_inner()?.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Packs into a staging file beside the archive and moves it into place once complete
+///
+/// A cancelled pack removes its staging file, leaving any earlier archive as it was.
+pub(crate) fn pack(
+    security: &SecurityManager,
+    plan: &PackPlan,
+    format: ArchiveFormat,
+    archive: &Path,
+    cancel: &CancelToken,
+) -> Result<u64, ArchiveFailure> {
+    if let Some(parent) = archive.parent() {
+        create_dir_all_with_mode(parent, security.dir_mode())?;
+    }
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let staging = archive.with_file_name(format!(".{}.{}.partial", name, uuid::Uuid::new_v4().simple()));
+    let written = File::create(&staging)
+        .map_err(ArchiveFailure::from)
+        .and_then(|file| write_archive(security, plan, format, file, cancel))
+        .and_then(|_| {
+            if security.is_encrypted_path(archive) {
+                let data = security.encrypt_for_path(archive, std::fs::read(&staging)?).map_err(io::Error::other)?;
+                std::fs::write(&staging, data)?;
+            }
+            set_mode(&staging, security.creation_mode(None, false).map_err(io::Error::other)?)?;
+            std::fs::rename(&staging, archive)?;
+            Ok(std::fs::metadata(archive)?.len())
+        });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&staging);
+    }
+    security.invalidate_path_cache(archive);
+    written
+}
+
+/// Entry count and total declared size of an archive, refusing it if any entry name is unsafe
+pub(crate) fn inspect_archive(
+    security: &SecurityManager,
+    path: &Path,
+    format: ArchiveFormat,
+    cancel: &CancelToken,
+) -> Result<(usize, u64), ArchiveFailure> {
+    let reader = open_archive(security, path)?;
+    let (mut count, mut bytes) = (0, 0u64);
+    let mut check = |name: &str, size: u64| {
+        cancel.check()?;
+        if entry_path(name).is_none() {
+            return Err(ArchiveFailure::UnsafeEntry(format!(
+                "Archive entry {:?} would be written outside the destination",
+                name
+            )));
+        }
+        count += 1;
+        bytes = bytes.saturating_add(size);
+        Ok(())
+    };
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipArchive::new(reader)?;
+            for index in 0..zip.len() {
+                let file = zip.by_index_raw(index)?;
+                check(file.name(), file.size())?;
+            }
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Archive::new(GzDecoder::new(reader));
+            for entry in tar.entries()? {
+                let entry = entry?;
+                check(&String::from_utf8_lossy(&entry.path_bytes()), entry.size())?;
+            }
+        }
+    }
+    Ok((count, bytes))
+}
+
+#[derive(PartialEq)]
+enum EntryKind {
+    Directory,
+    File,
+    /// Symlinks, hard links and special files, which are never unpacked
+    Other,
+}
+
+/// Writes entries below the destination with the checks `/api/write` applies
+struct Unpacker<'a> {
+    security: &'a SecurityManager,
+    destination: PathBuf,
+    overwrite: bool,
+    force: bool,
+    cancel: CancelToken,
+    entries: usize,
+    bytes: u64,
+    skipped: Vec<String>,
+}
+
+impl Unpacker<'_> {
+    fn check(&self, target: &Path) -> Result<(), ArchiveFailure> {
+        if !self.security.is_path_allowed(target) || self.security.is_workspace_policy_path(target) {
+            let error = format!("Access denied to destination path: {}", display_path(target));
+            return Err(ArchiveFailure::Refused(error, None));
+        }
+        self.security
+            .check_protected(target, self.force)
+            .map_err(|e| ArchiveFailure::Refused(e, Some(ErrorCode::ProtectedPath)))
+    }
+
+    /// Creates missing directories one level at a time, checking each once its parent exists
+    fn make_dirs(&self, dir: &Path) -> Result<(), ArchiveFailure> {
+        let missing: Vec<&Path> = dir.ancestors().take_while(|dir| !dir.exists()).collect();
+        for dir in missing.into_iter().rev() {
+            self.check(dir)?;
+            create_dir_all_with_mode(dir, self.security.dir_mode())?;
+            self.security.invalidate_path_cache(dir);
+        }
+        Ok(())
+    }
+
+    fn place(&mut self, name: &str, kind: EntryKind, mode: Option<u32>, content: &mut dyn Read) -> Result<(), ArchiveFailure> {
+        self.cancel.check()?;
+        let Some(relative) = entry_path(name) else {
+            return Err(ArchiveFailure::UnsafeEntry(format!(
+                "Archive entry {:?} would be written outside the destination",
+                name
+            )));
+        };
+        let target = self.destination.join(relative);
+        match kind {
+            EntryKind::Other => {
+                self.skipped.push(name.to_string());
+                return Ok(());
+            }
+            EntryKind::Directory => self.make_dirs(&target)?,
+            EntryKind::File => {
+                if let Some(parent) = target.parent() {
+                    self.make_dirs(parent)?;
+                }
+                self.check(&target)?;
+                // Existing symlinks and directories are never written through or replaced
+                if let Ok(existing) = std::fs::symlink_metadata(&target)
+                    && (!existing.is_file() || !self.overwrite)
+                {
+                    self.skipped.push(name.to_string());
+                    return Ok(());
+                }
+
+                let limit = self.security.max_file_size_bytes();
+                let mut data = Vec::new();
+                content.take(limit + 1).read_to_end(&mut data)?;
+                if data.len() as u64 > limit {
+                    let error = format!("Archive entry {} exceeds the maximum file size of {} bytes", name, limit);
+                    return Err(ArchiveFailure::Refused(error, None));
+                }
+                let len = data.len() as u64;
+                let data = self.security.encrypt_for_path(&target, data).map_err(io::Error::other)?;
+                let mode = mode
+                    .and_then(|mode| self.security.creation_mode(Some(mode & 0o777), false).ok())
+                    .map_or_else(|| self.security.creation_mode(None, false), Ok)
+                    .map_err(io::Error::other)?;
+                write_with_mode(&target, &data, mode, false, Placement::Replace, false)?;
+                self.bytes += len;
+            }
+        }
+        self.security.invalidate_path_cache(&target);
+        self.entries += 1;
+        Ok(())
+    }
+}
+
+fn unpack(unpacker: &mut Unpacker, path: &Path, format: ArchiveFormat) -> Result<(), ArchiveFailure> {
+    let reader = open_archive(unpacker.security, path)?;
+    unpacker.make_dirs(&unpacker.destination.clone())?;
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipArchive::new(reader)?;
+            for index in 0..zip.len() {
+                let mut file = zip.by_index(index)?;
+                let kind = if file.is_dir() {
+                    EntryKind::Directory
+                } else if file.is_symlink() {
+                    EntryKind::Other
+                } else {
+                    EntryKind::File
+                };
+                let (name, mode) = (file.name().to_string(), file.unix_mode());
+                unpacker.place(&name, kind, mode, &mut file)?;
+            }
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Archive::new(GzDecoder::new(reader));
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                let kind = match entry.header().entry_type() {
+                    tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+                    tar::EntryType::Directory => EntryKind::Directory,
+                    _ => EntryKind::Other,
+                };
+                let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+                let mode = entry.header().mode().ok();
+                unpacker.place(&name, kind, mode, &mut entry)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles requests to pack files and directories into a zip or tar.gz archive
+///
+/// The archive is written under a temporary name and renamed into place, so a
+/// failed request never leaves a truncated archive behind.
+pub async fn create_archive(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    operations: web::Data<Arc<OperationRegistry>>,
+    client: ClientId,
+    req: web::Json<ArchiveRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+    let failure = |error: String, error_code: Option<ErrorCode>| ArchiveResponse {
+        success: false,
+        path: None,
+        format: None,
+        entries: None,
+        bytes: None,
+        skipped: None,
+        error: Some(error),
+        error_code,
+    };
+
+    // The archive gets the checks of a write; sources are only read
+    if !security.is_path_allowed(&path) || security.is_workspace_policy_path(&path) {
+        let error = format!("Access denied to path: {}", req.path);
+        return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+    }
+    if let Err(e) = security.check_protected(&path, req.force.unwrap_or(false)) {
+        warn!("Archive refused for {}: {}", req.path, e);
+        return Ok(HttpResponse::Forbidden().json(failure(e, Some(ErrorCode::ProtectedPath))));
+    }
+    let Some(format) = req.format.or_else(|| format_from_path(&path)) else {
+        let error = format!("Cannot infer the archive format of {}; set format", req.path);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    };
+    if req.sources.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(failure("'sources' must not be empty".to_string(), None)));
+    }
+
+    let mut sources = Vec::new();
+    let mut names = HashSet::new();
+    for requested in &req.sources {
+        let source = extended_path(Path::new(requested));
+        if !security.is_path_allowed(&source) {
+            let error = format!("Access denied to source path: {}", requested);
+            return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+        }
+        if !source.exists() {
+            let error = format!("Source path does not exist: {}", requested);
+            return Ok(HttpResponse::Ok().json(failure(error, None)));
+        }
+        let Some(name) = source.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+            let error = format!("Source path has no name to store it under: {}", requested);
+            return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+        };
+        if !names.insert(name.clone()) {
+            let error = format!("More than one source is named {}", name);
+            return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+        }
+        sources.push((source, name));
+    }
+    let exclude = match req.exclude.as_deref().map(build_glob_set).transpose() {
+        Ok(exclude) => exclude,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(failure(format!("Invalid exclude pattern {}", e), None))),
+    };
+
+    if path.exists() {
+        let error = if path.is_dir() {
+            Some(format!("Archive path is a directory: {}", req.path))
+        } else if !req.overwrite.unwrap_or(false) {
+            Some(format!("Archive already exists: {}", req.path))
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            return Ok(HttpResponse::Ok().json(failure(error, None)));
+        }
+    }
+
+    let summary = format!("{} -> {}", req.sources.join(", "), req.path);
+    let operation = match operations.start(req.operation_id.as_deref(), "archive", &req.path, summary) {
+        Ok(operation) => operation,
+        Err(e) => return Ok(HttpResponse::build(start_failure_status(&e)).json(failure(e.to_string(), None))),
+    };
+
+    let planner = security.get_ref().clone();
+    let (target, cancel) = (path.clone(), operation.token());
+    let planned = run_fs(&pools, move || plan_archive(&planner, &sources, None, exclude.as_ref(), &target, &cancel)).await;
+    let plan = match planned {
+        Ok(plan) => plan,
+        Err(e) => {
+            error!("Failed to archive {}: {}", req.path, e);
+            let error_code = cancellation_code(&e);
+            return Ok(HttpResponse::Ok().json(failure(format!("Failed to create archive: {}", e), error_code)));
+        }
+    };
+
+    if let Err(e) = check_disk_space(&security, &pools, &path, plan.bytes).await {
+        warn!("Archive refused for {}: {}", req.path, e);
+        return Ok(HttpResponse::InsufficientStorage().json(failure(e, Some(ErrorCode::InsufficientSpace))));
+    }
+
+    throttle_write(&security, &client, &req.path, plan.bytes).await;
+
+    info!("Creating archive: {} ({} entries, {} bytes)", req.path, plan.entries.len(), plan.bytes);
+    let packer = security.get_ref().clone();
+    let (target, cancel) = (path.clone(), operation.token());
+    cancel.set_total(plan.entries.len());
+    let result = run_fs(&pools, move || Ok(pack(&packer, &plan, format, &target, &cancel).map(|bytes| (plan, bytes)))).await;
+
+    match result {
+        Ok(Ok((plan, bytes))) => {
+            info!("Successfully created archive: {}", req.path);
+            Ok(HttpResponse::Ok().json(ArchiveResponse {
+                success: true,
+                path: Some(req.path.clone()),
+                format: Some(format),
+                entries: Some(plan.entries.len()),
+                bytes: Some(bytes),
+                skipped: Some(plan.skipped),
+                error: None,
+                error_code: None,
+            }))
+        }
+        Ok(Err(ArchiveFailure::Refused(e, error_code))) => {
+            warn!("Archive refused for {}: {}", req.path, e);
+            Ok(HttpResponse::Forbidden().json(failure(e, error_code)))
+        }
+        Ok(Err(ArchiveFailure::UnsafeEntry(e))) => Ok(HttpResponse::BadRequest().json(failure(e, None))),
+        Ok(Err(ArchiveFailure::Io(e))) | Err(e) => {
+            error!("Failed to archive {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to create archive: {}", e), cancellation_code(&e))))
+        }
+    }
+}
+
+/// Handles requests to unpack a zip or tar.gz archive into a directory
+///
+/// Every entry name is checked before anything is written, so an archive with
+/// a name like `../../.bashrc` is refused as a whole. Links inside the archive
+/// are skipped rather than recreated.
+pub async fn extract_archive(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    operations: web::Data<Arc<OperationRegistry>>,
+    client: ClientId,
+    req: web::Json<ExtractRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+    let destination = extended_path(Path::new(&req.destination));
+    let failure = |error: String, error_code: Option<ErrorCode>| ExtractResponse {
+        success: false,
+        destination: None,
+        entries: None,
+        bytes: None,
+        skipped: None,
+        error: Some(error),
+        error_code,
+    };
+
+    if !security.is_path_allowed(&path) {
+        let error = format!("Access denied to path: {}", req.path);
+        return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+    }
+    if !security.is_path_allowed(&destination) || security.is_workspace_policy_path(&destination) {
+        let error = format!("Access denied to destination path: {}", req.destination);
+        return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+    }
+    let force = req.force.unwrap_or(false);
+    if let Err(e) = security.check_protected(&destination, force) {
+        warn!("Extract refused for {} -> {}: {}", req.path, req.destination, e);
+        return Ok(HttpResponse::Forbidden().json(failure(e, Some(ErrorCode::ProtectedPath))));
+    }
+    let Some(format) = req.format.or_else(|| format_from_path(&path)) else {
+        let error = format!("Cannot infer the archive format of {}; set format", req.path);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    };
+    if !path.is_file() {
+        let error = format!("Archive does not exist: {}", req.path);
+        return Ok(HttpResponse::Ok().json(failure(error, None)));
+    }
+
+    let summary = format!("{} -> {}", req.path, req.destination);
+    let operation = match operations.start(req.operation_id.as_deref(), "extract", &req.path, summary) {
+        Ok(operation) => operation,
+        Err(e) => return Ok(HttpResponse::build(start_failure_status(&e)).json(failure(e.to_string(), None))),
+    };
+
+    let inspector = security.get_ref().clone();
+    let (archive, cancel) = (path.clone(), operation.token());
+    let inspected = run_fs(&pools, move || Ok(inspect_archive(&inspector, &archive, format, &cancel))).await;
+    let (count, bytes) = match inspected {
+        Ok(Ok(inspected)) => inspected,
+        Ok(Err(ArchiveFailure::UnsafeEntry(e))) => {
+            warn!("Extract refused for {}: {}", req.path, e);
+            return Ok(HttpResponse::BadRequest().json(failure(e, Some(ErrorCode::InvalidPath))));
+        }
+        Ok(Err(ArchiveFailure::Refused(e, error_code))) => {
+            return Ok(HttpResponse::Forbidden().json(failure(e, error_code)));
+        }
+        Ok(Err(ArchiveFailure::Io(e))) | Err(e) => {
+            error!("Failed to read archive {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(failure(format!("Failed to read archive: {}", e), cancellation_code(&e))));
+        }
+    };
+
+    if let Some(limit) = security.max_affected_entries()
+        && count > limit
+        && !req.override_entry_limit.unwrap_or(false)
+    {
+        let error = format!(
+            "Operation would affect more than {} entries; set override_entry_limit to proceed",
+            limit
+        );
+        warn!("Extract refused for {}: {}", req.path, error);
+        return Ok(HttpResponse::Forbidden().json(failure(error, Some(ErrorCode::TooManyEntries))));
+    }
+
+    if let Err(e) = check_disk_space(&security, &pools, &destination, bytes).await {
+        warn!("Extract refused for {} -> {}: {}", req.path, req.destination, e);
+        return Ok(HttpResponse::InsufficientStorage().json(failure(e, Some(ErrorCode::InsufficientSpace))));
+    }
+
+    throttle_write(&security, &client, &req.destination, bytes).await;
+
+    info!("Extracting: {} -> {} ({} entries, {} bytes)", req.path, req.destination, count, bytes);
+    let unpacker_security = security.get_ref().clone();
+    let overwrite = req.overwrite.unwrap_or(false);
+    let (target, cancel) = (destination.clone(), operation.token());
+    cancel.set_total(count);
+    let result = run_fs(&pools, move || {
+        let mut unpacker = Unpacker {
+            security: &unpacker_security,
+            destination: target,
+            overwrite,
+            force,
+            cancel,
+            entries: 0,
+            bytes: 0,
+            skipped: Vec::new(),
+        };
+        Ok(unpack(&mut unpacker, &path, format).map(|_| (unpacker.entries, unpacker.bytes, unpacker.skipped)))
+    })
+    .await;
+    security.invalidate_path_cache(&destination);
+
+    match result {
+        Ok(Ok((entries, bytes, skipped))) => {
+            info!("Successfully extracted: {} -> {}", req.path, req.destination);
+            Ok(HttpResponse::Ok().json(ExtractResponse {
+                success: true,
+                destination: Some(req.destination.clone()),
+                entries: Some(entries),
+                bytes: Some(bytes),
+                skipped: Some(skipped),
+                error: None,
+                error_code: None,
+            }))
+        }
+        Ok(Err(ArchiveFailure::Refused(e, error_code))) => {
+            warn!("Extract refused for {} -> {}: {}", req.path, req.destination, e);
+            Ok(HttpResponse::Forbidden().json(failure(e, error_code)))
+        }
+        Ok(Err(ArchiveFailure::UnsafeEntry(e))) => {
+            Ok(HttpResponse::BadRequest().json(failure(e, Some(ErrorCode::InvalidPath))))
+        }
+        Ok(Err(ArchiveFailure::Io(e))) | Err(e) => {
+            error!("Failed to extract {} -> {}: {}", req.path, req.destination, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to extract: {}", e), cancellation_code(&e))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let dir = std::env::temp_dir().join(format!("exex-archive-{}", uuid::Uuid::new_v4().simple()));
+        let project = dir.join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::create_dir_all(project.join("target")).unwrap();
+        std::fs::write(project.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(project.join("target").join("app"), b"\x7fELF").unwrap();
+        std::fs::write(project.join("README.md"), "# Project\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc/passwd", project.join("passwd")).unwrap();
+
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let exclude = build_glob_set(&["target".to_string()]).unwrap();
+        let sources = [(project.clone(), "project".to_string())];
+        let cancel = CancelToken::default();
+
+        for (file_name, format) in [("project.zip", ArchiveFormat::Zip), ("project.tgz", ArchiveFormat::TarGz)] {
+            let archive = dir.join(file_name);
+            assert_eq!(format_from_path(&archive), Some(format));
+            let plan = plan_archive(&security, &sources, None, Some(&exclude), &archive, &cancel).unwrap();
+            let names: Vec<&str> = plan.entries.iter().map(|(_, name, _)| name.as_str()).collect();
+            assert_eq!(names, ["project", "project/README.md", "project/src", "project/src/main.rs"]);
+            assert!(pack(&security, &plan, format, &archive, &cancel).is_ok());
+            assert_eq!(inspect_archive(&security, &archive, format, &cancel).ok().map(|(count, _)| count), Some(4));
+
+            let destination = dir.join(format!("out-{}", file_name));
+            let mut unpacker = Unpacker {
+                security: &security,
+                destination: destination.clone(),
+                overwrite: false,
+                force: false,
+                cancel: cancel.clone(),
+                entries: 0,
+                bytes: 0,
+                skipped: Vec::new(),
+            };
+            assert!(unpack(&mut unpacker, &archive, format).is_ok());
+            assert_eq!(unpacker.entries, 4);
+            let main = std::fs::read_to_string(destination.join("project").join("src").join("main.rs")).unwrap();
+            assert_eq!(main, "fn main() {}\n");
+
+            // Unpacking again keeps the existing files
+            unpacker.entries = 0;
+            assert!(unpack(&mut unpacker, &archive, format).is_ok());
+            assert_eq!(unpacker.skipped, ["project/README.md", "project/src/main.rs"]);
+        }
+
+        // Entries that would escape the destination are refused before anything is written
+        let evil = dir.join("evil.zip");
+        let mut zip = ZipWriter::new(File::create(&evil).unwrap());
+        zip.start_file("../escaped.txt", SimpleFileOptions::default()).unwrap();
+        io::Write::write_all(&mut zip, b"gotcha").unwrap();
+        zip.finish().unwrap();
+        assert!(matches!(
+            inspect_archive(&security, &evil, ArchiveFormat::Zip, &cancel),
+            Err(ArchiveFailure::UnsafeEntry(_))
+        ));
+
+        assert_eq!(entry_path("a/./b"), Some(PathBuf::from("a").join("b")));
+        assert_eq!(entry_path("a\\..\\..\\b"), None);
+        assert_eq!(entry_path("/etc/passwd"), None);
+        assert_eq!(entry_path("./"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::backups::{BackupError, BackupService};
+use crate::config::is_pipeline_name;
+use crate::models::{BackupListResponse, BackupRunResponse};
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+fn run_failure(name: String, error: String) -> BackupRunResponse {
+    BackupRunResponse {
+        success: false,
+        name,
+        run: None,
+        error: Some(error),
+    }
+}
+
+/// Lists the configured backup profiles with their schedules, latest runs and archives
+pub async fn list_backups(
+    backups: web::Data<Arc<BackupService>>,
+    pools: web::Data<Arc<WorkerPools>>,
+) -> Result<HttpResponse> {
+    let service = backups.get_ref().clone();
+    match pools.fs.run(move || service.statuses()).await {
+        Ok(backups) => Ok(HttpResponse::Ok().json(BackupListResponse { success: true, backups })),
+        Err(e) => {
+            error!("Failed to list backups: {}", e);
+            Ok(HttpResponse::ServiceUnavailable().json(BackupListResponse { success: false, backups: vec![] }))
+        }
+    }
+}
+
+/// Runs a backup profile now and waits for it to finish
+///
+/// The profile's schedule is not affected; its next run is counted from this one.
+pub async fn run_backup(
+    backups: web::Data<Arc<BackupService>>,
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let name = path.into_inner();
+    if !is_pipeline_name(&name) {
+        let error = format!("Invalid backup name: {}", name);
+        return Ok(HttpResponse::BadRequest().json(run_failure(name, error)));
+    }
+    let (service, runner, profile) = (backups.get_ref().clone(), security.get_ref().clone(), name.clone());
+    let result = match pools.background.run(move || service.run(&runner, &profile)).await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to run backup {}: {}", name, e);
+            return Ok(HttpResponse::ServiceUnavailable().json(run_failure(name, e.to_string())));
+        }
+    };
+
+    match result {
+        Ok(run) => {
+            info!("Backup {} finished on request (success: {})", name, run.success);
+            Ok(HttpResponse::Ok().json(BackupRunResponse {
+                success: run.success,
+                error: run.error.clone(),
+                name,
+                run: Some(run),
+            }))
+        }
+        Err(BackupError::NotFound) => {
+            let error = format!("Backup not found: {}", name);
+            Ok(HttpResponse::NotFound().json(run_failure(name, error)))
+        }
+        Err(BackupError::Running) => {
+            let error = format!("Backup {} is already running", name);
+            Ok(HttpResponse::Conflict().json(run_failure(name, error)))
+        }
+        Err(BackupError::Refused(run)) => Ok(HttpResponse::Forbidden().json(BackupRunResponse {
+            success: false,
+            error: run.error.clone(),
+            name,
+            run: Some(*run),
+        })),
+    }
+}
@@ -0,0 +1,317 @@
+use actix_web::{body::to_bytes, dev, web, web::Bytes, Error, FromRequest, HttpRequest, HttpResponse, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::future::{ready, Ready};
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::audit::BatchAudit;
+use crate::handlers::stream::channel_body;
+use crate::handlers::{
+    copy_item, create_item, delete_item, edit_file, exec_command, read_file, rename_item, scan_directory, write_file,
+};
+use crate::jobs::JobLogs;
+use crate::models::{BatchLine, BatchOperation, BatchResult};
+use crate::operations::OperationRegistry;
+use crate::power::PowerManager;
+use crate::processes::ProcessRegistry;
+use crate::routes::version::ApiVersion;
+use crate::security::path_limits::{check_body_paths, MAX_INSPECTED_BODY};
+use crate::security::throttle::ClientId;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Longest accepted request line; the JSON body limit of the single-operation endpoints
+pub const MAX_BATCH_LINE_BYTES: usize = MAX_INSPECTED_BODY;
+
+impl BatchOperation {
+    /// Name of the operation as given in the `op` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchOperation::Exec(_) => "exec",
+            BatchOperation::Read(_) => "read",
+            BatchOperation::Write(_) => "write",
+            BatchOperation::Edit(_) => "edit",
+            BatchOperation::Scan(_) => "scan",
+            BatchOperation::Delete(_) => "delete",
+            BatchOperation::Create(_) => "create",
+            BatchOperation::Rename(_) => "rename",
+            BatchOperation::Copy(_) => "copy",
+        }
+    }
+}
+
+/// Shared state and request metadata every operation in a batch runs with
+#[derive(Clone)]
+pub struct BatchContext {
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    operations: web::Data<Arc<OperationRegistry>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    power: web::Data<Arc<PowerManager>>,
+    logs: web::Data<Arc<JobLogs>>,
+    client: ClientId,
+    api_version: ApiVersion,
+    audit: Option<BatchAudit>,
+}
+
+impl FromRequest for BatchContext {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let mut extract = || {
+            Ok(Self {
+                security: web::Data::from_request(req, payload).into_inner()?,
+                pools: web::Data::from_request(req, payload).into_inner()?,
+                operations: web::Data::from_request(req, payload).into_inner()?,
+                registry: web::Data::from_request(req, payload).into_inner()?,
+                power: web::Data::from_request(req, payload).into_inner()?,
+                logs: web::Data::from_request(req, payload).into_inner()?,
+                client: ClientId::from_request(req, payload).into_inner()?,
+                api_version: ApiVersion::from_request(req, payload).into_inner()?,
+                audit: BatchAudit::from_request(req),
+            })
+        };
+        ready(extract())
+    }
+}
+
+/// Handles NDJSON batch requests
+///
+/// Each request line is an operation object tagged by `op` with the same fields as
+/// the single-operation endpoint. Operations run in order, and each result is
+/// written as an NDJSON line as soon as it completes, so clients can pipeline
+/// many small operations over one connection.
+///
+/// Lines get the path validation and audit entry a single request gets from
+/// the middleware, which only sees the stream as a whole.
+pub async fn batch_stream(context: BatchContext, mut payload: web::Payload) -> Result<HttpResponse> {
+    let (tx, body) = channel_body();
+
+    actix_web::rt::spawn(async move {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut index = 0;
+
+        while let Some(chunk) = payload.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    warn!("Batch request stream failed: {}", e);
+                    return;
+                }
+            };
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                if !run_line(&line[..newline], index, &context, &tx).await {
+                    return;
+                }
+                if !line[..newline].trim_ascii().is_empty() {
+                    index += 1;
+                }
+            }
+
+            if buffer.len() > MAX_BATCH_LINE_BYTES {
+                let result = error_result(index, 413, "Batch line exceeds maximum allowed size");
+                let _ = send_result(&tx, &result).await;
+                return;
+            }
+        }
+
+        // The final line does not need a trailing newline
+        let _ = run_line(&buffer, index, &context, &tx).await;
+        info!("Batch stream finished");
+    });
+
+    Ok(HttpResponse::Ok().content_type("application/x-ndjson").body(body))
+}
+
+/// Parses and runs one request line, returning false once the client has gone away
+async fn run_line(
+    line: &[u8],
+    index: usize,
+    context: &BatchContext,
+    tx: &mpsc::Sender<std::io::Result<Bytes>>,
+) -> bool {
+    let line = line.trim_ascii();
+    if line.is_empty() {
+        return true;
+    }
+
+    let started = SystemTime::now();
+    let parsed = serde_json::from_slice::<Value>(line)
+        .and_then(|params| BatchLine::deserialize(&params).map(|parsed| (parsed, params)));
+    let (result, op, params) = match parsed {
+        Ok((BatchLine { id, operation }, params)) => {
+            let op = operation.name();
+            let response = match check_body_paths(&params) {
+                Some(rejection) => HttpResponse::BadRequest().json(rejection),
+                None => dispatch(operation, context.clone()).await,
+            };
+            let status = response.status().as_u16();
+            let result = match to_bytes(response.into_body()).await {
+                Ok(bytes) => BatchResult {
+                    index,
+                    id,
+                    op: Some(op.to_string()),
+                    status,
+                    result: serde_json::from_slice(&bytes).ok(),
+                    error: None,
+                },
+                Err(e) => BatchResult {
+                    index,
+                    id,
+                    op: Some(op.to_string()),
+                    status: 500,
+                    result: None,
+                    error: Some(format!("Failed to collect operation result: {}", e)),
+                },
+            };
+            (result, Some(op), Some(params))
+        }
+        Err(e) => (error_result(index, 400, &format!("Invalid batch operation: {}", e)), None, None),
+    };
+
+    if let Some(audit) = &context.audit {
+        audit.record(op, params.as_ref(), result.status, started);
+    }
+    send_result(tx, &result).await
+}
+
+/// Runs an operation through the same handler as its single-request endpoint
+async fn dispatch(operation: BatchOperation, context: BatchContext) -> HttpResponse {
+    let BatchContext {
+        security,
+        pools,
+        operations,
+        registry,
+        power,
+        logs,
+        client,
+        api_version,
+        audit: _,
+    } = context;
+
+    let response = match operation {
+        BatchOperation::Exec(req) => exec_command(security, pools, registry, power, logs, web::Json(req)).await,
+        BatchOperation::Read(req) => read_file(security, pools, web::Json(req)).await,
+        BatchOperation::Write(req) => write_file(security, pools, client, web::Json(req)).await,
+        BatchOperation::Edit(req) => edit_file(security, pools, client, web::Json(req)).await,
+        BatchOperation::Scan(req) => scan_directory(security, pools, operations, api_version, web::Json(req)).await,
+        BatchOperation::Delete(req) => delete_item(security, pools, web::Json(req)).await,
+        BatchOperation::Create(req) => create_item(security, pools, client, web::Json(req)).await,
+        BatchOperation::Rename(req) => rename_item(security, web::Json(req)).await,
+        BatchOperation::Copy(req) => copy_item(security, pools, operations, client, web::Json(req)).await,
+    };
+
+    response.unwrap_or_else(|e| e.error_response())
+}
+
+fn error_result(index: usize, status: u16, error: &str) -> BatchResult {
+    BatchResult {
+        index,
+        id: None,
+        op: None,
+        status,
+        result: None,
+        error: Some(error.to_string()),
+    }
+}
+
+async fn send_result(tx: &mpsc::Sender<std::io::Result<Bytes>>, result: &BatchResult) -> bool {
+    let mut line = serde_json::to_vec(result).unwrap_or_default();
+    line.push(b'\n');
+    tx.send(Ok(Bytes::from(line))).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditLogger;
+    use crate::config::get_default_config;
+    use crate::models::ServerConfig;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+
+    #[actix_web::test]
+    async fn test_batch_stream() {
+        let config = get_default_config();
+        let pools = Arc::new(WorkerPools::from_config(&ServerConfig {
+            exec_workers: Some(1),
+            fs_workers: Some(1),
+            background_workers: Some(1),
+            ..config.server.clone()
+        }));
+        let security = Arc::new(SecurityManager::new(config));
+        let audit_dir = tempfile::tempdir().unwrap();
+        let logger = Arc::new(AuditLogger::new(audit_dir.path().join("audit.log")));
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(security))
+                .app_data(web::Data::new(logger.clone()))
+                .app_data(web::Data::new(pools))
+                .app_data(web::Data::new(Arc::new(OperationRegistry::new())))
+                .app_data(web::Data::new(Arc::new(ProcessRegistry::new())))
+                .app_data(web::Data::new(Arc::new(PowerManager::new(false))))
+                .app_data(web::Data::new(Arc::new(JobLogs::new(std::env::temp_dir().join("exex-batch-jobs"), 0, 0))))
+                .route("/api/batch/stream", web::post().to(batch_stream)),
+        )
+        .await;
+
+        let dir = std::env::temp_dir().join(format!("exex-batch-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("note.txt");
+        let file = file.to_string_lossy().replace('\\', "\\\\");
+        let body = format!(
+            "{{\"op\":\"write\",\"id\":1,\"path\":\"{file}\",\"content\":\"hello\",\"verify\":true}}\n\
+             \n\
+             {{\"op\":\"read\",\"id\":\"r\",\"path\":\"{file}\"}}\n\
+             {{\"op\":\"read\",\"path\":\"{file}\\u0000.bak\"}}\n\
+             {{\"op\":\"bogus\"}}"
+        );
+
+        let req = TestRequest::post().uri("/api/batch/stream").set_payload(body).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+
+        let body = read_body(resp).await;
+        let lines: Vec<serde_json::Value> = body
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["id"], 1);
+        assert_eq!(lines[0]["result"]["success"], true);
+        assert_eq!(
+            lines[0]["result"]["checksum"],
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(lines[1]["index"], 1);
+        assert_eq!(lines[1]["op"], "read");
+        assert_eq!(lines[1]["result"]["content"], "hello");
+        // Paths are validated per line, as the middleware would for a single request
+        assert_eq!(lines[2]["status"], 400);
+        assert_eq!(lines[2]["result"]["error_code"], "INVALID_PATH");
+        assert_eq!(lines[3]["status"], 400);
+
+        let entries: Vec<serde_json::Value> = std::fs::read_to_string(logger.path())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let operations: Vec<_> = entries.iter().map(|entry| entry["operation"].as_str()).collect();
+        assert_eq!(operations, [Some("write"), Some("read"), Some("read"), None]);
+        assert_eq!(entries[0]["endpoint"], "/api/batch/stream");
+        assert_eq!(entries[0]["params"]["content"], "hello");
+        assert_eq!(entries[2]["result"], "rejected");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
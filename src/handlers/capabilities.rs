@@ -0,0 +1,65 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+
+use crate::handlers::batch::MAX_BATCH_LINE_BYTES;
+use crate::handlers::file_ops::READ_STREAM_THRESHOLD;
+use crate::models::{
+    CapabilitiesResponse, CapabilityFeatures, CapabilityLimits, Config, EndpointInfo,
+};
+use crate::routes::version::{ApiVersion, SUPPORTED_API_VERSIONS};
+use crate::routes::API_ENDPOINTS;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Describes enabled endpoints, features and limits so SDKs can adapt to this daemon
+pub async fn capabilities(
+    config: web::Data<Arc<Config>>,
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    api_version: ApiVersion,
+) -> Result<HttpResponse> {
+    let exec_stats = pools.exec.stats();
+    let fs_stats = pools.fs.stats();
+    let write_limit = security.write_limit_bytes_per_sec();
+
+    Ok(HttpResponse::Ok().json(CapabilitiesResponse {
+        success: true,
+        service: "EXEX".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        api_version: api_version.0,
+        supported_api_versions: SUPPORTED_API_VERSIONS.to_vec(),
+        endpoints: API_ENDPOINTS
+            .iter()
+            .map(|e| EndpointInfo {
+                method: e.method.to_string(),
+                path: format!("/api/v{}{}", api_version.0, e.path),
+                deprecated: e.deprecation.is_some(),
+                sunset: e.deprecation.and_then(|d| d.sunset).map(str::to_string),
+            })
+            .collect(),
+        features: CapabilityFeatures {
+            csrf_protection: security.is_csrf_protection_enabled(),
+            credentials: config.server.allow_credentials.unwrap_or(true),
+            malware_scan: security.is_malware_scan_enabled(),
+            encryption_at_rest: security.is_encryption_enabled(),
+            path_cache: config.security.path_cache_ttl_secs.unwrap_or(5) > 0
+                && config.security.path_cache_capacity.unwrap_or(4096) > 0,
+            streaming_scan: true,
+            streaming_read: true,
+            ndjson_batch: true,
+            write_throttle: write_limit > 0,
+        },
+        limits: CapabilityLimits {
+            max_file_size_bytes: security.max_file_size_bytes(),
+            read_stream_threshold_bytes: READ_STREAM_THRESHOLD,
+            max_batch_line_bytes: MAX_BATCH_LINE_BYTES,
+            exec_workers: exec_stats.workers,
+            fs_workers: fs_stats.workers,
+            background_workers: pools.background.stats().workers,
+            worker_queue_limit: fs_stats.queue_limit,
+            disk_reserve_bytes: security.disk_reserve_bytes(),
+            write_bytes_per_sec: write_limit,
+            max_affected_entries: security.max_affected_entries().unwrap_or(0),
+        },
+    }))
+}
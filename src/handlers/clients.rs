@@ -0,0 +1,35 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+
+use crate::clients::{sanitize, ClientRegistry};
+use crate::models::{ClientInfo, ClientResponse, ClientsResponse};
+use crate::security::throttle::ClientId;
+
+/// Lists the clients that have called the API, most recently seen first
+pub async fn list_clients(registry: web::Data<Arc<ClientRegistry>>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(ClientsResponse {
+        success: true,
+        clients: registry.list(),
+    }))
+}
+
+/// Records the calling client's name, type and version
+pub async fn register_client(
+    registry: web::Data<Arc<ClientRegistry>>,
+    client: ClientId,
+    req: web::Json<ClientInfo>,
+) -> Result<HttpResponse> {
+    let info = sanitize(req.into_inner());
+    if info.name.is_none() {
+        return Ok(HttpResponse::BadRequest().json(ClientResponse {
+            success: false,
+            client: None,
+            error: Some("Client name is required".to_string()),
+        }));
+    }
+    Ok(HttpResponse::Ok().json(ClientResponse {
+        success: true,
+        client: registry.register(&client, info),
+        error: None,
+    }))
+}
@@ -0,0 +1,21 @@
+use actix_web::{cookie::{Cookie, SameSite}, HttpResponse, Result};
+
+use crate::models::CsrfTokenResponse;
+use crate::security::csrf::{generate_token, CSRF_COOKIE_NAME, CSRF_HEADER_NAME};
+
+/// Issues a CSRF token cookie for browser clients
+///
+/// The token is only delivered as a cookie; pages on other sites cannot read it,
+/// so only same-host frontends can echo it back in the CSRF header.
+pub async fn issue_csrf_token() -> Result<HttpResponse> {
+    let cookie = Cookie::build(CSRF_COOKIE_NAME, generate_token())
+        .path("/")
+        .same_site(SameSite::Strict)
+        .finish();
+
+    Ok(HttpResponse::Ok().cookie(cookie).json(CsrfTokenResponse {
+        success: true,
+        cookie_name: CSRF_COOKIE_NAME.to_string(),
+        header_name: CSRF_HEADER_NAME.to_string(),
+    }))
+}
@@ -0,0 +1,23 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Result};
+use std::sync::Arc;
+
+use crate::i18n::ErrorCatalog;
+use crate::models::ErrorCatalogResponse;
+
+/// Lists the message for every error code in the language negotiated from `Accept-Language`
+pub async fn error_catalog(
+    catalog: web::Data<Arc<ErrorCatalog>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let accept_language = req.headers().get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok());
+    let language = catalog.negotiate(accept_language);
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_LANGUAGE, language.clone()))
+        .json(ErrorCatalogResponse {
+            success: true,
+            messages: catalog.messages(&language),
+            languages: catalog.languages(),
+            language,
+        }))
+}
@@ -1,93 +1,804 @@
 use actix_web::{web, HttpResponse, Result};
-use std::path::PathBuf;
-use std::process::Command;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tracing::{info, error, warn};
 
-use crate::models::{ExecRequest, ExecResponse, ErrorResponse};
-use crate::security::SecurityManager;
+use crate::jobs::artifacts::{ArtifactPatterns, ArtifactStore};
+use crate::jobs::isolation::{is_relative_within, IsolatedWorkspace};
+use crate::jobs::{wait_logged, JobLog, JobLogs};
+use crate::content::strip_ansi;
+use crate::models::{
+    AnsiMode, ArtifactInfo, EnvOptions, ExecCwd, ExecEnvironmentQuery, ExecEnvironmentResponse, ExecOutcome, ExecRequest,
+    ExecResponse, ExecShell, ErrorResponse, OutputFormat, OutputRecord, ProcessKind, ProcessPriority, ShellKind,
+};
+use crate::handlers::policy::resolve_commands;
+use crate::platform::{
+    apply_env, apply_force_color, apply_priority, create_dir_all_with_mode, display_path, exec_command_for,
+    is_essential_env_var, resolve_command,
+};
+use crate::platform::process_tree::{ProcessTree, TreeKiller};
+use crate::power::PowerManager;
+use crate::processes::{exit, ProcessRegistry};
+use crate::security::redact::REDACTED;
+use crate::security::{command_name, SecurityManager};
+use crate::workers::{PoolError, WorkerPools};
 
 /// Handles command execution requests with enhanced security
 pub async fn exec_command(
     security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    power: web::Data<Arc<PowerManager>>,
+    logs: web::Data<Arc<JobLogs>>,
     req: web::Json<ExecRequest>,
 ) -> Result<HttpResponse> {
-    let command = req.command.clone();
-    let args = req.args.clone();
-    let cwd = req.cwd.clone();
-
-    // Check command whitelist/blacklist
-    if !security.is_command_allowed(&command) {
-        warn!("Command execution denied: {}", command);
-        return Ok(HttpResponse::Forbidden().json(ErrorResponse {
-            error: format!("Command '{}' is not allowed by security policy", command),
-        }));
-    }
-
-    // Validate working directory if provided
-    if let Some(ref cwd_str) = cwd {
-        let cwd_path = PathBuf::from(cwd_str);
-        if !security.is_path_allowed(&cwd_path) {
-            warn!("Working directory access denied: {}", cwd_str);
-            return Ok(HttpResponse::Forbidden().json(ErrorResponse {
-                error: format!("Access denied to directory: {}", cwd_str),
-            }));
-        }
-    }
-
-    info!("Executing command: '{}' with args: {:?} in {:?}", command, args, cwd);
-
-    // Execute command in a blocking thread
-    let result = web::block(move || {
-        let mut cmd = if let Some(ref command_args) = args {
-            // If args are provided separately, use them directly
-            let mut c = Command::new(&command);
-            c.args(command_args);
-            c
-        } else {
-            // Backward compatibility: if no args provided, use shell execution
-            if cfg!(target_os = "windows") {
-                let mut c = Command::new("cmd");
-                c.args(["/C", &command]);
-                c
-            } else {
-                let mut c = Command::new("sh");
-                c.args(["-c", &command]);
-                c
+    let prepared = match PreparedExec::prepare(&security, req.into_inner()) {
+        Ok(prepared) => prepared,
+        Err(refusal) => return Ok(refusal.into_response()),
+    };
+    let services = ExecServices::new(&pools, &registry, &power, &logs);
+
+    match prepared.run_attached(&services).await {
+        Ok(response) => {
+            if response.outcome != ExecOutcome::SpawnFailed {
+                info!("Command executed successfully with exit code: {:?}", response.exit_code);
+            }
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(e) => Ok(e.into_response()),
+    }
+}
+
+/// The shared services running a command needs
+#[derive(Clone)]
+pub(crate) struct ExecServices {
+    pools: Arc<WorkerPools>,
+    registry: Arc<ProcessRegistry>,
+    power: Arc<PowerManager>,
+    logs: Arc<JobLogs>,
+}
+
+impl ExecServices {
+    pub(crate) fn new(
+        pools: &Arc<WorkerPools>,
+        registry: &Arc<ProcessRegistry>,
+        power: &Arc<PowerManager>,
+        logs: &Arc<JobLogs>,
+    ) -> Self {
+        Self {
+            pools: pools.clone(),
+            registry: registry.clone(),
+            power: power.clone(),
+            logs: logs.clone(),
+        }
+    }
+}
+
+/// Why a command produced no [`ExecResponse`]
+pub(crate) enum ExecError {
+    /// Its output could not be collected
+    Wait(std::io::Error),
+    /// It was cancelled while it waited for a worker
+    Cancelled,
+    Pool(PoolError),
+}
+
+impl std::fmt::Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecError::Wait(e) => write!(f, "IO error executing command: {}", e),
+            ExecError::Cancelled => write!(f, "Command was cancelled before it started"),
+            ExecError::Pool(e) => write!(f, "Failed to execute command: {}", e),
+        }
+    }
+}
+
+impl ExecError {
+    pub(crate) fn into_response(self) -> HttpResponse {
+        let error = self.to_string();
+        match self {
+            ExecError::Pool(PoolError::Saturated(_)) => {
+                warn!("Command execution rejected: {}", error);
+                HttpResponse::ServiceUnavailable().json(ErrorResponse { error })
+            }
+            _ => {
+                error!("{}", error);
+                HttpResponse::InternalServerError().json(ErrorResponse { error })
             }
+        }
+    }
+}
+
+/// Why a command was refused before anything ran
+pub(crate) enum Refusal {
+    /// Not allowed by the security policy
+    Denied(String),
+    /// Malformed, such as `outputs` without `isolate_cwd`
+    Invalid(String),
+}
+
+impl Refusal {
+    pub(crate) fn into_response(self) -> HttpResponse {
+        match self {
+            Refusal::Denied(error) => HttpResponse::Forbidden().json(ErrorResponse { error }),
+            Refusal::Invalid(error) => HttpResponse::BadRequest().json(ErrorResponse { error }),
+        }
+    }
+
+    /// Names the job of a group that was refused
+    pub(crate) fn for_job(self, name: &str) -> Self {
+        match self {
+            Refusal::Denied(error) => Refusal::Denied(format!("Job '{}': {}", name, error)),
+            Refusal::Invalid(error) => Refusal::Invalid(format!("Job '{}': {}", name, error)),
+        }
+    }
+
+    /// Names the pipeline step that was refused, counting from 1
+    pub(crate) fn for_step(self, pipeline: &str, index: usize) -> Self {
+        match self {
+            Refusal::Denied(error) => Refusal::Denied(format!("Pipeline '{}' step {}: {}", pipeline, index + 1, error)),
+            Refusal::Invalid(error) => Refusal::Invalid(format!("Pipeline '{}' step {}: {}", pipeline, index + 1, error)),
+        }
+    }
+}
+
+/// How an `isolate_cwd` command is copied into its workspace and back
+struct Isolation {
+    cwd: PathBuf,
+    inputs: Option<Vec<PathBuf>>,
+    outputs: Vec<PathBuf>,
+    security: Arc<SecurityManager>,
+}
+
+impl Isolation {
+    fn create_workspace(&self) -> std::io::Result<IsolatedWorkspace> {
+        IsolatedWorkspace::create(&self.cwd, self.inputs.as_deref(), &|path| self.security.is_path_allowed(path))
+    }
+
+    /// Copies the declared outputs back to `cwd`, with the checks a write through `/api/write` gets
+    fn collect(&self, workspace: &IsolatedWorkspace) -> std::io::Result<Vec<String>> {
+        let allow = |path: &Path| {
+            self.security.is_path_allowed(path)
+                && !self.security.is_workspace_policy_path(path)
+                && self.security.check_protected(path, false).is_ok()
         };
+        let collected = workspace.collect(&self.cwd, &self.outputs, &allow)?;
+        Ok(collected.iter().map(|path| display_path(path)).collect())
+    }
+}
+
+/// Where a job's `artifacts` are collected to once it ends
+struct ArtifactCollection {
+    store: Arc<ArtifactStore>,
+    patterns: ArtifactPatterns,
+    job_id: String,
+    security: Arc<SecurityManager>,
+}
 
-        if let Some(cwd_str) = cwd {
-            cmd.current_dir(cwd_str);
+impl ArtifactCollection {
+    /// Collects the matches below `root`; files in an isolated workspace were already checked on the way in
+    fn collect(&self, root: &Path, isolated: bool) -> Vec<ArtifactInfo> {
+        let allow = |path: &Path| isolated || self.security.is_path_allowed(path);
+        match self.store.collect(&self.job_id, root, &self.patterns, &allow) {
+            Ok(collected) => collected,
+            Err(e) => {
+                warn!("Failed to collect artifacts of job {}: {}", self.job_id, e);
+                vec![]
+            }
         }
+    }
+}
+
+/// Parses declared `inputs` or `outputs`, which must stay below `cwd`
+fn relative_paths(paths: Vec<String>, field: &str) -> std::result::Result<Vec<PathBuf>, Refusal> {
+    paths
+        .into_iter()
+        .map(|path| match is_relative_within(Path::new(&path)) {
+            true => Ok(PathBuf::from(path)),
+            false => Err(Refusal::Invalid(format!("'{}' entries must be relative paths below cwd, got '{}'", field, path))),
+        })
+        .collect()
+}
+
+/// Replaces each of `secrets` in command output with a placeholder
+fn mask_secrets(output: &[u8], secrets: &[String]) -> Vec<u8> {
+    let mut text = String::from_utf8_lossy(output).into_owned();
+    for secret in secrets {
+        text = text.replace(secret.as_str(), REDACTED);
+    }
+    text.into_bytes()
+}
+
+/// An `/api/exec` request that passed the security policy, ready to run
+pub(crate) struct PreparedExec {
+    command: String,
+    redacted_command: String,
+    program: String,
+    args: Option<Vec<String>>,
+    redacted_args: Option<Vec<String>>,
+    shell: Option<ShellKind>,
+    login_shell: bool,
+    cwd: Option<String>,
+    create_cwd: bool,
+    dir_mode: u32,
+    environment: EnvOptions,
+    child_path: Option<OsString>,
+    priority: ProcessPriority,
+    timeout: Option<Duration>,
+    parse_output: OutputFormat,
+    ansi: AnsiMode,
+    isolation: Option<Isolation>,
+    artifacts: Option<ArtifactCollection>,
+    secrets: Vec<String>,
+    mutex: Option<String>,
+}
 
-        cmd.output()
-    })
-    .await;
+impl PreparedExec {
+    /// Checks a request against the security policy
+    pub(crate) fn prepare(security: &Arc<SecurityManager>, req: ExecRequest) -> std::result::Result<Self, Refusal> {
+        Self::prepare_checked(security, req, true)
+    }
+
+    /// Checks a command the operator configured, such as a pipeline step
+    ///
+    /// The command whitelist and blacklist do not apply, as for formatters;
+    /// every other check of [`PreparedExec::prepare`] does.
+    pub(crate) fn prepare_trusted(security: &Arc<SecurityManager>, req: ExecRequest) -> std::result::Result<Self, Refusal> {
+        Self::prepare_checked(security, req, false)
+    }
+
+    fn prepare_checked(
+        security: &Arc<SecurityManager>,
+        req: ExecRequest,
+        check_command: bool,
+    ) -> std::result::Result<Self, Refusal> {
+        let command = req.command;
+        let shell = req.shell;
+        let login_shell = req.login_shell.unwrap_or(false);
+        let (program, args) = match (shell, req.args) {
+            // Without a shell, the command line is split into the program and its arguments
+            (Some(ShellKind::None), None) => {
+                let mut words = command.split_whitespace();
+                let program = security.command_program(words.next().unwrap_or_default()).to_string();
+                (program, Some(words.map(str::to_string).collect::<Vec<_>>()))
+            }
+            (_, args) => (security.command_program(&command).to_string(), args),
+        };
+        let cwd = req.cwd;
+        let create_cwd = req.create_cwd.unwrap_or(false);
+        if req.mutex.as_deref().is_some_and(|key| key.trim().is_empty()) {
+            return Err(Refusal::Invalid("'mutex' must not be empty".to_string()));
+        }
+
+        // Secrets passed on the command line must never reach logs or error echoes
+        let redacted_command = security.redact(&command);
+
+        // Check command whitelist/blacklist, including those of the workspace the command runs in
+        if check_command && !security.is_command_allowed_in(&command, cwd.as_deref().map(Path::new)) {
+            warn!("Command execution denied: {}", redacted_command);
+            return Err(Refusal::Denied(format!("Command '{}' is not allowed by security policy", redacted_command)));
+        }
+
+        if let Err(e) = security.check_env(&req.environment) {
+            warn!("Command environment denied: {}", e);
+            return Err(Refusal::Denied(e));
+        }
+
+        if let Err(e) = security.check_shell(shell, login_shell) {
+            warn!("Command shell denied: {}", e);
+            return Err(Refusal::Denied(e));
+        }
+
+        // Validate working directory if provided
+        if let Some(ref cwd_str) = cwd {
+            let cwd_path = PathBuf::from(cwd_str);
+            // A created directory must be one the client could also create through /api/create
+            let creates = create_cwd && !cwd_path.is_dir();
+            if !security.is_path_allowed(&cwd_path) || (creates && security.is_workspace_policy_path(&cwd_path)) {
+                warn!("Working directory access denied: {}", cwd_str);
+                return Err(Refusal::Denied(format!("Access denied to directory: {}", cwd_str)));
+            }
+        }
 
-    match result {
-        Ok(Ok(output)) => {
-            let response = ExecResponse {
-                success: output.status.success(),
-                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                exit_code: output.status.code(),
+        let isolation = match (req.isolate_cwd.unwrap_or(false), &cwd) {
+            (false, _) if req.inputs.is_some() || req.outputs.is_some() => {
+                return Err(Refusal::Invalid("'inputs' and 'outputs' require 'isolate_cwd'".to_string()));
+            }
+            (false, _) => None,
+            (true, Some(cwd)) if Path::new(cwd).is_dir() => {
+                let outputs = relative_paths(req.outputs.unwrap_or_default(), "outputs")?;
+                for output in &outputs {
+                    let target = Path::new(cwd).join(output);
+                    if !security.is_path_allowed(&target) || security.is_workspace_policy_path(&target) {
+                        warn!("Isolated output access denied: {}", target.display());
+                        return Err(Refusal::Denied(format!("Access denied to output: {}", display_path(&target))));
+                    }
+                }
+                Some(Isolation {
+                    cwd: PathBuf::from(cwd),
+                    inputs: req.inputs.map(|inputs| relative_paths(inputs, "inputs")).transpose()?,
+                    outputs,
+                    security: security.clone(),
+                })
+            }
+            (true, _) => return Err(Refusal::Invalid("'isolate_cwd' requires an existing 'cwd'".to_string())),
+        };
+
+        Ok(Self {
+            command,
+            redacted_command,
+            program,
+            redacted_args: args.as_deref().map(|a| security.redact_args(a)),
+            args,
+            shell,
+            login_shell,
+            cwd,
+            create_cwd,
+            dir_mode: security.dir_mode(),
+            environment: req.environment,
+            child_path: security.child_path().map(OsStr::to_owned),
+            priority: security.process_priority(req.priority),
+            timeout: security.exec_timeout(req.timeout_secs),
+            parse_output: req.parse_output.unwrap_or_default(),
+            ansi: req.ansi.unwrap_or_default(),
+            isolation,
+            artifacts: None,
+            secrets: Vec::new(),
+            mutex: req.mutex,
+        })
+    }
+
+    /// Masks secret values the command was given wherever its output repeats them
+    ///
+    /// The output is not written to a job log, since that can be read back later.
+    pub(crate) fn masking(mut self, secrets: Vec<String>) -> Self {
+        self.secrets = secrets.into_iter().filter(|secret| !secret.is_empty()).collect();
+        self
+    }
+
+    /// Collects files matching `patterns` into `store` under `job_id` once the command ends
+    pub(crate) fn with_artifacts(
+        mut self,
+        security: &Arc<SecurityManager>,
+        store: Arc<ArtifactStore>,
+        patterns: ArtifactPatterns,
+        job_id: &str,
+    ) -> Self {
+        self.artifacts = Some(ArtifactCollection {
+            store,
+            patterns,
+            job_id: job_id.to_string(),
+            security: security.clone(),
+        });
+        self
+    }
+
+    /// Redacted command line, for logs and listings
+    pub(crate) fn name(&self) -> &str {
+        &self.redacted_command
+    }
+
+    /// Runs the command for the current request, killing its process tree if the request is dropped
+    /// (server shutdown, reset HTTP/2 stream) before it finishes
+    pub(crate) async fn run_attached(self, services: &ExecServices) -> std::result::Result<ExecResponse, ExecError> {
+        let killer = Arc::new(TreeKiller::default());
+        let cancel_guard = KillOnDrop(Some(killer.clone()));
+        let result = self.run(services, killer, None, |_| {}).await;
+        cancel_guard.disarm();
+        result
+    }
+
+    /// Runs the command on the exec pool, logging its output to `log` or a new job log
+    ///
+    /// `killer` ends the command with everything it started, or keeps it from
+    /// starting if it is still waiting for a worker. `on_spawn` is told the PID.
+    pub(crate) async fn run<F>(
+        self,
+        services: &ExecServices,
+        killer: Arc<TreeKiller>,
+        log: Option<JobLog>,
+        on_spawn: F,
+    ) -> std::result::Result<ExecResponse, ExecError>
+    where
+        F: FnOnce(u32) + Send + 'static,
+    {
+        let Self {
+            command,
+            redacted_command,
+            program,
+            args,
+            redacted_args,
+            shell,
+            login_shell,
+            cwd,
+            create_cwd,
+            dir_mode,
+            environment,
+            child_path,
+            priority,
+            timeout,
+            parse_output,
+            ansi,
+            isolation,
+            artifacts,
+            secrets,
+            mutex,
+        } = self;
+
+        info!(
+            "Executing command: '{}' with args: {:?} in {:?} at {:?} priority",
+            redacted_command,
+            redacted_args,
+            cwd,
+            priority
+        );
+
+        // Held until the command ends, so commands sharing the key never overlap
+        let _serialized = match &mutex {
+            Some(key) => Some(match services.pools.mutexes.try_lock(key) {
+                Some(guard) => guard,
+                None => {
+                    info!("Command '{}' waits for mutex '{}'", redacted_command, key);
+                    services.pools.mutexes.lock(key).await
+                }
+            }),
+            None => None,
+        };
+
+        let name = command_name(&command);
+        let job_killer = killer.clone();
+        let job_registry = services.registry.clone();
+        let job_name = redacted_command.clone();
+        let job_power = services.power.clone();
+        let job_logs = services.logs.clone();
+
+        // Execute command on the dedicated exec pool so filesystem bursts cannot starve it
+        let job = services.pools.exec.run(move || {
+            if job_killer.was_killed() {
+                return Err(RunError::Cancelled);
+            }
+            let _awake = job_power.job_started();
+            // Without args, the command line runs through a shell for backward compatibility
+            let mut cmd = exec_command_for(shell, login_shell, &program, args.as_deref(), &command);
+
+            // An isolated command works on a copy, so it cannot change the real tree
+            let workspace = isolation
+                .as_ref()
+                .map(Isolation::create_workspace)
+                .transpose()
+                .map_err(|e| {
+                    RunError::Spawn(std::io::Error::new(e.kind(), format!("Failed to prepare isolated workspace: {}", e)))
+                })?;
+            if let Some(workspace) = &workspace {
+                cmd.current_dir(workspace.path());
+            } else if let Some(cwd_str) = &cwd {
+                if create_cwd && !Path::new(&cwd_str).is_dir() {
+                    info!("Creating working directory: {}", cwd_str);
+                    create_dir_all_with_mode(Path::new(&cwd_str), dir_mode).map_err(|e| {
+                        RunError::Spawn(std::io::Error::new(
+                            e.kind(),
+                            format!("Failed to create working directory {}: {}", cwd_str, e),
+                        ))
+                    })?;
+                }
+                cmd.current_dir(cwd_str);
+            }
+            apply_env(&mut cmd, &environment);
+            if let Some(path) = &child_path {
+                cmd.env("PATH", path);
+            }
+            if ansi == AnsiMode::Force {
+                apply_force_color(&mut cmd, &environment);
+            }
+            apply_priority(&mut cmd, priority);
+            ProcessTree::prepare(&mut cmd);
+
+            let child = cmd
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(RunError::Spawn)?;
+            let pid = child.id();
+            match ProcessTree::attach(&child) {
+                Ok(tree) => job_killer.track(tree),
+                Err(e) => warn!("Descendants of PID {} cannot be tracked: {}", pid, e),
+            }
+            job_registry.started(ProcessKind::Exec, &job_name, pid, Some(job_killer.clone()));
+            on_spawn(pid);
+
+            // Output is logged as it arrives, so it can be read back after a dropped connection
+            let log = match log
+                .map(Ok)
+                .or_else(|| (job_logs.is_enabled() && secrets.is_empty()).then(|| job_logs.create()))
+            {
+                Some(Ok(log)) => Some(log),
+                Some(Err(e)) => {
+                    warn!("Output of '{}' will not be logged: {}", job_name, e);
+                    None
+                }
+                None => None,
             };
-            info!("Command executed successfully with exit code: {:?}", response.exit_code);
-            Ok(HttpResponse::Ok().json(response))
+            if let Some(log) = &log {
+                job_registry.set_job_id(pid, log.id());
+            }
+
+            let output = wait_logged(child, log.as_ref());
+            job_killer.release();
+            job_registry.exited(pid, output.as_ref().ok().map(|o| o.status));
+            let mut output = output.map_err(RunError::Wait)?;
+            if !secrets.is_empty() {
+                output.stdout = mask_secrets(&output.stdout, &secrets);
+                output.stderr = mask_secrets(&output.stderr, &secrets);
+            }
+            // Parsed here rather than on the runtime, since build tools can print megabytes of JSON
+            let records = (parse_output == OutputFormat::JsonLines)
+                .then(|| parse_json_lines(&String::from_utf8_lossy(&output.stdout)));
+            // Only declared outputs of a successful command come back
+            let outputs = match (&isolation, &workspace) {
+                (Some(isolation), Some(workspace)) if output.status.success() => {
+                    Some(isolation.collect(workspace).map_err(RunError::Wait)?)
+                }
+                (Some(_), _) => Some(vec![]),
+                _ => None,
+            };
+            // Artifacts are kept whether or not the command succeeded, since failed builds leave reports
+            let artifacts = artifacts.map(|artifacts| match (&workspace, &cwd) {
+                (Some(workspace), _) => artifacts.collect(workspace.path(), true),
+                (None, Some(cwd)) => artifacts.collect(Path::new(cwd), false),
+                (None, None) => artifacts.collect(&std::env::current_dir().unwrap_or_default(), false),
+            });
+            Ok((output, log.map(|log| log.id().to_string()), records, outputs, artifacts))
+        });
+
+        // A kill through /api/processes/kill is reported as `killed`, not as a timeout
+        let mut timed_out = false;
+        let result = match timeout {
+            Some(limit) => {
+                tokio::pin!(job);
+                match tokio::time::timeout(limit, &mut job).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        warn!("Command '{}' timed out after {:?}; killing its process tree", redacted_command, limit);
+                        timed_out = true;
+                        killer.kill();
+                        job.await
+                    }
+                }
+            }
+            None => job.await,
+        };
+
+        match result {
+            Ok(Ok((output, job_id, records, outputs, artifacts))) => {
+                let text = |bytes: &[u8]| {
+                    let text = String::from_utf8_lossy(bytes);
+                    match ansi {
+                        AnsiMode::Strip => strip_ansi(&text),
+                        AnsiMode::Preserve | AnsiMode::Force => text.into_owned(),
+                    }
+                };
+                let outcome = exit::classify(output.status, timed_out);
+                Ok(ExecResponse {
+                    success: outcome == ExecOutcome::Succeeded,
+                    outcome,
+                    stdout: text(&output.stdout),
+                    stderr: text(&output.stderr),
+                    exit_code: output.status.code(),
+                    signal: exit::exit_signal(output.status),
+                    reason: exit::describe_exit(&name, output.status).map(str::to_string),
+                    error: None,
+                    timed_out,
+                    job_id,
+                    records,
+                    outputs,
+                    artifacts,
+                })
+            }
+            Ok(Err(RunError::Spawn(spawn_error))) => {
+                warn!("Command '{}' could not be started: {}", redacted_command, spawn_error);
+                Ok(ExecResponse {
+                    success: false,
+                    outcome: ExecOutcome::SpawnFailed,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    exit_code: None,
+                    signal: None,
+                    reason: Some(exit::describe_spawn_error(&spawn_error).to_string()),
+                    error: Some(spawn_error.to_string()),
+                    timed_out: false,
+                    job_id: None,
+                    records: None,
+                    outputs: None,
+                    artifacts: None,
+                })
+            }
+            Ok(Err(RunError::Wait(io_error))) => Err(ExecError::Wait(io_error)),
+            Ok(Err(RunError::Cancelled)) => Err(ExecError::Cancelled),
+            Err(e) => Err(ExecError::Pool(e)),
+        }
+    }
+}
+
+/// Shows the context `/api/exec` would run a command in: PATH, working directory, shell and environment
+///
+/// Nothing is run. Environment values are masked like logged commands.
+pub async fn exec_environment(
+    security: web::Data<Arc<SecurityManager>>,
+    query: web::Query<ExecEnvironmentQuery>,
+) -> Result<HttpResponse> {
+    let query = query.into_inner();
+    let cwd = match &query.cwd {
+        Some(cwd) => {
+            let path = PathBuf::from(cwd);
+            ExecCwd {
+                path: cwd.clone(),
+                requested: true,
+                exists: path.is_dir(),
+                allowed: security.is_path_allowed(&path),
+            }
         }
-        Ok(Err(io_error)) => {
-            error!("IO error executing command: {}", io_error);
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("IO error executing command: {}", io_error),
-            }))
+        None => {
+            let path = std::env::current_dir().unwrap_or_default();
+            ExecCwd {
+                path: display_path(&path),
+                requested: false,
+                exists: path.is_dir(),
+                allowed: security.is_path_allowed(&path),
+            }
         }
-        Err(e) => {
-            error!("Failed to execute command: {}", e);
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Failed to execute command: {}", e),
-            }))
+    };
+
+    let (command, command_allowed) = match &query.command {
+        Some(command) => {
+            let name = command_name(command);
+            let program = security.command_program(&name).to_string();
+            let allowed = security.is_command_allowed_in(command, query.cwd.as_deref().map(Path::new));
+            let search_path = security.child_path().map(OsStr::to_owned);
+            (resolve_commands(vec![(program, name)], false, search_path).pop(), Some(allowed))
         }
+        None => (None, None),
+    };
+
+    let shell = if cfg!(windows) { "cmd" } else { "sh" };
+    let clean_env = query.clean_env.unwrap_or(false);
+    let child_path = security.child_path();
+    let env = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.to_string_lossy().to_string())))
+        .filter(|(name, _)| !clean_env || is_essential_env_var(name))
+        .map(|(name, value)| match child_path {
+            Some(path) if name.eq_ignore_ascii_case("PATH") => (name, path.to_string_lossy().to_string()),
+            _ => (name, value),
+        })
+        .map(|(name, value)| {
+            let masked = security.redact(&format!("{}={}", name, value));
+            let value = masked.strip_prefix(&format!("{}=", name)).map_or_else(|| REDACTED.to_string(), str::to_string);
+            (name, value)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ExecEnvironmentResponse {
+        success: true,
+        command,
+        command_allowed,
+        cwd,
+        shell: ExecShell {
+            program: format!("{} {}", shell, if cfg!(windows) { "/C" } else { "-c" }),
+            path: resolve_command(shell).as_deref().map(display_path),
+        },
+        path: child_path
+            .map(OsStr::to_owned)
+            .or_else(|| std::env::var_os("PATH"))
+            .map(|path| std::env::split_paths(&path).map(|dir| display_path(&dir)).collect())
+            .unwrap_or_default(),
+        env,
+        blocked_env: security.blocked_env(),
+        default_timeout_secs: security.exec_timeout(None).map(|timeout| timeout.as_secs()),
+        default_priority: security.process_priority(None),
+    }))
+}
+
+/// Where running a command failed
+enum RunError {
+    /// The command could not be started
+    Spawn(std::io::Error),
+    /// Its output could not be collected
+    Wait(std::io::Error),
+    /// It was cancelled while it waited for a worker
+    Cancelled,
+}
+
+/// Parses each non-empty line of a command's output as JSON, flagging the lines that are not
+fn parse_json_lines(stdout: &str) -> Vec<OutputRecord> {
+    stdout
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| match serde_json::from_str(line) {
+            Ok(value) => OutputRecord {
+                line: index + 1,
+                value: Some(value),
+                text: None,
+                error: None,
+            },
+            Err(e) => OutputRecord {
+                line: index + 1,
+                value: None,
+                text: Some(line.to_string()),
+                error: Some(e.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// Kills a command's process tree if the request is dropped before the command finishes
+struct KillOnDrop(Option<Arc<TreeKiller>>);
+
+impl KillOnDrop {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        if let Some(killer) = self.0.take() {
+            warn!("Command request was cancelled; killing its process tree");
+            killer.kill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_lines() {
+        let records = parse_json_lines("{\"reason\":\"compiler-artifact\"}\r\n\n   Compiling app\n[1, 2]\n");
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].line, 1);
+        assert_eq!(records[0].value.as_ref().unwrap()["reason"], "compiler-artifact");
+        assert_eq!(records[1].line, 3);
+        assert!(records[1].value.is_none());
+        assert_eq!(records[1].text.as_deref(), Some("   Compiling app"));
+        assert!(records[1].error.is_some());
+        assert_eq!(records[2].value, Some(serde_json::json!([1, 2])));
+        assert!(parse_json_lines("").is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_exec_environment() {
+        use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+        use actix_web::App;
+
+        let security = Arc::new(SecurityManager::new(crate::config::get_default_config()));
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(security))
+                .route("/exec/environment", web::get().to(exec_environment)),
+        )
+        .await;
+        let snapshot = |uri: &'static str| TestRequest::get().uri(uri).to_request();
+
+        let body: serde_json::Value = read_body_json(call_service(&app, snapshot("/exec/environment?command=echo")).await).await;
+        assert_eq!(body["command"]["name"], "echo");
+        assert_eq!(body["command_allowed"], true);
+        assert_eq!(body["cwd"]["requested"], false);
+        assert!(body["blocked_env"].as_array().unwrap().iter().any(|name| name == "LD_*"));
+        if cfg!(unix) {
+            assert!(body["shell"]["path"].is_string());
+        }
+
+        let body: serde_json::Value =
+            read_body_json(call_service(&app, snapshot("/exec/environment?command=not-a-tool&cwd=/no/such/dir&clean_env=true")).await).await;
+        assert_eq!(body["command_allowed"], false);
+        assert!(body["command"]["path"].is_null());
+        assert_eq!(body["cwd"]["exists"], false);
+        assert!(body["env"].as_object().unwrap().keys().all(|name| is_essential_env_var(name)));
     }
 }
@@ -1,243 +1,1880 @@
-use actix_web::{web, HttpResponse, Result};
-use std::path::PathBuf;
+use actix_web::http::header::{self, ContentDisposition, ContentType, HttpDate, LastModified, Range};
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
+use globset::GlobSet;
+use std::io::{Read, Seek};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
 use tokio::fs;
-use tracing::{info, error};
+use tracing::{info, error, warn};
 
 use crate::models::{
-    ReadRequest, WriteRequest, ReadResponse, WriteResponse,
-    ScanRequest, ScanResponse, DeleteRequest, DeleteResponse,
-    CreateRequest, CreateResponse, RenameRequest, RenameResponse,
-    FileInfo
+    ContentEncoding, DownloadQuery, FileKind, ReadRequest, WriteRequest, WriteMode, ReadResponse, WriteResponse,
+    ScanRequest, ScanResponse, ScanSortKey, FindRequest, DeleteRequest, DeleteResponse,
+    CreateRequest, CreateResponse, RenameRequest, RenameResponse, CopyRequest, CopyResponse, EditRequest, EditResponse, LineEdit,
+    FileInfo, DirectoryStats, Paginated, ErrorCode, SpecialFileType
 };
-use crate::security::SecurityManager;
+use crate::content::{classify, kind_from_extension, mime_type, sniff_file, validate_syntax, SNIFF_LEN};
+use crate::content::edit::{apply_line_edits, plan_edits, EditOutcome};
+use crate::handlers::operations::start_failure_status;
+use crate::handlers::pagination::{paginate, paginate_at};
+use crate::handlers::stream::{escape_json_fragment, ChunkWriter, STREAM_CHUNK_SIZE};
+use crate::platform::owner::{OwnerLookup, Ownership};
+use crate::platform::{
+    available_space, create_dir_all_with_mode, display_path, entry_attributes, extended_path, listed_attributes,
+    is_sparse, open_for_read, read_bytes, set_mode, special_file_at, special_file_type, write_with_mode, EntryAttributes,
+    Placement,
+};
+use crate::operations::{cancellation_code, is_cancellation, CancelToken, OperationRegistry};
+use crate::security::{parse_mode, SecurityManager};
+use crate::security::malware::ScanVerdict;
+use crate::security::throttle::ClientId;
+use crate::security::ignore::{GitIgnores, IgnoreMatcher};
+use crate::security::workspace::build_glob_set;
+use crate::routes::version::ApiVersion;
+use crate::workers::WorkerPools;
+
+/// Files larger than this are streamed to the client instead of buffered
+pub const READ_STREAM_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// Reasons content can be refused by the malware scan hook
+enum ScanRejection {
+    Infected(String),
+    Failed(String),
+}
+
+/// Runs blocking filesystem work on the filesystem pool
+///
+/// Pool saturation surfaces through the handlers' regular IO error paths.
+pub(crate) async fn run_fs<F, R>(pools: &WorkerPools, job: F) -> io::Result<R>
+where
+    F: FnOnce() -> io::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    pools.fs.run(job).await.map_err(io::Error::other)?
+}
+
+/// Checks that `incoming` bytes fit on the volume holding `path` while leaving the configured reserve
+///
+/// Space held by a file that is about to be replaced counts as free. If free space
+/// cannot be determined the write goes ahead and reports any real IO error itself.
+pub(crate) async fn check_disk_space(
+    security: &SecurityManager,
+    pools: &WorkerPools,
+    path: &Path,
+    incoming: u64,
+) -> Result<(), String> {
+    let reserve = security.disk_reserve_bytes();
+    let target = path.to_path_buf();
+    let probe = run_fs(pools, move || {
+        let available = available_space(&target)?;
+        let replaced = std::fs::metadata(&target)
+            .map(|m| if m.is_file() { m.len() } else { 0 })
+            .unwrap_or(0);
+        Ok(available.saturating_add(replaced))
+    })
+    .await;
+
+    match probe {
+        Ok(available) if incoming.saturating_add(reserve) > available => Err(format!(
+            "Insufficient disk space: {} bytes needed plus a {} byte reserve, {} bytes available",
+            incoming, reserve, available
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            warn!("Could not determine free space for {}: {}", display_path(path), e);
+            Ok(())
+        }
+    }
+}
+
+/// Refuses recursive operations under `root` that would touch more than `security.max_affected_entries`
+///
+/// Counting stops as soon as the limit is exceeded, so checking a huge tree stays cheap.
+async fn check_affected_entries(
+    security: &SecurityManager,
+    pools: &WorkerPools,
+    root: &Path,
+) -> Result<(), String> {
+    let Some(limit) = security.max_affected_entries() else {
+        return Ok(());
+    };
+
+    let walk_root = root.to_path_buf();
+    let counted = run_fs(pools, move || count_entries(&walk_root, limit + 1)).await;
+    match counted {
+        Ok(count) if count > limit => Err(format!(
+            "Operation would affect more than {} entries; set override_entry_limit to proceed",
+            limit
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            // The operation itself will surface the underlying IO error
+            warn!("Could not count entries under {}: {}", display_path(root), e);
+            Ok(())
+        }
+    }
+}
+
+/// Counts entries below `root`, without following symlinks, stopping once `stop_at` is reached
+pub(crate) fn count_entries(root: &Path, stop_at: usize) -> io::Result<usize> {
+    let mut count = 0;
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            count += 1;
+            if count >= stop_at {
+                return Ok(count);
+            }
+            if entry.file_type()?.is_dir() {
+                stack.push(extended_path(&entry.path()));
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// Delays a write until it fits within the client's throughput limit
+pub(crate) async fn throttle_write(security: &SecurityManager, client: &ClientId, path: &str, bytes: u64) {
+    let delay = security.throttle_write(client, bytes);
+    if !delay.is_zero() {
+        info!("Throttling write to {} by {} ms for client {}", path, delay.as_millis(), client.0);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Runs content through the configured malware scanner on the filesystem pool
+async fn scan_for_malware(
+    security: &Arc<SecurityManager>,
+    pools: &WorkerPools,
+    path: &Path,
+    data: Vec<u8>,
+) -> Result<(), ScanRejection> {
+    if !security.is_malware_scan_enabled() {
+        return Ok(());
+    }
+
+    let scanner = security.clone();
+    let scan_path = path.to_path_buf();
+    match pools.fs.run(move || scanner.scan_content(&scan_path, &data)).await {
+        Ok(Ok(ScanVerdict::Clean)) => Ok(()),
+        Ok(Ok(ScanVerdict::Infected(signature))) => Err(ScanRejection::Infected(signature)),
+        Ok(Err(e)) => Err(ScanRejection::Failed(e)),
+        Err(e) => Err(ScanRejection::Failed(e.to_string())),
+    }
+}
 
 /// Handles file reading requests
+///
+/// Text is returned as UTF-8. Binary, image and archive files are refused unless
+/// `base64` encoding is requested, rather than failing midway through decoding.
 pub async fn read_file(
     security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
     req: web::Json<ReadRequest>,
 ) -> Result<HttpResponse> {
-    let path = PathBuf::from(&req.path);
+    let path = extended_path(Path::new(&req.path));
 
     if !security.is_path_allowed(&path) {
-        return Ok(HttpResponse::Forbidden().json(ReadResponse {
-            success: false,
-            content: None,
-            error: Some(format!("Access denied to file: {}", req.path)),
+        return Ok(HttpResponse::Forbidden().json(read_failure(format!("Access denied to file: {}", req.path))));
+    }
+
+    if let Some(special) = special_file_at(&path) {
+        warn!("Refusing to read {}: it is a {}", req.path, special);
+        return Ok(HttpResponse::BadRequest().json(ReadResponse {
+            error_code: Some(ErrorCode::SpecialFile),
+            ..read_failure(special_file_error(&req.path, special))
         }));
     }
 
     info!("Reading file: {}", req.path);
 
-    match fs::read_to_string(&path).await {
+    let encoding = req.encoding.unwrap_or_default();
+
+    // Large plaintext files are streamed so memory stays bounded by the chunk size
+    if let Ok(metadata) = fs::metadata(&path).await
+        && metadata.is_file()
+        && metadata.len() > READ_STREAM_THRESHOLD
+        && !security.is_encrypted_path(&path)
+    {
+        return Ok(stream_file_content(&pools, path, req.path.clone(), encoding).await);
+    }
+
+    let read_path = path.clone();
+    let bytes = match run_fs(&pools, move || read_bytes(&read_path)).await {
+        Ok(bytes) => security
+            .decrypt_for_path(&path, bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        Err(e) => Err(e),
+    };
+    let bytes = match bytes {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to read file {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(read_failure(format!("Failed to read file: {}", e))));
+        }
+    };
+
+    let kind = classify(&bytes[..bytes.len().min(SNIFF_LEN)]);
+    if kind != FileKind::Text && encoding != ContentEncoding::Base64 {
+        return Ok(binary_refusal(&req.path, kind));
+    }
+
+    let content = match encoding {
+        ContentEncoding::Base64 => Ok(BASE64.encode(&bytes)),
+        ContentEncoding::Utf8 => String::from_utf8(bytes).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+        }),
+    };
+
+    match content {
         Ok(content) => {
             info!("Successfully read file: {} ({} bytes)", req.path, content.len());
             Ok(HttpResponse::Ok().json(ReadResponse {
                 success: true,
                 content: Some(content),
+                kind: Some(kind),
+                encoding: Some(encoding),
                 error: None,
+                error_code: None,
             }))
         }
         Err(e) => {
             error!("Failed to read file {}: {}", req.path, e);
             Ok(HttpResponse::Ok().json(ReadResponse {
-                success: false,
-                content: None,
-                error: Some(format!("Failed to read file: {}", e)),
+                kind: Some(kind),
+                ..read_failure(format!("Failed to read file: {}", e))
             }))
         }
     }
 }
 
+fn read_failure(error: String) -> ReadResponse {
+    ReadResponse {
+        success: false,
+        content: None,
+        kind: None,
+        encoding: None,
+        error: Some(error),
+        error_code: None,
+    }
+}
+
+fn write_failure(error: String, error_code: Option<ErrorCode>) -> WriteResponse {
+    WriteResponse {
+        success: false,
+        error: Some(error),
+        error_code,
+        checksum: None,
+        syntax_error: None,
+        formatted_content: None,
+        format_error: None,
+    }
+}
+
+/// Explains why a FIFO, socket or device node is not opened; reading a FIFO would block indefinitely
+pub(crate) fn special_file_error(requested_path: &str, special: SpecialFileType) -> String {
+    format!("Not a regular file: {} is a {}", requested_path, special)
+}
+
+/// Refuses to decode non-text content as UTF-8
+fn binary_refusal(requested_path: &str, kind: FileKind) -> HttpResponse {
+    warn!("Refusing to read {} file as text: {}", kind, requested_path);
+    HttpResponse::UnsupportedMediaType().json(ReadResponse {
+        kind: Some(kind),
+        error_code: Some(ErrorCode::BinaryContent),
+        ..read_failure(format!(
+            "File is not text ({}); request \"encoding\": \"base64\" to read it",
+            kind
+        ))
+    })
+}
+
+/// Streams a file as a `ReadResponse` JSON document from the filesystem pool
+async fn stream_file_content(
+    pools: &Arc<WorkerPools>,
+    path: PathBuf,
+    requested_path: String,
+    encoding: ContentEncoding,
+) -> HttpResponse {
+    // Classify and, for text, validate up front so failures still get a regular error response
+    let validate_path = path.clone();
+    let checked = run_fs(pools, move || {
+        let kind = sniff_file(&validate_path)?;
+        if kind == FileKind::Text && encoding == ContentEncoding::Utf8 {
+            for_each_utf8_chunk(&validate_path, None)?;
+        }
+        Ok(kind)
+    })
+    .await;
+
+    let kind = match checked {
+        Ok(kind) if kind != FileKind::Text && encoding != ContentEncoding::Base64 => {
+            return binary_refusal(&requested_path, kind);
+        }
+        Ok(kind) => kind,
+        Err(e) => {
+            error!("Failed to read file {}: {}", requested_path, e);
+            return HttpResponse::Ok().json(read_failure(format!("Failed to read file: {}", e)));
+        }
+    };
+
+    let trailer = format!(
+        r#"","kind":"{}","encoding":"{}","error":null,"error_code":null}}"#,
+        kind, encoding
+    );
+
+    let (mut writer, body) = ChunkWriter::new();
+    let spawned = pools.fs.spawn(move || {
+        let result = writer
+            .write(br#"{"success":true,"content":""#)
+            .and_then(|_| match encoding {
+                ContentEncoding::Utf8 => for_each_utf8_chunk(&path, Some(&mut writer)),
+                ContentEncoding::Base64 => for_each_base64_chunk(&path, &mut writer),
+            })
+            .and_then(|size| writer.write(trailer.as_bytes()).map(|_| size));
+
+        match result {
+            Ok(size) => {
+                info!("Successfully streamed file: {} ({} bytes)", requested_path, size);
+                let _ = writer.finish();
+            }
+            Err(e) => {
+                error!("Failed to stream file {}: {}", requested_path, e);
+                writer.abort(e);
+            }
+        }
+    });
+
+    match spawned {
+        Ok(()) => HttpResponse::Ok().content_type(ContentType::json()).body(body),
+        Err(e) => HttpResponse::Ok().json(read_failure(format!("Failed to read file: {}", e))),
+    }
+}
+
+/// Reads a file chunk by chunk and writes it base64-encoded
+fn for_each_base64_chunk(path: &Path, writer: &mut ChunkWriter) -> io::Result<u64> {
+    let mut file = open_for_read(path)?;
+    let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
+    let mut pending = Vec::with_capacity(STREAM_CHUNK_SIZE + 2);
+    let mut total = 0u64;
+
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        total += n as u64;
+        pending.extend_from_slice(&buf[..n]);
+
+        // Only whole 3-byte groups can be encoded without padding mid-stream
+        let whole = pending.len() - pending.len() % 3;
+        writer.write(BASE64.encode(&pending[..whole]).as_bytes())?;
+        pending.drain(..whole);
+    }
+
+    writer.write(BASE64.encode(&pending).as_bytes())?;
+    Ok(total)
+}
+
+/// Reads a file chunk by chunk, checking it is UTF-8 and optionally writing it as an escaped JSON string
+fn for_each_utf8_chunk(path: &Path, mut writer: Option<&mut ChunkWriter>) -> io::Result<u64> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8");
+
+    let mut file = open_for_read(path)?;
+    let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
+    let mut pending = Vec::with_capacity(STREAM_CHUNK_SIZE + 4);
+    let mut total = 0u64;
+
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        total += n as u64;
+        pending.extend_from_slice(&buf[..n]);
+
+        // A multi-byte character may straddle the chunk boundary
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(invalid()),
+        };
+
+        if let Some(writer) = writer.as_deref_mut() {
+            let text = std::str::from_utf8(&pending[..valid]).map_err(|_| invalid())?;
+            writer.write(escape_json_fragment(text).as_bytes())?;
+        }
+        pending.drain(..valid);
+    }
+
+    if pending.is_empty() {
+        Ok(total)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Streams a file's raw bytes, honouring a single-range `Range` header
+///
+/// Unlike `/read`, content is neither JSON-encoded nor held in memory, so
+/// large artifacts download at disk speed and interrupted downloads can
+/// resume. Files encrypted at rest are the exception: they are decrypted whole.
+pub async fn download_file(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: HttpRequest,
+    query: web::Query<DownloadQuery>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&query.path));
+
+    if !security.is_path_allowed(&path) {
+        return Ok(HttpResponse::Forbidden().json(read_failure(format!("Access denied to file: {}", query.path))));
+    }
+
+    if let Some(special) = special_file_at(&path) {
+        warn!("Refusing to download {}: it is a {}", query.path, special);
+        return Ok(HttpResponse::BadRequest().json(ReadResponse {
+            error_code: Some(ErrorCode::SpecialFile),
+            ..read_failure(special_file_error(&query.path, special))
+        }));
+    }
+
+    // Error bodies must not pass for file content, so failures get real error statuses here
+    let metadata = match fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => {
+            return Ok(HttpResponse::BadRequest().json(read_failure(format!("Not a file: {}", query.path))));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(HttpResponse::NotFound().json(read_failure(format!("File not found: {}", query.path))));
+        }
+        Err(e) => {
+            error!("Failed to download file {}: {}", query.path, e);
+            return Ok(HttpResponse::InternalServerError().json(read_failure(format!("Failed to read file: {}", e))));
+        }
+    };
+
+    let decrypted = if security.is_encrypted_path(&path) {
+        let read_path = path.clone();
+        let bytes = match run_fs(&pools, move || read_bytes(&read_path)).await {
+            Ok(bytes) => security.decrypt_for_path(&path, bytes).map_err(io::Error::other),
+            Err(e) => Err(e),
+        };
+        match bytes {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                error!("Failed to download file {}: {}", query.path, e);
+                return Ok(HttpResponse::InternalServerError().json(read_failure(format!("Failed to read file: {}", e))));
+            }
+        }
+    } else {
+        None
+    };
+    Ok(send_file(&pools, &req, path, &metadata, decrypted, &query.path).await)
+}
+
+/// Streams a file the caller has already authorized, honouring a single-range `Range` header
+///
+/// `decrypted` replaces the content of files encrypted at rest; `label` names the file in logs.
+pub(crate) async fn send_file(
+    pools: &WorkerPools,
+    req: &HttpRequest,
+    path: PathBuf,
+    metadata: &Metadata,
+    decrypted: Option<Vec<u8>>,
+    label: &str,
+) -> HttpResponse {
+    let kind = match &decrypted {
+        Some(bytes) => classify(&bytes[..bytes.len().min(SNIFF_LEN)]),
+        None => {
+            let sniff_path = path.clone();
+            run_fs(pools, move || sniff_file(&sniff_path)).await.unwrap_or(FileKind::Binary)
+        }
+    };
+    let len = decrypted.as_ref().map_or(metadata.len(), |bytes| bytes.len() as u64);
+    let last_modified = metadata.modified().ok().map(HttpDate::from);
+
+    let range = match requested_range(req, len, last_modified) {
+        Ok(range) => range,
+        Err(()) => {
+            return HttpResponse::RangeNotSatisfiable()
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{}", len)))
+                .json(read_failure(format!("Requested range is outside the file's {} bytes", len)));
+        }
+    };
+    let (start, count) = range.map_or((0, len), |(first, last)| (first, last - first + 1));
+
+    let mut response = if range.is_some() { HttpResponse::PartialContent() } else { HttpResponse::Ok() };
+    response
+        .content_type(mime_type(&path, kind))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header(ContentDisposition::attachment(
+            path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+        ))
+        // Downloads share the daemon's origin, so HTML and SVG must never run as pages
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox"));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(last_modified));
+    }
+    if let Some((first, last)) = range {
+        response.insert_header((header::CONTENT_RANGE, format!("bytes {}-{}/{}", first, last, len)));
+    }
+    info!("Downloading file: {} ({} of {} bytes from {})", label, count, len, start);
+
+    if let Some(bytes) = decrypted {
+        let (start, end) = (start as usize, (start + count) as usize);
+        return response.body(bytes[start..end].to_vec());
+    }
+
+    let (mut writer, body) = ChunkWriter::new();
+    let requested_path = label.to_string();
+    let spawned = pools.fs.spawn(move || {
+        let result = (|| {
+            let mut file = open_for_read(&path)?;
+            file.seek(io::SeekFrom::Start(start))?;
+            let mut file = file.take(count);
+            let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
+            loop {
+                let n = file.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(());
+                }
+                writer.write(&buf[..n])?;
+            }
+        })();
+        match result {
+            Ok(()) => {
+                let _ = writer.finish();
+            }
+            Err(e) => {
+                error!("Failed to stream file {}: {}", requested_path, e);
+                writer.abort(e);
+            }
+        }
+    });
+
+    match spawned {
+        Ok(()) => response.no_chunking(count).body(body),
+        Err(e) => HttpResponse::ServiceUnavailable().json(read_failure(format!("Failed to read file: {}", e))),
+    }
+}
+
+/// The byte range a request asks for, inclusive, or None for the whole file
+///
+/// Only the first range of a multi-range request is served. Malformed
+/// headers, and `If-Range` validators that no longer match, get the whole
+/// file; a range that starts past the end is an error.
+fn requested_range(req: &HttpRequest, len: u64, last_modified: Option<HttpDate>) -> Result<Option<(u64, u64)>, ()> {
+    let Some(Ok(Range::Bytes(specs))) = req
+        .headers()
+        .get(header::RANGE)
+        .map(|value| value.to_str().unwrap_or_default().parse::<Range>())
+    else {
+        return Ok(None);
+    };
+    if let Some(validator) = req.headers().get(header::IF_RANGE)
+        && last_modified.map(|date| date.to_string()).as_deref() != validator.to_str().ok()
+    {
+        return Ok(None);
+    }
+    match specs.first() {
+        Some(spec) => spec.to_satisfiable_range(len).map(Some).ok_or(()),
+        None => Ok(None),
+    }
+}
+
 /// Handles file writing requests with size validation
 pub async fn write_file(
     security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    client: ClientId,
     req: web::Json<WriteRequest>,
 ) -> Result<HttpResponse> {
-    let path = PathBuf::from(&req.path);
+    let path = extended_path(Path::new(&req.path));
 
-    if !security.is_path_allowed(&path) {
-        return Ok(HttpResponse::Forbidden().json(WriteResponse {
-            success: false,
-            error: Some(format!("Access denied to file: {}", req.path)),
-        }));
+    if !security.is_path_allowed(&path) || security.is_workspace_policy_path(&path) {
+        return Ok(HttpResponse::Forbidden().json(write_failure(format!("Access denied to file: {}", req.path), None)));
     }
 
-    // Check file size limit
-    if !security.is_file_size_allowed(req.content.len() as u64) {
-        return Ok(HttpResponse::Forbidden().json(WriteResponse {
-            success: false,
-            error: Some(format!("File size exceeds maximum allowed size")),
-        }));
+    if let Err(e) = security.check_protected(&path, req.force.unwrap_or(false)) {
+        warn!("Write denied for {}: {}", req.path, e);
+        return Ok(HttpResponse::Forbidden().json(write_failure(e, Some(ErrorCode::ProtectedPath))));
     }
 
-    // Sanitize content
-    let sanitized_content = security.sanitize_content(&req.content);
+    if let Some(special) = special_file_at(&path) {
+        warn!("Refusing to write {}: it is a {}", req.path, special);
+        let error = special_file_error(&req.path, special);
+        return Ok(HttpResponse::BadRequest().json(write_failure(error, Some(ErrorCode::SpecialFile))));
+    }
 
-    info!("Writing to file: {} ({} bytes)", req.path, sanitized_content.len());
+    let placement = match (req.mode.unwrap_or_default(), req.offset) {
+        (WriteMode::Overwrite, None) => Placement::Replace,
+        (WriteMode::Overwrite, Some(offset)) => Placement::At(offset),
+        (WriteMode::Append, None) => Placement::Append,
+        (WriteMode::CreateNew, None) => Placement::CreateNew,
+        (_, Some(_)) => {
+            let error = "'offset' only applies to the overwrite mode".to_string();
+            return Ok(HttpResponse::BadRequest().json(write_failure(error, None)));
+        }
+    };
+    // Appends and patches leave the rest of the file as it is, so there is no whole file to validate or format
+    let partial = matches!(placement, Placement::Append | Placement::At(_));
+    if partial && req.validate.unwrap_or(false) {
+        let error = "'validate' checks whole files and cannot be combined with append or offset".to_string();
+        return Ok(HttpResponse::BadRequest().json(write_failure(error, None)));
+    }
 
-    // Create parent directories if they don't exist
-    if let Some(parent) = path.parent() {
-        if let Err(e) = fs::create_dir_all(parent).await {
-            error!("Failed to create directories for {}: {}", req.path, e);
-            return Ok(HttpResponse::Ok().json(WriteResponse {
-                success: false,
-                error: Some(format!("Failed to create directories: {}", e)),
-            }));
+    let requested_mode = match req.permissions.as_deref().map(parse_mode).transpose() {
+        Ok(mode) => mode,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(write_failure(e, None)));
+        }
+    };
+    let mode = match security.creation_mode(requested_mode, false) {
+        Ok(mode) => mode,
+        Err(e) => {
+            warn!("Write denied for {}: {}", req.path, e);
+            return Ok(HttpResponse::Forbidden().json(write_failure(e, None)));
         }
+    };
+
+    let decoded = match req.encoding.unwrap_or_default() {
+        ContentEncoding::Utf8 => None,
+        ContentEncoding::Base64 => match BASE64.decode(req.content.trim()) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                let error = format!("Content is not valid base64: {}", e);
+                return Ok(HttpResponse::BadRequest().json(write_failure(error, None)));
+            }
+        },
+    };
+
+    // Check file size limit; the part of the file a partial write keeps counts toward it
+    let size = decoded.as_ref().map_or(req.content.len(), Vec::len) as u64;
+    let existing_len = match partial {
+        true => std::fs::metadata(&path).map_or(0, |metadata| metadata.len()),
+        false => 0,
+    };
+    if let Placement::At(offset) = placement
+        && offset > existing_len
+    {
+        let error = format!("Offset {} is past the end of {} ({} bytes)", offset, req.path, existing_len);
+        return Ok(HttpResponse::BadRequest().json(write_failure(error, None)));
+    }
+    let resulting_size = match placement {
+        Placement::Append => existing_len + size,
+        Placement::At(offset) => existing_len.max(offset + size),
+        Placement::Replace | Placement::CreateNew => size,
+    };
+    if !security.is_file_size_allowed(resulting_size) {
+        let error = "File size exceeds maximum allowed size".to_string();
+        return Ok(HttpResponse::Forbidden().json(write_failure(error, None)));
     }
 
-    match fs::write(&path, &sanitized_content).await {
-        Ok(_) => {
+    // Text is normalized, validated and formatted; base64 content is written byte for byte
+    let mut formatted_content = None;
+    let mut format_error = None;
+    let content = match decoded {
+        Some(bytes) => bytes,
+        None => {
+            let mut sanitized_content = security.sanitize_content(&req.content);
+            if req.validate.unwrap_or(false)
+                && let Err(syntax_error) = validate_syntax(&path, &sanitized_content)
+            {
+                warn!("Write refused for {}: invalid {}: {}", req.path, syntax_error.format, syntax_error.message);
+                let error = format!("Content is not valid {}: {}", syntax_error.format, syntax_error.message);
+                return Ok(HttpResponse::UnprocessableEntity().json(WriteResponse {
+                    syntax_error: Some(syntax_error),
+                    ..write_failure(error, Some(ErrorCode::InvalidSyntax))
+                }));
+            }
+
+            if req.format.unwrap_or(true) && !partial && security.has_formatter(&path) {
+                let formatter = security.get_ref().clone();
+                let target = path.clone();
+                let content = sanitized_content.clone();
+                match pools.exec.run(move || formatter.format_content(&target, &content)).await {
+                    Ok(Ok(Some(formatted))) => {
+                        sanitized_content = formatted.clone();
+                        formatted_content = Some(formatted);
+                    }
+                    Ok(Ok(None)) => {}
+                    Ok(Err(e)) => format_error = Some(e),
+                    Err(e) => format_error = Some(e.to_string()),
+                }
+                if let Some(e) = &format_error {
+                    warn!("Formatting failed for {}, writing content as sent: {}", req.path, e);
+                }
+            }
+            sanitized_content.into_bytes()
+        }
+    };
+
+    match scan_for_malware(&security, &pools, &path, content.clone()).await {
+        Ok(()) => {}
+        Err(ScanRejection::Infected(signature)) => {
+            let error = format!("Content rejected by malware scanner: {}", signature);
+            return Ok(HttpResponse::Forbidden().json(write_failure(error, None)));
+        }
+        Err(ScanRejection::Failed(e)) => {
+            error!("Malware scan failed for {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(write_failure(format!("Malware scan failed: {}", e), None)));
+        }
+    }
+
+    info!("Writing to file: {} ({} bytes)", req.path, content.len());
+
+    if let Err(e) = check_disk_space(&security, &pools, &path, content.len() as u64).await {
+        warn!("Write refused for {}: {}", req.path, e);
+        return Ok(HttpResponse::InsufficientStorage().json(write_failure(e, Some(ErrorCode::InsufficientSpace))));
+    }
+
+    throttle_write(&security, &client, &req.path, content.len() as u64).await;
+
+    // Create parent directories if they don't exist
+    let dir_mode = security.dir_mode();
+    if let Some(parent) = path.parent().map(Path::to_path_buf)
+        && let Err(e) = run_fs(&pools, move || create_dir_all_with_mode(&parent, dir_mode)).await
+    {
+        error!("Failed to create directories for {}: {}", req.path, e);
+        return Ok(HttpResponse::Ok().json(write_failure(format!("Failed to create directories: {}", e), None)));
+    }
+
+    let verify = req.verify.unwrap_or(false);
+    let expected_checksum = verify.then(|| sha256_hex(&content));
+    let written_len = partial.then_some(content.len());
+
+    // Ciphertext cannot be patched in place, so partial writes to encrypted files rewrite them whole
+    let (content, placement, spliced_at) = if partial && security.is_encrypted_path(&path) {
+        let reader = security.get_ref().clone();
+        let read_path = path.clone();
+        match run_fs(&pools, move || splice_plaintext(&reader, &read_path, content, placement)).await {
+            Ok((content, start)) => (content, Placement::Replace, Some(start)),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                return Ok(HttpResponse::BadRequest().json(write_failure(format!("Failed to write file: {}", e), None)));
+            }
+            Err(e) => {
+                error!("Failed to read encrypted file {}: {}", req.path, e);
+                return Ok(HttpResponse::Ok().json(write_failure(format!("Failed to write file: {}", e), None)));
+            }
+        }
+    } else {
+        (content, placement, None)
+    };
+
+    let data = match security.encrypt_for_path(&path, content) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to encrypt file {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(write_failure(format!("Failed to encrypt file: {}", e), None)));
+        }
+    };
+
+    let write_path = path.clone();
+    let verifier = security.get_ref().clone();
+    let written = run_fs(&pools, move || {
+        let start = write_with_mode(&write_path, &data, mode, requested_mode.is_some(), placement, verify)?;
+        if !verify {
+            return Ok(None);
+        }
+        // Read back from the device so truncation on flaky drives shows up in the checksum
+        let content = read_back(&verifier, &write_path, spliced_at.unwrap_or(start), written_len)?;
+        Ok(Some(sha256_hex(&content)))
+    })
+    .await;
+
+    match written {
+        Ok(checksum) if checksum != expected_checksum => {
+            error!("Verification failed for {}: content read back does not match", req.path);
+            Ok(HttpResponse::Ok().json(WriteResponse {
+                checksum,
+                ..write_failure("Write verification failed: content read back does not match".to_string(), None)
+            }))
+        }
+        Ok(checksum) => {
             info!("Successfully wrote file: {}", req.path);
             Ok(HttpResponse::Ok().json(WriteResponse {
                 success: true,
                 error: None,
+                error_code: None,
+                checksum,
+                syntax_error: None,
+                formatted_content,
+                format_error,
             }))
         }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            info!("Not writing {}: it already exists", req.path);
+            Ok(HttpResponse::Ok().json(write_failure(format!("File already exists: {}", req.path), None)))
+        }
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+            Ok(HttpResponse::BadRequest().json(write_failure(format!("Failed to write file: {}", e), None)))
+        }
         Err(e) => {
             error!("Failed to write file {}: {}", req.path, e);
-            Ok(HttpResponse::Ok().json(WriteResponse {
-                success: false,
-                error: Some(format!("Failed to write file: {}", e)),
+            Ok(HttpResponse::Ok().json(write_failure(format!("Failed to write file: {}", e), None)))
+        }
+    }
+}
+
+/// The plaintext of an encrypted file with `content` placed into it, and where it starts
+fn splice_plaintext(security: &SecurityManager, path: &Path, content: Vec<u8>, placement: Placement) -> io::Result<(Vec<u8>, u64)> {
+    let mut plaintext = match std::fs::read(path) {
+        Ok(stored) => security.decrypt_for_path(path, stored).map_err(io::Error::other)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let start = match placement {
+        Placement::At(offset) => offset,
+        _ => plaintext.len() as u64,
+    };
+    if start > plaintext.len() as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("offset {} is past the end of the file ({} bytes)", start, plaintext.len()),
+        ));
+    }
+    let (start_index, end) = (start as usize, (start as usize + content.len()).min(plaintext.len()));
+    plaintext.splice(start_index..end, content);
+    Ok((plaintext, start))
+}
+
+/// Reads back `len` bytes a write put at `start`, or everything from there without a length
+fn read_back(security: &SecurityManager, path: &Path, start: u64, len: Option<usize>) -> io::Result<Vec<u8>> {
+    let limit = len.map_or(u64::MAX, |len| len as u64);
+    if security.is_encrypted_path(path) {
+        let content = security.decrypt_for_path(path, std::fs::read(path)?).map_err(io::Error::other)?;
+        return Ok(content.into_iter().skip(start as usize).take(limit as usize).collect());
+    }
+    let mut file = std::fs::File::open(path)?;
+    file.seek(io::SeekFrom::Start(start))?;
+    let mut content = Vec::new();
+    file.take(limit).read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Why a line edit was not applied
+enum EditFailure {
+    /// The file's current hash, which differs from `expected_hash`
+    Conflict(String),
+    TooLarge,
+    Io(io::Error),
+}
+
+/// Handles line-based edit requests
+///
+/// The file is streamed through the edits into a staged copy next to it, which
+/// replaces the original only if the original still has `expected_hash`. Huge
+/// files are never held in memory and only the edits travel over the wire.
+pub async fn edit_file(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    client: ClientId,
+    req: web::Json<EditRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+    let failure = |error: String, error_code: Option<ErrorCode>, hash: Option<String>| EditResponse {
+        success: false,
+        error: Some(error),
+        error_code,
+        hash,
+        lines: None,
+    };
+
+    if !security.is_path_allowed(&path) || security.is_workspace_policy_path(&path) {
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to file: {}", req.path), None, None)));
+    }
+
+    if let Err(e) = security.check_protected(&path, req.force.unwrap_or(false)) {
+        warn!("Edit denied for {}: {}", req.path, e);
+        return Ok(HttpResponse::Forbidden().json(failure(e, Some(ErrorCode::ProtectedPath), None)));
+    }
+
+    if let Some(special) = special_file_at(&path) {
+        warn!("Refusing to edit {}: it is a {}", req.path, special);
+        return Ok(HttpResponse::BadRequest().json(failure(
+            special_file_error(&req.path, special),
+            Some(ErrorCode::SpecialFile),
+            None,
+        )));
+    }
+    if req.operations.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(failure("No edit operations given".to_string(), None, None)));
+    }
+    if let Err(e) = plan_edits(&req.operations) {
+        return Ok(HttpResponse::BadRequest().json(failure(e, None, None)));
+    }
+    if security.is_encrypted_path(&path) {
+        return Ok(HttpResponse::BadRequest().json(failure(
+            "Line edits are not supported for encrypted files; use write instead".to_string(),
+            None,
+            None,
+        )));
+    }
+
+    // Inserted text is sanitized like written content
+    let operations: Vec<LineEdit> = req
+        .operations
+        .iter()
+        .map(|operation| match operation {
+            LineEdit::InsertAtLine { line, content } => LineEdit::InsertAtLine {
+                line: *line,
+                content: security.sanitize_content(content),
+            },
+            LineEdit::ReplaceRange { start_line, end_line, content } => LineEdit::ReplaceRange {
+                start_line: *start_line,
+                end_line: *end_line,
+                content: security.sanitize_content(content),
+            },
+            LineEdit::DeleteLines { .. } => operation.clone(),
+        })
+        .collect();
+    let inserted: Vec<u8> = operations
+        .iter()
+        .filter_map(|operation| match operation {
+            LineEdit::InsertAtLine { content, .. } | LineEdit::ReplaceRange { content, .. } => Some(content.as_bytes()),
+            LineEdit::DeleteLines { .. } => None,
+        })
+        .collect::<Vec<_>>()
+        .join(&b'\n');
+
+    if !inserted.is_empty() {
+        match scan_for_malware(&security, &pools, &path, inserted.clone()).await {
+            Ok(()) => {}
+            Err(ScanRejection::Infected(signature)) => {
+                return Ok(HttpResponse::Forbidden().json(failure(
+                    format!("Content rejected by malware scanner: {}", signature),
+                    None,
+                    None,
+                )));
+            }
+            Err(ScanRejection::Failed(e)) => {
+                error!("Malware scan failed for {}: {}", req.path, e);
+                return Ok(HttpResponse::Ok().json(failure(format!("Malware scan failed: {}", e), None, None)));
+            }
+        }
+    }
+
+    if let Err(e) = check_disk_space(&security, &pools, &path, inserted.len() as u64).await {
+        warn!("Edit refused for {}: {}", req.path, e);
+        return Ok(HttpResponse::InsufficientStorage().json(failure(e, Some(ErrorCode::InsufficientSpace), None)));
+    }
+
+    throttle_write(&security, &client, &req.path, inserted.len() as u64).await;
+
+    info!("Editing file: {} ({} operations)", req.path, operations.len());
+
+    let target = path.clone();
+    let expected_hash = req.expected_hash.clone();
+    let limits = security.get_ref().clone();
+    let edited = pools.fs.run(move || -> std::result::Result<EditOutcome, EditFailure> {
+        let spans = plan_edits(&operations).map_err(|e| EditFailure::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let original = std::fs::File::open(&target).map_err(EditFailure::Io)?;
+        let permissions = original.metadata().map_err(EditFailure::Io)?.permissions();
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let staged = target.with_file_name(format!(".{}.exex-edit-{}", name, uuid::Uuid::new_v4().simple()));
+
+        let applied = std::fs::File::create(&staged).and_then(|file| {
+            let mut output = io::BufWriter::new(file);
+            let outcome = apply_line_edits(io::BufReader::new(original), &mut output, &spans)?;
+            output.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            Ok(outcome)
+        });
+        let checked = match applied {
+            Ok(outcome) if expected_hash.as_ref().is_some_and(|hash| !hash.eq_ignore_ascii_case(&outcome.original_hash)) => {
+                Err(EditFailure::Conflict(outcome.original_hash))
+            }
+            Ok(outcome) if !limits.is_file_size_allowed(outcome.bytes) => Err(EditFailure::TooLarge),
+            Ok(outcome) => std::fs::set_permissions(&staged, permissions)
+                .and_then(|_| std::fs::rename(&staged, &target))
+                .map(|_| outcome)
+                .map_err(EditFailure::Io),
+            Err(e) => Err(EditFailure::Io(e)),
+        };
+        if checked.is_err() {
+            let _ = std::fs::remove_file(&staged);
+        }
+        checked
+    });
+
+    match edited.await {
+        Ok(Ok(outcome)) => {
+            info!("Successfully edited file: {}", req.path);
+            Ok(HttpResponse::Ok().json(EditResponse {
+                success: true,
+                error: None,
+                error_code: None,
+                hash: Some(outcome.hash),
+                lines: Some(outcome.lines),
             }))
         }
+        Ok(Err(EditFailure::Conflict(current))) => {
+            warn!("Edit refused for {}: file changed since it was read", req.path);
+            Ok(HttpResponse::Conflict().json(failure(
+                "File has changed since expected_hash was taken".to_string(),
+                Some(ErrorCode::HashMismatch),
+                Some(current),
+            )))
+        }
+        Ok(Err(EditFailure::TooLarge)) => Ok(HttpResponse::Forbidden().json(failure(
+            "File size exceeds maximum allowed size".to_string(),
+            None,
+            None,
+        ))),
+        Ok(Err(EditFailure::Io(e))) if e.kind() == io::ErrorKind::InvalidInput => {
+            Ok(HttpResponse::BadRequest().json(failure(e.to_string(), None, None)))
+        }
+        Ok(Err(EditFailure::Io(e))) => {
+            error!("Failed to edit file {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to edit file: {}", e), None, None)))
+        }
+        Err(e) => {
+            error!("Failed to edit file {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to edit file: {}", e), None, None)))
+        }
     }
 }
 
+/// Hex-encoded SHA-256 digest
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
 /// Handles directory scanning requests
 pub async fn scan_directory(
     security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    operations: web::Data<Arc<OperationRegistry>>,
+    api_version: ApiVersion,
     req: web::Json<ScanRequest>,
 ) -> Result<HttpResponse> {
-    
-    let path = PathBuf::from(&req.path);
+    let path = extended_path(Path::new(&req.path));
 
     if !security.is_path_allowed(&path) {
         return Ok(HttpResponse::Forbidden().json(ScanResponse {
             success: false,
             items: None,
             total_count: None,
+            stats: None,
             error: Some(format!("Access denied to directory: {}", req.path)),
         }));
     }
 
+    let ignore = match security.ignore_matcher(&path, req.ignore_patterns.as_deref()) {
+        Ok(ignore) => ignore,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ScanResponse {
+                success: false,
+                items: None,
+                total_count: None,
+                stats: None,
+                error: Some(e),
+            }));
+        }
+    };
+
+    let invalid = if req.max_depth == Some(0) {
+        Some("max_depth must be at least 1")
+    } else if req.offset.is_some() && req.cursor.is_some() {
+        Some("Give either offset or cursor, not both")
+    } else {
+        None
+    };
+    if let Some(error) = invalid {
+        return Ok(HttpResponse::BadRequest().json(ScanResponse {
+            success: false,
+            items: None,
+            total_count: None,
+            stats: None,
+            error: Some(error.to_string()),
+        }));
+    }
+
+    let names_only = req.names_only.unwrap_or(false);
+    if names_only && matches!(req.sort_by, Some(ScanSortKey::Size | ScanSortKey::Mtime)) {
+        return Ok(HttpResponse::BadRequest().json(ScanResponse {
+            success: false,
+            items: None,
+            total_count: None,
+            stats: None,
+            error: Some("Sorting by size or mtime needs metadata, which names_only skips".to_string()),
+        }));
+    }
+
+    let summary = match req.recursive {
+        Some(true) => format!("recursive scan of {}", req.path),
+        _ => format!("scan of {}", req.path),
+    };
+    let operation = match operations.start(req.operation_id.as_deref(), "scan", &req.path, summary) {
+        Ok(operation) => operation,
+        Err(e) => {
+            return Ok(HttpResponse::build(start_failure_status(&e)).json(ScanResponse {
+                success: false,
+                items: None,
+                total_count: None,
+                stats: None,
+                error: Some(e.to_string()),
+            }));
+        }
+    };
+
     info!("Scanning directory: {}", req.path);
 
-    let mut items = Vec::new();
-    let recursive = req.recursive.unwrap_or(false);
-    let include_hidden = req.include_hidden.unwrap_or(false);
+    let options = ScanOptions {
+        recursive: req.recursive.unwrap_or(false),
+        max_depth: req.max_depth,
+        include_hidden: req.include_hidden.unwrap_or(false),
+        rfc3339_times: api_version.0 >= 2,
+        names_only,
+        omit_private: security.omit_private_metadata(),
+        // Ownership is private metadata too
+        include_owner: req.include_owner.unwrap_or(false) && !security.omit_private_metadata(),
+        sniff_content: req.sniff_content.unwrap_or(false),
+    };
 
-    let scan_result = if recursive {
-        scan_directory_recursive(&path, include_hidden, &security).await
-    } else {
-        scan_directory_single(&path, include_hidden).await
+    // Open the root up front so failures still produce a regular error response
+    let root = path.clone();
+    let entries = match run_fs(&pools, move || std::fs::read_dir(root)).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to scan directory {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(ScanResponse {
+                success: false,
+                items: None,
+                total_count: None,
+                stats: None,
+                error: Some(format!("Failed to scan directory: {}", e)),
+            }));
+        }
     };
 
-    match scan_result {
-        Ok(mut scanned_items) => {
-            items.append(&mut scanned_items);
-            info!("Successfully scanned directory: {} ({} items)", req.path, items.len());
-            Ok(HttpResponse::Ok().json(ScanResponse {
-                success: true,
-                items: Some(items.clone()),
-                total_count: Some(items.len()),
-                error: None,
-            }))
+    // Sorting, paging and totals need the whole listing; otherwise items stream as they are found
+    if req.limit.is_some()
+        || req.cursor.is_some()
+        || req.offset.is_some()
+        || req.sort_by.is_some()
+        || req.directories_first.unwrap_or(false)
+        || req.aggregate.unwrap_or(false)
+    {
+        return scan_collected(&security, &pools, entries, &req, options, ignore, operation.token()).await;
+    }
+
+    // Items are serialized as they are found, so memory stays bounded by the chunk size
+    let (mut writer, body) = ChunkWriter::new();
+    let security = security.get_ref().clone();
+    let requested_path = req.path.clone();
+    let spawned = pools.fs.spawn(move || {
+        // Registered until the scan ends
+        let cancel = operation.token();
+        let _operation = operation;
+        match stream_scan(entries, options, &ignore, &security, &cancel, &mut writer) {
+            Ok(count) => {
+                info!("Successfully scanned directory: {} ({} items)", requested_path, count);
+                let _ = writer.finish();
+            }
+            Err(e) => {
+                error!("Failed to scan directory {}: {}", requested_path, e);
+                writer.abort(e);
+            }
+        }
+    });
+
+    match spawned {
+        Ok(()) => Ok(HttpResponse::Ok().content_type(ContentType::json()).body(body)),
+        Err(e) => Ok(HttpResponse::Ok().json(ScanResponse {
+            success: false,
+            items: None,
+            total_count: None,
+            stats: None,
+            error: Some(format!("Failed to scan directory: {}", e)),
+        })),
+    }
+}
+
+/// Collects and sorts the whole listing, returning one page of it when paging was requested
+async fn scan_collected(
+    security: &Arc<SecurityManager>,
+    pools: &WorkerPools,
+    entries: std::fs::ReadDir,
+    req: &ScanRequest,
+    options: ScanOptions,
+    ignore: IgnoreMatcher,
+    cancel: CancelToken,
+) -> Result<HttpResponse> {
+    let walker = security.clone();
+    let sort_by = req.sort_by;
+    let directories_first = req.directories_first.unwrap_or(false);
+    let root = display_path(&extended_path(Path::new(&req.path)));
+    let aggregate = req.aggregate.unwrap_or(false);
+    let listing = run_fs(pools, move || {
+        let mut sink = WalkedSink::default();
+        walk_directory(entries, options, &ignore, &walker, &cancel, &mut sink)?;
+        let WalkedSink { mut items, walked } = sink;
+        let stats = aggregate.then(|| aggregate_directories(&mut items, &root, &walked, options.rfc3339_times));
+        sort_items(&mut items, sort_by, directories_first);
+        Ok((items, stats))
+    })
+    .await;
+
+    let paged = req.limit.is_some() || req.cursor.is_some() || req.offset.is_some();
+    let (items, stats) = match listing {
+        Ok(listing) => listing,
+        Err(e) if paged => {
+            error!("Failed to scan directory {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(Paginated::<FileInfo>::failure(format!(
+                "Failed to scan directory: {}",
+                e
+            ))));
         }
         Err(e) => {
             error!("Failed to scan directory {}: {}", req.path, e);
-            Ok(HttpResponse::Ok().json(ScanResponse {
+            return Ok(HttpResponse::Ok().json(ScanResponse {
                 success: false,
                 items: None,
                 total_count: None,
+                stats: None,
                 error: Some(format!("Failed to scan directory: {}", e)),
-            }))
+            }));
+        }
+    };
+
+    if !paged {
+        info!("Successfully scanned directory: {} ({} items)", req.path, items.len());
+        return Ok(HttpResponse::Ok().json(ScanResponse {
+            success: true,
+            total_count: Some(items.len()),
+            items: Some(items),
+            stats,
+            error: None,
+        }));
+    }
+
+    let page = match req.offset {
+        Some(offset) => Ok(paginate_at(items, offset, req.limit)),
+        None => paginate(items, req.cursor.as_deref(), req.limit),
+    };
+    match page {
+        Ok(page) => {
+            info!("Successfully scanned directory: {} ({} items)", req.path, page.total);
+            Ok(HttpResponse::Ok().json(page))
         }
+        Err(e) => Ok(HttpResponse::BadRequest().json(Paginated::<FileInfo>::failure(e))),
     }
 }
 
-async fn scan_directory_single(path: &PathBuf, include_hidden: bool) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>> {
-    let mut items = Vec::new();
-    let mut entries = fs::read_dir(path).await?;
+/// Orders a listing; path breaks ties so pages stay stable across requests
+fn sort_items(items: &mut [FileInfo], sort_by: Option<ScanSortKey>, directories_first: bool) {
+    fn extension(item: &FileInfo) -> Option<String> {
+        if item.is_directory {
+            return None;
+        }
+        Path::new(&item.name)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+    }
 
-    while let Some(entry) = entries.next_entry().await? {
-        let metadata = entry.metadata().await?;
+    items.sort_by(|a, b| {
+        let group = if directories_first {
+            b.is_directory.cmp(&a.is_directory)
+        } else {
+            Ordering::Equal
+        };
+        let key = match sort_by {
+            Some(ScanSortKey::Name) => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            Some(ScanSortKey::Size) => a.size.cmp(&b.size),
+            Some(ScanSortKey::Mtime) => a.modified_epoch.cmp(&b.modified_epoch),
+            Some(ScanSortKey::Type) => extension(a).cmp(&extension(b)),
+            None => Ordering::Equal,
+        };
+        group.then(key).then_with(|| a.path.cmp(&b.path))
+    });
+}
+
+/// Receives scan entries as they are found
+trait ScanSink {
+    fn push(&mut self, item: FileInfo) -> io::Result<()>;
+
+    /// Whether the consumer has gone away and the scan should stop
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// Called for each subdirectory that is read, before its entries are pushed
+    fn entered(&mut self, _directory: &Path) {}
+}
+
+/// Serializes entries straight into a streamed JSON array
+struct StreamSink<'a> {
+    writer: &'a mut ChunkWriter,
+    count: usize,
+}
+
+impl ScanSink for StreamSink<'_> {
+    fn push(&mut self, item: FileInfo) -> io::Result<()> {
+        let json = serde_json::to_vec(&item).map_err(io::Error::other)?;
+        if self.count > 0 {
+            self.writer.write(b",")?;
+        }
+        self.writer.write(&json)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.writer.is_closed()
+    }
+}
+
+impl ScanSink for Vec<FileInfo> {
+    fn push(&mut self, item: FileInfo) -> io::Result<()> {
+        Vec::push(self, item);
+        Ok(())
+    }
+}
+
+/// Collects entries along with the subdirectories that were actually read
+#[derive(Default)]
+struct WalkedSink {
+    items: Vec<FileInfo>,
+    walked: HashSet<PathBuf>,
+}
+
+impl ScanSink for WalkedSink {
+    fn push(&mut self, item: FileInfo) -> io::Result<()> {
+        self.items.push(item);
+        Ok(())
+    }
+
+    fn entered(&mut self, directory: &Path) {
+        self.walked.insert(stats_key(&display_path(directory)));
+    }
+}
+
+/// Normalized form of a listed path, so trailing separators do not split totals
+fn stats_key(path: &str) -> PathBuf {
+    Path::new(path).components().collect()
+}
+
+/// Totals the listing under each directory, attaching them to the walked directories
+///
+/// Every entry counts towards all of its ancestors up to `root`, so totals
+/// cover the whole subtree as far as the scan listed it: hidden and ignored
+/// entries are left out just as they are from the items. Directories the scan
+/// did not read (non-recursive scans, denied or unreadable ones) get no
+/// totals rather than misleading zeros. Returns the totals for `root` itself.
+fn aggregate_directories(
+    items: &mut [FileInfo],
+    root: &str,
+    walked: &HashSet<PathBuf>,
+    rfc3339_times: bool,
+) -> DirectoryStats {
+    let root = stats_key(root);
+    let mut totals: HashMap<PathBuf, DirectoryStats> = HashMap::new();
+    for item in items.iter() {
+        let path = stats_key(&item.path);
+        for ancestor in path.ancestors().skip(1).take_while(|a| a.starts_with(&root)) {
+            let stats = totals.entry(ancestor.to_path_buf()).or_default();
+            if item.is_directory {
+                stats.directories += 1;
+            } else {
+                stats.files += 1;
+            }
+            stats.bytes += item.size.unwrap_or(0);
+            stats.newest_modified_epoch = stats.newest_modified_epoch.max(item.modified_epoch);
+        }
+    }
+
+    let finish = |mut stats: DirectoryStats| {
+        stats.newest_modified = stats.newest_modified_epoch.map(|secs| {
+            if rfc3339_times {
+                humantime::format_rfc3339_seconds(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)).to_string()
+            } else {
+                secs.to_string()
+            }
+        });
+        stats
+    };
+    for item in items.iter_mut().filter(|item| item.is_directory) {
+        let path = stats_key(&item.path);
+        if walked.contains(&path) {
+            item.stats = Some(finish(totals.remove(&path).unwrap_or_default()));
+        }
+    }
+    finish(totals.remove(&root).unwrap_or_default())
+}
+
+/// Writes a `ScanResponse` JSON document for the root entries and, if recursive, all allowed subdirectories
+fn stream_scan(
+    root: std::fs::ReadDir,
+    options: ScanOptions,
+    ignore: &IgnoreMatcher,
+    security: &SecurityManager,
+    cancel: &CancelToken,
+    writer: &mut ChunkWriter,
+) -> io::Result<usize> {
+    writer.write(br#"{"success":true,"items":["#)?;
+
+    let mut sink = StreamSink { writer, count: 0 };
+    walk_directory(root, options, ignore, security, cancel, &mut sink)?;
+    let count = sink.count;
+
+    writer.write(format!(r#"],"total_count":{},"stats":null,"error":null}}"#, count).as_bytes())?;
+    Ok(count)
+}
+
+/// Feeds the root entries and, if recursive, all allowed subdirectories to `sink`, leaving out ignored entries
+///
+/// Stops with an error once `cancel` is cancelled.
+fn walk_directory(
+    root: std::fs::ReadDir,
+    options: ScanOptions,
+    ignore: &IgnoreMatcher,
+    security: &SecurityManager,
+    cancel: &CancelToken,
+    sink: &mut impl ScanSink,
+) -> io::Result<()> {
+    // Account names are resolved once per scan
+    let mut owners = options.include_owner.then(OwnerLookup::new);
+    let mut stack: Vec<(PathBuf, usize)> = scan_entries(root, options, ignore, security, cancel, &mut owners, sink)?
+        .into_iter()
+        .map(|subdirectory| (subdirectory, 1))
+        .collect();
+
+    while options.recursive && let Some((current_path, depth)) = stack.pop() {
+        // Entries of a subdirectory at `depth` are one level deeper
+        if options.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            continue;
+        }
+
+        // Check if we still have permission for subdirectories
+        if !security.is_path_allowed(&current_path) {
+            continue;
+        }
+
+        let Ok(entries) = std::fs::read_dir(&current_path) else {
+            continue;
+        };
+        sink.entered(&current_path);
+        match scan_entries(entries, options, ignore, security, cancel, &mut owners, sink) {
+            Ok(subdirectories) => stack.extend(subdirectories.into_iter().map(|subdirectory| (subdirectory, depth + 1))),
+            Err(e) if sink.is_closed() || is_cancellation(&e) => return Err(e),
+            Err(_) => continue,
+        }
+    }
+
+    Ok(())
+}
+
+/// Options shared by every directory visited in a scan
+#[derive(Debug, Clone, Copy)]
+struct ScanOptions {
+    recursive: bool,
+    /// Levels a recursive scan lists, the scanned directory's own entries being the first
+    max_depth: Option<usize>,
+    include_hidden: bool,
+    /// API v2 reports times as RFC 3339; v1 keeps epoch seconds as strings
+    rfc3339_times: bool,
+    /// Report what the directory listing provides without reading each entry's metadata
+    names_only: bool,
+    /// Leave out creation and access times
+    omit_private: bool,
+    /// Look up each entry's owner
+    include_owner: bool,
+    /// Classify files by their leading bytes rather than by extension
+    sniff_content: bool,
+}
+
+/// Passes the entries of one directory to `sink` and returns its subdirectories
+fn scan_entries(
+    entries: std::fs::ReadDir,
+    options: ScanOptions,
+    ignore: &IgnoreMatcher,
+    security: &SecurityManager,
+    cancel: &CancelToken,
+    owners: &mut Option<OwnerLookup>,
+    sink: &mut impl ScanSink,
+) -> io::Result<Vec<PathBuf>> {
+    let mut subdirectories = Vec::new();
+
+    for entry in entries {
+        cancel.check()?;
+        let entry = entry?;
+        if options.names_only {
+            if let Some(subdirectory) = scan_name(&entry, options, ignore, sink)? {
+                subdirectories.push(subdirectory);
+            }
+            continue;
+        }
+        let metadata = entry.metadata()?;
         let file_name = entry.file_name().to_string_lossy().to_string();
-        
-        // Skip hidden files if not requested
-        if !include_hidden && file_name.starts_with('.') {
+        let attributes = entry_attributes(&file_name, &metadata);
+
+        // Skip hidden and system files if not requested
+        if !options.include_hidden && (attributes.hidden || attributes.system) {
+            continue;
+        }
+
+        let entry_path = entry.path();
+        if ignore.is_ignored(&entry_path) {
             continue;
         }
+        if metadata.is_dir() {
+            subdirectories.push(extended_path(&entry_path));
+        }
+
+        let kind = entry_kind(security, &entry_path, &metadata, options.sniff_content);
 
-        let file_info = FileInfo {
-            name: file_name,
-            path: entry.path().to_string_lossy().to_string(),
-            is_directory: metadata.is_dir(),
-            size: if metadata.is_file() { Some(metadata.len()) } else { None },
-            modified: metadata.modified().ok().and_then(|t| 
-                t.duration_since(SystemTime::UNIX_EPOCH).ok().map(|d| d.as_secs().to_string())
-            ),
-            created: metadata.created().ok().and_then(|t| 
-                t.duration_since(SystemTime::UNIX_EPOCH).ok().map(|d| d.as_secs().to_string())
-            ),
-            permissions: Some(format!("{:?}", metadata.permissions())),
+        let ownership = match owners.as_mut() {
+            Some(owners) => owners.lookup(&entry_path, &metadata),
+            None => Ownership::default(),
         };
-        
-        items.push(file_info);
+        sink.push(file_info(file_name, &entry_path, &metadata, attributes, kind, ownership, options))?;
+    }
+
+    Ok(subdirectories)
+}
+
+/// Passes one entry to `sink` with only its name, path and type; returns it if it is a directory
+fn scan_name(
+    entry: &std::fs::DirEntry,
+    options: ScanOptions,
+    ignore: &IgnoreMatcher,
+    sink: &mut impl ScanSink,
+) -> io::Result<Option<PathBuf>> {
+    let file_name = entry.file_name().to_string_lossy().to_string();
+    let attributes = listed_attributes(entry, &file_name)?;
+    if !options.include_hidden && (attributes.hidden || attributes.system) {
+        return Ok(None);
+    }
+
+    let entry_path = entry.path();
+    if ignore.is_ignored(&entry_path) {
+        return Ok(None);
     }
+    // Comes with the listing on most filesystems, so this rarely needs a stat
+    let file_type = entry.file_type()?;
+    let is_directory = file_type.is_dir();
+
+    sink.push(FileInfo {
+        name: file_name,
+        path: display_path(&entry_path),
+        is_directory,
+        size: None,
+        modified: None,
+        created: None,
+        accessed: None,
+        modified_epoch: None,
+        created_epoch: None,
+        accessed_epoch: None,
+        permissions: None,
+        is_hidden: attributes.hidden,
+        is_system: attributes.system,
+        attributes: attributes.raw,
+        kind: None,
+        special: special_file_type(&file_type),
+        sparse: None,
+        uid: None,
+        gid: None,
+        owner: None,
+        group: None,
+        owner_sid: None,
+        stats: None,
+    })?;
+    Ok(is_directory.then(|| extended_path(&entry_path)))
+}
 
+fn file_info(
+    name: String,
+    path: &Path,
+    metadata: &Metadata,
+    attributes: EntryAttributes,
+    kind: Option<FileKind>,
+    ownership: Ownership,
+    options: ScanOptions,
+) -> FileInfo {
+    let modified = epoch_seconds(metadata.modified());
+    let (created, accessed) = if options.omit_private {
+        (None, None)
+    } else {
+        (epoch_seconds(metadata.created()), epoch_seconds(metadata.accessed()))
+    };
+    let format = |time: Option<(SystemTime, u64)>| {
+        time.map(|(time, secs)| {
+            if options.rfc3339_times {
+                humantime::format_rfc3339_seconds(time).to_string()
+            } else {
+                secs.to_string()
+            }
+        })
+    };
+
+    FileInfo {
+        name,
+        path: display_path(path),
+        is_directory: metadata.is_dir(),
+        size: if metadata.is_file() { Some(metadata.len()) } else { None },
+        modified: format(modified),
+        created: format(created),
+        accessed: format(accessed),
+        modified_epoch: modified.map(|(_, secs)| secs),
+        created_epoch: created.map(|(_, secs)| secs),
+        accessed_epoch: accessed.map(|(_, secs)| secs),
+        permissions: Some(format!("{:?}", metadata.permissions())),
+        is_hidden: attributes.hidden,
+        is_system: attributes.system,
+        attributes: attributes.raw,
+        kind,
+        special: special_file_type(&metadata.file_type()),
+        sparse: is_sparse(metadata),
+        uid: ownership.uid,
+        gid: ownership.gid,
+        owner: ownership.owner,
+        group: ownership.group,
+        owner_sid: ownership.owner_sid,
+        stats: None,
+    }
+}
+
+/// Content classification of a listed file; `None` for anything else
+///
+/// Listings go by extension so that they do not open every file; `sniff` reads the leading bytes instead.
+fn entry_kind(security: &SecurityManager, path: &Path, metadata: &Metadata, sniff: bool) -> Option<FileKind> {
+    if !metadata.is_file() {
+        None
+    } else if sniff && !security.is_encrypted_path(path) {
+        sniff_file(&extended_path(path)).ok()
+    } else {
+        // Ciphertext says nothing about the content, so encrypted files go by name too
+        kind_from_extension(path)
+    }
+}
+
+/// What a find looks for and where
+struct FindPlan {
+    root: PathBuf,
+    patterns: GlobSet,
+    exclude: Option<GlobSet>,
+    ignore: IgnoreMatcher,
+    gitignore: Option<GitIgnores>,
+    include_directories: bool,
+    options: ScanOptions,
+}
+
+/// Walks the tree below the root in name order, returning the entries whose relative paths match
+///
+/// Only matches are stat'ed beyond what the directory listing provides.
+fn find_entries(security: &SecurityManager, plan: &mut FindPlan) -> io::Result<Vec<FileInfo>> {
+    let mut items = Vec::new();
+    let mut stack = vec![plan.root.clone()];
+    let mut at_root = true;
+    while let Some(dir) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            // Only the requested directory itself must be readable
+            Err(e) if at_root => return Err(e),
+            Err(_) => continue,
+        };
+        at_root = false;
+        let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut subdirectories = Vec::new();
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Ok(attributes) = listed_attributes(&entry, &name) else {
+                continue;
+            };
+            let path = entry.path();
+            if (!plan.options.include_hidden && (attributes.hidden || attributes.system))
+                || plan.ignore.is_ignored(&path)
+                || plan.gitignore.as_ref().is_some_and(|gitignore| gitignore.is_ignored(&path))
+            {
+                continue;
+            }
+            let relative = path.strip_prefix(&plan.root).unwrap_or(&path);
+            if plan.exclude.as_ref().is_some_and(|exclude| exclude.is_match(relative)) {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !security.is_path_allowed(&path) {
+                    continue;
+                }
+                if let Some(gitignore) = plan.gitignore.as_mut() {
+                    gitignore.enter(&path);
+                }
+                subdirectories.push(path.clone());
+            }
+
+            if (file_type.is_dir() && !plan.include_directories) || !plan.patterns.is_match(relative) {
+                continue;
+            }
+            let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+                continue;
+            };
+            let kind = entry_kind(security, &path, &metadata, plan.options.sniff_content);
+            items.push(file_info(name, &path, &metadata, attributes, kind, Ownership::default(), plan.options));
+        }
+        stack.extend(subdirectories.into_iter().rev());
+    }
     Ok(items)
 }
 
-async fn scan_directory_recursive(
-    path: &PathBuf, 
-    include_hidden: bool, 
-    security: &Arc<SecurityManager>
-) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>> {
-    let mut items = Vec::new();
-    let mut stack = vec![path.clone()];
+/// Finds files, and optionally directories, below a directory whose paths match glob patterns
+///
+/// Cheaper than a recursive scan: entries are matched on the names the
+/// directory listing provides, and only matches are inspected further.
+/// Matches are returned a page at a time, like a paginated scan.
+pub async fn find_files(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    api_version: ApiVersion,
+    req: web::Json<FindRequest>,
+) -> Result<HttpResponse> {
+    let failure = Paginated::<FileInfo>::failure;
+    let root = extended_path(Path::new(&req.path));
+    if !security.is_path_allowed(&root) {
+        warn!("Find access denied: {}", req.path);
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to directory: {}", req.path))));
+    }
+    if req.patterns.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(failure("'patterns' must not be empty".to_string())));
+    }
+
+    let exclude = req
+        .exclude
+        .as_deref()
+        .map(|exclude| build_glob_set(exclude).map_err(|e| format!("Invalid exclude pattern {}", e)))
+        .transpose();
+    let patterns = build_glob_set(&req.patterns).map_err(|e| format!("Invalid pattern {}", e));
+    let ignore = security.ignore_matcher(&root, req.ignore_patterns.as_deref());
+    let mut plan = match (patterns, exclude, ignore) {
+        (Ok(patterns), Ok(exclude), Ok(ignore)) => FindPlan {
+            gitignore: req.respect_gitignore.unwrap_or(false).then(|| GitIgnores::for_root(&root)),
+            root,
+            patterns,
+            exclude,
+            ignore,
+            include_directories: req.include_directories.unwrap_or(false),
+            options: ScanOptions {
+                recursive: true,
+                max_depth: None,
+                include_hidden: req.include_hidden.unwrap_or(false),
+                rfc3339_times: api_version.0 >= 2,
+                names_only: false,
+                omit_private: security.omit_private_metadata(),
+                include_owner: false,
+                sniff_content: req.sniff_content.unwrap_or(false),
+            },
+        },
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return Ok(HttpResponse::BadRequest().json(failure(e))),
+    };
 
-    while let Some(current_path) = stack.pop() {
-        // Check if we still have permission for subdirectories
-        if !security.is_path_allowed(&current_path) {
-            continue;
+    info!("Finding {:?} in {}", req.patterns, req.path);
+    let walker = security.get_ref().clone();
+    let items = match run_fs(&pools, move || find_entries(&walker, &mut plan)).await {
+        Ok(items) => items,
+        Err(e) => {
+            error!("Failed to find in {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(failure(format!("Failed to find files: {}", e))));
         }
-
-        if let Ok(single_items) = scan_directory_single(&current_path, include_hidden).await {
-            for item in single_items {
-                if item.is_directory {
-                    stack.push(PathBuf::from(&item.path));
-                }
-                items.push(item);
-            }
+    };
+    match paginate(items, req.cursor.as_deref(), req.limit) {
+        Ok(page) => {
+            info!("Found {} entries in {}", page.total, req.path);
+            Ok(HttpResponse::Ok().json(page))
         }
+        Err(e) => Ok(HttpResponse::BadRequest().json(failure(e))),
     }
+}
 
-    Ok(items)
+/// Pairs a timestamp with its whole Unix epoch seconds, if the platform reports it
+fn epoch_seconds(time: io::Result<SystemTime>) -> Option<(SystemTime, u64)> {
+    let time = time.ok()?;
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+    Some((time, secs))
 }
 
 /// Handles file/directory deletion requests
 pub async fn delete_item(
     security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
     req: web::Json<DeleteRequest>,
 ) -> Result<HttpResponse> {
-    let path = PathBuf::from(&req.path);
+    let path = extended_path(Path::new(&req.path));
 
-    if !security.is_path_allowed(&path) {
+    if !security.is_path_allowed(&path) || security.is_workspace_policy_path(&path) {
         return Ok(HttpResponse::Forbidden().json(DeleteResponse {
             success: false,
             deleted_count: None,
             error: Some(format!("Access denied to delete: {}", req.path)),
+            error_code: None,
         }));
     }
 
     info!("Deleting item: {}", req.path);
 
     let recursive = req.recursive.unwrap_or(false);
+    let force = req.force.unwrap_or(false);
     let mut deleted_count = 0;
 
+    // A recursive delete also removes whatever protected files lie below the directory
+    let checker = security.get_ref().clone();
+    let checked = path.clone();
+    let protection = if recursive && path.is_dir() {
+        run_fs(&pools, move || Ok(checker.check_protected_tree(&checked, force))).await
+    } else {
+        Ok(security.check_protected(&path, force))
+    };
+    match protection {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            warn!("Delete refused for {}: {}", req.path, e);
+            return Ok(HttpResponse::Forbidden().json(DeleteResponse {
+                success: false,
+                deleted_count: None,
+                error: Some(e),
+                error_code: Some(ErrorCode::ProtectedPath),
+            }));
+        }
+        Err(e) => {
+            error!("Failed to check {} for protected files: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(DeleteResponse {
+                success: false,
+                deleted_count: None,
+                error: Some(format!("Failed to delete: {}", e)),
+                error_code: None,
+            }));
+        }
+    }
+
+    if recursive
+        && path.is_dir()
+        && !req.override_entry_limit.unwrap_or(false)
+        && let Err(e) = check_affected_entries(&security, &pools, &path).await
+    {
+        warn!("Delete refused for {}: {}", req.path, e);
+        return Ok(HttpResponse::Forbidden().json(DeleteResponse {
+            success: false,
+            deleted_count: None,
+            error: Some(e),
+            error_code: Some(ErrorCode::TooManyEntries),
+        }));
+    }
+
     let result = if path.is_file() {
         match fs::remove_file(&path).await {
             Ok(_) => {
@@ -248,7 +1885,8 @@ pub async fn delete_item(
         }
     } else if path.is_dir() {
         if recursive {
-            match fs::remove_dir_all(&path).await {
+            let remove_path = path.clone();
+            match run_fs(&pools, move || std::fs::remove_dir_all(remove_path)).await {
                 Ok(_) => {
                     deleted_count = 1; // We don't count individual files in recursive delete
                     Ok(())
@@ -268,6 +1906,8 @@ pub async fn delete_item(
         Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Path not found"))
     };
 
+    security.invalidate_path_cache(&path);
+
     match result {
         Ok(_) => {
             info!("Successfully deleted: {}", req.path);
@@ -275,6 +1915,7 @@ pub async fn delete_item(
                 success: true,
                 deleted_count: Some(deleted_count),
                 error: None,
+                error_code: None,
             }))
         }
         Err(e) => {
@@ -283,6 +1924,7 @@ pub async fn delete_item(
                 success: false,
                 deleted_count: None,
                 error: Some(format!("Failed to delete: {}", e)),
+                error_code: None,
             }))
         }
     }
@@ -291,18 +1933,56 @@ pub async fn delete_item(
 /// Handles file/directory creation requests
 pub async fn create_item(
     security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    client: ClientId,
     req: web::Json<CreateRequest>,
 ) -> Result<HttpResponse> {
-    let path = PathBuf::from(&req.path);
+    let path = extended_path(Path::new(&req.path));
 
-    if !security.is_path_allowed(&path) {
+    if !security.is_path_allowed(&path) || security.is_workspace_policy_path(&path) {
         return Ok(HttpResponse::Forbidden().json(CreateResponse {
             success: false,
             created_path: None,
             error: Some(format!("Access denied to create: {}", req.path)),
+            error_code: None,
+        }));
+    }
+
+    if let Err(e) = security.check_protected(&path, req.force.unwrap_or(false)) {
+        warn!("Create denied for {}: {}", req.path, e);
+        return Ok(HttpResponse::Forbidden().json(CreateResponse {
+            success: false,
+            created_path: None,
+            error: Some(e),
+            error_code: Some(ErrorCode::ProtectedPath),
         }));
     }
 
+    let requested_mode = match req.permissions.as_deref().map(parse_mode).transpose() {
+        Ok(mode) => mode,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(CreateResponse {
+                success: false,
+                created_path: None,
+                error: Some(e),
+                error_code: None,
+            }));
+        }
+    };
+    let mode = match security.creation_mode(requested_mode, req.is_directory) {
+        Ok(mode) => mode,
+        Err(e) => {
+            warn!("Create denied for {}: {}", req.path, e);
+            return Ok(HttpResponse::Forbidden().json(CreateResponse {
+                success: false,
+                created_path: None,
+                error: Some(e),
+                error_code: None,
+            }));
+        }
+    };
+    let dir_mode = security.dir_mode();
+
     info!("Creating item: {} (directory: {})", req.path, req.is_directory);
 
     // Check if item already exists
@@ -311,28 +1991,76 @@ pub async fn create_item(
             success: false,
             created_path: None,
             error: Some(format!("Item already exists: {}", req.path)),
+            error_code: None,
         }));
     }
 
     let result = if req.is_directory {
-        fs::create_dir_all(&path).await
+        let dir_path = path.clone();
+        run_fs(&pools, move || {
+            create_dir_all_with_mode(&dir_path, dir_mode)?;
+            set_mode(&dir_path, mode)
+        })
+        .await
     } else {
         // Create parent directories if needed
-        if let Some(parent) = path.parent() {
-            if let Err(e) = fs::create_dir_all(parent).await {
-                error!("Failed to create parent directories for {}: {}", req.path, e);
+        if let Some(parent) = path.parent().map(Path::to_path_buf)
+            && let Err(e) = run_fs(&pools, move || create_dir_all_with_mode(&parent, dir_mode)).await
+        {
+            error!("Failed to create parent directories for {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(CreateResponse {
+                success: false,
+                created_path: None,
+                error: Some(format!("Failed to create parent directories: {}", e)),
+                error_code: None,
+            }));
+        }
+
+        // Create file with content
+        let content = req.content.as_deref().unwrap_or("");
+        let sanitized_content = security.sanitize_content(content);
+
+        match scan_for_malware(&security, &pools, &path, sanitized_content.clone().into_bytes()).await {
+            Ok(()) => {}
+            Err(ScanRejection::Infected(signature)) => {
+                return Ok(HttpResponse::Forbidden().json(CreateResponse {
+                    success: false,
+                    created_path: None,
+                    error: Some(format!("Content rejected by malware scanner: {}", signature)),
+                    error_code: None,
+                }));
+            }
+            Err(ScanRejection::Failed(e)) => {
+                error!("Malware scan failed for {}: {}", req.path, e);
                 return Ok(HttpResponse::Ok().json(CreateResponse {
                     success: false,
                     created_path: None,
-                    error: Some(format!("Failed to create parent directories: {}", e)),
+                    error: Some(format!("Malware scan failed: {}", e)),
+                    error_code: None,
                 }));
             }
         }
 
-        // Create file with content
-        let content = req.content.as_deref().unwrap_or("");
-        let sanitized_content = security.sanitize_content(content);
-        fs::write(&path, sanitized_content).await
+        if let Err(e) = check_disk_space(&security, &pools, &path, sanitized_content.len() as u64).await {
+            warn!("Create refused for {}: {}", req.path, e);
+            return Ok(HttpResponse::InsufficientStorage().json(CreateResponse {
+                success: false,
+                created_path: None,
+                error: Some(e),
+                error_code: Some(ErrorCode::InsufficientSpace),
+            }));
+        }
+
+        throttle_write(&security, &client, &req.path, sanitized_content.len() as u64).await;
+
+        match security.encrypt_for_path(&path, sanitized_content.into_bytes()) {
+            Ok(data) => {
+                let write_path = path.clone();
+                run_fs(&pools, move || write_with_mode(&write_path, &data, mode, false, Placement::Replace, false).map(|_| ()))
+                    .await
+            }
+            Err(e) => Err(std::io::Error::other(e)),
+        }
     };
 
     match result {
@@ -340,8 +2068,9 @@ pub async fn create_item(
             info!("Successfully created: {}", req.path);
             Ok(HttpResponse::Ok().json(CreateResponse {
                 success: true,
-                created_path: Some(path.to_string_lossy().to_string()),
+                created_path: Some(display_path(&path)),
                 error: None,
+                error_code: None,
             }))
         }
         Err(e) => {
@@ -350,6 +2079,7 @@ pub async fn create_item(
                 success: false,
                 created_path: None,
                 error: Some(format!("Failed to create: {}", e)),
+                error_code: None,
             }))
         }
     }
@@ -360,25 +2090,44 @@ pub async fn rename_item(
     security: web::Data<Arc<SecurityManager>>,
     req: web::Json<RenameRequest>,
 ) -> Result<HttpResponse> {
-    let from_path = PathBuf::from(&req.from_path);
-    let to_path = PathBuf::from(&req.to_path);
+    let from_path = extended_path(Path::new(&req.from_path));
+    let to_path = extended_path(Path::new(&req.to_path));
 
-    // Check permissions for both source and destination
-    if !security.is_path_allowed(&from_path) {
+    // Check permissions for both source and destination; workspace policies cannot be moved or replaced
+    if !security.is_path_allowed(&from_path) || security.is_workspace_policy_path(&from_path) {
         return Ok(HttpResponse::Forbidden().json(RenameResponse {
             success: false,
             old_path: None,
             new_path: None,
             error: Some(format!("Access denied to source path: {}", req.from_path)),
+            error_code: None,
         }));
     }
 
-    if !security.is_path_allowed(&to_path) {
+    if !security.is_path_allowed(&to_path) || security.is_workspace_policy_path(&to_path) {
         return Ok(HttpResponse::Forbidden().json(RenameResponse {
             success: false,
             old_path: None,
             new_path: None,
             error: Some(format!("Access denied to destination path: {}", req.to_path)),
+            error_code: None,
+        }));
+    }
+
+    // Moving a protected file away removes it; moving a file onto a protected path creates one
+    let force = req.force.unwrap_or(false);
+    if let Err(e) = security
+        .check_internal_tree(&from_path)
+        .and_then(|_| security.check_protected(&from_path, force))
+        .and_then(|_| security.check_protected(&to_path, force))
+    {
+        warn!("Rename refused for {} -> {}: {}", req.from_path, req.to_path, e);
+        return Ok(HttpResponse::Forbidden().json(RenameResponse {
+            success: false,
+            old_path: None,
+            new_path: None,
+            error: Some(e),
+            error_code: Some(ErrorCode::ProtectedPath),
         }));
     }
 
@@ -391,6 +2140,7 @@ pub async fn rename_item(
             old_path: None,
             new_path: None,
             error: Some(format!("Source path does not exist: {}", req.from_path)),
+            error_code: None,
         }));
     }
 
@@ -401,23 +2151,29 @@ pub async fn rename_item(
             old_path: None,
             new_path: None,
             error: Some(format!("Destination path already exists: {}", req.to_path)),
+            error_code: None,
         }));
     }
 
     // Create parent directory of destination if needed
-    if let Some(parent) = to_path.parent() {
-        if let Err(e) = fs::create_dir_all(parent).await {
-            error!("Failed to create parent directories for {}: {}", req.to_path, e);
-            return Ok(HttpResponse::Ok().json(RenameResponse {
-                success: false,
-                old_path: None,
-                new_path: None,
-                error: Some(format!("Failed to create parent directories: {}", e)),
-            }));
-        }
+    if let Some(parent) = to_path.parent()
+        && let Err(e) = fs::create_dir_all(parent).await
+    {
+        error!("Failed to create parent directories for {}: {}", req.to_path, e);
+        return Ok(HttpResponse::Ok().json(RenameResponse {
+            success: false,
+            old_path: None,
+            new_path: None,
+            error: Some(format!("Failed to create parent directories: {}", e)),
+            error_code: None,
+        }));
     }
 
-    match fs::rename(&from_path, &to_path).await {
+    let result = fs::rename(&from_path, &to_path).await;
+    security.invalidate_path_cache(&from_path);
+    security.invalidate_path_cache(&to_path);
+
+    match result {
         Ok(_) => {
             info!("Successfully renamed/moved: {} -> {}", req.from_path, req.to_path);
             Ok(HttpResponse::Ok().json(RenameResponse {
@@ -425,6 +2181,7 @@ pub async fn rename_item(
                 old_path: Some(req.from_path.clone()),
                 new_path: Some(req.to_path.clone()),
                 error: None,
+                error_code: None,
             }))
         }
         Err(e) => {
@@ -434,7 +2191,713 @@ pub async fn rename_item(
                 old_path: None,
                 new_path: None,
                 error: Some(format!("Failed to rename/move: {}", e)),
+                error_code: None,
+            }))
+        }
+    }
+}
+
+/// A copy worked out in full before anything is written
+struct CopyPlan {
+    /// Source and destination of each entry, directories before what they contain
+    entries: Vec<(PathBuf, PathBuf, bool)>,
+    bytes: u64,
+    skipped: Vec<String>,
+}
+
+/// Walks the source tree, leaving out symlinks, special files and entries the policy denies
+///
+/// A symlink given as the source itself is followed, as `/api/read` would.
+fn plan_copy(security: &SecurityManager, from: &Path, to: &Path, cancel: &CancelToken) -> io::Result<CopyPlan> {
+    let mut plan = CopyPlan {
+        entries: Vec::new(),
+        bytes: 0,
+        skipped: Vec::new(),
+    };
+    let mut stack = vec![(from.to_path_buf(), to.to_path_buf())];
+    while let Some((source, target)) = stack.pop() {
+        cancel.check()?;
+        let metadata = if source == from {
+            std::fs::metadata(&source)?
+        } else {
+            std::fs::symlink_metadata(&source)?
+        };
+        if !(metadata.is_dir() || metadata.is_file()) || !security.is_path_allowed(&source) {
+            plan.skipped.push(display_path(&source));
+        } else if metadata.is_dir() {
+            let mut children = std::fs::read_dir(&source)?.collect::<io::Result<Vec<_>>>()?;
+            children.sort_by_key(|child| std::cmp::Reverse(child.file_name()));
+            for child in children {
+                stack.push((extended_path(&child.path()), target.join(child.file_name())));
+            }
+            plan.entries.push((source, target, true));
+        } else {
+            plan.bytes += metadata.len();
+            plan.entries.push((source, target, false));
+        }
+    }
+    Ok(plan)
+}
+
+/// Why a copy stopped partway
+pub(crate) enum CopyFailure {
+    Refused(String, Option<ErrorCode>),
+    Io(io::Error),
+}
+
+impl From<io::Error> for CopyFailure {
+    fn from(e: io::Error) -> Self {
+        CopyFailure::Io(e)
+    }
+}
+
+/// Carries out a copy; files crossing into or out of an encrypted directory are re-encrypted
+///
+/// Every path is checked with the rules `/api/write` applies just before it is
+/// written, once the directories above it exist and it can be resolved. A
+/// refused entry stops the copy, as does cancellation; entries copied before are kept.
+fn execute_copy(security: &SecurityManager, plan: &CopyPlan, force: bool, cancel: &CancelToken) -> Result<(), CopyFailure> {
+    let dir_mode = security.dir_mode();
+    for (source, target, is_dir) in &plan.entries {
+        cancel.check()?;
+        if !security.is_path_allowed(target) || security.is_workspace_policy_path(target) {
+            let error = format!("Access denied to destination path: {}", display_path(target));
+            return Err(CopyFailure::Refused(error, None));
+        }
+        if let Err(e) = security.check_protected(target, force) {
+            return Err(CopyFailure::Refused(e, Some(ErrorCode::ProtectedPath)));
+        }
+        if *is_dir {
+            create_dir_all_with_mode(target, dir_mode)?;
+        } else if security.is_encrypted_path(source) || security.is_encrypted_path(target) {
+            let data = security.decrypt_for_path(source, std::fs::read(source)?).map_err(io::Error::other)?;
+            let data = security.encrypt_for_path(target, data).map_err(io::Error::other)?;
+            std::fs::write(target, data)?;
+        } else {
+            std::fs::copy(source, target)?;
+        }
+        security.invalidate_path_cache(target);
+    }
+    Ok(())
+}
+
+/// Canonical form of a path that may not exist yet, resolved through its deepest existing ancestor
+pub(crate) fn canonical_target(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find_map(|ancestor| {
+            let resolved = ancestor.canonicalize().ok()?;
+            Some(resolved.join(path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Handles file and directory copy requests
+///
+/// Content is copied byte for byte, so binary files and large trees need no
+/// round trip through `/api/read` and `/api/write`.
+pub async fn copy_item(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    operations: web::Data<Arc<OperationRegistry>>,
+    client: ClientId,
+    req: web::Json<CopyRequest>,
+) -> Result<HttpResponse> {
+    let from_path = extended_path(Path::new(&req.from_path));
+    let to_path = extended_path(Path::new(&req.to_path));
+    let failure = |error: String, error_code: Option<ErrorCode>| CopyResponse {
+        success: false,
+        from_path: None,
+        to_path: None,
+        copied_count: None,
+        copied_bytes: None,
+        skipped: None,
+        error: Some(error),
+        error_code,
+    };
+
+    // The source is only read; the destination gets the checks of a write
+    if !security.is_path_allowed(&from_path) {
+        let error = format!("Access denied to source path: {}", req.from_path);
+        return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+    }
+    if !security.is_path_allowed(&to_path) || security.is_workspace_policy_path(&to_path) {
+        let error = format!("Access denied to destination path: {}", req.to_path);
+        return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+    }
+
+    let force = req.force.unwrap_or(false);
+    if let Err(e) = security.check_protected(&to_path, force) {
+        warn!("Copy refused for {} -> {}: {}", req.from_path, req.to_path, e);
+        return Ok(HttpResponse::Forbidden().json(failure(e, Some(ErrorCode::ProtectedPath))));
+    }
+
+    if !from_path.exists() {
+        let error = format!("Source path does not exist: {}", req.from_path);
+        return Ok(HttpResponse::Ok().json(failure(error, None)));
+    }
+
+    let recursive = req.recursive.unwrap_or(false);
+    if from_path.is_dir() && !recursive {
+        let error = format!("Copying a directory requires recursive: {}", req.from_path);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    }
+
+    // Copying a file onto itself would truncate it, and a directory into itself would never end
+    let (from_real, to_real) = (canonical_target(&from_path), canonical_target(&to_path));
+    if to_real == from_real || (from_path.is_dir() && to_real.starts_with(&from_real)) {
+        let error = format!("Cannot copy {} into itself", req.from_path);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    }
+
+    if to_path.exists() {
+        let error = if !req.overwrite.unwrap_or(false) {
+            Some(format!("Destination path already exists: {}", req.to_path))
+        } else if from_path.is_dir() != to_path.is_dir() {
+            Some(format!("Cannot replace {} with a different kind of entry", req.to_path))
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            return Ok(HttpResponse::Ok().json(failure(error, None)));
+        }
+    }
+
+    if from_path.is_dir()
+        && !req.override_entry_limit.unwrap_or(false)
+        && let Err(e) = check_affected_entries(&security, &pools, &from_path).await
+    {
+        warn!("Copy refused for {} -> {}: {}", req.from_path, req.to_path, e);
+        return Ok(HttpResponse::Forbidden().json(failure(e, Some(ErrorCode::TooManyEntries))));
+    }
+
+    let summary = format!("{} -> {}", req.from_path, req.to_path);
+    let operation = match operations.start(req.operation_id.as_deref(), "copy", &req.from_path, summary) {
+        Ok(operation) => operation,
+        Err(e) => return Ok(HttpResponse::build(start_failure_status(&e)).json(failure(e.to_string(), None))),
+    };
+
+    let planner = security.get_ref().clone();
+    let (source, target, cancel) = (from_path.clone(), to_path.clone(), operation.token());
+    let plan = match run_fs(&pools, move || plan_copy(&planner, &source, &target, &cancel)).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            error!("Failed to copy {} -> {}: {}", req.from_path, req.to_path, e);
+            return Ok(HttpResponse::Ok().json(failure(format!("Failed to copy: {}", e), cancellation_code(&e))));
+        }
+    };
+
+    if let Err(e) = check_disk_space(&security, &pools, &to_path, plan.bytes).await {
+        warn!("Copy refused for {} -> {}: {}", req.from_path, req.to_path, e);
+        return Ok(HttpResponse::InsufficientStorage().json(failure(e, Some(ErrorCode::InsufficientSpace))));
+    }
+
+    throttle_write(&security, &client, &req.to_path, plan.bytes).await;
+
+    info!("Copying: {} -> {} ({} entries, {} bytes)", req.from_path, req.to_path, plan.entries.len(), plan.bytes);
+    let (copier, cancel) = (security.get_ref().clone(), operation.token());
+    cancel.set_total(plan.entries.len());
+    let result = run_fs(&pools, move || Ok(execute_copy(&copier, &plan, force, &cancel).map(|_| plan))).await;
+    security.invalidate_path_cache(&to_path);
+
+    match result {
+        Ok(Ok(plan)) => {
+            info!("Successfully copied: {} -> {}", req.from_path, req.to_path);
+            Ok(HttpResponse::Ok().json(CopyResponse {
+                success: true,
+                from_path: Some(req.from_path.clone()),
+                to_path: Some(req.to_path.clone()),
+                copied_count: Some(plan.entries.len()),
+                copied_bytes: Some(plan.bytes),
+                skipped: Some(plan.skipped),
+                error: None,
+                error_code: None,
             }))
         }
+        Ok(Err(CopyFailure::Refused(e, error_code))) => {
+            warn!("Copy refused for {} -> {}: {}", req.from_path, req.to_path, e);
+            Ok(HttpResponse::Forbidden().json(failure(e, error_code)))
+        }
+        Ok(Err(CopyFailure::Io(e))) | Err(e) => {
+            error!("Failed to copy {} -> {}: {}", req.from_path, req.to_path, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to copy: {}", e), cancellation_code(&e))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, is_directory: bool, size: Option<u64>, modified: u64) -> FileInfo {
+        FileInfo {
+            name: name.to_string(),
+            path: format!("/data/{}", name),
+            is_directory,
+            size,
+            modified: None,
+            created: None,
+            accessed: None,
+            modified_epoch: Some(modified),
+            created_epoch: None,
+            accessed_epoch: None,
+            permissions: None,
+            is_hidden: false,
+            is_system: false,
+            attributes: None,
+            kind: None,
+            special: None,
+            sparse: None,
+            uid: None,
+            gid: None,
+            owner: None,
+            group: None,
+            owner_sid: None,
+            stats: None,
+        }
+    }
+
+    fn names(items: &[FileInfo]) -> Vec<&str> {
+        items.iter().map(|i| i.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_items() {
+        let mut items = vec![
+            entry("b.txt", false, Some(30), 1),
+            entry("Zeta", true, None, 3),
+            entry("a.rs", false, Some(10), 2),
+            entry("docs", true, None, 0),
+        ];
+
+        sort_items(&mut items, Some(ScanSortKey::Name), false);
+        assert_eq!(names(&items), ["a.rs", "b.txt", "docs", "Zeta"]);
+
+        sort_items(&mut items, Some(ScanSortKey::Name), true);
+        assert_eq!(names(&items), ["docs", "Zeta", "a.rs", "b.txt"]);
+
+        // Directories have no size, so the path decides between them
+        sort_items(&mut items, Some(ScanSortKey::Size), true);
+        assert_eq!(names(&items), ["Zeta", "docs", "a.rs", "b.txt"]);
+
+        sort_items(&mut items, Some(ScanSortKey::Mtime), false);
+        assert_eq!(names(&items), ["docs", "b.txt", "a.rs", "Zeta"]);
+
+        sort_items(&mut items, Some(ScanSortKey::Type), false);
+        assert_eq!(names(&items), ["Zeta", "docs", "a.rs", "b.txt"]);
+    }
+
+    #[test]
+    fn test_names_only_scan() {
+        let dir = std::env::temp_dir().join(format!("exex-names-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        std::fs::write(dir.join("sub").join("b.txt"), "b").unwrap();
+        std::fs::write(dir.join(".hidden"), "").unwrap();
+
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let ignore = security.ignore_matcher(&dir, Some(&[])).unwrap();
+        let options = ScanOptions {
+            recursive: true,
+            max_depth: None,
+            include_hidden: false,
+            rfc3339_times: true,
+            names_only: true,
+            omit_private: false,
+            include_owner: false,
+            sniff_content: false,
+        };
+        let mut items = Vec::new();
+        walk_directory(std::fs::read_dir(&dir).unwrap(), options, &ignore, &security, &CancelToken::default(), &mut items).unwrap();
+        sort_items(&mut items, Some(ScanSortKey::Name), false);
+
+        assert_eq!(names(&items), ["a.txt", "b.txt", "sub"]);
+        assert!(items[2].is_directory && !items[0].is_directory);
+        assert!(items.iter().all(|i| i.size.is_none() && i.modified_epoch.is_none()));
+
+        // One level lists the directory's own entries without descending
+        let mut items = Vec::new();
+        let options = ScanOptions { max_depth: Some(1), ..options };
+        walk_directory(std::fs::read_dir(&dir).unwrap(), options, &ignore, &security, &CancelToken::default(), &mut items).unwrap();
+        sort_items(&mut items, Some(ScanSortKey::Name), false);
+        assert_eq!(names(&items), ["a.txt", "sub"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_aggregate_scan() {
+        let dir = std::env::temp_dir().join(format!("exex-aggregate-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("sub").join("deep")).unwrap();
+        std::fs::create_dir_all(dir.join("skipped")).unwrap();
+        std::fs::write(dir.join("a.txt"), "aaaa").unwrap();
+        std::fs::write(dir.join("sub").join("b.txt"), "bb").unwrap();
+        std::fs::write(dir.join("sub").join("deep").join("c.txt"), "c").unwrap();
+        std::fs::write(dir.join("skipped").join("d.txt"), "ddd").unwrap();
+
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let ignore = security.ignore_matcher(&dir, Some(&["skipped/".to_string()])).unwrap();
+        let options = ScanOptions {
+            recursive: true,
+            max_depth: None,
+            include_hidden: false,
+            rfc3339_times: false,
+            names_only: false,
+            omit_private: false,
+            include_owner: false,
+            sniff_content: false,
+        };
+        let mut sink = WalkedSink::default();
+        walk_directory(std::fs::read_dir(&dir).unwrap(), options, &ignore, &security, &CancelToken::default(), &mut sink).unwrap();
+        let WalkedSink { mut items, walked } = sink;
+        // A trailing separator on the requested path does not matter
+        let root = format!("{}/", display_path(&dir));
+        let totals = aggregate_directories(&mut items, &root, &walked, false);
+
+        assert_eq!((totals.files, totals.directories, totals.bytes), (3, 2, 7));
+        assert!(totals.newest_modified_epoch.is_some());
+        assert_eq!(totals.newest_modified, totals.newest_modified_epoch.map(|secs| secs.to_string()));
+        sort_items(&mut items, Some(ScanSortKey::Name), false);
+        let stats = |name: &str| {
+            let item = items.iter().find(|i| i.name == name).unwrap();
+            item.stats.as_ref().map(|s| (s.files, s.directories, s.bytes))
+        };
+        assert_eq!(stats("sub"), Some((2, 1, 3)));
+        assert_eq!(stats("deep"), Some((1, 0, 1)));
+        assert_eq!(stats("a.txt"), None);
+
+        // Directories that were listed but not walked carry no totals
+        let mut items = Vec::new();
+        let options = ScanOptions { recursive: false, ..options };
+        walk_directory(std::fs::read_dir(&dir).unwrap(), options, &ignore, &security, &CancelToken::default(), &mut items).unwrap();
+        let totals = aggregate_directories(&mut items, &display_path(&dir), &HashSet::new(), false);
+        assert_eq!((totals.files, totals.directories, totals.bytes), (1, 1, 4));
+        assert!(items.iter().all(|i| i.stats.is_none()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_entries() {
+        let dir = std::env::temp_dir().join(format!("exex-find-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("src").join("bin")).unwrap();
+        std::fs::create_dir_all(dir.join("generated")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        for file in ["Cargo.toml", "src/lib.rs", "src/bin/cli.rs", "generated/api.rs", "target/out.rs", ".hidden.rs"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        std::fs::write(dir.join(".gitignore"), "generated/\n").unwrap();
+
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let plan = |patterns: &[&str]| FindPlan {
+            root: dir.clone(),
+            patterns: build_glob_set(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap(),
+            exclude: None,
+            ignore: security.ignore_matcher(&dir, None).unwrap(),
+            gitignore: None,
+            include_directories: false,
+            options: ScanOptions {
+                recursive: true,
+                max_depth: None,
+                include_hidden: false,
+                rfc3339_times: true,
+                names_only: false,
+                omit_private: false,
+                include_owner: false,
+                sniff_content: false,
+            },
+        };
+        let find = |mut plan: FindPlan| {
+            let items = find_entries(&security, &mut plan).unwrap();
+            names(&items).into_iter().map(str::to_string).collect::<Vec<_>>()
+        };
+
+        // Ignored and hidden entries are left out; files come before subdirectories
+        assert_eq!(find(plan(&["**/*.rs"])), ["api.rs", "lib.rs", "cli.rs"]);
+        // Anchored patterns match from the root
+        assert_eq!(find(plan(&["src/*.rs", "Cargo.toml"])), ["Cargo.toml", "lib.rs"]);
+
+        let mut gitignored = plan(&["*.rs"]);
+        gitignored.gitignore = Some(GitIgnores::for_root(&dir));
+        assert_eq!(find(gitignored), ["lib.rs", "cli.rs"]);
+
+        let mut directories = plan(&["src/*"]);
+        directories.include_directories = true;
+        directories.exclude = Some(build_glob_set(&["bin".to_string()]).unwrap());
+        assert_eq!(find(directories), ["lib.rs"]);
+
+        // Pages continue in name order like scan pages
+        let first = paginate(find_entries(&security, &mut plan(&["*.rs"])).unwrap(), None, Some(2)).unwrap();
+        assert_eq!((names(&first.items), first.total), (vec!["api.rs", "lib.rs"], 3));
+        let rest = paginate(find_entries(&security, &mut plan(&["*.rs"])).unwrap(), first.next_cursor.as_deref(), Some(2)).unwrap();
+        assert_eq!(names(&rest.items), ["cli.rs"]);
+        assert!(rest.next_cursor.is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_private_metadata_omitted() {
+        let dir = std::env::temp_dir().join(format!("exex-private-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let ignore = security.ignore_matcher(&dir, Some(&[])).unwrap();
+        let options = ScanOptions {
+            recursive: false,
+            max_depth: None,
+            include_hidden: false,
+            rfc3339_times: true,
+            names_only: false,
+            omit_private: true,
+            include_owner: false,
+            sniff_content: false,
+        };
+        let mut items = Vec::new();
+        walk_directory(std::fs::read_dir(&dir).unwrap(), options, &ignore, &security, &CancelToken::default(), &mut items).unwrap();
+
+        assert!(items[0].modified.is_some() && items[0].modified_epoch.is_some());
+        assert!(items[0].created.is_none() && items[0].created_epoch.is_none());
+        assert!(items[0].accessed.is_none() && items[0].accessed_epoch.is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_binary_write_round_trip() {
+        use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+        use actix_web::App;
+
+        let dir = std::env::temp_dir().join(format!("exex-binary-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = crate::config::get_default_config();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+                .route("/write", web::post().to(write_file))
+                .route("/read", web::post().to(read_file)),
+        )
+        .await;
+
+        // NUL bytes and lone carriage returns would be stripped or normalized as text
+        let bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\xff".to_vec();
+        let path = dir.join("image.png").to_string_lossy().to_string();
+        let write = TestRequest::post()
+            .uri("/write")
+            .set_json(serde_json::json!({"path": path, "content": BASE64.encode(&bytes), "encoding": "base64", "verify": true}))
+            .to_request();
+        let written: serde_json::Value = read_body_json(call_service(&app, write).await).await;
+        assert_eq!(written["success"], true);
+        assert_eq!(written["checksum"], sha256_hex(&bytes));
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+
+        let read = TestRequest::post()
+            .uri("/read")
+            .set_json(serde_json::json!({"path": path, "encoding": "base64"}))
+            .to_request();
+        let read: serde_json::Value = read_body_json(call_service(&app, read).await).await;
+        assert_eq!(read["content"], BASE64.encode(&bytes));
+
+        let invalid = TestRequest::post()
+            .uri("/write")
+            .set_json(serde_json::json!({"path": path, "content": "not base64!", "encoding": "base64"}))
+            .to_request();
+        assert_eq!(call_service(&app, invalid).await.status(), 400);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_key_file_not_readable() {
+        use actix_web::test::{call_service, init_service, TestRequest};
+        use actix_web::App;
+
+        let dir = std::env::temp_dir().join(format!("exex-key-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = dir.join("exex.key");
+        std::fs::write(&key, [7u8; 32]).unwrap();
+        let mut config = crate::config::get_default_config();
+        config.security.encryption_key_file = Some(key.to_string_lossy().to_string());
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+                .route("/api/read", web::post().to(read_file))
+                .route("/api/write", web::post().to(write_file)),
+        )
+        .await;
+
+        // The temp dir is an allowed path by default; the key is denied anyway
+        let read = TestRequest::post()
+            .uri("/api/read")
+            .set_json(serde_json::json!({"path": key.to_string_lossy()}))
+            .to_request();
+        assert_eq!(call_service(&app, read).await.status(), 403);
+
+        let write = TestRequest::post()
+            .uri("/api/write")
+            .set_json(serde_json::json!({"path": key.to_string_lossy(), "content": "replaced"}))
+            .to_request();
+        assert_eq!(call_service(&app, write).await.status(), 403);
+        assert_eq!(std::fs::read(&key).unwrap(), [7u8; 32]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_download_ranges() {
+        use actix_web::test::{call_service, init_service, read_body, TestRequest};
+        use actix_web::App;
+
+        let dir = std::env::temp_dir().join(format!("exex-download-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("page.html");
+        std::fs::write(&path, "<html>0123456789</html>").unwrap();
+        let config = crate::config::get_default_config();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+                .route("/download", web::get().to(download_file)),
+        )
+        .await;
+        let uri = format!("/download?path={}", path.display());
+
+        let full = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(full.status(), 200);
+        assert_eq!(full.headers().get(header::CONTENT_TYPE).unwrap(), "text/html; charset=utf-8");
+        assert_eq!(full.headers().get(header::CONTENT_LENGTH).unwrap(), "23");
+        assert_eq!(full.headers().get(header::CONTENT_SECURITY_POLICY).unwrap(), "sandbox");
+        assert_eq!(read_body(full).await, "<html>0123456789</html>");
+
+        let partial = TestRequest::get().uri(&uri).insert_header((header::RANGE, "bytes=6-9")).to_request();
+        let partial = call_service(&app, partial).await;
+        assert_eq!(partial.status(), 206);
+        assert_eq!(partial.headers().get(header::CONTENT_RANGE).unwrap(), "bytes 6-9/23");
+        assert_eq!(read_body(partial).await, "0123");
+
+        let stale = TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::RANGE, "bytes=6-9"))
+            .insert_header((header::IF_RANGE, "Thu, 01 Jan 1970 00:00:00 GMT"))
+            .to_request();
+        assert_eq!(call_service(&app, stale).await.status(), 200);
+
+        let beyond = TestRequest::get().uri(&uri).insert_header((header::RANGE, "bytes=100-")).to_request();
+        let beyond = call_service(&app, beyond).await;
+        assert_eq!(beyond.status(), 416);
+        assert_eq!(beyond.headers().get(header::CONTENT_RANGE).unwrap(), "bytes */23");
+
+        let missing = format!("/download?path={}", dir.join("gone").display());
+        assert_eq!(call_service(&app, TestRequest::get().uri(&missing).to_request()).await.status(), 404);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_write_modes() {
+        use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+        use actix_web::App;
+
+        let dir = std::env::temp_dir().join(format!("exex-write-modes-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = crate::config::get_default_config();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+                .route("/write", web::post().to(write_file)),
+        )
+        .await;
+        let path = dir.join("app.log");
+        let write = |extra: serde_json::Value| {
+            let mut body = serde_json::json!({"path": path.to_string_lossy(), "verify": true});
+            body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            TestRequest::post().uri("/write").set_json(body).to_request()
+        };
+
+        let created: serde_json::Value =
+            read_body_json(call_service(&app, write(serde_json::json!({"content": "one\n", "mode": "create_new"}))).await).await;
+        assert_eq!(created["success"], true);
+        let again: serde_json::Value =
+            read_body_json(call_service(&app, write(serde_json::json!({"content": "two\n", "mode": "create_new"}))).await).await;
+        assert_eq!(again["success"], false);
+
+        let appended: serde_json::Value =
+            read_body_json(call_service(&app, write(serde_json::json!({"content": "two\n", "mode": "append"}))).await).await;
+        assert_eq!(appended["success"], true);
+        // The checksum covers the bytes written, read back from where they went
+        assert_eq!(appended["checksum"], sha256_hex(b"two\n"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+
+        let patched = call_service(&app, write(serde_json::json!({"content": "TW", "offset": 4}))).await;
+        assert_eq!(patched.status(), 200);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\nTWo\n");
+
+        let past_end = write(serde_json::json!({"content": "x", "offset": 100}));
+        assert_eq!(call_service(&app, past_end).await.status(), 400);
+        let append_at = write(serde_json::json!({"content": "x", "offset": 0, "mode": "append"}));
+        assert_eq!(call_service(&app, append_at).await.status(), 400);
+        let validated = write(serde_json::json!({"content": "x", "mode": "append", "validate": true}));
+        assert_eq!(call_service(&app, validated).await.status(), 400);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\nTWo\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_copy_tree() {
+        use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+        use actix_web::App;
+
+        let dir = std::env::temp_dir().join(format!("exex-copy-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("assets").join("icons")).unwrap();
+        let bytes = b"\x89PNG\r\n\x1a\n\0\xff".to_vec();
+        std::fs::write(dir.join("assets").join("icons").join("logo.png"), &bytes).unwrap();
+        std::fs::write(dir.join("assets").join("style.css"), "body {}").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc/hostname", dir.join("assets").join("link")).unwrap();
+        let config = crate::config::get_default_config();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+                .app_data(web::Data::new(Arc::new(OperationRegistry::new())))
+                .route("/copy", web::post().to(copy_item)),
+        )
+        .await;
+        let copy = |from: &Path, to: &Path, extra: serde_json::Value| {
+            let mut body = serde_json::json!({"from_path": from.to_string_lossy(), "to_path": to.to_string_lossy()});
+            body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            TestRequest::post().uri("/copy").set_json(body).to_request()
+        };
+
+        let (assets, dist) = (dir.join("assets"), dir.join("dist"));
+        let refused = call_service(&app, copy(&assets, &dist, serde_json::json!({}))).await;
+        assert_eq!(refused.status(), 400);
+        let inside = call_service(&app, copy(&assets, &assets.join("nested"), serde_json::json!({"recursive": true}))).await;
+        assert_eq!(inside.status(), 400);
+
+        let copied: serde_json::Value =
+            read_body_json(call_service(&app, copy(&assets, &dist, serde_json::json!({"recursive": true}))).await).await;
+        assert_eq!(copied["success"], true);
+        assert_eq!(copied["copied_bytes"], bytes.len() + 7);
+        assert_eq!(std::fs::read(dist.join("icons").join("logo.png")).unwrap(), bytes);
+        // Symlinks inside the tree are reported rather than followed
+        #[cfg(unix)]
+        assert_eq!(copied["skipped"].as_array().unwrap().len(), 1);
+        assert!(!dist.join("link").exists());
+
+        let css = dist.join("style.css");
+        let exists: serde_json::Value =
+            read_body_json(call_service(&app, copy(&dir.join("assets").join("style.css"), &css, serde_json::json!({}))).await).await;
+        assert_eq!(exists["success"], false);
+        std::fs::write(dir.join("new.css"), "p {}").unwrap();
+        let replaced = call_service(&app, copy(&dir.join("new.css"), &css, serde_json::json!({"overwrite": true}))).await;
+        assert_eq!(replaced.status(), 200);
+        assert_eq!(std::fs::read_to_string(&css).unwrap(), "p {}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
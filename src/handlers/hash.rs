@@ -0,0 +1,175 @@
+use actix_web::{web, HttpResponse, Result};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use xxhash_rust::xxh3::Xxh3;
+use xxhash_rust::xxh64::Xxh64;
+
+use crate::handlers::file_ops::{run_fs, special_file_error};
+use crate::handlers::stream::STREAM_CHUNK_SIZE;
+use crate::models::{ErrorCode, HashAlgorithm, HashRequest, HashResponse};
+use crate::platform::{extended_path, open_for_read, read_bytes, special_file_at};
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Running state of one of the supported digests
+enum ContentHasher {
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(Md5),
+    Xxh64(Xxh64),
+    Xxh3(Box<Xxh3>),
+}
+
+impl ContentHasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => ContentHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha1 => ContentHasher::Sha1(Sha1::new()),
+            HashAlgorithm::Md5 => ContentHasher::Md5(Md5::new()),
+            HashAlgorithm::Xxh64 => ContentHasher::Xxh64(Xxh64::new(0)),
+            HashAlgorithm::Xxh3 => ContentHasher::Xxh3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            ContentHasher::Sha256(hasher) => hasher.update(data),
+            ContentHasher::Sha1(hasher) => hasher.update(data),
+            ContentHasher::Md5(hasher) => hasher.update(data),
+            ContentHasher::Xxh64(hasher) => hasher.update(data),
+            ContentHasher::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Lowercase hex digest; xxHash values are written big-endian, as `xxhsum` prints them
+    fn finish(self) -> String {
+        match self {
+            ContentHasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+            ContentHasher::Sha1(hasher) => hex::encode(hasher.finalize()),
+            ContentHasher::Md5(hasher) => hex::encode(hasher.finalize()),
+            ContentHasher::Xxh64(hasher) => format!("{:016x}", hasher.digest()),
+            ContentHasher::Xxh3(hasher) => format!("{:016x}", hasher.digest()),
+        }
+    }
+}
+
+/// Hashes a file's plaintext and returns the digest with the number of bytes hashed
+///
+/// Plain files are read in chunks, so any size hashes in constant memory.
+/// Encrypted files are decrypted whole first, as `/api/read` does.
+fn hash_file(security: &SecurityManager, path: &Path, algorithm: HashAlgorithm) -> io::Result<(String, u64)> {
+    let mut hasher = ContentHasher::new(algorithm);
+    if security.is_encrypted_path(path) {
+        let data = security.decrypt_for_path(path, read_bytes(path)?).map_err(io::Error::other)?;
+        hasher.update(&data);
+        return Ok((hasher.finish(), data.len() as u64));
+    }
+
+    let mut file = open_for_read(path)?;
+    let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+    let mut size = 0;
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((hasher.finish(), size))
+}
+
+/// Handles requests for a file's checksum, so clients can verify transfers without downloading the file
+pub async fn hash_file_content(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: web::Json<HashRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+    let algorithm = req.algorithm.unwrap_or_default();
+    let failure = |error: String, error_code: Option<ErrorCode>| HashResponse {
+        success: false,
+        path: None,
+        algorithm: None,
+        hash: None,
+        size: None,
+        matches: None,
+        error: Some(error),
+        error_code,
+    };
+
+    if !security.is_path_allowed(&path) {
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to file: {}", req.path), None)));
+    }
+    if let Some(special) = special_file_at(&path) {
+        warn!("Refusing to hash {}: it is a {}", req.path, special);
+        let error = special_file_error(&req.path, special);
+        return Ok(HttpResponse::BadRequest().json(failure(error, Some(ErrorCode::SpecialFile))));
+    }
+    if path.is_dir() {
+        return Ok(HttpResponse::BadRequest().json(failure(format!("Path is a directory: {}", req.path), None)));
+    }
+
+    let hasher = security.get_ref().clone();
+    let target = path.clone();
+    match run_fs(&pools, move || hash_file(&hasher, &target, algorithm)).await {
+        Ok((hash, size)) => {
+            info!("Hashed {} ({} bytes)", req.path, size);
+            Ok(HttpResponse::Ok().json(HashResponse {
+                success: true,
+                path: Some(req.path.clone()),
+                algorithm: Some(algorithm),
+                matches: req.expected.as_ref().map(|expected| expected.trim().eq_ignore_ascii_case(&hash)),
+                hash: Some(hash),
+                size: Some(size),
+                error: None,
+                error_code: None,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to hash {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to hash file: {}", e), None)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_file() {
+        let path = std::env::temp_dir().join(format!("exex-hash-{}", uuid::Uuid::new_v4().simple()));
+        // Spans several read chunks
+        std::fs::write(&path, b"abc".repeat(STREAM_CHUNK_SIZE)).unwrap();
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let expected = |algorithm| {
+            let mut hasher = ContentHasher::new(algorithm);
+            hasher.update(&b"abc".repeat(STREAM_CHUNK_SIZE));
+            hasher.finish()
+        };
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Md5, HashAlgorithm::Xxh3] {
+            let (hash, size) = hash_file(&security, &path, algorithm).unwrap();
+            assert_eq!(hash, expected(algorithm));
+            assert_eq!(size, 3 * STREAM_CHUNK_SIZE as u64);
+        }
+
+        // Known digests of "abc"
+        std::fs::write(&path, b"abc").unwrap();
+        let hash = |algorithm| hash_file(&security, &path, algorithm).unwrap().0;
+        assert_eq!(hash(HashAlgorithm::Sha256), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hash(HashAlgorithm::Sha1), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hash(HashAlgorithm::Md5), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hash(HashAlgorithm::Xxh64), "44bc2cf5ad770999");
+        assert_eq!(hash(HashAlgorithm::Xxh3), "78af5f94892f3950");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
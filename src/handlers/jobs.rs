@@ -0,0 +1,489 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::handlers::exec::{ExecServices, PreparedExec, Refusal};
+use crate::handlers::file_ops::send_file;
+use crate::jobs::artifacts::{ArtifactPatterns, ArtifactStore};
+use crate::jobs::queue::{CancelError, JobQueue};
+use crate::jobs::graph::{JobGraph, Schedule};
+use crate::jobs::{is_job_id, new_job_id, JobLog, JobLogs};
+use crate::models::{
+    ContentEncoding, ErrorResponse, JobArtifactsResponse, JobGroupInfo, JobGroupRequest, JobGroupResponse, JobInfo,
+    JobLogQuery, JobLogResponse, JobRequest, JobResponse, JobState, LogStream,
+};
+use crate::platform::process_tree::TreeKiller;
+use crate::power::PowerManager;
+use crate::processes::ProcessRegistry;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Bytes returned by one log read unless `limit` is given
+const DEFAULT_LOG_CHUNK: u64 = 1024 * 1024;
+
+/// Bytes of the latest output of each stream returned while a job runs
+const OUTPUT_TAIL: u64 = 64 * 1024;
+
+fn job_response(job: Option<JobInfo>, error: Option<String>) -> JobResponse {
+    JobResponse {
+        success: error.is_none(),
+        job,
+        stdout: None,
+        stderr: None,
+        error,
+    }
+}
+
+/// A job waiting in the queue, with everything it needs to run
+struct QueuedJob {
+    id: String,
+    prepared: PreparedExec,
+    killer: Arc<TreeKiller>,
+    log: Option<JobLog>,
+}
+
+/// Checks a job against the policy under a fresh job ID; nothing is queued yet
+fn prepare_job(
+    security: &Arc<SecurityManager>,
+    artifacts: &Arc<ArtifactStore>,
+    req: JobRequest,
+) -> std::result::Result<(String, PreparedExec), Refusal> {
+    let JobRequest { exec, artifacts: patterns } = req;
+    let patterns = patterns.as_deref().map(ArtifactPatterns::new).transpose().map_err(Refusal::Invalid)?;
+    let prepared = PreparedExec::prepare(security, exec)?;
+    let id = new_job_id();
+    let prepared = match patterns {
+        Some(patterns) => prepared.with_artifacts(security, artifacts.clone(), patterns, &id),
+        None => prepared,
+    };
+    Ok((id, prepared))
+}
+
+impl QueuedJob {
+    /// Records a prepared job as queued
+    ///
+    /// The log exists from the start, so `/api/jobs/{id}/logs` works for queued jobs too.
+    async fn submit(
+        id: String,
+        prepared: PreparedExec,
+        pools: &WorkerPools,
+        logs: &Arc<JobLogs>,
+        queue: &JobQueue,
+    ) -> (Self, JobInfo) {
+        let log = if logs.is_enabled() {
+            let (logs, log_id) = (logs.clone(), id.clone());
+            match pools.fs.run(move || logs.create_as(log_id)).await {
+                Ok(Ok(log)) => Some(log),
+                Ok(Err(e)) => {
+                    warn!("Output of job {} will not be logged: {}", id, e);
+                    None
+                }
+                Err(e) => {
+                    warn!("Output of job {} will not be logged: {}", id, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let killer = Arc::new(TreeKiller::default());
+        let info = queue.submit(&id, prepared.name(), killer.clone());
+        info!("Queued job {}: {}", id, prepared.name());
+        (Self { id, prepared, killer, log }, info)
+    }
+
+    /// Runs the job and records how it ended; returns whether it succeeded
+    async fn run(self, services: &ExecServices, queue: &Arc<JobQueue>) -> bool {
+        let started = {
+            let (queue, id) = (queue.clone(), self.id.clone());
+            move |pid| queue.started(&id, pid)
+        };
+        let result = self.prepared.run(services, self.killer, self.log, started).await;
+        if let Err(e) = &result {
+            warn!("Job {} did not run: {}", self.id, e);
+        }
+        let succeeded = matches!(&result, Ok(response) if response.success);
+        queue.ended(&self.id, result.map_err(|e| e.to_string()));
+        succeeded
+    }
+}
+
+/// Queues a command to run in the background and returns its job ID at once
+///
+/// Takes the same body as `/api/exec` and applies the same policy, so long
+/// builds do not hold a connection open and any client can follow them.
+/// Files matching `artifacts` are kept once the job ends.
+#[allow(clippy::too_many_arguments)]
+pub async fn submit_job(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    power: web::Data<Arc<PowerManager>>,
+    logs: web::Data<Arc<JobLogs>>,
+    queue: web::Data<Arc<JobQueue>>,
+    artifacts: web::Data<Arc<ArtifactStore>>,
+    req: web::Json<JobRequest>,
+) -> Result<HttpResponse> {
+    let (id, prepared) = match prepare_job(&security, &artifacts, req.into_inner()) {
+        Ok(prepared) => prepared,
+        Err(refusal) => return Ok(refusal.into_response()),
+    };
+    let (job, info) = QueuedJob::submit(id, prepared, &pools, &logs, &queue).await;
+
+    let services = ExecServices::new(&pools, &registry, &power, &logs);
+    let queue = queue.get_ref().clone();
+    actix_web::rt::spawn(async move {
+        job.run(&services, &queue).await;
+    });
+
+    Ok(HttpResponse::Accepted().json(job_response(Some(info), None)))
+}
+
+fn group_response(group: Option<JobGroupInfo>, error: Option<String>) -> JobGroupResponse {
+    JobGroupResponse {
+        success: error.is_none(),
+        group,
+        error,
+    }
+}
+
+/// Queues jobs that wait for the jobs they `depends_on` and returns the group's ID at once
+///
+/// Each job takes the same body as `/api/jobs` plus a `name`; the whole group
+/// is refused if any job is. A job starts once all its dependencies
+/// succeeded, and is skipped once one of them did not.
+#[allow(clippy::too_many_arguments)]
+pub async fn submit_job_group(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    power: web::Data<Arc<PowerManager>>,
+    logs: web::Data<Arc<JobLogs>>,
+    queue: web::Data<Arc<JobQueue>>,
+    artifacts: web::Data<Arc<ArtifactStore>>,
+    req: web::Json<JobGroupRequest>,
+) -> Result<HttpResponse> {
+    let JobGroupRequest { jobs, max_parallel } = req.into_inner();
+    if jobs.is_empty() || max_parallel == Some(0) {
+        let error = "A job group needs at least one job and a 'max_parallel' of at least 1".to_string();
+        return Ok(HttpResponse::BadRequest().json(group_response(None, Some(error))));
+    }
+    let edges: Vec<(String, Vec<String>)> =
+        jobs.iter().map(|job| (job.name.clone(), job.depends_on.clone().unwrap_or_default())).collect();
+    let graph = match JobGraph::new(&edges) {
+        Ok(graph) => graph,
+        Err(error) => return Ok(HttpResponse::BadRequest().json(group_response(None, Some(error)))),
+    };
+
+    // Every job passes the policy before any is queued
+    let mut prepared = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        match prepare_job(&security, &artifacts, job.job) {
+            Ok(ready) => prepared.push(ready),
+            Err(refusal) => return Ok(refusal.for_job(&job.name).into_response()),
+        }
+    }
+    let mut queued = Vec::with_capacity(prepared.len());
+    let mut members = Vec::with_capacity(prepared.len());
+    for ((id, prepared), (name, depends_on)) in prepared.into_iter().zip(edges) {
+        members.push((name, depends_on, id.clone()));
+        queued.push(Some(QueuedJob::submit(id, prepared, &pools, &logs, &queue).await.0));
+    }
+
+    let group_id = new_job_id();
+    let group = queue.add_group(&group_id, members, max_parallel);
+    info!("Queued job group {} of {} jobs", group_id, queued.len());
+
+    let services = ExecServices::new(&pools, &registry, &power, &logs);
+    let queue = queue.get_ref().clone();
+    let limit = max_parallel.unwrap_or(usize::MAX);
+    actix_web::rt::spawn(async move {
+        let mut schedule = Schedule::new(graph);
+        let (done, mut ended) = tokio::sync::mpsc::unbounded_channel();
+        loop {
+            for (job, dep) in schedule.skip_blocked() {
+                if let Some(skipped) = queued[job].take() {
+                    let reason = format!("Dependency '{}' did not succeed", schedule.graph().name(dep));
+                    queue.skipped(&skipped.id, reason);
+                }
+            }
+            for job in schedule.start_ready(limit) {
+                let Some(ready) = queued[job].take() else {
+                    continue;
+                };
+                let (services, queue, done) = (services.clone(), queue.clone(), done.clone());
+                actix_web::rt::spawn(async move {
+                    let succeeded = ready.run(&services, &queue).await;
+                    let _ = done.send((job, succeeded));
+                });
+            }
+            if !schedule.is_running() {
+                break;
+            }
+            let Some((job, succeeded)) = ended.recv().await else {
+                break;
+            };
+            schedule.ended(job, succeeded);
+        }
+        info!("Job group {} ended", group_id);
+    });
+
+    Ok(HttpResponse::Accepted().json(group_response(Some(group), None)))
+}
+
+/// Reports a group's aggregate state and the state of each of its jobs
+pub async fn job_group_status(queue: web::Data<Arc<JobQueue>>, id: web::Path<String>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(group_response(None, Some(format!("Invalid group ID: {}", id)))));
+    }
+    match queue.group(&id) {
+        Some(group) => Ok(HttpResponse::Ok().json(group_response(Some(group), None))),
+        None => Ok(HttpResponse::NotFound().json(group_response(None, Some(format!("Job group not found: {}", id))))),
+    }
+}
+
+/// Cancels every job of a group that is still queued or running
+pub async fn cancel_job_group(queue: web::Data<Arc<JobQueue>>, id: web::Path<String>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(group_response(None, Some(format!("Invalid group ID: {}", id)))));
+    }
+    match queue.cancel_group(&id) {
+        Ok(group) => {
+            info!(target: "audit", "Cancelled job group {}", id);
+            Ok(HttpResponse::Ok().json(group_response(Some(group), None)))
+        }
+        Err(CancelError::NotFound) => {
+            Ok(HttpResponse::NotFound().json(group_response(None, Some(format!("Job group not found: {}", id)))))
+        }
+        Err(CancelError::Ended) => {
+            let error = format!("Job group {} has already ended", id);
+            Ok(HttpResponse::Conflict().json(group_response(queue.group(&id), Some(error))))
+        }
+    }
+}
+
+/// Reports a job's state, with the end of its output while it is queued or running
+///
+/// Once the job has ended, `result` holds the full `/api/exec` response.
+pub async fn job_status(
+    logs: web::Data<Arc<JobLogs>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    queue: web::Data<Arc<JobQueue>>,
+    id: web::Path<String>,
+) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(job_response(None, Some(format!("Invalid job ID: {}", id)))));
+    }
+    let Some(job) = queue.get(&id) else {
+        return Ok(HttpResponse::NotFound().json(job_response(None, Some(format!("Job not found: {}", id)))));
+    };
+
+    let mut response = job_response(Some(job.clone()), None);
+    if matches!(job.state, JobState::Queued | JobState::Running) && logs.is_enabled() {
+        let reader = logs.get_ref().clone();
+        let tail = move |stream| {
+            let chunk = reader.tail(&id, stream, OUTPUT_TAIL).ok()?;
+            // The cut may fall inside a character
+            let start = chunk.data.iter().position(|byte| byte & 0xC0 != 0x80).unwrap_or(chunk.data.len());
+            let data = &chunk.data[start..];
+            Some(String::from_utf8_lossy(&data[..complete_utf8_len(data)]).into_owned())
+        };
+        if let Ok((stdout, stderr)) = pools.fs.run(move || (tail(LogStream::Stdout), tail(LogStream::Stderr))).await {
+            response.stdout = stdout;
+            response.stderr = stderr;
+        }
+    }
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Kills a queued or running job with everything it started
+pub async fn cancel_job(queue: web::Data<Arc<JobQueue>>, id: web::Path<String>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(job_response(None, Some(format!("Invalid job ID: {}", id)))));
+    }
+    match queue.cancel(&id) {
+        Ok(job) => {
+            info!(target: "audit", "Cancelled job {}: {}", job.job_id, job.command);
+            Ok(HttpResponse::Ok().json(job_response(Some(job), None)))
+        }
+        Err(CancelError::NotFound) => {
+            Ok(HttpResponse::NotFound().json(job_response(None, Some(format!("Job not found: {}", id)))))
+        }
+        Err(CancelError::Ended) => {
+            let error = format!("Job {} has already ended", id);
+            Ok(HttpResponse::Conflict().json(job_response(queue.get(&id), Some(error))))
+        }
+    }
+}
+
+/// Lists the artifacts collected from a job
+pub async fn job_artifacts(
+    artifacts: web::Data<Arc<ArtifactStore>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    id: web::Path<String>,
+) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let failure = |error: String| JobArtifactsResponse {
+        success: false,
+        job_id: id.clone(),
+        artifacts: None,
+        error: Some(error),
+    };
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(failure(format!("Invalid job ID: {}", id))));
+    }
+
+    let (store, job) = (artifacts.get_ref().clone(), id.clone());
+    let error = match pools.fs.run(move || store.list(&job)).await {
+        Ok(Ok(artifacts)) => {
+            return Ok(HttpResponse::Ok().json(JobArtifactsResponse {
+                success: true,
+                job_id: id,
+                artifacts: Some(artifacts),
+                error: None,
+            }));
+        }
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(HttpResponse::NotFound().json(failure(e.to_string())));
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    };
+    error!("Failed to list the artifacts of job {}: {}", id, error);
+    Ok(HttpResponse::Ok().json(failure(error)))
+}
+
+/// Downloads one of a job's artifacts, with `Range` support like `/api/download`
+pub async fn job_artifact(
+    artifacts: web::Data<Arc<ArtifactStore>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse> {
+    let (id, artifact) = path.into_inner();
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse { error: format!("Invalid job ID: {}", id) }));
+    }
+    let file = match artifacts.path(&id, &artifact) {
+        Ok(file) => file,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(ErrorResponse { error: e.to_string() })),
+    };
+    let metadata = match std::fs::symlink_metadata(&file) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => {
+            let error = format!("Artifact not found: {}", artifact);
+            return Ok(HttpResponse::NotFound().json(ErrorResponse { error }));
+        }
+    };
+    info!(target: "audit", "Downloading artifact {} of job {}", artifact, id);
+    Ok(send_file(&pools, &req, file, &metadata, None, &artifact).await)
+}
+
+/// Reads a job's logged stdout or stderr from a byte offset
+///
+/// Clients poll with the previous `next_offset` until `complete` is true, so
+/// output can be resumed after a dropped connection.
+pub async fn job_logs(
+    logs: web::Data<Arc<JobLogs>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    id: web::Path<String>,
+    query: web::Query<JobLogQuery>,
+) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let stream = query.stream.unwrap_or_default();
+    let offset = query.offset.unwrap_or(0);
+    let encoding = query.encoding.unwrap_or_default();
+    let failure = |error: String| JobLogResponse {
+        success: false,
+        job_id: id.clone(),
+        stream,
+        offset,
+        next_offset: offset,
+        size: 0,
+        complete: false,
+        data: None,
+        encoding: None,
+        error: Some(error),
+    };
+
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(failure(format!("Invalid job ID: {}", id))));
+    }
+
+    let reader = logs.get_ref().clone();
+    let job = id.clone();
+    let limit = query.limit.unwrap_or(DEFAULT_LOG_CHUNK);
+    let error = match pools.fs.run(move || reader.read(&job, stream, offset, limit)).await {
+        Ok(Ok(chunk)) => {
+            let reaches_end = offset + chunk.data.len() as u64 >= chunk.size;
+            let (data, read) = match encoding {
+                ContentEncoding::Base64 => (BASE64.encode(&chunk.data), chunk.data.len()),
+                ContentEncoding::Utf8 => {
+                    // A character still being written is left for the next read
+                    let read = if chunk.complete && reaches_end {
+                        chunk.data.len()
+                    } else {
+                        complete_utf8_len(&chunk.data)
+                    };
+                    (String::from_utf8_lossy(&chunk.data[..read]).into_owned(), read)
+                }
+            };
+            let next_offset = offset + read as u64;
+            return Ok(HttpResponse::Ok().json(JobLogResponse {
+                success: true,
+                job_id: id,
+                stream,
+                offset,
+                next_offset,
+                size: chunk.size,
+                complete: chunk.complete && next_offset >= chunk.size,
+                data: Some(data),
+                encoding: Some(encoding),
+                error: None,
+            }));
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    };
+    error!("Failed to read the log of job {}: {}", id, error);
+    Ok(HttpResponse::Ok().json(failure(error)))
+}
+
+/// Length of `data` without a trailing, incomplete UTF-8 sequence
+fn complete_utf8_len(data: &[u8]) -> usize {
+    for back in 1..=data.len().min(4) {
+        let byte = data[data.len() - back];
+        if byte & 0xC0 != 0x80 {
+            let needed = match byte {
+                0xF0.. => 4,
+                0xE0.. => 3,
+                0xC0.. => 2,
+                _ => 1,
+            };
+            return if needed > back { data.len() - back } else { data.len() };
+        }
+    }
+    data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_utf8_len() {
+        let text = "ok ✓".as_bytes();
+        assert_eq!(complete_utf8_len(text), text.len());
+        assert_eq!(complete_utf8_len(&text[..text.len() - 1]), 3);
+        assert_eq!(complete_utf8_len(&text[..4]), 3);
+        assert_eq!(complete_utf8_len(b""), 0);
+        // Invalid bytes are not held back forever
+        assert_eq!(complete_utf8_len(b"a\x80\x80\x80\x80"), 5);
+    }
+}
@@ -1,9 +1,70 @@
+pub mod admin;
+pub mod archive;
+pub mod backups;
 pub mod exec;
 pub mod file_ops;
 pub mod app_ops;
+pub mod batch;
+pub mod capabilities;
+pub mod clients;
+pub mod config;
 pub mod health;
+pub mod jobs;
+pub mod csrf;
+pub mod errors;
+pub mod hash;
+pub mod mounts;
+pub mod operations;
+pub mod pagination;
+pub mod pipelines;
+pub mod policy;
+pub mod power;
+pub mod processes;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod quota;
+pub mod search;
+pub mod sessions;
+pub mod shortcut;
+pub mod stream;
+pub mod sync;
+pub mod tail;
+pub mod tools;
+pub mod watch;
+pub mod workers;
 
-pub use exec::exec_command;
-pub use file_ops::{read_file, write_file, scan_directory, delete_item, create_item, rename_item};
-pub use app_ops::{open_application, shutdown_server};
+pub use exec::{exec_command, exec_environment};
+pub use file_ops::{read_file, download_file, write_file, edit_file, scan_directory, find_files, delete_item, create_item, rename_item, copy_item};
+pub use app_ops::{open_application, open_editor, open_terminal, open_url, shutdown_server};
 pub use health::health_check;
+pub use csrf::issue_csrf_token;
+pub use mounts::list_mounts;
+pub use workers::worker_stats;
+pub use processes::{kill_process, list_processes};
+pub use quota::quota;
+pub use search::search_files;
+pub use archive::{create_archive, extract_archive};
+pub use backups::{list_backups, run_backup};
+pub use sync::sync_directories;
+pub use hash::hash_file_content;
+pub use tail::tail_file;
+pub use watch::{add_watch, list_watches, remove_watch, watch_events};
+pub use operations::{cancel_operation, list_operations};
+pub use clients::{list_clients, register_client};
+pub use batch::batch_stream;
+pub use capabilities::capabilities;
+pub use admin::self_test;
+pub use config::{get_config, update_config};
+pub use policy::command_report;
+pub use tools::tool_manifest;
+pub use errors::error_catalog;
+pub use shortcut::{resolve_shortcut, create_shortcut};
+pub use power::{power_action, power_status};
+pub use jobs::{
+    cancel_job, cancel_job_group, job_artifact, job_artifacts, job_group_status, job_logs, job_status, submit_job,
+    submit_job_group,
+};
+pub use pipelines::{list_pipelines, run_pipeline};
+pub use sessions::{start_session, session_diff, finalize_session, abort_session};
+#[cfg(feature = "registry")]
+pub use registry::read_registry;
@@ -0,0 +1,59 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+use sysinfo::Disks;
+use tracing::{info, error};
+
+use crate::models::{MountInfo, MountsResponse};
+use crate::platform::display_path;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Handles drive/mount point enumeration requests
+pub async fn list_mounts(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+) -> Result<HttpResponse> {
+    info!("Listing mounts");
+
+    // Querying free space can stall on network mounts, keep it off the executor
+    let security = security.get_ref().clone();
+    let result = pools.fs.run(move || {
+        let disks = Disks::new_with_refreshed_list();
+        disks
+            .list()
+            .iter()
+            .map(|disk| MountInfo {
+                mount_point: display_path(disk.mount_point()),
+                label: disk.name().to_string_lossy().to_string(),
+                file_system: disk.file_system().to_string_lossy().to_string(),
+                kind: disk.kind().to_string(),
+                total_space: disk.total_space(),
+                available_space: disk.available_space(),
+                is_removable: disk.is_removable(),
+                is_read_only: disk.is_read_only(),
+                accessible: security.is_path_allowed(disk.mount_point()),
+                has_restrictions: security.has_restrictions_under(disk.mount_point()),
+            })
+            .collect::<Vec<_>>()
+    })
+    .await;
+
+    match result {
+        Ok(mounts) => {
+            info!("Found {} mounts", mounts.len());
+            Ok(HttpResponse::Ok().json(MountsResponse {
+                success: true,
+                mounts: Some(mounts),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to list mounts: {}", e);
+            Ok(HttpResponse::Ok().json(MountsResponse {
+                success: false,
+                mounts: None,
+                error: Some(format!("Failed to list mounts: {}", e)),
+            }))
+        }
+    }
+}
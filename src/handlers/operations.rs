@@ -0,0 +1,52 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+
+use crate::models::{OperationListResponse, OperationResponse};
+use crate::operations::{is_operation_id, OperationError, OperationRegistry};
+
+/// Status for an operation that could not be registered
+pub(crate) fn start_failure_status(e: &OperationError) -> StatusCode {
+    match e {
+        OperationError::InvalidId(_) => StatusCode::BAD_REQUEST,
+        OperationError::Duplicate(_) => StatusCode::CONFLICT,
+    }
+}
+
+/// Lists the scans, searches, copies, archives, extractions and syncs in progress, oldest first
+///
+/// Background jobs are followed through `/api/jobs/{id}` instead.
+pub async fn list_operations(operations: web::Data<Arc<OperationRegistry>>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(OperationListResponse {
+        success: true,
+        operations: operations.list(),
+    }))
+}
+
+/// Asks a running scan, search, copy, archive, extract or sync to stop
+///
+/// The operation stops at its next check and responds with
+/// `"error_code": "CANCELLED"` where its response has one; work it completed
+/// before is kept.
+pub async fn cancel_operation(
+    operations: web::Data<Arc<OperationRegistry>>,
+    id: web::Path<String>,
+) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let failure = |error: String| OperationResponse {
+        success: false,
+        operation: None,
+        error: Some(error),
+    };
+    if !is_operation_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(failure(format!("Invalid operation ID: {}", id))));
+    }
+    match operations.cancel(&id) {
+        Some(operation) => Ok(HttpResponse::Ok().json(OperationResponse {
+            success: true,
+            operation: Some(operation),
+            error: None,
+        })),
+        None => Ok(HttpResponse::NotFound().json(failure(format!("Operation not found: {}", id)))),
+    }
+}
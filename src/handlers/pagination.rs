@@ -0,0 +1,101 @@
+use crate::models::Paginated;
+
+/// Page size used when a cursor is given without a limit
+pub const DEFAULT_PAGE_SIZE: usize = 1000;
+
+/// Largest page a client may request
+pub const MAX_PAGE_SIZE: usize = 10_000;
+
+/// Slices an ordered result set into the page selected by `cursor` and `limit`
+///
+/// Cursors are opaque to clients; they encode the offset of the next item, so the
+/// listing must be produced in a stable order for pages to line up.
+pub fn paginate<T>(
+    items: Vec<T>,
+    cursor: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Paginated<T>, String> {
+    let offset = match cursor {
+        Some(cursor) => decode_cursor(cursor).ok_or_else(|| format!("Invalid cursor: {}", cursor))?,
+        None => 0,
+    };
+    Ok(paginate_at(items, offset, limit))
+}
+
+/// Slices the page starting at item `offset`; its `next_cursor` continues from there
+pub fn paginate_at<T>(mut items: Vec<T>, offset: usize, limit: Option<usize>) -> Paginated<T> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let total = items.len();
+    let end = offset.saturating_add(limit).min(total);
+    let page = if offset < total {
+        items.truncate(end);
+        items.split_off(offset)
+    } else {
+        Vec::new()
+    };
+
+    Paginated {
+        success: true,
+        items: page,
+        next_cursor: (end < total).then(|| encode_cursor(end)),
+        total,
+        error: None,
+    }
+}
+
+impl<T> Paginated<T> {
+    /// An empty page carrying an error
+    pub fn failure(error: String) -> Self {
+        Paginated {
+            success: false,
+            items: Vec::new(),
+            next_cursor: None,
+            total: 0,
+            error: Some(error),
+        }
+    }
+}
+
+fn encode_cursor(offset: usize) -> String {
+    hex::encode(format!("o:{}", offset))
+}
+
+fn decode_cursor(cursor: &str) -> Option<usize> {
+    let decoded = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+    decoded.strip_prefix("o:")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate_walks_all_pages() {
+        let items: Vec<u32> = (0..5).collect();
+
+        let first = paginate(items.clone(), None, Some(2)).unwrap();
+        assert_eq!(first.items, vec![0, 1]);
+        assert_eq!(first.total, 5);
+
+        let second = paginate(items.clone(), first.next_cursor.as_deref(), Some(2)).unwrap();
+        assert_eq!(second.items, vec![2, 3]);
+
+        let last = paginate(items.clone(), second.next_cursor.as_deref(), Some(2)).unwrap();
+        assert_eq!(last.items, vec![4]);
+        assert!(last.next_cursor.is_none());
+
+        // A page from an offset continues like any other
+        let jumped = paginate_at(items.clone(), 1, Some(2));
+        assert_eq!(jumped.items, vec![1, 2]);
+        assert_eq!(paginate(items, jumped.next_cursor.as_deref(), Some(2)).unwrap().items, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_paginate_rejects_bad_cursor() {
+        assert!(paginate(vec![1, 2, 3], Some("not-a-cursor"), None).is_err());
+        let past_end = paginate(vec![1, 2, 3], Some(&encode_cursor(10)), None).unwrap();
+        assert!(past_end.items.is_empty());
+        assert!(past_end.next_cursor.is_none());
+    }
+}
@@ -0,0 +1,260 @@
+use actix_web::{web, HttpResponse, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::config::is_pipeline_name;
+use crate::handlers::exec::{ExecServices, PreparedExec};
+use crate::jobs::JobLogs;
+use crate::models::{
+    Config, EnvOptions, ExecRequest, PipelineConfig, PipelineInfo, PipelineListResponse, PipelineRunResponse,
+    PipelineStep, PipelineStepResult,
+};
+use crate::power::PowerManager;
+use crate::processes::ProcessRegistry;
+use crate::security::secrets::SecretStore;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+fn run_failure(pipeline: String, error: String) -> PipelineRunResponse {
+    PipelineRunResponse {
+        success: false,
+        pipeline,
+        steps: vec![],
+        failed_step: None,
+        error: Some(error),
+    }
+}
+
+/// The `/api/exec` request a step amounts to; a step without `cwd` runs in the pipeline's
+fn step_request(pipeline: &PipelineConfig, step: &PipelineStep) -> ExecRequest {
+    ExecRequest {
+        command: step.command.clone(),
+        args: step.args.clone(),
+        cwd: step.cwd.clone().or_else(|| pipeline.cwd.clone()),
+        priority: None,
+        timeout_secs: step.timeout_secs,
+        create_cwd: None,
+        parse_output: None,
+        ansi: None,
+        shell: None,
+        login_shell: None,
+        isolate_cwd: None,
+        inputs: None,
+        outputs: None,
+        mutex: None,
+        environment: EnvOptions {
+            env: step.env.clone(),
+            ..EnvOptions::default()
+        },
+    }
+}
+
+/// Resolves the secrets of a step, its own overriding the pipeline's, as variable name and value
+fn step_secrets(
+    store: &SecretStore,
+    pipeline: &PipelineConfig,
+    step: &PipelineStep,
+) -> std::result::Result<HashMap<String, String>, String> {
+    let mut names = pipeline.secrets.clone().unwrap_or_default();
+    names.extend(step.secrets.clone().unwrap_or_default());
+    names
+        .into_iter()
+        .map(|(var, name)| match store.get(&name)? {
+            Some(value) => Ok((var, value)),
+            None => Err(format!("Secret '{}' is not set", name)),
+        })
+        .collect()
+}
+
+/// Lists the configured pipelines
+pub async fn list_pipelines(
+    config: web::Data<Arc<Config>>,
+    security: web::Data<Arc<SecurityManager>>,
+) -> Result<HttpResponse> {
+    let mut pipelines: Vec<PipelineInfo> = config
+        .pipelines
+        .iter()
+        .flatten()
+        .map(|(name, pipeline)| PipelineInfo {
+            name: name.clone(),
+            description: pipeline.description.clone(),
+            steps: pipeline.steps.iter().map(|step| security.redact(&step.command)).collect(),
+        })
+        .collect();
+    pipelines.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(HttpResponse::Ok().json(PipelineListResponse {
+        success: true,
+        pipelines,
+    }))
+}
+
+/// Runs a configured pipeline's steps in order, stopping at the first one that does not succeed
+///
+/// Steps are checked like `/api/exec` requests before any of them runs, except
+/// against the command lists: an operator can allow curated workflows without
+/// allowing their commands to be run with arbitrary arguments.
+///
+/// Configured secrets reach steps as environment variables only: their values
+/// are masked in step output, which is not kept in job logs.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_pipeline(
+    config: web::Data<Arc<Config>>,
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    registry: web::Data<Arc<ProcessRegistry>>,
+    power: web::Data<Arc<PowerManager>>,
+    logs: web::Data<Arc<JobLogs>>,
+    secrets: web::Data<Arc<SecretStore>>,
+    name: web::Path<String>,
+) -> Result<HttpResponse> {
+    let name = name.into_inner();
+    if !is_pipeline_name(&name) {
+        return Ok(HttpResponse::BadRequest().json(run_failure(name.clone(), format!("Invalid pipeline name: {}", name))));
+    }
+    let Some(pipeline) = config.pipelines.as_ref().and_then(|pipelines| pipelines.get(&name)) else {
+        return Ok(HttpResponse::NotFound().json(run_failure(name.clone(), format!("Pipeline not found: {}", name))));
+    };
+
+    let mut prepared = Vec::with_capacity(pipeline.steps.len());
+    for (index, step) in pipeline.steps.iter().enumerate() {
+        let values = match step_secrets(&secrets, pipeline, step) {
+            Ok(values) => values,
+            Err(e) => {
+                let error = format!("Step {} of pipeline '{}': {}", index + 1, name, e);
+                error!("{}", error);
+                return Ok(HttpResponse::InternalServerError().json(run_failure(name, error)));
+            }
+        };
+        let mut request = step_request(pipeline, step);
+        if !values.is_empty() {
+            let env = request.environment.env.get_or_insert_with(HashMap::new);
+            env.extend(values.iter().map(|(var, value)| (var.clone(), value.clone())));
+        }
+        match PreparedExec::prepare_trusted(&security, request) {
+            Ok(step) => prepared.push(step.masking(values.into_values().collect())),
+            Err(refusal) => return Ok(refusal.for_step(&name, index).into_response()),
+        }
+    }
+
+    info!("Running pipeline '{}' ({} steps)", name, prepared.len());
+    let services = ExecServices::new(&pools, &registry, &power, &logs);
+    let mut steps = Vec::with_capacity(prepared.len());
+    let mut failed_step = None;
+    for (index, step) in prepared.into_iter().enumerate() {
+        let command = step.name().to_string();
+        let result = match step.run_attached(&services).await {
+            Ok(result) => result,
+            Err(e) => return Ok(e.into_response()),
+        };
+        let succeeded = result.success;
+        steps.push(PipelineStepResult { command, result });
+        if !succeeded {
+            failed_step = Some(index);
+            break;
+        }
+    }
+
+    let error = failed_step.map(|index| format!("Step {} of pipeline '{}' failed: {}", index + 1, name, steps[index].command));
+    match &error {
+        Some(error) => warn!("{}", error),
+        None => info!("Pipeline '{}' succeeded", name),
+    }
+    Ok(HttpResponse::Ok().json(PipelineRunResponse {
+        success: error.is_none(),
+        pipeline: name,
+        steps,
+        failed_step,
+        error,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::encryption::ContentCipher;
+
+    fn step(command: &str, args: Option<&[&str]>) -> PipelineStep {
+        PipelineStep {
+            command: command.to_string(),
+            args: args.map(|args| args.iter().map(|arg| arg.to_string()).collect()),
+            cwd: None,
+            env: None,
+            secrets: None,
+            timeout_secs: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_run_pipeline() {
+        use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+        use actix_web::App;
+
+        let dir = std::env::temp_dir().join(format!("exex-pipelines-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = crate::config::get_default_config();
+        let pipeline = |steps| PipelineConfig {
+            description: None,
+            cwd: Some(dir.to_string_lossy().to_string()),
+            secrets: None,
+            steps,
+        };
+        // Steps get the environment checks of /api/exec
+        let mut preload = step("echo", None);
+        preload.env = Some(HashMap::from([("LD_PRELOAD".to_string(), "/tmp/hook.so".to_string())]));
+        // Secrets reach the step's environment but not the response
+        let mut deploy = pipeline(vec![step("sh", Some(&["-c", "echo token=$DEPLOY_TOKEN"]))]);
+        deploy.secrets = Some(HashMap::from([("DEPLOY_TOKEN".to_string(), "deploy-token".to_string())]));
+        let mut unset = pipeline(vec![step("echo", None)]);
+        unset.steps[0].secrets = Some(HashMap::from([("TOKEN".to_string(), "exex-unset-secret".to_string())]));
+        config.pipelines = Some(HashMap::from([
+            // The missing tool is not whitelisted, but configured steps are not held to the command lists
+            (
+                "check".to_string(),
+                pipeline(vec![step("echo", Some(&["first"])), step("exex-missing-tool", None), step("echo", None)]),
+            ),
+            ("blocked".to_string(), pipeline(vec![step("echo", None), preload])),
+            ("deploy".to_string(), deploy),
+            ("unset".to_string(), unset),
+        ]));
+        let secrets = SecretStore::new(dir.join("secrets.enc"), ContentCipher::from_key(&[5u8; 32]));
+        secrets.set("deploy-token", "tok-8c1f").unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config.clone()))))
+                .app_data(web::Data::new(Arc::new(config)))
+                .app_data(web::Data::new(Arc::new(ProcessRegistry::new())))
+                .app_data(web::Data::new(Arc::new(PowerManager::new(false))))
+                .app_data(web::Data::new(Arc::new(JobLogs::new(dir.join("logs"), 0, 0))))
+                .app_data(web::Data::new(Arc::new(secrets)))
+                .route("/pipelines", web::get().to(list_pipelines))
+                .route("/pipelines/{name}/run", web::post().to(run_pipeline)),
+        )
+        .await;
+        let run = |name: &str| TestRequest::post().uri(&format!("/pipelines/{}/run", name)).to_request();
+
+        let listed: serde_json::Value = read_body_json(call_service(&app, TestRequest::get().uri("/pipelines").to_request()).await).await;
+        assert_eq!(listed["pipelines"][1]["name"], "check");
+        assert_eq!(listed["pipelines"][1]["steps"].as_array().unwrap().len(), 3);
+        assert!(!listed.to_string().contains("tok-8c1f"));
+
+        let body: serde_json::Value = read_body_json(call_service(&app, run("check")).await).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(body["failed_step"], 1);
+        assert_eq!(body["steps"].as_array().unwrap().len(), 2);
+        assert_eq!(body["steps"][0]["stdout"].as_str().unwrap().trim(), "first");
+
+        let body: serde_json::Value = read_body_json(call_service(&app, run("deploy")).await).await;
+        assert_eq!(body["success"], true);
+        assert_eq!(body["steps"][0]["stdout"].as_str().unwrap().trim(), "token=[REDACTED]");
+        assert!(body["steps"][0]["job_id"].is_null());
+        assert_eq!(call_service(&app, run("unset")).await.status(), 500);
+
+        assert_eq!(call_service(&app, run("blocked")).await.status(), 403);
+        assert_eq!(call_service(&app, run("missing")).await.status(), 404);
+        assert_eq!(call_service(&app, run("..%2Fcheck")).await.status(), 400);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use actix_web::{web, HttpResponse, Result};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+use crate::models::{CommandReport, CommandReportQuery, CommandReportResponse, ProcessPriority};
+use crate::platform::{apply_priority, display_path, is_executable, resolve_command_in};
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// How long all `--version` probes of one report may run together
+const VERSION_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reports where each whitelisted command resolves, so agents know which tools this machine has
+pub async fn command_report(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    query: web::Query<CommandReportQuery>,
+) -> Result<HttpResponse> {
+    let mut names: Vec<String> = security.get_command_whitelist().iter().cloned().collect();
+    names.sort();
+    let commands: Vec<(String, String)> = names
+        .into_iter()
+        .map(|name| (security.command_program(&name).to_string(), name))
+        .collect();
+    let probe = query.version.unwrap_or(false);
+    let search_path = security.child_path().map(OsStr::to_owned);
+
+    // Probes run the tools themselves, so they belong on the exec pool
+    match pools.exec.run(move || resolve_commands(commands, probe, search_path)).await {
+        Ok(commands) => Ok(HttpResponse::Ok().json(CommandReportResponse {
+            success: true,
+            commands,
+            error: None,
+        })),
+        Err(e) => {
+            error!("Failed to resolve commands: {}", e);
+            Ok(HttpResponse::Ok().json(CommandReportResponse {
+                success: false,
+                commands: vec![],
+                error: Some(format!("Failed to resolve commands: {}", e)),
+            }))
+        }
+    }
+}
+
+/// Resolves commands within `search_path`, or the daemon's PATH when it is `None`
+pub(crate) fn resolve_commands(
+    commands: Vec<(String, String)>,
+    probe: bool,
+    search_path: Option<OsString>,
+) -> Vec<CommandReport> {
+    let search_path = search_path.or_else(|| std::env::var_os("PATH"));
+    let paths: Vec<Option<PathBuf>> = commands
+        .iter()
+        .map(|(program, _)| resolve_command_in(program, search_path.as_deref()))
+        .collect();
+    let versions = if probe { probe_versions(&paths, search_path.as_deref()) } else { vec![None; paths.len()] };
+
+    commands
+        .into_iter()
+        .zip(paths)
+        .zip(versions)
+        .map(|(((program, name), path), version)| CommandReport {
+            aliased: program != name,
+            executable: path.as_deref().is_some_and(is_executable),
+            path: path.as_deref().map(display_path),
+            name,
+            program,
+            version,
+        })
+        .collect()
+}
+
+/// Runs each resolved program with `--version` and returns the first line it prints
+///
+/// All probes start before any is awaited and share one deadline, after which
+/// stragglers are killed and report no version.
+fn probe_versions(paths: &[Option<PathBuf>], search_path: Option<&OsStr>) -> Vec<Option<String>> {
+    let children: Vec<Option<Child>> = paths
+        .iter()
+        .map(|path| {
+            let mut command = Command::new(path.as_ref()?);
+            // Scripts such as nvm's shims look up their interpreter on PATH
+            if let Some(search_path) = search_path {
+                command.env("PATH", search_path);
+            }
+            command
+                .arg("--version")
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            apply_priority(&mut command, ProcessPriority::Low);
+            command.spawn().ok()
+        })
+        .collect();
+
+    let deadline = Instant::now() + VERSION_PROBE_TIMEOUT;
+    children
+        .into_iter()
+        .map(|child| {
+            let mut child = child?;
+            while matches!(child.try_wait(), Ok(None)) {
+                if Instant::now() >= deadline {
+                    let _ = child.kill();
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            let output = child.wait_with_output().ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            first_line(&output.stdout).or_else(|| first_line(&output.stderr))
+        })
+        .collect()
+}
+
+fn first_line(output: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_commands() {
+        let cargo = env!("CARGO").to_string();
+        let commands = vec![
+            (cargo.clone(), "cargo".to_string()),
+            ("exex-no-such-command".to_string(), "missing".to_string()),
+        ];
+
+        let report = resolve_commands(commands.clone(), false, None);
+        assert!(report[0].aliased);
+        assert!(report[0].executable);
+        assert!(report[0].version.is_none());
+        assert!(!report[1].executable);
+        assert!(report[1].path.is_none());
+
+        let report = resolve_commands(commands, true, None);
+        assert!(report[0].version.as_deref().is_some_and(|v| v.starts_with("cargo ")));
+        assert!(report[1].version.is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::{info, warn, error};
+
+use crate::config::default_power_actions;
+use crate::models::{Config, PowerAction, PowerRequest, PowerResponse, PowerStatusResponse};
+use crate::platform::power::run_power_action;
+use crate::power::{lease_duration, PowerManager};
+use crate::workers::WorkerPools;
+
+/// Lease length cap when `security.max_wake_lease_secs` is not set
+const DEFAULT_MAX_WAKE_LEASE_SECS: u64 = 12 * 60 * 60;
+
+fn allowed_actions(config: &Config) -> Vec<PowerAction> {
+    config.security.power_actions.clone().unwrap_or_else(default_power_actions)
+}
+
+fn max_wake_lease_secs(config: &Config) -> u64 {
+    config.security.max_wake_lease_secs.unwrap_or(DEFAULT_MAX_WAKE_LEASE_SECS)
+}
+
+/// Reports the allowed power actions and the active keep-awake leases
+pub async fn power_status(
+    config: web::Data<Arc<Config>>,
+    power: web::Data<Arc<PowerManager>>,
+) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(PowerStatusResponse {
+        success: true,
+        actions: allowed_actions(&config),
+        max_wake_lease_secs: max_wake_lease_secs(&config),
+        leases: power.leases(),
+        job_wake_lock: power.holds_job_wake_lock(),
+    }))
+}
+
+/// Handles lock, sleep and keep-awake requests
+///
+/// Shutdown and reboot are deliberately not offered. Sleep needs
+/// `confirm: true`, and every action is recorded under the `audit` target.
+pub async fn power_action(
+    config: web::Data<Arc<Config>>,
+    power: web::Data<Arc<PowerManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: web::Json<PowerRequest>,
+) -> Result<HttpResponse> {
+    let action = req.action;
+    let failure = |error: String| PowerResponse {
+        success: false,
+        action,
+        lease: None,
+        error: Some(error),
+    };
+
+    // Releasing only ever lets the machine sleep again, so it is always allowed
+    if action == PowerAction::Release {
+        let Some(id) = &req.lease_id else {
+            return Ok(HttpResponse::BadRequest().json(failure("release requires lease_id".to_string())));
+        };
+        return Ok(HttpResponse::Ok().json(match power.release(id) {
+            Some(lease) => {
+                info!(target: "audit", "power release: lease {}", id);
+                PowerResponse {
+                    success: true,
+                    action,
+                    lease: Some(lease),
+                    error: None,
+                }
+            }
+            None => failure(format!("No active lease {}", id)),
+        }));
+    }
+
+    if !allowed_actions(&config).contains(&action) {
+        warn!(target: "audit", "power {:?} denied: not in security.power_actions", action);
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Power action {:?} is not allowed", action))));
+    }
+
+    if action == PowerAction::Sleep && req.confirm != Some(true) {
+        return Ok(HttpResponse::BadRequest().json(failure("sleep requires \"confirm\": true".to_string())));
+    }
+
+    if action == PowerAction::KeepAwake {
+        let duration = match lease_duration(req.duration_secs, max_wake_lease_secs(&config)) {
+            Ok(duration) => duration,
+            Err(e) => return Ok(HttpResponse::BadRequest().json(failure(e))),
+        };
+        let reason = req.reason.clone().unwrap_or_else(|| "Requested through EXEX".to_string());
+        let manager = power.get_ref().clone();
+        let held = reason.clone();
+        let error = match pools.exec.run(move || manager.acquire(duration, &held)).await {
+            Ok(Ok(lease)) => {
+                info!(target: "audit", "power keep_awake: lease {} for {}s ({})", lease.id, duration.as_secs(), reason);
+                return Ok(HttpResponse::Ok().json(PowerResponse {
+                    success: true,
+                    action,
+                    lease: Some(lease),
+                    error: None,
+                }));
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => e.to_string(),
+        };
+        error!("Failed to keep the machine awake: {}", error);
+        return Ok(HttpResponse::Ok().json(failure(format!("Failed to keep the machine awake: {}", error))));
+    }
+
+    info!(target: "audit", "power {:?} allowed", action);
+    let result = pools.exec.run(move || run_power_action(action)).await;
+    let error = match result {
+        Ok(Ok(())) => {
+            return Ok(HttpResponse::Ok().json(PowerResponse {
+                success: true,
+                action,
+                lease: None,
+                error: None,
+            }));
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    };
+    error!("Power action {:?} failed: {}", action, error);
+    Ok(HttpResponse::Ok().json(failure(format!("Power action failed: {}", error))))
+}
@@ -0,0 +1,58 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::models::{KillProcessRequest, KillProcessResponse, ProcessListResponse, ProcessQuery};
+use crate::processes::{KillError, ProcessRegistry};
+
+/// Lists applications and commands spawned by EXEX, running ones first
+pub async fn list_processes(
+    registry: web::Data<Arc<ProcessRegistry>>,
+    query: web::Query<ProcessQuery>,
+) -> Result<HttpResponse> {
+    let processes = registry
+        .list()
+        .into_iter()
+        .filter(|info| query.pid.is_none_or(|pid| info.pid == pid))
+        .filter(|info| query.running.is_none_or(|running| info.running == running))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ProcessListResponse {
+        success: true,
+        processes,
+    }))
+}
+
+/// Forcibly terminates a running process EXEX started, along with its descendants when they are tracked
+///
+/// Only processes in the registry can be killed, so the endpoint cannot be
+/// used to signal arbitrary PIDs on the machine.
+pub async fn kill_process(
+    registry: web::Data<Arc<ProcessRegistry>>,
+    req: web::Json<KillProcessRequest>,
+) -> Result<HttpResponse> {
+    let failure = |error: String| KillProcessResponse {
+        success: false,
+        process: None,
+        error: Some(error),
+    };
+
+    match registry.kill(req.pid) {
+        Ok(process) => {
+            info!(target: "audit", "Killed {} (PID {})", process.name, process.pid);
+            Ok(HttpResponse::Ok().json(KillProcessResponse {
+                success: true,
+                process: Some(process),
+                error: None,
+            }))
+        }
+        Err(KillError::NotFound) => {
+            warn!("Refusing to kill PID {}: not a running process started by EXEX", req.pid);
+            Ok(HttpResponse::NotFound().json(failure(format!("PID {} is not a running process started by EXEX", req.pid))))
+        }
+        Err(KillError::Failed(e)) => {
+            error!("Failed to kill PID {}: {}", req.pid, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to kill PID {}: {}", req.pid, e))))
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+
+use crate::models::QuotaResponse;
+use crate::security::throttle::ClientId;
+use crate::security::SecurityManager;
+
+/// Reports the calling client's size limits and current write throttle state
+pub async fn quota(
+    security: web::Data<Arc<SecurityManager>>,
+    client: ClientId,
+) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(QuotaResponse {
+        success: true,
+        write_throttle: security.write_throttle_state(&client),
+        client_id: client.0,
+        max_file_size_bytes: security.max_file_size_bytes(),
+        disk_reserve_bytes: security.disk_reserve_bytes(),
+    }))
+}
@@ -0,0 +1,62 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::{info, warn, error};
+
+use crate::config::default_registry_keys;
+use crate::models::{Config, RegistryReadRequest, RegistryReadResponse};
+use crate::platform::registry::{is_key_allowed, read_key, RegistryPath};
+use crate::workers::WorkerPools;
+
+/// Handles registry read requests for keys under `security.registry_keys`
+pub async fn read_registry(
+    config: web::Data<Arc<Config>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: web::Json<RegistryReadRequest>,
+) -> Result<HttpResponse> {
+    let failure = |key: String, error: String| RegistryReadResponse {
+        success: false,
+        key,
+        subkeys: Vec::new(),
+        values: Vec::new(),
+        error: Some(error),
+    };
+
+    let path = match RegistryPath::parse(&req.key) {
+        Ok(path) => path,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(failure(req.key.clone(), e))),
+    };
+    let key = path.to_string();
+    info!("Reading registry key: {}", key);
+
+    let allowed = config.security.registry_keys.clone().unwrap_or_else(default_registry_keys);
+    if !is_key_allowed(&path, &allowed) {
+        warn!("Registry key not allowed: {}", key);
+        return Ok(HttpResponse::Forbidden().json(failure(key, "Registry key not allowed".to_string())));
+    }
+
+    if !cfg!(windows) {
+        return Ok(HttpResponse::NotImplemented().json(failure(key, "The registry is only available on Windows".to_string())));
+    }
+
+    let value = req.value.clone();
+    let result = pools.fs.run(move || read_key(&path, value.as_deref())).await;
+    match result {
+        Ok(Ok((subkeys, values))) => Ok(HttpResponse::Ok().json(RegistryReadResponse {
+            success: true,
+            key,
+            subkeys,
+            values,
+            error: None,
+        })),
+        Ok(Err(e)) => {
+            error!("Failed to read registry key {}: {}", key, e);
+            let error = format!("Failed to read registry key: {}", e);
+            Ok(HttpResponse::Ok().json(failure(key, error)))
+        }
+        Err(e) => {
+            error!("Failed to read registry key {}: {}", key, e);
+            let error = format!("Failed to read registry key: {}", e);
+            Ok(HttpResponse::Ok().json(failure(key, error)))
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use actix_web::{web, HttpResponse, Result};
+use globset::GlobSet;
+use regex::{Regex, RegexBuilder};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::content::{classify, SNIFF_LEN};
+use crate::handlers::operations::start_failure_status;
+use crate::models::{FileKind, SearchMatch, SearchRequest, SearchResponse};
+use crate::operations::{CancelToken, OperationRegistry};
+use crate::platform::{display_path, entry_attributes, extended_path, read_bytes};
+use crate::security::ignore::IgnoreMatcher;
+use crate::security::workspace::build_glob_set;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Matches returned when the request does not set `max_results`
+const DEFAULT_MAX_RESULTS: usize = 1000;
+
+/// Largest `max_results` a client may request
+const MAX_RESULTS: usize = 10_000;
+
+/// Largest `context_lines` a client may request
+const MAX_CONTEXT_LINES: usize = 20;
+
+/// Longer lines, as in minified files, are cut to this many characters
+const MAX_LINE_CHARS: usize = 1000;
+
+/// Compiled size limit for regular expressions, so a pattern cannot exhaust memory
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+fn search_failure(error: String) -> SearchResponse {
+    SearchResponse {
+        success: false,
+        matches: vec![],
+        files_searched: 0,
+        truncated: false,
+        error: Some(error),
+    }
+}
+
+/// What a search looks for and where
+struct SearchPlan {
+    root: PathBuf,
+    pattern: Regex,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    ignore: IgnoreMatcher,
+    include_hidden: bool,
+    max_results: usize,
+    context_lines: usize,
+    cancel: CancelToken,
+}
+
+#[derive(Default)]
+struct SearchOutcome {
+    matches: Vec<SearchMatch>,
+    files_searched: usize,
+    truncated: bool,
+}
+
+/// Searches the root file, or every allowed file below the root directory
+///
+/// Directories are walked depth-first in name order, so repeated searches list
+/// matches in the same order. Symlinks are not followed. Cancellation stops the
+/// search with an error rather than returning the matches found so far.
+fn search_tree(security: &SecurityManager, plan: &SearchPlan) -> io::Result<SearchOutcome> {
+    let mut outcome = SearchOutcome::default();
+    let metadata = std::fs::metadata(&plan.root)?;
+    if !metadata.is_dir() {
+        search_file(security, plan, &plan.root, metadata.len(), &mut outcome);
+        return Ok(outcome);
+    }
+
+    let mut stack = vec![plan.root.clone()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut subdirectories = Vec::new();
+        for entry in entries {
+            plan.cancel.check()?;
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            let attributes = entry_attributes(&entry.file_name().to_string_lossy(), &metadata);
+            if (!plan.include_hidden && (attributes.hidden || attributes.system)) || plan.ignore.is_ignored(&path) {
+                continue;
+            }
+            let relative = path.strip_prefix(&plan.root).unwrap_or(&path);
+            if plan.exclude.as_ref().is_some_and(|exclude| exclude.is_match(relative)) {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                if security.is_path_allowed(&path) {
+                    subdirectories.push(path);
+                }
+            } else if metadata.is_file()
+                && plan.include.as_ref().is_none_or(|include| include.is_match(relative))
+                && security.is_path_allowed(&path)
+            {
+                search_file(security, plan, &path, metadata.len(), &mut outcome);
+                if outcome.truncated {
+                    return Ok(outcome);
+                }
+            }
+        }
+        stack.extend(subdirectories.into_iter().rev());
+    }
+    Ok(outcome)
+}
+
+/// Adds the matching lines of one text file; binary, oversized and unreadable files are skipped
+fn search_file(security: &SecurityManager, plan: &SearchPlan, path: &Path, len: u64, outcome: &mut SearchOutcome) {
+    if !security.is_file_size_allowed(len) {
+        return;
+    }
+    let data = match read_bytes(path).map(|data| security.decrypt_for_path(path, data)) {
+        Ok(Ok(data)) => data,
+        Ok(Err(e)) => {
+            warn!("Skipping {} in search: {}", path.display(), e);
+            return;
+        }
+        Err(_) => return,
+    };
+    if classify(&data[..data.len().min(SNIFF_LEN)]) != FileKind::Text {
+        return;
+    }
+    outcome.files_searched += 1;
+
+    let text = String::from_utf8_lossy(&data);
+    let lines: Vec<&str> = text.lines().collect();
+    let clip = |lines: &[&str]| lines.iter().map(|line| clip_line(line)).collect();
+    for (index, line) in lines.iter().enumerate() {
+        if !plan.pattern.is_match(line) {
+            continue;
+        }
+        if outcome.matches.len() == plan.max_results {
+            outcome.truncated = true;
+            return;
+        }
+        let after_end = (index + 1 + plan.context_lines).min(lines.len());
+        outcome.matches.push(SearchMatch {
+            path: display_path(path),
+            line_number: index + 1,
+            line: clip_line(line),
+            before: clip(&lines[index.saturating_sub(plan.context_lines)..index]),
+            after: clip(&lines[index + 1..after_end]),
+        });
+    }
+}
+
+fn clip_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// Searches the contents of a file or directory tree for text or a regular expression
+///
+/// Directories are walked like recursive scans: ignored and hidden entries are
+/// left out, and paths the policy denies are skipped.
+pub async fn search_files(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    operations: web::Data<Arc<OperationRegistry>>,
+    req: web::Json<SearchRequest>,
+) -> Result<HttpResponse> {
+    let req = req.into_inner();
+    let root = extended_path(Path::new(&req.path));
+    if !security.is_path_allowed(&root) {
+        warn!("Search access denied: {}", req.path);
+        return Ok(HttpResponse::Forbidden().json(search_failure(format!("Access denied to path: {}", req.path))));
+    }
+    if req.query.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(search_failure("'query' must not be empty".to_string())));
+    }
+
+    let pattern = match req.regex.unwrap_or(false) {
+        true => req.query.clone(),
+        false => regex::escape(&req.query),
+    };
+    let pattern = match RegexBuilder::new(&pattern)
+        .case_insensitive(!req.case_sensitive.unwrap_or(true))
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+    {
+        Ok(pattern) => pattern,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(search_failure(format!("Invalid regex: {}", e)))),
+    };
+    let globs = |patterns: &Option<Vec<String>>, field: &str| {
+        patterns
+            .as_deref()
+            .map(|patterns| build_glob_set(patterns).map_err(|e| format!("Invalid {} pattern {}", field, e)))
+            .transpose()
+    };
+    let summary = format!("{:?} in {}", req.query, req.path);
+    let operation = match operations.start(req.operation_id.as_deref(), "search", &req.path, summary) {
+        Ok(operation) => operation,
+        Err(e) => return Ok(HttpResponse::build(start_failure_status(&e)).json(search_failure(e.to_string()))),
+    };
+    let plan = match (globs(&req.include, "include"), globs(&req.exclude, "exclude"), security.ignore_matcher(&root, None)) {
+        (Ok(include), Ok(exclude), Ok(ignore)) => SearchPlan {
+            root,
+            pattern,
+            include,
+            exclude,
+            ignore,
+            include_hidden: req.include_hidden.unwrap_or(false),
+            max_results: req.max_results.unwrap_or(DEFAULT_MAX_RESULTS).clamp(1, MAX_RESULTS),
+            context_lines: req.context_lines.unwrap_or(0).min(MAX_CONTEXT_LINES),
+            cancel: operation.token(),
+        },
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return Ok(HttpResponse::BadRequest().json(search_failure(e))),
+    };
+
+    info!("Searching {} for {:?}", req.path, req.query);
+    let walker = security.get_ref().clone();
+    match pools.fs.run(move || search_tree(&walker, &plan)).await {
+        Ok(Ok(outcome)) => {
+            info!(
+                "Search of {} found {} matches in {} files",
+                req.path,
+                outcome.matches.len(),
+                outcome.files_searched
+            );
+            Ok(HttpResponse::Ok().json(SearchResponse {
+                success: true,
+                matches: outcome.matches,
+                files_searched: outcome.files_searched,
+                truncated: outcome.truncated,
+                error: None,
+            }))
+        }
+        Ok(Err(e)) => {
+            error!("Failed to search {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(search_failure(format!("Failed to search: {}", e))))
+        }
+        Err(e) => {
+            error!("Failed to search {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(search_failure(format!("Failed to search: {}", e))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_tree() {
+        let dir = std::env::temp_dir().join(format!("exex-search-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("src").join("nested")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("src").join("main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+        std::fs::write(dir.join("src").join("nested").join("lib.rs"), "pub fn run() {}\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "Run it\n").unwrap();
+        std::fs::write(dir.join(".env"), "run=1\n").unwrap();
+        std::fs::write(dir.join("target").join("out.rs"), "run();\n").unwrap();
+        std::fs::write(dir.join("data.bin"), b"run\0\x01\x02").unwrap();
+
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let plan = |pattern: &str| SearchPlan {
+            root: dir.clone(),
+            pattern: Regex::new(pattern).unwrap(),
+            include: None,
+            exclude: None,
+            ignore: security.ignore_matcher(&dir, None).unwrap(),
+            include_hidden: false,
+            max_results: 10,
+            context_lines: 1,
+            cancel: CancelToken::default(),
+        };
+        let found = |outcome: &SearchOutcome| {
+            let names = outcome.matches.iter().map(|m| Path::new(&m.path).file_name().unwrap().to_string_lossy().to_string());
+            names.collect::<Vec<_>>()
+        };
+
+        // Hidden, ignored and binary files are left out; files come before subdirectories
+        let outcome = search_tree(&security, &plan("run")).unwrap();
+        assert_eq!(found(&outcome), ["main.rs", "lib.rs"]);
+        assert_eq!(outcome.matches[0].line_number, 2);
+        assert_eq!(outcome.matches[0].before, ["fn main() {"]);
+        assert_eq!(outcome.matches[0].after, ["}"]);
+        assert_eq!(outcome.files_searched, 3);
+
+        let outcome = search_tree(&security, &plan("(?i)^run")).unwrap();
+        assert_eq!(found(&outcome), ["notes.md"]);
+
+        let mut filtered = plan("run");
+        filtered.include = Some(build_glob_set(&["*.rs".to_string()]).unwrap());
+        filtered.exclude = Some(build_glob_set(&["nested".to_string()]).unwrap());
+        assert_eq!(found(&search_tree(&security, &filtered).unwrap()), ["main.rs"]);
+
+        let mut limited = plan("run");
+        limited.max_results = 1;
+        let outcome = search_tree(&security, &limited).unwrap();
+        assert_eq!(outcome.matches.len(), 1);
+        assert!(outcome.truncated);
+
+        // A file is searched on its own
+        let mut single = plan("run");
+        single.root = dir.join("src").join("nested").join("lib.rs");
+        assert_eq!(found(&search_tree(&security, &single).unwrap()), ["lib.rs"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use actix_web::{web, HttpResponse, Result};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::jobs::is_job_id;
+use crate::models::{ErrorCode, SessionResponse, StartSessionRequest};
+use crate::platform::{extended_path, special_file_at};
+use crate::security::SecurityManager;
+use crate::sessions::{SessionManager, MAX_SESSION_FILES};
+use crate::workers::WorkerPools;
+
+fn failure(session_id: Option<String>, error: String) -> SessionResponse {
+    SessionResponse {
+        success: false,
+        session_id,
+        files: None,
+        diff: None,
+        error: Some(error),
+        error_code: None,
+    }
+}
+
+/// Starts an editing session by snapshotting the given files
+///
+/// Edits are then made through the regular endpoints; `finalize` reports
+/// the consolidated diff and `abort` reverts every file.
+pub async fn start_session(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    sessions: web::Data<Arc<SessionManager>>,
+    req: web::Json<StartSessionRequest>,
+) -> Result<HttpResponse> {
+    if req.paths.is_empty() || req.paths.len() > MAX_SESSION_FILES {
+        return Ok(HttpResponse::BadRequest().json(failure(
+            None,
+            format!("A session needs between 1 and {} paths", MAX_SESSION_FILES),
+        )));
+    }
+
+    let mut files = Vec::with_capacity(req.paths.len());
+    for requested in &req.paths {
+        let path = extended_path(Path::new(requested));
+        if !security.is_path_allowed(&path) || security.is_workspace_policy_path(&path) {
+            return Ok(HttpResponse::Forbidden().json(failure(None, format!("Access denied to file: {}", requested))));
+        }
+        if let Err(e) = security.check_protected(&path, req.force.unwrap_or(false)) {
+            warn!("Session denied for {}: {}", requested, e);
+            return Ok(HttpResponse::Forbidden().json(SessionResponse {
+                error_code: Some(ErrorCode::ProtectedPath),
+                ..failure(None, e)
+            }));
+        }
+        if path.is_dir() {
+            return Ok(HttpResponse::BadRequest().json(failure(None, format!("Not a file: {}", requested))));
+        }
+        // Snapshotting a FIFO would block the session forever
+        if let Some(special) = special_file_at(&path) {
+            return Ok(HttpResponse::BadRequest().json(SessionResponse {
+                error_code: Some(ErrorCode::SpecialFile),
+                ..failure(None, format!("Not a regular file: {} is a {}", requested, special))
+            }));
+        }
+        files.push((path, requested.clone()));
+    }
+
+    let manager = sessions.get_ref().clone();
+    let count = files.len();
+    let (max_size, force) = (security.max_file_size_bytes(), req.force.unwrap_or(false));
+    match pools.fs.run(move || manager.start(files, max_size, force)).await {
+        Ok(Ok(id)) => {
+            info!(target: "audit", "session {} started with {} files", id, count);
+            Ok(HttpResponse::Ok().json(SessionResponse {
+                success: true,
+                session_id: Some(id),
+                files: None,
+                diff: None,
+                error: None,
+                error_code: None,
+            }))
+        }
+        Ok(Err(e)) if e.kind() == io::ErrorKind::FileTooLarge => {
+            warn!("Session refused: {}", e);
+            Ok(HttpResponse::Forbidden().json(failure(None, e.to_string())))
+        }
+        Ok(Err(e)) => {
+            error!("Failed to start session: {}", e);
+            Ok(HttpResponse::Ok().json(failure(None, e.to_string())))
+        }
+        Err(e) => Ok(HttpResponse::Ok().json(failure(None, e.to_string()))),
+    }
+}
+
+/// Reports how the session's files changed, as a unified diff, without ending it
+pub async fn session_diff(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    sessions: web::Data<Arc<SessionManager>>,
+    id: web::Path<String>,
+) -> Result<HttpResponse> {
+    diff_response(&security, &pools, &sessions, id.into_inner(), false).await
+}
+
+/// Ends a session, returning the consolidated diff of everything that changed
+pub async fn finalize_session(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    sessions: web::Data<Arc<SessionManager>>,
+    id: web::Path<String>,
+) -> Result<HttpResponse> {
+    diff_response(&security, &pools, &sessions, id.into_inner(), true).await
+}
+
+async fn diff_response(
+    security: &Arc<SecurityManager>,
+    pools: &WorkerPools,
+    sessions: &SessionManager,
+    id: String,
+    finalize: bool,
+) -> Result<HttpResponse> {
+    let Some(session) = sessions.get(&id) else {
+        return Ok(missing_session(&id));
+    };
+
+    let decoder = security.clone();
+    match pools.fs.run(move || session.diff(|path, data| decoder.decrypt_for_path(path, data))).await {
+        Ok(Ok((files, diff))) => {
+            if finalize {
+                sessions.remove(&id);
+                info!(target: "audit", "session {} finalized", id);
+            }
+            Ok(HttpResponse::Ok().json(SessionResponse {
+                success: true,
+                session_id: Some(id),
+                files: Some(files),
+                diff: Some(diff),
+                error: None,
+                error_code: None,
+            }))
+        }
+        Ok(Err(e)) => {
+            error!("Failed to diff session {}: {}", id, e);
+            Ok(HttpResponse::Ok().json(failure(Some(id), e.to_string())))
+        }
+        Err(e) => Ok(HttpResponse::Ok().json(failure(Some(id), e.to_string()))),
+    }
+}
+
+/// Ends a session, putting every file back as it was when the session started
+///
+/// Every file is checked against the current policy before it is restored. If
+/// some files cannot be restored the session stays open so the abort can be
+/// retried.
+pub async fn abort_session(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    sessions: web::Data<Arc<SessionManager>>,
+    id: web::Path<String>,
+) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let Some(session) = sessions.get(&id) else {
+        return Ok(missing_session(&id));
+    };
+
+    let restorer = security.get_ref().clone();
+    match pools.fs.run(move || session.restore(&restorer)).await {
+        Ok(failed) if failed.is_empty() => {
+            sessions.remove(&id);
+            info!(target: "audit", "session {} aborted: files restored", id);
+            Ok(HttpResponse::Ok().json(SessionResponse {
+                success: true,
+                session_id: Some(id),
+                files: None,
+                diff: None,
+                error: None,
+                error_code: None,
+            }))
+        }
+        Ok(failed) => {
+            let failed: Vec<String> = failed.iter().map(|(path, e)| format!("{}: {}", path, e)).collect();
+            error!("Failed to restore files of session {}: {}", id, failed.join("; "));
+            Ok(HttpResponse::Ok().json(failure(
+                Some(id),
+                format!("Failed to restore {} files: {}", failed.len(), failed.join("; ")),
+            )))
+        }
+        Err(e) => Ok(HttpResponse::Ok().json(failure(Some(id), e.to_string()))),
+    }
+}
+
+fn missing_session(id: &str) -> HttpResponse {
+    // Session IDs are generated like job IDs
+    if !is_job_id(id) {
+        return HttpResponse::BadRequest().json(failure(None, format!("Invalid session ID: {}", id)));
+    }
+    HttpResponse::NotFound().json(failure(Some(id.to_string()), format!("No open session {}", id)))
+}
@@ -0,0 +1,145 @@
+use actix_web::{web, HttpResponse, Result};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs;
+use tracing::{info, warn, error};
+
+use crate::models::{ShortcutCreateRequest, ShortcutInfo, ShortcutResolveRequest, ShortcutResponse};
+use crate::platform::extended_path;
+use crate::platform::shortcut::{create_shortcut as write_shortcut, is_shortcut, parse_shortcut, MAX_SHORTCUT_BYTES};
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+fn failure(error: String) -> ShortcutResponse {
+    ShortcutResponse {
+        success: false,
+        path: None,
+        shortcut: None,
+        target_allowed: None,
+        error: Some(error),
+    }
+}
+
+/// Reads and parses a shortcut, refusing files too large to be one
+pub async fn read_shortcut(path: &Path) -> std::io::Result<ShortcutInfo> {
+    let metadata = fs::metadata(path).await?;
+    if metadata.len() > MAX_SHORTCUT_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Invalid shortcut: larger than {} bytes", MAX_SHORTCUT_BYTES),
+        ));
+    }
+    parse_shortcut(&fs::read(path).await?)
+}
+
+/// Handles shortcut resolution requests
+pub async fn resolve_shortcut(
+    security: web::Data<Arc<SecurityManager>>,
+    req: web::Json<ShortcutResolveRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+
+    if !security.is_path_allowed(&path) {
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to path: {}", req.path))));
+    }
+
+    info!("Resolving shortcut: {}", req.path);
+
+    match read_shortcut(&path).await {
+        Ok(shortcut) => {
+            let target_allowed = shortcut
+                .target
+                .as_ref()
+                .is_some_and(|target| security.is_path_allowed(&extended_path(Path::new(target))));
+            Ok(HttpResponse::Ok().json(ShortcutResponse {
+                success: true,
+                path: Some(req.path.clone()),
+                shortcut: Some(shortcut),
+                target_allowed: Some(target_allowed),
+                error: None,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to resolve shortcut {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to resolve shortcut: {}", e))))
+        }
+    }
+}
+
+/// Handles shortcut creation requests, such as adding a tool to the Start Menu
+pub async fn create_shortcut(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: web::Json<ShortcutCreateRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+
+    if !is_shortcut(&path) {
+        return Ok(HttpResponse::BadRequest().json(failure(format!("Shortcut path must end in .lnk: {}", req.path))));
+    }
+
+    if !security.is_path_allowed(&path) {
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to path: {}", req.path))));
+    }
+
+    // A shortcut must not become a way to launch something outside the allowed paths
+    if !security.is_path_allowed(&extended_path(Path::new(&req.target))) {
+        warn!("Shortcut target not allowed: {}", req.target);
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to target: {}", req.target))));
+    }
+
+    if let Some(dir) = &req.working_dir
+        && !security.is_path_allowed(&extended_path(Path::new(dir)))
+    {
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to working directory: {}", dir))));
+    }
+
+    if !cfg!(windows) {
+        return Ok(HttpResponse::NotImplemented().json(failure("Shortcuts can only be created on Windows".to_string())));
+    }
+
+    if path.exists() && !req.overwrite.unwrap_or(false) {
+        return Ok(HttpResponse::Ok().json(failure(format!("Shortcut already exists: {}", req.path))));
+    }
+
+    info!("Creating shortcut: {} -> {}", req.path, req.target);
+
+    if let Some(parent) = path.parent()
+        && let Err(e) = fs::create_dir_all(parent).await
+    {
+        error!("Failed to create parent directories for {}: {}", req.path, e);
+        return Ok(HttpResponse::Ok().json(failure(format!("Failed to create parent directories: {}", e))));
+    }
+
+    let shortcut = ShortcutInfo {
+        target: Some(req.target.clone()),
+        arguments: req.arguments.clone(),
+        working_dir: req.working_dir.clone(),
+        description: req.description.clone(),
+        icon_location: req.icon_location.clone(),
+        relative_path: None,
+    };
+    let link = req.path.clone();
+    let result = pools.fs.run(move || write_shortcut(&link, &shortcut)).await;
+    security.invalidate_path_cache(&path);
+
+    let error = match result {
+        Ok(Ok(())) => match read_shortcut(&path).await {
+            Ok(shortcut) => {
+                info!("Successfully created shortcut: {}", req.path);
+                return Ok(HttpResponse::Ok().json(ShortcutResponse {
+                    success: true,
+                    path: Some(req.path.clone()),
+                    shortcut: Some(shortcut),
+                    target_allowed: Some(true),
+                    error: None,
+                }));
+            }
+            Err(e) => e.to_string(),
+        },
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    };
+    error!("Failed to create shortcut {}: {}", req.path, error);
+    Ok(HttpResponse::Ok().json(failure(format!("Failed to create shortcut: {}", error))))
+}
@@ -0,0 +1,158 @@
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::web::Bytes;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Size at which buffered output is flushed to the client
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Quiet time after which an event stream sends a comment, so dead connections are noticed
+pub const SSE_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Comment line sent on quiet event streams
+pub const SSE_KEEPALIVE: &[u8] = b": keepalive\n\n";
+
+/// Number of chunks that may be queued before producers wait for the client
+const STREAM_CHANNEL_CAPACITY: usize = 16;
+
+/// Response body fed from a channel, so producers never hold the whole response
+///
+/// Sending an `Err` aborts the response, which is the only way to signal a
+/// failure once the status line has gone out.
+pub struct ChannelBody {
+    rx: mpsc::Receiver<io::Result<Bytes>>,
+}
+
+impl MessageBody for ChannelBody {
+    type Error = io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Creates a bounded channel and the response body draining it, for async producers
+pub fn channel_body() -> (mpsc::Sender<io::Result<Bytes>>, ChannelBody) {
+    let (tx, rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+    (tx, ChannelBody { rx })
+}
+
+/// Buffers output into chunks and forwards them to a [`ChannelBody`]
+///
+/// Writes block while the client is slow, so the writer is meant to be driven
+/// from a worker pool thread rather than the async executor.
+pub struct ChunkWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl ChunkWriter {
+    /// Creates a writer and the response body draining it
+    pub fn new() -> (Self, ChannelBody) {
+        let (tx, body) = channel_body();
+        let writer = Self {
+            tx,
+            buffer: Vec::with_capacity(STREAM_CHUNK_SIZE),
+        };
+        (writer, body)
+    }
+
+    /// Appends bytes, flushing once a full chunk has accumulated
+    ///
+    /// Returns an error when the client has gone away.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= STREAM_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Sends any buffered bytes
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = Bytes::from(std::mem::replace(
+            &mut self.buffer,
+            Vec::with_capacity(STREAM_CHUNK_SIZE),
+        ));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+
+    /// Checks if the client has gone away
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Flushes remaining output and ends the response
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush()
+    }
+
+    /// Aborts the response with an error
+    pub fn abort(self, error: io::Error) {
+        let _ = self.tx.blocking_send(Err(error));
+    }
+}
+
+/// Formats a server-sent event whose data is JSON on a single line
+pub fn sse_event(event: &str, data: &impl serde::Serialize) -> Bytes {
+    let data = serde_json::to_string(data).unwrap_or_default();
+    Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// Sends a chunk from an async producer, returning false once the client has gone away
+pub async fn send_event(tx: &mpsc::Sender<io::Result<Bytes>>, event: Bytes) -> bool {
+    tx.send(Ok(event)).await.is_ok()
+}
+
+/// Escapes text for embedding inside a JSON string literal, without the quotes
+pub fn escape_json_fragment(text: &str) -> String {
+    let quoted = serde_json::to_string(text).unwrap_or_default();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[test]
+    fn test_escape_json_fragment() {
+        assert_eq!(escape_json_fragment("plain"), "plain");
+        assert_eq!(escape_json_fragment("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[actix_web::test]
+    async fn test_chunk_writer_streams_body() {
+        let (mut writer, body) = ChunkWriter::new();
+
+        std::thread::spawn(move || {
+            writer.write(b"{\"content\":\"").unwrap();
+            for _ in 0..3 {
+                let text = "x".repeat(STREAM_CHUNK_SIZE);
+                writer.write(escape_json_fragment(&text).as_bytes()).unwrap();
+            }
+            writer.write(b"\"}").unwrap();
+            writer.finish().unwrap();
+        });
+
+        let bytes = to_bytes(body).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(value["content"].as_str().unwrap().len(), 3 * STREAM_CHUNK_SIZE);
+    }
+}
@@ -0,0 +1,464 @@
+use actix_web::{web, HttpResponse, Result};
+use globset::GlobSet;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tracing::{error, info, warn};
+
+use crate::handlers::file_ops::{canonical_target, check_disk_space, run_fs, throttle_write, CopyFailure};
+use crate::handlers::operations::start_failure_status;
+use crate::models::{ErrorCode, SyncRequest, SyncResponse};
+use crate::operations::{cancellation_code, CancelToken, OperationRegistry};
+use crate::platform::{create_dir_all_with_mode, display_path, extended_path};
+use crate::security::throttle::ClientId;
+use crate::security::workspace::build_glob_set;
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Changes needed to make the destination match the source, worked out before anything is written
+#[derive(Default)]
+struct SyncPlan {
+    /// Paths relative to the roots of directories to create and files to copy, parents first
+    copies: Vec<(PathBuf, bool)>,
+    /// Paths relative to the roots of extraneous entries, contents before their directory
+    deletions: Vec<(PathBuf, bool)>,
+    unchanged: usize,
+    bytes: u64,
+    skipped: Vec<String>,
+}
+
+/// How the walk decides which files differ and which entries are extraneous
+struct SyncOptions<'a> {
+    exclude: Option<&'a GlobSet>,
+    checksum: bool,
+    delete: bool,
+    cancel: &'a CancelToken,
+}
+
+fn modified_secs(metadata: &Metadata) -> Option<u64> {
+    let modified = metadata.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// SHA-256 of a file's plaintext
+fn content_hash(security: &SecurityManager, path: &Path) -> io::Result<Vec<u8>> {
+    if security.is_encrypted_path(path) {
+        let data = security.decrypt_for_path(path, std::fs::read(path)?).map_err(io::Error::other)?;
+        return Ok(Sha256::digest(&data).to_vec());
+    }
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Whether a destination file already matches its source
+///
+/// Sizes are left out of the quick check when either side is encrypted, since
+/// ciphertext is longer than the content it holds.
+fn is_unchanged(
+    security: &SecurityManager,
+    source: (&Path, &Metadata),
+    target: (&Path, &Metadata),
+    checksum: bool,
+) -> io::Result<bool> {
+    if checksum {
+        return Ok(content_hash(security, source.0)? == content_hash(security, target.0)?);
+    }
+    let encrypted = security.is_encrypted_path(source.0) || security.is_encrypted_path(target.0);
+    Ok((encrypted || source.1.len() == target.1.len()) && modified_secs(source.1) == modified_secs(target.1))
+}
+
+/// Entries a sync would create, replace or delete, the number its endpoint holds against `max_affected_entries`
+pub(crate) fn count_changes(security: &SecurityManager, req: &SyncRequest) -> io::Result<usize> {
+    let exclude = req
+        .exclude
+        .as_deref()
+        .map(build_glob_set)
+        .transpose()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let options = SyncOptions {
+        exclude: exclude.as_ref(),
+        checksum: req.checksum.unwrap_or(false),
+        delete: req.delete.unwrap_or(false),
+        cancel: &CancelToken::default(),
+    };
+    let source = extended_path(Path::new(&req.source));
+    let destination = extended_path(Path::new(&req.destination));
+    let plan = plan_sync(security, &source, &destination, &options)?;
+    Ok(plan.copies.len() + plan.deletions.len())
+}
+
+/// Walks the source like a copy does and compares each entry with its counterpart at the destination
+///
+/// Symlinks are never followed on either side. Excluded entries are neither
+/// copied nor deleted, and neither are destination entries whose source the
+/// policy denies.
+fn plan_sync(security: &SecurityManager, source: &Path, destination: &Path, options: &SyncOptions) -> io::Result<SyncPlan> {
+    let mut plan = SyncPlan::default();
+    let mut walked = HashSet::new();
+    let mut source_dirs = HashSet::new();
+    if std::fs::symlink_metadata(destination).is_err() {
+        plan.copies.push((PathBuf::new(), true));
+    }
+
+    let mut stack = vec![PathBuf::new()];
+    while let Some(relative) = stack.pop() {
+        source_dirs.insert(relative.clone());
+        let mut children = std::fs::read_dir(source.join(&relative))?.collect::<io::Result<Vec<_>>>()?;
+        children.sort_by_key(|child| child.file_name());
+        let mut subdirectories = Vec::new();
+        for child in children {
+            options.cancel.check()?;
+            let child_relative = relative.join(child.file_name());
+            if options.exclude.is_some_and(|exclude| exclude.is_match(&child_relative)) {
+                continue;
+            }
+            walked.insert(child_relative.clone());
+
+            let from = extended_path(&child.path());
+            let to = destination.join(&child_relative);
+            let metadata = std::fs::symlink_metadata(&from)?;
+            let existing = std::fs::symlink_metadata(&to).ok();
+            if !(metadata.is_dir() || metadata.is_file()) || !security.is_path_allowed(&from) {
+                plan.skipped.push(display_path(&from));
+            } else if metadata.is_dir() {
+                match existing {
+                    None => plan.copies.push((child_relative.clone(), true)),
+                    Some(existing) if !existing.is_dir() => {
+                        plan.skipped.push(display_path(&to));
+                        continue;
+                    }
+                    Some(_) => {}
+                }
+                subdirectories.push(child_relative);
+            } else {
+                match existing {
+                    Some(existing) if !existing.is_file() => plan.skipped.push(display_path(&to)),
+                    Some(existing) if is_unchanged(security, (&from, &metadata), (&to, &existing), options.checksum)? => {
+                        plan.unchanged += 1;
+                    }
+                    _ => {
+                        plan.bytes += metadata.len();
+                        plan.copies.push((child_relative, false));
+                    }
+                }
+            }
+        }
+        stack.extend(subdirectories.into_iter().rev());
+    }
+
+    if options.delete && destination.is_dir() {
+        let mut stack = vec![PathBuf::new()];
+        while let Some(relative) = stack.pop() {
+            let mut children = std::fs::read_dir(destination.join(&relative))?.collect::<io::Result<Vec<_>>>()?;
+            children.sort_by_key(|child| child.file_name());
+            for child in children {
+                options.cancel.check()?;
+                let child_relative = relative.join(child.file_name());
+                let is_dir = child.file_type()?.is_dir();
+                if walked.contains(&child_relative) {
+                    if is_dir && source_dirs.contains(&child_relative) {
+                        stack.push(child_relative);
+                    }
+                } else if !options.exclude.is_some_and(|exclude| exclude.is_match(&child_relative)) {
+                    plan_deletion(destination, child_relative, is_dir, &mut plan.deletions)?;
+                }
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Adds an extraneous entry, and everything below it if it is a directory, contents first
+fn plan_deletion(destination: &Path, relative: PathBuf, is_dir: bool, deletions: &mut Vec<(PathBuf, bool)>) -> io::Result<()> {
+    if is_dir {
+        for child in std::fs::read_dir(destination.join(&relative))? {
+            let child = child?;
+            plan_deletion(destination, relative.join(child.file_name()), child.file_type()?.is_dir(), deletions)?;
+        }
+    }
+    deletions.push((relative, is_dir));
+    Ok(())
+}
+
+/// Carries out a sync; copied files take the source's modification time so later syncs see them as unchanged
+///
+/// Paths are checked with the rules `/api/write` applies just before they are
+/// written. Deletions are all checked before the first one, so a refused
+/// deletion removes nothing. Cancellation stops between entries, keeping what
+/// was already synced.
+fn execute_sync(
+    security: &SecurityManager,
+    source: &Path,
+    destination: &Path,
+    plan: &SyncPlan,
+    force: bool,
+    cancel: &CancelToken,
+) -> Result<(), CopyFailure> {
+    let check = |target: &Path| {
+        if !security.is_path_allowed(target) || security.is_workspace_policy_path(target) {
+            let error = format!("Access denied to destination path: {}", display_path(target));
+            return Err(CopyFailure::Refused(error, None));
+        }
+        security
+            .check_protected(target, force)
+            .map_err(|e| CopyFailure::Refused(e, Some(ErrorCode::ProtectedPath)))
+    };
+
+    let dir_mode = security.dir_mode();
+    for (relative, is_dir) in &plan.copies {
+        cancel.check()?;
+        let (from, to) = (source.join(relative), destination.join(relative));
+        check(&to)?;
+        if *is_dir {
+            create_dir_all_with_mode(&to, dir_mode)?;
+        } else {
+            if security.is_encrypted_path(&from) || security.is_encrypted_path(&to) {
+                let data = security.decrypt_for_path(&from, std::fs::read(&from)?).map_err(io::Error::other)?;
+                let data = security.encrypt_for_path(&to, data).map_err(io::Error::other)?;
+                std::fs::write(&to, data)?;
+            } else {
+                std::fs::copy(&from, &to)?;
+            }
+            File::options().write(true).open(&to)?.set_modified(std::fs::metadata(&from)?.modified()?)?;
+        }
+        security.invalidate_path_cache(&to);
+    }
+
+    for (relative, _) in &plan.deletions {
+        check(&destination.join(relative))?;
+    }
+    for (relative, is_dir) in &plan.deletions {
+        cancel.check()?;
+        let target = destination.join(relative);
+        if *is_dir {
+            std::fs::remove_dir(&target)?;
+        } else {
+            std::fs::remove_file(&target)?;
+        }
+        security.invalidate_path_cache(&target);
+    }
+    Ok(())
+}
+
+/// Handles requests to make a destination directory mirror a source directory
+///
+/// Only files that differ are copied, so repeating a sync is cheap. This covers
+/// what clients would otherwise do by running `rsync` or `robocopy` through exec.
+pub async fn sync_directories(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    operations: web::Data<Arc<OperationRegistry>>,
+    client: ClientId,
+    req: web::Json<SyncRequest>,
+) -> Result<HttpResponse> {
+    let source = extended_path(Path::new(&req.source));
+    let destination = extended_path(Path::new(&req.destination));
+    let dry_run = req.dry_run.unwrap_or(false);
+    let failure = |error: String, error_code: Option<ErrorCode>| SyncResponse {
+        success: false,
+        source: None,
+        destination: None,
+        dry_run,
+        copied: None,
+        deleted: None,
+        unchanged: None,
+        bytes: None,
+        skipped: None,
+        error: Some(error),
+        error_code,
+    };
+
+    // The source is only read; the destination gets the checks of a write
+    if !security.is_path_allowed(&source) {
+        let error = format!("Access denied to source path: {}", req.source);
+        return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+    }
+    if !security.is_path_allowed(&destination) || security.is_workspace_policy_path(&destination) {
+        let error = format!("Access denied to destination path: {}", req.destination);
+        return Ok(HttpResponse::Forbidden().json(failure(error, None)));
+    }
+    let force = req.force.unwrap_or(false);
+    if let Err(e) = security.check_protected(&destination, force) {
+        warn!("Sync refused for {} -> {}: {}", req.source, req.destination, e);
+        return Ok(HttpResponse::Forbidden().json(failure(e, Some(ErrorCode::ProtectedPath))));
+    }
+
+    if !source.exists() {
+        let error = format!("Source path does not exist: {}", req.source);
+        return Ok(HttpResponse::Ok().json(failure(error, None)));
+    }
+    if !source.is_dir() {
+        let error = format!("Source path is not a directory: {}", req.source);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    }
+    if destination.exists() && !destination.is_dir() {
+        let error = format!("Destination path is not a directory: {}", req.destination);
+        return Ok(HttpResponse::Ok().json(failure(error, None)));
+    }
+
+    // Either root inside the other would copy into what is being walked, or delete the source
+    let (source_real, destination_real) = (canonical_target(&source), canonical_target(&destination));
+    if destination_real.starts_with(&source_real) || source_real.starts_with(&destination_real) {
+        let error = format!("Cannot sync {} and {}, as one contains the other", req.source, req.destination);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    }
+
+    let exclude = match req.exclude.as_deref().map(build_glob_set).transpose() {
+        Ok(exclude) => exclude,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(failure(format!("Invalid exclude pattern {}", e), None))),
+    };
+    let (checksum, delete) = (req.checksum.unwrap_or(false), req.delete.unwrap_or(false));
+    let summary = format!("{} -> {}{}", req.source, req.destination, if delete { " with deletion" } else { "" });
+    let operation = match operations.start(req.operation_id.as_deref(), "sync", &req.source, summary) {
+        Ok(operation) => operation,
+        Err(e) => return Ok(HttpResponse::build(start_failure_status(&e)).json(failure(e.to_string(), None))),
+    };
+
+    let planner = security.get_ref().clone();
+    let (from, to, cancel) = (source.clone(), destination.clone(), operation.token());
+    let planned = run_fs(&pools, move || {
+        let options = SyncOptions { exclude: exclude.as_ref(), checksum, delete, cancel: &cancel };
+        plan_sync(&planner, &from, &to, &options)
+    })
+    .await;
+    let plan = match planned {
+        Ok(plan) => plan,
+        Err(e) => {
+            error!("Failed to sync {} -> {}: {}", req.source, req.destination, e);
+            return Ok(HttpResponse::Ok().json(failure(format!("Failed to sync: {}", e), cancellation_code(&e))));
+        }
+    };
+
+    let changes = plan.copies.len() + plan.deletions.len();
+    if let Some(limit) = security.max_affected_entries()
+        && changes > limit
+        && !req.override_entry_limit.unwrap_or(false)
+    {
+        let error = format!(
+            "Operation would affect more than {} entries; set override_entry_limit to proceed",
+            limit
+        );
+        warn!("Sync refused for {} -> {}: {}", req.source, req.destination, error);
+        return Ok(HttpResponse::Forbidden().json(failure(error, Some(ErrorCode::TooManyEntries))));
+    }
+
+    let listed = |entries: &[(PathBuf, bool)]| entries.iter().map(|(relative, _)| display_path(&destination.join(relative))).collect();
+    let response = |plan: &SyncPlan| SyncResponse {
+        success: true,
+        source: Some(req.source.clone()),
+        destination: Some(req.destination.clone()),
+        dry_run,
+        copied: Some(listed(&plan.copies)),
+        deleted: Some(listed(&plan.deletions)),
+        unchanged: Some(plan.unchanged),
+        bytes: Some(plan.bytes),
+        skipped: Some(plan.skipped.clone()),
+        error: None,
+        error_code: None,
+    };
+    if dry_run {
+        info!("Sync dry run: {} -> {} ({} changes)", req.source, req.destination, changes);
+        return Ok(HttpResponse::Ok().json(response(&plan)));
+    }
+
+    if let Err(e) = check_disk_space(&security, &pools, &destination, plan.bytes).await {
+        warn!("Sync refused for {} -> {}: {}", req.source, req.destination, e);
+        return Ok(HttpResponse::InsufficientStorage().json(failure(e, Some(ErrorCode::InsufficientSpace))));
+    }
+
+    throttle_write(&security, &client, &req.destination, plan.bytes).await;
+
+    info!(
+        "Syncing: {} -> {} ({} copies, {} deletions, {} bytes)",
+        req.source,
+        req.destination,
+        plan.copies.len(),
+        plan.deletions.len(),
+        plan.bytes
+    );
+    let syncer = security.get_ref().clone();
+    let (from, to, cancel) = (source.clone(), destination.clone(), operation.token());
+    cancel.set_total(changes);
+    let result = run_fs(&pools, move || Ok(execute_sync(&syncer, &from, &to, &plan, force, &cancel).map(|_| plan))).await;
+    security.invalidate_path_cache(&destination);
+
+    match result {
+        Ok(Ok(plan)) => {
+            info!("Successfully synced: {} -> {}", req.source, req.destination);
+            Ok(HttpResponse::Ok().json(response(&plan)))
+        }
+        Ok(Err(CopyFailure::Refused(e, error_code))) => {
+            warn!("Sync refused for {} -> {}: {}", req.source, req.destination, e);
+            Ok(HttpResponse::Forbidden().json(failure(e, error_code)))
+        }
+        Ok(Err(CopyFailure::Io(e))) | Err(e) => {
+            error!("Failed to sync {} -> {}: {}", req.source, req.destination, e);
+            Ok(HttpResponse::Ok().json(failure(format!("Failed to sync: {}", e), cancellation_code(&e))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_plan() {
+        let dir = std::env::temp_dir().join(format!("exex-sync-{}", uuid::Uuid::new_v4().simple()));
+        let (source, destination) = (dir.join("source"), dir.join("destination"));
+        std::fs::create_dir_all(source.join("src")).unwrap();
+        std::fs::create_dir_all(source.join("target")).unwrap();
+        std::fs::write(source.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(source.join("target").join("app"), "binary").unwrap();
+        std::fs::write(source.join("README.md"), "# Project\n").unwrap();
+
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let exclude = build_glob_set(&["target".to_string()]).unwrap();
+        let cancel = CancelToken::default();
+        let options = |checksum, delete| SyncOptions { exclude: Some(&exclude), checksum, delete, cancel: &cancel };
+        let relative = |entries: &[(PathBuf, bool)]| {
+            let names = entries.iter().map(|(path, _)| path.to_string_lossy().replace('\\', "/"));
+            names.collect::<Vec<_>>()
+        };
+
+        // Everything but the excluded directory is copied into a new destination
+        let plan = plan_sync(&security, &source, &destination, &options(false, true)).unwrap();
+        assert_eq!(relative(&plan.copies), ["", "README.md", "src", "src/main.rs"]);
+        assert!(execute_sync(&security, &source, &destination, &plan, false, &cancel).is_ok());
+        assert!(!destination.join("target").exists());
+
+        // A second pass finds nothing to do
+        let plan = plan_sync(&security, &source, &destination, &options(false, true)).unwrap();
+        assert!(plan.copies.is_empty() && plan.deletions.is_empty());
+        assert_eq!(plan.unchanged, 2);
+
+        // Changed files are copied again and extraneous entries removed, only with delete
+        std::fs::write(source.join("README.md"), "# Renamed project\n").unwrap();
+        std::fs::create_dir_all(destination.join("old")).unwrap();
+        std::fs::write(destination.join("old").join("notes.txt"), "stale").unwrap();
+        std::fs::create_dir_all(destination.join("target")).unwrap();
+        let plan = plan_sync(&security, &source, &destination, &options(false, false)).unwrap();
+        assert_eq!(relative(&plan.copies), ["README.md"]);
+        assert!(plan.deletions.is_empty());
+        let plan = plan_sync(&security, &source, &destination, &options(false, true)).unwrap();
+        assert_eq!(relative(&plan.deletions), ["old/notes.txt", "old"]);
+        assert!(execute_sync(&security, &source, &destination, &plan, false, &cancel).is_ok());
+        assert!(!destination.join("old").exists());
+        assert!(destination.join("target").exists());
+
+        // Same size and time but different content is only noticed by checksum
+        std::fs::write(destination.join("src").join("main.rs"), "fn mian() {}\n").unwrap();
+        let modified = std::fs::metadata(source.join("src").join("main.rs")).unwrap().modified().unwrap();
+        File::options().write(true).open(destination.join("src").join("main.rs")).unwrap().set_modified(modified).unwrap();
+        assert!(plan_sync(&security, &source, &destination, &options(false, true)).unwrap().copies.is_empty());
+        let plan = plan_sync(&security, &source, &destination, &options(true, true)).unwrap();
+        assert_eq!(relative(&plan.copies), ["src/main.rs"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
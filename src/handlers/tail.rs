@@ -0,0 +1,409 @@
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Result};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::handlers::file_ops::{run_fs, special_file_error};
+use crate::handlers::stream::{channel_body, send_event, sse_event, SSE_KEEPALIVE, SSE_KEEPALIVE_INTERVAL, STREAM_CHUNK_SIZE};
+use crate::models::{ErrorCode, TailLine, TailRequest, TailResponse};
+use crate::platform::{extended_path, file_id, open_for_read, read_bytes, special_file_at};
+use crate::security::SecurityManager;
+use crate::workers::WorkerPools;
+
+/// Lines returned when the request does not say
+const DEFAULT_TAIL_LINES: usize = 10;
+
+/// Most lines a single tail may ask for
+const MAX_TAIL_LINES: usize = 10_000;
+
+/// Bytes read backwards from the end before giving up on finding more lines
+const TAIL_SCAN_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// How often a followed file is checked for new content
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Longest unterminated line held back while following; longer ones are sent as they are
+const MAX_PENDING_LINE: usize = 1024 * 1024;
+
+/// Splits `data`, which starts at byte `base` of the file, into its last `count` lines
+///
+/// Without `complete`, the data does not start at a line boundary, so its
+/// first segment is dropped as a partial line.
+fn split_tail(data: &[u8], base: u64, count: usize, complete: bool) -> Vec<TailLine> {
+    if data.is_empty() {
+        return vec![];
+    }
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    let mut lines = Vec::new();
+    let mut offset = base;
+    for (index, segment) in data.split(|&b| b == b'\n').enumerate() {
+        if index > 0 || complete {
+            lines.push(TailLine {
+                offset,
+                line: String::from_utf8_lossy(segment.strip_suffix(b"\r").unwrap_or(segment)).into_owned(),
+            });
+        }
+        offset += segment.len() as u64 + 1;
+    }
+    let skip = lines.len().saturating_sub(count);
+    lines.split_off(skip)
+}
+
+/// Reads the last `count` lines of a file and the size they were read up to
+///
+/// Plain files are read backwards in chunks until enough lines are found, so
+/// large logs are not read whole. Encrypted files are decrypted whole first.
+fn last_lines(security: &SecurityManager, path: &Path, count: usize) -> io::Result<(Vec<TailLine>, u64)> {
+    if security.is_encrypted_path(path) {
+        let data = security.decrypt_for_path(path, read_bytes(path)?).map_err(io::Error::other)?;
+        return Ok((split_tail(&data, 0, count, true), data.len() as u64));
+    }
+
+    let mut file = open_for_read(path)?;
+    let size = file.metadata()?.len();
+    let mut chunks = Vec::new();
+    let mut position = size;
+    let mut newlines = 0;
+    while position > 0 && newlines < count && size - position < TAIL_SCAN_LIMIT {
+        let length = (position.min(STREAM_CHUNK_SIZE as u64)) as usize;
+        position -= length as u64;
+        let mut chunk = vec![0; length];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut chunk)?;
+        // A newline ending the file does not start another line
+        let trailing = usize::from(position + length as u64 == size && chunk.last() == Some(&b'\n'));
+        newlines += chunk.iter().filter(|&&b| b == b'\n').count() - trailing;
+        chunks.push(chunk);
+    }
+
+    let data: Vec<u8> = chunks.into_iter().rev().flatten().collect();
+    Ok((split_tail(&data, position, count, position == 0), size))
+}
+
+/// A followed file, held open so that another file appearing at its path can be told apart
+struct Followed {
+    file: File,
+    id: (u64, u64),
+}
+
+impl Followed {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = open_for_read(path)?;
+        let id = file_id(&file)?;
+        Ok(Self { file, id })
+    }
+}
+
+/// What changed in a followed file since the last poll
+enum Appended {
+    Data(Vec<u8>),
+    /// The file is now shorter than what was already sent
+    Truncated(u64),
+    /// Another file now sits at the path, as after log rotation; it is followed from its start
+    Replaced,
+}
+
+/// Reads up to `limit` bytes appended to the followed file after `offset`
+///
+/// Once the held file has been read to its end, the path is checked for a
+/// different file. A replacement gets the policy checks of a new tail before
+/// it is followed, and is refused with [`io::ErrorKind::PermissionDenied`].
+fn read_appended(security: &SecurityManager, path: &Path, followed: &mut Followed, offset: u64, limit: usize) -> io::Result<Appended> {
+    let size = followed.file.metadata()?.len();
+    if size < offset {
+        return Ok(Appended::Truncated(size));
+    }
+    if size > offset {
+        followed.file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::new();
+        (&followed.file).take((size - offset).min(limit as u64)).read_to_end(&mut data)?;
+        return Ok(Appended::Data(data));
+    }
+
+    // Checked before opening, since opening a FIFO would block
+    let denied = || io::Error::new(io::ErrorKind::PermissionDenied, "the file was replaced by one the policy does not allow");
+    if let Some(special) = special_file_at(path) {
+        let error = io::Error::new(io::ErrorKind::PermissionDenied, format!("the file was replaced by a {}", special));
+        return Err(error);
+    }
+    let current = match Followed::open(path) {
+        Ok(current) => current,
+        // The path is missing for a moment while a log is rotated
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Appended::Data(Vec::new())),
+        Err(e) => return Err(e),
+    };
+    if current.id == followed.id {
+        return Ok(Appended::Data(Vec::new()));
+    }
+    if !security.is_path_allowed(path) || current.file.metadata()?.is_dir() {
+        return Err(denied());
+    }
+    *followed = current;
+    Ok(Appended::Replaced)
+}
+
+/// Pushes lines appended to a file as `line` events until the client disconnects
+///
+/// The file stays open, and each poll is a short read on the filesystem pool,
+/// so followers do not hold a worker thread. A file that shrinks is announced
+/// with a `truncated` event and followed from its start. Once the held file
+/// has no more data, a different file at the path, as after log rotation, is
+/// announced with a `rotated` event and followed from its start; a path that
+/// is missing is waited for.
+async fn follow_file(
+    security: Arc<SecurityManager>,
+    pools: Arc<WorkerPools>,
+    path: PathBuf,
+    name: String,
+    followed: Followed,
+    mut offset: u64,
+    tx: mpsc::Sender<io::Result<Bytes>>,
+) {
+    let mut pending: Vec<u8> = Vec::new();
+    let mut last_sent = Instant::now();
+    // Shared with each poll, so that a poll the pool turns away does not take the file with it
+    let followed = Arc::new(Mutex::new(followed));
+    loop {
+        tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+        if tx.is_closed() {
+            break;
+        }
+
+        let (checker, reader, held) = (security.clone(), path.clone(), followed.clone());
+        let read_from = offset + pending.len() as u64;
+        let polled = pools.fs.run(move || {
+            let mut held = held.lock().unwrap_or_else(PoisonError::into_inner);
+            read_appended(&checker, &reader, &mut held, read_from, STREAM_CHUNK_SIZE)
+        });
+        let appended = match polled.await {
+            Ok(Ok(appended)) => appended,
+            Ok(Err(e)) => {
+                error!("Failed to follow {}: {}", name, e);
+                let event = sse_event("error", &serde_json::json!({"error": format!("Failed to read file: {}", e)}));
+                let _ = send_event(&tx, event).await;
+                break;
+            }
+            // A saturated pool only delays this poll
+            Err(_) => continue,
+        };
+
+        let data = match appended {
+            Appended::Truncated(size) => {
+                pending.clear();
+                offset = 0;
+                last_sent = Instant::now();
+                if !send_event(&tx, sse_event("truncated", &serde_json::json!({"size": size}))).await {
+                    break;
+                }
+                continue;
+            }
+            Appended::Replaced => {
+                // The old file ended without a newline; its last line is complete now
+                if !pending.is_empty() {
+                    let lines = split_tail(&pending, offset, usize::MAX, true);
+                    pending.clear();
+                    for line in lines {
+                        if !send_event(&tx, sse_event("line", &line)).await {
+                            return;
+                        }
+                    }
+                }
+                offset = 0;
+                last_sent = Instant::now();
+                if !send_event(&tx, sse_event("rotated", &serde_json::json!({}))).await {
+                    break;
+                }
+                continue;
+            }
+            Appended::Data(data) => data,
+        };
+
+        pending.extend_from_slice(&data);
+        let complete = match pending.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => newline + 1,
+            None if pending.len() >= MAX_PENDING_LINE => pending.len(),
+            None => 0,
+        };
+        if complete > 0 {
+            let lines = split_tail(&pending[..complete], offset, usize::MAX, true);
+            pending.drain(..complete);
+            offset += complete as u64;
+            for line in lines {
+                if !send_event(&tx, sse_event("line", &line)).await {
+                    return;
+                }
+            }
+            last_sent = Instant::now();
+        } else if last_sent.elapsed() >= SSE_KEEPALIVE_INTERVAL {
+            if !send_event(&tx, Bytes::from_static(SSE_KEEPALIVE)).await {
+                break;
+            }
+            last_sent = Instant::now();
+        }
+    }
+    info!("Stopped following {}", name);
+}
+
+/// Handles requests for the last lines of a file, optionally following it as it grows
+///
+/// With `follow`, the response is a `text/event-stream` that starts with the
+/// requested lines and then pushes lines as they are appended, which suits
+/// build logs written by launched processes.
+pub async fn tail_file(
+    security: web::Data<Arc<SecurityManager>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: web::Json<TailRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+    let count = req.lines.unwrap_or(DEFAULT_TAIL_LINES);
+    let follow = req.follow.unwrap_or(false);
+    let failure = |error: String, error_code: Option<ErrorCode>| TailResponse {
+        success: false,
+        path: None,
+        lines: vec![],
+        size: None,
+        error: Some(error),
+        error_code,
+    };
+
+    if !security.is_path_allowed(&path) {
+        return Ok(HttpResponse::Forbidden().json(failure(format!("Access denied to file: {}", req.path), None)));
+    }
+    if count > MAX_TAIL_LINES {
+        let error = format!("At most {} lines can be requested", MAX_TAIL_LINES);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    }
+    if let Some(special) = special_file_at(&path) {
+        warn!("Refusing to tail {}: it is a {}", req.path, special);
+        let error = special_file_error(&req.path, special);
+        return Ok(HttpResponse::BadRequest().json(failure(error, Some(ErrorCode::SpecialFile))));
+    }
+    if path.is_dir() {
+        return Ok(HttpResponse::BadRequest().json(failure(format!("Path is a directory: {}", req.path), None)));
+    }
+    if follow && security.is_encrypted_path(&path) {
+        let error = format!("Encrypted files cannot be followed: {}", req.path);
+        return Ok(HttpResponse::BadRequest().json(failure(error, None)));
+    }
+
+    let reader = security.get_ref().clone();
+    let target = path.clone();
+    // A follower holds the file open from the start, so a later replacement is noticed
+    let tailed = run_fs(&pools, move || {
+        let followed = follow.then(|| Followed::open(&target)).transpose()?;
+        let (lines, size) = last_lines(&reader, &target, count)?;
+        Ok((lines, size, followed))
+    });
+    let (lines, size, followed) = match tailed.await {
+        Ok(tail) => tail,
+        Err(e) => {
+            error!("Failed to tail {}: {}", req.path, e);
+            return Ok(HttpResponse::Ok().json(failure(format!("Failed to read file: {}", e), None)));
+        }
+    };
+
+    let Some(followed) = followed else {
+        info!("Read the last {} lines of {}", lines.len(), req.path);
+        return Ok(HttpResponse::Ok().json(TailResponse {
+            success: true,
+            path: Some(req.path.clone()),
+            lines: lines.into_iter().map(|line| line.line).collect(),
+            size: Some(size),
+            error: None,
+            error_code: None,
+        }));
+    };
+
+    info!("Following {}", req.path);
+    let (tx, body) = channel_body();
+    let (security, pools, name) = (security.get_ref().clone(), pools.get_ref().clone(), req.path.clone());
+    actix_web::rt::spawn(async move {
+        for line in lines {
+            if !send_event(&tx, sse_event("line", &line)).await {
+                return;
+            }
+        }
+        follow_file(security, pools, path, name, followed, size, tx).await;
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_lines() {
+        let path = std::env::temp_dir().join(format!("exex-tail-{}", uuid::Uuid::new_v4().simple()));
+        let security = SecurityManager::new(crate::config::get_default_config());
+        let text = |lines: Vec<TailLine>| lines.into_iter().map(|line| line.line).collect::<Vec<_>>();
+
+        std::fs::write(&path, "one\r\ntwo\nthree\n").unwrap();
+        let (lines, size) = last_lines(&security, &path, 2).unwrap();
+        assert_eq!(size, 15);
+        assert_eq!(lines[0].offset, 5);
+        assert_eq!(text(lines), ["two", "three"]);
+        assert_eq!(text(last_lines(&security, &path, 10).unwrap().0), ["one", "two", "three"]);
+        assert!(last_lines(&security, &path, 0).unwrap().0.is_empty());
+
+        // Lines spanning several read chunks, without a final newline
+        let long = "x".repeat(STREAM_CHUNK_SIZE);
+        std::fs::write(&path, format!("first\n{}\n{}\nlast", long, long)).unwrap();
+        let (lines, _) = last_lines(&security, &path, 3).unwrap();
+        assert_eq!(lines[0].offset, 6);
+        assert_eq!(text(lines), [long.clone(), long, "last".to_string()]);
+
+        std::fs::write(&path, "").unwrap();
+        assert!(last_lines(&security, &path, 5).unwrap().0.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_follow_rotation() {
+        let dir = std::env::temp_dir().join(format!("exex-tail-{}", uuid::Uuid::new_v4().simple()));
+        let denied = dir.join("denied");
+        std::fs::create_dir_all(&denied).unwrap();
+        let mut config = crate::config::get_default_config();
+        config.security.allowed_paths = vec![];
+        config.security.disallowed_paths = vec![denied.to_string_lossy().into_owned()];
+        let security = SecurityManager::new(config);
+        let data = |appended: io::Result<Appended>| match appended.unwrap() {
+            Appended::Data(data) => String::from_utf8(data).unwrap(),
+            _ => panic!("expected data"),
+        };
+
+        let path = dir.join("build.log");
+        std::fs::write(&path, "a\n").unwrap();
+        let mut followed = Followed::open(&path).unwrap();
+        assert_eq!(data(read_appended(&security, &path, &mut followed, 2, 1024)), "");
+
+        // The old file's last lines come first, then the new file from its start although it is larger
+        std::fs::write(&path, "a\nb\n").unwrap();
+        std::fs::rename(&path, dir.join("build.log.1")).unwrap();
+        std::fs::write(&path, "first line of the new log\nsecond\n").unwrap();
+        assert_eq!(data(read_appended(&security, &path, &mut followed, 2, 1024)), "b\n");
+        assert!(matches!(read_appended(&security, &path, &mut followed, 4, 1024).unwrap(), Appended::Replaced));
+        assert_eq!(data(read_appended(&security, &path, &mut followed, 0, 1024)), "first line of the new log\nsecond\n");
+
+        #[cfg(unix)]
+        {
+            std::fs::write(denied.join("secret"), "secret\n").unwrap();
+            std::fs::remove_file(&path).unwrap();
+            std::os::unix::fs::symlink(denied.join("secret"), &path).unwrap();
+            let refused = read_appended(&security, &path, &mut followed, 33, 1024).err().unwrap();
+            assert_eq!(refused.kind(), io::ErrorKind::PermissionDenied);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+use actix_web::{web, HttpResponse, Result};
+use schemars::{schema_for, JsonSchema};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::config::{default_editors, default_url_schemes};
+use crate::models::{
+    ArchiveRequest, Config, CopyRequest, CreateRequest, DeleteRequest, EditRequest, ExecRequest, ExtractRequest, FindRequest, HashRequest, OpenAppRequest, OpenEditorRequest, OpenTerminalRequest,
+    OpenUrlRequest, ReadRequest, RenameRequest, ScanRequest, SearchRequest, SyncRequest, TailRequest, ToolDescription, ToolManifestResponse, WriteRequest,
+};
+use crate::routes::version::ApiVersion;
+use crate::security::throttle::ClientId;
+use crate::security::SecurityManager;
+
+/// Describes the operations a client may call, with parameter schemas, limits and examples
+///
+/// Every client is held to the same policy, so the manifest only leaves out
+/// operations that the configuration makes unusable.
+pub async fn tool_manifest(
+    config: web::Data<Arc<Config>>,
+    security: web::Data<Arc<SecurityManager>>,
+    client: ClientId,
+    api_version: ApiVersion,
+) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(ToolManifestResponse {
+        success: true,
+        client_id: client.0,
+        api_version: api_version.0,
+        tools: describe_tools(&config, &security, api_version.0),
+    }))
+}
+
+/// Builds the manifest entries for the operations available under this configuration
+pub fn describe_tools(config: &Config, security: &SecurityManager, api_version: u32) -> Vec<ToolDescription> {
+    let prefix = format!("/api/v{}", api_version);
+    let tool = |name: &str, path: &str, parameters: Value, description: &str, limits: Value, examples: Vec<Value>| {
+        ToolDescription {
+            name: name.to_string(),
+            description: description.to_string(),
+            method: "POST".to_string(),
+            path: format!("{}{}", prefix, path),
+            parameters,
+            limits,
+            examples,
+        }
+    };
+
+    let mut allowed_commands: Vec<&String> = security.get_command_whitelist().iter().collect();
+    allowed_commands.sort();
+    let mut denied_commands: Vec<&String> = security.get_command_blacklist().iter().collect();
+    denied_commands.sort();
+    let paths = json!({
+        "allowed_paths": config.security.allowed_paths,
+        "disallowed_paths": config.security.disallowed_paths,
+    });
+    let with_paths = |limits: Value| {
+        let mut merged = paths.clone();
+        if let (Some(merged), Value::Object(extra)) = (merged.as_object_mut(), limits) {
+            merged.extend(extra);
+        }
+        merged
+    };
+    let max_spawned_processes = security.max_spawned_processes().unwrap_or(0);
+
+    let mut tools = vec![
+        tool(
+            "exec",
+            "/exec",
+            schema::<ExecRequest>(),
+            "Run a whitelisted command and return its output. Give `args` to run the program directly; without them `command` runs through the system shell.",
+            with_paths(json!({
+                "allowed_commands": allowed_commands,
+                "denied_commands": denied_commands,
+                "default_priority": security.process_priority(None),
+            })),
+            vec![
+                json!({"command": "git", "args": ["status", "--porcelain"], "cwd": "/home/user/Projects/app"}),
+                json!({"command": "cargo", "args": ["test"], "timeout_secs": 600}),
+            ],
+        ),
+        tool(
+            "read",
+            "/read",
+            schema::<ReadRequest>(),
+            "Read a file. Binary files must be requested with `encoding: base64`.",
+            with_paths(json!({ "max_file_size_bytes": security.max_file_size_bytes() })),
+            vec![json!({"path": "/home/user/Projects/app/README.md"})],
+        ),
+        tool(
+            "write",
+            "/write",
+            schema::<WriteRequest>(),
+            "Create or replace a file with the given text content.",
+            with_paths(json!({
+                "max_file_size_bytes": security.max_file_size_bytes(),
+                "disk_reserve_bytes": security.disk_reserve_bytes(),
+                "write_bytes_per_sec": security.write_limit_bytes_per_sec(),
+            })),
+            vec![json!({"path": "/home/user/Projects/app/notes.txt", "content": "hello", "verify": true})],
+        ),
+        tool(
+            "edit",
+            "/edit",
+            schema::<EditRequest>(),
+            "Insert, replace or delete lines of a text file without sending the whole file. Line numbers refer to the file before the edit; pass the `hash` of the previous edit as `expected_hash` to refuse edits of a file that changed.",
+            with_paths(json!({ "max_file_size_bytes": security.max_file_size_bytes() })),
+            vec![json!({
+                "path": "/home/user/Projects/app/src/main.rs",
+                "operations": [
+                    {"op": "replace_range", "start_line": 12, "end_line": 13, "content": "    run()?;"},
+                    {"op": "insert_at_line", "line": 1, "content": "use app::run;"}
+                ]
+            })],
+        ),
+        tool(
+            "scan",
+            "/scan",
+            schema::<ScanRequest>(),
+            "List a directory, optionally recursively, sorted and paginated.",
+            paths.clone(),
+            vec![json!({"path": "/home/user/Projects/app", "recursive": true, "limit": 100, "sort_by": "mtime"})],
+        ),
+        tool(
+            "search",
+            "/search",
+            schema::<SearchRequest>(),
+            "Search the text files below a directory for a string or regular expression, returning matching lines with context. Ignored, hidden and binary files are skipped.",
+            paths.clone(),
+            vec![json!({"path": "/home/user/Projects/app", "query": "fn main", "include": ["*.rs"], "context_lines": 2})],
+        ),
+        tool(
+            "find",
+            "/find",
+            schema::<FindRequest>(),
+            "Find files below a directory whose paths match glob patterns, such as `**/*.rs`. Much cheaper than a recursive scan.",
+            paths.clone(),
+            vec![json!({"path": "/home/user/Projects/app", "patterns": ["**/*.rs", "Cargo.toml"], "respect_gitignore": true})],
+        ),
+        tool(
+            "hash",
+            "/hash",
+            schema::<HashRequest>(),
+            "Compute a file's SHA-256, SHA-1, MD5, xxHash64 or XXH3 checksum without reading its content, optionally comparing it with an expected value.",
+            paths.clone(),
+            vec![json!({"path": "/home/user/Downloads/release.zip", "algorithm": "sha256", "expected": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"})],
+        ),
+        tool(
+            "tail",
+            "/tail",
+            schema::<TailRequest>(),
+            "Return the last lines of a file, such as a build log. With `follow`, the response becomes a server-sent event stream of lines appended later.",
+            paths.clone(),
+            vec![json!({"path": "/home/user/Projects/app/build.log", "lines": 50})],
+        ),
+        tool(
+            "delete",
+            "/delete",
+            schema::<DeleteRequest>(),
+            "Delete a file, or a directory with `recursive`.",
+            with_paths(json!({ "max_affected_entries": security.max_affected_entries().unwrap_or(0) })),
+            vec![json!({"path": "/home/user/Projects/app/target", "recursive": true})],
+        ),
+        tool(
+            "create",
+            "/create",
+            schema::<CreateRequest>(),
+            "Create a file with optional content, or a directory.",
+            with_paths(json!({ "max_file_size_bytes": security.max_file_size_bytes() })),
+            vec![
+                json!({"path": "/home/user/Projects/app/src", "is_directory": true}),
+                json!({"path": "/home/user/Projects/app/src/lib.rs", "is_directory": false, "content": ""}),
+            ],
+        ),
+        tool(
+            "rename",
+            "/rename",
+            schema::<RenameRequest>(),
+            "Rename or move a file or directory.",
+            paths.clone(),
+            vec![json!({"from_path": "/home/user/Projects/app/old.txt", "to_path": "/home/user/Projects/app/new.txt"})],
+        ),
+        tool(
+            "copy",
+            "/copy",
+            schema::<CopyRequest>(),
+            "Copy a file, or a directory with `recursive`, byte for byte.",
+            with_paths(json!({ "max_affected_entries": security.max_affected_entries().unwrap_or(0) })),
+            vec![json!({"from_path": "/home/user/Projects/app/assets", "to_path": "/home/user/Projects/app/dist/assets", "recursive": true})],
+        ),
+        tool(
+            "archive",
+            "/archive",
+            schema::<ArchiveRequest>(),
+            "Pack files and directories into a zip or tar.gz archive, the format following the extension of `path`.",
+            paths.clone(),
+            vec![json!({"path": "/home/user/Projects/app-src.tar.gz", "sources": ["/home/user/Projects/app"], "exclude": ["target", ".git"]})],
+        ),
+        tool(
+            "extract",
+            "/extract",
+            schema::<ExtractRequest>(),
+            "Unpack a zip or tar.gz archive into a directory. Links in the archive are skipped, and existing files are kept unless `overwrite` is set.",
+            with_paths(json!({ "max_affected_entries": security.max_affected_entries().unwrap_or(0) })),
+            vec![json!({"path": "/home/user/Downloads/release.zip", "destination": "/home/user/Projects/release"})],
+        ),
+        tool(
+            "sync",
+            "/sync",
+            schema::<SyncRequest>(),
+            "Make a destination directory mirror a source directory, copying only files that changed. Use `dry_run` to preview and `delete` to remove files missing from the source.",
+            with_paths(json!({ "max_affected_entries": security.max_affected_entries().unwrap_or(0) })),
+            vec![json!({"source": "/home/user/Projects/app/dist", "destination": "/mnt/deploy/app", "delete": true, "dry_run": true})],
+        ),
+        tool(
+            "open",
+            "/open",
+            schema::<OpenAppRequest>(),
+            "Launch an application, detached unless `wait` is set.",
+            with_paths(json!({ "max_spawned_processes": max_spawned_processes })),
+            vec![json!({"application": "/usr/bin/gitk", "cwd": "/home/user/Projects/app"})],
+        ),
+        tool(
+            "open_terminal",
+            "/open_terminal",
+            schema::<OpenTerminalRequest>(),
+            "Open a terminal window in a directory.",
+            with_paths(json!({ "max_spawned_processes": max_spawned_processes })),
+            vec![json!({"path": "/home/user/Projects/app"})],
+        ),
+    ];
+
+    let apps = config.apps.as_ref();
+    let editors = apps.and_then(|a| a.editors.clone()).unwrap_or_else(default_editors);
+    if !editors.is_empty() {
+        let mut names: Vec<&String> = editors.keys().collect();
+        names.sort();
+        tools.push(tool(
+            "open_editor",
+            "/open_editor",
+            schema::<OpenEditorRequest>(),
+            "Open a file in a configured editor at a line and column.",
+            with_paths(json!({
+                "editors": names,
+                "default_editor": apps.and_then(|a| a.default_editor.clone()),
+                "max_spawned_processes": max_spawned_processes,
+            })),
+            vec![json!({"path": "/home/user/Projects/app/src/main.rs", "line": 42, "column": 7})],
+        ));
+    }
+
+    let schemes = apps.and_then(|a| a.url_schemes.clone()).unwrap_or_else(default_url_schemes);
+    if !schemes.is_empty() {
+        tools.push(tool(
+            "open_url",
+            "/open_url",
+            schema::<OpenUrlRequest>(),
+            "Open a URL with the desktop's default handler.",
+            json!({
+                "url_schemes": schemes,
+                "url_domains": apps.and_then(|a| a.url_domains.clone()).unwrap_or_default(),
+                "max_spawned_processes": max_spawned_processes,
+            }),
+            vec![json!({"url": "https://docs.rs"})],
+        ));
+    }
+
+    tools
+}
+
+fn schema<T: JsonSchema>() -> Value {
+    serde_json::to_value(schema_for!(T)).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use crate::policy::PolicyRequest;
+
+    #[test]
+    fn test_tool_examples_match_schemas() {
+        let mut config = get_default_config();
+        let security = SecurityManager::new(config.clone());
+        let tools = describe_tools(&config, &security, 2);
+        assert_eq!(tools.len(), 20);
+
+        for tool in &tools {
+            assert!(tool.path.starts_with("/api/v2/"));
+            assert!(tool.parameters["properties"].is_object(), "{} has no parameter schema", tool.name);
+            // Every example must be a request the endpoint accepts
+            for example in &tool.examples {
+                let mut request = example.clone();
+                request["op"] = json!(tool.name);
+                let parsed: PolicyRequest = serde_json::from_value(request).unwrap();
+                assert_eq!(parsed.name(), tool.name);
+            }
+        }
+
+        let exec = &tools[0];
+        assert!(exec.parameters["properties"]["env"].is_object(), "flattened fields are described");
+        assert!(exec.limits["allowed_commands"].as_array().unwrap().contains(&json!("git")));
+
+        config.apps.as_mut().unwrap().url_schemes = Some(vec![]);
+        let tools = describe_tools(&config, &security, 1);
+        assert!(tools.iter().all(|tool| tool.name != "open_url"));
+    }
+}
@@ -0,0 +1,145 @@
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Result};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
+
+use crate::handlers::file_ops::run_fs;
+use crate::handlers::stream::{channel_body, send_event, sse_event, SSE_KEEPALIVE, SSE_KEEPALIVE_INTERVAL};
+use crate::jobs::is_job_id;
+use crate::models::{EventsQuery, WatchListResponse, WatchRequest, WatchResponse};
+use crate::platform::extended_path;
+use crate::security::SecurityManager;
+use crate::watch::{visible_event, WatchError, WatchService, MAX_WATCHES};
+use crate::workers::WorkerPools;
+
+fn watch_failure(error: String) -> WatchResponse {
+    WatchResponse {
+        success: false,
+        watch: None,
+        error: Some(error),
+    }
+}
+
+/// Registers a file or directory whose changes are streamed by `/api/events`
+pub async fn add_watch(
+    security: web::Data<Arc<SecurityManager>>,
+    watches: web::Data<Arc<WatchService>>,
+    pools: web::Data<Arc<WorkerPools>>,
+    req: web::Json<WatchRequest>,
+) -> Result<HttpResponse> {
+    let path = extended_path(Path::new(&req.path));
+    if !security.is_path_allowed(&path) {
+        return Ok(HttpResponse::Forbidden().json(watch_failure(format!("Access denied to path: {}", req.path))));
+    }
+
+    let service = watches.get_ref().clone();
+    let recursive = req.recursive.unwrap_or(true);
+    // Platform watchers report resolved paths, so symlinked locations are watched by their target
+    let result = run_fs(&pools, move || Ok(service.add(path.canonicalize()?, recursive))).await;
+    match result {
+        Ok(Ok(watch)) => Ok(HttpResponse::Ok().json(WatchResponse {
+            success: true,
+            watch: Some(watch),
+            error: None,
+        })),
+        Ok(Err(WatchError::Duplicate(existing))) => Ok(HttpResponse::Conflict().json(WatchResponse {
+            error: Some(format!("Path is already watched: {}", req.path)),
+            watch: Some(existing),
+            success: false,
+        })),
+        Ok(Err(WatchError::Limit)) => {
+            let error = format!("At most {} paths can be watched at once", MAX_WATCHES);
+            Ok(HttpResponse::Conflict().json(watch_failure(error)))
+        }
+        Ok(Err(WatchError::Failed(e))) => {
+            error!("Failed to watch {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(watch_failure(format!("Failed to watch path: {}", e))))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Ok(HttpResponse::NotFound().json(watch_failure(format!("Path not found: {}", req.path))))
+        }
+        Err(e) => {
+            error!("Failed to watch {}: {}", req.path, e);
+            Ok(HttpResponse::Ok().json(watch_failure(format!("Failed to watch path: {}", e))))
+        }
+    }
+}
+
+/// Lists the registered watches
+pub async fn list_watches(watches: web::Data<Arc<WatchService>>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(WatchListResponse {
+        success: true,
+        watches: watches.list(),
+    }))
+}
+
+/// Stops a watch
+pub async fn remove_watch(watches: web::Data<Arc<WatchService>>, id: web::Path<String>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    if !is_job_id(&id) {
+        return Ok(HttpResponse::BadRequest().json(watch_failure(format!("Invalid watch ID: {}", id))));
+    }
+    match watches.remove(&id) {
+        Some(watch) => Ok(HttpResponse::Ok().json(WatchResponse {
+            success: true,
+            watch: Some(watch),
+            error: None,
+        })),
+        None => Ok(HttpResponse::NotFound().json(watch_failure(format!("Watch not found: {}", id)))),
+    }
+}
+
+/// Streams changes below watched paths as server-sent events
+///
+/// Each event is named after its kind (`create`, `modify`, `delete` or
+/// `rename`). Events are checked against the policy as they are sent, so
+/// changes to denied paths never reach the client. A subscriber too slow to
+/// keep up gets a `lagged` event with the number of events it missed.
+pub async fn watch_events(
+    security: web::Data<Arc<SecurityManager>>,
+    watches: web::Data<Arc<WatchService>>,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse> {
+    let filter = query.into_inner().watch;
+    let mut events = watches.subscribe();
+    let security = security.get_ref().clone();
+    let (tx, body) = channel_body();
+
+    actix_web::rt::spawn(async move {
+        loop {
+            // Filtered events send nothing, so a departed client is not noticed by sending alone
+            if tx.is_closed() {
+                break;
+            }
+            let chunk = match tokio::time::timeout(SSE_KEEPALIVE_INTERVAL, events.recv()).await {
+                Err(_) => Bytes::from_static(SSE_KEEPALIVE),
+                Ok(Ok(event)) => {
+                    if filter.as_ref().is_some_and(|id| *id != event.watch_id) {
+                        continue;
+                    }
+                    let Some(event) = visible_event(&security, event) else {
+                        continue;
+                    };
+                    sse_event(event.kind.name(), &event)
+                }
+                Ok(Err(RecvError::Lagged(missed))) => {
+                    warn!("Event subscriber missed {} events", missed);
+                    sse_event("lagged", &serde_json::json!({"missed": missed}))
+                }
+                Ok(Err(RecvError::Closed)) => break,
+            };
+            if !send_event(&tx, chunk).await {
+                break;
+            }
+        }
+        info!("Event stream closed");
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .body(body))
+}
@@ -0,0 +1,15 @@
+use actix_web::{web, HttpResponse, Result};
+use std::sync::Arc;
+
+use crate::models::WorkerStatsResponse;
+use crate::workers::WorkerPools;
+
+/// Reports queue depth and throughput for the exec and filesystem worker pools
+pub async fn worker_stats(
+    pools: web::Data<Arc<WorkerPools>>,
+) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(WorkerStatsResponse {
+        success: true,
+        pools: pools.stats(),
+    }))
+}
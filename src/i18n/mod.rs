@@ -0,0 +1,333 @@
+use actix_web::{
+    body::{self, BodySize, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderValue},
+    middleware::Next,
+    web, Error,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use tracing::warn;
+
+use crate::models::ErrorCode;
+
+/// Language used when the client accepts none of the catalog's languages
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Directory in the config directory holding `<language>.json` message overrides
+pub const MESSAGES_DIR: &str = "messages";
+
+/// Largest JSON response that is inspected for an error code
+const MAX_LOCALIZED_BODY: u64 = 64 * 1024;
+
+const BUILTIN_MESSAGES: &[(&str, &[(ErrorCode, &str)])] = &[
+    (
+        "en",
+        &[
+            (ErrorCode::InsufficientSpace, "There is not enough free disk space to save this file."),
+            (ErrorCode::TooManyEntries, "This operation would affect more files than are allowed at once."),
+            (ErrorCode::BinaryContent, "This file is not text and cannot be shown as text."),
+            (ErrorCode::ProtectedPath, "This file is protected and can only be changed when explicitly forced."),
+            (ErrorCode::InvalidSyntax, "The content contains a syntax error and was not saved."),
+            (ErrorCode::HashMismatch, "The file was changed by someone else; reload it and try again."),
+            (ErrorCode::SpecialFile, "This is a pipe, socket or device, not a regular file, and cannot be opened."),
+            (ErrorCode::InvalidPath, "A path in the request is too long, too deeply nested or contains invalid characters."),
+            (ErrorCode::Cancelled, "The operation was cancelled before it finished."),
+        ],
+    ),
+    (
+        "de",
+        &[
+            (ErrorCode::InsufficientSpace, "Auf dem Datenträger ist nicht genug freier Speicherplatz, um diese Datei zu speichern."),
+            (ErrorCode::TooManyEntries, "Dieser Vorgang würde mehr Dateien betreffen, als auf einmal erlaubt sind."),
+            (ErrorCode::BinaryContent, "Diese Datei enthält keinen Text und kann nicht als Text angezeigt werden."),
+            (ErrorCode::ProtectedPath, "Diese Datei ist geschützt und kann nur ausdrücklich erzwungen geändert werden."),
+            (ErrorCode::InvalidSyntax, "Der Inhalt enthält einen Syntaxfehler und wurde nicht gespeichert."),
+            (ErrorCode::HashMismatch, "Die Datei wurde zwischenzeitlich geändert; laden Sie sie neu und versuchen Sie es erneut."),
+            (ErrorCode::SpecialFile, "Dies ist eine Pipe, ein Socket oder ein Gerät und keine normale Datei; sie kann nicht geöffnet werden."),
+            (ErrorCode::InvalidPath, "Ein Pfad in der Anfrage ist zu lang, zu tief verschachtelt oder enthält ungültige Zeichen."),
+            (ErrorCode::Cancelled, "Der Vorgang wurde abgebrochen, bevor er abgeschlossen war."),
+        ],
+    ),
+    (
+        "es",
+        &[
+            (ErrorCode::InsufficientSpace, "No hay suficiente espacio libre en el disco para guardar este archivo."),
+            (ErrorCode::TooManyEntries, "Esta operación afectaría a más archivos de los permitidos a la vez."),
+            (ErrorCode::BinaryContent, "Este archivo no es de texto y no se puede mostrar como texto."),
+            (ErrorCode::ProtectedPath, "Este archivo está protegido y solo se puede modificar si se fuerza explícitamente."),
+            (ErrorCode::InvalidSyntax, "El contenido tiene un error de sintaxis y no se ha guardado."),
+            (ErrorCode::HashMismatch, "El archivo ha cambiado mientras tanto; vuelva a cargarlo e inténtelo de nuevo."),
+            (ErrorCode::SpecialFile, "Esto es una tubería, un socket o un dispositivo, no un archivo normal, y no se puede abrir."),
+            (ErrorCode::InvalidPath, "Una ruta de la solicitud es demasiado larga, está demasiado anidada o contiene caracteres no válidos."),
+            (ErrorCode::Cancelled, "La operación se canceló antes de terminar."),
+        ],
+    ),
+    (
+        "fr",
+        &[
+            (ErrorCode::InsufficientSpace, "Il n'y a pas assez d'espace disque libre pour enregistrer ce fichier."),
+            (ErrorCode::TooManyEntries, "Cette opération toucherait plus de fichiers qu'il n'est permis en une seule fois."),
+            (ErrorCode::BinaryContent, "Ce fichier n'est pas un fichier texte et ne peut pas être affiché comme texte."),
+            (ErrorCode::ProtectedPath, "Ce fichier est protégé et ne peut être modifié qu'en le forçant explicitement."),
+            (ErrorCode::InvalidSyntax, "Le contenu contient une erreur de syntaxe et n'a pas été enregistré."),
+            (ErrorCode::HashMismatch, "Le fichier a été modifié entre-temps ; rechargez-le et réessayez."),
+            (ErrorCode::SpecialFile, "Il s'agit d'un tube, d'un socket ou d'un périphérique, pas d'un fichier ordinaire ; il ne peut pas être ouvert."),
+            (ErrorCode::InvalidPath, "Un chemin de la requête est trop long, trop profondément imbriqué ou contient des caractères non valides."),
+            (ErrorCode::Cancelled, "L'opération a été annulée avant d'être terminée."),
+        ],
+    ),
+];
+
+/// Human-readable messages for [`ErrorCode`]s, by language
+///
+/// Codes stay the stable contract for machines; messages are for people and may
+/// be reworded or overridden per installation.
+pub struct ErrorCatalog {
+    languages: HashMap<String, HashMap<ErrorCode, String>>,
+}
+
+impl ErrorCatalog {
+    /// The messages shipped with EXEX
+    pub fn builtin() -> Self {
+        let languages = BUILTIN_MESSAGES
+            .iter()
+            .map(|(language, messages)| {
+                let messages = messages.iter().map(|(code, text)| (*code, text.to_string())).collect();
+                (language.to_string(), messages)
+            })
+            .collect();
+        Self { languages }
+    }
+
+    /// Built-in messages, overridden and extended by `<language>.json` files in `dir`
+    ///
+    /// Each file maps codes to messages, e.g. `{"INSUFFICIENT_SPACE": "..."}`.
+    pub fn load(dir: &Path) -> Self {
+        let mut catalog = Self::builtin();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return catalog;
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(language) = path.file_stem().and_then(|s| s.to_str()).map(str::to_ascii_lowercase) else {
+                continue;
+            };
+            let messages: HashMap<ErrorCode, String> = match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+            {
+                Ok(messages) => messages,
+                Err(e) => {
+                    warn!("Ignoring message catalog {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            catalog.languages.entry(language).or_default().extend(messages);
+        }
+        catalog
+    }
+
+    /// Languages with at least one message, sorted
+    pub fn languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = self.languages.keys().cloned().collect();
+        languages.sort();
+        languages
+    }
+
+    /// Picks the best catalog language for an `Accept-Language` header
+    pub fn negotiate(&self, accept_language: Option<&str>) -> String {
+        let mut ranges: Vec<(String, f32)> = accept_language
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim().to_ascii_lowercase();
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, so equally weighted ranges keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges
+            .iter()
+            .find_map(|(tag, _)| {
+                if self.languages.contains_key(tag) {
+                    return Some(tag.clone());
+                }
+                let primary = tag.split('-').next().unwrap_or(tag);
+                self.languages.contains_key(primary).then(|| primary.to_string())
+            })
+            .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string())
+    }
+
+    /// Message for a code, falling back to English and then to the code itself
+    pub fn message(&self, language: &str, code: ErrorCode) -> String {
+        [language, DEFAULT_LANGUAGE]
+            .iter()
+            .find_map(|language| self.languages.get(*language)?.get(&code).cloned())
+            .unwrap_or_else(|| serde_json::to_value(code).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default())
+    }
+
+    /// Every code's message in one language
+    pub fn messages(&self, language: &str) -> BTreeMap<ErrorCode, String> {
+        ErrorCode::ALL.iter().map(|code| (*code, self.message(language, *code))).collect()
+    }
+}
+
+fn builtin_catalog() -> &'static ErrorCatalog {
+    static CATALOG: OnceLock<ErrorCatalog> = OnceLock::new();
+    CATALOG.get_or_init(ErrorCatalog::builtin)
+}
+
+/// Adds a localized `error_message` next to the `error_code` of JSON error responses
+///
+/// The message language is negotiated from `Accept-Language` and reported in
+/// `Content-Language`. Streaming and large responses are passed through untouched.
+pub async fn localize_errors(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody, Vec<u8>>>, Error> {
+    let catalog = req.app_data::<web::Data<Arc<ErrorCatalog>>>().map(|c| c.get_ref().clone());
+    let accept_language = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let res = next.call(req).await?;
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let small = matches!(res.response().body().size(), BodySize::Sized(size) if size <= MAX_LOCALIZED_BODY);
+    if !is_json || !small {
+        return Ok(res.map_into_left_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.into().to_string()))?;
+
+    let catalog = catalog.as_deref().unwrap_or_else(|| builtin_catalog());
+    let (body, language) = match add_error_message(&bytes, catalog, accept_language.as_deref()) {
+        Some((body, language)) => (body, Some(language)),
+        None => (bytes.to_vec(), None),
+    };
+    if let Some(value) = language.and_then(|l| HeaderValue::from_str(&l).ok()) {
+        res.headers_mut().insert(header::CONTENT_LANGUAGE, value);
+    }
+    Ok(ServiceResponse::new(req, res.set_body(body)).map_into_right_body())
+}
+
+/// Inserts `error_message` into a JSON object whose `error_code` is set
+fn add_error_message(body: &[u8], catalog: &ErrorCatalog, accept_language: Option<&str>) -> Option<(Vec<u8>, String)> {
+    // Cheap check before parsing: successful responses serialize `"error_code":null`
+    if !body.windows(14).any(|w| w == b"\"error_code\":\"") {
+        return None;
+    }
+
+    let mut value: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let object = value.as_object_mut()?;
+    let code: ErrorCode = serde_json::from_value(object.get("error_code")?.clone()).ok()?;
+    let language = catalog.negotiate(accept_language);
+    object.insert("error_message".to_string(), catalog.message(&language, code).into());
+    Some((serde_json::to_vec(&value).ok()?, language))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{App, HttpResponse};
+    use crate::models::WriteResponse;
+
+    #[test]
+    fn test_negotiate_language() {
+        let catalog = ErrorCatalog::builtin();
+        assert_eq!(catalog.negotiate(None), "en");
+        assert_eq!(catalog.negotiate(Some("de-CH, fr;q=0.9")), "de");
+        assert_eq!(catalog.negotiate(Some("ja, fr;q=0.5, de;q=0.8")), "de");
+        assert_eq!(catalog.negotiate(Some("de;q=0, es")), "es");
+        assert_eq!(catalog.negotiate(Some("*")), "en");
+    }
+
+    #[test]
+    fn test_catalog_overrides() {
+        let dir = std::env::temp_dir().join(format!("exex-messages-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("nl.json"), r#"{"BINARY_CONTENT": "Dit bestand is geen tekst."}"#).unwrap();
+        fs::write(dir.join("en.json"), r#"{"TOO_MANY_ENTRIES": "Too many files."}"#).unwrap();
+        fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let catalog = ErrorCatalog::load(&dir);
+        assert_eq!(catalog.message("nl", ErrorCode::BinaryContent), "Dit bestand is geen tekst.");
+        // Untranslated codes fall back to English
+        assert_eq!(catalog.message("nl", ErrorCode::TooManyEntries), "Too many files.");
+        assert!(catalog.languages().contains(&"nl".to_string()));
+        assert!(!catalog.languages().contains(&"broken".to_string()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_error_responses_are_localized() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(localize_errors))
+                .route(
+                    "/fail",
+                    web::get().to(|| async {
+                        HttpResponse::InsufficientStorage().json(WriteResponse {
+                            success: false,
+                            error: Some("Not enough space".to_string()),
+                            error_code: Some(ErrorCode::InsufficientSpace),
+                            checksum: None,
+                            syntax_error: None,
+                            formatted_content: None,
+                            format_error: None,
+                        })
+                    }),
+                )
+                .route(
+                    "/ok",
+                    web::get().to(|| async {
+                        HttpResponse::Ok().json(WriteResponse {
+                            success: true,
+                            error: None,
+                            error_code: None,
+                            checksum: None,
+                            syntax_error: None,
+                            formatted_content: None,
+                            format_error: None,
+                        })
+                    }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/fail").insert_header(("Accept-Language", "fr-CA")).to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 507);
+        assert_eq!(resp.headers().get("content-language").unwrap(), "fr");
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["error_code"], "INSUFFICIENT_SPACE");
+        assert_eq!(body["error_message"], ErrorCatalog::builtin().message("fr", ErrorCode::InsufficientSpace));
+
+        let resp = call_service(&app, TestRequest::get().uri("/ok").to_request()).await;
+        assert!(resp.headers().get("content-language").is_none());
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert!(body.get("error_message").is_none());
+    }
+}
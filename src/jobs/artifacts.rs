@@ -0,0 +1,242 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::jobs::is_job_id;
+use crate::jobs::isolation::is_relative_within;
+use crate::models::ArtifactInfo;
+use crate::platform::display_path;
+use crate::security::path_rules::GLOB_CHARS;
+
+/// The `artifacts` globs of a job, relative to the directory it ran in
+pub struct ArtifactPatterns {
+    matcher: GlobSet,
+    /// Literal directories the globs start below, so unrelated trees are not walked
+    bases: Vec<PathBuf>,
+}
+
+impl ArtifactPatterns {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut set = GlobSetBuilder::new();
+        let mut bases = Vec::new();
+        for pattern in patterns {
+            let normalized = pattern.replace('\\', "/");
+            if !is_relative_within(Path::new(&normalized)) {
+                return Err(format!("Artifact patterns must be relative paths below cwd, got '{}'", pattern));
+            }
+            let glob = GlobBuilder::new(normalized.trim_start_matches("./"))
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("Invalid artifact pattern '{}': {}", pattern, e))?;
+            set.add(glob);
+            let base: PathBuf = normalized
+                .split('/')
+                .take_while(|part| !part.contains(GLOB_CHARS))
+                .filter(|part| *part != ".")
+                .collect();
+            // A pattern without wildcards names a file, so its base is the directory holding it
+            let base = match normalized.contains(GLOB_CHARS) {
+                true => base,
+                false => base.parent().map(Path::to_path_buf).unwrap_or_default(),
+            };
+            if !bases.iter().any(|known: &PathBuf| base.starts_with(known)) {
+                bases.retain(|known: &PathBuf| !known.starts_with(&base));
+                bases.push(base);
+            }
+        }
+        let matcher = set.build().map_err(|e| e.to_string())?;
+        Ok(Self { matcher, bases })
+    }
+
+    fn matches(&self, relative: &Path) -> bool {
+        self.matcher.is_match(relative)
+    }
+}
+
+/// Files collected from jobs' working directories once they end, kept in `<dir>/<job id>/`
+///
+/// Each job's artifacts are capped at `max_bytes`; files past the cap are
+/// skipped. Only the newest `retention` jobs' artifacts are kept.
+pub struct ArtifactStore {
+    dir: PathBuf,
+    max_bytes: u64,
+    retention: usize,
+}
+
+impl ArtifactStore {
+    pub fn new(dir: PathBuf, max_bytes: u64, retention: usize) -> Self {
+        Self { dir, max_bytes, retention }
+    }
+
+    /// Copies the files below `root` that match `patterns` into the job's store
+    ///
+    /// `allow` is asked about every file; symlinks are not followed.
+    pub fn collect(
+        &self,
+        id: &str,
+        root: &Path,
+        patterns: &ArtifactPatterns,
+        allow: &dyn Fn(&Path) -> bool,
+    ) -> io::Result<Vec<ArtifactInfo>> {
+        if !is_job_id(id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid job ID"));
+        }
+        self.prune();
+        let store = self.dir.join(id);
+        fs::create_dir_all(&store)?;
+
+        let mut matched = Vec::new();
+        for base in &patterns.bases {
+            walk(root, base, &mut matched);
+        }
+        matched.sort();
+        matched.dedup();
+
+        let mut collected = Vec::new();
+        let mut total = 0u64;
+        for relative in matched.into_iter().filter(|relative| patterns.matches(relative)) {
+            let source = root.join(&relative);
+            if !allow(&source) {
+                warn!("Not collecting artifact {}: refused by security policy", source.display());
+                continue;
+            }
+            let size = fs::symlink_metadata(&source)?.len();
+            if total + size > self.max_bytes {
+                warn!("Not collecting artifact {} of job {}: over the {} byte limit", relative.display(), id, self.max_bytes);
+                continue;
+            }
+            let target = store.join(&relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&source, &target)?;
+            total += size;
+            collected.push(ArtifactInfo {
+                path: display_path(&relative),
+                size,
+            });
+        }
+        debug!("Collected {} artifacts ({} bytes) of job {}", collected.len(), total, id);
+        Ok(collected)
+    }
+
+    /// The artifacts stored for a job
+    pub fn list(&self, id: &str) -> io::Result<Vec<ArtifactInfo>> {
+        let store = self.job_dir(id)?;
+        if !store.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("No artifacts for job {}", id)));
+        }
+        let mut files = Vec::new();
+        walk(&store, Path::new(""), &mut files);
+        files.sort();
+        files
+            .into_iter()
+            .map(|relative| {
+                Ok(ArtifactInfo {
+                    size: fs::metadata(store.join(&relative))?.len(),
+                    path: display_path(&relative),
+                })
+            })
+            .collect()
+    }
+
+    /// Where a stored artifact is, if the path stays inside the job's store
+    pub fn path(&self, id: &str, artifact: &str) -> io::Result<PathBuf> {
+        let relative = Path::new(artifact);
+        if !is_relative_within(relative) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid artifact path: {}", artifact)));
+        }
+        Ok(self.job_dir(id)?.join(relative))
+    }
+
+    fn job_dir(&self, id: &str) -> io::Result<PathBuf> {
+        if !is_job_id(id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid job ID"));
+        }
+        Ok(self.dir.join(id))
+    }
+
+    /// Deletes the artifacts of the oldest jobs beyond the retention limit
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut jobs: Vec<(std::time::SystemTime, PathBuf)> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                is_job_id(&entry.file_name().to_string_lossy()).then(|| (modified, entry.path()))
+            })
+            .collect();
+        // Make room for the job being collected
+        let keep = self.retention.saturating_sub(1);
+        if jobs.len() <= keep {
+            return;
+        }
+        jobs.sort();
+        for (_, dir) in &jobs[..jobs.len() - keep] {
+            if let Err(e) = fs::remove_dir_all(dir) {
+                warn!("Failed to remove old artifacts {}: {}", dir.display(), e);
+            }
+        }
+    }
+}
+
+/// Collects the regular files below `root/base`, as paths relative to `root`; symlinks are skipped
+fn walk(root: &Path, base: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(root.join(base)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let relative = base.join(entry.file_name());
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => walk(root, &relative, files),
+            Ok(kind) if kind.is_file() => files.push(relative),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifact_store() {
+        let project = tempfile::tempdir().unwrap();
+        let root = project.path();
+        fs::create_dir_all(root.join("dist/wheels")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("dist/app.whl"), "wheel").unwrap();
+        fs::write(root.join("dist/wheels/dep.whl"), "a dependency wheel of 30 bytes").unwrap();
+        fs::write(root.join("dist/notes.txt"), "notes").unwrap();
+        fs::write(root.join("report.xml"), "<testsuite/>").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+
+        assert!(ArtifactPatterns::new(&["../secrets/*".to_string()]).is_err());
+        assert!(ArtifactPatterns::new(&["dist/[a".to_string()]).is_err());
+        let patterns = ArtifactPatterns::new(&["dist/**/*.whl".to_string(), "./report.xml".to_string()]).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(dir.path().to_path_buf(), 20, 2);
+        let first = crate::jobs::new_job_id();
+        let collected = store.collect(&first, root, &patterns, &|_| true).unwrap();
+        let paths: Vec<&str> = collected.iter().map(|artifact| artifact.path.as_str()).collect();
+        // The dependency wheel would go past the 20 byte limit
+        assert_eq!(paths, vec!["dist/app.whl", "report.xml"]);
+        assert_eq!(store.list(&first).unwrap().len(), 2);
+        assert_eq!(fs::read_to_string(store.path(&first, "dist/app.whl").unwrap()).unwrap(), "wheel");
+        assert!(store.path(&first, "../other").is_err());
+        assert!(store.list("../config").is_err());
+
+        let refused = store.collect(&crate::jobs::new_job_id(), root, &patterns, &|path| !path.ends_with("report.xml")).unwrap();
+        assert_eq!(refused.len(), 1);
+
+        // Only the newest jobs are kept
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        store.collect(&crate::jobs::new_job_id(), root, &patterns, &|_| true).unwrap();
+        assert_eq!(store.list(&first).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+/// The `depends_on` edges of a job group, checked to name known jobs and form no cycle
+#[derive(Debug)]
+pub struct JobGraph {
+    names: Vec<String>,
+    deps: Vec<Vec<usize>>,
+    /// Every job after the jobs it depends on
+    order: Vec<usize>,
+}
+
+impl JobGraph {
+    /// Builds the graph of `(name, depends_on)` pairs
+    pub fn new(jobs: &[(String, Vec<String>)]) -> Result<Self, String> {
+        let mut index = HashMap::new();
+        for (i, (name, _)) in jobs.iter().enumerate() {
+            if name.trim().is_empty() {
+                return Err("Every job of a group needs a name".to_string());
+            }
+            if index.insert(name.as_str(), i).is_some() {
+                return Err(format!("Job name '{}' is used more than once", name));
+            }
+        }
+
+        let mut deps = Vec::with_capacity(jobs.len());
+        for (name, depends_on) in jobs {
+            let mut edges = Vec::new();
+            for dep in depends_on {
+                match index.get(dep.as_str()) {
+                    Some(&i) if !edges.contains(&i) => edges.push(i),
+                    Some(_) => {}
+                    None => return Err(format!("Job '{}' depends on unknown job '{}'", name, dep)),
+                }
+            }
+            deps.push(edges);
+        }
+
+        // Kahn's algorithm; whatever is left over sits on a cycle
+        let mut waiting: Vec<usize> = deps.iter().map(Vec::len).collect();
+        let mut order: Vec<usize> = (0..jobs.len()).filter(|&i| waiting[i] == 0).collect();
+        let mut next = 0;
+        while next < order.len() {
+            let done = order[next];
+            next += 1;
+            for (i, edges) in deps.iter().enumerate() {
+                if edges.contains(&done) {
+                    waiting[i] -= 1;
+                    if waiting[i] == 0 {
+                        order.push(i);
+                    }
+                }
+            }
+        }
+        if let Some(i) = (0..jobs.len()).find(|&i| waiting[i] > 0) {
+            return Err(format!("Job '{}' is part of a dependency cycle", jobs[i].0));
+        }
+
+        Ok(Self {
+            names: jobs.iter().map(|(name, _)| name.clone()).collect(),
+            deps,
+            order,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn name(&self, job: usize) -> &str {
+        &self.names[job]
+    }
+}
+
+/// Which jobs of a graph may start, given how the ones before them ended
+pub struct Schedule {
+    graph: JobGraph,
+    started: Vec<bool>,
+    /// Whether each ended job succeeded
+    outcome: Vec<Option<bool>>,
+    running: usize,
+}
+
+impl Schedule {
+    pub fn new(graph: JobGraph) -> Self {
+        let len = graph.len();
+        Self {
+            graph,
+            started: vec![false; len],
+            outcome: vec![None; len],
+            running: 0,
+        }
+    }
+
+    pub fn graph(&self) -> &JobGraph {
+        &self.graph
+    }
+
+    /// Gives up on jobs that depend on one that did not succeed; returns them with that dependency
+    pub fn skip_blocked(&mut self) -> Vec<(usize, usize)> {
+        let mut skipped = Vec::new();
+        // In dependency order, so a skip reaches everything downstream in one pass
+        for &job in &self.graph.order {
+            if self.started[job] {
+                continue;
+            }
+            if let Some(&dep) = self.graph.deps[job].iter().find(|&&dep| self.outcome[dep] == Some(false)) {
+                self.started[job] = true;
+                self.outcome[job] = Some(false);
+                skipped.push((job, dep));
+            }
+        }
+        skipped
+    }
+
+    /// Marks as running the jobs whose dependencies all succeeded, while fewer than `limit` run
+    pub fn start_ready(&mut self, limit: usize) -> Vec<usize> {
+        let mut ready = Vec::new();
+        for &job in &self.graph.order {
+            if self.running >= limit {
+                break;
+            }
+            if !self.started[job] && self.graph.deps[job].iter().all(|&dep| self.outcome[dep] == Some(true)) {
+                self.started[job] = true;
+                self.running += 1;
+                ready.push(job);
+            }
+        }
+        ready
+    }
+
+    /// Records how a running job ended
+    pub fn ended(&mut self, job: usize, succeeded: bool) {
+        if self.started[job] && self.outcome[job].is_none() {
+            self.outcome[job] = Some(succeeded);
+            self.running -= 1;
+        }
+    }
+
+    /// Whether jobs are still running
+    pub fn is_running(&self) -> bool {
+        self.running > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(name: &str, deps: &[&str]) -> (String, Vec<String>) {
+        (name.to_string(), deps.iter().map(|dep| dep.to_string()).collect())
+    }
+
+    #[test]
+    fn test_job_graph_schedule() {
+        assert!(JobGraph::new(&[job("a", &["b"]), job("b", &["a"])]).unwrap_err().contains("cycle"));
+        assert!(JobGraph::new(&[job("a", &["missing"])]).unwrap_err().contains("unknown"));
+        assert!(JobGraph::new(&[job("a", &[]), job("a", &[])]).is_err());
+        assert!(JobGraph::new(&[job("a", &["a"])]).is_err());
+
+        // lint and unit run first, build waits for both, deploy waits for build, docs for lint
+        let graph = JobGraph::new(&[
+            job("deploy", &["build"]),
+            job("build", &["lint", "unit"]),
+            job("lint", &[]),
+            job("unit", &[]),
+            job("docs", &["lint"]),
+        ])
+        .unwrap();
+        let mut schedule = Schedule::new(graph);
+        assert_eq!(schedule.start_ready(1), vec![2]);
+        assert_eq!(schedule.start_ready(2), vec![3]);
+        assert!(schedule.start_ready(2).is_empty());
+
+        schedule.ended(2, true);
+        assert_eq!(schedule.start_ready(2), vec![4]);
+        schedule.ended(3, false);
+        // build failed to get a passing unit run, so deploy is given up on too
+        assert_eq!(schedule.skip_blocked(), vec![(1, 3), (0, 1)]);
+        assert!(schedule.start_ready(2).is_empty());
+        assert!(schedule.is_running());
+        schedule.ended(4, true);
+        assert!(!schedule.is_running());
+        assert_eq!(schedule.graph().name(4), "docs");
+    }
+}
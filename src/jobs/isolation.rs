@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, warn};
+
+/// A fresh temporary directory a command runs in instead of its real working directory
+///
+/// Declared inputs are copied in before the command starts, and only declared
+/// outputs are copied back, so the real tree is never written to directly.
+/// The directory is removed when the workspace is dropped, and on Unix only
+/// its owner can enter it, since the copied inputs may be private.
+pub struct IsolatedWorkspace {
+    root: PathBuf,
+}
+
+impl IsolatedWorkspace {
+    /// Creates the workspace and copies `inputs`, relative to `source`, into it; `None` copies all of `source`
+    ///
+    /// `allow` is asked about every source path; refused entries are skipped.
+    pub fn create(source: &Path, inputs: Option<&[PathBuf]>, allow: &dyn Fn(&Path) -> bool) -> io::Result<Self> {
+        let root = std::env::temp_dir().join(format!("exex-isolated-{}", uuid::Uuid::new_v4().simple()));
+        // Not recursive: a directory another user created at this path first is refused
+        let builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        let builder = {
+            let mut builder = builder;
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            builder
+        };
+        builder.create(&root)?;
+        let workspace = Self { root };
+        match inputs {
+            Some(inputs) => {
+                for input in inputs {
+                    let from = source.join(input);
+                    if !from.exists() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("Input {} does not exist", input.display()),
+                        ));
+                    }
+                    copy_tree(&from, &workspace.root.join(input), &|from, _| allow(from))?;
+                }
+            }
+            None => copy_tree(source, &workspace.root, &|from, _| allow(from))?,
+        }
+        debug!("Created isolated workspace {}", workspace.root.display());
+        Ok(workspace)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Copies `outputs` back below `target`; returns the ones the command produced
+    ///
+    /// `allow` is asked about every destination path; refused entries are skipped.
+    pub fn collect(&self, target: &Path, outputs: &[PathBuf], allow: &dyn Fn(&Path) -> bool) -> io::Result<Vec<PathBuf>> {
+        let mut collected = Vec::new();
+        for output in outputs {
+            let from = self.root.join(output);
+            if fs::symlink_metadata(&from).is_err() {
+                debug!("Declared output {} was not produced", output.display());
+                continue;
+            }
+            copy_tree(&from, &target.join(output), &|_, to| allow(to))?;
+            collected.push(output.clone());
+        }
+        Ok(collected)
+    }
+}
+
+impl Drop for IsolatedWorkspace {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.root) {
+            warn!("Failed to remove isolated workspace {}: {}", self.root.display(), e);
+        }
+    }
+}
+
+/// Whether a declared input or output stays below the directory it is relative to
+pub fn is_relative_within(path: &Path) -> bool {
+    path.components().any(|component| matches!(component, Component::Normal(_)))
+        && path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Copies a file or directory tree; symlinks are not followed and are skipped
+fn copy_tree(from: &Path, to: &Path, allow: &dyn Fn(&Path, &Path) -> bool) -> io::Result<()> {
+    if !allow(from, to) {
+        warn!("Not copying {} to {}: refused by security policy", from.display(), to.display());
+        return Ok(());
+    }
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()), allow)?;
+        }
+    } else if metadata.is_file() {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(from, to)?;
+    } else {
+        debug!("Skipping {}: not a regular file or directory", from.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isolated_workspace() {
+        let project = tempfile::tempdir().unwrap();
+        fs::create_dir_all(project.path().join("src")).unwrap();
+        fs::write(project.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(project.path().join("Cargo.toml"), "[package]").unwrap();
+        fs::write(project.path().join(".env"), "TOKEN=secret").unwrap();
+        let allow = |path: &Path| path.file_name().is_none_or(|name| name != ".env");
+
+        let inputs = [PathBuf::from("src")];
+        let workspace = IsolatedWorkspace::create(project.path(), Some(&inputs), &allow).unwrap();
+        let root = workspace.path().to_path_buf();
+        assert!(root.join("src/main.rs").is_file());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&root).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        assert!(!root.join("Cargo.toml").exists());
+
+        // The command writes there, not to the project
+        fs::write(root.join("src/main.rs"), "changed").unwrap();
+        fs::create_dir_all(root.join("dist")).unwrap();
+        fs::write(root.join("dist/app"), "binary").unwrap();
+        fs::write(root.join("dist/.env"), "leak").unwrap();
+        let outputs = [PathBuf::from("dist"), PathBuf::from("missing")];
+        let collected = workspace.collect(project.path(), &outputs, &allow).unwrap();
+        assert_eq!(collected, vec![PathBuf::from("dist")]);
+        assert_eq!(fs::read_to_string(project.path().join("dist/app")).unwrap(), "binary");
+        assert!(!project.path().join("dist/.env").exists());
+        assert_eq!(fs::read_to_string(project.path().join("src/main.rs")).unwrap(), "fn main() {}");
+
+        drop(workspace);
+        assert!(!root.exists());
+
+        let everything = IsolatedWorkspace::create(project.path(), None, &allow).unwrap();
+        assert!(everything.path().join("Cargo.toml").is_file());
+        assert!(!everything.path().join(".env").exists());
+        assert!(IsolatedWorkspace::create(project.path(), Some(&[PathBuf::from("nope")]), &allow).is_err());
+
+        assert!(is_relative_within(Path::new("dist/app")));
+        assert!(is_relative_within(Path::new("./dist")));
+        assert!(!is_relative_within(Path::new("../outside")));
+        assert!(!is_relative_within(Path::new("/etc")));
+        assert!(!is_relative_within(Path::new("")));
+    }
+}
@@ -0,0 +1,307 @@
+pub mod artifacts;
+pub mod graph;
+pub mod isolation;
+pub mod queue;
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process::{Child, Output};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::{debug, warn};
+
+use crate::models::LogStream;
+
+/// Largest chunk returned by one log read
+pub const MAX_LOG_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// Persists the stdout and stderr of jobs to `<id>.stdout.log` and `<id>.stderr.log`
+///
+/// Output is written as it is produced, so a client that lost its connection
+/// can read it back from any offset, whether the job is still running or
+/// not. Each stream is capped; once a cap is reached a marker line is written
+/// and the rest is dropped. Only the newest `retention` jobs are kept.
+pub struct JobLogs {
+    dir: PathBuf,
+    max_bytes: u64,
+    retention: usize,
+    running: Mutex<HashSet<String>>,
+}
+
+/// A chunk of a job's log
+#[derive(Debug)]
+pub struct LogChunk {
+    pub data: Vec<u8>,
+    /// Size of the log when it was read
+    pub size: u64,
+    /// Whether the job has finished, so the log will not grow
+    pub complete: bool,
+}
+
+impl JobLogs {
+    pub fn new(dir: PathBuf, max_bytes: u64, retention: usize) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            retention,
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Whether job output is logged at all
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    /// Creates the log files for a new job; they are complete once the returned log is dropped
+    pub fn create(self: &Arc<Self>) -> io::Result<JobLog> {
+        self.create_as(new_job_id())
+    }
+
+    /// Creates the log files for a job whose ID was already handed out
+    pub fn create_as(self: &Arc<Self>, id: String) -> io::Result<JobLog> {
+        std::fs::create_dir_all(&self.dir)?;
+        self.prune();
+
+        let open = |stream| File::create(self.path(&id, stream));
+        let log = JobLog {
+            stdout: Mutex::new(CappedWriter::new(open(LogStream::Stdout)?, self.max_bytes)),
+            stderr: Mutex::new(CappedWriter::new(open(LogStream::Stderr)?, self.max_bytes)),
+            logs: self.clone(),
+            id: id.clone(),
+        };
+        self.running.lock().unwrap_or_else(PoisonError::into_inner).insert(id);
+        Ok(log)
+    }
+
+    /// Reads up to `limit` bytes of a job's stream from `offset`
+    pub fn read(&self, id: &str, stream: LogStream, offset: u64, limit: u64) -> io::Result<LogChunk> {
+        if !is_job_id(id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid job ID"));
+        }
+        // Check before reading, so output written after the check is picked up next time
+        let complete = !self.running.lock().unwrap_or_else(PoisonError::into_inner).contains(id);
+        let mut file = File::open(self.path(id, stream)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, format!("No logs for job {}", id)),
+            _ => e,
+        })?;
+        let size = file.metadata()?.len();
+
+        let mut data = Vec::new();
+        if offset < size {
+            file.seek(SeekFrom::Start(offset))?;
+            file.take(limit.min(MAX_LOG_CHUNK)).read_to_end(&mut data)?;
+        }
+        Ok(LogChunk { data, size, complete })
+    }
+
+    /// Reads the last `limit` bytes of a job's stream
+    pub fn tail(&self, id: &str, stream: LogStream, limit: u64) -> io::Result<LogChunk> {
+        let size = self.read(id, stream, u64::MAX, 0)?.size;
+        self.read(id, stream, size.saturating_sub(limit), limit)
+    }
+
+    fn path(&self, id: &str, stream: LogStream) -> PathBuf {
+        let stream = match stream {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+        };
+        self.dir.join(format!("{}.{}.log", id, stream))
+    }
+
+    /// Deletes the logs of the oldest finished jobs beyond the retention limit
+    fn prune(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let running = self.running.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let mut jobs: Vec<(std::time::SystemTime, String)> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let id = name.strip_suffix(".stdout.log")?.to_string();
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                (!running.contains(&id)).then_some((modified, id))
+            })
+            .collect();
+        // Make room for the job being created
+        let keep = self.retention.saturating_sub(running.len() + 1);
+        if jobs.len() <= keep {
+            return;
+        }
+        jobs.sort();
+        for (_, id) in &jobs[..jobs.len() - keep] {
+            for stream in [LogStream::Stdout, LogStream::Stderr] {
+                if let Err(e) = std::fs::remove_file(self.path(id, stream))
+                    && e.kind() != io::ErrorKind::NotFound
+                {
+                    warn!("Failed to remove old log of job {}: {}", id, e);
+                }
+            }
+            debug!("Removed logs of job {}", id);
+        }
+    }
+}
+
+/// A fresh job ID
+pub fn new_job_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Whether a string has the form of a generated job ID
+pub fn is_job_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The open log files of a running job
+pub struct JobLog {
+    stdout: Mutex<CappedWriter>,
+    stderr: Mutex<CappedWriter>,
+    logs: Arc<JobLogs>,
+    id: String,
+}
+
+impl JobLog {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Appends output to one of the streams; write errors stop the log, not the job
+    pub fn append(&self, stream: LogStream, data: &[u8]) {
+        let writer = match stream {
+            LogStream::Stdout => &self.stdout,
+            LogStream::Stderr => &self.stderr,
+        };
+        if let Err(e) = writer.lock().unwrap_or_else(PoisonError::into_inner).write(data) {
+            warn!("Failed to write the log of job {}: {}", self.id, e);
+        }
+    }
+}
+
+impl Drop for JobLog {
+    fn drop(&mut self) {
+        self.logs.running.lock().unwrap_or_else(PoisonError::into_inner).remove(&self.id);
+    }
+}
+
+struct CappedWriter {
+    file: Option<File>,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl CappedWriter {
+    fn new(file: File, max_bytes: u64) -> Self {
+        Self {
+            file: Some(file),
+            written: 0,
+            max_bytes,
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        let room = self.max_bytes.saturating_sub(self.written);
+        if (data.len() as u64) <= room {
+            self.written += data.len() as u64;
+            return file.write_all(data);
+        }
+
+        let result = file
+            .write_all(&data[..room as usize])
+            .and_then(|_| write!(file, "\n[exex: log truncated at {} bytes]\n", self.max_bytes));
+        self.file = None;
+        result
+    }
+}
+
+/// Like [`Child::wait_with_output`], also copying the output to the job log as it arrives
+pub fn wait_logged(mut child: Child, log: Option<&JobLog>) -> io::Result<Output> {
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    std::thread::scope(|scope| {
+        let stderr = scope.spawn(move || -> io::Result<Vec<u8>> {
+            let mut buffer = Vec::new();
+            if let Some(pipe) = stderr {
+                tee_output(pipe, LogStream::Stderr, log, &mut buffer)?;
+            }
+            Ok(buffer)
+        });
+        let mut stdout_buffer = Vec::new();
+        let stdout = match stdout {
+            Some(pipe) => tee_output(pipe, LogStream::Stdout, log, &mut stdout_buffer),
+            None => Ok(()),
+        };
+        let stderr = stderr.join().unwrap_or_else(|_| Err(io::Error::other("stderr reader panicked")));
+        let status = child.wait()?;
+        stdout?;
+        Ok(Output {
+            status,
+            stdout: stdout_buffer,
+            stderr: stderr?,
+        })
+    })
+}
+
+/// Copies a child's output pipe into `buffer` and the job log as it arrives
+fn tee_output(mut pipe: impl Read, stream: LogStream, log: Option<&JobLog>, buffer: &mut Vec<u8>) -> io::Result<()> {
+    let mut chunk = [0u8; 8192];
+    loop {
+        let read = match pipe.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(log) = log {
+            log.append(stream, &chunk[..read]);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_logs() {
+        let dir = std::env::temp_dir().join(format!("exex-job-logs-{}", uuid::Uuid::new_v4().simple()));
+        let logs = Arc::new(JobLogs::new(dir.clone(), 8, 2));
+
+        let log = logs.create().unwrap();
+        let id = log.id().to_string();
+        log.append(LogStream::Stdout, b"hello ");
+        log.append(LogStream::Stderr, b"oops");
+
+        let chunk = logs.read(&id, LogStream::Stdout, 0, 100).unwrap();
+        assert_eq!(chunk.data, b"hello ");
+        assert!(!chunk.complete);
+
+        // The cap cuts the stream and notes where
+        log.append(LogStream::Stdout, b"world");
+        log.append(LogStream::Stdout, b"dropped");
+        drop(log);
+        let chunk = logs.read(&id, LogStream::Stdout, 6, 2).unwrap();
+        assert_eq!(chunk.data, b"wo");
+        let rest = logs.read(&id, LogStream::Stdout, 8, 100).unwrap();
+        assert_eq!(rest.data, b"\n[exex: log truncated at 8 bytes]\n");
+        assert!(rest.complete);
+        assert_eq!(logs.read(&id, LogStream::Stderr, 0, 100).unwrap().data, b"oops");
+        assert!(logs.read(&id, LogStream::Stdout, rest.size, 100).unwrap().data.is_empty());
+
+        // Only the newest jobs are kept
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let second = logs.create().unwrap().id().to_string();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let third = logs.create().unwrap().id().to_string();
+        assert_eq!(logs.read(&id, LogStream::Stdout, 0, 1).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(logs.read(&second, LogStream::Stdout, 0, 1).is_ok());
+        assert!(logs.read(&third, LogStream::Stdout, 0, 1).is_ok());
+        assert_eq!(logs.read("../config", LogStream::Stdout, 0, 1).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
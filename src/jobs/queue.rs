@@ -0,0 +1,316 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use crate::models::{ExecResponse, GroupJob, JobGroupInfo, JobInfo, JobState};
+use crate::platform::process_tree::TreeKiller;
+
+/// Ended jobs kept for status queries, oldest dropped first
+const MAX_ENDED: usize = 256;
+
+/// Job groups kept for status queries, oldest dropped first
+const MAX_GROUPS: usize = 64;
+
+struct Entry {
+    info: JobInfo,
+    killer: Arc<TreeKiller>,
+}
+
+/// A job group's members by name, with the IDs they were queued under
+struct Group {
+    jobs: Vec<(String, Vec<String>, String)>,
+    max_parallel: Option<usize>,
+    submitted: String,
+}
+
+/// Why a job could not be cancelled
+#[derive(Debug)]
+pub enum CancelError {
+    NotFound,
+    /// The job had already ended
+    Ended,
+}
+
+/// Commands submitted through `/api/jobs`, which run without a request waiting on them
+///
+/// Jobs wait for an exec worker like `/api/exec` commands do. Their state is
+/// kept from submission until well after they end, so any client can poll it.
+#[derive(Default)]
+pub struct JobQueue {
+    jobs: Mutex<HashMap<String, Entry>>,
+    ended: Mutex<VecDeque<String>>,
+    groups: Mutex<(HashMap<String, Group>, VecDeque<String>)>,
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a job waiting for a worker; `killer` cancels it
+    pub fn submit(&self, id: &str, command: &str, killer: Arc<TreeKiller>) -> JobInfo {
+        let info = JobInfo {
+            job_id: id.to_string(),
+            command: command.to_string(),
+            state: JobState::Queued,
+            pid: None,
+            submitted: timestamp(),
+            started: None,
+            finished: None,
+            result: None,
+            error: None,
+        };
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner).insert(id.to_string(), Entry { info: info.clone(), killer });
+        info
+    }
+
+    /// Records that a job's command was spawned
+    pub fn started(&self, id: &str, pid: u32) {
+        if let Some(entry) = self.jobs.lock().unwrap_or_else(PoisonError::into_inner).get_mut(id) {
+            entry.info.pid = Some(pid);
+            entry.info.started = Some(timestamp());
+            if entry.info.state == JobState::Queued {
+                entry.info.state = JobState::Running;
+            }
+        }
+    }
+
+    /// Records how a job ended; a cancelled job stays cancelled
+    pub fn ended(&self, id: &str, result: Result<ExecResponse, String>) {
+        let (state, result, error) = match result {
+            Ok(response) => (JobState::Finished, Some(response), None),
+            Err(error) => (JobState::Failed, None, Some(error)),
+        };
+        self.end(id, state, result, error);
+    }
+
+    /// Records that a job of a group will never run, because `reason`
+    pub fn skipped(&self, id: &str, reason: String) {
+        self.end(id, JobState::Skipped, None, Some(reason));
+    }
+
+    fn end(&self, id: &str, state: JobState, result: Option<ExecResponse>, error: Option<String>) {
+        {
+            let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+            let Some(entry) = jobs.get_mut(id) else {
+                return;
+            };
+            let info = &mut entry.info;
+            info.finished = Some(timestamp());
+            if info.state != JobState::Cancelled {
+                info.state = state;
+            }
+            info.result = result;
+            info.error = error;
+        }
+
+        let mut ended = self.ended.lock().unwrap_or_else(PoisonError::into_inner);
+        ended.push_back(id.to_string());
+        if ended.len() > MAX_ENDED
+            && let Some(oldest) = ended.pop_front()
+        {
+            self.jobs.lock().unwrap_or_else(PoisonError::into_inner).remove(&oldest);
+        }
+    }
+
+    /// Kills a queued or running job with everything it started
+    pub fn cancel(&self, id: &str) -> Result<JobInfo, CancelError> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = jobs.get_mut(id).ok_or(CancelError::NotFound)?;
+        if !matches!(entry.info.state, JobState::Queued | JobState::Running) {
+            return Err(CancelError::Ended);
+        }
+        entry.killer.kill();
+        entry.info.state = JobState::Cancelled;
+        Ok(entry.info.clone())
+    }
+
+    /// Latest known state of a job
+    pub fn get(&self, id: &str) -> Option<JobInfo> {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner).get(id).map(|entry| entry.info.clone())
+    }
+
+    /// Records a group of already submitted jobs, as `(name, depends_on, job ID)`
+    pub fn add_group(
+        &self,
+        id: &str,
+        jobs: Vec<(String, Vec<String>, String)>,
+        max_parallel: Option<usize>,
+    ) -> JobGroupInfo {
+        let mut groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        let (entries, order) = &mut *groups;
+        entries.insert(
+            id.to_string(),
+            Group {
+                jobs,
+                max_parallel,
+                submitted: timestamp(),
+            },
+        );
+        order.push_back(id.to_string());
+        if order.len() > MAX_GROUPS
+            && let Some(oldest) = order.pop_front()
+        {
+            entries.remove(&oldest);
+        }
+        self.group_info(id, &entries[id])
+    }
+
+    /// Latest known state of a group and each of its jobs
+    pub fn group(&self, id: &str) -> Option<JobGroupInfo> {
+        let groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        groups.0.get(id).map(|group| self.group_info(id, group))
+    }
+
+    /// Cancels every job of a group that is still queued or running
+    pub fn cancel_group(&self, id: &str) -> Result<JobGroupInfo, CancelError> {
+        let group = self.group(id).ok_or(CancelError::NotFound)?;
+        let cancelled = group
+            .jobs
+            .iter()
+            .filter(|member| self.cancel(&member.job.job_id).is_ok())
+            .count();
+        if cancelled == 0 {
+            return Err(CancelError::Ended);
+        }
+        self.group(id).ok_or(CancelError::NotFound)
+    }
+
+    fn group_info(&self, id: &str, group: &Group) -> JobGroupInfo {
+        let jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        // Members that aged out of the ended jobs are left out
+        let members: Vec<GroupJob> = group
+            .jobs
+            .iter()
+            .filter_map(|(name, depends_on, job_id)| {
+                Some(GroupJob {
+                    name: name.clone(),
+                    depends_on: depends_on.clone(),
+                    job: jobs.get(job_id)?.info.clone(),
+                })
+            })
+            .collect();
+        JobGroupInfo {
+            group_id: id.to_string(),
+            state: group_state(members.iter().map(|member| &member.job)),
+            max_parallel: group.max_parallel,
+            submitted: group.submitted.clone(),
+            jobs: members,
+        }
+    }
+}
+
+/// A group is queued until a job starts and running until all ended; then the worst outcome counts
+fn group_state<'a>(jobs: impl Iterator<Item = &'a JobInfo> + Clone) -> JobState {
+    let states = || jobs.clone().map(|job| job.state);
+    if states().all(|state| state == JobState::Queued) {
+        JobState::Queued
+    } else if states().any(|state| matches!(state, JobState::Queued | JobState::Running)) {
+        JobState::Running
+    } else if states().any(|state| state == JobState::Cancelled) {
+        JobState::Cancelled
+    } else if jobs
+        .clone()
+        .all(|job| job.state == JobState::Finished && job.result.as_ref().is_some_and(|result| result.success))
+    {
+        JobState::Finished
+    } else {
+        JobState::Failed
+    }
+}
+
+fn timestamp() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ExecOutcome;
+
+    fn response() -> ExecResponse {
+        ExecResponse {
+            success: true,
+            outcome: ExecOutcome::Succeeded,
+            stdout: "done\n".to_string(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            signal: None,
+            reason: None,
+            error: None,
+            timed_out: false,
+            job_id: None,
+            records: None,
+            outputs: None,
+            artifacts: None,
+        }
+    }
+
+    #[test]
+    fn test_job_queue_states() {
+        let queue = JobQueue::new();
+        let killer = Arc::new(TreeKiller::default());
+        assert_eq!(queue.submit("a", "cargo build", killer.clone()).state, JobState::Queued);
+        queue.started("a", 42);
+        let job = queue.get("a").unwrap();
+        assert_eq!((job.state, job.pid), (JobState::Running, Some(42)));
+        queue.ended("a", Ok(response()));
+        let job = queue.get("a").unwrap();
+        assert_eq!(job.state, JobState::Finished);
+        assert!(job.finished.is_some());
+        assert!(matches!(queue.cancel("a"), Err(CancelError::Ended)));
+        assert!(matches!(queue.cancel("missing"), Err(CancelError::NotFound)));
+
+        // Cancelling a queued job kills it before it starts, and it stays cancelled
+        let killer = Arc::new(TreeKiller::default());
+        queue.submit("b", "sleep 60", killer.clone());
+        assert_eq!(queue.cancel("b").unwrap().state, JobState::Cancelled);
+        assert!(killer.was_killed());
+        queue.ended("b", Err("Command was cancelled before it started".to_string()));
+        let job = queue.get("b").unwrap();
+        assert_eq!(job.state, JobState::Cancelled);
+        assert!(job.error.is_some());
+
+        queue.submit("c", "false", Arc::new(TreeKiller::default()));
+        queue.ended("c", Err("exec worker pool is saturated, try again later".to_string()));
+        assert_eq!(queue.get("c").unwrap().state, JobState::Failed);
+
+        // Only the newest ended jobs are kept
+        for n in 0..MAX_ENDED {
+            let id = n.to_string();
+            queue.submit(&id, "true", Arc::new(TreeKiller::default()));
+            queue.ended(&id, Ok(response()));
+        }
+        assert!(queue.get("a").is_none());
+        assert!(queue.get("0").is_some());
+    }
+
+    #[test]
+    fn test_job_group_state() {
+        let queue = JobQueue::new();
+        for id in ["build", "test"] {
+            queue.submit(id, "cargo", Arc::new(TreeKiller::default()));
+        }
+        let members = vec![
+            ("build".to_string(), vec![], "build".to_string()),
+            ("test".to_string(), vec!["build".to_string()], "test".to_string()),
+        ];
+        assert_eq!(queue.add_group("g", members, Some(1)).state, JobState::Queued);
+
+        queue.started("build", 1);
+        assert_eq!(queue.group("g").unwrap().state, JobState::Running);
+        let mut failed = response();
+        failed.success = false;
+        queue.ended("build", Ok(failed));
+        queue.skipped("test", "Dependency 'build' did not succeed".to_string());
+        let group = queue.group("g").unwrap();
+        assert_eq!(group.state, JobState::Failed);
+        assert_eq!(group.jobs[1].job.state, JobState::Skipped);
+        assert!(matches!(queue.cancel_group("g"), Err(CancelError::Ended)));
+
+        queue.submit("lint", "cargo", Arc::new(TreeKiller::default()));
+        queue.add_group("h", vec![("lint".to_string(), vec![], "lint".to_string())], None);
+        assert_eq!(queue.cancel_group("h").unwrap().state, JobState::Cancelled);
+        assert!(matches!(queue.cancel_group("missing"), Err(CancelError::NotFound)));
+    }
+}
@@ -1,4 +1,20 @@
+pub mod audit;
+pub mod backups;
+pub mod clients;
 pub mod config;
+pub mod content;
 pub mod handlers;
+pub mod i18n;
+pub mod jobs;
 pub mod models;
+pub mod operations;
+pub mod platform;
+pub mod policy;
+pub mod power;
+pub mod processes;
+pub mod routes;
 pub mod security;
+pub mod selftest;
+pub mod sessions;
+pub mod watch;
+pub mod workers;
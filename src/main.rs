@@ -1,85 +1,262 @@
-mod config;
-mod handlers;
-mod models;
-mod security;
-
-use actix_web::{web, App, HttpServer, middleware::Logger};
+use actix_web::{web, App, HttpServer, middleware::{from_fn, Logger}};
 use actix_cors::Cors;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{error, info, warn};
 
-use crate::config::load_config;
-use crate::handlers::{
-    exec_command, read_file, write_file, health_check,
-    scan_directory, delete_item, create_item, rename_item,
-    open_application, shutdown_server
-};
-use crate::security::SecurityManager;
+use exex::audit::access::{log_access, AccessLogger};
+use exex::audit::{audit_requests, AuditLogger};
+use exex::backups::{run_backup_scheduler, BackupService};
+use exex::clients::{track_clients, ClientRegistry};
+use exex::config::{get_config_dir, load_config};
+use exex::handlers::health_check;
+use exex::i18n::{localize_errors, ErrorCatalog, MESSAGES_DIR};
+use exex::routes::configure_versioned;
+use exex::routes::version::{api_version_negotiation, API_VERSION_HEADER};
+use exex::security::SecurityManager;
+use exex::security::csrf::csrf_guard;
+use exex::security::origin::origin_guard;
+use exex::security::path_limits::validate_paths;
+use exex::security::secrets::{run_secrets_command, SecretStore};
+use exex::security::tls;
+use exex::selftest::{log_self_test, run_self_test};
+use exex::config::remote::{load_remote_policy, refresh_interval, watch_remote_policy, PolicySource};
+use exex::config::signature::{verify_config_signature, Verification};
+use exex::policy::run_policy_command;
+use exex::jobs::JobLogs;
+use exex::jobs::artifacts::ArtifactStore;
+use exex::jobs::queue::JobQueue;
+use exex::power::PowerManager;
+use exex::processes::{run_reaper, ProcessRegistry};
+use exex::sessions::SessionManager;
+use exex::models::AccessLogFormat;
+use exex::config::store::ConfigStore;
+use exex::operations::OperationRegistry;
+use exex::watch::WatchService;
+use exex::workers::WorkerPools;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Initialize logging
     env_logger::init();
-    
+
     info!("Starting EXEX - Local Execution Daemon");
 
     // Load configuration
-    let config = load_config();
-    let server_host = config.server.host.clone();
-    let server_port = config.server.port;
-    
-    let security_manager = Arc::new(SecurityManager::new(config));
-
-    info!("Loaded {} disallowed paths", security_manager.get_disallowed_paths().len());
-    for path in security_manager.get_disallowed_paths() {
-        info!("Disallowed: {}", path.display());
+    let mut config = load_config();
+
+    // `exex policy test <file>` checks hypothetical requests against the policy instead of serving
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("policy") {
+        std::process::exit(run_policy_command(&args[1..], config));
+    }
+    // `exex secrets set|list|remove` manages the secrets pipelines inject
+    if args.first().map(String::as_str) == Some("secrets") {
+        std::process::exit(run_secrets_command(&args[1..], &config));
     }
 
-    info!("Loaded {} allowed path exceptions", security_manager.get_allowed_paths().len());
-    for path in security_manager.get_allowed_paths() {
-        info!("Allowed exception: {}", path.display());
+    // A managed config must carry a valid signature from a trusted key
+    match verify_config_signature() {
+        Ok(Verification::Verified(key)) => info!("Configuration signature verified with key {}", key),
+        Ok(Verification::NotRequired) => {}
+        Err(e) if args.iter().any(|a| a == "--allow-unverified-config") => {
+            warn!("{}; continuing because --allow-unverified-config was given", e);
+        }
+        Err(e) => {
+            error!("Refusing to start: {}", e);
+            error!("Restore the signed configuration or start with --allow-unverified-config");
+            std::process::exit(1);
+        }
     }
 
-    // Start HTTP server
-    let bind_address = format!("{}:{}", server_host, server_port);
-    info!("Starting server on http://{}", bind_address);
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(security_manager.clone()))
-            .wrap(
-                Cors::default()
-                    .allow_any_origin()
-                    .allow_any_method()
-                    .allow_any_header()
-                    .supports_credentials()
-            )
-            .wrap(Logger::default())
-            .wrap(
-                Cors::default()
-                    .allow_any_origin()
-                    .allow_any_method()
-                    .allow_any_header()
-                    .supports_credentials()
-            )
-            .service(
-                web::scope("/api")
-                    // Command execution
-                    .route("/exec", web::post().to(exec_command))
-                    // File operations
-                    .route("/read", web::post().to(read_file))
-                    .route("/write", web::post().to(write_file))
-                    .route("/scan", web::post().to(scan_directory))
-                    .route("/delete", web::post().to(delete_item))
-                    .route("/create", web::post().to(create_item))
-                    .route("/rename", web::post().to(rename_item))
-                    // Application operations
-                    .route("/open", web::post().to(open_application))
-                    .route("/shutdown", web::post().to(shutdown_server))
-            )
-            .route("/health", web::get().to(health_check))
-    })
-    .bind(&bind_address)?
-    .run()
-    .await
+    // A centrally managed policy replaces the local security section, falling back to the cached copy offline
+    let mut policy_digest = None;
+    if let Some(url) = config.remote_url.clone() {
+        match load_remote_policy(&url)
+            .await
+            .and_then(|policy| Ok((policy.apply_to(&config)?, policy)))
+        {
+            Ok((managed, policy)) => {
+                let origin = if policy.source == PolicySource::Cache { "cached" } else { "remote" };
+                info!("Using {} policy from {} signed by {}", origin, url, policy.key);
+                config = managed;
+                policy_digest = Some(policy.digest);
+            }
+            Err(e) => warn!("Remote policy unavailable, using the local policy: {}", e),
+        }
+    }
+
+    log_self_test(&run_self_test(&config));
+
+    let worker_pools = Arc::new(WorkerPools::from_config(&config.server));
+    let process_registry = Arc::new(ProcessRegistry::new());
+    let power_manager = Arc::new(PowerManager::new(config.server.keep_awake_during_jobs.unwrap_or(true)));
+    let job_logs = Arc::new(JobLogs::new(
+        get_config_dir().map_or_else(|_| std::env::temp_dir().join("exex-jobs"), |dir| dir.join("jobs")),
+        config.server.job_log_max_mb.unwrap_or(16) * 1024 * 1024,
+        config.server.job_log_retention.unwrap_or(200),
+    ));
+    let job_queue = Arc::new(JobQueue::new());
+    let backup_service = Arc::new(BackupService::from_config(&config));
+    let watch_service = Arc::new(WatchService::new());
+    let operation_registry = Arc::new(OperationRegistry::new());
+    let config_store = Arc::new(ConfigStore::from_config(&config));
+    let secret_store = Arc::new(SecretStore::from_config(&config));
+    let artifact_store = Arc::new(ArtifactStore::new(
+        get_config_dir().map_or_else(|_| std::env::temp_dir().join("exex-artifacts"), |dir| dir.join("jobs").join("artifacts")),
+        config.server.job_artifact_max_mb.unwrap_or(256) * 1024 * 1024,
+        config.server.job_log_retention.unwrap_or(200),
+    ));
+    let session_manager = Arc::new(SessionManager::new());
+    let audit_logger = Arc::new(AuditLogger::new(&config.logging.audit_file));
+    let access_logger = Arc::new(AccessLogger::new(
+        config.logging.access_log_file.clone().unwrap_or_default(),
+        config.logging.access_log_format.unwrap_or(AccessLogFormat::Combined),
+        config.logging.access_log_max_mb.unwrap_or(10) * 1024 * 1024,
+        config.logging.access_log_keep.unwrap_or(5),
+    ));
+    let client_registry = Arc::new(ClientRegistry::new());
+    let error_catalog = Arc::new(match get_config_dir() {
+        Ok(dir) => ErrorCatalog::load(&dir.join(MESSAGES_DIR)),
+        Err(_) => ErrorCatalog::builtin(),
+    });
+    actix_web::rt::spawn(run_reaper(process_registry.clone()));
+
+    // Serve until a changed remote policy or a configuration update arrives, then restart with it
+    loop {
+        let shared_config = Arc::new(config.clone());
+        let allow_credentials = config.server.allow_credentials.unwrap_or(true);
+        let security_manager = Arc::new(SecurityManager::new(config.clone()));
+        watch_service.set_policy(security_manager.clone());
+
+        info!("Loaded {} disallowed paths", security_manager.get_disallowed_paths().len());
+        for path in security_manager.get_disallowed_paths().iter() {
+            info!("Disallowed: {}", path.display());
+        }
+
+        info!("Loaded {} allowed path exceptions", security_manager.get_allowed_paths().len());
+        for path in security_manager.get_allowed_paths().iter() {
+            info!("Allowed exception: {}", path.display());
+        }
+
+        // Restarted with the server, so backups always run under the current policy
+        let backup_scheduler = backup_service.is_scheduled().then(|| {
+            actix_web::rt::spawn(run_backup_scheduler(
+                backup_service.clone(),
+                security_manager.clone(),
+                worker_pools.clone(),
+            ))
+        });
+
+        if security_manager.is_csrf_protection_enabled() {
+            info!("CSRF protection enabled for credentialed browser requests");
+        }
+
+        // Start HTTP server, over TLS when a certificate is configured
+        let bind_address = format!("{}:{}", config.server.host, config.server.port);
+        let acceptor = tls::acceptor(&config.server)?;
+        let scheme = if acceptor.is_some() { "https" } else { "http" };
+        info!("Starting server on {}://{}", scheme, bind_address);
+        if acceptor.is_none() && !tls::is_loopback_host(&config.server.host) {
+            warn!("Serving plain HTTP beyond loopback; configure server.tls to encrypt traffic");
+        }
+
+        // The server closure takes the shared handles, so keep one to follow updates
+        let config_updates = config_store.clone();
+        let worker_pools = worker_pools.clone();
+        let process_registry = process_registry.clone();
+        let error_catalog = error_catalog.clone();
+        let power_manager = power_manager.clone();
+        let job_logs = job_logs.clone();
+        let job_queue = job_queue.clone();
+        let backup_service = backup_service.clone();
+        let watch_service = watch_service.clone();
+        let operation_registry = operation_registry.clone();
+        let config_store = config_store.clone();
+        let secret_store = secret_store.clone();
+        let artifact_store = artifact_store.clone();
+        let session_manager = session_manager.clone();
+        let audit_logger = audit_logger.clone();
+        let access_logger = access_logger.clone();
+        let client_registry = client_registry.clone();
+        let server = HttpServer::new(move || {
+            let mut cors = Cors::default()
+                .allow_any_origin()
+                .allow_any_method()
+                .allow_any_header()
+                .expose_headers(vec![API_VERSION_HEADER]);
+            if allow_credentials {
+                cors = cors.supports_credentials();
+            }
+
+            App::new()
+                .app_data(web::Data::new(shared_config.clone()))
+                .app_data(web::Data::new(security_manager.clone()))
+                .app_data(web::Data::new(worker_pools.clone()))
+                .app_data(web::Data::new(process_registry.clone()))
+                .app_data(web::Data::new(error_catalog.clone()))
+                .app_data(web::Data::new(power_manager.clone()))
+                .app_data(web::Data::new(job_logs.clone()))
+                .app_data(web::Data::new(job_queue.clone()))
+                .app_data(web::Data::new(backup_service.clone()))
+                .app_data(web::Data::new(watch_service.clone()))
+                .app_data(web::Data::new(operation_registry.clone()))
+                .app_data(web::Data::new(config_store.clone()))
+                .app_data(web::Data::new(secret_store.clone()))
+                .app_data(web::Data::new(artifact_store.clone()))
+                .app_data(web::Data::new(session_manager.clone()))
+                .app_data(web::Data::new(audit_logger.clone()))
+                .app_data(web::Data::new(access_logger.clone()))
+                .app_data(web::Data::new(client_registry.clone()))
+                .wrap(from_fn(validate_paths))
+                .wrap(from_fn(localize_errors))
+                .wrap(from_fn(api_version_negotiation))
+                .wrap(from_fn(origin_guard))
+                .wrap(from_fn(csrf_guard))
+                .wrap(from_fn(track_clients))
+                .wrap(from_fn(audit_requests))
+                .wrap(from_fn(log_access))
+                .wrap(Logger::default())
+                .wrap(cors)
+                .configure(configure_versioned)
+                .route("/health", web::get().to(health_check))
+        });
+        let server = match acceptor {
+            #[cfg(feature = "tls")]
+            Some(acceptor) => server.bind_openssl(&bind_address, acceptor)?,
+            #[cfg(not(feature = "tls"))]
+            Some(never) => match never {},
+            None => server.bind(&bind_address)?,
+        }
+        .run();
+
+        let handle = server.handle();
+        let mut server = std::pin::pin!(server);
+        let remote_policy = async {
+            match config.remote_url.clone() {
+                Some(url) => watch_remote_policy(url, refresh_interval(&config), policy_digest.clone(), config.clone()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let next = tokio::select! {
+            result = &mut server => return result,
+            (managed, policy) = remote_policy => {
+                info!("Remote policy changed (signed by {}); restarting with it", policy.key);
+                policy_digest = Some(policy.digest);
+                config_updates.set_active(managed.clone());
+                managed
+            }
+            updated = config_updates.updated() => {
+                info!("Configuration updated through the API; restarting with it");
+                updated
+            }
+        };
+        // The server future has to be polled for the graceful stop to complete
+        let (_, result) = tokio::join!(handle.stop(true), server);
+        result?;
+        if let Some(scheduler) = backup_scheduler {
+            scheduler.abort();
+        }
+        config = next;
+    }
 }
@@ -1,4 +1,6 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 /// Configuration structure for EXEX daemon
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -7,6 +9,20 @@ pub struct Config {
     pub server: ServerConfig,
     pub security: SecurityConfig,
     pub logging: LoggingConfig,
+    /// Desktop integrations (editors, browsers)
+    pub apps: Option<AppsConfig>,
+    /// URL of a centrally managed, signed security policy
+    pub remote_url: Option<String>,
+    /// How often the remote policy is fetched again
+    pub remote_refresh_secs: Option<u64>,
+    /// Entries left out of scans and other tree walks, as `.gitignore`-style globs
+    pub ignore_patterns: Option<Vec<String>>,
+    /// Formatter commands by file extension, run over written content; `{file}` is the file to format in place
+    pub formatters: Option<HashMap<String, Vec<String>>>,
+    /// Command sequences run by name through `/api/pipelines/{name}/run`
+    pub pipelines: Option<HashMap<String, PipelineConfig>>,
+    /// Directories archived on a schedule or through `/api/backups/{name}/run`
+    pub backups: Option<HashMap<String, BackupProfile>>,
 }
 
 /// Server configuration
@@ -14,6 +30,38 @@ pub struct Config {
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Whether CORS responses allow credentials (cookies) from browser clients
+    pub allow_credentials: Option<bool>,
+    /// Whether double-submit CSRF tokens are required on credentialed mutating requests
+    pub csrf_protection: Option<bool>,
+    /// Threads dedicated to command execution
+    pub exec_workers: Option<usize>,
+    /// Threads dedicated to heavy filesystem work (scans, large reads/writes, deletes)
+    pub fs_workers: Option<usize>,
+    /// Low-priority threads for background maintenance such as scheduled backups
+    pub background_workers: Option<usize>,
+    /// Jobs each pool may queue before new requests are rejected with 503
+    pub worker_queue_limit: Option<usize>,
+    /// Keep the machine from idling into sleep while commands or waited-on applications run
+    pub keep_awake_during_jobs: Option<bool>,
+    /// Most output kept per stream of a job's log (0 disables job logs)
+    pub job_log_max_mb: Option<u64>,
+    /// Finished jobs whose logs are kept, newest first
+    pub job_log_retention: Option<usize>,
+    /// Most artifacts kept per job, in MiB; jobs beyond `job_log_retention` lose theirs
+    pub job_artifact_max_mb: Option<u64>,
+    /// Serve HTTPS instead of HTTP, in builds with the `tls` feature
+    pub tls: Option<TlsConfig>,
+}
+
+/// Certificate the server presents when `enabled`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain, leaf first
+    pub cert_path: String,
+    /// PEM private key of the certificate
+    pub key_path: String,
 }
 
 /// Security configuration
@@ -23,7 +71,128 @@ pub struct SecurityConfig {
     pub disallowed_paths: Vec<String>,
     pub command_whitelist: Vec<String>,
     pub command_blacklist: Option<Vec<String>>,
+    /// Program to run for a whitelisted command name, such as `python` -> `python3` or an absolute path
+    pub command_aliases: Option<HashMap<String, String>>,
     pub max_file_size_mb: u64,
+    pub malware_scan: Option<MalwareScanConfig>,
+    /// Directories whose files are transparently encrypted on write and decrypted on read
+    pub encrypted_paths: Option<Vec<String>>,
+    /// Key file for encrypted directories (defaults to `exex.key` in the config directory)
+    pub encryption_key_file: Option<String>,
+    /// How long canonicalized directories are cached for policy checks (0 disables)
+    pub path_cache_ttl_secs: Option<u64>,
+    /// Maximum number of cached directories
+    pub path_cache_capacity: Option<usize>,
+    /// Free space to keep on a volume; writes that would dip below it are refused
+    pub disk_reserve_mb: Option<u64>,
+    /// Per-client write throughput limit (0 disables throttling)
+    pub write_limit_mb_per_sec: Option<u64>,
+    /// Most entries a recursive operation may touch without an explicit override (0 disables)
+    pub max_affected_entries: Option<usize>,
+    /// Scheduling priority for commands and applications that do not request one
+    pub default_priority: Option<ProcessPriority>,
+    /// Most launched applications, terminals, editors and URL handlers running at once (0 disables)
+    pub max_spawned_processes: Option<usize>,
+    /// Timeout for `/api/exec` commands that do not set their own; 0 means none
+    pub default_exec_timeout_secs: Option<u64>,
+    /// Registry keys, with their subkeys, readable through `/api/registry/read` (`registry` feature)
+    pub registry_keys: Option<Vec<String>>,
+    /// Actions `/api/power` may perform; shutdown and reboot are never available
+    pub power_actions: Option<Vec<PowerAction>>,
+    /// Longest keep-awake lease a client may take
+    pub max_wake_lease_secs: Option<u64>,
+    /// Octal permissions of files EXEX creates, regardless of its umask (Unix)
+    pub file_mode: Option<String>,
+    /// Octal permissions of directories EXEX creates (Unix)
+    pub dir_mode: Option<String>,
+    /// Octal permission bits a request's `mode` may include
+    pub max_mode: Option<String>,
+    /// Globs, matched at any depth, of files that may only be changed or deleted with `force`
+    pub protected_patterns: Option<Vec<String>>,
+    /// Whether `force` may be used on protected files at all
+    pub allow_protected_override: Option<bool>,
+    /// Leave creation and access times out of listings
+    pub omit_private_metadata: Option<bool>,
+    /// What clients of each origin class may do; classes left out have full access
+    pub origin_access: Option<HashMap<OriginClass, OriginAccess>>,
+    /// Refused requests from one address, within `ban_window_secs`, that trigger the ban hook (0 disables)
+    pub ban_after_failures: Option<usize>,
+    pub ban_window_secs: Option<u64>,
+    /// Command run to ban an address; `{ip}` and `{reason}` are substituted
+    pub ban_command: Option<Vec<String>>,
+    /// URL POSTed `{"ip", "failures", "reason"}` to ban an address
+    pub ban_webhook: Option<String>,
+    /// Shells `/api/exec` requests may choose with `shell`
+    pub allowed_shells: Option<Vec<ShellKind>>,
+    /// Whether requests may set `login_shell`, which sources the user's shell profiles
+    pub allow_login_shell: Option<bool>,
+    /// Variables requests may not set, on top of the built-in list; a trailing `*` blocks a prefix
+    pub blocked_env_vars: Option<Vec<String>>,
+    /// Directories put ahead of the inherited PATH for commands and applications; `~` is the home directory
+    pub extra_path_entries: Option<Vec<String>>,
+    /// Whether `PUT /api/config` may change and save the configuration
+    pub allow_config_updates: Option<bool>,
+}
+
+/// Where a request came from, judged by its peer address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OriginClass {
+    /// The same machine, without proxy headers
+    Loopback,
+    /// Private, link-local and unique local addresses
+    Lan,
+    /// Carrier-grade NAT addresses used by mesh VPNs, or a local proxy that forwarded the request
+    Tunnel,
+    /// Any other address
+    Public,
+}
+
+/// What clients of an origin class may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OriginAccess {
+    #[default]
+    Full,
+    /// Only `GET` endpoints, reads and scans
+    ReadOnly,
+    None,
+}
+
+/// Power management action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    /// Lock the screen
+    Lock,
+    /// Suspend the machine; requires `confirm`
+    Sleep,
+    /// Keep the machine from idling into sleep for a while
+    KeepAwake,
+    /// End a keep-awake lease early
+    Release,
+}
+
+/// CPU and IO scheduling priority for spawned processes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessPriority {
+    /// Nice 10 and best-effort lowest IO priority on Unix; below-normal priority class on Windows
+    #[default]
+    Low,
+    Normal,
+}
+
+/// Malware scan hook configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MalwareScanConfig {
+    pub enabled: bool,
+    /// clamd endpoint: a Unix socket path or `tcp://host:port`
+    pub clamd_socket: Option<String>,
+    /// Scanner command (clamscan conventions); `{file}` is replaced with the scanned file
+    pub command: Option<Vec<String>>,
+    /// Where rejected content is kept for inspection
+    pub quarantine_dir: Option<String>,
 }
 
 /// Logging configuration
@@ -31,6 +200,87 @@ pub struct SecurityConfig {
 pub struct LoggingConfig {
     pub level: String,
     pub audit_file: String,
+    /// Extra regex patterns whose matches are masked in logs and echoed commands
+    pub redact_patterns: Option<Vec<String>>,
+    /// Fraction of allowed path and command checks logged as `policy` events, 0 to 1
+    pub policy_allow_sample_rate: Option<f64>,
+    /// Fraction of denied checks logged as `policy` events, 0 to 1
+    pub policy_deny_sample_rate: Option<f64>,
+    /// File each HTTP request is appended to in a standard web log format; empty disables it
+    pub access_log_file: Option<String>,
+    pub access_log_format: Option<AccessLogFormat>,
+    /// Size at which the access log is rotated (0 never rotates)
+    pub access_log_max_mb: Option<u64>,
+    /// Rotated access logs kept as `<file>.1`, `<file>.2`, ...
+    pub access_log_keep: Option<usize>,
+}
+
+/// Line format of the access log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogFormat {
+    /// Apache Common Log Format
+    Common,
+    /// Common Log Format followed by the quoted `Referer` and `User-Agent`
+    Combined,
+}
+
+/// Desktop application integrations
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AppsConfig {
+    /// Editor argument templates by name; `{file}`, `{line}` and `{column}` are substituted
+    pub editors: Option<HashMap<String, Vec<String>>>,
+    /// Editor used when a request does not name one
+    pub default_editor: Option<String>,
+    /// URL schemes `open_url` may hand to the system handler
+    pub url_schemes: Option<Vec<String>>,
+    /// Hosts (and their subdomains) `open_url` may open over http and https; empty allows any
+    pub url_domains: Option<Vec<String>>,
+}
+
+/// A curated workflow, such as `build` or `test`, whose steps run in order until one fails
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PipelineConfig {
+    pub description: Option<String>,
+    /// Working directory of steps that do not set their own
+    pub cwd: Option<String>,
+    /// Secrets set as environment variables of every step, keyed by variable name
+    pub secrets: Option<HashMap<String, String>>,
+    pub steps: Vec<PipelineStep>,
+}
+
+/// A command of a pipeline, with fixed arguments
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PipelineStep {
+    pub command: String,
+    /// Without `args`, `command` runs through the system shell as in `/api/exec`
+    pub args: Option<Vec<String>>,
+    pub cwd: Option<String>,
+    /// Variables set for the step, overriding inherited values
+    pub env: Option<HashMap<String, String>>,
+    /// Secrets set for the step only, keyed by variable name; they are never returned
+    pub secrets: Option<HashMap<String, String>>,
+    /// Defaults to `security.default_exec_timeout_secs`
+    pub timeout_secs: Option<u64>,
+}
+
+/// A directory packed into timestamped archives, of which the newest are kept
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BackupProfile {
+    /// Directory to back up
+    pub source: String,
+    /// Glob patterns, relative to `source`, of the files to include; all files when unset
+    pub include: Option<Vec<String>>,
+    /// Glob patterns, relative to `source`, of entries to leave out
+    pub exclude: Option<Vec<String>>,
+    /// Directory the archives are written to, created if missing
+    pub destination: String,
+    /// Defaults to `tar_gz`
+    pub format: Option<ArchiveFormat>,
+    /// Minutes between scheduled backups; without it the profile only runs on request
+    pub interval_minutes: Option<u64>,
+    /// Archives kept, newest first; older ones are deleted after each backup. All are kept when unset
+    pub keep: Option<usize>,
 }
 
 /// Legacy config support for backward compatibility
@@ -44,33 +294,460 @@ pub struct LegacyConfig {
 }
 
 /// Request structure for command execution
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct ExecRequest {
     pub command: String,
     pub args: Option<Vec<String>>,
     pub cwd: Option<String>,
+    /// Defaults to `security.default_priority`
+    pub priority: Option<ProcessPriority>,
+    /// Kill the command and everything it started after this many seconds;
+    /// defaults to `security.default_exec_timeout_secs`
+    #[serde(alias = "timeout_seconds")]
+    pub timeout_secs: Option<u64>,
+    /// Create `cwd` if it does not exist
+    pub create_cwd: Option<bool>,
+    /// Also return stdout as structured records; defaults to `none`
+    pub parse_output: Option<OutputFormat>,
+    /// Handling of ANSI escape codes in the output; defaults to `preserve`
+    pub ansi: Option<AnsiMode>,
+    /// Shell that runs the command; defaults to `sh -c` (`cmd /C` on Windows) without `args`,
+    /// and to running the program directly with them
+    pub shell: Option<ShellKind>,
+    /// Run the shell as a login shell, so profiles that set up nvm, pyenv or conda are sourced
+    pub login_shell: Option<bool>,
+    /// Run in a fresh temporary copy of `cwd` instead of `cwd` itself
+    pub isolate_cwd: Option<bool>,
+    /// Paths relative to `cwd` copied into the isolated workspace; all of `cwd` when omitted
+    pub inputs: Option<Vec<String>>,
+    /// Paths relative to `cwd` copied back from the isolated workspace if the command succeeds
+    pub outputs: Option<Vec<String>>,
+    /// Commands with the same key run one at a time, e.g. `cargo-target-dir` for a shared build directory
+    pub mutex: Option<String>,
+    #[serde(flatten)]
+    pub environment: EnvOptions,
+}
+
+/// Shell an `/api/exec` request may ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Pwsh,
+    Cmd,
+    /// Run the program directly, splitting `command` on whitespace when no `args` are given
+    None,
+}
+
+/// What happens to ANSI color and cursor codes in a command's output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AnsiMode {
+    /// Return the output as the command printed it
+    #[default]
+    Preserve,
+    /// Remove escape codes, for frontends that show plain text
+    Strip,
+    /// Ask the command to print colors although its output is piped, for terminal renderers
+    Force,
+}
+
+/// How a command's stdout is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    None,
+    /// One JSON value per line, as printed by `cargo --message-format=json` or `eslint -f json`
+    JsonLines,
+}
+
+/// Environment control for spawned processes, applied as clean, then remove, then set
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct EnvOptions {
+    /// Start from an empty environment that keeps only essentials such as PATH, HOME and the display
+    pub clean_env: Option<bool>,
+    /// Inherited variables to drop
+    pub env_remove: Option<Vec<String>>,
+    /// Variables to set, overriding inherited values
+    pub env: Option<HashMap<String, String>>,
 }
 
 /// Request structure for file reading
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct ReadRequest {
     pub path: String,
+    /// `base64` is required to read binary, image and archive files
+    pub encoding: Option<ContentEncoding>,
 }
 
-/// Request structure for file writing
+/// Query of a file download
 #[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    pub path: String,
+}
+
+/// How file content is carried in JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+/// Coarse classification of file content, used to decide whether it is safe to preview as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Text,
+    Binary,
+    Image,
+    Archive,
+}
+
+/// Filesystem entries that are neither regular files, directories nor symlinks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecialFileType {
+    /// Named pipe; reading blocks until a writer appears
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+/// Request structure for file writing
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct WriteRequest {
     pub path: String,
     pub content: String,
+    /// `base64` writes the decoded bytes as they are, for binary files
+    pub encoding: Option<ContentEncoding>,
+    /// Re-read the file after writing and return its checksum
+    pub verify: Option<bool>,
+    /// Octal permissions instead of `security.file_mode`; also applied to an existing file
+    pub permissions: Option<String>,
+    /// Refuse content that does not parse as the format of the file's extension (JSON, YAML or TOML)
+    pub validate: Option<bool>,
+    /// Required to overwrite a protected file
+    pub force: Option<bool>,
+    /// Run the formatter configured for the file's extension; on by default when one is configured
+    pub format: Option<bool>,
+    /// Where the content goes; defaults to `overwrite`
+    pub mode: Option<WriteMode>,
+    /// Byte position to write from in `overwrite` mode, keeping the rest of the file
+    pub offset: Option<u64>,
+}
+
+/// How `/api/write` places content in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    /// Replace the file's content, or only the bytes from `offset` on
+    #[default]
+    Overwrite,
+    /// Add the content at the end, creating the file if needed
+    Append,
+    /// Create the file, failing if it already exists
+    CreateNew,
 }
 
 /// Response structure for command execution
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ExecResponse {
     pub success: bool,
+    pub outcome: ExecOutcome,
     pub stdout: String,
     pub stderr: String,
     pub exit_code: Option<i32>,
+    /// Signal that terminated the command, on Unix
+    pub signal: Option<i32>,
+    /// Human-readable reason for a failure, when the exit code or error is well known
+    pub reason: Option<String>,
+    /// Why the command could not be started
+    pub error: Option<String>,
+    /// Whether the command's process tree was killed after `timeout_secs`
+    pub timed_out: bool,
+    /// ID under which the output was logged, for `/api/jobs/{id}/logs`
+    pub job_id: Option<String>,
+    /// Non-empty stdout lines, when `parse_output` is `json_lines`
+    pub records: Option<Vec<OutputRecord>>,
+    /// Declared outputs copied back to `cwd`, for `isolate_cwd` commands
+    pub outputs: Option<Vec<String>>,
+    /// Files collected into the job's artifact store, for jobs that declared `artifacts`
+    pub artifacts: Option<Vec<ArtifactInfo>>,
+}
+
+/// How a command ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecOutcome {
+    Succeeded,
+    /// Exited with a non-zero code
+    Failed,
+    /// Terminated by a signal it did not handle, or crashed
+    Killed,
+    /// Killed by EXEX after `timeout_secs`
+    TimedOut,
+    /// Could not be started at all
+    SpawnFailed,
+}
+
+/// A line of a command's stdout parsed as JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputRecord {
+    /// 1-based line number in stdout
+    pub line: usize,
+    pub value: Option<serde_json::Value>,
+    /// The raw line, kept when it is not valid JSON
+    pub text: Option<String>,
+    pub error: Option<String>,
+}
+
+/// An output stream of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+/// Query parameters for reading a job's log
+#[derive(Debug, Deserialize)]
+pub struct JobLogQuery {
+    /// Byte offset to read from, usually the previous response's `next_offset`
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+    pub stream: Option<LogStream>,
+    pub encoding: Option<ContentEncoding>,
+}
+
+/// Response structure for reading a job's log
+#[derive(Debug, Serialize)]
+pub struct JobLogResponse {
+    pub success: bool,
+    pub job_id: String,
+    pub stream: LogStream,
+    pub offset: u64,
+    /// Offset to resume from; it stops short of a UTF-8 character that is not fully written yet
+    pub next_offset: u64,
+    /// Bytes logged so far
+    pub size: u64,
+    /// Whether the job has finished, so nothing follows `size`
+    pub complete: bool,
+    pub data: Option<String>,
+    pub encoding: Option<ContentEncoding>,
+    pub error: Option<String>,
+}
+
+/// Request structure for submitting a background job
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct JobRequest {
+    #[serde(flatten)]
+    pub exec: ExecRequest,
+    /// Globs relative to `cwd` whose matches are kept once the job ends, for `/api/jobs/{id}/artifacts`
+    pub artifacts: Option<Vec<String>>,
+}
+
+/// A job of a group, named so other jobs of the group can depend on it
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GroupJobRequest {
+    pub name: String,
+    /// Names of jobs that must succeed before this one starts
+    pub depends_on: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub job: JobRequest,
+}
+
+/// Request structure for submitting jobs that depend on each other
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct JobGroupRequest {
+    pub jobs: Vec<GroupJobRequest>,
+    /// Jobs of the group that run at once; only the exec pool limits them when omitted
+    pub max_parallel: Option<usize>,
+}
+
+/// A file kept in a job's artifact store
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactInfo {
+    /// Path relative to the job's `cwd`, with `/` separators
+    pub path: String,
+    pub size: u64,
+}
+
+/// Where a background job is in its life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for an exec worker
+    Queued,
+    Running,
+    /// The command ended; `result` says how
+    Finished,
+    /// The command could not be run, see `error`
+    Failed,
+    /// Cancelled through `/api/jobs/{id}/cancel`
+    Cancelled,
+    /// Never run, because a job of its group it depends on did not succeed
+    Skipped,
+}
+
+/// A command submitted through `/api/jobs`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub job_id: String,
+    /// Redacted command line
+    pub command: String,
+    pub state: JobState,
+    pub pid: Option<u32>,
+    /// RFC 3339 times the job was submitted, started and ended
+    pub submitted: String,
+    pub started: Option<String>,
+    pub finished: Option<String>,
+    /// The `/api/exec` response, once the command has ended
+    pub result: Option<ExecResponse>,
+    pub error: Option<String>,
+}
+
+/// A job of a group, as submitted and as it stands
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupJob {
+    pub name: String,
+    pub depends_on: Vec<String>,
+    pub job: JobInfo,
+}
+
+/// A group submitted through `/api/jobs/groups`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobGroupInfo {
+    pub group_id: String,
+    /// `finished` once every job succeeded, `failed` or `cancelled` once all ended otherwise
+    pub state: JobState,
+    pub max_parallel: Option<usize>,
+    pub submitted: String,
+    pub jobs: Vec<GroupJob>,
+}
+
+/// Response structure for submitting, polling and cancelling job groups
+#[derive(Debug, Serialize)]
+pub struct JobGroupResponse {
+    pub success: bool,
+    pub group: Option<JobGroupInfo>,
+    pub error: Option<String>,
+}
+
+/// Response structure for submitting, polling and cancelling jobs
+#[derive(Debug, Serialize)]
+pub struct JobResponse {
+    pub success: bool,
+    pub job: Option<JobInfo>,
+    /// End of the output logged so far, while the job is queued or running
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub error: Option<String>,
+}
+
+/// A configured pipeline, as listed by `/api/pipelines`
+#[derive(Debug, Serialize)]
+pub struct PipelineInfo {
+    pub name: String,
+    pub description: Option<String>,
+    /// Redacted command of each step
+    pub steps: Vec<String>,
+}
+
+/// How one backup run ended
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupRun {
+    pub success: bool,
+    /// RFC 3339 UTC time the run started
+    pub started: String,
+    pub duration_ms: u64,
+    /// Archive written, if the run got that far
+    pub archive: Option<String>,
+    /// Files and directories packed
+    pub entries: Option<usize>,
+    /// Size of the archive
+    pub bytes: Option<u64>,
+    /// Entries left out: symlinks, special files and paths the policy denies
+    pub skipped: Option<Vec<String>>,
+    /// Older archives deleted to honor `keep`
+    pub pruned: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// A backup profile with its schedule and latest run
+#[derive(Debug, Serialize)]
+pub struct BackupStatus {
+    pub name: String,
+    pub source: String,
+    pub destination: String,
+    pub format: ArchiveFormat,
+    pub interval_minutes: Option<u64>,
+    pub keep: Option<usize>,
+    pub running: bool,
+    /// RFC 3339 UTC time of the next scheduled run
+    pub next_run: Option<String>,
+    /// Latest run since the server started
+    pub last_run: Option<BackupRun>,
+    /// Archives of the profile in `destination`, newest first
+    pub archives: Vec<String>,
+}
+
+/// Response structure for listing backup profiles
+#[derive(Debug, Serialize)]
+pub struct BackupListResponse {
+    pub success: bool,
+    pub backups: Vec<BackupStatus>,
+}
+
+/// Response structure for running a backup on request
+#[derive(Debug, Serialize)]
+pub struct BackupRunResponse {
+    pub success: bool,
+    pub name: String,
+    pub run: Option<BackupRun>,
+    pub error: Option<String>,
+}
+
+/// Response structure for listing pipelines
+#[derive(Debug, Serialize)]
+pub struct PipelineListResponse {
+    pub success: bool,
+    pub pipelines: Vec<PipelineInfo>,
+}
+
+/// How one step of a pipeline run ended
+#[derive(Debug, Serialize)]
+pub struct PipelineStepResult {
+    /// Redacted command of the step
+    pub command: String,
+    #[serde(flatten)]
+    pub result: ExecResponse,
+}
+
+/// Response structure for running a pipeline
+#[derive(Debug, Serialize)]
+pub struct PipelineRunResponse {
+    pub success: bool,
+    pub pipeline: String,
+    /// The steps that ran; steps after a failed one are not run
+    pub steps: Vec<PipelineStepResult>,
+    /// Index of the step that failed
+    pub failed_step: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// Response structure for listing a job's artifacts
+#[derive(Debug, Serialize)]
+pub struct JobArtifactsResponse {
+    pub success: bool,
+    pub job_id: String,
+    pub artifacts: Option<Vec<ArtifactInfo>>,
+    pub error: Option<String>,
 }
 
 /// Response structure for file reading
@@ -78,7 +755,10 @@ pub struct ExecResponse {
 pub struct ReadResponse {
     pub success: bool,
     pub content: Option<String>,
+    pub kind: Option<FileKind>,
+    pub encoding: Option<ContentEncoding>,
     pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
 }
 
 /// Response structure for file writing
@@ -86,6 +766,333 @@ pub struct ReadResponse {
 pub struct WriteResponse {
     pub success: bool,
     pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    /// Hex SHA-256 of the content read back from disk, when verification was requested
+    pub checksum: Option<String>,
+    /// Where validated content failed to parse
+    pub syntax_error: Option<SyntaxError>,
+    /// Content as written after formatting, when a formatter ran
+    pub formatted_content: Option<String>,
+    /// Why the formatter failed; the content was then written as sent
+    pub format_error: Option<String>,
+}
+
+/// A syntax error in content validated against its file type
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyntaxError {
+    /// `json`, `yaml` or `toml`
+    pub format: String,
+    /// 1-based line, when the parser reports one
+    pub line: Option<usize>,
+    /// 1-based column, when the parser reports one
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// Request structure for line-based edits of a text file
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EditRequest {
+    pub path: String,
+    /// Applied together; line numbers refer to the file before any of them
+    pub operations: Vec<LineEdit>,
+    /// Hex SHA-256 the file must still have, from a previous read or edit
+    pub expected_hash: Option<String>,
+    /// Required to edit a protected file
+    pub force: Option<bool>,
+}
+
+/// One line-based edit, tagged by `op`; line numbers are 1-based and ranges inclusive
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum LineEdit {
+    /// Inserts `content` before `line`; the line after the last one appends
+    InsertAtLine { line: usize, content: String },
+    ReplaceRange { start_line: usize, end_line: usize, content: String },
+    DeleteLines { start_line: usize, end_line: usize },
+}
+
+/// Response structure for line-based edits
+#[derive(Debug, Serialize)]
+pub struct EditResponse {
+    pub success: bool,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    /// Hex SHA-256 of the file after the edit, or its current one when `expected_hash` did not match
+    pub hash: Option<String>,
+    /// Lines in the file after the edit
+    pub lines: Option<usize>,
+}
+
+/// Request structure for starting an editing session
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct StartSessionRequest {
+    /// Files to snapshot; files that do not exist yet are deleted again on abort
+    pub paths: Vec<String>,
+    /// Required to include protected files, which abort may rewrite
+    pub force: Option<bool>,
+}
+
+/// Response structure for editing sessions
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub success: bool,
+    pub session_id: Option<String>,
+    /// Each snapshotted file and how it changed since the session started
+    pub files: Option<Vec<SessionFile>>,
+    /// Unified diff of all changes, for `diff` and `finalize`
+    pub diff: Option<String>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// A file of an editing session
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionFile {
+    pub path: String,
+    pub status: FileChangeStatus,
+}
+
+/// How a file changed since its snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeStatus {
+    Unchanged,
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// Write throttle state for one client
+#[derive(Debug, Serialize)]
+pub struct ThrottleState {
+    pub enabled: bool,
+    pub bytes_per_sec: u64,
+    pub burst_bytes: u64,
+    /// Bytes the client can write right now without being delayed
+    pub available_bytes: u64,
+    /// Whether the client's next write will be delayed
+    pub throttled: bool,
+    pub delay_ms: u64,
+    pub bytes_written: u64,
+    pub throttled_writes: u64,
+}
+
+/// Response structure for quota queries
+#[derive(Debug, Serialize)]
+pub struct QuotaResponse {
+    pub success: bool,
+    pub client_id: String,
+    pub max_file_size_bytes: u64,
+    pub disk_reserve_bytes: u64,
+    pub write_throttle: ThrottleState,
+}
+
+/// What a client says about itself, from `X-Exex-Client-Info` or `POST /api/clients`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub name: Option<String>,
+    /// Kind of tool, such as `editor`, `agent` or `script`
+    #[serde(rename = "type")]
+    pub client_type: Option<String>,
+    pub version: Option<String>,
+}
+
+/// A client the daemon has seen, keyed by its client ID
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientRecord {
+    /// `X-Exex-Client-Id`, or the caller's address
+    pub id: String,
+    #[serde(flatten)]
+    pub info: ClientInfo,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub requests: u64,
+}
+
+/// Response structure for client registration
+#[derive(Debug, Serialize)]
+pub struct ClientResponse {
+    pub success: bool,
+    pub client: Option<ClientRecord>,
+    pub error: Option<String>,
+}
+
+/// Response structure for client listings
+#[derive(Debug, Serialize)]
+pub struct ClientsResponse {
+    pub success: bool,
+    pub clients: Vec<ClientRecord>,
+}
+
+/// Machine-readable codes for failures clients are expected to handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The target volume lacks room for the payload plus the configured reserve
+    InsufficientSpace,
+    /// A recursive operation would touch more entries than allowed without an override
+    TooManyEntries,
+    /// The file is not text and was requested without `base64` encoding
+    BinaryContent,
+    /// The path matches `security.protected_patterns` and the change was not forced or may not be
+    ProtectedPath,
+    /// Content written with `validate` does not parse as its file type
+    InvalidSyntax,
+    /// The file no longer has the hash an edit was based on
+    HashMismatch,
+    /// The path is a FIFO, socket or device node, which is never read or written as a file
+    SpecialFile,
+    /// A path in the request is too long, too deep or contains a NUL byte
+    InvalidPath,
+    /// The operation was cancelled through `/api/operations/{id}/cancel` before it finished
+    Cancelled,
+}
+
+impl ErrorCode {
+    /// Every code, for building message catalogs
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::InsufficientSpace,
+        ErrorCode::TooManyEntries,
+        ErrorCode::BinaryContent,
+        ErrorCode::ProtectedPath,
+        ErrorCode::InvalidSyntax,
+        ErrorCode::HashMismatch,
+        ErrorCode::SpecialFile,
+        ErrorCode::InvalidPath,
+        ErrorCode::Cancelled,
+    ];
+}
+
+/// Response structure for the error message catalog
+#[derive(Debug, Serialize)]
+pub struct ErrorCatalogResponse {
+    pub success: bool,
+    /// Language the messages are in, negotiated from `Accept-Language`
+    pub language: String,
+    pub languages: Vec<String>,
+    pub messages: BTreeMap<ErrorCode, String>,
+}
+
+/// Fields of a Windows shortcut (.lnk)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ShortcutInfo {
+    /// Path the shortcut launches; absent for links to virtual shell items
+    pub target: Option<String>,
+    pub arguments: Option<String>,
+    pub working_dir: Option<String>,
+    pub description: Option<String>,
+    /// Icon file and index, such as `C:\Tools\tool.exe,0`
+    pub icon_location: Option<String>,
+    /// Target relative to the shortcut's directory
+    pub relative_path: Option<String>,
+}
+
+/// Request structure for resolving a shortcut
+#[derive(Debug, Deserialize)]
+pub struct ShortcutResolveRequest {
+    pub path: String,
+}
+
+/// Request structure for creating a shortcut
+#[derive(Debug, Deserialize)]
+pub struct ShortcutCreateRequest {
+    /// Where to write the `.lnk` file
+    pub path: String,
+    pub target: String,
+    pub arguments: Option<String>,
+    pub working_dir: Option<String>,
+    pub description: Option<String>,
+    pub icon_location: Option<String>,
+    /// Replace an existing shortcut at `path`
+    pub overwrite: Option<bool>,
+}
+
+/// Response structure for shortcut resolution and creation
+#[derive(Debug, Serialize)]
+pub struct ShortcutResponse {
+    pub success: bool,
+    pub path: Option<String>,
+    pub shortcut: Option<ShortcutInfo>,
+    /// Whether the target lies within the allowed paths, so it can be scanned or opened
+    pub target_allowed: Option<bool>,
+    pub error: Option<String>,
+}
+
+/// Request structure for power management
+#[derive(Debug, Deserialize)]
+pub struct PowerRequest {
+    pub action: PowerAction,
+    /// Must be `true` for `sleep`, so a stray request cannot suspend the machine
+    pub confirm: Option<bool>,
+    /// Length of a keep-awake lease; defaults to an hour
+    pub duration_secs: Option<u64>,
+    /// Why the machine is kept awake, shown by the platform's inhibitor list where it has one
+    pub reason: Option<String>,
+    /// Lease to end with `release`
+    pub lease_id: Option<String>,
+}
+
+/// A keep-awake lease
+#[derive(Debug, Clone, Serialize)]
+pub struct WakeLease {
+    pub id: String,
+    pub reason: String,
+    /// RFC 3339 UTC timestamps
+    pub acquired: String,
+    pub expires: String,
+    pub remaining_secs: u64,
+}
+
+/// Response structure for power actions
+#[derive(Debug, Serialize)]
+pub struct PowerResponse {
+    pub success: bool,
+    pub action: PowerAction,
+    /// The lease taken by `keep_awake` or ended by `release`
+    pub lease: Option<WakeLease>,
+    pub error: Option<String>,
+}
+
+/// Response structure for the power status
+#[derive(Debug, Serialize)]
+pub struct PowerStatusResponse {
+    pub success: bool,
+    pub actions: Vec<PowerAction>,
+    pub max_wake_lease_secs: u64,
+    pub leases: Vec<WakeLease>,
+    /// Whether the lock held while commands run is currently taken
+    pub job_wake_lock: bool,
+}
+
+/// Request structure for reading a registry key
+#[derive(Debug, Deserialize)]
+pub struct RegistryReadRequest {
+    /// Key such as `HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall`
+    pub key: String,
+    /// Read only this value instead of listing the key
+    pub value: Option<String>,
+}
+
+/// A registry value, decoded to JSON where the type allows
+#[derive(Debug, Clone, Serialize)]
+pub struct RegistryValue {
+    /// Value name; empty for the key's default value
+    pub name: String,
+    /// Registry type, such as `REG_SZ` or `REG_DWORD`
+    pub kind: String,
+    /// Strings, string lists and numbers as JSON; other types as hex
+    pub data: serde_json::Value,
+}
+
+/// Response structure for registry reads
+#[derive(Debug, Serialize)]
+pub struct RegistryReadResponse {
+    pub success: bool,
+    /// Normalized key that was read
+    pub key: String,
+    pub subkeys: Vec<String>,
+    pub values: Vec<RegistryValue>,
+    pub error: Option<String>,
 }
 
 /// Generic error response structure
@@ -94,6 +1101,24 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+/// Response for requests refused before reaching a handler because of a malformed path
+#[derive(Debug, Serialize)]
+pub struct PathErrorResponse {
+    pub success: bool,
+    pub error: String,
+    pub error_code: ErrorCode,
+    /// Request field holding the path, such as `operations[2].path`; `body` when the body itself is malformed
+    pub field: String,
+}
+
+/// Response structure for CSRF token issuance
+#[derive(Debug, Serialize)]
+pub struct CsrfTokenResponse {
+    pub success: bool,
+    pub cookie_name: String,
+    pub header_name: String,
+}
+
 /// Health check response structure
 #[derive(Debug, Serialize)]
 pub struct HealthResponse {
@@ -103,41 +1128,144 @@ pub struct HealthResponse {
 }
 
 /// Request structure for opening applications
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct OpenAppRequest {
     pub application: String,
     pub args: Option<Vec<String>>,
     pub cwd: Option<String>,
+    /// Defaults to `security.default_priority`
+    pub priority: Option<ProcessPriority>,
+    /// Wait for the application to exit and report its exit status instead of detaching
+    pub wait: Option<bool>,
+    /// Return the application's stdout and stderr; requires `wait`
+    pub capture_output: Option<bool>,
+    /// Files or folders for the application to open, passed after `args`
+    pub files: Option<Vec<String>>,
+    /// Bring a macOS app bundle to the front (default); `false` launches it in the background
+    pub activate: Option<bool>,
+    /// Start another instance of a macOS app bundle even if one is already running
+    pub new_instance: Option<bool>,
+    #[serde(flatten)]
+    pub environment: EnvOptions,
+}
+
+/// Request structure for opening a terminal
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct OpenTerminalRequest {
+    /// Directory the terminal starts in
+    pub path: String,
+}
+
+/// Request structure for opening a file in an editor
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct OpenEditorRequest {
+    pub path: String,
+    /// 1-based line to jump to
+    pub line: Option<u32>,
+    /// 1-based column to jump to
+    pub column: Option<u32>,
+    /// Name of a configured editor; defaults to `apps.default_editor`
+    pub editor: Option<String>,
+}
+
+/// Request structure for opening a URL with the system handler
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct OpenUrlRequest {
+    pub url: String,
 }
 
 /// Request structure for scanning directories
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct ScanRequest {
     pub path: String,
     pub recursive: Option<bool>,
+    /// Levels a recursive scan lists; `1` is the directory's own entries
+    pub max_depth: Option<usize>,
     pub include_hidden: Option<bool>,
+    /// Page size; setting this, `cursor` or `offset` returns a [`Paginated`] listing (by path unless `sort_by` is set)
+    pub limit: Option<usize>,
+    /// Cursor from a previous page's `next_cursor`
+    pub cursor: Option<String>,
+    /// Index of the first item of the page, instead of `cursor`
+    pub offset: Option<usize>,
+    /// Server-side ordering of the listing
+    pub sort_by: Option<ScanSortKey>,
+    /// List directories before files, within the chosen ordering
+    pub directories_first: Option<bool>,
+    /// Replaces the configured and workspace ignore patterns for this scan; `[]` ignores nothing
+    pub ignore_patterns: Option<Vec<String>>,
+    /// Return only names, paths and types, skipping the per-entry metadata lookups
+    pub names_only: Option<bool>,
+    /// Attach totals of their contents to the scanned directory and every subdirectory walked
+    pub aggregate: Option<bool>,
+    /// Report each entry's owner: uid, gid and names on Unix, SID and account name on Windows
+    pub include_owner: Option<bool>,
+    /// Classify files by reading their leading bytes; by default `kind` comes from the extension
+    pub sniff_content: Option<bool>,
+    /// ID for cancelling the operation through `/api/operations/{id}/cancel`; generated when absent
+    pub operation_id: Option<String>,
+}
+
+/// Orderings supported by scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanSortKey {
+    /// Case-insensitive file name
+    Name,
+    Size,
+    /// Last modification time
+    Mtime,
+    /// File extension
+    Type,
 }
 
 /// Request structure for delete operations
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct DeleteRequest {
     pub path: String,
     pub recursive: Option<bool>,
+    /// Proceed even if more than `security.max_affected_entries` entries would be removed
+    pub override_entry_limit: Option<bool>,
+    /// Required to delete a protected file, or a directory holding one
+    pub force: Option<bool>,
 }
 
 /// Request structure for create operations
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct CreateRequest {
     pub path: String,
     pub is_directory: bool,
     pub content: Option<String>, // For files
+    /// Octal permissions instead of `security.file_mode` or `security.dir_mode`
+    pub permissions: Option<String>,
+    /// Required to create a protected file
+    pub force: Option<bool>,
 }
 
 /// Request structure for rename/move operations
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct RenameRequest {
     pub from_path: String,
     pub to_path: String,
+    /// Required to move a protected file, or to move a file to a protected path
+    pub force: Option<bool>,
+}
+
+/// Request structure for copy operations
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CopyRequest {
+    pub from_path: String,
+    pub to_path: String,
+    /// Required to copy a directory, with everything below it
+    pub recursive: Option<bool>,
+    /// Replace existing files; existing directories are merged into
+    pub overwrite: Option<bool>,
+    /// Proceed even if more than `security.max_affected_entries` entries would be copied
+    pub override_entry_limit: Option<bool>,
+    /// Required to copy onto a protected path
+    pub force: Option<bool>,
+    /// ID for cancelling the operation through `/api/operations/{id}/cancel`; generated when absent
+    pub operation_id: Option<String>,
 }
 
 /// File/Directory information
@@ -147,9 +1275,49 @@ pub struct FileInfo {
     pub path: String,
     pub is_directory: bool,
     pub size: Option<u64>,
+    /// RFC 3339 UTC timestamp in API v2; epoch seconds as a string in v1
     pub modified: Option<String>,
     pub created: Option<String>,
+    pub accessed: Option<String>,
+    /// Unix epoch seconds, in every API version
+    pub modified_epoch: Option<u64>,
+    pub created_epoch: Option<u64>,
+    pub accessed_epoch: Option<u64>,
     pub permissions: Option<String>,
+    pub is_hidden: bool,
+    pub is_system: bool,
+    /// Raw Windows file attribute bits
+    pub attributes: Option<u32>,
+    /// Content classification; absent for directories, unknown extensions and, with `sniff_content`, unreadable files
+    pub kind: Option<FileKind>,
+    /// Set for FIFOs, sockets and device nodes
+    pub special: Option<SpecialFileType>,
+    /// Whether a regular file occupies less space on disk than its length; absent when not known
+    pub sparse: Option<bool>,
+    /// Owning user and group IDs on Unix, when `include_owner` was requested
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// User name on Unix, `DOMAIN\name` on Windows
+    pub owner: Option<String>,
+    /// Group name on Unix
+    pub group: Option<String>,
+    /// Owner security identifier on Windows
+    pub owner_sid: Option<String>,
+    /// Totals of the directory's contents, for directories walked by an `aggregate` scan
+    pub stats: Option<DirectoryStats>,
+}
+
+/// Totals of everything a scan listed beneath a directory, at any depth
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct DirectoryStats {
+    /// Entries that are not directories
+    pub files: u64,
+    pub directories: u64,
+    /// Sum of file sizes; 0 for `names_only` scans
+    pub bytes: u64,
+    /// Latest modification time among the entries, formatted like [`FileInfo::modified`]
+    pub newest_modified: Option<String>,
+    pub newest_modified_epoch: Option<u64>,
 }
 
 /// Response structure for opening applications
@@ -160,12 +1328,38 @@ pub struct OpenAppResponse {
     pub error: Option<String>,
 }
 
+/// Response structure for applications launched with `wait`
+#[derive(Debug, Serialize)]
+pub struct AppRunResponse {
+    pub success: bool,
+    pub pid: Option<u32>,
+    pub exit_code: Option<i32>,
+    /// Present when `capture_output` was requested
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub error: Option<String>,
+}
+
 /// Response structure for scanning directories
 #[derive(Debug, Serialize)]
 pub struct ScanResponse {
     pub success: bool,
     pub items: Option<Vec<FileInfo>>,
     pub total_count: Option<usize>,
+    /// Totals for the scanned directory itself, when `aggregate` was requested
+    pub stats: Option<DirectoryStats>,
+    pub error: Option<String>,
+}
+
+/// Page of results shared by listing endpoints
+#[derive(Debug, Serialize)]
+pub struct Paginated<T> {
+    pub success: bool,
+    pub items: Vec<T>,
+    /// Opaque cursor for the next page; absent on the last page
+    pub next_cursor: Option<String>,
+    /// Number of items across all pages
+    pub total: usize,
     pub error: Option<String>,
 }
 
@@ -175,6 +1369,7 @@ pub struct DeleteResponse {
     pub success: bool,
     pub deleted_count: Option<usize>,
     pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
 }
 
 /// Response structure for create operations
@@ -183,6 +1378,7 @@ pub struct CreateResponse {
     pub success: bool,
     pub created_path: Option<String>,
     pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
 }
 
 /// Response structure for rename operations
@@ -192,6 +1388,727 @@ pub struct RenameResponse {
     pub old_path: Option<String>,
     pub new_path: Option<String>,
     pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Response structure for copy operations
+#[derive(Debug, Serialize)]
+pub struct CopyResponse {
+    pub success: bool,
+    pub from_path: Option<String>,
+    pub to_path: Option<String>,
+    /// Files and directories created or replaced
+    pub copied_count: Option<usize>,
+    pub copied_bytes: Option<u64>,
+    /// Entries left out: symlinks, special files and paths the policy denies
+    pub skipped: Option<Vec<String>>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Digest algorithm for `/api/hash`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha1,
+    Md5,
+    /// 64-bit xxHash, seed 0
+    Xxh64,
+    /// 64-bit XXH3, seed 0
+    Xxh3,
+}
+
+/// Request structure for hashing a file
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct HashRequest {
+    pub path: String,
+    /// Defaults to `sha256`
+    pub algorithm: Option<HashAlgorithm>,
+    /// Hex digest to compare against, case-insensitively
+    pub expected: Option<String>,
+}
+
+/// Response structure for file hashing
+#[derive(Debug, Serialize)]
+pub struct HashResponse {
+    pub success: bool,
+    pub path: Option<String>,
+    pub algorithm: Option<HashAlgorithm>,
+    /// Lowercase hex digest
+    pub hash: Option<String>,
+    /// Bytes hashed
+    pub size: Option<u64>,
+    /// Whether `hash` equals `expected`, when it was given
+    pub matches: Option<bool>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Request structure for reading the end of a file
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TailRequest {
+    pub path: String,
+    /// Lines to return from the end of the file; defaults to 10
+    pub lines: Option<usize>,
+    /// Keep the response open as a server-sent event stream of lines appended later
+    pub follow: Option<bool>,
+}
+
+/// Response structure for the last lines of a file
+#[derive(Debug, Serialize)]
+pub struct TailResponse {
+    pub success: bool,
+    pub path: Option<String>,
+    /// Lines without their line endings, oldest first
+    pub lines: Vec<String>,
+    /// File size the lines were read up to, for resuming with `/api/read` or a later tail
+    pub size: Option<u64>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// A line pushed by a followed tail, sent as the data of a `line` event
+#[derive(Debug, Serialize)]
+pub struct TailLine {
+    /// Byte offset of the line in the file
+    pub offset: u64,
+    pub line: String,
+}
+
+/// Request structure for watching a path for changes
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct WatchRequest {
+    /// File or directory to watch
+    pub path: String,
+    /// Whether changes anywhere below a directory are reported; defaults to true
+    pub recursive: Option<bool>,
+}
+
+/// A registered watch
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchInfo {
+    pub watch_id: String,
+    pub path: String,
+    pub recursive: bool,
+    pub created: String,
+}
+
+/// Response structure for registering or removing a watch
+#[derive(Debug, Serialize)]
+pub struct WatchResponse {
+    pub success: bool,
+    pub watch: Option<WatchInfo>,
+    pub error: Option<String>,
+}
+
+/// Response structure for listing watches
+#[derive(Debug, Serialize)]
+pub struct WatchListResponse {
+    pub success: bool,
+    pub watches: Vec<WatchInfo>,
+}
+
+/// What happened to a watched path
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchEventKind {
+    Create,
+    Modify,
+    Delete,
+    Rename,
+}
+
+/// A change below a watched path, streamed by `/api/events`
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchEvent {
+    pub watch_id: String,
+    pub kind: WatchEventKind,
+    pub path: String,
+    /// New path of a renamed entry
+    pub to: Option<String>,
+    pub time: String,
+}
+
+/// A long-running operation that can be cancelled
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationInfo {
+    pub operation_id: String,
+    /// Endpoint that started it, such as `scan` or `copy`
+    pub kind: String,
+    /// Path the operation works on
+    pub target: String,
+    /// Its main parameters, such as the destination of a copy
+    pub summary: String,
+    pub started: String,
+    /// Whether cancellation was requested; the operation stops at its next check
+    pub cancelled: bool,
+    pub progress: OperationProgress,
+}
+
+/// How far an operation has got
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationProgress {
+    /// Entries handled so far; while the work is still being planned, entries examined
+    pub processed: u64,
+    /// Entries to handle, once planning has worked it out
+    pub total: Option<u64>,
+}
+
+/// Response structure for cancelling an operation
+#[derive(Debug, Serialize)]
+pub struct OperationResponse {
+    pub success: bool,
+    pub operation: Option<OperationInfo>,
+    pub error: Option<String>,
+}
+
+/// Response structure for listing the operations in progress
+#[derive(Debug, Serialize)]
+pub struct OperationListResponse {
+    pub success: bool,
+    pub operations: Vec<OperationInfo>,
+}
+
+/// Query parameters for the event stream
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Only stream events of this watch
+    pub watch: Option<String>,
+}
+
+/// Request structure for mirroring a directory
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SyncRequest {
+    /// Directory to mirror
+    pub source: String,
+    /// Directory made to match the source, created if missing
+    pub destination: String,
+    /// Compare file contents instead of size and modification time
+    pub checksum: Option<bool>,
+    /// Remove destination entries that are not in the source
+    pub delete: Option<bool>,
+    /// Report what would change without changing anything
+    pub dry_run: Option<bool>,
+    /// Glob patterns, relative to the roots, of entries neither copied nor deleted
+    pub exclude: Option<Vec<String>>,
+    /// Proceed even if more than `security.max_affected_entries` entries would change
+    pub override_entry_limit: Option<bool>,
+    /// Required to change protected paths
+    pub force: Option<bool>,
+    /// ID for cancelling the operation through `/api/operations/{id}/cancel`; generated when absent
+    pub operation_id: Option<String>,
+}
+
+/// Response structure for directory mirroring
+#[derive(Debug, Serialize)]
+pub struct SyncResponse {
+    pub success: bool,
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub dry_run: bool,
+    /// Destination files and directories created or replaced
+    pub copied: Option<Vec<String>>,
+    /// Destination entries removed with `delete`
+    pub deleted: Option<Vec<String>>,
+    /// Files already up to date
+    pub unchanged: Option<usize>,
+    /// Bytes of file content copied
+    pub bytes: Option<u64>,
+    /// Entries left out: symlinks, special files, paths the policy denies and entries of a different kind at the destination
+    pub skipped: Option<Vec<String>>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Container format of an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    Zip,
+    /// Gzip-compressed tar
+    TarGz,
+}
+
+/// Request structure for packing files into an archive
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ArchiveRequest {
+    /// Archive to create
+    pub path: String,
+    /// Files and directories to pack; each is stored under its own name
+    pub sources: Vec<String>,
+    /// Inferred from the extension of `path` (`.zip`, `.tar.gz`, `.tgz`) when omitted
+    pub format: Option<ArchiveFormat>,
+    /// Glob patterns, relative to each source, of entries to leave out
+    pub exclude: Option<Vec<String>>,
+    /// Replace an existing archive
+    pub overwrite: Option<bool>,
+    /// Required to write a protected path
+    pub force: Option<bool>,
+    /// ID for cancelling the operation through `/api/operations/{id}/cancel`; generated when absent
+    pub operation_id: Option<String>,
+}
+
+/// Response structure for archive creation
+#[derive(Debug, Serialize)]
+pub struct ArchiveResponse {
+    pub success: bool,
+    pub path: Option<String>,
+    pub format: Option<ArchiveFormat>,
+    /// Files and directories packed
+    pub entries: Option<usize>,
+    /// Size of the archive
+    pub bytes: Option<u64>,
+    /// Entries left out: symlinks, special files and paths the policy denies
+    pub skipped: Option<Vec<String>>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Request structure for unpacking an archive
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExtractRequest {
+    /// Archive to unpack
+    pub path: String,
+    /// Directory to unpack into, created if missing
+    pub destination: String,
+    /// Inferred from the extension of `path` when omitted
+    pub format: Option<ArchiveFormat>,
+    /// Replace existing files; without it they are kept and listed in `skipped`
+    pub overwrite: Option<bool>,
+    /// Proceed even if the archive holds more than `security.max_affected_entries` entries
+    pub override_entry_limit: Option<bool>,
+    /// Required to write protected paths
+    pub force: Option<bool>,
+    /// ID for cancelling the operation through `/api/operations/{id}/cancel`; generated when absent
+    pub operation_id: Option<String>,
+}
+
+/// Response structure for archive extraction
+#[derive(Debug, Serialize)]
+pub struct ExtractResponse {
+    pub success: bool,
+    pub destination: Option<String>,
+    /// Files and directories written
+    pub entries: Option<usize>,
+    /// Bytes of file content written
+    pub bytes: Option<u64>,
+    /// Entries left out: links, special files and existing files without `overwrite`
+    pub skipped: Option<Vec<String>>,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Request structure for finding files by name
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FindRequest {
+    pub path: String,
+    /// Globs relative to `path`, such as `**/*.rs`; one without a `/` matches names at any depth
+    pub patterns: Vec<String>,
+    /// Globs relative to `path` of files and directories to leave out
+    pub exclude: Option<Vec<String>>,
+    /// Replaces the configured and workspace ignore patterns, as in scan
+    pub ignore_patterns: Option<Vec<String>>,
+    /// Also leave out what `.gitignore` files ignore
+    pub respect_gitignore: Option<bool>,
+    pub include_hidden: Option<bool>,
+    /// Return matching directories too, not just files
+    pub include_directories: Option<bool>,
+    /// Page size of the [`Paginated`] matches; defaults to 1000, at most 10000
+    pub limit: Option<usize>,
+    /// Cursor from a previous page's `next_cursor`
+    pub cursor: Option<String>,
+    /// Classify files by reading their leading bytes, as in scan
+    pub sniff_content: Option<bool>,
+}
+
+/// Request structure for searching file contents
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchRequest {
+    /// File, or directory searched recursively
+    pub path: String,
+    /// Text to find, or a regular expression with `regex`
+    pub query: String,
+    pub regex: Option<bool>,
+    /// Defaults to true
+    pub case_sensitive: Option<bool>,
+    /// Globs relative to `path`; only matching files are searched
+    pub include: Option<Vec<String>>,
+    /// Globs relative to `path` of files and directories to skip, on top of the ignore patterns
+    pub exclude: Option<Vec<String>>,
+    pub include_hidden: Option<bool>,
+    /// Defaults to 1000, at most 10000
+    pub max_results: Option<usize>,
+    /// Lines returned before and after each match; defaults to 0, at most 20
+    pub context_lines: Option<usize>,
+    /// ID for cancelling the operation through `/api/operations/{id}/cancel`; generated when absent
+    pub operation_id: Option<String>,
+}
+
+/// A line matching a search
+#[derive(Debug, Serialize)]
+pub struct SearchMatch {
+    pub path: String,
+    /// 1-based
+    pub line_number: usize,
+    pub line: String,
+    /// Up to `context_lines` lines preceding the match
+    pub before: Vec<String>,
+    /// Up to `context_lines` lines following the match
+    pub after: Vec<String>,
+}
+
+/// Response structure for searching file contents
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub success: bool,
+    pub matches: Vec<SearchMatch>,
+    pub files_searched: usize,
+    /// Whether the search stopped at `max_results`
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+/// Drive/volume information
+#[derive(Debug, Serialize, Clone)]
+pub struct MountInfo {
+    pub mount_point: String,
+    pub label: String,
+    pub file_system: String,
+    pub kind: String,
+    pub total_space: u64,
+    pub available_space: u64,
+    pub is_removable: bool,
+    pub is_read_only: bool,
+    /// Whether the mount root itself passes the path policy
+    pub accessible: bool,
+    /// Whether any disallowed path lies inside this mount
+    pub has_restrictions: bool,
+}
+
+/// Response structure for mount enumeration
+#[derive(Debug, Serialize)]
+pub struct MountsResponse {
+    pub success: bool,
+    pub mounts: Option<Vec<MountInfo>>,
+    pub error: Option<String>,
+}
+
+/// Queue and throughput metrics for a worker pool
+#[derive(Debug, Serialize, Clone)]
+pub struct WorkerPoolStats {
+    pub name: String,
+    pub workers: usize,
+    pub queue_limit: usize,
+    pub queued: usize,
+    pub active: usize,
+    pub completed: u64,
+    pub rejected: u64,
+}
+
+/// What launched a process tracked by the process registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessKind {
+    App,
+    Terminal,
+    Editor,
+    Url,
+    Exec,
+}
+
+/// A spawned process and, once it has exited, its exit status
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub kind: ProcessKind,
+    /// Program, or redacted command line, that was launched
+    pub name: String,
+    /// RFC 3339 launch time
+    pub started: String,
+    /// RFC 3339 time the exit was observed
+    pub finished: Option<String>,
+    pub running: bool,
+    /// None while running, or when the process was ended by a signal
+    pub exit_code: Option<i32>,
+    /// Job whose output is logged, for `/api/exec` commands
+    pub job_id: Option<String>,
+}
+
+/// Query parameters for listing spawned processes
+#[derive(Debug, Deserialize)]
+pub struct ProcessQuery {
+    /// Only this process
+    pub pid: Option<u32>,
+    /// Only running (`true`) or exited (`false`) processes
+    pub running: Option<bool>,
+}
+
+/// Response structure for spawned process listings
+#[derive(Debug, Serialize)]
+pub struct ProcessListResponse {
+    pub success: bool,
+    pub processes: Vec<ProcessInfo>,
+}
+
+/// Request structure for killing a spawned process
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct KillProcessRequest {
+    /// PID reported by the launch, or listed by `/api/processes`
+    pub pid: u32,
+}
+
+/// Response structure for killing a spawned process
+#[derive(Debug, Serialize)]
+pub struct KillProcessResponse {
+    pub success: bool,
+    /// The process as it was when killed
+    pub process: Option<ProcessInfo>,
+    pub error: Option<String>,
+}
+
+/// Response structure for worker pool metrics
+#[derive(Debug, Serialize)]
+pub struct WorkerStatsResponse {
+    pub success: bool,
+    pub pools: Vec<WorkerPoolStats>,
+}
+
+/// Query parameters for the command resolvability report
+#[derive(Debug, Deserialize)]
+pub struct CommandReportQuery {
+    /// Run each resolved command with `--version` (off by default)
+    pub version: Option<bool>,
+}
+
+/// How a whitelisted command resolves on this machine
+#[derive(Debug, Serialize)]
+pub struct CommandReport {
+    pub name: String,
+    /// Program actually run, after `security.command_aliases`
+    pub program: String,
+    pub aliased: bool,
+    /// Absolute path the program resolves to through PATH
+    pub path: Option<String>,
+    /// Whether the resolved file can currently be executed
+    pub executable: bool,
+    /// First line printed by `--version`, when requested and the probe succeeded
+    pub version: Option<String>,
+}
+
+/// Response structure for the command resolvability report
+#[derive(Debug, Serialize)]
+pub struct CommandReportResponse {
+    pub success: bool,
+    pub commands: Vec<CommandReport>,
+    pub error: Option<String>,
+}
+
+/// Query parameters for the exec environment snapshot
+#[derive(Debug, Deserialize)]
+pub struct ExecEnvironmentQuery {
+    /// Command to resolve and check against the policy
+    pub command: Option<String>,
+    /// Working directory a command would be run in
+    pub cwd: Option<String>,
+    /// Show the environment a `clean_env` request would get
+    pub clean_env: Option<bool>,
+}
+
+/// Working directory a command would run in
+#[derive(Debug, Serialize)]
+pub struct ExecCwd {
+    /// The requested directory, or the daemon's own when none was given
+    pub path: String,
+    pub requested: bool,
+    pub exists: bool,
+    pub allowed: bool,
+}
+
+/// Shell used for commands sent without `args`
+#[derive(Debug, Serialize)]
+pub struct ExecShell {
+    /// `sh -c` or `cmd /C`
+    pub program: String,
+    /// Where the shell resolves through PATH; `None` means shell commands cannot run
+    pub path: Option<String>,
+}
+
+/// Response structure for the exec environment snapshot
+#[derive(Debug, Serialize)]
+pub struct ExecEnvironmentResponse {
+    pub success: bool,
+    /// Resolution of the requested command, after `security.command_aliases`
+    pub command: Option<CommandReport>,
+    /// Whether the policy, including the workspace at `cwd`, allows the command
+    pub command_allowed: Option<bool>,
+    pub cwd: ExecCwd,
+    pub shell: ExecShell,
+    /// PATH entries in search order
+    pub path: Vec<String>,
+    /// Variables commands inherit, with secrets masked
+    pub env: BTreeMap<String, String>,
+    /// Variables requests may not set
+    pub blocked_env: Vec<String>,
+    pub default_timeout_secs: Option<u64>,
+    pub default_priority: ProcessPriority,
+}
+
+/// Outcome of one self-test check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but something is likely misconfigured
+    Warn,
+    Fail,
+    /// Not applicable to this configuration
+    Skipped,
+}
+
+/// One component checked by the self-test
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestCheck {
+    /// `audit_file`, `workspace_root`, `temp_dir`, `command` or `tls`
+    pub component: String,
+    /// Path or command checked, for components that are checked once per entry
+    pub target: Option<String>,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Response structure for the self-test
+#[derive(Debug, Serialize)]
+pub struct SelfTestResponse {
+    pub success: bool,
+    /// False when any check failed
+    pub healthy: bool,
+    pub checks: Vec<SelfTestCheck>,
+    pub error: Option<String>,
+}
+
+/// Response structure for reading and updating the configuration
+#[derive(Debug, Serialize)]
+pub struct ConfigResponse {
+    pub success: bool,
+    /// The configuration in effect, with values that look like credentials masked
+    pub config: Option<Config>,
+    pub error: Option<String>,
+}
+
+/// One operation in an NDJSON batch request, tagged by `op`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Exec(ExecRequest),
+    Read(ReadRequest),
+    Write(WriteRequest),
+    Edit(EditRequest),
+    Scan(ScanRequest),
+    Delete(DeleteRequest),
+    Create(CreateRequest),
+    Rename(RenameRequest),
+    Copy(CopyRequest),
+}
+
+/// A line of an NDJSON batch request; `id` is echoed back for correlation
+#[derive(Debug, Deserialize)]
+pub struct BatchLine {
+    pub id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub operation: BatchOperation,
+}
+
+/// A line of an NDJSON batch response
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    /// Zero-based position of the operation in the request stream
+    pub index: usize,
+    pub id: Option<serde_json::Value>,
+    pub op: Option<String>,
+    /// HTTP status the equivalent single request would have returned
+    pub status: u16,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// An endpoint listed in the capabilities document
+#[derive(Debug, Serialize)]
+pub struct EndpointInfo {
+    pub method: String,
+    pub path: String,
+    pub deprecated: bool,
+    pub sunset: Option<String>,
+}
+
+/// Optional features and whether they are active on this daemon
+#[derive(Debug, Serialize)]
+pub struct CapabilityFeatures {
+    pub csrf_protection: bool,
+    pub credentials: bool,
+    pub malware_scan: bool,
+    pub encryption_at_rest: bool,
+    pub path_cache: bool,
+    pub streaming_scan: bool,
+    pub streaming_read: bool,
+    pub ndjson_batch: bool,
+    pub write_throttle: bool,
+}
+
+/// Size and concurrency limits clients should respect
+#[derive(Debug, Serialize)]
+pub struct CapabilityLimits {
+    pub max_file_size_bytes: u64,
+    /// Reads of plaintext files above this size are streamed
+    pub read_stream_threshold_bytes: u64,
+    pub max_batch_line_bytes: usize,
+    pub exec_workers: usize,
+    pub fs_workers: usize,
+    pub background_workers: usize,
+    pub worker_queue_limit: usize,
+    pub disk_reserve_bytes: u64,
+    /// Per-client write throughput limit; 0 when unlimited
+    pub write_bytes_per_sec: u64,    /// Entries a recursive operation may touch without an override; 0 when unlimited
+    pub max_affected_entries: usize,
+}
+
+/// One operation an agent may call, described for tool-using planners
+#[derive(Debug, Serialize)]
+pub struct ToolDescription {
+    /// Operation name, matching the `op` of batch and policy test entries
+    pub name: String,
+    pub description: String,
+    pub method: String,
+    pub path: String,
+    /// JSON Schema of the request body
+    pub parameters: serde_json::Value,
+    /// Policy limits that apply to this operation
+    pub limits: serde_json::Value,
+    /// Valid request bodies
+    pub examples: Vec<serde_json::Value>,
+}
+
+/// Response structure for the tool manifest
+#[derive(Debug, Serialize)]
+pub struct ToolManifestResponse {
+    pub success: bool,
+    pub client_id: String,
+    pub api_version: u32,
+    pub tools: Vec<ToolDescription>,
+}
+
+/// Response structure for capability discovery
+#[derive(Debug, Serialize)]
+pub struct CapabilitiesResponse {
+    pub success: bool,
+    pub service: String,
+    pub version: String,
+    pub api_version: u32,
+    pub supported_api_versions: Vec<u32>,
+    pub endpoints: Vec<EndpointInfo>,
+    pub features: CapabilityFeatures,
+    pub limits: CapabilityLimits,
 }
 
 /// Response structure for shutdown operation
@@ -201,3 +2118,26 @@ pub struct ShutdownResponse {
     pub message: String,
     pub shutdown_in_seconds: Option<u32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_request_verify() {
+        let plain: WriteRequest = serde_json::from_str(r#"{"path":"a.txt","content":"x"}"#).unwrap();
+        assert_eq!(plain.verify, None);
+        let verified: WriteRequest = serde_json::from_str(r#"{"path":"a.txt","content":"x","verify":true}"#).unwrap();
+        assert_eq!(verified.verify, Some(true));
+    }
+
+    #[test]
+    fn test_read_request_encoding() {
+        let plain: ReadRequest = serde_json::from_str(r#"{"path":"a.png"}"#).unwrap();
+        assert_eq!(plain.encoding, None);
+        let binary: ReadRequest = serde_json::from_str(r#"{"path":"a.png","encoding":"base64"}"#).unwrap();
+        assert_eq!(binary.encoding, Some(ContentEncoding::Base64));
+        assert!(serde_json::from_str::<ReadRequest>(r#"{"path":"a.png","encoding":"hex"}"#).is_err());
+        assert_eq!(serde_json::to_value(FileKind::Image).unwrap(), "image");
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::info;
+
+use crate::models::{ErrorCode, OperationInfo, OperationProgress};
+
+/// Longest operation ID a client may choose
+const MAX_OPERATION_ID_LEN: usize = 64;
+
+/// Error an operation stops with once it has been cancelled
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("operation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Whether an error is an operation stopping because it was cancelled
+pub fn is_cancellation(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|inner| inner.is::<Cancelled>())
+}
+
+/// `CANCELLED` for a cancellation, so responses can tell it from other failures
+pub fn cancellation_code(e: &io::Error) -> Option<ErrorCode> {
+    is_cancellation(e).then_some(ErrorCode::Cancelled)
+}
+
+/// Total of a token whose total is not known yet
+const UNKNOWN_TOTAL: u64 = u64::MAX;
+
+#[derive(Debug)]
+struct TokenState {
+    cancelled: AtomicBool,
+    processed: AtomicU64,
+    total: AtomicU64,
+}
+
+impl Default for TokenState {
+    fn default() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            processed: AtomicU64::new(0),
+            total: AtomicU64::new(UNKNOWN_TOTAL),
+        }
+    }
+}
+
+/// Flag a long operation checks in its loops to stop early, counting its progress as it goes
+///
+/// A default token is never cancelled, for work that runs outside a request.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<TokenState>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fails with [`Cancelled`] once the token has been cancelled; otherwise counts an entry as processed
+    pub fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            return Err(io::Error::other(Cancelled));
+        }
+        self.0.processed.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Starts counting towards a known number of entries, once planning has worked it out
+    pub fn set_total(&self, total: usize) {
+        self.0.processed.store(0, Ordering::Relaxed);
+        self.0.total.store(total as u64, Ordering::Relaxed);
+    }
+
+    pub fn progress(&self) -> OperationProgress {
+        let total = self.0.total.load(Ordering::Relaxed);
+        OperationProgress {
+            processed: self.0.processed.load(Ordering::Relaxed),
+            total: (total != UNKNOWN_TOTAL).then_some(total),
+        }
+    }
+}
+
+/// Whether a string may be used as an operation ID
+pub fn is_operation_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_OPERATION_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Why an operation could not be started
+#[derive(Debug)]
+pub enum OperationError {
+    InvalidId(String),
+    /// An operation with this ID is still running
+    Duplicate(String),
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationError::InvalidId(id) => write!(f, "Invalid operation ID: {}", id),
+            OperationError::Duplicate(id) => write!(f, "Operation {} is already running", id),
+        }
+    }
+}
+
+struct Entry {
+    info: OperationInfo,
+    token: CancelToken,
+}
+
+impl Entry {
+    /// The operation as reported to clients, with its current progress
+    fn snapshot(&self) -> OperationInfo {
+        OperationInfo {
+            progress: self.token.progress(),
+            ..self.info.clone()
+        }
+    }
+}
+
+/// Long-running operations in progress, by ID, so they can be cancelled
+#[derive(Default)]
+pub struct OperationRegistry {
+    running: Mutex<HashMap<String, Entry>>,
+}
+
+/// A registered operation; dropping it cancels anything still running and unregisters it
+pub struct Operation {
+    registry: Arc<OperationRegistry>,
+    id: String,
+    token: CancelToken,
+}
+
+impl Operation {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Token to pass to the operation's work
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        self.token.cancel();
+        self.registry.running.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id);
+    }
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an operation under the client's ID, or a generated one
+    ///
+    /// `summary` describes its main parameters for `/api/operations`.
+    pub fn start(
+        self: &Arc<Self>,
+        id: Option<&str>,
+        kind: &str,
+        target: &str,
+        summary: String,
+    ) -> Result<Operation, OperationError> {
+        let id = match id {
+            Some(id) if !is_operation_id(id) => return Err(OperationError::InvalidId(id.to_string())),
+            Some(id) => id.to_string(),
+            None => uuid::Uuid::new_v4().simple().to_string(),
+        };
+
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if running.contains_key(&id) {
+            return Err(OperationError::Duplicate(id));
+        }
+        let token = CancelToken::default();
+        let info = OperationInfo {
+            operation_id: id.clone(),
+            kind: kind.to_string(),
+            target: target.to_string(),
+            summary,
+            started: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            cancelled: false,
+            progress: token.progress(),
+        };
+        running.insert(id.clone(), Entry { info, token: token.clone() });
+        Ok(Operation {
+            registry: self.clone(),
+            id,
+            token,
+        })
+    }
+
+    /// Asks a running operation to stop; None when no operation has this ID
+    ///
+    /// The operation stops at its next check, keeping whatever it completed.
+    pub fn cancel(&self, id: &str) -> Option<OperationInfo> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = running.get_mut(id)?;
+        entry.token.cancel();
+        entry.info.cancelled = true;
+        info!(target: "audit", "Cancelled {} operation {} on {}", entry.info.kind, id, entry.info.target);
+        Some(entry.snapshot())
+    }
+
+    /// Operations in progress, oldest first
+    pub fn list(&self) -> Vec<OperationInfo> {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let mut operations: Vec<OperationInfo> = running.values().map(Entry::snapshot).collect();
+        operations.sort_by(|a, b| a.started.cmp(&b.started).then_with(|| a.operation_id.cmp(&b.operation_id)));
+        operations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_operation() {
+        let registry = Arc::new(OperationRegistry::new());
+        let start = |id: &str, target: &str| registry.start(Some(id), "copy", target, format!("{} -> /tmp/c", target));
+        let operation = start("copy-1", "/tmp/a").unwrap();
+        let token = operation.token();
+        assert!(token.check().is_ok());
+        assert!(matches!(start("copy-1", "/tmp/b"), Err(OperationError::Duplicate(_))));
+        assert!(matches!(start("../x", "/tmp/b"), Err(OperationError::InvalidId(_))));
+
+        // Entries checked while planning count until the total is known, then progress restarts
+        let listed = registry.list();
+        assert_eq!((listed.len(), listed[0].progress.processed, listed[0].progress.total), (1, 1, None));
+        token.set_total(3);
+        token.check().unwrap();
+        let progress = registry.list()[0].progress.clone();
+        assert_eq!((progress.processed, progress.total), (1, Some(3)));
+
+        assert!(registry.cancel("copy-1").unwrap().cancelled);
+        assert!(is_cancellation(&token.check().unwrap_err()));
+
+        // Finished operations can no longer be cancelled, and their IDs are free again
+        drop(operation);
+        assert!(registry.cancel("copy-1").is_none());
+        assert!(registry.list().is_empty());
+        assert!(start("copy-1", "/tmp/a").is_ok());
+    }
+}
@@ -0,0 +1,330 @@
+use std::path::{Path, PathBuf};
+
+use super::resolve_command;
+
+/// Terminals that run a command given after their "execute" option, for `Terminal=true` entries
+const TERMINALS: &[(&str, &str)] = &[
+    ("x-terminal-emulator", "-e"),
+    ("gnome-terminal", "--"),
+    ("konsole", "-e"),
+    ("xfce4-terminal", "-x"),
+    ("xterm", "-e"),
+];
+
+/// The launch-relevant keys of a freedesktop `.desktop` application entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesktopEntry {
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub exec: String,
+    pub icon: Option<String>,
+    /// Working directory from the `Path` key
+    pub working_dir: Option<String>,
+    pub terminal: bool,
+}
+
+/// Whether desktop entries are how this platform registers applications
+pub fn desktop_entries_supported() -> bool {
+    cfg!(all(unix, not(target_os = "macos")))
+}
+
+/// Whether a path names a `.desktop` file
+pub fn is_desktop_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "desktop")
+}
+
+/// Whether an application name is a desktop ID, such as `org.gnome.gedit.desktop`, rather than a path
+pub fn is_desktop_id(application: &str) -> bool {
+    is_desktop_file(Path::new(application)) && !application.contains('/')
+}
+
+/// `applications` directories under `$XDG_DATA_HOME` and `$XDG_DATA_DIRS`, most preferred first
+pub fn application_dirs() -> Vec<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local/share")));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+/// Finds the file for a desktop ID
+///
+/// Per the spec, `-` in an ID may stand for a subdirectory, so
+/// `kde-konsole.desktop` also matches `applications/kde/konsole.desktop`.
+pub fn find_desktop_entry(id: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let dashes = id.matches('-').count();
+    let candidates: Vec<String> = (0..=dashes).map(|n| id.replacen('-', "/", n)).collect();
+    dirs.iter()
+        .flat_map(|dir| candidates.iter().map(move |candidate| dir.join(candidate)))
+        .find(|path| path.is_file())
+}
+
+/// Parses the `[Desktop Entry]` group of an application entry
+pub fn parse_desktop_entry(content: &str, path: &Path) -> Result<DesktopEntry, String> {
+    let mut in_entry = false;
+    let mut kind = None;
+    let mut entry = DesktopEntry {
+        path: path.to_path_buf(),
+        name: None,
+        exec: String::new(),
+        icon: None,
+        working_dir: None,
+        terminal: false,
+    };
+    let mut hidden = false;
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry || line.starts_with('#') {
+            continue;
+        }
+        // Localized keys such as `Name[de]` are not needed to launch
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = unescape_value(value.trim());
+        match key.trim() {
+            "Type" => kind = Some(value),
+            "Name" => entry.name = Some(value),
+            "Exec" => entry.exec = value,
+            "Icon" => entry.icon = Some(value),
+            "Path" => entry.working_dir = Some(value).filter(|dir| !dir.is_empty()),
+            "Terminal" => entry.terminal = value == "true",
+            "Hidden" => hidden = value == "true",
+            _ => {}
+        }
+    }
+
+    if hidden {
+        return Err("Desktop entry is hidden".to_string());
+    }
+    if kind.as_deref() != Some("Application") {
+        return Err("Desktop entry is not an application".to_string());
+    }
+    if entry.exec.is_empty() {
+        return Err("Desktop entry has no Exec key".to_string());
+    }
+    Ok(entry)
+}
+
+impl DesktopEntry {
+    /// The program `Exec` runs, before any terminal is wrapped around it
+    pub fn program(&self) -> Result<String, String> {
+        split_exec(&self.exec)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Desktop entry has an empty Exec key".to_string())
+    }
+
+    /// Builds the command line from `Exec`, substituting files and URLs for its field codes
+    ///
+    /// `%f`/`%u` take a single file or URL and `%F`/`%U` take the list; an
+    /// entry without file codes is launched without them, as desktops do.
+    pub fn command_line(&self, files: &[String]) -> Result<Vec<String>, String> {
+        let tokens = split_exec(&self.exec)?;
+        let takes_list = tokens.iter().any(|t| t == "%F" || t == "%U");
+        if files.len() > 1 && !takes_list {
+            return Err("Desktop entry opens a single file at a time".to_string());
+        }
+        let as_paths: Vec<String> = files.iter().filter_map(|file| local_path(file)).collect();
+
+        let mut argv = Vec::new();
+        for token in tokens {
+            match token.as_str() {
+                "%F" => argv.extend(as_paths.iter().cloned()),
+                "%U" => argv.extend(files.iter().cloned()),
+                "%i" => {
+                    if let Some(icon) = &self.icon {
+                        argv.push("--icon".to_string());
+                        argv.push(icon.clone());
+                    }
+                }
+                // A lone single-file code disappears when there is nothing to open
+                "%f" if as_paths.is_empty() => {}
+                "%u" if files.is_empty() => {}
+                _ => argv.push(self.expand_codes(&token, as_paths.first(), files.first())),
+            }
+        }
+
+        if argv.is_empty() {
+            return Err("Desktop entry has an empty Exec key".to_string());
+        }
+        if self.terminal {
+            let (terminal, flag) = TERMINALS
+                .iter()
+                .find(|(terminal, _)| resolve_command(terminal).is_some())
+                .ok_or("Desktop entry needs a terminal and none was found")?;
+            argv.splice(0..0, [terminal.to_string(), flag.to_string()]);
+        }
+        Ok(argv)
+    }
+
+    /// Expands field codes inside an argument; list and deprecated codes are dropped
+    fn expand_codes(&self, token: &str, file: Option<&String>, url: Option<&String>) -> String {
+        let mut expanded = String::new();
+        let mut chars = token.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => expanded.push('%'),
+                Some('f') => expanded.push_str(file.map_or("", String::as_str)),
+                Some('u') => expanded.push_str(url.map_or("", String::as_str)),
+                Some('c') => expanded.push_str(self.name.as_deref().unwrap_or_default()),
+                Some('k') => expanded.push_str(&super::display_path(&self.path)),
+                _ => {}
+            }
+        }
+        expanded
+    }
+}
+
+/// Undoes the value escapes of the desktop file format (`\s`, `\n`, `\t`, `\r`, `\\`)
+fn unescape_value(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits an `Exec` value into arguments, honoring double quotes and their backslash escapes
+fn split_exec(exec: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '`' | '$' | '\\')) => current.push(escaped),
+                            Some(other) => {
+                                current.push('\\');
+                                current.push(other);
+                            }
+                            None => return Err("Exec key ends inside an escape".to_string()),
+                        },
+                        Some(other) => current.push(other),
+                        None => return Err("Exec key has an unterminated quote".to_string()),
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_token {
+                    args.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                in_token = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_token {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// The local path for a plain path or `file:` URL; other URLs have none
+fn local_path(file: &str) -> Option<String> {
+    match url::Url::parse(file) {
+        Ok(url) if url.scheme() == "file" => url.to_file_path().ok().map(|path| super::display_path(&path)),
+        Ok(url) if url.scheme().len() > 1 => None,
+        _ => Some(file.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str = "[Desktop Entry]\n\
+        Type=Application\n\
+        Name=Text Editor\n\
+        Name[de]=Texteditor\n\
+        Icon=org.gnome.TextEditor\n\
+        Exec=gnome-text-editor --title \"%c \\\\$HOME\" %i %U\n\
+        Path=/work\n\
+        \n\
+        [Desktop Action new-window]\n\
+        Exec=gnome-text-editor --new-window\n";
+
+    #[test]
+    fn test_desktop_entry_command_line() {
+        let entry = parse_desktop_entry(ENTRY, Path::new("/usr/share/applications/org.gnome.TextEditor.desktop")).unwrap();
+        assert_eq!(entry.name.as_deref(), Some("Text Editor"));
+        assert_eq!(entry.working_dir.as_deref(), Some("/work"));
+
+        let files = vec!["/work/a.txt".to_string(), "file:///work/b%20c.txt".to_string()];
+        assert_eq!(
+            entry.command_line(&files).unwrap(),
+            [
+                "gnome-text-editor", "--title", "Text Editor $HOME", "--icon", "org.gnome.TextEditor",
+                "/work/a.txt", "file:///work/b%20c.txt",
+            ]
+        );
+
+        let single = DesktopEntry { exec: "viewer --file=%f %u".to_string(), ..entry.clone() };
+        assert_eq!(
+            single.command_line(&["file:///work/b%20c.txt".to_string()]).unwrap(),
+            ["viewer", "--file=/work/b c.txt", "file:///work/b%20c.txt"]
+        );
+        assert_eq!(single.command_line(&[]).unwrap(), ["viewer", "--file="]);
+        assert!(single.command_line(&files).is_err());
+        assert_eq!(entry.program().unwrap(), "gnome-text-editor");
+
+        assert!(parse_desktop_entry("[Desktop Entry]\nType=Link\nURL=https://docs.rs\n", &entry.path).is_err());
+        assert!(parse_desktop_entry("[Desktop Entry]\nType=Application\nExec=x\nHidden=true\n", &entry.path).is_err());
+        assert!(split_exec("app \"unterminated").is_err());
+    }
+
+    #[test]
+    fn test_find_desktop_entry() {
+        let dir = std::env::temp_dir().join(format!("exex-applications-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("kde")).unwrap();
+        std::fs::write(dir.join("kde").join("konsole.desktop"), "").unwrap();
+        std::fs::write(dir.join("org.gnome.gedit.desktop"), "").unwrap();
+
+        let dirs = vec![dir.join("missing"), dir.clone()];
+        assert!(is_desktop_id("org.gnome.gedit.desktop"));
+        assert!(!is_desktop_id("/usr/share/applications/org.gnome.gedit.desktop"));
+        assert_eq!(find_desktop_entry("org.gnome.gedit.desktop", &dirs), Some(dir.join("org.gnome.gedit.desktop")));
+        assert_eq!(find_desktop_entry("kde-konsole.desktop", &dirs), Some(dir.join("kde/konsole.desktop")));
+        assert_eq!(find_desktop_entry("firefox.desktop", &dirs), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
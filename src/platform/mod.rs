@@ -0,0 +1,1021 @@
+pub mod desktop_entry;
+pub mod owner;
+pub mod power;
+pub mod process_tree;
+pub mod registry;
+pub mod shortcut;
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{DirEntry, FileType, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::models::{EnvOptions, ProcessPriority, ShellKind, SpecialFileType};
+use crate::security::secrets::SECRET_ENV_PREFIX;
+
+/// Windows MAX_PATH, including the terminating NUL
+#[cfg(any(windows, test))]
+const MAX_PATH: usize = 260;
+
+/// Windows FILE_ATTRIBUTE_HIDDEN
+#[cfg(windows)]
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+/// Windows FILE_ATTRIBUTE_SYSTEM
+#[cfg(windows)]
+const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+
+/// Windows FILE_ATTRIBUTE_SPARSE_FILE
+#[cfg(windows)]
+const FILE_ATTRIBUTE_SPARSE_FILE: u32 = 0x200;
+
+/// Variables a clean environment keeps so programs can still find tools, files and the desktop
+#[cfg(windows)]
+const ESSENTIAL_ENV_VARS: &[&str] = &[
+    "PATH", "PATHEXT", "SYSTEMROOT", "SYSTEMDRIVE", "WINDIR", "COMSPEC", "TEMP", "TMP",
+    "USERPROFILE", "USERNAME", "HOMEDRIVE", "HOMEPATH", "APPDATA", "LOCALAPPDATA",
+    "PROGRAMDATA", "PROGRAMFILES", "PROGRAMFILES(X86)", "NUMBER_OF_PROCESSORS",
+    "PROCESSOR_ARCHITECTURE", "OS",
+];
+
+/// Variables a clean environment keeps so programs can still find tools, files and the desktop
+#[cfg(not(windows))]
+const ESSENTIAL_ENV_VARS: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "TMPDIR", "LANG", "LC_ALL", "LC_CTYPE",
+    "DISPLAY", "WAYLAND_DISPLAY", "XDG_RUNTIME_DIR", "DBUS_SESSION_BUS_ADDRESS",
+];
+
+/// Windows BELOW_NORMAL_PRIORITY_CLASS process creation flag
+#[cfg(windows)]
+const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x4000;
+
+/// Nice value for low-priority processes on Unix
+#[cfg(unix)]
+const LOW_PRIORITY_NICE: libc::c_int = 10;
+
+/// ioprio_set target selector for a single process
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+/// Best-effort IO class (2) at its lowest level (7), encoded as `class << 13 | level`
+#[cfg(target_os = "linux")]
+const IOPRIO_BEST_EFFORT_LOWEST: libc::c_int = (2 << 13) | 7;
+
+/// Visibility-related attributes of a directory entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntryAttributes {
+    pub hidden: bool,
+    pub system: bool,
+    /// Raw Windows file attribute bits
+    pub raw: Option<u32>,
+}
+
+/// Determines whether an entry is hidden or a system file on this platform
+///
+/// Dot-files count as hidden everywhere; on Windows the HIDDEN and SYSTEM
+/// attributes are honored as well.
+pub fn entry_attributes(file_name: &str, metadata: &Metadata) -> EntryAttributes {
+    let dot_hidden = file_name.starts_with('.');
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        let raw = metadata.file_attributes();
+        EntryAttributes {
+            hidden: dot_hidden || raw & FILE_ATTRIBUTE_HIDDEN != 0,
+            system: raw & FILE_ATTRIBUTE_SYSTEM != 0,
+            raw: Some(raw),
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = metadata;
+        EntryAttributes {
+            hidden: dot_hidden,
+            system: false,
+            raw: None,
+        }
+    }
+}
+
+/// Attributes of a directory entry that the listing itself provides
+///
+/// Windows returns attributes with each listed entry; elsewhere only the
+/// name is consulted, so no per-entry `stat` is needed.
+pub fn listed_attributes(entry: &DirEntry, file_name: &str) -> io::Result<EntryAttributes> {
+    #[cfg(windows)]
+    {
+        Ok(entry_attributes(file_name, &entry.metadata()?))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = entry;
+        Ok(EntryAttributes {
+            hidden: file_name.starts_with('.'),
+            system: false,
+            raw: None,
+        })
+    }
+}
+
+impl std::fmt::Display for SpecialFileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SpecialFileType::Fifo => "FIFO",
+            SpecialFileType::Socket => "socket",
+            SpecialFileType::BlockDevice => "block device",
+            SpecialFileType::CharDevice => "character device",
+        })
+    }
+}
+
+/// Classifies FIFOs, sockets and device nodes; None for everything else
+///
+/// Windows has no such entries in the filesystem namespace.
+pub fn special_file_type(file_type: &FileType) -> Option<SpecialFileType> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_fifo() {
+            Some(SpecialFileType::Fifo)
+        } else if file_type.is_socket() {
+            Some(SpecialFileType::Socket)
+        } else if file_type.is_block_device() {
+            Some(SpecialFileType::BlockDevice)
+        } else if file_type.is_char_device() {
+            Some(SpecialFileType::CharDevice)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = file_type;
+        None
+    }
+}
+
+/// The special file at `path`, following symlinks; None for anything that can be opened as a file
+pub fn special_file_at(path: &Path) -> Option<SpecialFileType> {
+    special_file_type(&std::fs::metadata(path).ok()?.file_type())
+}
+
+/// Identity of an open file: device and inode on Unix, volume serial number and file index on Windows
+///
+/// Two handles with the same ID refer to the same file, which tells a file
+/// replaced at its path, as by log rotation, from one that was only written to.
+pub fn file_id(file: &std::fs::File) -> io::Result<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = file.metadata()?;
+        Ok((metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::Storage::FileSystem::{GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION};
+
+        // SAFETY: the handle stays open for the call, and the structure is plain data the call fills in
+        let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+        if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let index = (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow);
+        Ok((u64::from(info.dwVolumeSerialNumber), index))
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = file;
+        Err(io::Error::new(io::ErrorKind::Unsupported, "file IDs are not available on this platform"))
+    }
+}
+
+/// Whether a regular file is stored sparsely; None for other entries
+///
+/// Windows reports the sparse attribute. Elsewhere a file counts as sparse
+/// when fewer blocks are allocated than its length needs, which also catches
+/// files the filesystem stores compressed.
+pub fn is_sparse(metadata: &Metadata) -> Option<bool> {
+    if !metadata.is_file() {
+        return None;
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        Some(metadata.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0)
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // st_blocks counts 512-byte units whatever the filesystem's block size
+        Some(metadata.blocks().saturating_mul(512) < metadata.len())
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        None
+    }
+}
+
+/// Converts a path to Windows extended-length (`\\?\`) form when it exceeds MAX_PATH
+///
+/// Shorter and relative paths, and all paths on other platforms, are returned unchanged.
+pub fn extended_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(extended) = path.to_str().and_then(to_extended_length) {
+        return PathBuf::from(extended);
+    }
+
+    path.to_path_buf()
+}
+
+/// Strips the extended-length prefix so paths shown to clients look conventional
+pub fn display_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = path.strip_prefix(r"\\?\") {
+        local.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Free space available to this process on the volume that holds, or would hold, `path`
+///
+/// Walks up to the nearest existing ancestor so targets whose parent
+/// directories have not been created yet are measured on the right volume.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .unwrap_or_else(|| Path::new("."));
+    fs2::available_space(existing)
+}
+
+/// Gives a file or directory EXEX created exact permissions, whatever the daemon's umask
+///
+/// Windows has no mode bits; new files there inherit their directory's ACL.
+pub fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+/// Creates a directory and its missing parents, giving each directory it creates `mode`
+pub fn create_dir_all_with_mode(path: &Path, mode: u32) -> io::Result<()> {
+    let missing: Vec<&Path> = path
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .collect();
+    std::fs::create_dir_all(path)?;
+    for dir in missing.into_iter().rev() {
+        set_mode(dir, mode)?;
+    }
+    Ok(())
+}
+
+/// Where [`write_with_mode`] puts data in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// Replace the whole content
+    Replace,
+    /// After the current end
+    Append,
+    /// Over the bytes from an offset on, which must not lie past the end
+    At(u64),
+    /// As the content of a file that must not exist yet
+    CreateNew,
+}
+
+/// Writes a file, creating it with `mode` or, when `force_mode` is set, also changing an existing file's mode
+///
+/// A new file is opened with `mode` already, so it is never more open than
+/// requested while being written; the umask is corrected for afterwards.
+/// Returns the offset the data was written at.
+pub fn write_with_mode(
+    path: &Path,
+    data: &[u8],
+    mode: u32,
+    force_mode: bool,
+    placement: Placement,
+    sync: bool,
+) -> io::Result<u64> {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let existed = path.exists();
+    let mut options = std::fs::OpenOptions::new();
+    match placement {
+        Placement::Replace => options.write(true).create(true).truncate(true),
+        Placement::Append => options.append(true).create(true),
+        Placement::At(_) => options.write(true).create(true),
+        Placement::CreateNew => options.write(true).create_new(true),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    let mut file = options.open(path)?;
+    if !existed || force_mode {
+        set_mode(path, mode)?;
+    }
+    let start = match placement {
+        Placement::Append => file.seek(SeekFrom::End(0))?,
+        Placement::At(offset) => {
+            let len = file.metadata()?.len();
+            if offset > len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("offset {} is past the end of the file ({} bytes)", offset, len),
+                ));
+            }
+            file.seek(SeekFrom::Start(offset))?
+        }
+        Placement::Replace | Placement::CreateNew => 0,
+    };
+    file.write_all(data)?;
+    if sync {
+        file.sync_all()?;
+    }
+    Ok(start)
+}
+
+/// Opens a file for reading without updating its access time where the platform allows it
+///
+/// Linux only honours `O_NOATIME` for the file's owner (or with
+/// `CAP_FOWNER`), so other files are opened normally. Elsewhere this is a
+/// plain open; the mount's atime policy applies.
+pub fn open_for_read(path: &Path) -> io::Result<std::fs::File> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        match std::fs::OpenOptions::new().read(true).custom_flags(libc::O_NOATIME).open(path) {
+            Err(e) if e.raw_os_error() == Some(libc::EPERM) => {}
+            result => return result,
+        }
+    }
+    std::fs::File::open(path)
+}
+
+/// Reads a whole file, see [`open_for_read`]
+pub fn read_bytes(path: &Path) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut file = open_for_read(path)?;
+    let mut data = Vec::with_capacity(file.metadata().map_or(0, |m| m.len() as usize));
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Terminal emulators to try, in order, for opening a shell in `dir`
+///
+/// Each entry is a program and its arguments; callers also set the working
+/// directory, for terminals that have no directory argument.
+pub fn terminal_launchers(dir: &Path) -> Vec<(&'static str, Vec<String>)> {
+    let dir = display_path(dir);
+
+    if cfg!(target_os = "windows") {
+        vec![
+            ("wt.exe", vec!["-d".to_string(), dir]),
+            ("cmd.exe", vec!["/C".to_string(), "start".to_string(), String::new(), "cmd.exe".to_string()]),
+        ]
+    } else if cfg!(target_os = "macos") {
+        vec![("open", vec!["-a".to_string(), "Terminal".to_string(), dir])]
+    } else {
+        vec![
+            ("x-terminal-emulator", vec![]),
+            ("gnome-terminal", vec![format!("--working-directory={}", dir)]),
+            ("konsole", vec!["--workdir".to_string(), dir.clone()]),
+            ("xfce4-terminal", vec![format!("--working-directory={}", dir)]),
+            ("xterm", vec![]),
+        ]
+    }
+}
+
+/// Variables that make common tools color their output even when it is not a terminal
+const FORCE_COLOR_ENV: &[(&str, &str)] = &[
+    ("FORCE_COLOR", "1"),
+    ("CLICOLOR_FORCE", "1"),
+    ("CARGO_TERM_COLOR", "always"),
+    ("PY_COLORS", "1"),
+];
+
+/// Asks a command to print color codes although its output is piped
+///
+/// No pseudo-terminal is allocated, so tools that only check whether they
+/// write to a terminal stay plain. Variables set by the request itself,
+/// `NO_COLOR` included, are left alone.
+pub fn apply_force_color(command: &mut Command, options: &EnvOptions) {
+    let is_set = |name: &str| options.env.as_ref().is_some_and(|env| env.contains_key(name));
+    if !is_set("NO_COLOR") {
+        command.env_remove("NO_COLOR");
+    }
+    for (name, value) in FORCE_COLOR_ENV {
+        if !is_set(name) {
+            command.env(name, value);
+        }
+    }
+}
+
+impl std::fmt::Display for ShellKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ShellKind::Bash => "bash",
+            ShellKind::Zsh => "zsh",
+            ShellKind::Pwsh => "pwsh",
+            ShellKind::Cmd => "cmd",
+            ShellKind::None => "none",
+        })
+    }
+}
+
+/// Command for an `/api/exec` request: `program` run directly with `args`, or a shell running a script
+///
+/// Without a requested shell, requests with `args` run directly and others
+/// through `sh -c`, or `cmd /C` on Windows. A login shell sources the user's
+/// profile first; `cmd` has none, and PowerShell only loads its profile for
+/// login shells. With a shell, `args` are quoted into the script after `program`,
+/// which PowerShell invokes with `&` since a quoted first word would be a string.
+pub fn exec_command_for(
+    shell: Option<ShellKind>,
+    login: bool,
+    program: &str,
+    args: Option<&[String]>,
+    command_line: &str,
+) -> Command {
+    if shell == Some(ShellKind::None) || (shell.is_none() && !login && args.is_some()) {
+        let mut command = Command::new(program);
+        command.args(args.unwrap_or_default());
+        return command;
+    }
+
+    let script = match args {
+        Some(args) => {
+            let words = std::iter::once(program)
+                .chain(args.iter().map(String::as_str))
+                .map(|arg| quote_for_shell(shell, arg))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if shell == Some(ShellKind::Pwsh) { format!("& {}", words) } else { words }
+        }
+        None => command_line.to_string(),
+    };
+    let login_flags = if login { vec!["-l", "-c"] } else { vec!["-c"] };
+    let (shell_program, mut shell_args) = match shell {
+        Some(ShellKind::Bash) => ("bash", login_flags),
+        Some(ShellKind::Zsh) => ("zsh", login_flags),
+        Some(ShellKind::Pwsh) if login => ("pwsh", vec!["-NoLogo", "-NonInteractive", "-Command"]),
+        Some(ShellKind::Pwsh) => ("pwsh", vec!["-NoLogo", "-NonInteractive", "-NoProfile", "-Command"]),
+        Some(ShellKind::Cmd) => ("cmd", vec!["/C"]),
+        // `none` ran directly above
+        None | Some(ShellKind::None) if cfg!(windows) => ("cmd", vec!["/C"]),
+        None | Some(ShellKind::None) => ("sh", login_flags),
+    };
+    shell_args.push(&script);
+    let mut command = Command::new(shell_program);
+    command.args(shell_args);
+    command
+}
+
+/// Quotes an argument so a shell passes it to the program unchanged
+pub fn quote_for_shell(shell: Option<ShellKind>, arg: &str) -> String {
+    let default = if cfg!(windows) { ShellKind::Cmd } else { ShellKind::Bash };
+    let plain = !arg.is_empty()
+        && arg.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | ':' | '=' | ',' | '+'));
+    match shell.unwrap_or(default) {
+        _ if plain => arg.to_string(),
+        ShellKind::Cmd => format!("\"{}\"", arg.replace('"', "\"\"")),
+        ShellKind::Pwsh => format!("'{}'", arg.replace('\'', "''")),
+        _ => format!("'{}'", arg.replace('\'', "'\\''")),
+    }
+}
+
+/// Whether a clean environment keeps a variable
+pub fn is_essential_env_var(name: &str) -> bool {
+    ESSENTIAL_ENV_VARS.contains(&name.to_ascii_uppercase().as_str())
+}
+
+/// Keeps the daemon's `EXEX_SECRET_*` overrides from reaching a child process
+///
+/// Secrets are only passed on through the variables a pipeline maps them to.
+pub fn remove_secret_env(command: &mut Command) {
+    for (name, _) in std::env::vars_os() {
+        if name.to_string_lossy().to_ascii_uppercase().starts_with(SECRET_ENV_PREFIX) {
+            command.env_remove(name);
+        }
+    }
+}
+
+/// Applies a request's environment options to a command
+///
+/// A clean environment drops everything the daemon inherited, including any
+/// tokens in it, except the platform's essential variables. Secret overrides
+/// are dropped either way.
+pub fn apply_env(command: &mut Command, options: &EnvOptions) {
+    remove_secret_env(command);
+    if options.clean_env.unwrap_or(false) {
+        command.env_clear();
+        for (name, value) in std::env::vars_os() {
+            if name.to_str().is_some_and(is_essential_env_var) {
+                command.env(name, value);
+            }
+        }
+    }
+
+    for name in options.env_remove.iter().flatten() {
+        command.env_remove(name);
+    }
+    if let Some(vars) = &options.env {
+        command.envs(vars);
+    }
+}
+
+/// Configures a command to run at the given CPU and IO priority once spawned
+///
+/// Low priority maps to nice 10 plus the lowest best-effort IO priority on
+/// Linux, nice 10 on other Unix systems, and the below-normal priority class on
+/// Windows. Children inherit it, so whole build trees stay in the background.
+pub fn apply_priority(command: &mut Command, priority: ProcessPriority) {
+    if priority == ProcessPriority::Normal {
+        return;
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: the hook only makes async-signal-safe syscalls between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                lower_current_priority();
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Lowers the calling process's priority; failures are ignored so the command still runs
+#[cfg(unix)]
+fn lower_current_priority() {
+    // SAFETY: plain syscalls on the current process with constant arguments
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, LOW_PRIORITY_NICE);
+        #[cfg(target_os = "linux")]
+        libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_BEST_EFFORT_LOWEST);
+    }
+}
+
+/// Moves the calling thread into background mode for CPU and IO scheduling
+///
+/// Used by worker threads that run maintenance such as scheduled backups.
+/// Linux lowers the thread's nice value and IO priority (both are per-thread
+/// there), macOS uses the background QoS class, and Windows enters
+/// `THREAD_MODE_BACKGROUND_BEGIN`, which also lowers disk and memory priority.
+/// Other Unix systems leave the thread alone, as `setpriority` would slow the
+/// whole process. Failures are ignored; the work still runs.
+pub fn enter_background_mode() {
+    #[cfg(target_os = "linux")]
+    lower_current_priority();
+
+    #[cfg(target_os = "macos")]
+    // SAFETY: only changes the QoS class of the calling thread
+    unsafe {
+        libc::pthread_set_qos_class_self_np(libc::qos_class_t::QOS_CLASS_BACKGROUND, 0);
+    }
+
+    #[cfg(windows)]
+    // SAFETY: GetCurrentThread returns a pseudo-handle that needs no cleanup
+    unsafe {
+        use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN};
+        SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN);
+    }
+}
+
+/// Whether a path is a macOS application bundle (a directory ending in `.app`)
+pub fn is_app_bundle(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("app")) && path.is_dir()
+}
+
+/// Program and arguments that launch a macOS app bundle through Launch Services
+///
+/// `files` are opened by the application, `args` reach it after `--args`,
+/// and `activate: false` leaves it in the background. With `wait`, `open`
+/// exits only once the application has quit.
+pub fn app_bundle_launcher(
+    bundle: &Path,
+    files: &[String],
+    args: &[String],
+    activate: bool,
+    new_instance: bool,
+    wait: bool,
+) -> (&'static str, Vec<String>) {
+    let mut launch = Vec::new();
+    if !activate {
+        launch.push("-g".to_string());
+    }
+    if new_instance {
+        launch.push("-n".to_string());
+    }
+    if wait {
+        launch.push("-W".to_string());
+    }
+    launch.push("-a".to_string());
+    launch.push(display_path(bundle));
+    launch.extend(files.iter().cloned());
+    if !args.is_empty() {
+        launch.push("--args".to_string());
+        launch.extend(args.iter().cloned());
+    }
+    ("open", launch)
+}
+
+/// Program and arguments that hand a URL to the desktop's default handler
+pub fn url_opener(url: &str) -> (&'static str, Vec<String>) {
+    if cfg!(target_os = "windows") {
+        // `cmd /C start` would interpret `&` in query strings
+        ("rundll32.exe", vec!["url.dll,FileProtocolHandler".to_string(), url.to_string()])
+    } else if cfg!(target_os = "macos") {
+        ("open", vec![url.to_string()])
+    } else {
+        ("xdg-open", vec![url.to_string()])
+    }
+}
+
+/// Expands a leading `~` in configured PATH entries to the home directory
+pub fn expand_path_entries(entries: &[String]) -> Vec<PathBuf> {
+    let home = dirs::home_dir();
+    entries
+        .iter()
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| match (entry.strip_prefix('~'), &home) {
+            (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+                home.join(rest.trim_start_matches(['/', '\\']))
+            }
+            _ => PathBuf::from(entry),
+        })
+        .collect()
+}
+
+/// The PATH children get: `extra` ahead of the daemon's own, or `None` to inherit it unchanged
+///
+/// Daemons started by systemd or launchd get a minimal PATH, without the
+/// directories where cargo, pip or nvm install tools.
+pub fn search_path(extra: &[PathBuf]) -> Option<OsString> {
+    if extra.is_empty() {
+        return None;
+    }
+    let inherited = std::env::var_os("PATH").unwrap_or_default();
+    let inherited = std::env::split_paths(&inherited).filter(|dir| !extra.contains(dir));
+    std::env::join_paths(extra.iter().cloned().chain(inherited)).ok()
+}
+
+/// Finds the executable a command name runs, searching PATH (and PATHEXT on Windows)
+pub fn resolve_command(program: &str) -> Option<PathBuf> {
+    resolve_command_in(program, std::env::var_os("PATH").as_deref())
+}
+
+/// Finds the executable a command name runs within the given search path
+pub fn resolve_command_in(program: &str, search_path: Option<&OsStr>) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return is_executable(path).then(|| path.to_path_buf());
+    }
+
+    std::env::split_paths(search_path?)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find_map(|dir| executable_candidates(&dir.join(program)).into_iter().find(|c| is_executable(c)))
+}
+
+/// Whether a path is a file the current user may execute
+pub fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+
+    #[cfg(not(unix))]
+    metadata.is_file()
+}
+
+/// File names a bare command may resolve to within one PATH directory
+fn executable_candidates(path: &Path) -> Vec<PathBuf> {
+    if cfg!(windows) && path.extension().is_none() {
+        let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+        extensions
+            .split(';')
+            .filter(|ext| !ext.is_empty())
+            .map(|ext| {
+                let mut candidate = path.as_os_str().to_owned();
+                candidate.push(ext);
+                PathBuf::from(candidate)
+            })
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    }
+}
+
+/// Rewrites an absolute Windows path as `\\?\C:\...` or `\\?\UNC\server\share\...`
+///
+/// Extended-length paths bypass Win32 normalization, so separators and
+/// `.`/`..` components are resolved here first.
+#[cfg(any(windows, test))]
+fn to_extended_length(path: &str) -> Option<String> {
+    if path.len() < MAX_PATH || path.starts_with(r"\\?\") {
+        return None;
+    }
+
+    let path = path.replace('/', "\\");
+    let bytes = path.as_bytes();
+    let (prefix, rest, root_len) = if let Some(unc) = path.strip_prefix(r"\\") {
+        (r"\\?\UNC\", unc, 2)
+    } else if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        (r"\\?\", path.as_str(), 1)
+    } else {
+        return None;
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.len() > root_len {
+                    components.pop();
+                }
+            }
+            other => components.push(other),
+        }
+    }
+
+    Some(format!("{}{}", prefix, components.join("\\")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exec_command_for() {
+        let argv = |command: &Command| {
+            std::iter::once(command.get_program())
+                .chain(command.get_args())
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+        let args = ["it's".to_string(), "x y".to_string()];
+
+        assert_eq!(argv(&exec_command_for(None, false, "node", Some(&args), "node")), ["node", "it's", "x y"]);
+        assert_eq!(argv(&exec_command_for(Some(ShellKind::None), false, "ls", None, "ls")), ["ls"]);
+        assert_eq!(
+            argv(&exec_command_for(Some(ShellKind::Bash), true, "node", Some(&args), "node")),
+            ["bash", "-l", "-c", "node 'it'\\''s' 'x y'"]
+        );
+        assert_eq!(
+            argv(&exec_command_for(Some(ShellKind::Pwsh), false, "npm", None, "npm run build")),
+            ["pwsh", "-NoLogo", "-NonInteractive", "-NoProfile", "-Command", "npm run build"]
+        );
+        assert_eq!(argv(&exec_command_for(Some(ShellKind::Cmd), false, "dir", Some(&args), "dir"))[2], "dir \"it's\" \"x y\"");
+        assert_eq!(
+            argv(&exec_command_for(Some(ShellKind::Pwsh), false, "C:\\Program Files\\nodejs\\node.exe", Some(&args), "node")),
+            ["pwsh", "-NoLogo", "-NonInteractive", "-NoProfile", "-Command", "& 'C:\\Program Files\\nodejs\\node.exe' 'it''s' 'x y'"]
+        );
+        if cfg!(unix) {
+            assert_eq!(argv(&exec_command_for(None, true, "nvm", None, "nvm use")), ["sh", "-l", "-c", "nvm use"]);
+        }
+    }
+
+    #[test]
+    fn test_dot_files_are_hidden() {
+        let metadata = std::fs::metadata(env!("CARGO_MANIFEST_DIR")).unwrap();
+
+        assert!(entry_attributes(".git", &metadata).hidden);
+        assert!(!entry_attributes("src", &metadata).hidden);
+    }
+
+    #[test]
+    fn test_to_extended_length() {
+        assert_eq!(to_extended_length(r"C:\short\path"), None);
+
+        let deep = format!(r"C:\projects\{}\node_modules\pkg", "a".repeat(260));
+        assert_eq!(
+            to_extended_length(&deep),
+            Some(format!(r"\\?\C:\projects\{}\node_modules\pkg", "a".repeat(260)))
+        );
+
+        let dotted = format!(r"C:/projects/{}/./x/../pkg", "b".repeat(260));
+        assert_eq!(
+            to_extended_length(&dotted),
+            Some(format!(r"\\?\C:\projects\{}\pkg", "b".repeat(260)))
+        );
+
+        let unc = format!(r"\\server\share\{}", "c".repeat(260));
+        assert_eq!(
+            to_extended_length(&unc),
+            Some(format!(r"\\?\UNC\server\share\{}", "c".repeat(260)))
+        );
+
+        let relative = format!(r"relative\{}", "d".repeat(260));
+        assert_eq!(to_extended_length(&relative), None);
+    }
+
+    #[test]
+    fn test_display_path() {
+        assert_eq!(display_path(Path::new(r"\\?\C:\dir\file")), r"C:\dir\file");
+        assert_eq!(display_path(Path::new(r"\\?\UNC\server\share")), r"\\server\share");
+        assert_eq!(display_path(Path::new("/home/user")), "/home/user");
+    }
+
+    #[test]
+    fn test_available_space_for_missing_directories() {
+        let missing = std::env::temp_dir().join("exex-missing").join("nested").join("file.txt");
+        assert!(available_space(&missing).is_ok());
+    }
+
+    #[test]
+    fn test_terminal_launchers() {
+        let launchers = terminal_launchers(Path::new("/work/project"));
+        assert!(!launchers.is_empty());
+        if cfg!(target_os = "linux") {
+            assert!(launchers.iter().any(|(_, args)| args.iter().any(|a| a.contains("/work/project"))));
+        }
+    }
+
+    #[test]
+    fn test_app_bundle_launcher() {
+        let files = vec!["/work/report.txt".to_string()];
+        let (program, args) = app_bundle_launcher(
+            Path::new("/Applications/TextEdit.app"),
+            &files,
+            &["--safe".to_string()],
+            false,
+            true,
+            true,
+        );
+        assert_eq!(program, "open");
+        assert_eq!(
+            args,
+            ["-g", "-n", "-W", "-a", "/Applications/TextEdit.app", "/work/report.txt", "--args", "--safe"]
+        );
+
+        let (_, args) = app_bundle_launcher(Path::new("/Applications/Safari.app"), &[], &[], true, false, false);
+        assert_eq!(args, ["-a", "/Applications/Safari.app"]);
+
+        let bundle = std::env::temp_dir().join(format!("exex-{}.app", uuid::Uuid::new_v4().simple()));
+        assert!(!is_app_bundle(&bundle));
+        std::fs::create_dir_all(&bundle).unwrap();
+        assert!(is_app_bundle(&bundle));
+        std::fs::remove_dir_all(&bundle).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_low_priority_is_applied() {
+        // `nice` without arguments prints the niceness it runs at
+        let niceness = |priority| {
+            let mut command = Command::new("sh");
+            command.args(["-c", "nice"]);
+            apply_priority(&mut command, priority);
+            let output = command.output().unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().parse::<i32>().unwrap()
+        };
+
+        assert!(niceness(ProcessPriority::Low) >= LOW_PRIORITY_NICE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_env() {
+        let run = |options: &EnvOptions| {
+            let mut command = Command::new("sh");
+            command.args(["-c", "echo \"$HOME|$EXEX_TEST_TOKEN|$EXEX_TEST_INJECTED\""]);
+            command.env("EXEX_TEST_TOKEN", "secret");
+            apply_env(&mut command, options);
+            let output = command.output().unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let home = std::env::var("HOME").unwrap_or_default();
+
+        assert_eq!(run(&EnvOptions::default()), format!("{}|secret|", home));
+        let injected = EnvOptions {
+            env_remove: Some(vec!["EXEX_TEST_TOKEN".to_string()]),
+            env: Some([("EXEX_TEST_INJECTED".to_string(), "1".to_string())].into()),
+            ..EnvOptions::default()
+        };
+        assert_eq!(run(&injected), format!("{}||1", home));
+
+        // Variables set on the command before a clean start are dropped too
+        let clean = EnvOptions {
+            clean_env: Some(true),
+            ..EnvOptions::default()
+        };
+        assert_eq!(run(&clean), format!("{}||", home));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_overrides_not_inherited() {
+        // SAFETY: no other test reads or writes this variable
+        unsafe { std::env::set_var("EXEX_SECRET_PLATFORM_TEST", "hunter2") };
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo \"[$EXEX_SECRET_PLATFORM_TEST]\""]);
+        apply_env(&mut command, &EnvOptions::default());
+        let output = command.output().unwrap();
+        unsafe { std::env::remove_var("EXEX_SECRET_PLATFORM_TEST") };
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "[]");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_and_sparse_files() {
+        let dir = std::env::temp_dir().join(format!("exex-special-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (fifo, dense, sparse) = (dir.join("pipe"), dir.join("dense"), dir.join("sparse"));
+        let fifo_name = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        // SAFETY: the path is NUL-terminated
+        assert_eq!(unsafe { libc::mkfifo(fifo_name.as_ptr(), 0o600) }, 0);
+        std::fs::write(&dense, vec![1u8; 64 * 1024]).unwrap();
+        std::fs::File::create(&sparse).unwrap().set_len(64 * 1024 * 1024).unwrap();
+
+        assert_eq!(special_file_at(&fifo), Some(SpecialFileType::Fifo));
+        assert_eq!(special_file_at(Path::new("/dev/null")), Some(SpecialFileType::CharDevice));
+        assert_eq!(special_file_at(&dense), None);
+        assert_eq!(special_file_at(&dir), None);
+
+        assert_eq!(is_sparse(&std::fs::metadata(&dense).unwrap()), Some(false));
+        assert_eq!(is_sparse(&std::fs::metadata(&sparse).unwrap()), Some(true));
+        assert_eq!(is_sparse(&std::fs::metadata(&dir).unwrap()), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_command() {
+        let shell = if cfg!(windows) { "cmd" } else { "sh" };
+        let resolved = resolve_command(shell).unwrap();
+        assert!(resolved.is_absolute());
+        assert!(is_executable(&resolved));
+        assert_eq!(resolve_command(resolved.to_str().unwrap()), Some(resolved));
+
+        assert!(resolve_command("exex-no-such-command").is_none());
+        assert!(!is_executable(&std::env::temp_dir()));
+    }
+
+    #[test]
+    fn test_search_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join(if cfg!(windows) { "exex-tool.exe" } else { "exex-tool" });
+        std::fs::write(&tool, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        assert!(search_path(&[]).is_none());
+        let path = search_path(&[dir.path().to_path_buf()]).unwrap();
+        assert_eq!(std::env::split_paths(&path).next().as_deref(), Some(dir.path()));
+        assert_eq!(resolve_command_in("exex-tool", Some(&path)), Some(tool));
+        assert!(resolve_command("exex-tool").is_none());
+
+        if let Some(home) = dirs::home_dir() {
+            let entries = ["~/.cargo/bin".to_string(), "~".to_string(), "".to_string(), "/opt/~bin".to_string()];
+            assert_eq!(
+                expand_path_entries(&entries),
+                vec![home.join(".cargo/bin"), home.clone(), PathBuf::from("/opt/~bin")]
+            );
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::Path;
+
+/// Who owns a file, as far as the platform reports it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ownership {
+    /// Owning user and group IDs on Unix
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// User name on Unix; `DOMAIN\name` on Windows
+    pub owner: Option<String>,
+    /// Group name on Unix
+    pub group: Option<String>,
+    /// Owner security identifier on Windows, such as `S-1-5-21-...-1001`
+    pub owner_sid: Option<String>,
+}
+
+/// Resolves file owners, remembering account names for the lifetime of the lookup
+///
+/// A scan keeps one lookup so each account is resolved once, however many
+/// files it owns. Accounts that cannot be resolved keep only their ID.
+#[derive(Debug, Default)]
+pub struct OwnerLookup {
+    #[cfg(unix)]
+    users: HashMap<u32, Option<String>>,
+    #[cfg(unix)]
+    groups: HashMap<u32, Option<String>>,
+    #[cfg(windows)]
+    accounts: HashMap<String, Option<String>>,
+}
+
+impl OwnerLookup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Owner of the file at `path`, whose metadata has already been read
+    #[cfg(unix)]
+    pub fn lookup(&mut self, path: &Path, metadata: &Metadata) -> Ownership {
+        use std::os::unix::fs::MetadataExt;
+
+        let _ = path;
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        Ownership {
+            uid: Some(uid),
+            gid: Some(gid),
+            owner: self.users.entry(uid).or_insert_with(|| user_name(uid)).clone(),
+            group: self.groups.entry(gid).or_insert_with(|| group_name(gid)).clone(),
+            owner_sid: None,
+        }
+    }
+
+    /// Owner of the file at `path`; reads its security descriptor, so this costs a file open
+    #[cfg(windows)]
+    pub fn lookup(&mut self, path: &Path, metadata: &Metadata) -> Ownership {
+        let _ = metadata;
+        let Some((sid, name)) = windows::owner(path, &mut self.accounts) else {
+            return Ownership::default();
+        };
+        Ownership {
+            owner: name,
+            owner_sid: Some(sid),
+            ..Ownership::default()
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn lookup(&mut self, path: &Path, metadata: &Metadata) -> Ownership {
+        let _ = (path, metadata);
+        Ownership::default()
+    }
+}
+
+/// Signature shared by `getpwuid_r` and `getgrgid_r`
+#[cfg(unix)]
+type LookupById<E> = unsafe extern "C" fn(u32, *mut E, *mut libc::c_char, libc::size_t, *mut *mut E) -> libc::c_int;
+
+/// Runs a `get*id_r` lookup, growing the scratch buffer while it reports ERANGE
+#[cfg(unix)]
+fn account_name<E>(id: u32, lookup: LookupById<E>, name: impl Fn(&E) -> *const libc::c_char) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 1024];
+    loop {
+        // SAFETY: passwd and group are plain data that the lookup fills in
+        let mut entry: E = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        // SAFETY: every pointer is valid for the call and `buffer.len()` is the buffer's size
+        match unsafe { lookup(id, &mut entry, buffer.as_mut_ptr(), buffer.len(), &mut found) } {
+            libc::ERANGE if buffer.len() < 1 << 20 => buffer.resize(buffer.len() * 2, 0),
+            0 if !found.is_null() => {
+                // SAFETY: the name is a NUL-terminated string inside `buffer`, which is still alive
+                return Some(unsafe { std::ffi::CStr::from_ptr(name(&entry)) }.to_string_lossy().into_owned());
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> Option<String> {
+    account_name(uid, libc::getpwuid_r, |entry: &libc::passwd| entry.pw_name)
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> Option<String> {
+    account_name(gid, libc::getgrgid_r, |entry: &libc::group| entry.gr_name)
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::collections::HashMap;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr::{null, null_mut};
+    use windows_sys::Win32::Foundation::LocalFree;
+    use windows_sys::Win32::Security::Authorization::{ConvertSidToStringSidW, GetNamedSecurityInfoW, SE_FILE_OBJECT};
+    use windows_sys::Win32::Security::{LookupAccountSidW, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID};
+
+    /// Frees memory the security APIs allocated with LocalAlloc
+    struct Local(*mut core::ffi::c_void);
+    impl Drop for Local {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                // SAFETY: the pointer was allocated by the API that returned it and is freed exactly once
+                unsafe { LocalFree(self.0) };
+            }
+        }
+    }
+
+    /// The owner's string SID and, if it resolves, account name
+    pub fn owner(path: &Path, accounts: &mut HashMap<String, Option<String>>) -> Option<(String, Option<String>)> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+        let mut sid: PSID = null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = null_mut();
+        // SAFETY: `wide` is NUL-terminated; the owner SID points into `descriptor`, which is freed below
+        let code = unsafe {
+            GetNamedSecurityInfoW(
+                wide.as_ptr(),
+                SE_FILE_OBJECT,
+                OWNER_SECURITY_INFORMATION,
+                &mut sid,
+                null_mut(),
+                null_mut(),
+                null_mut(),
+                &mut descriptor,
+            )
+        };
+        let _descriptor = Local(descriptor);
+        if code != 0 || sid.is_null() {
+            return None;
+        }
+
+        let mut text = null_mut();
+        // SAFETY: `sid` is valid while `descriptor` lives; `text` receives a LocalAlloc'd string
+        if unsafe { ConvertSidToStringSidW(sid, &mut text) } == 0 {
+            return None;
+        }
+        let text = Local(text as _);
+        let string_sid = wide_to_string(text.0 as *const u16);
+
+        let name = accounts.entry(string_sid.clone()).or_insert_with(|| account_name(sid)).clone();
+        Some((string_sid, name))
+    }
+
+    fn account_name(sid: PSID) -> Option<String> {
+        let (mut name_len, mut domain_len, mut usage) = (0u32, 0u32, 0);
+        // SAFETY: null buffers ask only for the required lengths
+        unsafe { LookupAccountSidW(null(), sid, null_mut(), &mut name_len, null_mut(), &mut domain_len, &mut usage) };
+        if name_len == 0 {
+            return None;
+        }
+        let mut name = vec![0u16; name_len as usize];
+        let mut domain = vec![0u16; domain_len.max(1) as usize];
+        // SAFETY: the buffers hold the lengths reported by the first call
+        let found = unsafe {
+            LookupAccountSidW(
+                null(),
+                sid,
+                name.as_mut_ptr(),
+                &mut name_len,
+                domain.as_mut_ptr(),
+                &mut domain_len,
+                &mut usage,
+            )
+        };
+        if found == 0 {
+            return None;
+        }
+        let name = String::from_utf16_lossy(&name[..name_len as usize]);
+        let domain = String::from_utf16_lossy(&domain[..domain_len as usize]);
+        Some(if domain.is_empty() { name } else { format!("{}\\{}", domain, name) })
+    }
+
+    fn wide_to_string(text: *const u16) -> String {
+        let mut len = 0;
+        // SAFETY: `text` is NUL-terminated
+        while unsafe { *text.add(len) } != 0 {
+            len += 1;
+        }
+        // SAFETY: the `len` units before the terminator are initialized
+        String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(text, len) })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_lookup() {
+        let path = Path::new("/");
+        let metadata = std::fs::metadata(path).unwrap();
+        let mut owners = OwnerLookup::new();
+        let ownership = owners.lookup(path, &metadata);
+
+        assert_eq!(ownership.uid, Some(0));
+        assert_eq!(ownership.owner.as_deref(), Some("root"));
+        assert!(ownership.gid.is_some() && ownership.owner_sid.is_none());
+        // Names are remembered
+        assert_eq!(owners.users.len(), 1);
+        assert_eq!(owners.lookup(path, &metadata), ownership);
+    }
+}
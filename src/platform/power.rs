@@ -0,0 +1,139 @@
+use std::io;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// How long a new inhibitor process is watched for failing to take its lock
+#[cfg(not(windows))]
+const INHIBITOR_STARTUP: Duration = Duration::from_millis(250);
+
+use crate::models::PowerAction;
+
+/// Commands that lock the screen or suspend, tried in order until one succeeds
+pub fn power_commands(action: PowerAction) -> Vec<(&'static str, Vec<String>)> {
+    let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+    match action {
+        PowerAction::Lock if cfg!(target_os = "windows") => vec![("rundll32.exe", args(&["user32.dll,LockWorkStation"]))],
+        // Locks when the account requires a password after the display sleeps
+        PowerAction::Lock if cfg!(target_os = "macos") => vec![("pmset", args(&["displaysleepnow"]))],
+        PowerAction::Lock => vec![
+            ("loginctl", args(&["lock-session"])),
+            ("xdg-screensaver", args(&["lock"])),
+        ],
+        PowerAction::Sleep if cfg!(target_os = "windows") => {
+            vec![("rundll32.exe", args(&["powrprof.dll,SetSuspendState", "0,1,0"]))]
+        }
+        PowerAction::Sleep if cfg!(target_os = "macos") => vec![("pmset", args(&["sleepnow"]))],
+        PowerAction::Sleep => vec![("systemctl", args(&["suspend"]))],
+        PowerAction::KeepAwake | PowerAction::Release => Vec::new(),
+    }
+}
+
+/// Runs the first available command for a lock or sleep action
+pub fn run_power_action(action: PowerAction) -> io::Result<()> {
+    let mut last_error = io::Error::new(io::ErrorKind::Unsupported, "No command performs this action here");
+    for (program, args) in power_commands(action) {
+        match Command::new(program).args(&args).stdin(Stdio::null()).output() {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                last_error = io::Error::other(format!("{} failed: {}", program, stderr.trim()));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                last_error = io::Error::new(io::ErrorKind::NotFound, format!("{} is not available", program));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error)
+}
+
+/// Keeps the system from idling into sleep until dropped or `duration` passes
+///
+/// Unix platforms hold an inhibitor process (`systemd-inhibit`, `caffeinate`)
+/// that exits on its own at the deadline; Windows holds the execution state
+/// on a thread that clears it when released or expired.
+pub struct WakeGuard {
+    #[cfg(not(windows))]
+    child: std::process::Child,
+    /// Dropping the sender wakes the thread holding the execution state
+    #[cfg(windows)]
+    _stop: std::sync::mpsc::Sender<()>,
+    #[cfg(windows)]
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl WakeGuard {
+    #[cfg(not(windows))]
+    pub fn hold(duration: Duration, reason: &str) -> io::Result<Self> {
+        let secs = duration.as_secs().max(1).to_string();
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("caffeinate");
+            command.args(["-i", "-s", "-t", &secs]);
+            command
+        } else {
+            let mut command = Command::new("systemd-inhibit");
+            command.args([
+                "--what=idle:sleep",
+                "--who=EXEX",
+                &format!("--why={}", reason),
+                "--mode=block",
+                "sleep",
+                &secs,
+            ]);
+            command
+        };
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        // Without a session bus the inhibitor exits at once instead of holding the lock
+        std::thread::sleep(INHIBITOR_STARTUP);
+        if let Some(status) = child.try_wait()? {
+            return Err(io::Error::other(format!("the inhibitor exited early with {}", status)));
+        }
+        Ok(Self { child })
+    }
+
+    #[cfg(windows)]
+    pub fn hold(duration: Duration, _reason: &str) -> io::Result<Self> {
+        use windows_sys::Win32::System::Power::{SetThreadExecutionState, ES_CONTINUOUS, ES_SYSTEM_REQUIRED};
+
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let (ready, acquired) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new().name("exex-wake-lease".to_string()).spawn(move || {
+            // SAFETY: only changes this thread's execution state requirements
+            let previous = unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED) };
+            let _ = ready.send(previous != 0);
+            // Returns on release (the sender is dropped) or at the deadline
+            let _ = stopped.recv_timeout(duration);
+            // SAFETY: as above
+            unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+        })?;
+
+        match acquired.recv() {
+            Ok(true) => Ok(Self { _stop: stop, thread }),
+            _ => Err(io::Error::other("SetThreadExecutionState failed")),
+        }
+    }
+
+    /// Whether the guard still holds the system awake
+    pub fn is_active(&mut self) -> bool {
+        #[cfg(not(windows))]
+        return matches!(self.child.try_wait(), Ok(None));
+        #[cfg(windows)]
+        return !self.thread.is_finished();
+    }
+}
+
+impl Drop for WakeGuard {
+    fn drop(&mut self) {
+        #[cfg(not(windows))]
+        {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::io;
+use std::process::{Child, Command};
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(windows)]
+use std::os::windows::io::OwnedHandle;
+
+/// A spawned process together with every descendant it starts
+///
+/// On Unix the command leads a new process group; on Windows it is assigned to
+/// a Job Object, which descendants join automatically.
+pub struct ProcessTree {
+    #[cfg(unix)]
+    pgid: libc::pid_t,
+    #[cfg(windows)]
+    job: OwnedHandle,
+}
+
+impl ProcessTree {
+    /// Configures a command so its descendants can be terminated together
+    ///
+    /// Must be called before the command is spawned.
+    pub fn prepare(command: &mut Command) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+
+        #[cfg(not(unix))]
+        let _ = command;
+    }
+
+    /// Starts tracking a child spawned from a prepared command
+    pub fn attach(child: &Child) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            Ok(Self { pgid: child.id() as libc::pid_t })
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::{AsRawHandle, FromRawHandle};
+            use windows_sys::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW};
+
+            // SAFETY: a null name and attributes create an anonymous job with default security
+            let raw = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+            if raw.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: `raw` is a freshly created handle owned by nothing else
+            let job = unsafe { OwnedHandle::from_raw_handle(raw as _) };
+
+            // SAFETY: both handles are valid for the duration of the call
+            if unsafe { AssignProcessToJobObject(job.as_raw_handle() as _, child.as_raw_handle() as _) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { job })
+        }
+    }
+
+    /// Forcibly terminates the process and all of its descendants
+    pub fn kill(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            // SAFETY: signalling a process group has no memory-safety preconditions
+            if unsafe { libc::killpg(self.pgid, libc::SIGKILL) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::AsRawHandle;
+            use windows_sys::Win32::System::JobObjects::TerminateJobObject;
+
+            // SAFETY: the job handle is owned by `self` and still open
+            if unsafe { TerminateJobObject(self.job.as_raw_handle() as _, 1) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[derive(Default)]
+struct KillState {
+    tree: Option<ProcessTree>,
+    killed: bool,
+}
+
+/// Terminates a process tree on request, even before the process has been spawned
+///
+/// Shared between the request that may cancel or time out a job and the worker
+/// that spawns its process. A kill that arrives first makes the later `track`
+/// terminate the tree immediately.
+#[derive(Default)]
+pub struct TreeKiller {
+    state: Mutex<KillState>,
+}
+
+impl TreeKiller {
+    /// Registers a freshly spawned tree; kills it at once if a kill was already requested
+    pub fn track(&self, tree: ProcessTree) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.killed {
+            let _ = tree.kill();
+        }
+        state.tree = Some(tree);
+    }
+
+    /// Stops tracking a tree whose leader has exited, so it can never be signalled again
+    pub fn release(&self) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).tree = None;
+    }
+
+    /// Terminates the tracked tree, or the next one to be tracked
+    pub fn kill(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.killed = true;
+        if let Some(tree) = &state.tree {
+            let _ = tree.kill();
+        }
+    }
+
+    /// Whether a kill was requested
+    pub fn was_killed(&self) -> bool {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).killed
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_kill_terminates_descendants() {
+        // The shell prints its grandchild's pid and then waits on it
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "sleep 30 & echo $!; wait"])
+            .stdout(Stdio::piped());
+        ProcessTree::prepare(&mut command);
+        let mut child = command.spawn().unwrap();
+
+        let mut line = String::new();
+        std::io::BufRead::read_line(
+            &mut std::io::BufReader::new(child.stdout.take().unwrap()),
+            &mut line,
+        )
+        .unwrap();
+        let grandchild: libc::pid_t = line.trim().parse().unwrap();
+
+        let killer = TreeKiller::default();
+        killer.track(ProcessTree::attach(&child).unwrap());
+        killer.kill();
+        child.wait().unwrap();
+
+        // The orphaned sleep is reparented and reaped elsewhere; wait for it to disappear
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while is_running(grandchild) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!is_running(grandchild));
+        assert!(killer.was_killed());
+    }
+
+    /// Whether a process exists and is not a zombie awaiting its reaper
+    fn is_running(pid: libc::pid_t) -> bool {
+        // SAFETY: signal 0 only checks for existence
+        if unsafe { libc::kill(pid, 0) } != 0 {
+            return false;
+        }
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat.rsplit(") ").next().is_some_and(|rest| !rest.starts_with('Z')),
+            Err(_) => true,
+        }
+    }
+}
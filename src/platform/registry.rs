@@ -0,0 +1,271 @@
+use serde_json::Value;
+use std::fmt;
+use std::io;
+
+use crate::models::RegistryValue;
+
+/// Registry hives by full name and accepted abbreviation
+const HIVES: &[(&str, &str)] = &[
+    ("HKEY_LOCAL_MACHINE", "HKLM"),
+    ("HKEY_CURRENT_USER", "HKCU"),
+    ("HKEY_CLASSES_ROOT", "HKCR"),
+    ("HKEY_USERS", "HKU"),
+    ("HKEY_CURRENT_CONFIG", "HKCC"),
+];
+
+/// Names of the registry value types, indexed by type number
+const VALUE_KINDS: &[&str] = &[
+    "REG_NONE",
+    "REG_SZ",
+    "REG_EXPAND_SZ",
+    "REG_BINARY",
+    "REG_DWORD",
+    "REG_DWORD_BIG_ENDIAN",
+    "REG_LINK",
+    "REG_MULTI_SZ",
+    "REG_RESOURCE_LIST",
+    "REG_FULL_RESOURCE_DESCRIPTOR",
+    "REG_RESOURCE_REQUIREMENTS_LIST",
+    "REG_QWORD",
+];
+
+/// A registry key split into its hive and the path below it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryPath {
+    /// Full hive name, such as `HKEY_LOCAL_MACHINE`
+    pub hive: &'static str,
+    /// Backslash-separated path below the hive; empty for the hive itself
+    pub subkey: String,
+}
+
+impl RegistryPath {
+    /// Parses `HKLM\Software\...` or `HKEY_LOCAL_MACHINE/Software/...`
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let normalized = path.trim().replace('/', "\\");
+        let mut parts = normalized.split('\\').filter(|part| !part.is_empty());
+        let root = parts.next().ok_or("Registry key is empty")?;
+        let hive = HIVES
+            .iter()
+            .find(|(name, short)| root.eq_ignore_ascii_case(name) || root.eq_ignore_ascii_case(short))
+            .map(|(name, _)| *name)
+            .ok_or_else(|| format!("Unknown registry hive '{}'", root))?;
+
+        Ok(Self {
+            hive,
+            subkey: parts.collect::<Vec<_>>().join("\\"),
+        })
+    }
+
+    /// Whether this key is `other` or one of its descendants; key names are case-insensitive
+    pub fn is_within(&self, other: &RegistryPath) -> bool {
+        if self.hive != other.hive {
+            return false;
+        }
+        let key = self.subkey.to_lowercase();
+        let scope = other.subkey.to_lowercase();
+        scope.is_empty() || key == scope || key.starts_with(&format!("{}\\", scope))
+    }
+}
+
+impl fmt::Display for RegistryPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.subkey.is_empty() {
+            write!(f, "{}", self.hive)
+        } else {
+            write!(f, "{}\\{}", self.hive, self.subkey)
+        }
+    }
+}
+
+/// Whether a key lies under one of the keys in `security.registry_keys`
+pub fn is_key_allowed(path: &RegistryPath, allowed: &[String]) -> bool {
+    allowed
+        .iter()
+        .filter_map(|key| RegistryPath::parse(key).ok())
+        .any(|scope| path.is_within(&scope))
+}
+
+/// Converts raw value data to JSON: strings, string lists, numbers, or hex for everything else
+pub fn decode_value(kind: u32, data: &[u8]) -> (String, Value) {
+    let name = VALUE_KINDS
+        .get(kind as usize)
+        .map_or_else(|| format!("REG_{}", kind), |name| name.to_string());
+
+    let value = match kind {
+        1 | 2 => Value::String(utf16_strings(data).into_iter().next().unwrap_or_default()),
+        7 => Value::from(utf16_strings(data)),
+        4 if data.len() >= 4 => Value::from(u32::from_le_bytes([data[0], data[1], data[2], data[3]])),
+        5 if data.len() >= 4 => Value::from(u32::from_be_bytes([data[0], data[1], data[2], data[3]])),
+        11 if data.len() >= 8 => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[..8]);
+            Value::from(u64::from_le_bytes(bytes))
+        }
+        _ => Value::String(hex::encode(data)),
+    };
+    (name, value)
+}
+
+/// Splits NUL-separated UTF-16LE data into strings, dropping the empty terminator entries
+fn utf16_strings(data: &[u8]) -> Vec<String> {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let mut strings: Vec<String> = units.split(|unit| *unit == 0).map(String::from_utf16_lossy).collect();
+    while strings.last().is_some_and(|s| s.is_empty()) {
+        strings.pop();
+    }
+    strings
+}
+
+/// Reads a key's subkeys and values, or only the named value
+#[cfg(all(windows, feature = "registry"))]
+pub fn read_key(path: &RegistryPath, value: Option<&str>) -> io::Result<(Vec<String>, Vec<RegistryValue>)> {
+    use std::ptr::{null, null_mut};
+    use windows_sys::Win32::System::Registry::{
+        RegCloseKey, RegEnumKeyExW, RegEnumValueW, RegOpenKeyExW, RegQueryInfoKeyW, RegQueryValueExW, HKEY,
+        HKEY_CLASSES_ROOT, HKEY_CURRENT_CONFIG, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, HKEY_USERS, KEY_READ,
+        KEY_WOW64_64KEY,
+    };
+
+    struct OpenKey(HKEY);
+    impl Drop for OpenKey {
+        fn drop(&mut self) {
+            // SAFETY: the handle was opened by RegOpenKeyExW and is closed exactly once
+            unsafe { RegCloseKey(self.0) };
+        }
+    }
+
+    let check = |code: u32| if code == 0 { Ok(()) } else { Err(io::Error::from_raw_os_error(code as i32)) };
+    let wide = |text: &str| text.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>();
+
+    let root = match path.hive {
+        "HKEY_LOCAL_MACHINE" => HKEY_LOCAL_MACHINE,
+        "HKEY_CURRENT_USER" => HKEY_CURRENT_USER,
+        "HKEY_CLASSES_ROOT" => HKEY_CLASSES_ROOT,
+        "HKEY_USERS" => HKEY_USERS,
+        _ => HKEY_CURRENT_CONFIG,
+    };
+    let subkey = wide(&path.subkey);
+    let mut handle: HKEY = null_mut();
+    // SAFETY: `subkey` is NUL-terminated and `handle` receives the opened key
+    check(unsafe { RegOpenKeyExW(root, subkey.as_ptr(), 0, KEY_READ | KEY_WOW64_64KEY, &mut handle) })?;
+    let key = OpenKey(handle);
+
+    if let Some(name) = value {
+        let value_name = wide(name);
+        let (mut kind, mut size) = (0u32, 0u32);
+        // SAFETY: a null data pointer asks only for the type and size
+        check(unsafe { RegQueryValueExW(key.0, value_name.as_ptr(), null(), &mut kind, null_mut(), &mut size) })?;
+        let mut data = vec![0u8; size as usize];
+        // SAFETY: `data` holds `size` bytes
+        check(unsafe { RegQueryValueExW(key.0, value_name.as_ptr(), null(), &mut kind, data.as_mut_ptr(), &mut size) })?;
+        data.truncate(size as usize);
+
+        let (kind, data) = decode_value(kind, &data);
+        return Ok((Vec::new(), vec![RegistryValue { name: name.to_string(), kind, data }]));
+    }
+
+    let (mut subkey_count, mut max_subkey_len, mut value_count, mut max_name_len, mut max_data_len) = (0, 0, 0, 0, 0);
+    // SAFETY: every out pointer is either null or a valid u32
+    check(unsafe {
+        RegQueryInfoKeyW(
+            key.0,
+            null_mut(),
+            null_mut(),
+            null(),
+            &mut subkey_count,
+            &mut max_subkey_len,
+            null_mut(),
+            &mut value_count,
+            &mut max_name_len,
+            &mut max_data_len,
+            null_mut(),
+            null_mut(),
+        )
+    })?;
+
+    // Entries added or resized between calls are skipped rather than failing the read
+    let mut subkeys = Vec::with_capacity(subkey_count as usize);
+    let mut name = vec![0u16; max_subkey_len as usize + 1];
+    for index in 0..subkey_count {
+        let mut len = name.len() as u32;
+        // SAFETY: `name` holds `len` UTF-16 units
+        let code = unsafe {
+            RegEnumKeyExW(key.0, index, name.as_mut_ptr(), &mut len, null(), null_mut(), null_mut(), null_mut())
+        };
+        if code == 0 {
+            subkeys.push(String::from_utf16_lossy(&name[..len as usize]));
+        }
+    }
+
+    let mut values = Vec::with_capacity(value_count as usize);
+    let mut name = vec![0u16; max_name_len as usize + 1];
+    let mut data = vec![0u8; max_data_len as usize];
+    for index in 0..value_count {
+        let (mut name_len, mut data_len, mut kind) = (name.len() as u32, data.len() as u32, 0u32);
+        // SAFETY: `name` holds `name_len` units and `data` holds `data_len` bytes
+        let code = unsafe {
+            RegEnumValueW(
+                key.0,
+                index,
+                name.as_mut_ptr(),
+                &mut name_len,
+                null(),
+                &mut kind,
+                data.as_mut_ptr(),
+                &mut data_len,
+            )
+        };
+        if code == 0 {
+            let (kind, decoded) = decode_value(kind, &data[..data_len as usize]);
+            values.push(RegistryValue {
+                name: String::from_utf16_lossy(&name[..name_len as usize]),
+                kind,
+                data: decoded,
+            });
+        }
+    }
+
+    Ok((subkeys, values))
+}
+
+/// The registry only exists on Windows, and is read only with the `registry` feature
+#[cfg(not(all(windows, feature = "registry")))]
+pub fn read_key(_path: &RegistryPath, _value: Option<&str>) -> io::Result<(Vec<String>, Vec<RegistryValue>)> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Registry access requires Windows and the registry feature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_registry_paths() {
+        let path = RegistryPath::parse("hklm/Software/Microsoft/Windows/CurrentVersion/Uninstall/Git_is1").unwrap();
+        assert_eq!(path.hive, "HKEY_LOCAL_MACHINE");
+        assert_eq!(path.to_string(), r"HKEY_LOCAL_MACHINE\Software\Microsoft\Windows\CurrentVersion\Uninstall\Git_is1");
+        assert!(RegistryPath::parse(r"HKEY_NOPE\Software").is_err());
+
+        let allowed = vec![r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall".to_string()];
+        assert!(is_key_allowed(&path, &allowed));
+        let sibling = RegistryPath::parse(r"HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\UninstallX").unwrap();
+        assert!(!is_key_allowed(&sibling, &allowed));
+        let other_hive = RegistryPath::parse(r"HKCU\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall").unwrap();
+        assert!(!is_key_allowed(&other_hive, &allowed));
+    }
+
+    #[test]
+    fn test_decode_value() {
+        let wide = |text: &str| text.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>();
+
+        assert_eq!(decode_value(1, &wide("Git\0")), ("REG_SZ".to_string(), json!("Git")));
+        assert_eq!(decode_value(7, &wide("a\0b\0\0")), ("REG_MULTI_SZ".to_string(), json!(["a", "b"])));
+        assert_eq!(decode_value(4, &7u32.to_le_bytes()), ("REG_DWORD".to_string(), json!(7)));
+        assert_eq!(decode_value(11, &(1u64 << 40).to_le_bytes()), ("REG_QWORD".to_string(), json!(1u64 << 40)));
+        assert_eq!(decode_value(3, &[0xde, 0xad]), ("REG_BINARY".to_string(), json!("dead")));
+        assert_eq!(decode_value(42, &[1]), ("REG_42".to_string(), json!("01")));
+    }
+}
@@ -0,0 +1,317 @@
+use std::io;
+use std::path::Path;
+
+use crate::models::ShortcutInfo;
+
+/// Largest .lnk file that is parsed; real shortcuts are a few kilobytes
+pub const MAX_SHORTCUT_BYTES: u64 = 1024 * 1024;
+
+/// Size of the fixed ShellLinkHeader
+const HEADER_SIZE: usize = 0x4C;
+
+/// CLSID 00021401-0000-0000-C000-000000000046 in its on-disk byte order
+const LINK_CLSID: [u8; 16] = [0x01, 0x14, 0x02, 0, 0, 0, 0, 0, 0xC0, 0, 0, 0, 0, 0, 0, 0x46];
+
+// LinkFlags
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+// LinkInfoFlags
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK: u32 = 0x2;
+
+/// EnvironmentVariableDataBlock signature, holding a target such as `%ProgramFiles%\...`
+const ENVIRONMENT_BLOCK_SIGNATURE: u32 = 0xA000_0001;
+
+/// Whether a path names a Windows shortcut
+pub fn is_shortcut(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"))
+}
+
+/// Parses a shell link (MS-SHLLINK) without needing Windows
+///
+/// The target comes from the link's LinkInfo (local or network path), or its
+/// environment-variable block when the link only stores a shell item list.
+pub fn parse_shortcut(data: &[u8]) -> io::Result<ShortcutInfo> {
+    if data.len() < HEADER_SIZE || u32_at(data, 0)? != HEADER_SIZE as u32 || data[4..20] != LINK_CLSID {
+        return Err(invalid("not a Windows shortcut"));
+    }
+    let flags = u32_at(data, 20)?;
+    let mut pos = HEADER_SIZE;
+
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        pos += 2 + u16_at(data, pos)? as usize;
+    }
+
+    let mut target = None;
+    if flags & HAS_LINK_INFO != 0 {
+        let size = u32_at(data, pos)? as usize;
+        let info = data.get(pos..pos + size).ok_or_else(|| invalid("truncated link info"))?;
+        target = link_info_target(info)?;
+        pos += size;
+    }
+
+    let unicode = flags & IS_UNICODE != 0;
+    let mut read_string = |flag: u32| -> io::Result<Option<String>> {
+        if flags & flag == 0 {
+            return Ok(None);
+        }
+        let count = u16_at(data, pos)? as usize;
+        let len = if unicode { count * 2 } else { count };
+        let bytes = data.get(pos + 2..pos + 2 + len).ok_or_else(|| invalid("truncated string data"))?;
+        pos += 2 + len;
+        Ok(Some(if unicode { utf16(bytes) } else { latin1(bytes) }))
+    };
+    let description = read_string(HAS_NAME)?;
+    let relative_path = read_string(HAS_RELATIVE_PATH)?;
+    let working_dir = read_string(HAS_WORKING_DIR)?;
+    let arguments = read_string(HAS_ARGUMENTS)?;
+    let icon_location = read_string(HAS_ICON_LOCATION)?;
+
+    if target.is_none() {
+        target = environment_target(data.get(pos..).unwrap_or_default());
+    }
+
+    Ok(ShortcutInfo {
+        target,
+        arguments,
+        working_dir: working_dir.map(|dir| expand_env(&dir)),
+        description,
+        icon_location,
+        relative_path,
+    })
+}
+
+/// Builds the target path from a LinkInfo structure
+fn link_info_target(info: &[u8]) -> io::Result<Option<String>> {
+    let header_size = u32_at(info, 4)?;
+    let info_flags = u32_at(info, 8)?;
+    let has_unicode = header_size >= 0x24;
+    let suffix = if has_unicode {
+        utf16_at(info, u32_at(info, 0x20)? as usize)?
+    } else {
+        latin1_at(info, u32_at(info, 0x18)? as usize)?
+    };
+
+    if info_flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+        let base = if has_unicode {
+            utf16_at(info, u32_at(info, 0x1C)? as usize)?
+        } else {
+            latin1_at(info, u32_at(info, 0x10)? as usize)?
+        };
+        return Ok(Some(join_windows_path(&base, &suffix)));
+    }
+
+    if info_flags & COMMON_NETWORK_RELATIVE_LINK != 0 {
+        let link = info.get(u32_at(info, 0x14)? as usize..).ok_or_else(|| invalid("truncated network link"))?;
+        let name_offset = u32_at(link, 8)? as usize;
+        let share = if name_offset > 0x14 {
+            utf16_at(link, u32_at(link, 0x14)? as usize)?
+        } else {
+            latin1_at(link, name_offset)?
+        };
+        return Ok(Some(join_windows_path(&share, &suffix)));
+    }
+
+    Ok(None)
+}
+
+/// Target stored in an EnvironmentVariableDataBlock, expanded with the daemon's environment
+fn environment_target(extra: &[u8]) -> Option<String> {
+    let mut pos = 0;
+    while let (Ok(size), Ok(signature)) = (u32_at(extra, pos), u32_at(extra, pos + 4)) {
+        let size = size as usize;
+        if size < 8 {
+            break;
+        }
+        if signature == ENVIRONMENT_BLOCK_SIGNATURE {
+            // 260 ANSI bytes followed by 260 UTF-16 units
+            let unicode = extra.get(pos + 268..pos + 788).map(utf16).filter(|s| !s.is_empty());
+            let target = unicode.or_else(|| extra.get(pos + 8..pos + 268).map(latin1))?;
+            return Some(expand_env(&target));
+        }
+        pos += size;
+    }
+    None
+}
+
+/// Expands `%NAME%` references; unknown variables are left as written
+fn expand_env(value: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else {
+            break;
+        };
+        let name = &rest[start + 1..start + 1 + len];
+        expanded.push_str(&rest[..start]);
+        match std::env::var(name) {
+            Ok(val) if !name.is_empty() => expanded.push_str(&val),
+            _ => expanded.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn join_windows_path(base: &str, suffix: &str) -> String {
+    if suffix.is_empty() || base.ends_with('\\') {
+        format!("{}{}", base, suffix)
+    } else {
+        format!("{}\\{}", base, suffix)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid shortcut: {}", message))
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<u16> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid("unexpected end of file"))
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("unexpected end of file"))
+}
+
+/// Decodes UTF-16LE up to the first NUL
+fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Decodes single-byte code page text up to the first NUL; exact for ASCII paths
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().take_while(|b| **b != 0).map(|b| *b as char).collect()
+}
+
+fn utf16_at(data: &[u8], at: usize) -> io::Result<String> {
+    data.get(at..).map(utf16).ok_or_else(|| invalid("string offset out of range"))
+}
+
+fn latin1_at(data: &[u8], at: usize) -> io::Result<String> {
+    data.get(at..).map(latin1).ok_or_else(|| invalid("string offset out of range"))
+}
+
+/// Creates or replaces a shortcut through the WScript.Shell COM object
+///
+/// Values reach PowerShell through the environment rather than the script
+/// text, so paths and arguments are never interpreted as code.
+#[cfg(windows)]
+pub fn create_shortcut(path: &str, shortcut: &ShortcutInfo) -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const SCRIPT: &str = "$ErrorActionPreference = 'Stop'; \
+        $link = (New-Object -ComObject WScript.Shell).CreateShortcut($env:EXEX_SHORTCUT_PATH); \
+        $link.TargetPath = $env:EXEX_SHORTCUT_TARGET; \
+        if ($env:EXEX_SHORTCUT_ARGUMENTS) { $link.Arguments = $env:EXEX_SHORTCUT_ARGUMENTS }; \
+        if ($env:EXEX_SHORTCUT_WORKING_DIR) { $link.WorkingDirectory = $env:EXEX_SHORTCUT_WORKING_DIR }; \
+        if ($env:EXEX_SHORTCUT_DESCRIPTION) { $link.Description = $env:EXEX_SHORTCUT_DESCRIPTION }; \
+        if ($env:EXEX_SHORTCUT_ICON) { $link.IconLocation = $env:EXEX_SHORTCUT_ICON }; \
+        $link.Save()";
+
+    let fields = [
+        ("EXEX_SHORTCUT_PATH", Some(path)),
+        ("EXEX_SHORTCUT_TARGET", shortcut.target.as_deref()),
+        ("EXEX_SHORTCUT_ARGUMENTS", shortcut.arguments.as_deref()),
+        ("EXEX_SHORTCUT_WORKING_DIR", shortcut.working_dir.as_deref()),
+        ("EXEX_SHORTCUT_DESCRIPTION", shortcut.description.as_deref()),
+        ("EXEX_SHORTCUT_ICON", shortcut.icon_location.as_deref()),
+    ];
+    let mut command = Command::new("powershell.exe");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .stdin(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW);
+    for (name, value) in fields {
+        command.env(name, value.unwrap_or_default());
+    }
+
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+/// Shortcuts can only be created on Windows
+#[cfg(not(windows))]
+pub fn create_shortcut(_path: &str, _shortcut: &ShortcutInfo) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Shortcuts can only be created on Windows"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide(text: &str) -> Vec<u8> {
+        text.encode_utf16().chain(std::iter::once(0)).flat_map(u16::to_le_bytes).collect()
+    }
+
+    /// A link with a Unicode LinkInfo local path and Unicode string data, as Explorer writes them
+    fn sample_link() -> Vec<u8> {
+        let mut link = vec![0u8; HEADER_SIZE];
+        link[0] = HEADER_SIZE as u8;
+        link[4..20].copy_from_slice(&LINK_CLSID);
+        let flags = HAS_LINK_TARGET_ID_LIST | HAS_LINK_INFO | HAS_NAME | HAS_WORKING_DIR | HAS_ARGUMENTS | IS_UNICODE;
+        link[20..24].copy_from_slice(&flags.to_le_bytes());
+
+        // An opaque shell item list that the parser skips
+        link.extend_from_slice(&4u16.to_le_bytes());
+        link.extend_from_slice(&[0xAA; 4]);
+
+        let base = wide(r"C:\Program Files\Tool");
+        let suffix = wide(r"tool.exe");
+        let base_offset = 0x24u32;
+        let suffix_offset = base_offset + base.len() as u32;
+        let size = suffix_offset + suffix.len() as u32;
+        for field in [size, 0x24, VOLUME_ID_AND_LOCAL_BASE_PATH, 0, 0, 0, 0, base_offset, suffix_offset] {
+            link.extend_from_slice(&field.to_le_bytes());
+        }
+        link.extend_from_slice(&base);
+        link.extend_from_slice(&suffix);
+
+        for text in ["Build tool", r"C:\Work", "--verbose \"a b\""] {
+            link.extend_from_slice(&(text.encode_utf16().count() as u16).to_le_bytes());
+            link.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        }
+        link.extend_from_slice(&0u32.to_le_bytes());
+        link
+    }
+
+    #[test]
+    fn test_parse_shortcut() {
+        let shortcut = parse_shortcut(&sample_link()).unwrap();
+        assert_eq!(shortcut.target.as_deref(), Some(r"C:\Program Files\Tool\tool.exe"));
+        assert_eq!(shortcut.description.as_deref(), Some("Build tool"));
+        assert_eq!(shortcut.working_dir.as_deref(), Some(r"C:\Work"));
+        assert_eq!(shortcut.arguments.as_deref(), Some("--verbose \"a b\""));
+        assert_eq!(shortcut.icon_location, None);
+
+        let mut truncated = sample_link();
+        truncated.truncate(HEADER_SIZE + 20);
+        assert!(parse_shortcut(&truncated).is_err());
+        assert!(parse_shortcut(b"not a link").is_err());
+
+        assert!(is_shortcut(Path::new(r"C:\Users\me\Desktop\Tool.LNK")));
+        assert_eq!(expand_env("%EXEX_NO_SUCH_VAR%\\x%"), "%EXEX_NO_SUCH_VAR%\\x%");
+    }
+}
@@ -0,0 +1,521 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+
+use crate::handlers::app_ops::check_url_policy;
+use crate::handlers::archive::{format_from_path, inspect_archive, ArchiveFailure};
+use crate::handlers::file_ops::count_entries;
+use crate::handlers::sync::count_changes;
+use crate::models::{
+    ArchiveRequest, Config, ContentEncoding, CopyRequest, CreateRequest, DeleteRequest, EditRequest, ExecRequest, ExtractRequest, FindRequest, HashRequest, OpenAppRequest, OpenEditorRequest, OpenTerminalRequest,
+    OpenUrlRequest, ReadRequest, RenameRequest, ScanRequest, SearchRequest, SyncRequest, TailRequest, WatchRequest, WriteRequest,
+};
+use crate::operations::CancelToken;
+use crate::platform::extended_path;
+use crate::security::SecurityManager;
+
+const USAGE: &str = "usage: exex policy test <requests.json> [--format json|junit]";
+
+/// A hypothetical request, tagged by `op` with the same fields as its endpoint
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PolicyRequest {
+    Exec(ExecRequest),
+    Read(ReadRequest),
+    Write(WriteRequest),
+    Edit(EditRequest),
+    Scan(ScanRequest),
+    Search(SearchRequest),
+    Find(FindRequest),
+    Hash(HashRequest),
+    Tail(TailRequest),
+    Watch(WatchRequest),
+    Delete(DeleteRequest),
+    Create(CreateRequest),
+    Rename(RenameRequest),
+    Copy(CopyRequest),
+    Archive(ArchiveRequest),
+    Extract(ExtractRequest),
+    Sync(SyncRequest),
+    Open(OpenAppRequest),
+    OpenTerminal(OpenTerminalRequest),
+    OpenEditor(OpenEditorRequest),
+    OpenUrl(OpenUrlRequest),
+}
+
+impl PolicyRequest {
+    /// Name of the operation as given in the `op` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            PolicyRequest::Exec(_) => "exec",
+            PolicyRequest::Read(_) => "read",
+            PolicyRequest::Write(_) => "write",
+            PolicyRequest::Edit(_) => "edit",
+            PolicyRequest::Scan(_) => "scan",
+            PolicyRequest::Search(_) => "search",
+            PolicyRequest::Find(_) => "find",
+            PolicyRequest::Hash(_) => "hash",
+            PolicyRequest::Tail(_) => "tail",
+            PolicyRequest::Watch(_) => "watch",
+            PolicyRequest::Delete(_) => "delete",
+            PolicyRequest::Create(_) => "create",
+            PolicyRequest::Rename(_) => "rename",
+            PolicyRequest::Copy(_) => "copy",
+            PolicyRequest::Archive(_) => "archive",
+            PolicyRequest::Extract(_) => "extract",
+            PolicyRequest::Sync(_) => "sync",
+            PolicyRequest::Open(_) => "open",
+            PolicyRequest::OpenTerminal(_) => "open_terminal",
+            PolicyRequest::OpenEditor(_) => "open_editor",
+            PolicyRequest::OpenUrl(_) => "open_url",
+        }
+    }
+}
+
+/// Outcome of checking a request against the policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    Deny,
+}
+
+/// One entry of a policy test file
+#[derive(Debug, Deserialize)]
+pub struct PolicyCase {
+    pub name: Option<String>,
+    /// Decision the policy is expected to reach; cases without one only report
+    pub expect: Option<Decision>,
+    #[serde(flatten)]
+    pub request: PolicyRequest,
+}
+
+/// Result of one policy test case
+#[derive(Debug, Serialize)]
+pub struct PolicyCaseResult {
+    pub index: usize,
+    pub name: String,
+    pub op: &'static str,
+    pub decision: Decision,
+    /// Why the request was denied
+    pub reason: Option<String>,
+    pub expected: Option<Decision>,
+    pub passed: bool,
+}
+
+/// Summary of a policy test run
+#[derive(Debug, Serialize)]
+pub struct PolicyReport {
+    pub total: usize,
+    pub allowed: usize,
+    pub denied: usize,
+    pub failures: usize,
+    pub results: Vec<PolicyCaseResult>,
+}
+
+/// Report formats for `exex policy test`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Junit,
+}
+
+/// Runs the `exex policy ...` subcommand and returns the process exit code
+///
+/// Exits with 0 when every case met its expectation, 1 when any did not, and 2
+/// on usage or input errors.
+pub fn run_policy_command(args: &[String], config: Config) -> i32 {
+    let (path, format) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    let cases: Vec<PolicyCase> = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+    {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("Failed to load policy tests from {}: {}", path, e);
+            return 2;
+        }
+    };
+
+    let security = SecurityManager::new(config.clone());
+    let report = run_cases(&security, &config, cases);
+
+    let output = match format {
+        ReportFormat::Json => serde_json::to_string_pretty(&report).unwrap_or_default() + "\n",
+        ReportFormat::Junit => to_junit(&report),
+    };
+    // A closed pipe (`| head`) is not an error worth panicking over
+    let _ = std::io::stdout().write_all(output.as_bytes());
+
+    if report.failures == 0 { 0 } else { 1 }
+}
+
+fn parse_args(args: &[String]) -> Result<(String, ReportFormat), String> {
+    let mut args = args.iter();
+    match args.next().map(String::as_str) {
+        Some("test") => {}
+        Some(other) => return Err(format!("unknown policy command '{}'", other)),
+        None => return Err("missing policy command".to_string()),
+    }
+
+    let mut path = None;
+    let mut format = ReportFormat::Json;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().map(String::as_str) {
+                    Some("json") => ReportFormat::Json,
+                    Some("junit") => ReportFormat::Junit,
+                    other => return Err(format!("unsupported format {:?}", other.unwrap_or_default())),
+                }
+            }
+            file if path.is_none() => path = Some(file.to_string()),
+            extra => return Err(format!("unexpected argument '{}'", extra)),
+        }
+    }
+
+    path.map(|path| (path, format)).ok_or_else(|| "missing requests file".to_string())
+}
+
+/// Evaluates every case and compares it with its expectation
+pub fn run_cases(security: &SecurityManager, config: &Config, cases: Vec<PolicyCase>) -> PolicyReport {
+    let results: Vec<PolicyCaseResult> = cases
+        .into_iter()
+        .enumerate()
+        .map(|(index, case)| {
+            let reason = evaluate(security, config, &case.request).err();
+            let decision = if reason.is_some() { Decision::Deny } else { Decision::Allow };
+            PolicyCaseResult {
+                index,
+                name: case.name.unwrap_or_else(|| format!("{} #{}", case.request.name(), index)),
+                op: case.request.name(),
+                decision,
+                reason,
+                expected: case.expect,
+                passed: case.expect.is_none_or(|expected| expected == decision),
+            }
+        })
+        .collect();
+
+    PolicyReport {
+        total: results.len(),
+        allowed: results.iter().filter(|r| r.decision == Decision::Allow).count(),
+        denied: results.iter().filter(|r| r.decision == Decision::Deny).count(),
+        failures: results.iter().filter(|r| !r.passed).count(),
+        results,
+    }
+}
+
+/// Applies the same policy checks as the request's endpoint, without performing it
+///
+/// That includes `protected_patterns` and `max_affected_entries`, so recursive
+/// operations walk their trees, and extract and sync read the archive or plan
+/// the sync to count entries. Checks the endpoints make entry by entry while
+/// the operation runs are not repeated here.
+pub fn evaluate(security: &SecurityManager, config: &Config, request: &PolicyRequest) -> Result<(), String> {
+    match request {
+        PolicyRequest::Exec(req) => {
+            if !security.is_command_allowed_in(&req.command, req.cwd.as_deref().map(Path::new)) {
+                return Err(format!("Command '{}' is not allowed by security policy", security.redact(&req.command)));
+            }
+            security.check_env(&req.environment)?;
+            security.check_shell(req.shell, req.login_shell.unwrap_or(false))?;
+            if let Some(cwd) = &req.cwd {
+                check_path(security, cwd, "directory")?;
+            }
+            Ok(())
+        }
+        PolicyRequest::Read(req) => check_path(security, &req.path, "file"),
+        PolicyRequest::Write(req) => {
+            check_writable_path(security, &req.path, "file")?;
+            check_protected(security, &req.path, req.force)?;
+            // Base64 decodes to three bytes for every four characters
+            let size = match req.encoding.unwrap_or_default() {
+                ContentEncoding::Utf8 => req.content.len(),
+                ContentEncoding::Base64 => req.content.trim().len() / 4 * 3,
+            };
+            if !security.is_file_size_allowed(size as u64) {
+                return Err("File size exceeds maximum allowed size".to_string());
+            }
+            Ok(())
+        }
+        PolicyRequest::Edit(req) => {
+            check_writable_path(security, &req.path, "file")?;
+            check_protected(security, &req.path, req.force)
+        }
+        PolicyRequest::Scan(req) => check_path(security, &req.path, "directory"),
+        PolicyRequest::Search(req) => check_path(security, &req.path, "path"),
+        PolicyRequest::Find(req) => check_path(security, &req.path, "directory"),
+        PolicyRequest::Hash(req) => check_path(security, &req.path, "file"),
+        PolicyRequest::Tail(req) => check_path(security, &req.path, "file"),
+        PolicyRequest::Watch(req) => check_path(security, &req.path, "path"),
+        PolicyRequest::Delete(req) => {
+            check_writable_path(security, &req.path, "path")?;
+            let path = extended_path(Path::new(&req.path));
+            let force = req.force.unwrap_or(false);
+            // A recursive delete also removes whatever lies below the directory
+            if req.recursive.unwrap_or(false) && path.is_dir() {
+                security.check_protected_tree(&path, force)?;
+                check_entry_count(security, &path, req.override_entry_limit)
+            } else {
+                security.check_protected(&path, force)
+            }
+        }
+        PolicyRequest::Create(req) => {
+            check_writable_path(security, &req.path, "path")?;
+            check_protected(security, &req.path, req.force)
+        }
+        PolicyRequest::Rename(req) => {
+            check_writable_path(security, &req.from_path, "source path")?;
+            check_writable_path(security, &req.to_path, "destination path")?;
+            let from_path = extended_path(Path::new(&req.from_path));
+            security.check_internal_tree(&from_path)?;
+            check_protected(security, &req.from_path, req.force)?;
+            check_protected(security, &req.to_path, req.force)
+        }
+        PolicyRequest::Copy(req) => {
+            check_path(security, &req.from_path, "source path")?;
+            check_writable_path(security, &req.to_path, "destination path")?;
+            check_protected(security, &req.to_path, req.force)?;
+            let from_path = extended_path(Path::new(&req.from_path));
+            if from_path.is_dir() {
+                check_entry_count(security, &from_path, req.override_entry_limit)?;
+            }
+            Ok(())
+        }
+        PolicyRequest::Archive(req) => {
+            for source in &req.sources {
+                check_path(security, source, "source path")?;
+            }
+            check_writable_path(security, &req.path, "archive path")?;
+            check_protected(security, &req.path, req.force)
+        }
+        PolicyRequest::Extract(req) => {
+            check_path(security, &req.path, "archive path")?;
+            check_writable_path(security, &req.destination, "destination path")?;
+            check_protected(security, &req.destination, req.force)?;
+            let path = extended_path(Path::new(&req.path));
+            let Some(format) = req.format.or_else(|| format_from_path(&path)) else {
+                return Ok(());
+            };
+            match inspect_archive(security, &path, format, &CancelToken::default()) {
+                Ok((count, _)) => check_entry_limit(security, count, req.override_entry_limit),
+                Err(ArchiveFailure::Refused(e, _)) => Err(e),
+                // Unsafe entry names and unreadable archives are request errors, not policy decisions
+                Err(ArchiveFailure::UnsafeEntry(_) | ArchiveFailure::Io(_)) => Ok(()),
+            }
+        }
+        PolicyRequest::Sync(req) => {
+            check_path(security, &req.source, "source path")?;
+            check_writable_path(security, &req.destination, "destination path")?;
+            check_protected(security, &req.destination, req.force)?;
+            match count_changes(security, req) {
+                Ok(count) => check_entry_limit(security, count, req.override_entry_limit),
+                Err(_) => Ok(()),
+            }
+        }
+        PolicyRequest::Open(req) => {
+            check_path(security, &req.application, "application")?;
+            if !security.is_command_safe(&req.application) {
+                return Err(format!("Application deemed unsafe: {}", req.application));
+            }
+            security.check_env(&req.environment)?;
+            if let Some(cwd) = &req.cwd {
+                check_path(security, cwd, "working directory")?;
+            }
+            Ok(())
+        }
+        PolicyRequest::OpenTerminal(req) => check_path(security, &req.path, "directory"),
+        PolicyRequest::OpenEditor(req) => check_path(security, &req.path, "file"),
+        PolicyRequest::OpenUrl(req) => check_url_policy(config, &req.url).map(|_| ()),
+    }
+}
+
+fn check_path(security: &SecurityManager, path: &str, what: &str) -> Result<(), String> {
+    if security.is_path_allowed(&extended_path(Path::new(path))) {
+        Ok(())
+    } else {
+        Err(format!("Access denied to {}: {}", what, path))
+    }
+}
+
+/// Like [`check_path`], also refusing changes to a workspace's policy directory
+fn check_writable_path(security: &SecurityManager, path: &str, what: &str) -> Result<(), String> {
+    check_path(security, path, what)?;
+    if security.is_workspace_policy_path(&extended_path(Path::new(path))) {
+        return Err(format!("Workspace policy files cannot be changed: {}", path));
+    }
+    Ok(())
+}
+
+/// Protected paths need `force`, and `force` needs `security.allow_protected_override`
+fn check_protected(security: &SecurityManager, path: &str, force: Option<bool>) -> Result<(), String> {
+    security.check_protected(&extended_path(Path::new(path)), force.unwrap_or(false))
+}
+
+/// Counts the entries below a directory a recursive operation would touch
+fn check_entry_count(security: &SecurityManager, root: &Path, override_limit: Option<bool>) -> Result<(), String> {
+    let Some(limit) = security.max_affected_entries() else {
+        return Ok(());
+    };
+    // The operation itself would surface a walk that fails
+    let count = count_entries(root, limit + 1).unwrap_or(0);
+    check_entry_limit(security, count, override_limit)
+}
+
+fn check_entry_limit(security: &SecurityManager, count: usize, override_limit: Option<bool>) -> Result<(), String> {
+    match security.max_affected_entries() {
+        Some(limit) if count > limit && !override_limit.unwrap_or(false) => Err(format!(
+            "Operation would affect more than {} entries; set override_entry_limit to proceed",
+            limit
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Renders a report as a JUnit XML test suite, one test case per request
+pub fn to_junit(report: &PolicyReport) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"exex-policy\" tests=\"{}\" failures=\"{}\">",
+        report.total, report.failures
+    );
+
+    for result in &report.results {
+        let _ = write!(
+            xml,
+            "  <testcase classname=\"exex.policy.{}\" name=\"{}\"",
+            result.op,
+            xml_escape(&result.name)
+        );
+        let decision = format!("{:?}", result.decision).to_lowercase();
+        let detail = result.reason.as_deref().unwrap_or("allowed");
+
+        if result.passed {
+            let _ = writeln!(xml, ">\n    <system-out>{}: {}</system-out>\n  </testcase>", decision, xml_escape(detail));
+        } else {
+            let expected = result.expected.map(|e| format!("{:?}", e).to_lowercase()).unwrap_or_default();
+            let _ = writeln!(
+                xml,
+                ">\n    <failure message=\"expected {}, got {}\">{}</failure>\n  </testcase>",
+                expected,
+                decision,
+                xml_escape(detail)
+            );
+        }
+    }
+
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use crate::models::SecurityConfig;
+
+    #[test]
+    fn test_policy_cases() {
+        let mut config = get_default_config();
+        config.security = SecurityConfig {
+            allowed_paths: vec![],
+            disallowed_paths: vec!["/etc".to_string()],
+            command_whitelist: vec!["git".to_string()],
+            ..config.security
+        };
+        let security = SecurityManager::new(config.clone());
+
+        let cases: Vec<PolicyCase> = serde_json::from_str(
+            r#"[
+                {"name": "git is allowed", "op": "exec", "command": "git", "args": ["status"], "expect": "allow"},
+                {"op": "exec", "command": "curl", "expect": "deny"},
+                {"op": "read", "path": "/etc/shadow", "expect": "deny"},
+                {"op": "open_url", "url": "https://example.com"},
+                {"name": "wrong expectation", "op": "exec", "command": "git", "env": {"LD_PRELOAD": "x"}, "expect": "allow"}
+            ]"#,
+        )
+        .unwrap();
+
+        let report = run_cases(&security, &config, cases);
+        assert_eq!(report.total, 5);
+        assert_eq!(report.allowed, 2);
+        assert_eq!(report.denied, 3);
+        assert_eq!(report.failures, 1);
+        assert_eq!(report.results[1].name, "exec #1");
+        assert!(!report.results[4].passed);
+
+        let xml = to_junit(&report);
+        assert!(xml.contains("tests=\"5\" failures=\"1\""));
+        assert!(xml.contains("<failure message=\"expected allow, got deny\">"));
+    }
+
+    #[test]
+    fn test_protected_and_entry_limits() {
+        let root = std::env::temp_dir().join(format!("exex-policy-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&root).unwrap();
+        for name in [".env", "a.txt", "b.txt"] {
+            std::fs::write(root.join(name), "x").unwrap();
+        }
+
+        let mut config = get_default_config();
+        config.security = SecurityConfig {
+            allowed_paths: vec![],
+            protected_patterns: Some(vec!["**/.env".to_string()]),
+            max_affected_entries: Some(2),
+            ..config.security
+        };
+        let security = SecurityManager::new(config.clone());
+        let path = |name: &str| root.join(name).to_string_lossy().into_owned();
+        let cases = serde_json::json!([
+            {"op": "write", "path": path(".env"), "content": "x", "expect": "deny"},
+            {"op": "write", "path": path(".env"), "content": "x", "force": true, "expect": "deny"},
+            {"op": "write", "path": path("notes.txt"), "content": "x", "expect": "allow"},
+            {"op": "delete", "path": path(""), "recursive": true, "expect": "deny"},
+            {"op": "rename", "from_path": path(".env"), "to_path": path("env.bak"), "expect": "deny"},
+            {"op": "copy", "from_path": path(""), "to_path": path("../copy"), "recursive": true, "expect": "deny"},
+            {"op": "copy", "from_path": path(""), "to_path": path("../copy"), "recursive": true, "override_entry_limit": true, "expect": "allow"}
+        ]);
+        let report = run_cases(&security, &config, serde_json::from_value(cases).unwrap());
+        assert_eq!(report.failures, 0, "{:?}", report.results);
+
+        config.security.allow_protected_override = Some(true);
+        let security = SecurityManager::new(config.clone());
+        let forced = serde_json::json!([{"op": "write", "path": path(".env"), "content": "x", "force": true}]);
+        let report = run_cases(&security, &config, serde_json::from_value(forced).unwrap());
+        assert_eq!(report.allowed, 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(parse_args(&args(&["test", "cases.json"])).unwrap(), ("cases.json".to_string(), ReportFormat::Json));
+        assert_eq!(
+            parse_args(&args(&["test", "--format", "junit", "cases.json"])).unwrap().1,
+            ReportFormat::Junit
+        );
+        assert!(parse_args(&args(&["test"])).is_err());
+        assert!(parse_args(&args(&["lint", "cases.json"])).is_err());
+        assert!(parse_args(&args(&["test", "a.json", "--format", "xml"])).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+use crate::models::WakeLease;
+use crate::platform::power::WakeGuard;
+
+/// Keep-awake lease length when a request does not give one
+pub const DEFAULT_WAKE_LEASE: Duration = Duration::from_secs(60 * 60);
+
+/// Lifetime of the lock held for running jobs; it is renewed when a job starts after it lapses
+const JOB_WAKE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+struct Lease {
+    reason: String,
+    acquired: SystemTime,
+    expires: SystemTime,
+    guard: WakeGuard,
+}
+
+impl Lease {
+    fn info(&self, id: &str) -> WakeLease {
+        WakeLease {
+            id: id.to_string(),
+            reason: self.reason.clone(),
+            acquired: humantime::format_rfc3339_seconds(self.acquired).to_string(),
+            expires: humantime::format_rfc3339_seconds(self.expires).to_string(),
+            remaining_secs: self
+                .expires
+                .duration_since(SystemTime::now())
+                .map_or(0, |remaining| remaining.as_secs()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct JobWake {
+    running: usize,
+    guard: Option<WakeGuard>,
+    /// Set once the platform refuses a wake lock, so later jobs do not retry it
+    unavailable: bool,
+}
+
+/// Keep-awake leases held for clients, so long builds are not interrupted by sleep
+///
+/// Each lease ends at its deadline or when released; the manager outlives
+/// server restarts, so leases survive a remote policy reload. Running jobs
+/// share one more lock, held from the first job's start to the last one's end.
+#[derive(Default)]
+pub struct PowerManager {
+    leases: Mutex<HashMap<String, Lease>>,
+    keep_awake_during_jobs: bool,
+    jobs: Mutex<JobWake>,
+}
+
+impl PowerManager {
+    pub fn new(keep_awake_during_jobs: bool) -> Self {
+        Self {
+            keep_awake_during_jobs,
+            ..Self::default()
+        }
+    }
+
+    /// Marks a job as running until the returned handle is dropped
+    ///
+    /// May block briefly while the wake lock is taken, so call it from a
+    /// worker thread.
+    pub fn job_started(self: &Arc<Self>) -> JobWakeLock {
+        if self.keep_awake_during_jobs {
+            let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+            jobs.running += 1;
+            let lapsed = jobs.guard.as_mut().is_none_or(|guard| !guard.is_active());
+            if lapsed && !jobs.unavailable {
+                match WakeGuard::hold(JOB_WAKE_DURATION, "Running commands") {
+                    Ok(guard) => {
+                        debug!("Holding a wake lock while jobs run");
+                        jobs.guard = Some(guard);
+                    }
+                    Err(e) => {
+                        warn!("Cannot keep the machine awake while jobs run: {}", e);
+                        jobs.unavailable = true;
+                    }
+                }
+            }
+        }
+        JobWakeLock(self.keep_awake_during_jobs.then(|| self.clone()))
+    }
+
+    fn job_finished(&self) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.running -= 1;
+        if jobs.running == 0 && jobs.guard.take().is_some() {
+            debug!("Last job finished; released the wake lock");
+        }
+    }
+
+    /// Whether a wake lock is currently held for running jobs
+    pub fn holds_job_wake_lock(&self) -> bool {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner).guard.is_some()
+    }
+
+    /// Keeps the machine awake for `duration`
+    pub fn acquire(&self, duration: Duration, reason: &str) -> io::Result<WakeLease> {
+        let guard = WakeGuard::hold(duration, reason)?;
+        let acquired = SystemTime::now();
+        let lease = Lease {
+            reason: reason.to_string(),
+            acquired,
+            expires: acquired + duration,
+            guard,
+        };
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let info = lease.info(&id);
+        self.leases.lock().unwrap_or_else(PoisonError::into_inner).insert(id, lease);
+        Ok(info)
+    }
+
+    /// Ends a lease early, returning it as it was
+    pub fn release(&self, id: &str) -> Option<WakeLease> {
+        let lease = self.leases.lock().unwrap_or_else(PoisonError::into_inner).remove(id)?;
+        Some(lease.info(id))
+    }
+
+    /// Leases that still hold the machine awake, soonest to expire first
+    pub fn leases(&self) -> Vec<WakeLease> {
+        let mut leases = self.leases.lock().unwrap_or_else(PoisonError::into_inner);
+        leases.retain(|_, lease| lease.guard.is_active());
+        let mut active: Vec<WakeLease> = leases.iter().map(|(id, lease)| lease.info(id)).collect();
+        active.sort_by(|a, b| a.expires.cmp(&b.expires));
+        active
+    }
+}
+
+/// Keeps the jobs' wake lock held until dropped
+pub struct JobWakeLock(Option<Arc<PowerManager>>);
+
+impl Drop for JobWakeLock {
+    fn drop(&mut self) {
+        if let Some(power) = self.0.take() {
+            power.job_finished();
+        }
+    }
+}
+
+/// Lease length for a request, capped at `max_secs`
+pub fn lease_duration(requested_secs: Option<u64>, max_secs: u64) -> Result<Duration, String> {
+    let duration = requested_secs.map_or(DEFAULT_WAKE_LEASE, Duration::from_secs);
+    if duration.is_zero() {
+        return Err("duration_secs must be greater than 0".to_string());
+    }
+    Ok(duration.min(Duration::from_secs(max_secs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_duration() {
+        assert_eq!(lease_duration(None, 43_200), Ok(DEFAULT_WAKE_LEASE));
+        assert_eq!(lease_duration(Some(90), 43_200), Ok(Duration::from_secs(90)));
+        assert_eq!(lease_duration(Some(100_000), 43_200), Ok(Duration::from_secs(43_200)));
+        assert_eq!(lease_duration(None, 600), Ok(Duration::from_secs(600)));
+        assert!(lease_duration(Some(0), 43_200).is_err());
+
+        let power = Arc::new(PowerManager::new(false));
+        assert!(power.release("missing").is_none());
+        assert!(power.leases().is_empty());
+        // Disabled job locks do nothing
+        let job = power.job_started();
+        assert!(!power.holds_job_wake_lock());
+        drop(job);
+    }
+}
@@ -0,0 +1,156 @@
+use std::io;
+use std::process::ExitStatus;
+
+use crate::models::ExecOutcome;
+
+/// Exit codes with a documented meaning for a specific tool
+const TOOL_EXIT_CODES: &[(&str, i32, &str)] = &[
+    ("grep", 1, "No lines matched"),
+    ("grep", 2, "grep failed, e.g. on an unreadable file or invalid pattern"),
+    ("rg", 1, "No lines matched"),
+    ("rg", 2, "ripgrep failed, e.g. on an unreadable file or invalid pattern"),
+    ("diff", 1, "The inputs differ"),
+    ("diff", 2, "diff failed, e.g. on a missing file"),
+    ("git", 1, "git reported a failure or, for diff-like commands, found differences"),
+    ("git", 128, "git fatal error, e.g. not a repository or invalid reference"),
+    ("git", 129, "Invalid git usage"),
+    ("cargo", 101, "cargo failed: compilation errors, failed tests or a panic"),
+    ("rustc", 1, "Compilation failed"),
+    ("npm", 1, "npm command or script failed"),
+    ("npx", 1, "The npx command failed"),
+    ("node", 1, "Uncaught exception"),
+    ("node", 9, "Invalid node argument"),
+    ("python", 1, "Uncaught exception"),
+    ("python", 2, "Invalid python usage or file not found"),
+    ("python3", 1, "Uncaught exception"),
+    ("python3", 2, "Invalid python usage or file not found"),
+    ("pytest", 1, "Some tests failed"),
+    ("pytest", 2, "Test run interrupted"),
+    ("pytest", 4, "Invalid pytest usage"),
+    ("pytest", 5, "No tests were collected"),
+    ("eslint", 1, "Linting errors found"),
+    ("eslint", 2, "eslint configuration or internal error"),
+    ("tsc", 1, "Type errors found"),
+    ("tsc", 2, "Type errors found; output was still emitted"),
+    ("go", 1, "go command failed, e.g. build errors or failing tests"),
+    ("curl", 6, "Could not resolve host"),
+    ("curl", 7, "Failed to connect to host"),
+    ("curl", 22, "HTTP error response"),
+    ("curl", 28, "Operation timed out"),
+    ("curl", 35, "TLS handshake failed"),
+];
+
+/// Exit codes with a conventional meaning for any command
+const COMMON_EXIT_CODES: &[(i32, &str)] = &[
+    (126, "Command found but not executable"),
+    (127, "Command not found"),
+    (130, "Interrupted (Ctrl+C)"),
+    (137, "Killed, often by the out-of-memory killer"),
+    (139, "Segmentation fault"),
+    (143, "Terminated"),
+    // Windows NTSTATUS codes, as returned for crashed processes
+    (0xC000_0005_u32 as i32, "Access violation"),
+    (0xC000_00FD_u32 as i32, "Stack overflow"),
+    (0xC000_0135_u32 as i32, "A required DLL was not found"),
+    (0xC000_013A_u32 as i32, "Interrupted (Ctrl+C)"),
+    (0xC000_0409_u32 as i32, "Stack buffer overrun"),
+];
+
+/// Signals whose numbers are the same on Linux and macOS
+const SIGNALS: &[(i32, &str)] = &[
+    (1, "SIGHUP: terminal hung up"),
+    (2, "SIGINT: interrupted"),
+    (3, "SIGQUIT: quit"),
+    (4, "SIGILL: illegal instruction"),
+    (6, "SIGABRT: aborted"),
+    (8, "SIGFPE: arithmetic error"),
+    (9, "SIGKILL: killed, often by the out-of-memory killer"),
+    (11, "SIGSEGV: segmentation fault"),
+    (13, "SIGPIPE: broken pipe"),
+    (14, "SIGALRM: timer expired"),
+    (15, "SIGTERM: terminated"),
+];
+
+/// Classifies how a command that was started ended
+pub fn classify(status: ExitStatus, timed_out: bool) -> ExecOutcome {
+    if timed_out {
+        ExecOutcome::TimedOut
+    } else if status.success() {
+        ExecOutcome::Succeeded
+    } else if exit_signal(status).is_some() {
+        ExecOutcome::Killed
+    } else {
+        ExecOutcome::Failed
+    }
+}
+
+/// The signal that terminated a process, on Unix
+pub fn exit_signal(status: ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// Reason for a failed exit, for codes and signals whose meaning is well known
+///
+/// `command_name` is the base name of the command as requested, so tools
+/// are recognized whether they were run directly or through the shell.
+pub fn describe_exit(command_name: &str, status: ExitStatus) -> Option<&'static str> {
+    if let Some(signal) = exit_signal(status) {
+        return SIGNALS.iter().find(|(number, _)| *number == signal).map(|(_, reason)| *reason);
+    }
+    let code = status.code().filter(|code| *code != 0)?;
+    TOOL_EXIT_CODES
+        .iter()
+        .find(|(tool, tool_code, _)| tool.eq_ignore_ascii_case(command_name) && *tool_code == code)
+        .map(|(_, _, reason)| *reason)
+        .or_else(|| COMMON_EXIT_CODES.iter().find(|(common, _)| *common == code).map(|(_, reason)| *reason))
+}
+
+/// Reason a command could not be started
+pub fn describe_spawn_error(error: &io::Error) -> &'static str {
+    match error.kind() {
+        io::ErrorKind::NotFound => "Program or working directory not found",
+        io::ErrorKind::PermissionDenied => "Program is not executable or the working directory is not accessible",
+        _ => "Program could not be started",
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_exit() {
+        use std::os::unix::process::ExitStatusExt;
+        let exited = |code: i32| ExitStatus::from_raw(code << 8);
+
+        assert_eq!(describe_exit("grep", exited(1)), Some("No lines matched"));
+        assert_eq!(describe_exit("cargo", exited(101)), Some("cargo failed: compilation errors, failed tests or a panic"));
+        // Tool codes take precedence over common ones
+        assert_eq!(describe_exit("pytest", exited(2)), Some("Test run interrupted"));
+        assert_eq!(describe_exit("make", exited(2)), None);
+        assert_eq!(describe_exit("sh", exited(127)), Some("Command not found"));
+        assert_eq!(describe_exit("make", exited(1)), None);
+        assert_eq!(describe_exit("grep", exited(0)), None);
+
+        let segfault = ExitStatus::from_raw(11);
+        assert_eq!(describe_exit("app", segfault), Some("SIGSEGV: segmentation fault"));
+        assert_eq!(classify(segfault, false), ExecOutcome::Killed);
+        assert_eq!(classify(ExitStatus::from_raw(9), true), ExecOutcome::TimedOut);
+        assert_eq!(classify(exited(1), false), ExecOutcome::Failed);
+        assert_eq!(classify(exited(0), false), ExecOutcome::Succeeded);
+
+        assert_eq!(
+            describe_spawn_error(&io::Error::from(io::ErrorKind::NotFound)),
+            "Program or working directory not found"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{HashMap, VecDeque};
+use std::process::{Child, ExitStatus};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+use crate::models::{ProcessInfo, ProcessKind};
+use crate::platform::process_tree::TreeKiller;
+
+pub mod exit;
+
+/// How often the reaper collects exited children
+pub const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Exited processes kept for status queries, oldest dropped first
+const MAX_FINISHED: usize = 256;
+
+struct Entry {
+    info: ProcessInfo,
+    /// Handle for children nothing else waits on; None when the spawner waits itself
+    child: Option<Child>,
+    /// Terminates the process with its descendants, for spawners that track its tree
+    killer: Option<Arc<TreeKiller>>,
+}
+
+/// Why a process could not be killed
+#[derive(Debug)]
+pub enum KillError {
+    /// Not a running process EXEX started
+    NotFound,
+    Failed(std::io::Error),
+}
+
+/// Registry of processes spawned by EXEX
+///
+/// Launched applications are adopted here instead of having their `Child`
+/// handles dropped, and the reaper collects them once they exit so they do not
+/// linger as zombies. Exited processes are kept, with their exit status, for
+/// later status queries.
+#[derive(Default)]
+pub struct ProcessRegistry {
+    running: Mutex<HashMap<u32, Entry>>,
+    finished: Mutex<VecDeque<ProcessInfo>>,
+}
+
+impl ProcessRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes ownership of a detached child so it is reaped when it exits
+    pub fn adopt(&self, kind: ProcessKind, name: &str, child: Child) -> u32 {
+        let pid = child.id();
+        self.insert(pid, kind, name, Some(child), None);
+        pid
+    }
+
+    /// Records a process whose spawner waits on it and reports back through [`Self::exited`]
+    pub fn started(&self, kind: ProcessKind, name: &str, pid: u32, killer: Option<Arc<TreeKiller>>) {
+        self.insert(pid, kind, name, None, killer);
+    }
+
+    /// Forcibly terminates a running process EXEX started, with its descendants when they are tracked
+    ///
+    /// The process is reported as exited by its spawner or the reaper, not here.
+    pub fn kill(&self, pid: u32) -> Result<ProcessInfo, KillError> {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = running.get_mut(&pid).ok_or(KillError::NotFound)?;
+        match (&entry.killer, entry.child.as_mut()) {
+            (Some(killer), _) => killer.kill(),
+            (None, Some(child)) => child.kill().map_err(KillError::Failed)?,
+            (None, None) => return Err(KillError::Failed(std::io::Error::other("the process is not tracked"))),
+        }
+        Ok(entry.info.clone())
+    }
+
+    /// Links a running process to the job its output is logged under
+    pub fn set_job_id(&self, pid: u32, job_id: &str) {
+        if let Some(entry) = self.running.lock().unwrap_or_else(PoisonError::into_inner).get_mut(&pid) {
+            entry.info.job_id = Some(job_id.to_string());
+        }
+    }
+
+    /// Records the exit of a process registered with [`Self::started`]
+    pub fn exited(&self, pid: u32, status: Option<ExitStatus>) {
+        let entry = self.running.lock().unwrap_or_else(PoisonError::into_inner).remove(&pid);
+        if let Some(entry) = entry {
+            self.finish(entry.info, status);
+        }
+    }
+
+    /// Collects adopted children that have exited; returns how many were reaped
+    pub fn reap(&self) -> usize {
+        let mut exited = Vec::new();
+        {
+            let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+            running.retain(|pid, entry| {
+                let Some(child) = entry.child.as_mut() else {
+                    return true;
+                };
+                match child.try_wait() {
+                    Ok(None) => true,
+                    Ok(Some(status)) => {
+                        exited.push((entry.info.clone(), Some(status)));
+                        false
+                    }
+                    Err(e) => {
+                        warn!("Failed to poll PID {}: {}", pid, e);
+                        exited.push((entry.info.clone(), None));
+                        false
+                    }
+                }
+            });
+        }
+
+        let reaped = exited.len();
+        for (info, status) in exited {
+            debug!("Reaped {} (PID {}) with {:?}", info.name, info.pid, status);
+            self.finish(info, status);
+        }
+        reaped
+    }
+
+    /// Processes that are still running
+    pub fn running_count(&self) -> usize {
+        self.running.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    /// Running processes launched through the app endpoints, excluding `/api/exec` commands
+    pub fn running_launches(&self) -> usize {
+        self.running
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.info.kind != ProcessKind::Exec)
+            .count()
+    }
+
+    /// Running processes followed by recently exited ones, newest first
+    pub fn list(&self) -> Vec<ProcessInfo> {
+        let mut running: Vec<ProcessInfo> = self
+            .running
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect();
+        running.sort_by(|a, b| b.started.cmp(&a.started).then(b.pid.cmp(&a.pid)));
+
+        let finished = self.finished.lock().unwrap_or_else(PoisonError::into_inner);
+        running.extend(finished.iter().rev().cloned());
+        running
+    }
+
+    /// Latest known state of a process
+    pub fn get(&self, pid: u32) -> Option<ProcessInfo> {
+        if let Some(entry) = self.running.lock().unwrap_or_else(PoisonError::into_inner).get(&pid) {
+            return Some(entry.info.clone());
+        }
+        self.finished
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|info| info.pid == pid)
+            .cloned()
+    }
+
+    fn insert(&self, pid: u32, kind: ProcessKind, name: &str, child: Option<Child>, killer: Option<Arc<TreeKiller>>) {
+        let info = ProcessInfo {
+            pid,
+            kind,
+            name: name.to_string(),
+            started: timestamp(),
+            finished: None,
+            running: true,
+            exit_code: None,
+            job_id: None,
+        };
+        self.running.lock().unwrap_or_else(PoisonError::into_inner).insert(pid, Entry { info, child, killer });
+    }
+
+    fn finish(&self, mut info: ProcessInfo, status: Option<ExitStatus>) {
+        info.running = false;
+        info.finished = Some(timestamp());
+        info.exit_code = status.and_then(|s| s.code());
+
+        let mut finished = self.finished.lock().unwrap_or_else(PoisonError::into_inner);
+        if finished.len() >= MAX_FINISHED {
+            finished.pop_front();
+        }
+        finished.push_back(info);
+    }
+}
+
+/// Periodically reaps exited children for as long as the server runs
+pub async fn run_reaper(registry: Arc<ProcessRegistry>) {
+    let mut interval = tokio::time::interval(REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+        registry.reap();
+    }
+}
+
+fn timestamp() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::time::Instant;
+
+    #[test]
+    fn test_adopted_children_are_reaped() {
+        let registry = ProcessRegistry::new();
+        let (program, args) = if cfg!(windows) {
+            ("cmd", vec!["/C", "exit 3"])
+        } else {
+            ("sh", vec!["-c", "exit 3"])
+        };
+        let child = Command::new(program).args(&args).spawn().unwrap();
+        let pid = registry.adopt(ProcessKind::App, program, child);
+        assert_eq!(registry.running_count(), 1);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while registry.reap() == 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let info = registry.get(pid).unwrap();
+        assert!(!info.running);
+        assert_eq!(info.exit_code, Some(3));
+        assert_eq!(registry.running_count(), 0);
+    }
+
+    #[test]
+    fn test_waited_processes_report_exit() {
+        let registry = ProcessRegistry::new();
+        registry.started(ProcessKind::Exec, "cargo build", 42, None);
+        assert_eq!(registry.reap(), 0);
+        assert!(registry.get(42).unwrap().running);
+
+        assert_eq!(registry.running_launches(), 0);
+        registry.exited(42, None);
+        let info = registry.get(42).unwrap();
+        assert!(!info.running);
+        assert_eq!(info.kind, ProcessKind::Exec);
+        assert_eq!(registry.list().len(), 1);
+    }
+
+    #[test]
+    fn test_kill_only_registered_processes() {
+        let registry = ProcessRegistry::new();
+        let (program, args) = if cfg!(windows) {
+            ("ping", vec!["-n", "30", "127.0.0.1"])
+        } else {
+            ("sleep", vec!["30"])
+        };
+        let child = Command::new(program).args(&args).spawn().unwrap();
+        let pid = registry.adopt(ProcessKind::App, program, child);
+
+        assert!(matches!(registry.kill(pid + 1), Err(KillError::NotFound)));
+        assert_eq!(registry.kill(pid).unwrap().pid, pid);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while registry.reap() == 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!registry.get(pid).unwrap().running);
+        assert!(matches!(registry.kill(pid), Err(KillError::NotFound)));
+    }
+}
@@ -0,0 +1,275 @@
+pub mod version;
+
+use actix_web::http::Method;
+use actix_web::web;
+
+use crate::handlers::{
+    exec_command, exec_environment, read_file, download_file, write_file, edit_file, scan_directory, delete_item, create_item, rename_item,
+    copy_item, open_application, open_editor, open_terminal, open_url, shutdown_server, issue_csrf_token, list_mounts, worker_stats, batch_stream,
+    capabilities, quota, list_processes, kill_process, self_test, list_clients, register_client,
+    command_report, tool_manifest, error_catalog, resolve_shortcut, create_shortcut, power_action, power_status,
+    job_logs, submit_job, job_status, cancel_job, job_artifacts, job_artifact, submit_job_group, job_group_status,
+    cancel_job_group, list_pipelines, run_pipeline, start_session, session_diff, finalize_session, abort_session, search_files, find_files,
+    create_archive, extract_archive, sync_directories, hash_file_content, tail_file, add_watch, list_watches, remove_watch, watch_events,
+    list_backups, run_backup, list_operations, cancel_operation, get_config, update_config,
+};
+
+use self::version::SUPPORTED_API_VERSIONS;
+
+/// Deprecation notice announced through `Deprecation`, `Sunset` and `Link` headers
+#[derive(Debug, Clone, Copy)]
+pub struct Deprecation {
+    /// Unix timestamp at which the endpoint was deprecated
+    pub since: i64,
+    /// HTTP-date after which the endpoint may be removed
+    pub sunset: Option<&'static str>,
+    /// Replacement endpoint path, relative to the versioned prefix
+    pub successor: Option<&'static str>,
+}
+
+/// An endpoint exposed under `/api` and every `/api/v{n}` prefix
+#[derive(Debug, Clone, Copy)]
+pub struct Endpoint {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub deprecation: Option<Deprecation>,
+}
+
+const fn endpoint(method: &'static str, path: &'static str) -> Endpoint {
+    Endpoint { method, path, deprecation: None }
+}
+
+/// Applies to every unversioned `/api/...` path; clients should move to `/api/v{n}/...`
+pub const LEGACY_PATH_DEPRECATION: Deprecation = Deprecation {
+    since: 1_792_195_200, // 2026-10-17
+    sunset: None,
+    successor: None,
+};
+
+/// Declares the route table once and derives both [`API_ENDPOINTS`] and [`configure_api`] from it
+///
+/// Each entry is `METHOD "/path" => handler`; `as "pattern"` overrides the actix pattern when it
+/// differs from the advertised path (e.g. a tail-matching segment).
+macro_rules! api_routes {
+    (@pattern $path:literal) => { $path };
+    (@pattern $path:literal $pattern:literal) => { $pattern };
+    ($($(#[$attr:meta])* $method:ident $path:literal $(as $pattern:literal)? => $handler:path),* $(,)?) => {
+        /// Every endpoint registered by [`configure_api`], reported by `/api/capabilities`
+        pub const API_ENDPOINTS: &[Endpoint] = &[
+            $($(#[$attr])* endpoint(stringify!($method), $path),)*
+        ];
+
+        /// Registers the API routes on a scope
+        pub fn configure_api(cfg: &mut web::ServiceConfig) {
+            $(
+                $(#[$attr])*
+                cfg.route(
+                    api_routes!(@pattern $path $($pattern)?),
+                    web::method(Method::$method).to($handler),
+                );
+            )*
+        }
+    };
+}
+
+api_routes! {
+    // Command execution
+    POST "/exec" => exec_command,
+    GET "/exec/environment" => exec_environment,
+    // File operations
+    POST "/read" => read_file,
+    GET "/download" => download_file,
+    POST "/write" => write_file,
+    POST "/edit" => edit_file,
+    POST "/scan" => scan_directory,
+    POST "/search" => search_files,
+    POST "/find" => find_files,
+    POST "/hash" => hash_file_content,
+    POST "/tail" => tail_file,
+    POST "/watch" => add_watch,
+    GET "/watch" => list_watches,
+    POST "/watch/{id}/remove" => remove_watch,
+    GET "/events" => watch_events,
+    POST "/delete" => delete_item,
+    POST "/create" => create_item,
+    POST "/rename" => rename_item,
+    POST "/copy" => copy_item,
+    POST "/archive" => create_archive,
+    POST "/extract" => extract_archive,
+    POST "/sync" => sync_directories,
+    GET "/operations" => list_operations,
+    POST "/operations/{id}/cancel" => cancel_operation,
+    POST "/batch/stream" => batch_stream,
+    POST "/shortcut/resolve" => resolve_shortcut,
+    POST "/shortcut/create" => create_shortcut,
+    GET "/mounts" => list_mounts,
+    // Daemon introspection
+    GET "/workers" => worker_stats,
+    GET "/processes" => list_processes,
+    POST "/processes/kill" => kill_process,
+    // Background jobs
+    POST "/jobs" => submit_job,
+    // Before `/jobs/{id}`, which would otherwise take `groups` for a job ID
+    POST "/jobs/groups" => submit_job_group,
+    GET "/jobs/groups/{id}" => job_group_status,
+    POST "/jobs/groups/{id}/cancel" => cancel_job_group,
+    GET "/jobs/{id}" => job_status,
+    POST "/jobs/{id}/cancel" => cancel_job,
+    GET "/jobs/{id}/logs" => job_logs,
+    GET "/jobs/{id}/artifacts" => job_artifacts,
+    GET "/jobs/{id}/artifacts/{path}" as "/jobs/{id}/artifacts/{path:.*}" => job_artifact,
+    // Curated workflows from the config
+    GET "/pipelines" => list_pipelines,
+    POST "/pipelines/{name}/run" => run_pipeline,
+    GET "/backups" => list_backups,
+    POST "/backups/{name}/run" => run_backup,
+    // Editing sessions
+    POST "/sessions" => start_session,
+    GET "/sessions/{id}/diff" => session_diff,
+    POST "/sessions/{id}/finalize" => finalize_session,
+    POST "/sessions/{id}/abort" => abort_session,
+    GET "/capabilities" => capabilities,
+    GET "/policy/commands" => command_report,
+    GET "/tools" => tool_manifest,
+    GET "/errors" => error_catalog,
+    GET "/quota" => quota,
+    GET "/clients" => list_clients,
+    POST "/clients" => register_client,
+    // Application operations
+    POST "/open" => open_application,
+    POST "/open_terminal" => open_terminal,
+    POST "/open_editor" => open_editor,
+    POST "/open_url" => open_url,
+    POST "/shutdown" => shutdown_server,
+    GET "/power" => power_status,
+    POST "/power" => power_action,
+    POST "/admin/selftest" => self_test,
+    GET "/config" => get_config,
+    PUT "/config" => update_config,
+    #[cfg(feature = "registry")]
+    POST "/registry/read" => crate::handlers::read_registry,
+    // Browser session protection
+    GET "/csrf-token" => issue_csrf_token,
+}
+
+/// Looks up an endpoint in the route table by method and unprefixed path
+pub fn find_endpoint(method: &str, path: &str) -> Option<&'static Endpoint> {
+    API_ENDPOINTS
+        .iter()
+        .find(|e| e.method.eq_ignore_ascii_case(method) && e.path == path)
+}
+
+/// Registers the endpoints under each `/api/v{n}` prefix and the legacy `/api` prefix
+pub fn configure_versioned(cfg: &mut web::ServiceConfig) {
+    for version in SUPPORTED_API_VERSIONS {
+        cfg.service(web::scope(&format!("/api/v{}", version)).configure(configure_api));
+    }
+    cfg.service(web::scope("/api").configure(configure_api));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backups::BackupService;
+    use crate::watch::WatchService;
+    use crate::operations::OperationRegistry;
+    use crate::config::store::ConfigStore;
+    use crate::config::get_default_config;
+    use crate::clients::ClientRegistry;
+    use crate::i18n::ErrorCatalog;
+    use crate::jobs::JobLogs;
+    use crate::jobs::queue::JobQueue;
+    use crate::jobs::artifacts::ArtifactStore;
+    use crate::power::PowerManager;
+    use crate::processes::ProcessRegistry;
+    use crate::security::encryption::ContentCipher;
+    use crate::security::secrets::SecretStore;
+    use crate::security::SecurityManager;
+    use crate::sessions::SessionManager;
+    use crate::workers::WorkerPools;
+    use crate::routes::version::api_version_negotiation;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// Registers the shared services every handler expects, then the versioned routes
+    fn test_app(cfg: &mut web::ServiceConfig) {
+        let config = get_default_config();
+        cfg.app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+            .app_data(web::Data::new(Arc::new(config.clone())))
+            .app_data(web::Data::new(Arc::new(ConfigStore::new(config.clone(), None, None))))
+            .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+            .app_data(web::Data::new(Arc::new(ProcessRegistry::new())))
+            .app_data(web::Data::new(Arc::new(ErrorCatalog::builtin())))
+            .app_data(web::Data::new(Arc::new(PowerManager::new(false))))
+            .app_data(web::Data::new(Arc::new(JobLogs::new(std::env::temp_dir().join("exex-route-jobs"), 0, 0))))
+            .app_data(web::Data::new(Arc::new(SecretStore::new(
+                std::env::temp_dir().join("exex-route-secrets.enc"),
+                ContentCipher::from_key(&[0u8; 32]),
+            ))))
+            .app_data(web::Data::new(Arc::new(JobQueue::new())))
+            .app_data(web::Data::new(Arc::new(BackupService::new(HashMap::new()))))
+            .app_data(web::Data::new(Arc::new(WatchService::new())))
+            .app_data(web::Data::new(Arc::new(OperationRegistry::new())))
+            .app_data(web::Data::new(Arc::new(ArtifactStore::new(std::env::temp_dir().join("exex-route-artifacts"), 0, 0))))
+            .app_data(web::Data::new(Arc::new(SessionManager::new())))
+            .app_data(web::Data::new(Arc::new(ClientRegistry::new())));
+        configure_versioned(cfg);
+    }
+
+    #[actix_web::test]
+    async fn test_endpoint_table_matches_routes() {
+        let app = init_service(App::new().configure(test_app)).await;
+
+        for prefix in ["/api", "/api/v1", "/api/v2"] {
+            for endpoint in API_ENDPOINTS {
+                // Actually calling shutdown would end the test process
+                if endpoint.path == "/shutdown" {
+                    continue;
+                }
+                let method = Method::from_bytes(endpoint.method.as_bytes()).unwrap();
+                let req = TestRequest::default()
+                    .method(method)
+                    .uri(&format!("{}{}", prefix, endpoint.path))
+                    .to_request();
+                let status = call_service(&app, req).await.status();
+                assert_ne!(status, 404, "{} {}{} is not routed", endpoint.method, prefix, endpoint.path);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_unversioned_paths_are_deprecated() {
+        let app = init_service(App::new().wrap(from_fn(api_version_negotiation)).configure(test_app)).await;
+
+        let req = TestRequest::get().uri("/api/capabilities").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.headers().get("x-exex-api-version").unwrap(), "1");
+        assert_eq!(resp.headers().get("deprecation").unwrap(), "@1792195200");
+        assert_eq!(
+            resp.headers().get("link").unwrap(),
+            "</api/v1/capabilities>; rel=\"successor-version\""
+        );
+
+        let req = TestRequest::get()
+            .uri("/api/capabilities")
+            .insert_header(("X-Exex-Api-Version", "2"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.headers().get("x-exex-api-version").unwrap(), "2");
+        assert_eq!(
+            resp.headers().get("link").unwrap(),
+            "</api/v2/capabilities>; rel=\"successor-version\""
+        );
+
+        let req = TestRequest::get().uri("/api/v2/capabilities").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.headers().get("x-exex-api-version").unwrap(), "2");
+        assert!(resp.headers().get("deprecation").is_none());
+        let body: serde_json::Value = read_body_json(resp).await;
+        assert_eq!(body["api_version"], 2);
+        assert!(body["endpoints"].as_array().unwrap().iter().any(|e| e["path"] == "/api/v2/read"));
+    }
+}
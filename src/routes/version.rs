@@ -0,0 +1,169 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use std::future::{ready, Ready};
+
+use crate::models::ErrorResponse;
+use crate::routes::{find_endpoint, Deprecation, LEGACY_PATH_DEPRECATION};
+
+/// Header clients use to request, and the daemon uses to report, an API version
+pub const API_VERSION_HEADER: &str = "X-Exex-Api-Version";
+
+/// Newest API version, served under `/api/v2`
+pub const CURRENT_API_VERSION: u32 = 2;
+
+/// Version served on unversioned `/api/...` paths when no header is sent
+///
+/// Pinned so integrations written before versioned routing keep their response shapes.
+pub const DEFAULT_API_VERSION: u32 = 1;
+
+/// Versions whose response shapes this daemon can still produce
+pub const SUPPORTED_API_VERSIONS: &[u32] = &[1, 2];
+
+/// API version negotiated for the current request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiVersion(pub u32);
+
+impl FromRequest for ApiVersion {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let version = req
+            .extensions()
+            .get::<ApiVersion>()
+            .copied()
+            .unwrap_or(ApiVersion(DEFAULT_API_VERSION));
+        ready(Ok(version))
+    }
+}
+
+/// Parses a requested version, returning None for malformed or unsupported values
+pub fn parse_api_version(value: &str) -> Option<u32> {
+    let version = value.trim().trim_start_matches(['v', 'V']).parse().ok()?;
+    SUPPORTED_API_VERSIONS.contains(&version).then_some(version)
+}
+
+/// Splits `/api/v2/read` into `(Some(2), "/read")` and `/api/read` into `(None, "/read")`
+pub fn split_versioned_path(path: &str) -> Option<(Option<u32>, &str)> {
+    let rest = path.strip_prefix("/api")?;
+    if let Some(versioned) = rest.strip_prefix("/v")
+        && let Some(slash) = versioned.find('/')
+        && let Ok(version) = versioned[..slash].parse::<u32>()
+        && SUPPORTED_API_VERSIONS.contains(&version)
+    {
+        return Some((Some(version), &versioned[slash..]));
+    }
+    Some((None, rest))
+}
+
+/// Middleware negotiating the API version and announcing deprecations
+///
+/// The version comes from the `/api/v{n}` path prefix, else from
+/// `X-Exex-Api-Version`, else [`DEFAULT_API_VERSION`]. It is stored for handlers
+/// (see [`ApiVersion`]) and echoed back in the response header. Deprecated
+/// endpoints, and unversioned `/api/...` paths, get `Deprecation`, `Sunset` and
+/// `Link` headers from the central route table.
+pub async fn api_version_negotiation(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let requested = req
+        .headers()
+        .get(API_VERSION_HEADER)
+        .map(|v| v.to_str().unwrap_or_default().to_string());
+
+    let header_version = match requested {
+        None => None,
+        Some(value) => match parse_api_version(&value) {
+            Some(version) => Some(version),
+            None => {
+                let response = HttpResponse::BadRequest().json(ErrorResponse {
+                    error: format!(
+                        "Unsupported API version '{}'; supported versions: {:?}",
+                        value, SUPPORTED_API_VERSIONS
+                    ),
+                });
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+        },
+    };
+
+    let path = req.path().to_string();
+    let route = split_versioned_path(&path);
+    let version = route
+        .and_then(|(path_version, _)| path_version)
+        .or(header_version)
+        .unwrap_or(DEFAULT_API_VERSION);
+
+    // Resolve deprecations before the request is consumed; endpoint entries take precedence
+    let deprecation = route.and_then(|(path_version, endpoint_path)| {
+        find_endpoint(req.method().as_str(), endpoint_path)
+            .and_then(|endpoint| endpoint.deprecation)
+            .map(|deprecation| {
+                let successor = deprecation
+                    .successor
+                    .map(|s| format!("/api/v{}{}", CURRENT_API_VERSION, s));
+                (deprecation, successor)
+            })
+            .or_else(|| {
+                path_version.is_none().then(|| {
+                    let successor = format!("/api/v{}{}", version, endpoint_path);
+                    (LEGACY_PATH_DEPRECATION, Some(successor))
+                })
+            })
+    });
+
+    req.extensions_mut().insert(ApiVersion(version));
+    let mut response = next.call(req).await?;
+    let headers = response.headers_mut();
+    headers.insert(
+        HeaderName::from_static("x-exex-api-version"),
+        HeaderValue::from(version),
+    );
+    if let Some((deprecation, successor)) = deprecation {
+        insert_deprecation_headers(headers, &deprecation, successor.as_deref());
+    }
+    Ok(response.map_into_left_body())
+}
+
+/// Adds RFC 9745 `Deprecation`, RFC 8594 `Sunset` and a successor `Link`
+fn insert_deprecation_headers(headers: &mut HeaderMap, deprecation: &Deprecation, successor: Option<&str>) {
+    if let Ok(value) = HeaderValue::from_str(&format!("@{}", deprecation.since)) {
+        headers.insert(HeaderName::from_static("deprecation"), value);
+    }
+    if let Some(sunset) = deprecation.sunset {
+        headers.insert(HeaderName::from_static("sunset"), HeaderValue::from_static(sunset));
+    }
+    if let Some(successor) = successor
+        && let Ok(value) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor))
+    {
+        headers.append(header::LINK, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_version() {
+        assert_eq!(parse_api_version("1"), Some(1));
+        assert_eq!(parse_api_version("v2"), Some(2));
+        assert_eq!(parse_api_version("99"), None);
+        assert_eq!(parse_api_version("latest"), None);
+    }
+
+    #[test]
+    fn test_split_versioned_path() {
+        assert_eq!(split_versioned_path("/api/v2/read"), Some((Some(2), "/read")));
+        assert_eq!(split_versioned_path("/api/v1/batch/stream"), Some((Some(1), "/batch/stream")));
+        assert_eq!(split_versioned_path("/api/read"), Some((None, "/read")));
+        assert_eq!(split_versioned_path("/api/v9/read"), Some((None, "/v9/read")));
+        assert_eq!(split_versioned_path("/health"), None);
+    }
+}
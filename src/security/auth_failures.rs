@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Log target auth failures are emitted under, for fail2ban filters and log routing
+pub const AUTH_TARGET: &str = "auth";
+
+/// Counts refused requests per peer address and runs the ban hook on repeat offenders
+///
+/// Every failure is logged as `auth failure from <ip> (<reason>): <method> <path>`.
+/// Once an address fails `threshold` times within `window`, `ban_command` is
+/// run and `ban_webhook` is called, and its count starts over. Loopback peers
+/// are logged but never banned: tunnels and local proxies connect from there.
+#[derive(Debug)]
+pub struct AuthFailures {
+    threshold: usize,
+    window: Duration,
+    ban_command: Vec<String>,
+    ban_webhook: Option<String>,
+    failures: Mutex<HashMap<IpAddr, Vec<Instant>>>,
+}
+
+impl AuthFailures {
+    /// A threshold of 0 never bans
+    pub fn new(threshold: usize, window: Duration, ban_command: Vec<String>, ban_webhook: Option<String>) -> Self {
+        Self {
+            threshold,
+            window,
+            ban_command,
+            ban_webhook: ban_webhook.filter(|url| !url.is_empty()),
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn has_hook(&self) -> bool {
+        self.threshold > 0 && (!self.ban_command.is_empty() || self.ban_webhook.is_some())
+    }
+
+    /// Logs a refused request and bans its peer if it crossed the threshold; returns whether it did
+    pub fn record(&self, peer: Option<IpAddr>, reason: &str, method: &str, path: &str) -> bool {
+        let peer = peer.map(|ip| ip.to_canonical());
+        let host = peer.map_or_else(|| "-".to_string(), |ip| ip.to_string());
+        warn!(target: AUTH_TARGET, "auth failure from {} ({}): {} {}", host, reason, method, path);
+
+        let Some(ip) = peer.filter(|ip| !ip.is_loopback() && self.has_hook()) else {
+            return false;
+        };
+        let now = Instant::now();
+        let failures = {
+            let mut all = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+            all.retain(|_, times| {
+                times.retain(|time| now.duration_since(*time) < self.window);
+                !times.is_empty()
+            });
+            let times = all.entry(ip).or_default();
+            times.push(now);
+            if times.len() < self.threshold {
+                return false;
+            }
+            all.remove(&ip).map_or(0, |times| times.len())
+        };
+
+        info!(target: AUTH_TARGET, "banning {} after {} auth failures ({})", ip, failures, reason);
+        self.run_command(ip, reason);
+        self.call_webhook(ip, failures, reason);
+        true
+    }
+
+    /// Runs `ban_command` with `{ip}` and `{reason}` substituted, without waiting on the request path
+    fn run_command(&self, ip: IpAddr, reason: &str) {
+        let Some((program, args)) = self.ban_command.split_first() else {
+            return;
+        };
+        let substitute = |arg: &String| arg.replace("{ip}", &ip.to_string()).replace("{reason}", reason);
+        let mut command = Command::new(substitute(program));
+        command
+            .args(args.iter().map(substitute))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let program = program.clone();
+        std::thread::spawn(move || match command.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!(target: AUTH_TARGET, "Ban command {} exited with {}", program, status),
+            Err(e) => error!(target: AUTH_TARGET, "Failed to run ban command {}: {}", program, e),
+        });
+    }
+
+    /// POSTs `{"ip", "failures", "reason"}` to `ban_webhook`
+    fn call_webhook(&self, ip: IpAddr, failures: usize, reason: &str) {
+        let Some(url) = self.ban_webhook.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!(target: AUTH_TARGET, "No runtime to call ban webhook {}", url);
+            return;
+        };
+        let body = serde_json::json!({ "ip": ip, "failures": failures, "reason": reason }).to_string();
+        runtime.spawn(async move {
+            let sent = reqwest::Client::new()
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .timeout(Duration::from_secs(10))
+                .body(body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = sent {
+                error!(target: AUTH_TARGET, "Ban webhook {} failed: {}", url, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_failure_bans() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("banned");
+        let command = if cfg!(windows) {
+            vec!["cmd".to_string(), "/C".to_string(), format!("echo {{ip}}> {}", marker.display())]
+        } else {
+            vec!["sh".to_string(), "-c".to_string(), format!("echo {{ip}} > '{}'", marker.display())]
+        };
+        let failures = AuthFailures::new(3, Duration::from_secs(60), command, None);
+        let attacker = Some("192.168.1.50".parse().unwrap());
+
+        assert!(!failures.record(attacker, "csrf", "POST", "/api/v2/write"));
+        assert!(!failures.record(attacker, "csrf", "POST", "/api/v2/write"));
+        // Other addresses and loopback peers have counts of their own, or none
+        assert!(!failures.record(Some("10.0.0.9".parse().unwrap()), "origin", "GET", "/api/v2/mounts"));
+        for _ in 0..5 {
+            assert!(!failures.record(Some("127.0.0.1".parse().unwrap()), "csrf", "POST", "/api/v2/write"));
+        }
+        assert!(failures.record(attacker, "csrf", "POST", "/api/v2/write"));
+        assert!(!failures.record(attacker, "csrf", "POST", "/api/v2/write"));
+
+        let mut banned = String::new();
+        for _ in 0..100 {
+            banned = std::fs::read_to_string(&marker).unwrap_or_default();
+            if !banned.trim().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(banned.trim(), "192.168.1.50");
+
+        let unhooked = AuthFailures::new(1, Duration::from_secs(60), vec![], None);
+        assert!(!unhooked.record(attacker, "csrf", "POST", "/api/v2/write"));
+    }
+}
@@ -0,0 +1,139 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, Method},
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::models::ErrorResponse;
+use crate::security::SecurityManager;
+
+/// Cookie carrying the CSRF token issued to browser clients
+pub const CSRF_COOKIE_NAME: &str = "exex_csrf";
+
+/// Header browser clients must echo the cookie value in
+pub const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+
+/// Generates a fresh random CSRF token
+pub fn generate_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Compares two tokens without short-circuiting on the first mismatch
+pub fn tokens_match(expected: &str, provided: &str) -> bool {
+    if expected.len() != provided.len() {
+        return false;
+    }
+
+    expected
+        .bytes()
+        .zip(provided.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Middleware enforcing double-submit CSRF tokens on credentialed mutating requests
+///
+/// Requests without cookies cannot ride a browser session, so non-browser clients
+/// (curl, scripts, SDKs) are unaffected. A cookieless cross-site request is
+/// passed through as well; which peers may send it is left to `origin_guard`.
+pub async fn csrf_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let enabled = req
+        .app_data::<web::Data<Arc<SecurityManager>>>()
+        .map(|security| security.is_csrf_protection_enabled())
+        .unwrap_or(false);
+
+    let is_safe_method = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let is_credentialed = req.headers().contains_key(header::COOKIE);
+
+    if !enabled || is_safe_method || !is_credentialed {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    let cookie_token = req.cookie(CSRF_COOKIE_NAME).map(|c| c.value().to_string());
+    let header_token = req
+        .headers()
+        .get(CSRF_HEADER_NAME)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+
+    match (cookie_token, header_token) {
+        (Some(expected), Some(provided)) if tokens_match(&expected, &provided) => {
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        _ => {
+            warn!("CSRF validation failed for {} {}", req.method(), req.path());
+            if let Some(security) = req.app_data::<web::Data<Arc<SecurityManager>>>() {
+                let peer = req.peer_addr().map(|addr| addr.ip());
+                security.record_auth_failure(peer, "csrf", req.method().as_str(), req.path());
+            }
+            let response = HttpResponse::Forbidden().json(ErrorResponse {
+                error: "CSRF token missing or invalid".to_string(),
+            });
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{middleware::from_fn, App};
+
+    async fn ok_handler() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc123", "abc123"));
+        assert!(!tokens_match("abc123", "abc124"));
+        assert!(!tokens_match("abc123", "abc12"));
+        assert_ne!(generate_token(), generate_token());
+    }
+
+    #[actix_web::test]
+    async fn test_csrf_guard() {
+        let security = Arc::new(SecurityManager::new(get_default_config()));
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(security))
+                .wrap(from_fn(csrf_guard))
+                .route("/api/write", web::post().to(ok_handler)),
+        )
+        .await;
+
+        // Non-browser clients without cookies are not affected
+        let req = TestRequest::post().uri("/api/write").to_request();
+        assert_eq!(call_service(&app, req).await.status(), 200);
+
+        // Credentialed requests must echo the cookie in the header
+        let req = TestRequest::post()
+            .uri("/api/write")
+            .insert_header((header::COOKIE, "exex_csrf=token1"))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), 403);
+
+        let req = TestRequest::post()
+            .uri("/api/write")
+            .insert_header((header::COOKIE, "exex_csrf=token1"))
+            .insert_header((CSRF_HEADER_NAME, "token2"))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), 403);
+
+        let req = TestRequest::post()
+            .uri("/api/write")
+            .insert_header((header::COOKIE, "exex_csrf=token1"))
+            .insert_header((CSRF_HEADER_NAME, "token1"))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), 200);
+    }
+}
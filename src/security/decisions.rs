@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tracing::info;
+
+use crate::policy::Decision;
+
+/// Log target policy decisions are emitted under, so they can be routed or filtered apart
+pub const DECISION_TARGET: &str = "policy";
+
+/// What a policy decision was about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecisionKind {
+    Path,
+    Command,
+}
+
+/// One policy decision, serialized as a single JSON line
+///
+/// Field order is fixed so the lines can be compared and grepped as text.
+#[derive(Debug, Serialize)]
+pub struct DecisionEvent<'a> {
+    pub event: &'static str,
+    /// RFC 3339 UTC time of the decision
+    pub timestamp: String,
+    pub decision: Decision,
+    pub kind: DecisionKind,
+    /// Canonical path, or the command name without its arguments
+    pub subject: &'a str,
+    /// Which part of the policy decided: `allowed_paths`, `disallowed_paths`,
+    /// `whitelist`, `blacklist`, `workspace_policy`, `default` or `unresolvable`
+    pub reason: &'static str,
+    /// The rule or workspace root that matched, if any
+    pub rule: Option<String>,
+    /// Fraction of decisions like this one that are logged, for weighting counts
+    pub sample_rate: f64,
+}
+
+/// Emits allow and deny decisions at independent sample rates
+///
+/// Sampling is by count rather than at random: a rate of 0.01 logs exactly
+/// every hundredth allow, so counts recovered as `logged / sample_rate` are
+/// exact over any long enough window.
+#[derive(Debug)]
+pub struct DecisionLog {
+    allow_rate: f64,
+    deny_rate: f64,
+    allows: AtomicU64,
+    denies: AtomicU64,
+}
+
+impl DecisionLog {
+    /// Rates are clamped to 0..=1; 0 disables logging of that decision
+    pub fn new(allow_rate: f64, deny_rate: f64) -> Self {
+        Self {
+            allow_rate: allow_rate.clamp(0.0, 1.0),
+            deny_rate: deny_rate.clamp(0.0, 1.0),
+            allows: AtomicU64::new(0),
+            denies: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether this decision falls in the sample; advances the count for its outcome
+    fn sampled(&self, decision: Decision) -> Option<f64> {
+        let (rate, count) = match decision {
+            Decision::Allow => (self.allow_rate, &self.allows),
+            Decision::Deny => (self.deny_rate, &self.denies),
+        };
+        if rate <= 0.0 {
+            return None;
+        }
+        let n = count.fetch_add(1, Ordering::Relaxed) as f64;
+        // Logged when the running total of `rate` crosses the next whole number
+        (((n + 1.0) * rate).floor() > (n * rate).floor()).then_some(rate)
+    }
+
+    /// Logs a decision if it is sampled; returns `allowed` for use in tail position
+    pub fn record(
+        &self,
+        allowed: bool,
+        kind: DecisionKind,
+        subject: &str,
+        reason: &'static str,
+        rule: Option<String>,
+    ) -> bool {
+        let decision = if allowed { Decision::Allow } else { Decision::Deny };
+        if let Some(sample_rate) = self.sampled(decision) {
+            let event = DecisionEvent {
+                event: "policy_decision",
+                timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+                decision,
+                kind,
+                subject,
+                reason,
+                rule,
+                sample_rate,
+            };
+            if let Ok(line) = serde_json::to_string(&event) {
+                info!(target: DECISION_TARGET, "{}", line);
+            }
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_sampling() {
+        let log = DecisionLog::new(0.25, 1.0);
+        let allows = (0..100).filter(|_| log.sampled(Decision::Allow).is_some()).count();
+        let denies = (0..100).filter(|_| log.sampled(Decision::Deny).is_some()).count();
+        assert_eq!((allows, denies), (25, 100));
+
+        let off = DecisionLog::new(0.0, 7.0);
+        assert!(off.sampled(Decision::Allow).is_none());
+        assert_eq!(off.sampled(Decision::Deny), Some(1.0));
+
+        let event = DecisionEvent {
+            event: "policy_decision",
+            timestamp: "2024-01-01T00:00:00.000Z".to_string(),
+            decision: Decision::Deny,
+            kind: DecisionKind::Path,
+            subject: "/etc/passwd",
+            reason: "disallowed_paths",
+            rule: Some("/etc".to_string()),
+            sample_rate: 1.0,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"policy_decision","timestamp":"2024-01-01T00:00:00.000Z","decision":"deny","kind":"path","subject":"/etc/passwd","reason":"disallowed_paths","rule":"/etc","sample_rate":1.0}"#
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::config::get_config_dir;
+
+/// Marker identifying files written by EXEX's at-rest encryption
+const MAGIC: &[u8; 8] = b"EXEXENC1";
+
+/// Length of the ChaCha20-Poly1305 nonce stored after the marker
+const NONCE_LEN: usize = 12;
+
+/// Environment variable that supplies the key (hex) instead of the key file
+pub const KEY_ENV_VAR: &str = "EXEX_ENCRYPTION_KEY";
+
+/// Encrypts and decrypts file content with ChaCha20-Poly1305
+///
+/// Encrypted files are laid out as `MAGIC || nonce || ciphertext+tag`.
+pub struct ContentCipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl ContentCipher {
+    /// Creates a cipher from a raw 32-byte key
+    pub fn from_key(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// Loads the key from `EXEX_ENCRYPTION_KEY` or the key file, generating the file if missing
+    ///
+    /// Deployments that keep the key in an OS keyring can export it through the
+    /// environment variable so it never touches disk.
+    pub fn load_or_create(key_file: &Path) -> Result<Self, String> {
+        if let Ok(hex_key) = std::env::var(KEY_ENV_VAR) {
+            let bytes = hex::decode(hex_key.trim())
+                .map_err(|e| format!("{} is not valid hex: {}", KEY_ENV_VAR, e))?;
+            let key: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| format!("{} must be 32 bytes", KEY_ENV_VAR))?;
+            return Ok(Self::from_key(&key));
+        }
+
+        if key_file.exists() {
+            let bytes = fs::read(key_file).map_err(|e| format!("Failed to read encryption key: {}", e))?;
+            let key: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| format!("Encryption key file {:?} must contain 32 bytes", key_file))?;
+            return Ok(Self::from_key(&key));
+        }
+
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        write_key_file(key_file, key.as_slice())?;
+        info!("Generated new encryption key at {:?}", key_file);
+        Ok(Self {
+            cipher: ChaCha20Poly1305::new(&key),
+        })
+    }
+
+    /// Checks if data carries the EXEX encryption marker
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    /// Encrypts plaintext with a fresh random nonce
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Failed to encrypt content".to_string())?;
+
+        let mut output = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(nonce.as_slice());
+        output.extend_from_slice(&ciphertext);
+        Ok(output)
+    }
+
+    /// Decrypts data produced by [`ContentCipher::encrypt`]
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        if !Self::is_encrypted(data) || data.len() < MAGIC.len() + NONCE_LEN {
+            return Err("Content is not in EXEX encrypted format".to_string());
+        }
+
+        let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt content (wrong key or corrupted file)".to_string())
+    }
+}
+
+/// The configured key file, or `exex.key` in the config directory
+pub fn key_file_path(configured: Option<&str>) -> PathBuf {
+    configured
+        .map(PathBuf::from)
+        .or_else(|| get_config_dir().ok().map(|dir| dir.join("exex.key")))
+        .unwrap_or_else(|| PathBuf::from("exex.key"))
+}
+
+fn write_key_file(key_file: &Path, key: &[u8]) -> Result<(), String> {
+    if let Some(parent) = key_file.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create key directory: {}", e))?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(key_file)
+        .map_err(|e| format!("Failed to create encryption key file: {}", e))?;
+    file.write_all(key)
+        .map_err(|e| format!("Failed to write encryption key file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let cipher = ContentCipher::from_key(&[7u8; 32]);
+        let encrypted = cipher.encrypt(b"api_token=secret").unwrap();
+
+        assert!(ContentCipher::is_encrypted(&encrypted));
+        assert!(!encrypted.windows(6).any(|w| w == b"secret"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"api_token=secret");
+    }
+
+    #[test]
+    fn test_wrong_key_and_plaintext() {
+        let encrypted = ContentCipher::from_key(&[1u8; 32]).encrypt(b"data").unwrap();
+        let other = ContentCipher::from_key(&[2u8; 32]);
+
+        assert!(other.decrypt(&encrypted).is_err());
+        assert!(other.decrypt(b"plain text").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use globset::GlobSet;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::security::workspace::{build_glob_set, POLICY_DIR};
+
+/// Ignore file inside a workspace's `.exex` directory, one pattern per line
+pub const IGNORE_FILE: &str = "ignore";
+
+/// Entries that a tree walk (scan, search, watch) leaves out
+///
+/// Global and per-request patterns are relative to the walked root, and
+/// `.exex/ignore` patterns to their workspace root. Ignored directories are
+/// not descended into; the root itself is never ignored.
+#[derive(Debug, Default)]
+pub struct IgnoreMatcher {
+    root: PathBuf,
+    /// Pattern sets, each with the walked root's path relative to the set's base
+    sets: Vec<(PathBuf, GlobSet)>,
+}
+
+impl IgnoreMatcher {
+    /// Matches `patterns` below the walked `root`
+    pub fn new(root: &Path, patterns: &[String]) -> Result<Self, String> {
+        let mut matcher = Self {
+            root: root.to_path_buf(),
+            sets: Vec::new(),
+        };
+        matcher.add(PathBuf::new(), patterns)?;
+        Ok(matcher)
+    }
+
+    fn add(&mut self, prefix: PathBuf, patterns: &[String]) -> Result<(), String> {
+        if !patterns.is_empty() {
+            let set = build_glob_set(patterns).map_err(|e| format!("Invalid ignore pattern {}", e))?;
+            self.sets.push((prefix, set));
+        }
+        Ok(())
+    }
+
+    /// Adds the `.exex/ignore` files of every workspace enclosing the root, given in canonical form
+    ///
+    /// Unreadable files and invalid lines are skipped with a warning, since
+    /// ignoring too little is harmless.
+    pub fn add_workspace_files(&mut self, canonical_root: &Path) {
+        for dir in canonical_root.ancestors() {
+            let file = dir.join(POLICY_DIR).join(IGNORE_FILE);
+            let Ok(content) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            let prefix = canonical_root.strip_prefix(dir).unwrap_or(Path::new("")).to_path_buf();
+            for pattern in parse_ignore_file(&content) {
+                if let Err(e) = self.add(prefix.clone(), std::slice::from_ref(&pattern)) {
+                    warn!("Skipping a line of {:?}: {}", file, e);
+                }
+            }
+        }
+    }
+
+    /// Whether an entry below the walked root is ignored
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        !relative.as_os_str().is_empty()
+            && self.sets.iter().any(|(prefix, set)| set.is_match(prefix.join(relative)))
+    }
+}
+
+/// `.gitignore` files met during a walk, each applying to the entries below its directory
+///
+/// Negated (`!`) patterns are not supported and are skipped, so a walk may
+/// leave out a little more than git would.
+#[derive(Debug, Default)]
+pub struct GitIgnores {
+    sets: Vec<(PathBuf, GlobSet)>,
+}
+
+impl GitIgnores {
+    /// Loads the `.gitignore` of `root` and, inside a repository, those of its ancestors up to the repository root
+    pub fn for_root(root: &Path) -> Self {
+        let mut ignores = Self::default();
+        let repository = root.ancestors().position(|dir| dir.join(".git").exists());
+        for dir in root.ancestors().take(repository.map_or(1, |depth| depth + 1)) {
+            ignores.enter(dir);
+        }
+        ignores
+    }
+
+    /// Adds `dir/.gitignore`, if there is one
+    pub fn enter(&mut self, dir: &Path) {
+        let file = dir.join(".gitignore");
+        let Ok(content) = std::fs::read_to_string(&file) else {
+            return;
+        };
+        let patterns: Vec<String> = parse_ignore_file(&content).into_iter().filter(|p| !p.starts_with('!')).collect();
+        match build_glob_set(&patterns) {
+            Ok(set) if !patterns.is_empty() => self.sets.push((dir.to_path_buf(), set)),
+            Ok(_) => {}
+            Err(e) => warn!("Skipping {:?}: invalid pattern {}", file, e),
+        }
+    }
+
+    /// Whether a `.gitignore` above `path` matches it
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.sets
+            .iter()
+            .any(|(dir, set)| path.strip_prefix(dir).is_ok_and(|relative| set.is_match(relative)))
+    }
+}
+
+/// Patterns in an ignore file, skipping blank lines and `#` comments
+pub fn parse_ignore_file(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::default_ignore_patterns;
+
+    #[test]
+    fn test_ignore_matcher() {
+        let root = Path::new("/work/repo/crates");
+        let mut matcher = IgnoreMatcher::new(root, &default_ignore_patterns()).unwrap();
+        // A workspace file at /work/repo
+        matcher
+            .add(PathBuf::from("crates"), &parse_ignore_file("# generated\n\n*.log\n/crates/dist\n"))
+            .unwrap();
+
+        assert!(matcher.is_ignored(&root.join("app/target")));
+        assert!(matcher.is_ignored(&root.join("web/node_modules")));
+        assert!(matcher.is_ignored(&root.join("app/build.log")));
+        assert!(matcher.is_ignored(&root.join("dist")));
+        assert!(!matcher.is_ignored(&root.join("app/dist")));
+        assert!(!matcher.is_ignored(&root.join("app/src/target.rs")));
+        assert!(!matcher.is_ignored(root));
+        assert!(!matcher.is_ignored(Path::new("/elsewhere/target")));
+
+        // A scan may start inside an ignored tree
+        let inside = IgnoreMatcher::new(&root.join("app/target"), &default_ignore_patterns()).unwrap();
+        assert!(!inside.is_ignored(&root.join("app/target/debug")));
+
+        assert!(IgnoreMatcher::new(root, &[]).unwrap().sets.is_empty());
+        assert!(IgnoreMatcher::new(root, &["[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_gitignores() {
+        let repo = std::env::temp_dir().join(format!("exex-gitignore-{}", uuid::Uuid::new_v4().simple()));
+        let app = repo.join("app");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(app.join("web")).unwrap();
+        std::fs::write(repo.join(".gitignore"), "*.log\n/dist\n").unwrap();
+        std::fs::write(app.join("web").join(".gitignore"), "build/\n!keep.log\n").unwrap();
+
+        // The repository's .gitignore applies to a walk starting below it
+        let mut ignores = GitIgnores::for_root(&app);
+        assert!(ignores.is_ignored(&app.join("debug.log")));
+        assert!(!ignores.is_ignored(&app.join("dist")));
+        assert!(!ignores.is_ignored(&app.join("web/build")));
+
+        ignores.enter(&app.join("web"));
+        assert!(ignores.is_ignored(&app.join("web/build")));
+        assert!(!ignores.is_ignored(&app.join("build")));
+
+        // Outside a repository only the root's own file counts
+        assert!(GitIgnores::for_root(&std::env::temp_dir()).sets.iter().all(|(dir, _)| dir == &std::env::temp_dir()));
+
+        std::fs::remove_dir_all(&repo).unwrap();
+    }
+}
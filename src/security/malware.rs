@@ -0,0 +1,209 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+use crate::models::MalwareScanConfig;
+
+/// Size of the chunks streamed to clamd
+const CLAMD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Timeout for clamd socket reads and writes
+const CLAMD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Outcome of a malware scan
+#[derive(Debug, PartialEq)]
+pub enum ScanVerdict {
+    Clean,
+    Infected(String),
+}
+
+/// Scans content through clamd or an external scanner command
+pub struct MalwareScanner {
+    clamd_socket: Option<String>,
+    command: Option<Vec<String>>,
+    quarantine_dir: Option<PathBuf>,
+}
+
+impl MalwareScanner {
+    /// Builds a scanner from configuration, returning None when scanning is disabled
+    pub fn from_config(config: Option<&MalwareScanConfig>) -> Option<Self> {
+        let config = config?;
+        if !config.enabled {
+            return None;
+        }
+
+        if config.clamd_socket.is_none() && config.command.as_ref().is_none_or(|c| c.is_empty()) {
+            warn!("Malware scanning enabled but neither clamd_socket nor command is configured");
+            return None;
+        }
+
+        Some(Self {
+            clamd_socket: config.clamd_socket.clone(),
+            command: config.command.clone().filter(|c| !c.is_empty()),
+            quarantine_dir: config.quarantine_dir.as_ref().map(PathBuf::from),
+        })
+    }
+
+    /// Scans a buffer, preferring clamd when configured
+    pub fn scan(&self, data: &[u8]) -> Result<ScanVerdict, String> {
+        if let Some(socket) = &self.clamd_socket {
+            return scan_with_clamd(socket, data);
+        }
+
+        match &self.command {
+            Some(command) => scan_with_command(command, data),
+            None => Ok(ScanVerdict::Clean),
+        }
+    }
+
+    /// Stores infected content in the quarantine directory for later inspection
+    pub fn quarantine(&self, original_path: &Path, data: &[u8]) -> Result<Option<PathBuf>, String> {
+        let Some(dir) = &self.quarantine_dir else {
+            return Ok(None);
+        };
+
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create quarantine directory: {}", e))?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let file_name = original_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "content".to_string());
+        let target = dir.join(format!("{}-{}.quarantined", timestamp, file_name));
+
+        fs::write(&target, data).map_err(|e| format!("Failed to write quarantine file: {}", e))?;
+        Ok(Some(target))
+    }
+}
+
+/// Streams data to clamd using the INSTREAM protocol
+///
+/// `socket` is either a Unix socket path or `tcp://host:port`.
+fn scan_with_clamd(socket: &str, data: &[u8]) -> Result<ScanVerdict, String> {
+    debug!("Scanning {} bytes with clamd at {}", data.len(), socket);
+
+    let reply = if let Some(address) = socket.strip_prefix("tcp://") {
+        let stream = TcpStream::connect(address).map_err(|e| format!("Failed to connect to clamd: {}", e))?;
+        stream.set_read_timeout(Some(CLAMD_TIMEOUT)).ok();
+        stream.set_write_timeout(Some(CLAMD_TIMEOUT)).ok();
+        clamd_instream(stream, data)?
+    } else {
+        #[cfg(unix)]
+        {
+            let stream = std::os::unix::net::UnixStream::connect(socket)
+                .map_err(|e| format!("Failed to connect to clamd: {}", e))?;
+            stream.set_read_timeout(Some(CLAMD_TIMEOUT)).ok();
+            stream.set_write_timeout(Some(CLAMD_TIMEOUT)).ok();
+            clamd_instream(stream, data)?
+        }
+        #[cfg(not(unix))]
+        {
+            return Err(format!("Unix sockets are not supported on this platform: {}", socket));
+        }
+    };
+
+    parse_clamd_reply(&reply)
+}
+
+fn clamd_instream<S: Read + Write>(mut stream: S, data: &[u8]) -> Result<String, String> {
+    let io_err = |e: std::io::Error| format!("clamd communication failed: {}", e);
+
+    stream.write_all(b"zINSTREAM\0").map_err(io_err)?;
+    for chunk in data.chunks(CLAMD_CHUNK_SIZE) {
+        stream.write_all(&(chunk.len() as u32).to_be_bytes()).map_err(io_err)?;
+        stream.write_all(chunk).map_err(io_err)?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).map_err(io_err)?;
+    stream.flush().map_err(io_err)?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).map_err(io_err)?;
+    Ok(String::from_utf8_lossy(&reply).trim_end_matches('\0').trim().to_string())
+}
+
+/// Parses a clamd reply such as `stream: OK` or `stream: Eicar-Signature FOUND`
+pub fn parse_clamd_reply(reply: &str) -> Result<ScanVerdict, String> {
+    let status = reply.split_once(": ").map(|(_, s)| s).unwrap_or(reply);
+
+    if status == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = status.strip_suffix(" FOUND") {
+        Ok(ScanVerdict::Infected(signature.to_string()))
+    } else {
+        Err(format!("Unexpected clamd reply: {}", reply))
+    }
+}
+
+/// Runs an external scanner against a temporary copy of the data
+///
+/// The command follows clamscan conventions: exit code 0 means clean, 1 means infected.
+/// `{file}` in the arguments is replaced with the temporary file path.
+fn scan_with_command(command: &[String], data: &[u8]) -> Result<ScanVerdict, String> {
+    let temp_path = std::env::temp_dir().join(format!("exex-scan-{}", uuid::Uuid::new_v4().simple()));
+    fs::write(&temp_path, data).map_err(|e| format!("Failed to stage content for scanning: {}", e))?;
+
+    let temp_str = temp_path.to_string_lossy().to_string();
+    let mut args: Vec<String> = command[1..].iter().map(|a| a.replace("{file}", &temp_str)).collect();
+    if !command[1..].iter().any(|a| a.contains("{file}")) {
+        args.push(temp_str);
+    }
+
+    debug!("Scanning {} bytes with {}", data.len(), command[0]);
+    let output = Command::new(&command[0]).args(&args).output();
+    let _ = fs::remove_file(&temp_path);
+
+    let output = output.map_err(|e| format!("Failed to run malware scanner: {}", e))?;
+    match output.status.code() {
+        Some(0) => Ok(ScanVerdict::Clean),
+        Some(1) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let signature = stdout
+                .lines()
+                .find_map(|line| line.strip_suffix(" FOUND"))
+                .and_then(|line| line.rsplit(": ").next())
+                .unwrap_or("unknown threat")
+                .to_string();
+            Ok(ScanVerdict::Infected(signature))
+        }
+        code => Err(format!(
+            "Malware scanner exited with {:?}: {}",
+            code,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clamd_reply() {
+        assert_eq!(parse_clamd_reply("stream: OK"), Ok(ScanVerdict::Clean));
+        assert_eq!(
+            parse_clamd_reply("stream: Eicar-Signature FOUND"),
+            Ok(ScanVerdict::Infected("Eicar-Signature".to_string()))
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR").is_err());
+    }
+
+    #[test]
+    fn test_disabled_scanner() {
+        assert!(MalwareScanner::from_config(None).is_none());
+
+        let config = MalwareScanConfig {
+            enabled: true,
+            clamd_socket: None,
+            command: None,
+            quarantine_dir: None,
+        };
+        assert!(MalwareScanner::from_config(Some(&config)).is_none());
+    }
+}
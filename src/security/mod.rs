@@ -1,76 +1,190 @@
-use std::collections::HashSet;
+pub mod auth_failures;
+pub mod csrf;
+pub mod decisions;
+pub mod encryption;
+pub mod ignore;
+pub mod malware;
+pub mod origin;
+pub mod path_cache;
+pub mod path_limits;
+pub mod path_rules;
+pub mod redact;
+pub mod secrets;
+pub mod throttle;
+pub mod tls;
+pub mod workspace;
+
+use globset::GlobSet;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
-use crate::models::Config;
+use std::time::Duration;
+use tracing::info;
+use crate::config::{default_allowed_shells, default_ignore_patterns, default_protected_patterns, get_config_dir};
+use crate::models::{Config, EnvOptions, OriginAccess, OriginClass, ProcessPriority, ShellKind, ThrottleState};
+use crate::platform::{display_path, expand_path_entries, extended_path, search_path};
+use crate::security::auth_failures::AuthFailures;
+use crate::security::decisions::{DecisionKind, DecisionLog};
+use crate::security::encryption::{key_file_path, ContentCipher};
+use crate::security::ignore::IgnoreMatcher;
+use crate::content::format::Formatters;
+use crate::security::malware::{MalwareScanner, ScanVerdict};
+use crate::security::path_cache::PathCache;
+use crate::security::path_rules::{normalize_rule_path, PathRules};
+use crate::security::redact::Redactor;
+use crate::security::throttle::{ClientId, WriteThrottle};
+use crate::security::workspace::{build_glob_set, is_policy_path, WorkspacePolicies};
 
 /// Application state containing security policies
 pub struct SecurityManager {
-    disallowed_paths: HashSet<PathBuf>,
-    allowed_paths: HashSet<PathBuf>,
+    disallowed_paths: PathRules,
+    allowed_paths: PathRules,
     command_whitelist: HashSet<String>,
     command_blacklist: HashSet<String>,
+    command_aliases: HashMap<String, String>,
     max_file_size_mb: u64,
+    disk_reserve_mb: u64,
+    max_affected_entries: usize,
+    default_priority: ProcessPriority,
+    default_exec_timeout_secs: u64,
+    max_spawned_processes: usize,
+    csrf_protection: bool,
+    redactor: Redactor,
+    malware_scanner: Option<MalwareScanner>,
+    formatters: Formatters,
+    encrypted_paths: Vec<PathBuf>,
+    content_cipher: Option<ContentCipher>,
+    path_cache: PathCache,
+    write_throttle: WriteThrottle,
+    workspaces: WorkspacePolicies,
+    ignore_patterns: Vec<String>,
+    file_mode: u32,
+    dir_mode: u32,
+    max_mode: u32,
+    protected: GlobSet,
+    allow_protected_override: bool,
+    omit_private_metadata: bool,
+    origin_access: HashMap<OriginClass, OriginAccess>,
+    decisions: DecisionLog,
+    auth_failures: AuthFailures,
+    allowed_shells: Vec<ShellKind>,
+    allow_login_shell: bool,
+    blocked_env_vars: Vec<String>,
+    child_path: Option<OsString>,
+    internal_paths: Vec<PathBuf>,
+}
+
+/// Variables that change which program runs or inject code into it, so clients may not set them
+const BLOCKED_ENV_VARS: &[&str] = &["PATH", "BASH_ENV", "ENV", "NODE_OPTIONS"];
+
+/// Dynamic loader variables (`LD_PRELOAD`, `DYLD_INSERT_LIBRARIES`, ...)
+const BLOCKED_ENV_PREFIXES: &[&str] = &["LD_", "DYLD_"];
+
+/// Parses octal permission bits such as `644` or `0o755`
+pub fn parse_mode(mode: &str) -> Result<u32, String> {
+    let digits = mode.trim().trim_start_matches("0o");
+    match u32::from_str_radix(digits, 8) {
+        Ok(bits) if !digits.is_empty() && bits <= 0o777 => Ok(bits),
+        _ => Err(format!("'{}' is not an octal permission mode between 000 and 777", mode)),
+    }
+}
+
+/// Compiles protected path patterns, which match at any depth of an absolute path
+///
+/// `dir/**` also matches `dir` itself, so the directory cannot be deleted or
+/// replaced as a whole.
+pub fn build_protected_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut expanded = Vec::new();
+    for pattern in patterns {
+        let pattern = format!("**/{}", pattern.trim_start_matches('/'));
+        if let Some(dir) = pattern.strip_suffix("/**") {
+            expanded.push(dir.to_string());
+        }
+        expanded.push(pattern);
+    }
+    build_glob_set(&expanded)
+}
+
+/// The server's own state: the config directory, the encryption key and the audit log
+///
+/// These are denied whatever `allowed_paths` says, so a client can neither read
+/// the key and secrets nor rewrite the config, keyring or audit trail.
+fn internal_paths(config: &Config) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = get_config_dir().ok().into_iter().collect();
+    paths.push(key_file_path(config.security.encryption_key_file.as_deref()));
+    paths.push(PathBuf::from(&config.logging.audit_file));
+
+    paths
+        .into_iter()
+        .filter(|path| !path.as_os_str().is_empty())
+        .map(|path| {
+            let path = extended_path(&std::path::absolute(&path).unwrap_or(path));
+            match (path.canonicalize(), path.parent(), path.file_name()) {
+                (Ok(canonical), _, _) => canonical,
+                (Err(_), Some(parent), Some(name)) => parent.canonicalize().map_or(path.clone(), |p| p.join(name)),
+                _ => path,
+            }
+        })
+        .collect()
+}
+
+/// Command name without path or extension, as matched against the command lists
+pub fn command_name(command: &str) -> String {
+    // Extract the base command (first word)
+    let base_command = command.split_whitespace().next().unwrap_or(command);
+
+    // Remove path and extension to get base command name
+    match Path::new(base_command).file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => base_command.to_string(),
+    }
 }
 
 impl SecurityManager {
     /// Creates a new SecurityManager from configuration
     pub fn new(config: Config) -> Self {
-        use tracing::debug;
-        
-        let disallowed_paths = config
+        // CSRF tokens only matter when browsers are allowed to send credentials
+        let csrf_protection = config.server.allow_credentials.unwrap_or(true)
+            && config.server.csrf_protection.unwrap_or(true);
+
+        let malware_scanner = MalwareScanner::from_config(config.security.malware_scan.as_ref());
+        let redactor = Redactor::new(config.logging.redact_patterns.as_deref().unwrap_or_default());
+
+        let internal_paths = internal_paths(&config);
+
+        let path_cache = PathCache::new(
+            Duration::from_secs(config.security.path_cache_ttl_secs.unwrap_or(5)),
+            config.security.path_cache_capacity.unwrap_or(4096),
+        );
+
+        let write_throttle = WriteThrottle::new(
+            config.security.write_limit_mb_per_sec.unwrap_or(0) * 1024 * 1024,
+        );
+
+        let disallowed_paths = PathRules::new(&config.security.disallowed_paths, "disallowed path");
+        let allowed_paths = PathRules::new(&config.security.allowed_paths, "allowed path exception");
+
+        let encrypted_paths: Vec<PathBuf> = config
             .security
-            .disallowed_paths
-            .into_iter()
-            .filter_map(|p| {
-                // Normalize paths based on platform
-                let normalized = if cfg!(target_os = "windows") {
-                    p.replace('/', "\\")
-                } else {
-                    p.replace('\\', "/")
-                };
-                let path = PathBuf::from(normalized);
-                
-                // Try to canonicalize the disallowed path
-                match path.canonicalize() {
-                    Ok(canonical) => {
-                        debug!("Added disallowed path: {:?} (canonical: {:?})", path, canonical);
-                        Some(canonical)
-                    },
-                    Err(e) => {
-                        debug!("Could not canonicalize disallowed path {:?}: {}, using as-is", path, e);
-                        // If canonicalization fails, use the path as-is (it might not exist yet)
-                        Some(path)
-                    }
-                }
-            })
+            .encrypted_paths
+            .unwrap_or_default()
+            .iter()
+            .map(|p| normalize_rule_path(p, "encrypted path"))
             .collect();
-            
-        let allowed_paths = config
-            .security
-            .allowed_paths
-            .into_iter()
-            .filter_map(|p| {
-                // Normalize paths based on platform
-                let normalized = if cfg!(target_os = "windows") {
-                    p.replace('/', "\\")
-                } else {
-                    p.replace('\\', "/")
-                };
-                let path = PathBuf::from(normalized);
-                
-                // Try to canonicalize the allowed path
-                match path.canonicalize() {
-                    Ok(canonical) => {
-                        debug!("Added allowed path exception: {:?} (canonical: {:?})", path, canonical);
-                        Some(canonical)
-                    },
-                    Err(e) => {
-                        debug!("Could not canonicalize allowed path {:?}: {}, using as-is", path, e);
-                        // If canonicalization fails, use the path as-is (it might not exist yet)
-                        Some(path)
-                    }
+
+        let content_cipher = if encrypted_paths.is_empty() {
+            None
+        } else {
+            let key_file = key_file_path(config.security.encryption_key_file.as_deref());
+            match ContentCipher::load_or_create(&key_file) {
+                Ok(cipher) => Some(cipher),
+                Err(e) => {
+                    tracing::error!("Encryption at rest unavailable: {}", e);
+                    None
                 }
-            })
-            .collect();
+            }
+        };
             
         let command_whitelist = config
             .security
@@ -84,51 +198,283 @@ impl SecurityManager {
             .unwrap_or_default()
             .into_iter()
             .collect();
+
+        // Modes were checked by validate_config
+        let mode = |mode: Option<&String>, default| mode.and_then(|m| parse_mode(m).ok()).unwrap_or(default);
+        let file_mode = mode(config.security.file_mode.as_ref(), 0o644);
+        let dir_mode = mode(config.security.dir_mode.as_ref(), 0o755);
+        let max_mode = mode(config.security.max_mode.as_ref(), 0o775);
+
+        // Checked by validate_config as well; an invalid list that gets here anyway protects everything
+        let protected = build_protected_set(
+            &config.security.protected_patterns.unwrap_or_else(default_protected_patterns),
+        )
+        .unwrap_or_else(|e| {
+            tracing::error!("Invalid protected pattern {}; treating every path as protected", e);
+            build_protected_set(&["**".to_string()]).expect("** is a valid pattern")
+        });
         
         Self { 
             disallowed_paths, 
             allowed_paths,
             command_whitelist,
             command_blacklist,
+            command_aliases: config.security.command_aliases.unwrap_or_default(),
             max_file_size_mb: config.security.max_file_size_mb,
+            disk_reserve_mb: config.security.disk_reserve_mb.unwrap_or(256),
+            max_affected_entries: config.security.max_affected_entries.unwrap_or(10_000),
+            default_priority: config.security.default_priority.unwrap_or_default(),
+            default_exec_timeout_secs: config.security.default_exec_timeout_secs.unwrap_or(0),
+            max_spawned_processes: config.security.max_spawned_processes.unwrap_or(32),
+            csrf_protection,
+            redactor,
+            malware_scanner,
+            formatters: Formatters::new(config.formatters.unwrap_or_default()),
+            encrypted_paths,
+            content_cipher,
+            path_cache,
+            write_throttle,
+            workspaces: WorkspacePolicies::default(),
+            ignore_patterns: config.ignore_patterns.unwrap_or_else(default_ignore_patterns),
+            file_mode,
+            dir_mode,
+            max_mode,
+            protected,
+            allow_protected_override: config.security.allow_protected_override.unwrap_or(false),
+            omit_private_metadata: config.security.omit_private_metadata.unwrap_or(false),
+            origin_access: config.security.origin_access.unwrap_or_default(),
+            decisions: DecisionLog::new(
+                config.logging.policy_allow_sample_rate.unwrap_or(0.0),
+                config.logging.policy_deny_sample_rate.unwrap_or(1.0),
+            ),
+            auth_failures: AuthFailures::new(
+                config.security.ban_after_failures.unwrap_or(5),
+                Duration::from_secs(config.security.ban_window_secs.unwrap_or(600)),
+                config.security.ban_command.unwrap_or_default(),
+                config.security.ban_webhook,
+            ),
+            allowed_shells: config.security.allowed_shells.unwrap_or_else(default_allowed_shells),
+            allow_login_shell: config.security.allow_login_shell.unwrap_or(true),
+            blocked_env_vars: config
+                .security
+                .blocked_env_vars
+                .unwrap_or_default()
+                .iter()
+                .map(|name| name.trim().to_ascii_uppercase())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            child_path: search_path(&expand_path_entries(&config.security.extra_path_entries.unwrap_or_default())),
+            internal_paths,
+        }
+    }
+
+    /// Whether a path matches `security.protected_patterns`
+    pub fn is_protected_path(&self, path: &Path) -> bool {
+        // Unresolvable paths are matched as given; the path policy refuses them anyway
+        !self.protected.is_empty()
+            && self.protected.is_match(self.resolve_path(path).unwrap_or_else(|| path.to_path_buf()))
+    }
+
+    /// Checks a change to a path: protected paths need `force`, and `force` needs `security.allow_protected_override`
+    pub fn check_protected(&self, path: &Path, force: bool) -> Result<(), String> {
+        if !self.is_protected_path(path) {
+            return Ok(());
+        }
+        self.check_forced(path, force)
+    }
+
+    /// Checks a recursive change to a directory, which is protected if anything below it is
+    ///
+    /// Stops at the first protected entry; symlinks are not followed. A tree
+    /// holding the server's own state is refused even with `force`.
+    pub fn check_protected_tree(&self, root: &Path, force: bool) -> Result<(), String> {
+        self.check_internal_tree(root)?;
+        if self.is_protected_path(root) {
+            return self.check_forced(root, force);
+        }
+        let Some(canonical_root) = self.resolve_path(root).filter(|_| !self.protected.is_empty()) else {
+            return Ok(());
+        };
+        match self.first_protected_under(&canonical_root) {
+            Some(protected) => self.check_forced(&protected, force),
+            None => Ok(()),
         }
     }
 
+    /// Refuses a directory whose removal or move would take the config directory, key or audit log with it
+    pub fn check_internal_tree(&self, root: &Path) -> Result<(), String> {
+        match self
+            .resolve_path(root)
+            .and_then(|canonical| self.internal_paths.iter().find(|internal| internal.starts_with(&canonical)))
+        {
+            Some(internal) => Err(format!("{} holds the server's own state and cannot be changed", display_path(internal))),
+            None => Ok(()),
+        }
+    }
+
+    /// Unreadable directories are skipped; the operation cannot remove them either
+    fn first_protected_under(&self, root: &Path) -> Option<PathBuf> {
+        let mut stack = vec![root.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if self.protected.is_match(&path) {
+                    return Some(path);
+                }
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    stack.push(path);
+                }
+            }
+        }
+        None
+    }
+
+    fn check_forced(&self, path: &Path, force: bool) -> Result<(), String> {
+        let shown = display_path(path);
+        if !force {
+            return Err(format!("{} is protected; set force to change it", shown));
+        }
+        if !self.allow_protected_override {
+            return Err(format!(
+                "{} is protected and security.allow_protected_override is off",
+                shown
+            ));
+        }
+        info!(target: "audit", "protected path {} changed with force", shown);
+        Ok(())
+    }
+
+    /// Permissions for a file or directory being created, honoring a request's mode within `max_mode`
+    pub fn creation_mode(&self, requested: Option<u32>, directory: bool) -> Result<u32, String> {
+        match requested {
+            Some(mode) if mode & !self.max_mode != 0 => Err(format!(
+                "Mode {:03o} exceeds the allowed maximum {:03o}",
+                mode, self.max_mode
+            )),
+            Some(mode) => Ok(mode),
+            None if directory => Ok(self.dir_mode),
+            None => Ok(self.file_mode),
+        }
+    }
+
+    /// Permissions for directories created along the way, such as missing parents
+    pub fn dir_mode(&self) -> u32 {
+        self.dir_mode
+    }
+
+    /// Permissions for files EXEX creates without a requested mode
+    pub fn file_mode(&self) -> u32 {
+        self.file_mode
+    }
+
+    /// Masks secrets (tokens, passwords, URL credentials) in text destined for logs
+    pub fn redact(&self, text: &str) -> String {
+        self.redactor.redact(text)
+    }
+
+    /// Masks secrets in each element of an argument list
+    pub fn redact_args(&self, args: &[String]) -> Vec<String> {
+        self.redactor.redact_args(args)
+    }
+
+    /// Checks if written content must pass the malware scan hook
+    pub fn is_malware_scan_enabled(&self) -> bool {
+        self.malware_scanner.is_some()
+    }
+
+    /// Scans content destined for `path`, quarantining anything flagged as infected
+    ///
+    /// This performs blocking socket/process IO and must run off the async executor.
+    pub fn scan_content(&self, path: &Path, data: &[u8]) -> Result<ScanVerdict, String> {
+        use tracing::{error, warn};
+
+        let Some(scanner) = &self.malware_scanner else {
+            return Ok(ScanVerdict::Clean);
+        };
+
+        let verdict = scanner.scan(data)?;
+        if let ScanVerdict::Infected(signature) = &verdict {
+            warn!("Malware detected in content for {:?}: {}", path, signature);
+            match scanner.quarantine(path, data) {
+                Ok(Some(target)) => warn!("Quarantined content at {:?}", target),
+                Ok(None) => {}
+                Err(e) => error!("Failed to quarantine content for {:?}: {}", path, e),
+            }
+        }
+
+        Ok(verdict)
+    }
+
+    /// Checks if a formatter is configured for the path's extension
+    pub fn has_formatter(&self, path: &Path) -> bool {
+        self.formatters.for_path(path).is_some()
+    }
+
+    /// Runs the configured formatter over content destined for `path`
+    ///
+    /// This runs an external process and must run off the async executor.
+    pub fn format_content(&self, path: &Path, content: &str) -> Result<Option<String>, String> {
+        self.formatters.format(path, content)
+    }
+
+    /// Checks if double-submit CSRF tokens are enforced
+    pub fn is_csrf_protection_enabled(&self) -> bool {
+        self.csrf_protection
+    }
+
     /// Checks if a command is allowed to be executed
     pub fn is_command_allowed(&self, command: &str) -> bool {
+        let command_name = command_name(command);
+        let (allowed, reason) = self.global_command_decision(command, &command_name);
+        self.decisions.record(allowed, DecisionKind::Command, &command_name, reason, None)
+    }
+
+    /// Applies the command blacklist and whitelist, returning the decision and the list that made it
+    fn global_command_decision(&self, command: &str, command_name: &str) -> (bool, &'static str) {
         use tracing::{debug, warn};
         
-        debug!("Checking command access for: {}", command);
-        
-        // Extract the base command (first word)
-        let base_command = command.split_whitespace().next().unwrap_or(command);
-        
-        // Remove path and extension to get base command name
-        let command_name = if let Some(path) = Path::new(base_command).file_stem() {
-            path.to_string_lossy().to_string()
-        } else {
-            base_command.to_string()
-        };
-        
+        debug!("Checking command access for: {}", self.redact(command));
         debug!("Base command extracted: {}", command_name);
         
         // First check blacklist - if it's blacklisted, deny immediately
-        if self.command_blacklist.contains(&command_name) {
+        if self.command_blacklist.contains(command_name) {
             warn!("Command '{}' is blacklisted", command_name);
-            return false;
+            return (false, "blacklist");
         }
         
         // If whitelist is not empty, command must be in whitelist
         if !self.command_whitelist.is_empty() {
-            let allowed = self.command_whitelist.contains(&command_name);
+            let allowed = self.command_whitelist.contains(command_name);
             if !allowed {
                 warn!("Command '{}' not in whitelist", command_name);
             }
-            allowed
+            (allowed, "whitelist")
         } else {
             // If no whitelist specified, allow all commands not in blacklist
             debug!("No whitelist specified, allowing command '{}'", command_name);
-            true
+            (true, "default")
+        }
+    }
+
+    /// Checks a command against the global policy and the policies of workspaces enclosing `cwd`
+    pub fn is_command_allowed_in(&self, command: &str, cwd: Option<&Path>) -> bool {
+        use tracing::warn;
+
+        let command_name = command_name(command);
+        let (allowed, reason) = self.global_command_decision(command, &command_name);
+        let Some(cwd) = cwd.filter(|_| allowed).and_then(|cwd| self.resolve_path(cwd)) else {
+            return self.decisions.record(allowed, DecisionKind::Command, &command_name, reason, None);
+        };
+        match self.workspaces.for_path(&cwd).iter().find(|policy| !policy.is_command_allowed(&command_name)) {
+            Some(policy) => {
+                warn!("Command '{}' denied by the workspace policy of {:?}", command_name, policy.root);
+                let root = Some(display_path(&policy.root));
+                self.decisions.record(false, DecisionKind::Command, &command_name, "workspace_policy", root)
+            }
+            None => self.decisions.record(true, DecisionKind::Command, &command_name, reason, None),
         }
     }
 
@@ -138,74 +484,345 @@ impl SecurityManager {
         size_mb <= self.max_file_size_mb
     }
 
+    /// Maximum accepted file size in bytes
+    pub fn max_file_size_bytes(&self) -> u64 {
+        self.max_file_size_mb * 1024 * 1024
+    }
+
+    /// Whether listings leave out creation and access times
+    pub fn omit_private_metadata(&self) -> bool {
+        self.omit_private_metadata
+    }
+
+    /// What clients of an origin class may do; full access unless configured
+    pub fn origin_access(&self, origin: OriginClass) -> OriginAccess {
+        self.origin_access.get(&origin).copied().unwrap_or_default()
+    }
+
+    /// Logs a request refused for lack of authority and applies the ban hook to its peer
+    pub fn record_auth_failure(&self, peer: Option<IpAddr>, reason: &str, method: &str, path: &str) {
+        self.auth_failures.record(peer, reason, method, path);
+    }
+
+    /// Free space that writes must leave on the target volume, in bytes
+    pub fn disk_reserve_bytes(&self) -> u64 {
+        self.disk_reserve_mb * 1024 * 1024
+    }
+
+    /// Most entries a recursive operation may touch without an override; None when unlimited
+    pub fn max_affected_entries(&self) -> Option<usize> {
+        (self.max_affected_entries > 0).then_some(self.max_affected_entries)
+    }
+
+    /// Most launched processes that may run at once; None when unlimited
+    pub fn max_spawned_processes(&self) -> Option<usize> {
+        (self.max_spawned_processes > 0).then_some(self.max_spawned_processes)
+    }
+
+    /// Priority for spawned processes, honoring an explicit request over the configured default
+    pub fn process_priority(&self, requested: Option<ProcessPriority>) -> ProcessPriority {
+        requested.unwrap_or(self.default_priority)
+    }
+
+    /// Timeout for a command, honoring an explicit request over the configured default
+    pub fn exec_timeout(&self, requested: Option<u64>) -> Option<Duration> {
+        Some(requested.unwrap_or(self.default_exec_timeout_secs))
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    /// Accounts a write against the client's throughput limit and returns how long to delay it
+    pub fn throttle_write(&self, client: &ClientId, bytes: u64) -> Duration {
+        self.write_throttle.reserve(client, bytes)
+    }
+
+    /// Per-client write throughput limit in bytes per second; 0 when unlimited
+    pub fn write_limit_bytes_per_sec(&self) -> u64 {
+        self.write_throttle.bytes_per_sec()
+    }
+
+    /// Current write throttle state for a client
+    pub fn write_throttle_state(&self, client: &ClientId) -> ThrottleState {
+        self.write_throttle.state(client)
+    }
+
     /// Checks if a path is allowed based on security policies
     /// Priority: 
+    /// 0. The server's own config directory, key file and audit log are always denied
     /// 1. First check if path is explicitly allowed (allowed_paths override disallowed)
     /// 2. Then check if path is disallowed (disallowed_paths)
     /// 3. Default: allow all other paths
+    ///
+    /// Paths allowed by these rules may still be blocked by the
+    /// `.exex/policy.json` of an enclosing workspace.
     pub fn is_path_allowed(&self, path: &Path) -> bool {
-        use tracing::debug;
+        use tracing::{debug, warn};
         
         debug!("Checking path access for: {:?}", path);
         
         // Canonicalize the path to resolve any .. or symlinks
-        let canonical_path = match path.canonicalize() {
+        let Some(canonical_path) = self.resolve_path(path) else {
+            return self.decisions.record(false, DecisionKind::Path, &display_path(path), "unresolvable", None);
+        };
+        let subject = display_path(&canonical_path);
+
+        if let Some(internal) = self.internal_paths.iter().find(|internal| canonical_path.starts_with(internal)) {
+            warn!("Access DENIED: {:?} is part of the server's own state", canonical_path);
+            return self.decisions.record(false, DecisionKind::Path, &subject, "internal_path", Some(display_path(internal)));
+        }
+
+        let (allowed, reason, rule) = self.global_path_decision(&canonical_path);
+        if !allowed {
+            return self.decisions.record(false, DecisionKind::Path, &subject, reason, rule);
+        }
+
+        // Workspace policies can only narrow the decision above
+        if let Some(policy) = self
+            .workspaces
+            .for_path(&canonical_path)
+            .into_iter()
+            .find(|policy| policy.is_path_blocked(&canonical_path))
+        {
+            warn!("Access DENIED: {:?} is blocked by the workspace policy of {:?}", canonical_path, policy.root);
+            let root = Some(display_path(&policy.root));
+            return self.decisions.record(false, DecisionKind::Path, &subject, "workspace_policy", root);
+        }
+        self.decisions.record(true, DecisionKind::Path, &subject, reason, rule)
+    }
+
+    /// Entries a tree walk from `root` leaves out
+    ///
+    /// `requested` patterns replace both the configured ones and those in the
+    /// `.exex/ignore` files of enclosing workspaces.
+    pub fn ignore_matcher(&self, root: &Path, requested: Option<&[String]>) -> Result<IgnoreMatcher, String> {
+        if let Some(patterns) = requested {
+            return IgnoreMatcher::new(root, patterns);
+        }
+        let mut matcher = IgnoreMatcher::new(root, &self.ignore_patterns)?;
+        if let Some(canonical) = self.resolve_path(root) {
+            matcher.add_workspace_files(&canonical);
+        }
+        Ok(matcher)
+    }
+
+    /// Whether a path is, or lies inside, a workspace's `.exex` policy directory
+    ///
+    /// Such paths may be read but not changed through the API, so a client
+    /// cannot loosen the policy that restricts it.
+    pub fn is_workspace_policy_path(&self, path: &Path) -> bool {
+        self.resolve_path(path).is_some_and(|canonical| is_policy_path(&canonical))
+    }
+
+    /// Applies the configured allowed and disallowed paths to a canonical path
+    ///
+    /// Returns the decision with the list that made it and the matching rule.
+    fn global_path_decision(&self, canonical_path: &Path) -> (bool, &'static str, Option<String>) {
+        use tracing::debug;
+
+        // STEP 1: Check if the path is explicitly allowed (highest priority)
+        // If a path is in allowed_paths, it overrides any disallowed restriction
+        if let Some(allowed) = self.allowed_paths.matching(canonical_path) {
+            debug!("Access EXPLICITLY ALLOWED: {:?} matches allowed rule: {:?}", canonical_path, allowed);
+            return (true, "allowed_paths", Some(allowed));
+        }
+
+        // STEP 2: Check if the path is disallowed
+        // If no explicit allow rule matched, check disallow rules
+        if let Some(disallowed) = self.disallowed_paths.matching(canonical_path) {
+            debug!("Access DENIED: {:?} matches disallowed rule: {:?}", canonical_path, disallowed);
+            return (false, "disallowed_paths", Some(disallowed));
+        }
+
+        // STEP 3: Default behavior - allow all other paths
+        debug!("Access ALLOWED (default): {:?} not in any restriction list", canonical_path);
+        (true, "default", None)
+    }
+
+    /// Checks if any disallowed path lies at or below the given path
+    pub fn has_restrictions_under(&self, path: &Path) -> bool {
+        let Some(canonical_path) = self.resolve_path(path) else {
+            return false;
+        };
+
+        self.disallowed_paths.has_rules_at_or_below(&canonical_path)
+            || self.internal_paths.iter().any(|internal| internal.starts_with(&canonical_path))
+    }
+
+    /// Resolves a path to its canonical form for policy checks
+    ///
+    /// Paths that don't exist yet resolve through their canonicalized parent.
+    fn resolve_path(&self, path: &Path) -> Option<PathBuf> {
+        use tracing::debug;
+
+        // Deep trees (e.g. node_modules) exceed MAX_PATH on Windows
+        let path = extended_path(path);
+        let path = path.as_path();
+
+        // Fast path: reuse the cached canonical parent and append the final component.
+        // Only safe when the entry is not a symlink and no rule lies below the parent,
+        // since then the leaf's spelling (case, 8.3 names, trailing dots) cannot
+        // change the policy decision.
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name())
+            && !parent.as_os_str().is_empty()
+            && !std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+            && let Ok(parent_canonical) = self.path_cache.canonicalize_dir(parent)
+            && !self.has_rules_below(&parent_canonical)
+        {
+            return Some(parent_canonical.join(name));
+        }
+
+        match path.canonicalize() {
             Ok(p) => {
                 debug!("Canonicalized path: {:?}", p);
-                p
+                Some(p)
             },
             Err(e) => {
                 debug!("Failed to canonicalize path {:?}: {}", path, e);
                 // If we can't canonicalize, check if the parent exists
                 if let Some(parent) = path.parent() {
                     match parent.canonicalize() {
-                        Ok(parent_canonical) => parent_canonical.join(path.file_name().unwrap_or_default()),
+                        Ok(parent_canonical) => Some(parent_canonical.join(path.file_name().unwrap_or_default())),
                         Err(_) => {
                             debug!("Path and parent cannot be canonicalized, denying access");
-                            return false;
+                            None
                         }
                     }
                 } else {
                     debug!("Path has no parent and cannot be canonicalized, denying access");
-                    return false;
+                    None
                 }
             }
-        };
+        }
+    }
 
-        // STEP 1: Check if the path is explicitly allowed (highest priority)
-        // If a path is in allowed_paths, it overrides any disallowed restriction
-        for allowed in &self.allowed_paths {
-            if canonical_path.starts_with(allowed) {
-                debug!("Access EXPLICITLY ALLOWED: {:?} matches allowed rule: {:?}", canonical_path, allowed);
-                return true;
-            }
+    /// Checks if any policy rule lies strictly below a canonical directory
+    fn has_rules_below(&self, dir: &Path) -> bool {
+        self.allowed_paths.has_rules_below(dir)
+            || self.disallowed_paths.has_rules_below(dir)
+            || self.encrypted_paths.iter().any(|rule| rule != dir && rule.starts_with(dir))
+            || self.internal_paths.iter().any(|internal| internal != dir && internal.starts_with(dir))
+    }
+
+    /// Drops cached canonicalizations at or below a path after it was renamed or removed
+    pub fn invalidate_path_cache(&self, path: &Path) {
+        let path = extended_path(path);
+        self.path_cache.invalidate(&path);
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name())
+            && let Ok(parent_canonical) = parent.canonicalize()
+        {
+            self.path_cache.invalidate(&parent_canonical.join(name));
         }
+    }
 
-        // STEP 2: Check if the path is disallowed
-        // If no explicit allow rule matched, check disallow rules
-        for disallowed in &self.disallowed_paths {
-            debug!("Checking against disallowed path: {:?}", disallowed);
-            if canonical_path.starts_with(disallowed) {
-                debug!("Access DENIED: {:?} starts with disallowed rule: {:?}", canonical_path, disallowed);
-                return false;
-            }
+    /// Checks if encryption at rest is active for at least one directory
+    pub fn is_encryption_enabled(&self) -> bool {
+        self.content_cipher.is_some()
+    }
+
+    /// Checks if a path lies inside a directory marked for encryption at rest
+    pub fn is_encrypted_path(&self, path: &Path) -> bool {
+        if self.content_cipher.is_none() {
+            return false;
         }
 
-        // STEP 3: Default behavior - allow all other paths
-        debug!("Access ALLOWED (default): {:?} not in any restriction list", canonical_path);
-        true
+        self.resolve_path(path)
+            .map(|canonical| self.encrypted_paths.iter().any(|dir| canonical.starts_with(dir)))
+            .unwrap_or(false)
+    }
+
+    /// Encrypts content destined for `path` when it lies in an encrypted directory
+    pub fn encrypt_for_path(&self, path: &Path, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        match &self.content_cipher {
+            Some(cipher) if self.is_encrypted_path(path) => cipher.encrypt(&data),
+            _ => Ok(data),
+        }
+    }
+
+    /// Decrypts content read from `path` if it was written encrypted
+    ///
+    /// Plaintext files (e.g. created before the directory was marked) pass through unchanged.
+    pub fn decrypt_for_path(&self, path: &Path, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        match &self.content_cipher {
+            Some(cipher) if ContentCipher::is_encrypted(&data) && self.is_encrypted_path(path) => {
+                cipher.decrypt(&data)
+            }
+            _ => Ok(data),
+        }
     }
 
     /// Gets the list of disallowed paths for debugging/logging
-    pub fn get_disallowed_paths(&self) -> &HashSet<PathBuf> {
+    pub fn get_disallowed_paths(&self) -> &PathRules {
         &self.disallowed_paths
     }
 
     /// Gets the list of allowed paths for debugging/logging
-    pub fn get_allowed_paths(&self) -> &HashSet<PathBuf> {
+    pub fn get_allowed_paths(&self) -> &PathRules {
         &self.allowed_paths
     }
 
+    /// Gets the whitelisted command names
+    pub fn get_command_whitelist(&self) -> &HashSet<String> {
+        &self.command_whitelist
+    }
+
+    /// Gets the blacklisted command names
+    pub fn get_command_blacklist(&self) -> &HashSet<String> {
+        &self.command_blacklist
+    }
+
+    /// Program run for a command name, following `security.command_aliases`
+    pub fn command_program<'a>(&'a self, command: &'a str) -> &'a str {
+        self.command_aliases.get(command).map(String::as_str).unwrap_or(command)
+    }
+
+    /// Checks a request's `shell` and `login_shell` against the policy
+    pub fn check_shell(&self, shell: Option<ShellKind>, login_shell: bool) -> Result<(), String> {
+        if let Some(shell) = shell
+            && !self.allowed_shells.contains(&shell)
+        {
+            return Err(format!("Shell '{}' is not allowed by security policy", shell));
+        }
+        if login_shell && !self.allow_login_shell {
+            return Err("Login shells are not allowed by security policy".to_string());
+        }
+        Ok(())
+    }
+
+    /// PATH for commands and applications, with `security.extra_path_entries` first; `None` inherits the daemon's
+    pub fn child_path(&self) -> Option<&OsStr> {
+        self.child_path.as_deref()
+    }
+
+    /// Variables `check_env` refuses, with `*` marking prefixes
+    pub fn blocked_env(&self) -> Vec<String> {
+        BLOCKED_ENV_VARS
+            .iter()
+            .map(|name| name.to_string())
+            .chain(BLOCKED_ENV_PREFIXES.iter().map(|prefix| format!("{}*", prefix)))
+            .chain(self.blocked_env_vars.iter().cloned())
+            .collect()
+    }
+
+    /// Checks that injected environment variables cannot redirect which code a command runs
+    pub fn check_env(&self, env: &EnvOptions) -> Result<(), String> {
+        let Some(vars) = &env.env else {
+            return Ok(());
+        };
+        for name in vars.keys() {
+            let upper = name.to_ascii_uppercase();
+            let blocked = BLOCKED_ENV_VARS.contains(&upper.as_str())
+                || BLOCKED_ENV_PREFIXES.iter().any(|prefix| upper.starts_with(prefix))
+                || self.blocked_env_vars.iter().any(|rule| match rule.strip_suffix('*') {
+                    Some(prefix) => upper.starts_with(prefix),
+                    None => upper == *rule,
+                });
+            if blocked || name.is_empty() || name.contains('=') || name.contains('\0') {
+                return Err(format!("Environment variable '{}' may not be set", name));
+            }
+        }
+        Ok(())
+    }
+
     /// Validates if a command is safe to execute
     pub fn is_command_safe(&self, command: &str) -> bool {
         // Basic command safety checks
@@ -241,6 +858,7 @@ impl SecurityManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::get_default_config;
     use crate::models::{ServerConfig, SecurityConfig, LoggingConfig};
 
     fn create_test_config() -> Config {
@@ -249,6 +867,7 @@ mod tests {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 8080,
+                ..get_default_config().server
             },
             security: SecurityConfig {
                 allowed_paths: vec![],
@@ -256,11 +875,20 @@ mod tests {
                 command_whitelist: vec!["echo".to_string(), "dir".to_string()],
                 command_blacklist: Some(vec!["format".to_string(), "del".to_string()]),
                 max_file_size_mb: 100,
+                ..get_default_config().security
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
                 audit_file: "test.log".to_string(),
+                ..get_default_config().logging
             },
+            apps: None,
+            remote_url: None,
+            remote_refresh_secs: None,
+            ignore_patterns: None,
+            formatters: None,
+            pipelines: None,
+            backups: None,
         }
     }
 
@@ -286,6 +914,28 @@ mod tests {
         assert!(!security.is_command_allowed("del"));
     }
 
+    #[test]
+    fn test_command_aliases() {
+        let mut config = create_test_config();
+        config.security.command_aliases = Some([("echo".to_string(), "/bin/echo".to_string())].into());
+        let security = SecurityManager::new(config);
+
+        assert_eq!(security.command_program("echo"), "/bin/echo");
+        assert_eq!(security.command_program("dir"), "dir");
+    }
+
+    #[test]
+    fn test_exec_timeout_default() {
+        let mut config = create_test_config();
+        assert_eq!(SecurityManager::new(config.clone()).exec_timeout(None), None);
+
+        config.security.default_exec_timeout_secs = Some(30);
+        let security = SecurityManager::new(config);
+        assert_eq!(security.exec_timeout(None), Some(Duration::from_secs(30)));
+        assert_eq!(security.exec_timeout(Some(5)), Some(Duration::from_secs(5)));
+        assert_eq!(security.exec_timeout(Some(0)), None);
+    }
+
     #[test]
     fn test_file_size_limits() {
         let security = SecurityManager::new(create_test_config());
@@ -297,6 +947,50 @@ mod tests {
         assert!(!security.is_file_size_allowed(150 * 1024 * 1024)); // 150MB
     }
 
+    #[test]
+    fn test_creation_modes() {
+        let mut config = create_test_config();
+        config.security.file_mode = Some("600".to_string());
+        config.security.dir_mode = None;
+        let security = SecurityManager::new(config);
+
+        assert_eq!(security.creation_mode(None, false), Ok(0o600));
+        assert_eq!(security.creation_mode(None, true), Ok(0o755));
+        assert_eq!(security.creation_mode(Some(0o640), false), Ok(0o640));
+        // World-writable or beyond the limit
+        assert!(security.creation_mode(Some(0o777), true).is_err());
+
+        assert_eq!(parse_mode("0o750"), Ok(0o750));
+        assert_eq!(parse_mode("0644"), Ok(0o644));
+        assert!(parse_mode("1777").is_err());
+        assert!(parse_mode("rw-").is_err());
+        assert!(parse_mode("").is_err());
+    }
+
+
+    #[test]
+    fn test_env_injection_limits() {
+        let security = SecurityManager::new(create_test_config());
+        let env = |name: &str| EnvOptions {
+            env: Some([(name.to_string(), "x".to_string())].into()),
+            ..EnvOptions::default()
+        };
+
+        assert!(security.check_env(&EnvOptions::default()).is_ok());
+        assert!(security.check_env(&env("RUST_LOG")).is_ok());
+        assert!(security.check_env(&env("LD_PRELOAD")).is_err());
+        assert!(security.check_env(&env("dyld_insert_libraries")).is_err());
+        assert!(security.check_env(&env("Path")).is_err());
+        assert!(security.check_env(&env("A=B")).is_err());
+
+        let mut config = create_test_config();
+        config.security.blocked_env_vars = Some(vec!["aws_*".to_string(), "GITHUB_TOKEN".to_string()]);
+        let security = SecurityManager::new(config);
+        assert!(security.check_env(&env("AWS_SECRET_ACCESS_KEY")).is_err());
+        assert!(security.check_env(&env("github_token")).is_err());
+        assert!(security.check_env(&env("GITHUB_TOKEN_FILE")).is_ok());
+        assert!(security.blocked_env().contains(&"AWS_*".to_string()));
+    }
 
     #[test]
     fn test_path_validation() {
@@ -305,6 +999,7 @@ mod tests {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 8080,
+                ..get_default_config().server
             },
             security: SecurityConfig {
                 allowed_paths: vec![],
@@ -312,11 +1007,20 @@ mod tests {
                 command_whitelist: vec![],
                 command_blacklist: None,
                 max_file_size_mb: 100,
+                ..get_default_config().security
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
                 audit_file: "test.log".to_string(),
+                ..get_default_config().logging
             },
+            apps: None,
+            remote_url: None,
+            remote_refresh_secs: None,
+            ignore_patterns: None,
+            formatters: None,
+            pipelines: None,
+            backups: None,
         };
         let security = SecurityManager::new(config);
         
@@ -324,4 +1028,120 @@ mod tests {
         let temp_dir = std::env::temp_dir();
         assert!(security.is_path_allowed(&temp_dir));
     }
+
+    #[test]
+    fn test_workspace_policy_restricts() {
+        let root = std::env::temp_dir().join(format!("exex-workspace-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(root.join(".exex")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let policy = root.join(".exex").join("policy.json");
+        std::fs::write(&policy, r#"{"blocked_paths": ["*.key"], "command_whitelist": ["echo"]}"#).unwrap();
+
+        let security = SecurityManager::new(create_test_config());
+        assert!(security.is_path_allowed(&root.join("src").join("main.rs")));
+        assert!(!security.is_path_allowed(&root.join("src").join("id.key")));
+        assert!(security.is_command_allowed_in("echo", Some(&root.join("src"))));
+        assert!(!security.is_command_allowed_in("dir", Some(&root)));
+        assert!(security.is_command_allowed_in("dir", None));
+        assert!(security.is_workspace_policy_path(&policy));
+        assert!(!security.is_workspace_policy_path(&root.join("src")));
+
+        // Edits apply on the next check, and an unreadable policy closes the workspace
+        std::fs::write(&policy, r#"{"blocked_paths": ["src"]}"#).unwrap();
+        assert!(!security.is_path_allowed(&root.join("src").join("main.rs")));
+        std::fs::write(&policy, "not json").unwrap();
+        assert!(!security.is_path_allowed(&root.join("README.md")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_protected_paths() {
+        let root = std::env::temp_dir().join(format!("exex-protected-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(root.join("repo").join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("repo").join(".git").join("HEAD"), "ref").unwrap();
+
+        let security = SecurityManager::new(create_test_config());
+        assert!(security.is_protected_path(&root.join("repo").join(".git").join("HEAD")));
+        assert!(security.is_protected_path(&root.join("repo").join(".git")));
+        assert!(security.is_protected_path(&root.join("certs").join("server.key")));
+        assert!(security.is_protected_path(&root.join("id_rsa.pub")));
+        assert!(!security.is_protected_path(&root.join("src").join("keys.rs")));
+        assert!(!security.is_protected_path(&root.join("repo").join(".gitignore")));
+
+        assert!(security.check_protected(&root.join("src").join("main.rs"), false).is_ok());
+        assert!(security.check_protected(&root.join("server.pem"), false).is_err());
+        // Forcing needs the override to be allowed too
+        assert!(security.check_protected(&root.join("server.pem"), true).is_err());
+        assert!(security.check_protected_tree(&root, true).is_err());
+        assert!(security.check_protected_tree(&root.join("src"), false).is_ok());
+
+        let mut config = create_test_config();
+        config.security.allow_protected_override = Some(true);
+        let security = SecurityManager::new(config);
+        assert!(security.check_protected(&root.join("server.pem"), true).is_ok());
+        assert!(security.check_protected_tree(&root, false).is_err());
+        assert!(security.check_protected_tree(&root, true).is_ok());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_protected_patterns_fail_closed() {
+        let mut config = create_test_config();
+        config.security.protected_patterns = Some(vec!["src/[unclosed".to_string()]);
+        let security = SecurityManager::new(config);
+        let path = std::env::temp_dir().join("notes.txt");
+        assert!(security.is_protected_path(&path));
+        assert!(security.check_protected(&path, false).is_err());
+    }
+
+    #[test]
+    fn test_internal_paths_always_denied() {
+        let root = std::env::temp_dir().join(format!("exex-internal-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&root).unwrap();
+        let key = root.join("exex.key");
+        std::fs::write(&key, "key").unwrap();
+
+        let mut config = create_test_config();
+        config.security.allowed_paths = vec![root.to_string_lossy().to_string()];
+        config.security.encryption_key_file = Some(key.to_string_lossy().to_string());
+        config.logging.audit_file = root.join("audit.log").to_string_lossy().to_string();
+        let security = SecurityManager::new(config);
+
+        assert!(!security.is_path_allowed(&key));
+        assert!(!security.is_path_allowed(&root.join("audit.log")));
+        assert!(security.is_path_allowed(&root.join("notes.txt")));
+        assert!(security.check_protected_tree(&root, true).is_err());
+        assert!(security.check_internal_tree(&root.join("notes.txt")).is_ok());
+        if let Ok(config_dir) = get_config_dir() {
+            assert!(!security.is_path_allowed(&config_dir.join("exex.config.json")));
+            assert!(!security.is_path_allowed(&config_dir.join("trusted_keys")));
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cached_resolution_follows_symlinks() {
+        let root = std::env::temp_dir().join(format!("exex-policy-{}", uuid::Uuid::new_v4().simple()));
+        let secret = root.join("a").join("secret");
+        let public = root.join("b");
+        std::fs::create_dir_all(&secret).unwrap();
+        std::fs::create_dir_all(&public).unwrap();
+        std::os::unix::fs::symlink(&secret, public.join("link")).unwrap();
+
+        let mut config = create_test_config();
+        config.security.disallowed_paths = vec![secret.to_string_lossy().to_string()];
+        let security = SecurityManager::new(config);
+
+        // Warm the parent cache, then make sure the symlinked leaf is still resolved
+        assert!(security.is_path_allowed(&public.join("file.txt")));
+        assert!(!security.is_path_allowed(&public.join("link")));
+        assert!(!security.is_path_allowed(&root.join("a").join("secret")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,148 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header::HeaderMap, Method},
+    middleware::Next,
+    web, Error, HttpRequest, HttpResponse,
+};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::models::{ErrorResponse, OriginAccess, OriginClass};
+use crate::routes::version::split_versioned_path;
+use crate::security::SecurityManager;
+
+/// Headers a reverse proxy or tunnel agent adds when it forwards a request
+const FORWARDING_HEADERS: &[&str] = &["forwarded", "x-forwarded-for", "x-real-ip", "cf-connecting-ip"];
+
+/// `POST` endpoints that only read, and so stay open to read-only origins
+const READ_ONLY_POSTS: &[&str] = &["/read", "/scan", "/search", "/find", "/hash", "/tail"];
+
+impl std::fmt::Display for OriginClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OriginClass::Loopback => "loopback",
+            OriginClass::Lan => "LAN",
+            OriginClass::Tunnel => "tunnel",
+            OriginClass::Public => "public",
+        })
+    }
+}
+
+/// Classifies a peer address, using proxy headers to tell tunnelled requests from local ones
+///
+/// A request without a peer address cannot have come over the network and counts as loopback.
+pub fn classify(peer: Option<IpAddr>, headers: &HeaderMap) -> OriginClass {
+    let Some(peer) = peer.map(|ip| ip.to_canonical()) else {
+        return OriginClass::Loopback;
+    };
+    if peer.is_loopback() {
+        return if FORWARDING_HEADERS.iter().any(|name| headers.contains_key(*name)) {
+            OriginClass::Tunnel
+        } else {
+            OriginClass::Loopback
+        };
+    }
+    match peer {
+        // 100.64.0.0/10, the shared address space Tailscale and similar VPNs assign from
+        IpAddr::V4(v4) if v4.octets()[0] == 100 && v4.octets()[1] & 0xc0 == 64 => OriginClass::Tunnel,
+        IpAddr::V4(v4) if v4.is_private() || v4.is_link_local() => OriginClass::Lan,
+        // fc00::/7 unique local and fe80::/10 link-local
+        IpAddr::V6(v6) if v6.segments()[0] & 0xfe00 == 0xfc00 || v6.segments()[0] & 0xffc0 == 0xfe80 => {
+            OriginClass::Lan
+        }
+        _ => OriginClass::Public,
+    }
+}
+
+/// Origin class of a request
+pub fn request_origin(req: &HttpRequest) -> OriginClass {
+    classify(req.peer_addr().map(|addr| addr.ip()), req.headers())
+}
+
+/// Whether a request only reads, judged by its method and endpoint
+fn is_read_only(method: &Method, path: &str) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || split_versioned_path(path).is_some_and(|(_, endpoint)| READ_ONLY_POSTS.contains(&endpoint))
+}
+
+/// Middleware applying `security.origin_access` to each request's origin class
+pub async fn origin_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(security) = req.app_data::<web::Data<Arc<SecurityManager>>>().cloned() else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+    let origin = request_origin(req.request());
+    let refusal = match security.origin_access(origin) {
+        OriginAccess::Full => None,
+        OriginAccess::ReadOnly if is_read_only(req.method(), req.path()) => None,
+        OriginAccess::ReadOnly => Some(format!("Requests from {} clients are read-only", origin)),
+        OriginAccess::None => Some(format!("Requests from {} clients are not allowed", origin)),
+    };
+    let Some(error) = refusal else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    warn!(target: "audit", "{} {} denied: {}", req.method(), req.path(), error);
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    security.record_auth_failure(peer, "origin", req.method().as_str(), req.path());
+    let response = HttpResponse::Forbidden().json(ErrorResponse { error });
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{middleware::from_fn, App};
+
+    #[actix_web::test]
+    async fn test_origin_policies() {
+        let none = HeaderMap::new();
+        let classes = [
+            ("127.0.0.1", OriginClass::Loopback),
+            ("::ffff:127.0.0.1", OriginClass::Loopback),
+            ("192.168.1.20", OriginClass::Lan),
+            ("10.0.0.5", OriginClass::Lan),
+            ("fe80::1", OriginClass::Lan),
+            ("fd12:3456::1", OriginClass::Lan),
+            ("100.101.102.103", OriginClass::Tunnel),
+            ("100.128.0.1", OriginClass::Public),
+            ("8.8.8.8", OriginClass::Public),
+        ];
+        for (ip, class) in classes {
+            assert_eq!(classify(Some(ip.parse().unwrap()), &none), class, "{}", ip);
+        }
+        let mut forwarded = HeaderMap::new();
+        forwarded.insert(HeaderName::from_static("x-forwarded-for"), HeaderValue::from_static("203.0.113.7"));
+        assert_eq!(classify(Some("127.0.0.1".parse().unwrap()), &forwarded), OriginClass::Tunnel);
+
+        let mut config = get_default_config();
+        config.security.origin_access = Some([(OriginClass::Lan, OriginAccess::ReadOnly), (OriginClass::Public, OriginAccess::None)].into());
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+                .wrap(from_fn(origin_guard))
+                .default_service(web::to(HttpResponse::Ok)),
+        )
+        .await;
+        let call = |method: Method, path: &str, peer: &str| {
+            TestRequest::default()
+                .method(method)
+                .uri(path)
+                .peer_addr(format!("{}:50000", peer).parse().unwrap())
+                .to_request()
+        };
+
+        assert_eq!(call_service(&app, call(Method::POST, "/api/v2/write", "127.0.0.1")).await.status(), 200);
+        assert_eq!(call_service(&app, call(Method::POST, "/api/v2/read", "192.168.1.20")).await.status(), 200);
+        assert_eq!(call_service(&app, call(Method::GET, "/api/processes", "192.168.1.20")).await.status(), 200);
+        assert_eq!(call_service(&app, call(Method::POST, "/api/v2/write", "192.168.1.20")).await.status(), 403);
+        assert_eq!(call_service(&app, call(Method::GET, "/api/v2/mounts", "8.8.8.8")).await.status(), 403);
+    }
+}
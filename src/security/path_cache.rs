@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Bounded TTL cache of canonicalized directories used by policy checks
+///
+/// Keys are directories as requested, values their canonical form. Entries are
+/// dropped on expiry, when the cache is full, or when a mutation under them is
+/// reported through [`PathCache::invalidate`]. Every hit is checked against the
+/// directory itself, so one that was removed or swapped for a symlink since is
+/// canonicalized again; a changed ancestor is only noticed on invalidation or
+/// expiry.
+pub struct PathCache {
+    entries: Mutex<HashMap<PathBuf, CachedDir>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+struct CachedDir {
+    canonical: PathBuf,
+    inserted: Instant,
+    /// Target of the directory when it was a symlink
+    link: Option<PathBuf>,
+}
+
+impl CachedDir {
+    /// Whether the directory is still what it was when cached
+    fn is_current(&self, dir: &Path) -> bool {
+        let link = std::fs::read_link(dir).ok();
+        link == self.link && (link.is_some() || dir.is_dir())
+    }
+}
+
+impl PathCache {
+    /// Creates a cache; a zero TTL or capacity disables caching
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            capacity,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    /// Returns the canonical form of a directory, canonicalizing on a miss
+    pub fn canonicalize_dir(&self, dir: &Path) -> std::io::Result<PathBuf> {
+        if !self.is_enabled() {
+            return dir.canonicalize();
+        }
+
+        let now = Instant::now();
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(cached) = entries.get(dir)
+                && now.duration_since(cached.inserted) < self.ttl
+            {
+                if cached.is_current(dir) {
+                    return Ok(cached.canonical.clone());
+                }
+                entries.remove(dir);
+            }
+        }
+
+        let link = std::fs::read_link(dir).ok();
+        let canonical = dir.canonicalize()?;
+
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.capacity {
+            entries.retain(|_, cached| now.duration_since(cached.inserted) < self.ttl);
+        }
+        if entries.len() >= self.capacity
+            && let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.inserted)
+                .map(|(key, _)| key.clone())
+        {
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            dir.to_path_buf(),
+            CachedDir {
+                canonical: canonical.clone(),
+                inserted: now,
+                link,
+            },
+        );
+
+        Ok(canonical)
+    }
+
+    /// Drops every entry at or below `path`, matching either the requested or canonical form
+    pub fn invalidate(&self, path: &Path) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|key, cached| !key.starts_with(path) && !cached.canonical.starts_with(path));
+    }
+
+    /// Number of cached directories
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    /// Checks if the cache holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hit_and_invalidate() {
+        let dir = std::env::temp_dir().join(format!("exex-path-cache-{}", uuid::Uuid::new_v4().simple()));
+        let nested = dir.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let cache = PathCache::new(Duration::from_secs(60), 8);
+        let canonical = cache.canonicalize_dir(&nested).unwrap();
+        assert_eq!(canonical, nested.canonicalize().unwrap());
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.canonicalize_dir(&nested).unwrap(), canonical);
+
+        cache.invalidate(&dir);
+        assert!(cache.is_empty());
+        cache.canonicalize_dir(&nested).unwrap();
+
+        // A hit whose directory disappeared is not served
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(cache.canonicalize_dir(&nested).is_err());
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_swapped_directory_not_served() {
+        let dir = std::env::temp_dir().join(format!("exex-path-cache-{}", uuid::Uuid::new_v4().simple()));
+        let (link, elsewhere) = (dir.join("link"), dir.join("elsewhere"));
+        std::fs::create_dir_all(&link).unwrap();
+        std::fs::create_dir_all(&elsewhere).unwrap();
+
+        let cache = PathCache::new(Duration::from_secs(60), 8);
+        assert_eq!(cache.canonicalize_dir(&link).unwrap(), link.canonicalize().unwrap());
+
+        // Replaced by a symlink without the cache being told
+        std::fs::remove_dir(&link).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, &link).unwrap();
+        assert_eq!(cache.canonicalize_dir(&link).unwrap(), elsewhere.canonicalize().unwrap());
+        assert_eq!(cache.canonicalize_dir(&link).unwrap(), elsewhere.canonicalize().unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_capacity_bound() {
+        let cache = PathCache::new(Duration::from_secs(60), 2);
+        let temp = std::env::temp_dir();
+        let root = temp.ancestors().last().unwrap().to_path_buf();
+
+        cache.canonicalize_dir(&temp).unwrap();
+        cache.canonicalize_dir(&root).unwrap();
+        cache.canonicalize_dir(Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        let disabled = PathCache::new(Duration::ZERO, 2);
+        disabled.canonicalize_dir(&temp).unwrap();
+        assert!(disabled.is_empty());
+    }
+}
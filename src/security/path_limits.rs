@@ -0,0 +1,258 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::{header, Method},
+    middleware::Next,
+    web::BytesMut,
+    Error, HttpMessage, HttpResponse,
+};
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::rc::Rc;
+use tracing::warn;
+
+use crate::models::{ErrorCode, PathErrorResponse};
+
+/// Longest path accepted, in bytes; Windows' extended-length limit
+pub const MAX_PATH_LEN: usize = 32_767;
+
+/// Longest single path component accepted; 255 is the name limit of every common filesystem
+pub const MAX_COMPONENT_LEN: usize = 255;
+
+/// Most components a path may have
+pub const MAX_PATH_DEPTH: usize = 256;
+
+/// Largest JSON body inspected; actix's JSON extractor refuses larger ones anyway
+pub const MAX_INSPECTED_BODY: usize = 2 * 1024 * 1024;
+
+/// A request's JSON body, parsed once here and kept in the request extensions for the audit log
+#[derive(Debug, Clone)]
+pub struct InspectedBody(pub Rc<Value>);
+
+/// Checks that a path can be handed to filesystem APIs
+///
+/// Catches what those APIs would otherwise reject with confusing,
+/// platform-specific errors, or mistake for a shorter path (an embedded NUL).
+pub fn validate_path(path: &str) -> Result<(), String> {
+    if path.contains('\0') {
+        return Err("Path contains a NUL byte".to_string());
+    }
+    if path.len() > MAX_PATH_LEN {
+        return Err(format!("Path is {} bytes long; at most {} are allowed", path.len(), MAX_PATH_LEN));
+    }
+
+    let separators: &[char] = if cfg!(windows) { &['/', '\\'] } else { &['/'] };
+    let mut depth = 0;
+    for component in path.split(separators).filter(|c| !c.is_empty() && *c != ".") {
+        // NTFS counts name length in UTF-16 units, other filesystems in bytes
+        let len = if cfg!(windows) { component.encode_utf16().count() } else { component.len() };
+        if len > MAX_COMPONENT_LEN {
+            let shown: String = component.chars().take(32).collect();
+            return Err(format!(
+                "Path component '{}...' is too long ({} of at most {})",
+                shown, len, MAX_COMPONENT_LEN
+            ));
+        }
+        depth += 1;
+    }
+    if depth > MAX_PATH_DEPTH {
+        return Err(format!("Path is {} levels deep; at most {} are allowed", depth, MAX_PATH_DEPTH));
+    }
+    Ok(())
+}
+
+/// Whether a request field holds a path or a list of paths
+fn is_path_field(name: &str) -> bool {
+    matches!(name, "path" | "paths" | "cwd" | "working_dir" | "files") || name.ends_with("_path")
+}
+
+/// Finds the first invalid path in a request body, with the field it was found in
+fn find_invalid_path(value: &Value, field: &str) -> Option<(String, String)> {
+    match value {
+        Value::Object(object) => object.iter().find_map(|(name, value)| {
+            let field = if field.is_empty() { name.clone() } else { format!("{}.{}", field, name) };
+            if is_path_field(name) {
+                check_paths(value, &field)
+            } else {
+                find_invalid_path(value, &field)
+            }
+        }),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .find_map(|(i, item)| find_invalid_path(item, &format!("{}[{}]", field, i))),
+        _ => None,
+    }
+}
+
+fn check_paths(value: &Value, field: &str) -> Option<(String, String)> {
+    match value {
+        Value::String(path) => validate_path(path).err().map(|e| (field.to_string(), e)),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .find_map(|(i, item)| check_paths(item, &format!("{}[{}]", field, i))),
+        _ => None,
+    }
+}
+
+/// Middleware rejecting JSON requests whose paths could not be valid, before any handler runs
+///
+/// Every field named `path`, `paths`, `cwd`, `working_dir`, `files` or
+/// `*_path`, at any depth, is checked with [`validate_path`]. Bodies that are
+/// not UTF-8 are refused too; JSON that does not parse is left for the handler
+/// to report. NDJSON batches pass through unread, and the batch handler checks
+/// each line with [`check_body_paths`] instead.
+pub async fn validate_paths(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let is_json = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json || matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    let mut payload = req.take_payload();
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        body.extend_from_slice(&chunk?);
+        if body.len() > MAX_INSPECTED_BODY {
+            let response = HttpResponse::PayloadTooLarge().json(rejection(
+                "body".to_string(),
+                format!("Request body exceeds {} bytes", MAX_INSPECTED_BODY),
+            ));
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+    let body = body.freeze();
+
+    let invalid = match std::str::from_utf8(&body) {
+        Err(e) => Some(("body".to_string(), format!("Request body is not valid UTF-8 (at byte {})", e.valid_up_to()))),
+        Ok(_) => serde_json::from_slice::<Value>(&body).ok().and_then(|value| {
+            let invalid = find_invalid_path(&value, "");
+            req.extensions_mut().insert(InspectedBody(Rc::new(value)));
+            invalid
+        }),
+    };
+    if let Some((field, error)) = invalid {
+        warn!("Rejected {} {}: {}: {}", req.method(), req.path(), field, error);
+        let response = HttpResponse::BadRequest().json(rejection(field, error));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    req.set_payload(Payload::from(body));
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+/// Checks every path field of a parsed request, returning the rejection to answer with
+pub fn check_body_paths(value: &Value) -> Option<PathErrorResponse> {
+    find_invalid_path(value, "").map(|(field, error)| rejection(field, error))
+}
+
+fn rejection(field: String, error: String) -> PathErrorResponse {
+    PathErrorResponse {
+        success: false,
+        error,
+        error_code: ErrorCode::InvalidPath,
+        field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use crate::handlers::batch_stream;
+    use crate::jobs::JobLogs;
+    use crate::operations::OperationRegistry;
+    use crate::power::PowerManager;
+    use crate::processes::ProcessRegistry;
+    use crate::security::SecurityManager;
+    use crate::workers::WorkerPools;
+    use actix_web::test::{call_service, init_service, read_body, read_body_json, TestRequest};
+    use actix_web::{middleware::from_fn, web, App};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_validate_path() {
+        assert!(validate_path("/home/user/project/src/main.rs").is_ok());
+        assert!(validate_path(&format!("/tmp/{}", "a".repeat(MAX_COMPONENT_LEN))).is_ok());
+
+        assert!(validate_path("/tmp/a\0b").unwrap_err().contains("NUL"));
+        assert!(validate_path(&format!("/tmp/{}", "a".repeat(MAX_COMPONENT_LEN + 1))).unwrap_err().contains("too long (256 of at most 255)"));
+        assert!(validate_path(&"/a".repeat(MAX_PATH_DEPTH + 1)).unwrap_err().contains("levels deep"));
+        assert!(validate_path(&"/".repeat(MAX_PATH_LEN + 1)).unwrap_err().contains("bytes long"));
+        // Empty and `.` components do not add depth
+        assert!(validate_path(&"/.//a".repeat(MAX_PATH_DEPTH)).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_validate_paths_middleware() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(validate_paths))
+                .route("/api/echo", web::post().to(|body: web::Json<Value>| async move { HttpResponse::Ok().json(body.0) })),
+        )
+        .await;
+        let post = |body: Vec<u8>| {
+            TestRequest::post()
+                .uri("/api/echo")
+                .insert_header((header::CONTENT_TYPE, "application/json"))
+                .set_payload(body)
+                .to_request()
+        };
+
+        // Valid bodies reach the handler intact
+        let body = br#"{"operations":[{"op":"read","path":"/tmp/a.txt","content":"x\u0000y"}]}"#.to_vec();
+        let res = call_service(&app, post(body)).await;
+        assert_eq!(res.status(), 200);
+        let echoed: Value = read_body_json(res).await;
+        assert_eq!(echoed["operations"][0]["content"], "x\0y");
+
+        let body = br#"{"operations":[{"op":"read"},{"op":"rename","from_path":"/tmp/a","to_path":"/tmp/b\u0000"}]}"#.to_vec();
+        let res = call_service(&app, post(body)).await;
+        assert_eq!(res.status(), 400);
+        let rejected: Value = read_body_json(res).await;
+        assert_eq!(rejected["error_code"], "INVALID_PATH");
+        assert_eq!(rejected["field"], "operations[1].to_path");
+
+        let res = call_service(&app, post(br#"{"paths":["/tmp/ok","#.iter().chain(b"\xff\"]}").copied().collect())).await;
+        assert_eq!(res.status(), 400);
+        let rejected: Value = read_body_json(res).await;
+        assert_eq!(rejected["field"], "body");
+    }
+
+    #[actix_web::test]
+    async fn test_batch_lines_validated() {
+        let config = get_default_config();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Arc::new(WorkerPools::from_config(&config.server))))
+                .app_data(web::Data::new(Arc::new(SecurityManager::new(config))))
+                .app_data(web::Data::new(Arc::new(OperationRegistry::new())))
+                .app_data(web::Data::new(Arc::new(ProcessRegistry::new())))
+                .app_data(web::Data::new(Arc::new(PowerManager::new(false))))
+                .app_data(web::Data::new(Arc::new(JobLogs::new(std::env::temp_dir().join("exex-path-limit-jobs"), 0, 0))))
+                .wrap(from_fn(validate_paths))
+                .route("/api/batch/stream", web::post().to(batch_stream)),
+        )
+        .await;
+
+        let body = r#"{"op":"rename","id":1,"from_path":"/tmp/a","to_path":"/tmp/b\u0000"}"#;
+        let req = TestRequest::post()
+            .uri("/api/batch/stream")
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .set_payload(body)
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        let line: Value = serde_json::from_slice(read_body(res).await.trim_ascii()).unwrap();
+        assert_eq!(line["status"], 400);
+        assert_eq!(line["result"]["error_code"], "INVALID_PATH");
+        assert_eq!(line["result"]["field"], "to_path");
+    }
+}
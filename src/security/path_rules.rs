@@ -0,0 +1,207 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::platform::{display_path, extended_path};
+
+/// Characters that make a path component a glob
+pub(crate) const GLOB_CHARS: &[char] = &['*', '?', '[', '{'];
+
+/// A rule with wildcards, such as `/home/*/Projects/`
+///
+/// The part before the first wildcard component is canonicalized like a plain
+/// rule; the rest is matched against the remainder of a canonical path, so a
+/// rule also covers everything below what it matches.
+struct PathGlob {
+    rule: PathBuf,
+    prefix: PathBuf,
+    matcher: GlobSet,
+    /// Components in a full match, or `None` when `**` matches any depth
+    depth: Option<usize>,
+}
+
+impl PathGlob {
+    fn matches(&self, canonical: &Path) -> bool {
+        canonical
+            .strip_prefix(&self.prefix)
+            .is_ok_and(|rest| !rest.as_os_str().is_empty() && self.matcher.is_match(rest))
+    }
+
+    /// Whether the glob could match `path` or something below it
+    fn may_match_at_or_below(&self, path: &Path) -> bool {
+        if self.prefix.starts_with(path) {
+            return true;
+        }
+        path.starts_with(&self.prefix) && self.depth.is_none_or(|depth| path.components().count() <= depth)
+    }
+}
+
+/// The allowed or disallowed path list, with literal rules kept apart from globs
+#[derive(Default)]
+pub struct PathRules {
+    literal: HashSet<PathBuf>,
+    globs: Vec<PathGlob>,
+}
+
+impl PathRules {
+    /// Builds the list from configured rules; globs that do not compile are skipped with a warning
+    pub fn new(rules: &[String], kind: &str) -> Self {
+        let mut list = Self::default();
+        for rule in rules {
+            let normalized = normalize_separators(rule);
+            match split_glob(&normalized) {
+                None => {
+                    list.literal.insert(normalize_rule_path(&normalized, kind));
+                }
+                Some((literal, pattern)) => match compile(&pattern) {
+                    Ok(matcher) => {
+                        let prefix = normalize_rule_path(&literal, kind);
+                        let depth = (!pattern.split('/').any(|part| part == "**"))
+                            .then(|| prefix.components().count() + pattern.split('/').count());
+                        debug!("Added {} glob: {:?} under {:?}", kind, pattern, prefix);
+                        list.globs.push(PathGlob {
+                            rule: prefix.join(&pattern),
+                            prefix,
+                            matcher,
+                            depth,
+                        });
+                    }
+                    Err(e) => warn!("Ignoring invalid {} '{}': {}", kind, rule, e),
+                },
+            }
+        }
+        list
+    }
+
+    /// The first rule covering a canonical path, for logging which one matched
+    pub fn matching(&self, canonical: &Path) -> Option<String> {
+        self.literal
+            .iter()
+            .find(|rule| canonical.starts_with(rule))
+            .map(|rule| display_path(rule))
+            .or_else(|| self.globs.iter().find(|glob| glob.matches(canonical)).map(|glob| display_path(&glob.rule)))
+    }
+
+    /// Whether a rule could apply to `path` or to anything below it
+    pub fn has_rules_at_or_below(&self, path: &Path) -> bool {
+        self.literal.iter().any(|rule| rule.starts_with(path)) || self.globs.iter().any(|glob| glob.may_match_at_or_below(path))
+    }
+
+    /// Whether a rule could apply to something strictly below a directory
+    pub fn has_rules_below(&self, dir: &Path) -> bool {
+        self.literal.iter().any(|rule| rule != dir && rule.starts_with(dir))
+            || self.globs.iter().any(|glob| {
+                glob.may_match_at_or_below(dir) && glob.depth.is_none_or(|depth| dir.components().count() < depth)
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        self.literal.len() + self.globs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every rule, globs with their prefix canonicalized
+    pub fn iter(&self) -> impl Iterator<Item = &Path> {
+        self.literal.iter().map(PathBuf::as_path).chain(self.globs.iter().map(|glob| glob.rule.as_path()))
+    }
+}
+
+/// Checks that a configured path rule's wildcards compile
+pub fn check_path_rule(rule: &str) -> Result<(), String> {
+    match split_glob(&normalize_separators(rule)) {
+        Some((_, pattern)) => compile(&pattern).map(|_| ()).map_err(|e| format!("Invalid path pattern '{}': {}", rule, e)),
+        None => Ok(()),
+    }
+}
+
+fn normalize_separators(rule: &str) -> String {
+    if cfg!(target_os = "windows") {
+        rule.replace('/', "\\")
+    } else {
+        rule.replace('\\', "/")
+    }
+}
+
+/// Splits a rule at its first wildcard component into the literal directory and a `/`-separated pattern
+fn split_glob(rule: &str) -> Option<(String, String)> {
+    let path = Path::new(rule);
+    let wildcard = path
+        .components()
+        .position(|component| matches!(component, Component::Normal(part) if part.to_string_lossy().contains(GLOB_CHARS)))?;
+    let literal: PathBuf = path.components().take(wildcard).collect();
+    let pattern: Vec<String> = path
+        .components()
+        .skip(wildcard)
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Some((literal.to_string_lossy().to_string(), pattern.join("/")))
+}
+
+/// Compiles the pattern part of a rule to match the rest of a path and everything below it
+fn compile(pattern: &str) -> Result<GlobSet, globset::Error> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in [pattern.to_string(), format!("{}/**", pattern)] {
+        set.add(
+            GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .case_insensitive(cfg!(any(windows, target_os = "macos")))
+                .build()?,
+        );
+    }
+    set.build()
+}
+
+/// Canonicalizes a configured policy path when possible
+pub fn normalize_rule_path(p: &str, kind: &str) -> PathBuf {
+    let path = extended_path(Path::new(&normalize_separators(p)));
+
+    match path.canonicalize() {
+        Ok(canonical) => {
+            debug!("Added {}: {:?} (canonical: {:?})", kind, path, canonical);
+            canonical
+        },
+        Err(e) => {
+            debug!("Could not canonicalize {} {:?}: {}, using as-is", kind, path, e);
+            // If canonicalization fails, use the path as-is (it might not exist yet)
+            path
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_path_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let projects = root.join("home/alice/Projects");
+        std::fs::create_dir_all(&projects).unwrap();
+        let pattern = format!("{}/home/*/Projects/", dir.path().display());
+
+        assert!(check_path_rule(&pattern).is_ok());
+        assert!(check_path_rule("/home/[a/Projects").is_err());
+
+        let rules = PathRules::new(&[pattern, format!("{}/plain", dir.path().display())], "allowed path");
+        assert_eq!(rules.len(), 2);
+        assert!(rules.matching(&projects).is_some());
+        assert!(rules.matching(&projects.join("app/src/main.rs")).is_some());
+        assert!(rules.matching(&root.join("home/bob/Projects")).is_some());
+        assert!(rules.matching(&root.join("home/alice/Documents")).is_none());
+        assert!(rules.matching(&root.join("home/alice")).is_none());
+        assert!(rules.matching(&root.join("plain/file")).is_some());
+
+        // `*` stays within one component, so the rule's depth is fixed
+        assert!(rules.matching(&root.join("home/a/b/Projects")).is_none());
+        assert!(rules.has_rules_below(&root.join("home")));
+        assert!(rules.has_rules_below(&root.join("home/alice")));
+        assert!(!rules.has_rules_below(&projects));
+        assert!(rules.has_rules_at_or_below(&projects));
+        assert!(!rules.has_rules_at_or_below(&projects.join("app")));
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::config::get_config_dir;
+use crate::models::Config;
+use crate::security::encryption::{key_file_path, ContentCipher};
+
+const USAGE: &str = "usage: exex secrets set <name> | list | remove <name>   (set reads the value from stdin)";
+
+/// Prefix of environment variables that supply a secret instead of the store
+///
+/// Deployments that keep secrets in an OS keyring can export them this way so
+/// they never touch disk.
+pub const SECRET_ENV_PREFIX: &str = "EXEX_SECRET_";
+
+/// Checks a secret name: letters, digits, `-`, `_` and `.`
+pub fn is_secret_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// The environment variable that overrides a secret, e.g. `EXEX_SECRET_DEPLOY_TOKEN` for `deploy-token`
+pub fn secret_env_var(name: &str) -> String {
+    let suffix: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("{}{}", SECRET_ENV_PREFIX, suffix)
+}
+
+/// Named secrets kept in a file encrypted with the at-rest encryption key
+///
+/// Values are only ever handed to the processes they are configured for; no
+/// endpoint returns them.
+pub struct SecretStore {
+    path: PathBuf,
+    key_file: PathBuf,
+    cipher: OnceLock<ContentCipher>,
+}
+
+impl SecretStore {
+    pub fn new(path: PathBuf, cipher: ContentCipher) -> Self {
+        Self {
+            key_file: PathBuf::new(),
+            path,
+            cipher: OnceLock::from(cipher),
+        }
+    }
+
+    /// `secrets.enc` in the config directory, encrypted with the configured key
+    ///
+    /// The key is loaded on first use, so servers without secrets never create one.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            path: get_config_dir()
+                .map(|dir| dir.join("secrets.enc"))
+                .unwrap_or_else(|_| PathBuf::from("secrets.enc")),
+            key_file: key_file_path(config.security.encryption_key_file.as_deref()),
+            cipher: OnceLock::new(),
+        }
+    }
+
+    fn cipher(&self) -> Result<&ContentCipher, String> {
+        if let Some(cipher) = self.cipher.get() {
+            return Ok(cipher);
+        }
+        let cipher = ContentCipher::load_or_create(&self.key_file)?;
+        Ok(self.cipher.get_or_init(|| cipher))
+    }
+
+    /// Looks a secret up, preferring its `EXEX_SECRET_*` environment variable
+    pub fn get(&self, name: &str) -> Result<Option<String>, String> {
+        if let Ok(value) = std::env::var(secret_env_var(name)) {
+            return Ok(Some(value));
+        }
+        Ok(self.load()?.remove(name))
+    }
+
+    pub fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        let mut secrets = self.load()?;
+        secrets.insert(name.to_string(), value.to_string());
+        self.save(&secrets)
+    }
+
+    /// Removes a secret, returning whether it was stored
+    pub fn remove(&self, name: &str) -> Result<bool, String> {
+        let mut secrets = self.load()?;
+        let removed = secrets.remove(name).is_some();
+        if removed {
+            self.save(&secrets)?;
+        }
+        Ok(removed)
+    }
+
+    /// Names of the stored secrets, without their values
+    pub fn names(&self) -> Result<Vec<String>, String> {
+        Ok(self.load()?.into_keys().collect())
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>, String> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(format!("Failed to read secrets from {:?}: {}", self.path, e)),
+        };
+        let plaintext = self.cipher()?.decrypt(&data)?;
+        serde_json::from_slice(&plaintext).map_err(|e| format!("Secrets file {:?} is corrupted: {}", self.path, e))
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), String> {
+        let plaintext = serde_json::to_vec(secrets).map_err(|e| e.to_string())?;
+        let data = self.cipher()?.encrypt(&plaintext)?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create secrets directory: {}", e))?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&data))
+            .map_err(|e| format!("Failed to write secrets to {:?}: {}", self.path, e))
+    }
+}
+
+/// Runs the `exex secrets ...` subcommand and returns the process exit code
+///
+/// Exits with 0 on success, 1 when the store cannot be used or the secret is
+/// missing, and 2 on usage errors.
+pub fn run_secrets_command(args: &[String], config: &Config) -> i32 {
+    let (action, name) = match args {
+        [action] if action == "list" => (action.as_str(), None),
+        [action, name] if action == "set" || action == "remove" => (action.as_str(), Some(name.as_str())),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    if let Some(name) = name.filter(|name| !is_secret_name(name)) {
+        eprintln!("Invalid secret name: {}\n{}", name, USAGE);
+        return 2;
+    }
+
+    let store = SecretStore::from_config(config);
+    let result = match (action, name) {
+        ("set", Some(name)) => {
+            let mut value = String::new();
+            std::io::stdin()
+                .read_to_string(&mut value)
+                .map_err(|e| format!("Failed to read the secret from stdin: {}", e))
+                .and_then(|_| store.set(name, value.trim_end_matches(['\r', '\n'])))
+        }
+        ("remove", Some(name)) => store
+            .remove(name)
+            .and_then(|removed| if removed { Ok(()) } else { Err(format!("Secret not found: {}", name)) }),
+        _ => store.names().map(|names| names.iter().for_each(|name| println!("{}", name))),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_store() {
+        let dir = std::env::temp_dir().join(format!("exex-secrets-{}", uuid::Uuid::new_v4().simple()));
+        let store = SecretStore::new(dir.join("secrets.enc"), ContentCipher::from_key(&[3u8; 32]));
+
+        assert_eq!(store.get("deploy-token").unwrap(), None);
+        store.set("deploy-token", "hunter2").unwrap();
+        store.set("other", "value").unwrap();
+        assert_eq!(store.get("deploy-token").unwrap().as_deref(), Some("hunter2"));
+        assert_eq!(store.names().unwrap(), ["deploy-token", "other"]);
+
+        // The file holds ciphertext only
+        let data = fs::read(dir.join("secrets.enc")).unwrap();
+        assert!(ContentCipher::is_encrypted(&data));
+        assert!(!data.windows(7).any(|w| w == b"hunter2"));
+
+        // Another key cannot read it
+        let other = SecretStore::new(dir.join("secrets.enc"), ContentCipher::from_key(&[4u8; 32]));
+        assert!(other.get("deploy-token").is_err());
+
+        assert!(store.remove("other").unwrap());
+        assert!(!store.remove("other").unwrap());
+        assert_eq!(store.names().unwrap(), ["deploy-token"]);
+
+        assert_eq!(secret_env_var("deploy-token.v2"), "EXEX_SECRET_DEPLOY_TOKEN_V2");
+        assert!(is_secret_name("deploy-token.v2"));
+        assert!(!is_secret_name("../token"));
+        assert!(!is_secret_name(""));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
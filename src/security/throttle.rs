@@ -0,0 +1,177 @@
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::models::ThrottleState;
+
+/// Header clients may send to be throttled under a stable identity instead of their address
+pub const CLIENT_ID_HEADER: &str = "X-Exex-Client-Id";
+
+/// Idle buckets are pruned once this many clients are tracked
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/// Identity that per-client limits are keyed by
+///
+/// Taken from `X-Exex-Client-Id` when present, else the peer address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientId(pub String);
+
+impl ClientId {
+    pub fn of(req: &HttpRequest) -> Self {
+        let id = req
+            .headers()
+            .get(CLIENT_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .or_else(|| req.peer_addr().map(|addr| addr.ip().to_string()))
+            .unwrap_or_else(|| "unknown".to_string());
+        ClientId(id)
+    }
+}
+
+impl FromRequest for ClientId {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(ClientId::of(req)))
+    }
+}
+
+struct Bucket {
+    /// Bytes that may be written without waiting; negative while a client is in debt
+    tokens: f64,
+    updated: Instant,
+    bytes_written: u64,
+    throttled_writes: u64,
+}
+
+/// Per-client token buckets limiting write throughput
+///
+/// Each client may burst up to one second's worth of bytes; beyond that, writes
+/// are delayed until the bucket refills rather than rejected.
+pub struct WriteThrottle {
+    bytes_per_sec: u64,
+    clients: Mutex<HashMap<String, Bucket>>,
+}
+
+impl WriteThrottle {
+    /// Creates a throttle; a rate of zero disables throttling
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.bytes_per_sec > 0
+    }
+
+    /// Configured limit in bytes per second; 0 when unlimited
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    fn capacity(&self) -> f64 {
+        self.bytes_per_sec as f64
+    }
+
+    /// Accounts `bytes` against a client and returns how long the write must wait
+    pub fn reserve(&self, client: &ClientId, bytes: u64) -> Duration {
+        if !self.is_enabled() {
+            return Duration::ZERO;
+        }
+
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&client.0) {
+            let capacity = self.capacity();
+            let rate = self.bytes_per_sec as f64;
+            clients.retain(|_, bucket| refilled(bucket, now, rate, capacity) < capacity);
+        }
+
+        let bucket = clients.entry(client.0.clone()).or_insert_with(|| Bucket {
+            tokens: self.capacity(),
+            updated: now,
+            bytes_written: 0,
+            throttled_writes: 0,
+        });
+        bucket.tokens = refilled(bucket, now, self.bytes_per_sec as f64, self.capacity());
+        bucket.updated = now;
+        bucket.tokens -= bytes as f64;
+        bucket.bytes_written += bytes;
+
+        if bucket.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        bucket.throttled_writes += 1;
+        Duration::from_secs_f64(-bucket.tokens / self.bytes_per_sec as f64)
+    }
+
+    /// Current throttle state for a client
+    pub fn state(&self, client: &ClientId) -> ThrottleState {
+        let capacity = self.capacity();
+        let clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        let bucket = clients.get(&client.0);
+        let tokens = bucket
+            .map(|b| refilled(b, Instant::now(), self.bytes_per_sec as f64, capacity))
+            .unwrap_or(capacity);
+        let delay = if tokens < 0.0 && self.is_enabled() {
+            Duration::from_secs_f64(-tokens / self.bytes_per_sec as f64)
+        } else {
+            Duration::ZERO
+        };
+
+        ThrottleState {
+            enabled: self.is_enabled(),
+            bytes_per_sec: self.bytes_per_sec,
+            burst_bytes: self.bytes_per_sec,
+            available_bytes: tokens.max(0.0) as u64,
+            throttled: !delay.is_zero(),
+            delay_ms: delay.as_millis() as u64,
+            bytes_written: bucket.map(|b| b.bytes_written).unwrap_or(0),
+            throttled_writes: bucket.map(|b| b.throttled_writes).unwrap_or(0),
+        }
+    }
+}
+
+fn refilled(bucket: &Bucket, now: Instant, rate: f64, capacity: f64) -> f64 {
+    let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * rate).min(capacity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_delay() {
+        let throttle = WriteThrottle::new(1000);
+        let client = ClientId("sync".to_string());
+
+        assert_eq!(throttle.reserve(&client, 1000), Duration::ZERO);
+        let delay = throttle.reserve(&client, 500);
+        assert!(delay > Duration::from_millis(400) && delay <= Duration::from_millis(500));
+
+        let state = throttle.state(&client);
+        assert!(state.throttled);
+        assert_eq!(state.bytes_written, 1500);
+        assert_eq!(state.throttled_writes, 1);
+
+        // Other clients have their own budget
+        assert_eq!(throttle.reserve(&ClientId("editor".to_string()), 1000), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_disabled_throttle() {
+        let throttle = WriteThrottle::new(0);
+        let client = ClientId("sync".to_string());
+        assert_eq!(throttle.reserve(&client, u64::MAX), Duration::ZERO);
+        assert!(!throttle.state(&client).enabled);
+    }
+}
@@ -0,0 +1,54 @@
+use std::io;
+use std::net::IpAddr;
+
+use crate::models::ServerConfig;
+
+/// What `HttpServer::bind_openssl` is given
+#[cfg(feature = "tls")]
+pub type Acceptor = openssl::ssl::SslAcceptorBuilder;
+
+/// Builds without the `tls` feature can never produce an acceptor
+#[cfg(not(feature = "tls"))]
+pub enum Acceptor {}
+
+/// The HTTPS acceptor for `server.tls`, or `None` to serve plain HTTP
+///
+/// Certificates are read again on every start, so a renewed certificate
+/// is picked up by a restart or a remote policy change.
+#[cfg(feature = "tls")]
+pub fn acceptor(server: &ServerConfig) -> io::Result<Option<Acceptor>> {
+    use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+
+    let Some(tls) = server.tls.as_ref().filter(|tls| tls.enabled) else {
+        return Ok(None);
+    };
+    let failed = |what: &str, path: &str, e: openssl::error::ErrorStack| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Failed to load TLS {} {}: {}", what, path, e))
+    };
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).map_err(io::Error::other)?;
+    builder
+        .set_certificate_chain_file(&tls.cert_path)
+        .map_err(|e| failed("certificate", &tls.cert_path, e))?;
+    builder
+        .set_private_key_file(&tls.key_path, SslFiletype::PEM)
+        .map_err(|e| failed("key", &tls.key_path, e))?;
+    builder.check_private_key().map_err(|e| failed("key", &tls.key_path, e))?;
+    Ok(Some(builder))
+}
+
+/// Refuses to start when `server.tls` is enabled, rather than silently serving plain HTTP
+#[cfg(not(feature = "tls"))]
+pub fn acceptor(server: &ServerConfig) -> io::Result<Option<Acceptor>> {
+    match server.tls.as_ref().is_some_and(|tls| tls.enabled) {
+        true => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "server.tls is enabled, but this build lacks the `tls` feature (cargo build --release --features tls)",
+        )),
+        false => Ok(None),
+    }
+}
+
+/// Whether the server only listens on the loopback interface
+pub fn is_loopback_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
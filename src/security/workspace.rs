@@ -0,0 +1,210 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+use tracing::warn;
+
+/// Directory, at a workspace root, that holds the workspace's policy
+pub const POLICY_DIR: &str = ".exex";
+
+/// Policy file inside [`POLICY_DIR`]
+pub const POLICY_FILE: &str = "policy.json";
+
+/// Contents of `.exex/policy.json`
+///
+/// Every field only narrows the daemon's own policy; unknown fields are
+/// rejected so a file cannot appear to grant access it does not.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspacePolicyFile {
+    /// Globs, relative to the workspace root, that may not be read, written or used as a working directory
+    pub blocked_paths: Option<Vec<String>>,
+    /// When set, commands run in the workspace must also be listed here
+    pub command_whitelist: Option<Vec<String>>,
+    /// Commands that may not run in the workspace
+    pub command_blacklist: Option<Vec<String>>,
+}
+
+/// A parsed workspace policy
+#[derive(Debug)]
+pub struct WorkspacePolicy {
+    pub root: PathBuf,
+    blocked: GlobSet,
+    command_whitelist: Option<HashSet<String>>,
+    command_blacklist: HashSet<String>,
+    /// Set when the file could not be read or parsed; the workspace is then closed to everything
+    deny_all: bool,
+}
+
+impl WorkspacePolicy {
+    /// Parses a policy file for the workspace at `root`
+    pub fn parse(root: &Path, content: &str) -> Result<Self, String> {
+        let file: WorkspacePolicyFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            blocked: build_glob_set(&file.blocked_paths.unwrap_or_default())
+                .map_err(|e| format!("Invalid blocked path {}", e))?,
+            command_whitelist: file.command_whitelist.map(|commands| commands.into_iter().collect()),
+            command_blacklist: file.command_blacklist.unwrap_or_default().into_iter().collect(),
+            deny_all: false,
+        })
+    }
+
+    fn deny_all(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            blocked: GlobSet::empty(),
+            command_whitelist: None,
+            command_blacklist: HashSet::new(),
+            deny_all: true,
+        }
+    }
+
+    /// Whether a canonical path inside the workspace, or one of its parent directories, is blocked
+    pub fn is_path_blocked(&self, path: &Path) -> bool {
+        if self.deny_all {
+            return true;
+        }
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        relative
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.blocked.is_match(ancestor))
+    }
+
+    /// Whether a command name may run in the workspace
+    pub fn is_command_allowed(&self, command_name: &str) -> bool {
+        !self.deny_all
+            && !self.command_blacklist.contains(command_name)
+            && self
+                .command_whitelist
+                .as_ref()
+                .is_none_or(|whitelist| whitelist.contains(command_name))
+    }
+}
+
+/// Compiles path patterns that are matched against paths relative to a base directory
+///
+/// Patterns without a `/` match a name at any depth, as in `.gitignore`; a
+/// leading `/`, or a `/` inside the pattern, anchors it at the base.
+pub fn build_glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        let trimmed = pattern.trim_end_matches('/');
+        let anchored = match trimmed.strip_prefix('/') {
+            Some(rest) => rest.to_string(),
+            None if trimmed.contains('/') => trimmed.to_string(),
+            None => format!("**/{}", trimmed),
+        };
+        let glob = GlobBuilder::new(&anchored)
+            .literal_separator(true)
+            .case_insensitive(cfg!(any(windows, target_os = "macos")))
+            .build()
+            .map_err(|e| format!("'{}': {}", pattern, e))?;
+        set.add(glob);
+    }
+    set.build().map_err(|e| e.to_string())
+}
+
+struct CachedPolicy {
+    modified: Option<SystemTime>,
+    len: u64,
+    policy: Arc<WorkspacePolicy>,
+}
+
+/// Finds and caches the `.exex/policy.json` files that apply to a path
+///
+/// Files are looked up on every check, so edits take effect on the next
+/// request; a file is only parsed again when its size or mtime changes.
+#[derive(Default)]
+pub struct WorkspacePolicies {
+    cache: Mutex<HashMap<PathBuf, CachedPolicy>>,
+}
+
+impl WorkspacePolicies {
+    /// Policies of every workspace enclosing a canonical path, innermost first
+    pub fn for_path(&self, path: &Path) -> Vec<Arc<WorkspacePolicy>> {
+        path.ancestors()
+            .filter_map(|dir| self.load(dir, &dir.join(POLICY_DIR).join(POLICY_FILE)))
+            .collect()
+    }
+
+    fn load(&self, root: &Path, file: &Path) -> Option<Arc<WorkspacePolicy>> {
+        let Ok(metadata) = std::fs::metadata(file) else {
+            self.cache.lock().unwrap_or_else(PoisonError::into_inner).remove(file);
+            return None;
+        };
+        let modified = metadata.modified().ok();
+        if let Some(cached) = self.cache.lock().unwrap_or_else(PoisonError::into_inner).get(file)
+            && cached.modified == modified
+            && cached.len == metadata.len()
+        {
+            return Some(cached.policy.clone());
+        }
+
+        let parsed = std::fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|content| WorkspacePolicy::parse(root, &content));
+        let policy = Arc::new(parsed.unwrap_or_else(|e| {
+            warn!("Workspace policy {:?} is invalid, denying access to its workspace: {}", file, e);
+            WorkspacePolicy::deny_all(root)
+        }));
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner).insert(
+            file.to_path_buf(),
+            CachedPolicy {
+                modified,
+                len: metadata.len(),
+                policy: policy.clone(),
+            },
+        );
+        Some(policy)
+    }
+}
+
+/// Whether a canonical path is a workspace's policy directory or lies inside one
+pub fn is_policy_path(path: &Path) -> bool {
+    path.ancestors()
+        .any(|dir| dir.file_name().is_some_and(|name| name == POLICY_DIR) && dir.join(POLICY_FILE).is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_policy() {
+        let root = Path::new("/work/repo");
+        let policy = WorkspacePolicy::parse(
+            root,
+            r#"{
+                "blocked_paths": ["*.pem", "secrets/", "/build/**"],
+                "command_whitelist": ["cargo", "git"],
+                "command_blacklist": ["git"]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(policy.is_path_blocked(&root.join("keys/server.pem")));
+        assert!(policy.is_path_blocked(&root.join("secrets")));
+        assert!(policy.is_path_blocked(&root.join("secrets/token.txt")));
+        assert!(policy.is_path_blocked(&root.join("build/out/app")));
+        assert!(!policy.is_path_blocked(&root.join("src/build/mod.rs")));
+        assert!(!policy.is_path_blocked(&root.join("src/main.rs")));
+        assert!(!policy.is_path_blocked(root));
+        assert!(!policy.is_path_blocked(Path::new("/elsewhere/key.pem")));
+
+        assert!(policy.is_command_allowed("cargo"));
+        assert!(!policy.is_command_allowed("git"));
+        assert!(!policy.is_command_allowed("npm"));
+
+        // Files may only restrict, so fields that would broaden access are rejected
+        assert!(WorkspacePolicy::parse(root, r#"{"allowed_paths": ["/"]}"#).is_err());
+        assert!(WorkspacePolicy::parse(root, r#"{"blocked_paths": ["a/[b"]}"#).is_err());
+        assert!(WorkspacePolicy::deny_all(root).is_path_blocked(&root.join("README.md")));
+    }
+}
@@ -0,0 +1,189 @@
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+use tracing::{error, info, warn};
+
+use crate::models::{CheckStatus, Config, SelfTestCheck, ServerConfig};
+use crate::platform::{display_path, expand_path_entries, resolve_command_in, search_path};
+use crate::security::tls::acceptor;
+
+/// Checks that the daemon's files, directories and tools are usable with this configuration
+///
+/// Runs at startup and through `POST /api/admin/selftest`. Checks only read
+/// state or write and remove their own probe files.
+pub fn run_self_test(config: &Config) -> Vec<SelfTestCheck> {
+    let mut checks = vec![check_audit_file(Path::new(&config.logging.audit_file))];
+    checks.extend(config.security.allowed_paths.iter().map(|root| check_workspace_root(root)));
+    checks.push(check_temp_dir(&std::env::temp_dir()));
+    let aliases = config.security.command_aliases.clone().unwrap_or_default();
+    let extra_path = expand_path_entries(config.security.extra_path_entries.as_deref().unwrap_or_default());
+    let search_path = search_path(&extra_path).or_else(|| std::env::var_os("PATH"));
+    checks.extend(config.security.command_whitelist.iter().map(|command| {
+        let program = aliases.get(command).map(String::as_str).unwrap_or(command);
+        check_command(command, program, search_path.as_deref())
+    }));
+    checks.push(check_tls(&config.server));
+    checks
+}
+
+/// Whether every check passed or only warned
+pub fn is_healthy(checks: &[SelfTestCheck]) -> bool {
+    checks.iter().all(|check| check.status != CheckStatus::Fail)
+}
+
+/// Logs each check at a level matching its outcome
+pub fn log_self_test(checks: &[SelfTestCheck]) {
+    for check in checks {
+        let component = match &check.target {
+            Some(target) => format!("{} {}", check.component, target),
+            None => check.component.clone(),
+        };
+        match check.status {
+            CheckStatus::Pass | CheckStatus::Skipped => info!("Self-test {}: {:?}, {}", component, check.status, check.detail),
+            CheckStatus::Warn => warn!("Self-test {}: {}", component, check.detail),
+            CheckStatus::Fail => error!("Self-test {} failed: {}", component, check.detail),
+        }
+    }
+}
+
+fn check(component: &str, target: Option<&str>, status: CheckStatus, detail: impl Into<String>) -> SelfTestCheck {
+    SelfTestCheck {
+        component: component.to_string(),
+        target: target.map(str::to_string),
+        status,
+        detail: detail.into(),
+    }
+}
+
+fn check_audit_file(path: &Path) -> SelfTestCheck {
+    let target = display_path(path);
+    let target = Some(target.as_str());
+
+    if path.exists() {
+        return match OpenOptions::new().append(true).open(path) {
+            Ok(_) => check("audit_file", target, CheckStatus::Pass, "writable"),
+            Err(e) => check("audit_file", target, CheckStatus::Fail, format!("not writable: {}", e)),
+        };
+    }
+
+    // The file is created on first use, so its directory must accept new files
+    let parent = path
+        .parent()
+        .map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir });
+    match parent {
+        Some(dir) => match probe_directory(dir) {
+            Ok(()) => check("audit_file", target, CheckStatus::Pass, "will be created on first use"),
+            Err(e) => check("audit_file", target, CheckStatus::Fail, format!("cannot be created: {}", e)),
+        },
+        None => check("audit_file", target, CheckStatus::Fail, "has no parent directory"),
+    }
+}
+
+fn check_workspace_root(root: &str) -> SelfTestCheck {
+    let target = Some(root);
+    if root.contains('*') {
+        return check("workspace_root", target, CheckStatus::Skipped, "pattern is matched per request");
+    }
+
+    match fs::metadata(root) {
+        Ok(metadata) if metadata.is_dir() => check("workspace_root", target, CheckStatus::Pass, "exists"),
+        Ok(_) => check("workspace_root", target, CheckStatus::Warn, "is not a directory"),
+        Err(e) => check("workspace_root", target, CheckStatus::Warn, format!("does not exist: {}", e)),
+    }
+}
+
+fn check_temp_dir(dir: &Path) -> SelfTestCheck {
+    let target = display_path(dir);
+    match probe_directory(dir) {
+        Ok(()) => check("temp_dir", Some(&target), CheckStatus::Pass, "writable"),
+        Err(e) => check("temp_dir", Some(&target), CheckStatus::Fail, format!("not usable: {}", e)),
+    }
+}
+
+fn check_command(command: &str, program: &str, search_path: Option<&OsStr>) -> SelfTestCheck {
+    match resolve_command_in(program, search_path) {
+        Some(path) => check("command", Some(command), CheckStatus::Pass, display_path(&path)),
+        None => check("command", Some(command), CheckStatus::Warn, "not found in PATH"),
+    }
+}
+
+fn check_tls(server: &ServerConfig) -> SelfTestCheck {
+    let Some(tls) = server.tls.as_ref().filter(|tls| tls.enabled) else {
+        return check("tls", None, CheckStatus::Skipped, "TLS is not configured; the server listens on plain HTTP");
+    };
+    match acceptor(server) {
+        Ok(_) => check("tls", Some(&tls.cert_path), CheckStatus::Pass, "certificate and key loaded"),
+        Err(e) => check("tls", Some(&tls.cert_path), CheckStatus::Fail, e.to_string()),
+    }
+}
+
+/// Writes, reads back and removes a probe file
+fn probe_directory(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".exex-selftest-{}", uuid::Uuid::new_v4().simple()));
+    let result = fs::write(&probe, b"exex").and_then(|_| fs::read(&probe)).and_then(|content| {
+        if content == b"exex" {
+            Ok(())
+        } else {
+            Err(std::io::Error::other("probe file read back differently"))
+        }
+    });
+    let _ = fs::remove_file(&probe);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use crate::models::TlsConfig;
+
+    #[test]
+    fn test_self_test_components() {
+        let dir = std::env::temp_dir().join(format!("exex-selftest-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut config = get_default_config();
+        config.logging.audit_file = dir.join("audit.log").to_string_lossy().to_string();
+        config.security.allowed_paths = vec![
+            dir.to_string_lossy().to_string(),
+            dir.join("missing").to_string_lossy().to_string(),
+            "/home/*/Projects/".to_string(),
+        ];
+        config.security.command_whitelist = vec![
+            if cfg!(windows) { "cmd" } else { "sh" }.to_string(),
+            "exex-no-such-command".to_string(),
+        ];
+
+        let checks = run_self_test(&config);
+        let statuses: Vec<(&str, CheckStatus)> = checks.iter().map(|c| (c.component.as_str(), c.status)).collect();
+        assert_eq!(
+            statuses,
+            [
+                ("audit_file", CheckStatus::Pass),
+                ("workspace_root", CheckStatus::Pass),
+                ("workspace_root", CheckStatus::Warn),
+                ("workspace_root", CheckStatus::Skipped),
+                ("temp_dir", CheckStatus::Pass),
+                ("command", CheckStatus::Pass),
+                ("command", CheckStatus::Warn),
+                ("tls", CheckStatus::Skipped),
+            ]
+        );
+        assert!(is_healthy(&checks));
+        // Probing the audit directory leaves nothing behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        // An enabled certificate that cannot be loaded would keep the server from starting
+        config.server.tls = Some(TlsConfig {
+            enabled: true,
+            cert_path: dir.join("cert.pem").to_string_lossy().to_string(),
+            key_path: dir.join("key.pem").to_string_lossy().to_string(),
+        });
+        assert_eq!(run_self_test(&config).last().unwrap().status, CheckStatus::Fail);
+        config.server.tls = None;
+
+        config.logging.audit_file = dir.join("missing").join("audit.log").to_string_lossy().to_string();
+        assert!(!is_healthy(&run_self_test(&config)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use similar::TextDiff;
+
+use crate::models::{FileChangeStatus, SessionFile};
+use crate::platform::{create_dir_all_with_mode, special_file_at, write_with_mode, Placement};
+use crate::security::SecurityManager;
+
+/// Sessions open at once; finalize or abort one to start another
+pub const MAX_SESSIONS: usize = 32;
+
+/// Files one session may snapshot
+pub const MAX_SESSION_FILES: usize = 1000;
+
+/// Open editing sessions by ID
+///
+/// A session snapshots a set of files when it starts. Edits are made through
+/// the regular endpoints; the session can then report a unified diff of
+/// everything that changed, or put every file back as it was. Snapshots are
+/// kept in memory and do not survive a restart.
+#[derive(Default)]
+pub struct SessionManager {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+}
+
+/// The files of a session as they were when it started
+pub struct Session {
+    files: Vec<Snapshot>,
+    /// Whether the session was started with `force`, which restoring protected files needs again
+    force: bool,
+}
+
+struct Snapshot {
+    path: PathBuf,
+    /// Path as given by the client, used in diffs and responses
+    display: String,
+    /// Raw bytes, or None if the file did not exist
+    original: Option<Vec<u8>>,
+    /// Whether the path was a symlink; one put there later is not written through
+    was_link: bool,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Snapshots `files` (resolved path and client path) and opens a session for them
+    ///
+    /// Files larger than `max_size` bytes are refused with
+    /// [`io::ErrorKind::FileTooLarge`]. This reads every file and must run off
+    /// the async executor.
+    pub fn start(&self, files: Vec<(PathBuf, String)>, max_size: u64, force: bool) -> io::Result<String> {
+        if self.sessions.lock().unwrap_or_else(PoisonError::into_inner).len() >= MAX_SESSIONS {
+            return Err(io::Error::other(format!("Too many open sessions (at most {})", MAX_SESSIONS)));
+        }
+        let mut snapshots = Vec::with_capacity(files.len());
+        for (path, display) in files {
+            let original = match read_capped(&path, max_size) {
+                Ok(data) => Some(data),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(io::Error::new(e.kind(), format!("Failed to snapshot {}: {}", display, e))),
+            };
+            let was_link = std::fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink());
+            snapshots.push(Snapshot { path, display, original, was_link });
+        }
+
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        if sessions.len() >= MAX_SESSIONS {
+            return Err(io::Error::other(format!("Too many open sessions (at most {})", MAX_SESSIONS)));
+        }
+        sessions.insert(id.clone(), Arc::new(Session { files: snapshots, force }));
+        Ok(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner).get(id).cloned()
+    }
+
+    /// Closes a session; None if it was not open
+    pub fn remove(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner).remove(id)
+    }
+}
+
+impl Session {
+    /// Compares every file with its snapshot and renders a unified diff of the changes
+    ///
+    /// `decode` turns stored bytes into content, e.g. to decrypt encrypted
+    /// paths. Content that is not UTF-8 is reported as a binary change.
+    pub fn diff(
+        &self,
+        decode: impl Fn(&Path, Vec<u8>) -> Result<Vec<u8>, String>,
+    ) -> io::Result<(Vec<SessionFile>, String)> {
+        let decode = |path: &Path, data: Option<Vec<u8>>| -> io::Result<Option<Vec<u8>>> {
+            data.map(|data| decode(path, data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+                .transpose()
+        };
+
+        let mut files = Vec::with_capacity(self.files.len());
+        let mut diff = String::new();
+        for snapshot in &self.files {
+            let current = match std::fs::read(&snapshot.path) {
+                Ok(data) => Some(data),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(io::Error::new(e.kind(), format!("Failed to read {}: {}", snapshot.display, e))),
+            };
+            let status = match (&snapshot.original, &current) {
+                (None, None) => FileChangeStatus::Unchanged,
+                (None, Some(_)) => FileChangeStatus::Added,
+                (Some(_), None) => FileChangeStatus::Deleted,
+                (Some(before), Some(after)) if before == after => FileChangeStatus::Unchanged,
+                (Some(_), Some(_)) => FileChangeStatus::Modified,
+            };
+            if status != FileChangeStatus::Unchanged {
+                let before = decode(&snapshot.path, snapshot.original.clone())?;
+                let after = decode(&snapshot.path, current)?;
+                diff.push_str(&unified_diff(&snapshot.display, before.as_deref(), after.as_deref()));
+            }
+            files.push(SessionFile { path: snapshot.display.clone(), status });
+        }
+        Ok((files, diff))
+    }
+
+    /// Puts every file back as it was, deleting files that did not exist
+    ///
+    /// Each path is checked against the policy again, and written like
+    /// `/api/write` writes, with the configured file and directory modes.
+    /// All files are attempted; the ones that could not be restored are returned.
+    pub fn restore(&self, security: &SecurityManager) -> Vec<(String, io::Error)> {
+        let mut failed = Vec::new();
+        for snapshot in &self.files {
+            if let Err(e) = self.restore_file(security, snapshot) {
+                failed.push((snapshot.display.clone(), e));
+            }
+        }
+        failed
+    }
+
+    fn restore_file(&self, security: &SecurityManager, snapshot: &Snapshot) -> io::Result<()> {
+        let path = &snapshot.path;
+        let denied = |error: String| io::Error::new(io::ErrorKind::PermissionDenied, error);
+        if !security.is_path_allowed(path) || security.is_workspace_policy_path(path) {
+            return Err(denied("access denied by security policy".to_string()));
+        }
+        security.check_protected(path, self.force).map_err(denied)?;
+
+        let Some(data) = &snapshot.original else {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            };
+        };
+        if !snapshot.was_link && std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Err(denied("replaced by a symlink since the session started".to_string()));
+        }
+        if let Some(special) = special_file_at(path) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("not a regular file: it is a {}", special)));
+        }
+        if std::fs::read(path).is_ok_and(|current| current == *data) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            create_dir_all_with_mode(parent, security.dir_mode())?;
+        }
+        write_with_mode(path, data, security.file_mode(), false, Placement::Replace, false).map(|_| ())
+    }
+}
+
+/// Reads a whole file, failing if it holds more than `max_size` bytes
+fn read_capped(path: &Path, max_size: u64) -> io::Result<Vec<u8>> {
+    let too_large = || io::Error::new(io::ErrorKind::FileTooLarge, "file size exceeds maximum allowed size");
+    let file = std::fs::File::open(path)?;
+    if file.metadata()?.len() > max_size {
+        return Err(too_large());
+    }
+    // The file may grow while it is read
+    let mut data = Vec::new();
+    file.take(max_size + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > max_size {
+        return Err(too_large());
+    }
+    Ok(data)
+}
+
+/// Unified diff of one file, `None` standing for a missing file
+fn unified_diff(path: &str, before: Option<&[u8]>, after: Option<&[u8]>) -> String {
+    let path = path.trim_start_matches('/');
+    let old_name = if before.is_some() { format!("a/{}", path) } else { "/dev/null".to_string() };
+    let new_name = if after.is_some() { format!("b/{}", path) } else { "/dev/null".to_string() };
+    match (
+        std::str::from_utf8(before.unwrap_or_default()),
+        std::str::from_utf8(after.unwrap_or_default()),
+    ) {
+        (Ok(before), Ok(after)) => TextDiff::from_lines(before, after)
+            .unified_diff()
+            .header(&old_name, &new_name)
+            .to_string(),
+        _ => format!("Binary files {} and {} differ\n", old_name, new_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+
+    fn open_security() -> SecurityManager {
+        let mut config = get_default_config();
+        config.security.allowed_paths = vec![];
+        config.security.file_mode = Some("600".to_string());
+        SecurityManager::new(config)
+    }
+
+    #[test]
+    fn test_session_diff_and_restore() {
+        let dir = std::env::temp_dir().join(format!("exex-session-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (kept, edited, created) = (dir.join("kept.txt"), dir.join("edited.txt"), dir.join("created.txt"));
+        std::fs::write(&kept, "same\n").unwrap();
+        std::fs::write(&edited, "one\ntwo\n").unwrap();
+
+        let sessions = SessionManager::new();
+        let files = [&kept, &edited, &created].map(|p| (p.clone(), p.file_name().unwrap().to_string_lossy().to_string()));
+        let id = sessions.start(files.to_vec(), 1024, false).unwrap();
+
+        std::fs::write(&edited, "one\nTWO\n").unwrap();
+        std::fs::write(&created, "new\n").unwrap();
+        let session = sessions.get(&id).unwrap();
+        let (changes, diff) = session.diff(|_, data| Ok(data)).unwrap();
+        let statuses: Vec<_> = changes.iter().map(|f| f.status).collect();
+        assert_eq!(
+            statuses,
+            [FileChangeStatus::Unchanged, FileChangeStatus::Modified, FileChangeStatus::Added]
+        );
+        assert!(diff.contains("--- a/edited.txt\n+++ b/edited.txt\n"));
+        assert!(diff.contains("-two\n+TWO\n"));
+        assert!(diff.contains("--- /dev/null\n+++ b/created.txt\n"));
+        assert!(!diff.contains("kept.txt"));
+
+        std::fs::remove_file(&kept).unwrap();
+        assert!(session.restore(&open_security()).is_empty());
+        assert_eq!(std::fs::read_to_string(&kept).unwrap(), "same\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&kept).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "one\ntwo\n");
+        assert!(!created.exists());
+
+        assert!(sessions.remove(&id).is_some());
+        assert!(sessions.get(&id).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_session_restore_checks_policy() {
+        let dir = std::env::temp_dir().join(format!("exex-session-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (file, large) = (dir.join("file.txt"), dir.join("large.bin"));
+        std::fs::write(&file, "original\n").unwrap();
+        std::fs::write(&large, vec![0; 2048]).unwrap();
+
+        let sessions = SessionManager::new();
+        let too_large = sessions.start(vec![(large, "large.bin".to_string())], 1024, false).unwrap_err();
+        assert_eq!(too_large.kind(), io::ErrorKind::FileTooLarge);
+
+        let id = sessions.start(vec![(file.clone(), "file.txt".to_string())], 1024, false).unwrap();
+        let session = sessions.get(&id).unwrap();
+
+        // The policy in force at abort time decides, not the one the session started under
+        let mut config = get_default_config();
+        config.security.allowed_paths = vec![];
+        config.security.disallowed_paths = vec![dir.to_string_lossy().into_owned()];
+        std::fs::write(&file, "edited\n").unwrap();
+        let failed = session.restore(&SecurityManager::new(config));
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].1.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "edited\n");
+
+        #[cfg(unix)]
+        {
+            let outside = dir.join("outside.txt");
+            std::fs::write(&outside, "outside\n").unwrap();
+            std::fs::remove_file(&file).unwrap();
+            std::os::unix::fs::symlink(&outside, &file).unwrap();
+            let failed = session.restore(&open_security());
+            assert_eq!(failed.len(), 1);
+            assert_eq!(std::fs::read_to_string(&outside).unwrap(), "outside\n");
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
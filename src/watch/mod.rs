@@ -0,0 +1,382 @@
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::models::{WatchEvent, WatchEventKind, WatchInfo};
+use crate::platform::{display_path, extended_path};
+use crate::security::SecurityManager;
+
+/// Most paths that may be watched at once
+pub const MAX_WATCHES: usize = 64;
+
+/// Events buffered for each subscriber before slow ones start missing events
+const EVENT_CAPACITY: usize = 1024;
+
+/// Why a watch could not be registered
+#[derive(Debug)]
+pub enum WatchError {
+    /// The path is already watched; holds that watch
+    Duplicate(WatchInfo),
+    /// [`MAX_WATCHES`] paths are watched already
+    Limit,
+    /// The platform watcher refused the path
+    Failed(String),
+}
+
+struct Watch {
+    info: WatchInfo,
+    path: PathBuf,
+}
+
+impl Watch {
+    /// Whether a change to `path` is reported by this watch
+    fn covers(&self, path: &Path) -> bool {
+        path.starts_with(&self.path)
+            && (self.info.recursive || path == self.path || path.parent() == Some(self.path.as_path()))
+    }
+}
+
+/// Watched paths and the stream of changes below them
+///
+/// One platform watcher (inotify, FSEvents or ReadDirectoryChangesW) serves
+/// every watch. Changes are broadcast unfiltered; subscribers apply the
+/// current policy with [`visible_event`] before passing them on.
+pub struct WatchService {
+    watches: Arc<Mutex<HashMap<String, Watch>>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    events: broadcast::Sender<WatchEvent>,
+    /// Policy whose path cache structural changes are reported to
+    security: Arc<Mutex<Option<Arc<SecurityManager>>>>,
+}
+
+impl Default for WatchService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WatchEventKind {
+    /// Name of the kind, which is also the name of its server-sent event
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchEventKind::Create => "create",
+            WatchEventKind::Modify => "modify",
+            WatchEventKind::Delete => "delete",
+            WatchEventKind::Rename => "rename",
+        }
+    }
+}
+
+/// Event kind reported for a platform event, or None for accesses and unknown events
+fn event_kind(kind: &EventKind) -> Option<WatchEventKind> {
+    match kind {
+        EventKind::Create(_) => Some(WatchEventKind::Create),
+        EventKind::Remove(_) => Some(WatchEventKind::Delete),
+        // Halves of a rename are reported as the entry leaving and arriving
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => Some(WatchEventKind::Delete),
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Some(WatchEventKind::Create),
+        EventKind::Modify(ModifyKind::Name(_)) => Some(WatchEventKind::Rename),
+        EventKind::Modify(_) => Some(WatchEventKind::Modify),
+        EventKind::Any | EventKind::Access(_) | EventKind::Other => None,
+    }
+}
+
+/// Whether an event can change what a path resolves to, as opposed to its content
+fn is_structural(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)))
+}
+
+/// Turns a platform event into one event per watch that covers it
+fn watch_events(watches: &HashMap<String, Watch>, event: &Event) -> Vec<WatchEvent> {
+    let Some(kind) = event_kind(&event.kind) else {
+        return vec![];
+    };
+    let time = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+    let changes: Vec<(&PathBuf, Option<&PathBuf>)> =
+        if matches!(event.kind, EventKind::Modify(ModifyKind::Name(RenameMode::Both))) {
+            event.paths.first().map(|from| (from, event.paths.get(1))).into_iter().collect()
+        } else {
+            event.paths.iter().map(|path| (path, None)).collect()
+        };
+
+    let mut events = Vec::new();
+    for (path, to) in changes {
+        for watch in watches.values() {
+            if watch.covers(path) || to.is_some_and(|to| watch.covers(to)) {
+                events.push(WatchEvent {
+                    watch_id: watch.info.watch_id.clone(),
+                    kind,
+                    path: display_path(path),
+                    to: to.map(|to| display_path(to)),
+                    time: time.clone(),
+                });
+            }
+        }
+    }
+    events
+}
+
+/// The event as a client under the current policy may see it
+///
+/// Events on denied paths are dropped. A rename is reported as a delete when
+/// its destination is denied, and as a create when only its source is.
+pub fn visible_event(security: &SecurityManager, mut event: WatchEvent) -> Option<WatchEvent> {
+    let allowed = |path: &str| security.is_path_allowed(&extended_path(Path::new(path)));
+    let to_allowed = event.to.as_deref().map(allowed);
+    match (allowed(&event.path), to_allowed) {
+        (true, None | Some(true)) => Some(event),
+        (true, Some(false)) => {
+            event.kind = WatchEventKind::Delete;
+            event.to = None;
+            Some(event)
+        }
+        (false, Some(true)) => {
+            event.kind = WatchEventKind::Create;
+            event.path = event.to.take().unwrap_or_default();
+            Some(event)
+        }
+        (false, _) => None,
+    }
+}
+
+impl WatchService {
+    pub fn new() -> Self {
+        Self {
+            watches: Arc::new(Mutex::new(HashMap::new())),
+            watcher: Mutex::new(None),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            security: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Reports paths created, removed or renamed below a watch to the path cache of `security`
+    ///
+    /// Replaces the policy set before, since the server builds a new one when
+    /// its configuration changes.
+    pub fn set_policy(&self, security: Arc<SecurityManager>) {
+        *self.security.lock().unwrap_or_else(|e| e.into_inner()) = Some(security);
+    }
+
+    /// Starts the platform watcher on first use
+    fn start_watcher(&self) -> notify::Result<RecommendedWatcher> {
+        let (watches, events, security) = (self.watches.clone(), self.events.clone(), self.security.clone());
+        notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+            Ok(event) => {
+                if is_structural(&event.kind)
+                    && let Some(security) = security.lock().unwrap_or_else(|e| e.into_inner()).as_ref()
+                {
+                    for path in &event.paths {
+                        security.invalidate_path_cache(path);
+                    }
+                }
+                let Ok(watches) = watches.lock() else {
+                    return;
+                };
+                for change in watch_events(&watches, &event) {
+                    // Nobody may be listening
+                    let _ = events.send(change);
+                }
+            }
+            Err(e) => warn!("File watcher error: {}", e),
+        })
+    }
+
+    /// Watches `path`, which the caller has checked against the policy
+    ///
+    /// Registering a recursive watch walks the tree on some platforms, so this
+    /// is meant to run on a worker pool.
+    pub fn add(&self, path: PathBuf, recursive: bool) -> Result<WatchInfo, WatchError> {
+        let mut watcher = self.watcher.lock().unwrap_or_else(|e| e.into_inner());
+        {
+            let watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(existing) = watches.values().find(|watch| watch.path == path) {
+                return Err(WatchError::Duplicate(existing.info.clone()));
+            }
+            if watches.len() >= MAX_WATCHES {
+                return Err(WatchError::Limit);
+            }
+        }
+
+        if watcher.is_none() {
+            *watcher = Some(self.start_watcher().map_err(|e| WatchError::Failed(e.to_string()))?);
+        }
+        let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        if let Some(watcher) = watcher.as_mut() {
+            watcher.watch(&path, mode).map_err(|e| WatchError::Failed(e.to_string()))?;
+        }
+
+        let info = WatchInfo {
+            watch_id: uuid::Uuid::new_v4().simple().to_string(),
+            path: display_path(&path),
+            recursive,
+            created: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        };
+        info!("Watching {} (recursive: {})", info.path, recursive);
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        watches.insert(info.watch_id.clone(), Watch { info: info.clone(), path });
+        Ok(info)
+    }
+
+    /// Stops a watch, returning it; None when there is no such watch
+    pub fn remove(&self, id: &str) -> Option<WatchInfo> {
+        let mut watcher = self.watcher.lock().unwrap_or_else(|e| e.into_inner());
+        let watch = self.watches.lock().unwrap_or_else(|e| e.into_inner()).remove(id)?;
+        // The path may be gone already, which ends the platform watch by itself
+        if let Some(watcher) = watcher.as_mut()
+            && let Err(e) = watcher.unwatch(&watch.path)
+        {
+            warn!("Failed to stop watching {}: {}", watch.info.path, e);
+        }
+        info!("Stopped watching {}", watch.info.path);
+        Some(watch.info)
+    }
+
+    /// Every watch, oldest first
+    pub fn list(&self) -> Vec<WatchInfo> {
+        let watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        let mut list: Vec<WatchInfo> = watches.values().map(|watch| watch.info.clone()).collect();
+        list.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.path.cmp(&b.path)));
+        list
+    }
+
+    /// Receives every change from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchEvent> {
+        self.events.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::get_default_config;
+    use notify::event::{CreateKind, DataChange};
+    use std::time::Duration;
+
+    #[test]
+    fn test_watch_events() {
+        let watch = |id: &str, path: &str, recursive| {
+            let info = WatchInfo {
+                watch_id: id.to_string(),
+                path: path.to_string(),
+                recursive,
+                created: String::new(),
+            };
+            (id.to_string(), Watch { info, path: PathBuf::from(path) })
+        };
+        let watches = HashMap::from([watch("deep", "/work", true), watch("flat", "/work/app", false)]);
+        let ids = |event: Event| {
+            let mut ids: Vec<String> = watch_events(&watches, &event).into_iter().map(|e| e.watch_id).collect();
+            ids.sort();
+            ids
+        };
+
+        let created = Event::new(EventKind::Create(CreateKind::File)).add_path("/work/app/main.rs".into());
+        assert_eq!(ids(created), ["deep", "flat"]);
+        let nested = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path("/work/app/src/lib.rs".into());
+        assert_eq!(ids(nested), ["deep"]);
+        let outside = Event::new(EventKind::Create(CreateKind::File)).add_path("/workspace/a".into());
+        assert!(ids(outside).is_empty());
+
+        let renamed = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path("/other/a".into())
+            .add_path("/work/a".into());
+        let events = watch_events(&watches, &renamed);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, WatchEventKind::Rename);
+        assert_eq!(events[0].to.as_deref(), Some("/work/a"));
+    }
+
+    #[test]
+    fn test_visible_event_follows_policy() {
+        let root = std::env::temp_dir().join(format!("exex-watch-{}", uuid::Uuid::new_v4().simple()));
+        let denied = root.join("secret");
+        std::fs::create_dir_all(&denied).unwrap();
+        let mut config = get_default_config();
+        config.security.disallowed_paths = vec![denied.to_string_lossy().into_owned()];
+        // The default exceptions include the temp directory
+        config.security.allowed_paths = vec![];
+        let security = SecurityManager::new(config);
+        let event = |path: &Path, to: Option<&Path>| WatchEvent {
+            watch_id: "w".to_string(),
+            kind: WatchEventKind::Rename,
+            path: path.to_string_lossy().into_owned(),
+            to: to.map(|to| to.to_string_lossy().into_owned()),
+            time: String::new(),
+        };
+        let (open, hidden) = (root.join("a.txt"), denied.join("a.txt"));
+
+        assert!(visible_event(&security, event(&hidden, None)).is_none());
+        let moved_out = visible_event(&security, event(&open, Some(&hidden))).unwrap();
+        assert_eq!((moved_out.kind, moved_out.to), (WatchEventKind::Delete, None));
+        let moved_in = visible_event(&security, event(&hidden, Some(&open))).unwrap();
+        assert_eq!(moved_in.kind, WatchEventKind::Create);
+        assert_eq!(moved_in.path, open.to_string_lossy());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_watch_reports_changes() {
+        let root = std::env::temp_dir().join(format!("exex-watch-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        let service = WatchService::new();
+        let info = service.add(root.clone(), true).unwrap();
+        assert!(matches!(service.add(root.clone(), false), Err(WatchError::Duplicate(_))));
+
+        let mut events = service.subscribe();
+        std::fs::write(root.join("new.txt"), "x").unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        let mut seen = None;
+        while seen.is_none() && std::time::Instant::now() < deadline {
+            match events.try_recv() {
+                Ok(event) if event.path.ends_with("new.txt") => seen = Some(event),
+                Ok(_) => {}
+                Err(_) => std::thread::sleep(Duration::from_millis(20)),
+            }
+        }
+        let event = seen.expect("no event for the new file");
+        assert_eq!(event.watch_id, info.watch_id);
+
+        assert_eq!(service.remove(&info.watch_id).unwrap().path, info.path);
+        assert!(service.list().is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_changes_invalidate_path_cache() {
+        let root = std::env::temp_dir().join(format!("exex-watch-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(root.join("open").join("deep")).unwrap();
+        std::fs::create_dir_all(root.join("secret").join("deep")).unwrap();
+        let root = root.canonicalize().unwrap();
+        let mut config = get_default_config();
+        config.security.allowed_paths = vec![];
+        config.security.disallowed_paths = vec![root.join("secret").to_string_lossy().into_owned()];
+        config.security.path_cache_ttl_secs = Some(600);
+        let security = Arc::new(SecurityManager::new(config));
+        let file = root.join("open").join("deep").join("a.txt");
+        assert!(security.is_path_allowed(&file));
+
+        let service = WatchService::new();
+        service.set_policy(security.clone());
+        service.add(root.clone(), true).unwrap();
+
+        // Swapping an ancestor leaves the cached directory itself looking unchanged
+        std::fs::rename(root.join("open"), root.join("old")).unwrap();
+        std::os::unix::fs::symlink(root.join("secret"), root.join("open")).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while security.is_path_allowed(&file) && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!security.is_path_allowed(&file));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
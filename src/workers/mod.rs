@@ -0,0 +1,247 @@
+pub mod mutexes;
+
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::oneshot;
+use tracing::{error, info};
+
+use crate::models::{ServerConfig, WorkerPoolStats};
+use crate::platform::enter_background_mode;
+use crate::workers::mutexes::ExecMutexes;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Errors returned when work cannot be run on a pool
+#[derive(Debug)]
+pub enum PoolError {
+    /// The queue is full; the caller should back off and retry
+    Saturated(String),
+    /// The job panicked or the pool shut down before it finished
+    Canceled(String),
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::Saturated(pool) => write!(f, "{} worker pool is saturated, try again later", pool),
+            PoolError::Canceled(pool) => write!(f, "{} worker pool job did not complete", pool),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+#[derive(Default)]
+struct PoolCounters {
+    queued: AtomicUsize,
+    active: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Fixed-size pool of OS threads with a bounded job queue
+pub struct WorkerPool {
+    name: String,
+    workers: usize,
+    queue_limit: usize,
+    sender: SyncSender<Job>,
+    counters: Arc<PoolCounters>,
+}
+
+impl WorkerPool {
+    /// Starts `workers` threads sharing a queue of at most `queue_limit` jobs
+    pub fn new(name: &str, workers: usize, queue_limit: usize) -> Self {
+        Self::start(name, workers, queue_limit, false)
+    }
+
+    /// Starts a pool whose threads run at background CPU and IO priority
+    pub fn background(name: &str, workers: usize, queue_limit: usize) -> Self {
+        Self::start(name, workers, queue_limit, true)
+    }
+
+    fn start(name: &str, workers: usize, queue_limit: usize, background: bool) -> Self {
+        let workers = workers.max(1);
+        let (sender, receiver) = sync_channel::<Job>(queue_limit);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let counters = Arc::new(PoolCounters::default());
+
+        for index in 0..workers {
+            let receiver = receiver.clone();
+            let counters = counters.clone();
+            let pool_name = name.to_string();
+            thread::Builder::new()
+                .name(format!("exex-{}-{}", name, index))
+                .spawn(move || {
+                    if background {
+                        enter_background_mode();
+                    }
+                    worker_loop(&pool_name, &receiver, &counters)
+                })
+                .expect("failed to spawn worker thread");
+        }
+
+        info!("Started {} worker pool with {} threads (queue limit {})", name, workers, queue_limit);
+
+        Self {
+            name: name.to_string(),
+            workers,
+            queue_limit,
+            sender,
+            counters,
+        }
+    }
+
+    /// Queues a job without waiting for it
+    pub fn spawn<F>(&self, job: F) -> Result<(), PoolError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.counters.queued.fetch_add(1, Ordering::SeqCst);
+        match self.sender.try_send(Box::new(job)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.counters.queued.fetch_sub(1, Ordering::SeqCst);
+                self.counters.rejected.fetch_add(1, Ordering::SeqCst);
+                Err(PoolError::Saturated(self.name.clone()))
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.counters.queued.fetch_sub(1, Ordering::SeqCst);
+                Err(PoolError::Canceled(self.name.clone()))
+            }
+        }
+    }
+
+    /// Runs a blocking closure on the pool and awaits its result
+    pub async fn run<F, R>(&self, job: F) -> Result<R, PoolError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.spawn(move || {
+            let _ = tx.send(job());
+        })?;
+        rx.await.map_err(|_| PoolError::Canceled(self.name.clone()))
+    }
+
+    /// Current queue and throughput metrics
+    pub fn stats(&self) -> WorkerPoolStats {
+        WorkerPoolStats {
+            name: self.name.clone(),
+            workers: self.workers,
+            queue_limit: self.queue_limit,
+            queued: self.counters.queued.load(Ordering::SeqCst),
+            active: self.counters.active.load(Ordering::SeqCst),
+            completed: self.counters.completed.load(Ordering::SeqCst),
+            rejected: self.counters.rejected.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn worker_loop(name: &str, receiver: &Mutex<Receiver<Job>>, counters: &PoolCounters) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        let Ok(job) = job else {
+            return;
+        };
+
+        counters.queued.fetch_sub(1, Ordering::SeqCst);
+        counters.active.fetch_add(1, Ordering::SeqCst);
+        // A panicking job must not take the worker thread down with it
+        if catch_unwind(AssertUnwindSafe(job)).is_err() {
+            error!("Job panicked on {} worker pool", name);
+        }
+        counters.active.fetch_sub(1, Ordering::SeqCst);
+        counters.completed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Separate pools so a burst of filesystem work cannot starve command execution and vice versa
+pub struct WorkerPools {
+    pub exec: WorkerPool,
+    pub fs: WorkerPool,
+    /// Low-priority threads for maintenance such as scheduled backups
+    pub background: WorkerPool,
+    /// Serializes commands that share a `mutex` key
+    pub mutexes: ExecMutexes,
+}
+
+impl WorkerPools {
+    /// Builds the pools from server configuration
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let queue_limit = config.worker_queue_limit.unwrap_or(256);
+        Self {
+            exec: WorkerPool::new("exec", config.exec_workers.unwrap_or(4), queue_limit),
+            fs: WorkerPool::new("fs", config.fs_workers.unwrap_or(8), queue_limit),
+            background: WorkerPool::background("background", config.background_workers.unwrap_or(1), queue_limit),
+            mutexes: ExecMutexes::new(),
+        }
+    }
+
+    /// Metrics for every pool
+    pub fn stats(&self) -> Vec<WorkerPoolStats> {
+        vec![self.exec.stats(), self.fs.stats(), self.background.stats()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+
+    #[actix_web::test]
+    async fn test_run_returns_result() {
+        let pool = WorkerPool::new("test", 2, 4);
+        assert_eq!(pool.run(|| 40 + 2).await.unwrap(), 42);
+
+        // Panics are reported without killing the worker
+        assert!(matches!(pool.run(|| panic!("boom")).await, Err(PoolError::Canceled(_))));
+        assert_eq!(pool.run(|| "still alive").await.unwrap(), "still alive");
+    }
+
+    #[actix_web::test]
+    async fn test_background_pool_runs_at_low_priority() {
+        let pool = WorkerPool::background("test-background", 1, 4);
+        assert_eq!(pool.run(|| 40 + 2).await.unwrap(), 42);
+
+        // Linux niceness is per thread, so only the worker is lowered
+        #[cfg(target_os = "linux")]
+        {
+            // SAFETY: reads the calling thread's nice value
+            let nice = || unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            let own = nice();
+            assert!(pool.run(nice).await.unwrap() >= 10);
+            assert_eq!(nice(), own);
+        }
+    }
+
+    #[test]
+    fn test_saturated_queue_rejects() {
+        let pool = WorkerPool::new("test", 1, 1);
+        let barrier = Arc::new(Barrier::new(2));
+
+        // Occupy the only worker, then fill the single queue slot
+        let held = barrier.clone();
+        pool.spawn(move || {
+            held.wait();
+        })
+        .unwrap();
+        while pool.stats().active == 0 {
+            thread::yield_now();
+        }
+        pool.spawn(|| {}).unwrap();
+
+        assert!(matches!(pool.spawn(|| {}), Err(PoolError::Saturated(_))));
+        assert_eq!(pool.stats().rejected, 1);
+        assert_eq!(pool.stats().queued, 1);
+
+        barrier.wait();
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Named locks that serialize commands tagged with the same `mutex` key
+///
+/// Locks exist only while someone holds or waits for them, so arbitrary keys
+/// do not accumulate.
+#[derive(Default)]
+pub struct ExecMutexes {
+    locks: Mutex<HashMap<String, Weak<AsyncMutex<()>>>>,
+}
+
+impl ExecMutexes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The lock for `key`, created on first use
+    fn get(&self, key: &str) -> Arc<AsyncMutex<()>> {
+        let mut locks = self.locks.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(lock) = locks.get(key).and_then(Weak::upgrade) {
+            return lock;
+        }
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(AsyncMutex::new(()));
+        locks.insert(key.to_string(), Arc::downgrade(&lock));
+        lock
+    }
+
+    /// Takes the lock for `key` without waiting, if it is free
+    pub fn try_lock(&self, key: &str) -> Option<OwnedMutexGuard<()>> {
+        self.get(key).try_lock_owned().ok()
+    }
+
+    /// Waits for the lock for `key`; holders are served in the order they asked
+    pub async fn lock(&self, key: &str) -> OwnedMutexGuard<()> {
+        self.get(key).lock_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_mutexes_serialize_by_key() {
+        let mutexes = ExecMutexes::new();
+
+        let held = mutexes.lock("cargo-target-dir").await;
+        assert!(mutexes.try_lock("cargo-target-dir").is_none());
+        // Other keys are independent
+        assert!(mutexes.try_lock("npm-cache").is_some());
+
+        drop(held);
+        assert!(mutexes.try_lock("cargo-target-dir").is_some());
+        // Nothing keeps released locks alive
+        assert!(mutexes.locks.lock().unwrap().values().all(|lock| lock.strong_count() == 0));
+    }
+}
@@ -1,6 +1,4 @@
 use std::env;
-use tokio;
-use reqwest;
 use serde_json::json;
 
 const BASE_URL: &str = "http://127.0.0.1:8080";